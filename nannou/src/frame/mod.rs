@@ -33,6 +33,8 @@ pub struct RenderData {
     size: [u32; 2],
     // For writing the intermediary linear sRGBA texture to the swap chain texture.
     texture_reshaper: wgpu::TextureReshaper,
+    // Used in place of the reshaper if the window was built with tonemapping.
+    tonemap: Option<(wgpu::Tonemap, wgpu::BindGroup)>,
 }
 
 /// Data related to the capturing of a frame.
@@ -123,12 +125,21 @@ impl<'swap_chain> Frame<'swap_chain> {
         // Convert the linear sRGBA image to the swapchain image.
         //
         // To do so, we sample the linear sRGBA image and draw it to the swapchain image using
-        // two triangles and a fragment shader.
+        // two triangles and a fragment shader, tonemapping the image if requested.
         {
             let mut encoder = raw_frame.command_encoder();
-            render_data
-                .texture_reshaper
-                .encode_render_pass(raw_frame.swap_chain_texture(), &mut *encoder);
+            match render_data.tonemap {
+                Some((ref tonemap, ref bind_group)) => {
+                    let queue = raw_frame.device_queue_pair().queue();
+                    let dst_texture = raw_frame.swap_chain_texture();
+                    tonemap.encode_render_pass(queue, &mut *encoder, bind_group, dst_texture);
+                }
+                None => {
+                    render_data
+                        .texture_reshaper
+                        .encode_render_pass(raw_frame.swap_chain_texture(), &mut *encoder);
+                }
+            }
         }
 
         // Submit all commands on the device queue.
@@ -284,11 +295,15 @@ impl RenderData {
     ///
     /// If `msaa_samples` is greater than 1 a `multisampled` texture will also be created. Otherwise the
     /// a regular non-multisampled image will be created.
+    ///
+    /// If `tonemap` is `Some`, the image is tonemapped with the given curve and exposure when
+    /// written to the swap chain texture.
     pub(crate) fn new(
         device: &wgpu::Device,
         swap_chain_dims: [u32; 2],
         swap_chain_format: wgpu::TextureFormat,
         msaa_samples: u32,
+        tonemap: Option<(wgpu::Tonemapper, f32)>,
     ) -> Self {
         let intermediary_lin_srgba =
             create_intermediary_lin_srgba(device, swap_chain_dims, msaa_samples);
@@ -302,9 +317,15 @@ impl RenderData {
             swap_chain_sample_count,
             swap_chain_format,
         );
+        let tonemap = tonemap.map(|(tonemapper, exposure)| {
+            let tonemap = wgpu::Tonemap::new(device, swap_chain_format, tonemapper, exposure);
+            let bind_group = tonemap.bind_group(device, &intermediary_lin_srgba.texture_view);
+            (tonemap, bind_group)
+        });
         RenderData {
             intermediary_lin_srgba,
             texture_reshaper,
+            tonemap,
             size: swap_chain_dims,
             msaa_samples,
         }
@@ -50,6 +50,7 @@ pub struct Builder<'app> {
     device_desc: Option<wgpu::DeviceDescriptor<'static>>,
     user_functions: UserFunctions,
     msaa_samples: Option<u32>,
+    tonemap: Option<(wgpu::Tonemapper, f32)>,
     max_capture_frame_jobs: u32,
     capture_frame_timeout: Option<Duration>,
     clear_color: Option<wgpu::Color>,
@@ -246,6 +247,7 @@ pub struct Window {
     pub(crate) surface_conf: wgpu::SurfaceConfiguration,
     pub(crate) device_queue_pair: Arc<wgpu::DeviceQueuePair>,
     msaa_samples: u32,
+    tonemap: Option<(wgpu::Tonemapper, f32)>,
     pub(crate) frame_data: Option<FrameData>,
     pub(crate) frame_count: u64,
     pub(crate) user_functions: UserFunctions,
@@ -373,6 +375,7 @@ impl<'app> Builder<'app> {
             device_desc: None,
             user_functions: Default::default(),
             msaa_samples: None,
+            tonemap: None,
             max_capture_frame_jobs: Default::default(),
             capture_frame_timeout: Default::default(),
             clear_color: None,
@@ -434,15 +437,47 @@ impl<'app> Builder<'app> {
     /// `Window::build` method will `panic!` if the user tries to specify `msaa_samples` as well as
     /// a `raw_view` method.
     ///
-    /// *TODO: Perhaps it would be worth adding two separate methods for specifying msaa samples.
-    /// One for forcing a certain number of samples and returning an error otherwise, and another
-    /// for attempting to use the given number of samples but falling back to a supported value in
-    /// the case that the specified number is not supported.*
+    /// If the requested number of samples is not supported by the adapter for the `Frame`'s
+    /// texture format, the greatest supported number of samples that does not exceed it is used
+    /// instead. The number in use may be retrieved via `Window::msaa_samples`.
     pub fn msaa_samples(mut self, msaa_samples: u32) -> Self {
         self.msaa_samples = Some(msaa_samples);
         self
     }
 
+    /// Tonemap the `Frame`'s high dynamic range texture with the given curve when writing it to
+    /// the surface texture.
+    ///
+    /// By default, colours outside the `0.0..=1.0` range are clipped when written to the surface.
+    /// Tonemapping instead maps them onto the displayable range, retaining detail in bright
+    /// highlights, e.g. those produced by additive blending or bloom.
+    ///
+    /// If only `exposure` is specified, `wgpu::Tonemapper::Aces` is used.
+    ///
+    /// **Note:** Like `msaa_samples`, this parameter has no meaning if the window uses a
+    /// **raw_view** function.
+    pub fn tonemapper(mut self, tonemapper: wgpu::Tonemapper) -> Self {
+        let exposure = self
+            .tonemap
+            .map_or(wgpu::HdrBuilder::DEFAULT_EXPOSURE, |(_, exposure)| exposure);
+        self.tonemap = Some((tonemapper, exposure));
+        self
+    }
+
+    /// The exposure in stops applied to the `Frame`'s texture before tonemapping.
+    ///
+    /// Colours are scaled by `2^exposure`, so `1.0` doubles the brightness and `-1.0` halves it.
+    /// Specifying an exposure enables tonemapping. See `tonemapper` for details.
+    ///
+    /// By default, this value is `wgpu::HdrBuilder::DEFAULT_EXPOSURE`.
+    pub fn exposure(mut self, exposure: f32) -> Self {
+        let tonemapper = self
+            .tonemap
+            .map_or_else(Default::default, |(tonemapper, _)| tonemapper);
+        self.tonemap = Some((tonemapper, exposure));
+        self
+    }
+
     /// Provide a simple function for drawing to the window.
     ///
     /// This is similar to `view` but does not provide access to user data via a Model type. This
@@ -757,6 +792,7 @@ impl<'app> Builder<'app> {
             device_desc,
             user_functions,
             msaa_samples,
+            tonemap,
             max_capture_frame_jobs,
             capture_frame_timeout,
             clear_color,
@@ -948,13 +984,19 @@ impl<'app> Builder<'app> {
         let (frame_data, msaa_samples) = match user_functions.view {
             Some(View::WithModel(_)) | Some(View::Sketch(_)) | None => {
                 let msaa_samples = msaa_samples.unwrap_or(Frame::DEFAULT_MSAA_SAMPLES);
-                // TODO: Verity that requested sample count is valid for surface?
+                let msaa_samples = wgpu::clamp_sample_count(
+                    &adapter,
+                    &device,
+                    Frame::TEXTURE_FORMAT,
+                    msaa_samples,
+                );
                 let surface_dims = [surface_conf.width, surface_conf.height];
                 let render = frame::RenderData::new(
                     &device,
                     surface_dims,
                     surface_conf.format,
                     msaa_samples,
+                    tonemap,
                 );
                 let capture =
                     frame::CaptureData::new(max_capture_frame_jobs, capture_frame_timeout);
@@ -978,6 +1020,7 @@ impl<'app> Builder<'app> {
             surface_conf,
             device_queue_pair,
             msaa_samples,
+            tonemap,
             frame_data,
             frame_count,
             user_functions,
@@ -1009,6 +1052,7 @@ impl<'app> Builder<'app> {
             surface_conf_builder,
             user_functions,
             msaa_samples,
+            tonemap,
             max_capture_frame_jobs,
             capture_frame_timeout,
             clear_color,
@@ -1024,6 +1068,7 @@ impl<'app> Builder<'app> {
             surface_conf_builder,
             user_functions,
             msaa_samples,
+            tonemap,
             max_capture_frame_jobs,
             capture_frame_timeout,
             clear_color,
@@ -1517,6 +1562,7 @@ impl Window {
                 self.tracked_state.physical_size.into(),
                 self.surface_conf.format,
                 self.msaa_samples,
+                self.tonemap,
             );
             self.frame_data.as_mut().unwrap().render = render_data;
         }
@@ -0,0 +1,706 @@
+//! Onset detection and tempo estimation for locking visuals to the beat of music.
+//!
+//! A [**BeatTracker**](./struct.BeatTracker.html) is fed each buffer captured by an input stream.
+//! The signal is divided into short hops, and the rise in log energy of each hop is used as an
+//! onset detection function. Onsets are picked from peaks that rise above an adaptive threshold.
+//!
+//! At a configurable rate, the tempo is estimated from the autocorrelation of the onset detection
+//! function over the most recent window, weighted towards common tempos to reduce octave errors.
+//! Once the tempo is known with confidence, the tracker emits beats at the estimated period,
+//! aligning its phase to the onsets that fall near each expected beat. Beats are emitted through
+//! breaks in the music until the tempo estimate loses confidence.
+//!
+//! Beats and tempo estimates are sent to a [**BeatReceiver**](./struct.BeatReceiver.html) that
+//! may be polled from the main thread.
+//!
+//! All buffers are allocated when the `BeatTracker` is built, so `process` may be called from
+//! within an input stream's capture function.
+
+use super::pitch::{mono, parabolic_peak};
+use crate::Buffer;
+use dasp_sample::Sample;
+use std::sync::mpsc;
+
+/// A beat detected or predicted by a `BeatTracker`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Beat {
+    /// The index of the frame at which the beat occurred, counted from the first frame processed.
+    pub frame: u64,
+    /// The strength of the onset at the beat, or `0.0` if the beat was predicted.
+    pub strength: f32,
+    /// Whether the beat was predicted from the tempo rather than coinciding with an onset.
+    pub predicted: bool,
+}
+
+/// An estimate of the tempo of the most recent window of audio.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TempoEstimate {
+    /// The estimated tempo in beats per minute.
+    pub bpm: f32,
+    /// The confidence in the estimate in the range `0.0..=1.0`.
+    pub confidence: f32,
+    /// The index of the frame following the end of the analysed window, counted from the first
+    /// frame processed.
+    pub frame: u64,
+}
+
+/// An event produced by a `BeatTracker`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BeatEvent {
+    Beat(Beat),
+    Tempo(TempoEstimate),
+}
+
+/// Detects the beats and estimates the tempo of the buffers captured by an input stream.
+///
+/// The `BeatTracker` should be stored within the input stream's model and fed each captured
+/// buffer via `process`.
+pub struct BeatTracker {
+    hop_len: usize,
+    rate: f32,
+    min_bpm: f32,
+    max_bpm: f32,
+    threshold: f32,
+    min_confidence: f32,
+    silence_rms: f32,
+    channel: Option<usize>,
+    // The previous sample, for emphasising transients.
+    prev_sample: f32,
+    // The sums of the emphasised energy and the raw energy over the current hop.
+    hop_energy: f32,
+    hop_rms: f32,
+    // The number of frames within the current hop.
+    hop_frames: usize,
+    // The log energy of the previous hop.
+    prev_energy: f32,
+    // A ring buffer of the most recent values of the onset detection function, one per hop.
+    odf: Vec<f32>,
+    // The index within `odf` at which the next value will be written.
+    write_ix: usize,
+    // The onset detection function ordered from oldest to newest, ready for analysis.
+    window: Vec<f32>,
+    // Per-lag autocorrelation of the window.
+    scratch: Vec<f32>,
+    // The total number of hops processed.
+    hops: u64,
+    // The number of hops processed since the last tempo estimate.
+    hops_since_estimate: usize,
+    // The most recent tempo estimate.
+    tempo: Option<TempoEstimate>,
+    // The frame of the most recent beat and whether or not it was predicted.
+    last_beat: Option<(u64, bool)>,
+    // The total number of frames processed.
+    frame: u64,
+    tx: mpsc::SyncSender<BeatEvent>,
+}
+
+/// Receives the events produced by a `BeatTracker`, typically on the main thread.
+pub struct BeatReceiver {
+    rx: mpsc::Receiver<BeatEvent>,
+    tempo: Option<TempoEstimate>,
+    last_beat: Option<Beat>,
+}
+
+/// A builder for a `BeatTracker` and its `BeatReceiver`.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    hop_len: usize,
+    window_len: usize,
+    rate: f32,
+    min_bpm: f32,
+    max_bpm: f32,
+    threshold: f32,
+    min_confidence: f32,
+    silence_rms: f32,
+    channel: Option<usize>,
+    capacity: usize,
+}
+
+// The number of hops over which the adaptive onset threshold is averaged.
+const THRESHOLD_HOPS: usize = 16;
+// The compression applied to hop energy before taking its log.
+const LOG_COMPRESSION: f32 = 1_000.0;
+// The tempo towards which estimates are weighted, along with the width of the weighting in
+// octaves.
+const PREFERRED_BPM: f32 = 120.0;
+const PREFERRED_BPM_OCTAVES: f32 = 1.4;
+// The fraction of a period after a predicted beat within which an onset realigns the phase.
+const PHASE_TOLERANCE: f64 = 0.25;
+// The fraction of a period that must pass after a beat before an onset is considered a beat.
+const MIN_BEAT_INTERVAL: f64 = 0.75;
+
+impl TempoEstimate {
+    /// The interval between beats in seconds.
+    pub fn beat_secs(&self) -> f32 {
+        60.0 / self.bpm
+    }
+}
+
+impl BeatTracker {
+    /// Build a `BeatTracker` and its receiver with the default parameters.
+    pub fn new() -> (Self, BeatReceiver) {
+        Self::builder().build()
+    }
+
+    /// Begin building a `BeatTracker`.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// The number of frames within each hop of the onset detection function.
+    pub fn hop_len(&self) -> usize {
+        self.hop_len
+    }
+
+    /// The length of the tempo estimation window in hops.
+    pub fn window_len(&self) -> usize {
+        self.odf.len()
+    }
+
+    /// The most recent tempo estimate.
+    pub fn tempo(&self) -> Option<TempoEstimate> {
+        self.tempo
+    }
+
+    /// Feed a buffer captured by an input stream to the tracker.
+    ///
+    /// Events are dropped if the receiver is full or has been dropped.
+    pub fn process<S>(&mut self, buffer: &Buffer<S>)
+    where
+        S: Sample,
+    {
+        let sample_rate = buffer.sample_rate();
+        for frame in buffer.frames() {
+            let sample = mono(frame, self.channel);
+            let emphasised = sample - self.prev_sample;
+            self.prev_sample = sample;
+            self.hop_energy += emphasised * emphasised;
+            self.hop_rms += sample * sample;
+            self.hop_frames += 1;
+            self.frame += 1;
+            if self.hop_frames == self.hop_len {
+                self.hop(sample_rate);
+            }
+        }
+    }
+
+    /// Clear all history, e.g. after the input stream has been paused or the track has changed.
+    pub fn reset(&mut self) {
+        self.odf.iter_mut().for_each(|s| *s = 0.0);
+        self.prev_sample = 0.0;
+        self.hop_energy = 0.0;
+        self.hop_rms = 0.0;
+        self.hop_frames = 0;
+        self.prev_energy = 0.0;
+        self.write_ix = 0;
+        self.hops = 0;
+        self.hops_since_estimate = 0;
+        self.tempo = None;
+        self.last_beat = None;
+        self.frame = 0;
+    }
+
+    // Complete the current hop, updating the onset detection function, beats and tempo.
+    fn hop(&mut self, sample_rate: u32) {
+        let n = self.hop_frames as f32;
+        let rms = (self.hop_rms / n).sqrt();
+        let energy = (1.0 + LOG_COMPRESSION * self.hop_energy / n).ln();
+        let flux = match rms < self.silence_rms {
+            true => 0.0,
+            false => (energy - self.prev_energy).max(0.0),
+        };
+        self.prev_energy = energy;
+        self.hop_energy = 0.0;
+        self.hop_rms = 0.0;
+        self.hop_frames = 0;
+
+        let len = self.odf.len();
+        self.odf[self.write_ix] = flux;
+        self.write_ix = (self.write_ix + 1) % len;
+        self.hops += 1;
+
+        // The previous hop is an onset if it peaks above the mean of the hops preceding it.
+        if self.hops >= 3 {
+            let odf = |back: usize| self.odf[(self.write_ix + len - back) % len];
+            let (prev2, prev, current) = (odf(3), odf(2), odf(1));
+            let count = std::cmp::min(THRESHOLD_HOPS, len - 3).min(self.hops as usize - 2);
+            let mean = (3..3 + count).map(odf).sum::<f32>() / count as f32;
+            if prev > prev2 && prev >= current && prev > mean * self.threshold {
+                let onset_frame = self.frame.saturating_sub(self.hop_len as u64 * 2);
+                self.onset(onset_frame, prev, sample_rate);
+            }
+        }
+
+        self.predict_beat(sample_rate);
+
+        // Estimate the tempo at the configured rate once the window has filled.
+        let hops_per_estimate = sample_rate as f32 / (self.hop_len as f32 * self.rate);
+        let hops_per_estimate = (hops_per_estimate.round() as usize).max(1);
+        self.hops_since_estimate += 1;
+        if self.hops_since_estimate >= hops_per_estimate && self.hops >= len as u64 {
+            self.hops_since_estimate = 0;
+            if let Some(tempo) = self.estimate_tempo(sample_rate) {
+                self.tempo = Some(tempo);
+                let _ = self.tx.try_send(BeatEvent::Tempo(tempo));
+            }
+        }
+    }
+
+    // The period between beats in frames, if the tempo is known with enough confidence.
+    fn period(&self, sample_rate: u32) -> Option<f64> {
+        self.tempo
+            .filter(|tempo| tempo.confidence >= self.min_confidence)
+            .map(|tempo| 60.0 * sample_rate as f64 / tempo.bpm as f64)
+    }
+
+    // Handle an onset at the given frame, emitting a beat or realigning the beat phase.
+    fn onset(&mut self, frame: u64, strength: f32, sample_rate: u32) {
+        let period = self.period(sample_rate);
+        let min_interval = match period {
+            Some(period) => period * MIN_BEAT_INTERVAL,
+            None => 60.0 * sample_rate as f64 / self.max_bpm as f64,
+        };
+        if let (Some((last, true)), Some(period)) = (self.last_beat, period) {
+            let since = frame.saturating_sub(last) as f64;
+            if since < period * PHASE_TOLERANCE {
+                self.last_beat = Some((frame, false));
+                return;
+            }
+        }
+        let is_beat = match self.last_beat {
+            None => true,
+            Some((last, _)) => frame.saturating_sub(last) as f64 >= min_interval,
+        };
+        if is_beat {
+            self.emit_beat(frame, strength, false);
+        }
+    }
+
+    // Emit a predicted beat if one is due and the tempo is known.
+    //
+    // If more than one period has passed since the last beat, e.g. when the tempo first becomes
+    // confident after a break, the missed beats are skipped and only the most recent is emitted.
+    // The following beat is then due at `last + ceil(elapsed / period) * period`.
+    fn predict_beat(&mut self, sample_rate: u32) {
+        if let (Some((last, _)), Some(period)) = (self.last_beat, self.period(sample_rate)) {
+            let elapsed = self.frame.saturating_sub(last) as f64;
+            let periods = (elapsed / period).floor();
+            if periods >= 1.0 {
+                let beat = last + (periods * period).round() as u64;
+                self.emit_beat(beat, 0.0, true);
+            }
+        }
+    }
+
+    fn emit_beat(&mut self, frame: u64, strength: f32, predicted: bool) {
+        self.last_beat = Some((frame, predicted));
+        let beat = Beat {
+            frame,
+            strength,
+            predicted,
+        };
+        let _ = self.tx.try_send(BeatEvent::Beat(beat));
+    }
+
+    // Estimate the tempo from the autocorrelation of the onset detection function.
+    fn estimate_tempo(&mut self, sample_rate: u32) -> Option<TempoEstimate> {
+        let len = self.odf.len();
+        let (newest, oldest) = self.odf.split_at(self.write_ix);
+        self.window[..oldest.len()].copy_from_slice(oldest);
+        self.window[oldest.len()..].copy_from_slice(newest);
+        let mean = self.window.iter().sum::<f32>() / len as f32;
+        self.window.iter_mut().for_each(|s| *s -= mean);
+
+        let hops_per_minute = 60.0 * sample_rate as f32 / self.hop_len as f32;
+        let lag_min = ((hops_per_minute / self.max_bpm).floor() as usize).max(1);
+        let lag_max = ((hops_per_minute / self.min_bpm).ceil() as usize).min(len / 2 - 1);
+        if lag_min >= lag_max {
+            return None;
+        }
+
+        let x = &self.window;
+        for lag in 0..=lag_max + 1 {
+            self.scratch[lag] = (0..len - lag).map(|j| x[j] * x[j + lag]).sum();
+        }
+        let energy = self.scratch[0];
+        if energy <= 0.0 {
+            return None;
+        }
+
+        // Choose the lag with the highest weighted autocorrelation.
+        let weight = |lag: usize| {
+            let octaves = (hops_per_minute / lag as f32 / PREFERRED_BPM).log2();
+            (-0.5 * (octaves / PREFERRED_BPM_OCTAVES).powi(2)).exp()
+        };
+        let best = (lag_min..=lag_max).max_by(|&a, &b| {
+            let a = self.scratch[a] * weight(a);
+            let b = self.scratch[b] * weight(b);
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        if self.scratch[best] <= 0.0 {
+            return None;
+        }
+        let lag = parabolic_peak(&self.scratch, best);
+        Some(TempoEstimate {
+            bpm: hops_per_minute / lag,
+            confidence: (self.scratch[best] / energy).max(0.0).min(1.0),
+            frame: self.frame,
+        })
+    }
+}
+
+impl BeatReceiver {
+    /// Receive all pending events, returning the number of beats received.
+    ///
+    /// This is typically called once per frame, triggering a visual response when the result is
+    /// non-zero.
+    pub fn update(&mut self) -> usize {
+        let mut beats = 0;
+        for event in self.rx.try_iter() {
+            match event {
+                BeatEvent::Beat(beat) => {
+                    self.last_beat = Some(beat);
+                    beats += 1;
+                }
+                BeatEvent::Tempo(tempo) => self.tempo = Some(tempo),
+            }
+        }
+        beats
+    }
+
+    /// The most recent tempo estimate received by `update` or `try_iter`.
+    pub fn tempo(&self) -> Option<TempoEstimate> {
+        self.tempo
+    }
+
+    /// The most recent beat received by `update` or `try_iter`.
+    pub fn last_beat(&self) -> Option<Beat> {
+        self.last_beat
+    }
+
+    /// An iterator yielding all pending events in the order in which they were produced.
+    pub fn try_iter(&mut self) -> impl Iterator<Item = BeatEvent> + '_ {
+        let tempo = &mut self.tempo;
+        let last_beat = &mut self.last_beat;
+        self.rx.try_iter().inspect(move |event| match *event {
+            BeatEvent::Beat(beat) => *last_beat = Some(beat),
+            BeatEvent::Tempo(estimate) => *tempo = Some(estimate),
+        })
+    }
+}
+
+impl Builder {
+    /// The default number of frames within each hop of the onset detection function.
+    pub const DEFAULT_HOP_LEN: usize = 512;
+
+    /// The default length of the tempo estimation window in hops, ~5.5 seconds at 48khz.
+    pub const DEFAULT_WINDOW_LEN: usize = 512;
+
+    /// The default number of tempo estimates per second.
+    pub const DEFAULT_RATE: f32 = 2.0;
+
+    /// The default lowest detectable tempo in beats per minute.
+    pub const DEFAULT_MIN_BPM: f32 = 60.0;
+
+    /// The default highest detectable tempo in beats per minute.
+    pub const DEFAULT_MAX_BPM: f32 = 200.0;
+
+    /// The default factor by which an onset must exceed the recent mean.
+    pub const DEFAULT_THRESHOLD: f32 = 1.5;
+
+    /// The default confidence required before beats are predicted from the tempo.
+    pub const DEFAULT_MIN_CONFIDENCE: f32 = 0.2;
+
+    /// The default RMS amplitude below which hops are considered silent.
+    pub const DEFAULT_SILENCE_RMS: f32 = 0.01;
+
+    /// The default number of events that may be pending within the receiver.
+    pub const DEFAULT_CAPACITY: usize = 64;
+
+    /// The number of frames within each hop of the onset detection function.
+    ///
+    /// Shorter hops locate onsets more precisely at the cost of CPU.
+    ///
+    /// By default, this value is `Builder::DEFAULT_HOP_LEN`.
+    pub fn hop_len(mut self, frames: usize) -> Self {
+        self.hop_len = frames;
+        self
+    }
+
+    /// The length of the tempo estimation window in hops.
+    ///
+    /// Longer windows produce more stable estimates but respond more slowly to tempo changes.
+    ///
+    /// By default, this value is `Builder::DEFAULT_WINDOW_LEN`.
+    pub fn window_len(mut self, hops: usize) -> Self {
+        self.window_len = hops;
+        self
+    }
+
+    /// The number of tempo estimates to produce per second of processed audio.
+    ///
+    /// By default, this value is `Builder::DEFAULT_RATE`.
+    pub fn rate(mut self, hz: f32) -> Self {
+        self.rate = hz;
+        self
+    }
+
+    /// The range of tempos within which to search, in beats per minute.
+    ///
+    /// By default, this range is `Builder::DEFAULT_MIN_BPM` to `Builder::DEFAULT_MAX_BPM`.
+    pub fn range_bpm(mut self, min_bpm: f32, max_bpm: f32) -> Self {
+        self.min_bpm = min_bpm;
+        self.max_bpm = max_bpm;
+        self
+    }
+
+    /// The factor by which an onset must exceed the mean of the preceding hops.
+    ///
+    /// Lower values detect softer onsets at the cost of more false positives.
+    ///
+    /// By default, this value is `Builder::DEFAULT_THRESHOLD`.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// The tempo confidence required before beats are predicted from the tempo.
+    ///
+    /// Below this confidence, every sufficiently spaced onset is treated as a beat.
+    ///
+    /// By default, this value is `Builder::DEFAULT_MIN_CONFIDENCE`.
+    pub fn min_confidence(mut self, confidence: f32) -> Self {
+        self.min_confidence = confidence;
+        self
+    }
+
+    /// The RMS amplitude below which a hop is considered silent and yields no onset.
+    ///
+    /// By default, this value is `Builder::DEFAULT_SILENCE_RMS`.
+    pub fn silence_rms(mut self, rms: f32) -> Self {
+        self.silence_rms = rms;
+        self
+    }
+
+    /// Analyse only the channel at the given index rather than the mean of all channels.
+    ///
+    /// Frames that have no channel at the given index are treated as silent.
+    ///
+    /// By default, all channels are mixed down to mono.
+    pub fn channel(mut self, index: usize) -> Self {
+        self.channel = Some(index);
+        self
+    }
+
+    /// The number of events that may be pending within the receiver before new events are
+    /// dropped.
+    ///
+    /// By default, this value is `Builder::DEFAULT_CAPACITY`.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Build the `BeatTracker` and the receiver for its events.
+    ///
+    /// **Panic!**s if the hop length is zero, if the window length is less than `8`, if the rate
+    /// is not positive or if the minimum tempo is not positive and less than the maximum.
+    pub fn build(self) -> (BeatTracker, BeatReceiver) {
+        assert!(self.hop_len > 0, "the hop length must be positive");
+        assert!(self.window_len >= 8, "the window length must be at least 8");
+        assert!(self.rate > 0.0, "the rate must be positive");
+        assert!(
+            self.min_bpm > 0.0 && self.min_bpm < self.max_bpm,
+            "the minimum tempo must be positive and less than the maximum"
+        );
+        let (tx, rx) = mpsc::sync_channel(self.capacity);
+        let tracker = BeatTracker {
+            hop_len: self.hop_len,
+            rate: self.rate,
+            min_bpm: self.min_bpm,
+            max_bpm: self.max_bpm,
+            threshold: self.threshold,
+            min_confidence: self.min_confidence,
+            silence_rms: self.silence_rms,
+            channel: self.channel,
+            prev_sample: 0.0,
+            hop_energy: 0.0,
+            hop_rms: 0.0,
+            hop_frames: 0,
+            prev_energy: 0.0,
+            odf: vec![0.0; self.window_len],
+            write_ix: 0,
+            window: vec![0.0; self.window_len],
+            scratch: vec![0.0; self.window_len / 2 + 1],
+            hops: 0,
+            hops_since_estimate: 0,
+            tempo: None,
+            last_beat: None,
+            frame: 0,
+            tx,
+        };
+        let receiver = BeatReceiver {
+            rx,
+            tempo: None,
+            last_beat: None,
+        };
+        (tracker, receiver)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            hop_len: Self::DEFAULT_HOP_LEN,
+            window_len: Self::DEFAULT_WINDOW_LEN,
+            rate: Self::DEFAULT_RATE,
+            min_bpm: Self::DEFAULT_MIN_BPM,
+            max_bpm: Self::DEFAULT_MAX_BPM,
+            threshold: Self::DEFAULT_THRESHOLD,
+            min_confidence: Self::DEFAULT_MIN_CONFIDENCE,
+            silence_rms: Self::DEFAULT_SILENCE_RMS,
+            channel: None,
+            capacity: Self::DEFAULT_CAPACITY,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    // A mono click track with a unit impulse on each beat at the given tempo.
+    fn click_track(bpm: f64, secs: f64) -> Vec<f32> {
+        let period = 60.0 * SAMPLE_RATE as f64 / bpm;
+        let len = (secs * SAMPLE_RATE as f64) as usize;
+        let mut samples = vec![0.0; len];
+        let mut beat = 0.0;
+        while (beat as usize) < len {
+            samples[beat as usize] = 1.0;
+            beat += period;
+        }
+        samples
+    }
+
+    // Feed the given samples to the tracker in buffers of the given length.
+    fn process(tracker: &mut BeatTracker, samples: &[f32], frames_per_buffer: usize) {
+        for chunk in samples.chunks(frames_per_buffer) {
+            let buffer = Buffer {
+                interleaved_samples: chunk.to_vec().into_boxed_slice(),
+                channels: 1,
+                sample_rate: SAMPLE_RATE,
+            };
+            tracker.process(&buffer);
+        }
+    }
+
+    fn beats(receiver: &mut BeatReceiver) -> Vec<Beat> {
+        receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                BeatEvent::Beat(beat) => Some(beat),
+                BeatEvent::Tempo(_) => None,
+            })
+            .collect()
+    }
+
+    // A tracker whose tempo is known with full confidence.
+    fn confident_tracker(bpm: f32) -> (BeatTracker, BeatReceiver) {
+        let (mut tracker, receiver) = BeatTracker::builder().capacity(1_024).build();
+        tracker.tempo = Some(TempoEstimate {
+            bpm,
+            confidence: 1.0,
+            frame: 0,
+        });
+        (tracker, receiver)
+    }
+
+    #[test]
+    fn click_track_tempo() {
+        let (mut tracker, mut receiver) = BeatTracker::builder().capacity(1_024).build();
+        process(&mut tracker, &click_track(120.0, 8.0), 256);
+        receiver.update();
+        let tempo = receiver.tempo().expect("no tempo estimate");
+        assert!((tempo.bpm - 120.0).abs() < 2.0, "bpm: {}", tempo.bpm);
+        assert!(tempo.confidence >= Builder::DEFAULT_MIN_CONFIDENCE);
+    }
+
+    #[test]
+    fn click_track_beats_are_in_phase() {
+        let (mut tracker, mut receiver) = BeatTracker::builder().capacity(1_024).build();
+        let period = 60.0 * SAMPLE_RATE as f64 / 120.0;
+        process(&mut tracker, &click_track(120.0, 10.0), 256);
+        let beats = beats(&mut receiver);
+        assert!(beats.len() >= 18, "beats: {}", beats.len());
+        assert!(beats.iter().any(|beat| beat.predicted));
+        // Onsets are located to the hop within which they occur.
+        let tolerance = 2.0 * Builder::DEFAULT_HOP_LEN as f64;
+        for beat in &beats {
+            let phase = beat.frame as f64 % period;
+            let offset = phase.min(period - phase);
+            assert!(
+                offset <= tolerance,
+                "beat {:?} is {} frames off",
+                beat,
+                offset
+            );
+        }
+        for pair in beats.windows(2) {
+            assert!(pair[1].frame > pair[0].frame);
+        }
+    }
+
+    #[test]
+    fn predicted_beats_continue_through_a_break() {
+        let (mut tracker, mut receiver) = BeatTracker::builder().capacity(1_024).build();
+        let mut samples = click_track(120.0, 8.0);
+        let clicks = samples.len() as u64;
+        samples.extend(std::iter::repeat(0.0).take(SAMPLE_RATE as usize * 2));
+        process(&mut tracker, &samples, 256);
+        let during_break: Vec<_> = beats(&mut receiver)
+            .into_iter()
+            .filter(|beat| beat.frame >= clicks)
+            .collect();
+        assert!(during_break.len() >= 3, "beats: {:?}", during_break);
+        assert!(during_break.iter().all(|beat| beat.predicted));
+    }
+
+    #[test]
+    fn predict_beat_skips_missed_beats() {
+        let (mut tracker, mut receiver) = confident_tracker(120.0);
+        let period = 24_000;
+        tracker.last_beat = Some((1_000, false));
+        tracker.frame = 1_000 + period * 5 + 10;
+        tracker.predict_beat(SAMPLE_RATE);
+        let beats = beats(&mut receiver);
+        assert_eq!(beats.len(), 1);
+        assert_eq!(beats[0].frame, 1_000 + period * 5);
+        assert!(beats[0].predicted);
+
+        // The next beat is not due until a full period after the emitted beat.
+        tracker.frame += period - 20;
+        tracker.predict_beat(SAMPLE_RATE);
+        assert!(receiver.try_iter().next().is_none());
+        tracker.frame += 10;
+        tracker.predict_beat(SAMPLE_RATE);
+        let beats: Vec<_> = receiver.try_iter().collect();
+        let expected = Beat {
+            frame: 1_000 + period * 6,
+            strength: 0.0,
+            predicted: true,
+        };
+        assert_eq!(beats, [BeatEvent::Beat(expected)]);
+    }
+
+    #[test]
+    fn predict_beat_requires_confidence() {
+        let (mut tracker, mut receiver) = confident_tracker(120.0);
+        tracker.tempo.as_mut().unwrap().confidence = 0.0;
+        tracker.last_beat = Some((0, false));
+        tracker.frame = 48_000;
+        tracker.predict_beat(SAMPLE_RATE);
+        assert!(receiver.try_iter().next().is_none());
+    }
+}
@@ -0,0 +1,22 @@
+//! Adapters for analysing the audio of a stream and delivering the results to the main thread.
+//!
+//! Each analyser is split into two halves. The analyser itself lives within the model of an input
+//! stream and is fed each captured **Buffer** on the audio thread. Results are sent to a receiver
+//! that may be polled from the main thread, e.g. once per frame within an app's `update`
+//! function.
+//!
+//! - [**BeatTracker**](./beat/struct.BeatTracker.html) and
+//!   [**BeatReceiver**](./beat/struct.BeatReceiver.html) for detecting the beats and estimating
+//!   the tempo of music (requires the `analysis` feature).
+//! - [**Pitch**](./pitch/struct.Pitch.html) and
+//!   [**PitchReceiver**](./pitch/struct.PitchReceiver.html) for estimating the fundamental
+//!   frequency of a monophonic signal such as a voice or instrument (requires the `pitch-yin` or
+//!   `pitch-mpm` feature).
+
+#[cfg(feature = "analysis")]
+pub use self::beat::{Beat, BeatEvent, BeatReceiver, BeatTracker, TempoEstimate};
+pub use self::pitch::{Pitch, PitchEstimate, PitchReceiver};
+
+#[cfg(feature = "analysis")]
+pub mod beat;
+pub mod pitch;
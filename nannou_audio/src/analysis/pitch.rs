@@ -0,0 +1,691 @@
+//! Fundamental frequency estimation for monophonic signals such as a voice or instrument.
+//!
+//! A [**Pitch**](./struct.Pitch.html) analyser is fed each buffer captured by an input stream. It
+//! keeps a sliding window of the most recent frames and, at a configurable rate, estimates the
+//! fundamental frequency of the window along with a confidence in the estimate. Estimates are sent
+//! to a [**PitchReceiver**](./struct.PitchReceiver.html) that may be polled from the main thread.
+//!
+//! Two estimation algorithms are provided, each behind its own feature:
+//!
+//! - **YIN** (de Cheveigné & Kawahara, 2002, `pitch-yin`) - robust against octave errors and the
+//!   default.
+//! - **MPM** (McLeod & Wyvill, 2005, `pitch-mpm`) - the McLeod Pitch Method, which tends to track
+//!   rapid changes in pitch more closely at the cost of occasional octave errors.
+//!
+//! All buffers are allocated when the `Pitch` is built, so `process` may be called from within an
+//! input stream's capture function.
+
+use crate::Buffer;
+use dasp_sample::Sample;
+use std::sync::mpsc;
+
+/// The algorithm used to estimate the fundamental frequency of each window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Algorithm {
+    /// The YIN algorithm.
+    ///
+    /// The `threshold` is the maximum value of the cumulative mean normalised difference at which
+    /// a period is accepted. Lower values reject more noisy or unpitched windows. Windows in which
+    /// no period falls below the threshold yield an estimate without a frequency.
+    #[cfg(feature = "pitch-yin")]
+    Yin { threshold: f32 },
+    /// The McLeod Pitch Method.
+    ///
+    /// The `cutoff` is the fraction of the highest normalised square difference peak at which an
+    /// earlier peak is chosen instead, guarding against choosing a multiple of the true period.
+    #[cfg(feature = "pitch-mpm")]
+    Mpm { cutoff: f32 },
+}
+
+/// An estimate of the fundamental frequency of a window of audio.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PitchEstimate {
+    /// The estimated fundamental frequency in hz.
+    ///
+    /// `None` if the window was silent or no clear period could be found.
+    pub hz: Option<f32>,
+    /// The confidence in the estimate in the range `0.0..=1.0`.
+    pub confidence: f32,
+    /// The index of the frame following the end of the analysed window, counted from the first
+    /// frame processed.
+    pub frame: u64,
+}
+
+/// Estimates the fundamental frequency of the buffers captured by an input stream.
+///
+/// The `Pitch` should be stored within the input stream's model and fed each captured buffer via
+/// `process`.
+pub struct Pitch {
+    algorithm: Algorithm,
+    channel: Option<usize>,
+    rate: f32,
+    min_hz: f32,
+    max_hz: f32,
+    silence_rms: f32,
+    // A ring buffer of the most recent mono samples.
+    history: Vec<f32>,
+    // The index within `history` at which the next sample will be written.
+    write_ix: usize,
+    // The history ordered from oldest to newest, ready for analysis.
+    window: Vec<f32>,
+    // Per-lag results for the algorithm.
+    scratch: Vec<f32>,
+    // The number of frames processed since the last estimate.
+    frames_since_estimate: usize,
+    // The total number of frames processed.
+    frame: u64,
+    tx: mpsc::SyncSender<PitchEstimate>,
+}
+
+/// Receives the estimates produced by a `Pitch` analyser, typically on the main thread.
+pub struct PitchReceiver {
+    rx: mpsc::Receiver<PitchEstimate>,
+    latest: Option<PitchEstimate>,
+}
+
+/// A builder for a `Pitch` analyser and its `PitchReceiver`.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    algorithm: Algorithm,
+    window_len: usize,
+    rate: f32,
+    min_hz: f32,
+    max_hz: f32,
+    silence_rms: f32,
+    channel: Option<usize>,
+    capacity: usize,
+}
+
+impl Algorithm {
+    /// The default threshold for the YIN algorithm.
+    #[cfg(feature = "pitch-yin")]
+    pub const DEFAULT_YIN_THRESHOLD: f32 = 0.15;
+
+    /// The default cutoff for the McLeod Pitch Method.
+    #[cfg(feature = "pitch-mpm")]
+    pub const DEFAULT_MPM_CUTOFF: f32 = 0.93;
+
+    /// The YIN algorithm with the default threshold.
+    #[cfg(feature = "pitch-yin")]
+    pub fn yin() -> Self {
+        Algorithm::Yin {
+            threshold: Self::DEFAULT_YIN_THRESHOLD,
+        }
+    }
+
+    /// The McLeod Pitch Method with the default cutoff.
+    #[cfg(feature = "pitch-mpm")]
+    pub fn mpm() -> Self {
+        Algorithm::Mpm {
+            cutoff: Self::DEFAULT_MPM_CUTOFF,
+        }
+    }
+}
+
+impl Default for Algorithm {
+    #[cfg(feature = "pitch-yin")]
+    fn default() -> Self {
+        Algorithm::yin()
+    }
+
+    #[cfg(not(feature = "pitch-yin"))]
+    fn default() -> Self {
+        Algorithm::mpm()
+    }
+}
+
+impl PitchEstimate {
+    /// The estimated frequency as a fractional MIDI note number, where `69.0` is A4 at 440hz.
+    pub fn midi_note(&self) -> Option<f32> {
+        self.hz.map(|hz| 69.0 + 12.0 * (hz / 440.0).log2())
+    }
+}
+
+impl Pitch {
+    /// Build a `Pitch` analyser and its receiver with the default parameters.
+    pub fn new() -> (Self, PitchReceiver) {
+        Self::builder().build()
+    }
+
+    /// Begin building a `Pitch` analyser.
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    /// The algorithm used to estimate the frequency of each window.
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// The length of the analysis window in frames.
+    pub fn window_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The number of estimates produced per second of processed audio.
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// Feed a buffer captured by an input stream to the analyser.
+    ///
+    /// Produces an estimate each time the configured number of frames has been processed since
+    /// the last, once the analysis window has filled. Estimates are dropped if the receiver is full
+    /// or has been dropped.
+    pub fn process<S>(&mut self, buffer: &Buffer<S>)
+    where
+        S: Sample,
+    {
+        let sample_rate = buffer.sample_rate();
+        let hop = ((sample_rate as f32 / self.rate).round() as usize).max(1);
+        let window_len = self.history.len() as u64;
+        for frame in buffer.frames() {
+            self.history[self.write_ix] = mono(frame, self.channel);
+            self.write_ix = (self.write_ix + 1) % self.history.len();
+            self.frame += 1;
+            self.frames_since_estimate += 1;
+            if self.frames_since_estimate >= hop && self.frame >= window_len {
+                self.frames_since_estimate = 0;
+                let estimate = self.estimate(sample_rate);
+                let _ = self.tx.try_send(estimate);
+            }
+        }
+    }
+
+    /// Clear the analysis window, e.g. after the input stream has been paused.
+    pub fn reset(&mut self) {
+        self.history.iter_mut().for_each(|s| *s = 0.0);
+        self.write_ix = 0;
+        self.frames_since_estimate = 0;
+        self.frame = 0;
+    }
+
+    // Estimate the frequency of the current window.
+    fn estimate(&mut self, sample_rate: u32) -> PitchEstimate {
+        let len = self.history.len();
+        let (newest, oldest) = self.history.split_at(self.write_ix);
+        self.window[..oldest.len()].copy_from_slice(oldest);
+        self.window[oldest.len()..].copy_from_slice(newest);
+
+        let unpitched = PitchEstimate {
+            hz: None,
+            confidence: 0.0,
+            frame: self.frame,
+        };
+
+        let rms = (self.window.iter().map(|s| s * s).sum::<f32>() / len as f32).sqrt();
+        if rms < self.silence_rms {
+            return unpitched;
+        }
+
+        let sample_rate = sample_rate as f32;
+        let tau_min = ((sample_rate / self.max_hz).floor() as usize).max(2);
+        let tau_max = ((sample_rate / self.min_hz).ceil() as usize).min(len / 2);
+        if tau_min >= tau_max {
+            return unpitched;
+        }
+
+        let (period, confidence) = match self.algorithm {
+            #[cfg(feature = "pitch-yin")]
+            Algorithm::Yin { threshold } => {
+                yin(&self.window, &mut self.scratch, tau_min, tau_max, threshold)
+            }
+            #[cfg(feature = "pitch-mpm")]
+            Algorithm::Mpm { cutoff } => {
+                mpm(&self.window, &mut self.scratch, tau_min, tau_max, cutoff)
+            }
+        };
+        PitchEstimate {
+            hz: period.map(|period| sample_rate / period),
+            confidence: confidence.max(0.0).min(1.0),
+            frame: self.frame,
+        }
+    }
+}
+
+impl PitchReceiver {
+    /// Receive all pending estimates, returning the most recent estimate received so far.
+    ///
+    /// The returned estimate may have been received during an earlier call if no estimates were
+    /// pending. Returns `None` if no estimate has been received yet.
+    pub fn update(&mut self) -> Option<PitchEstimate> {
+        for estimate in self.rx.try_iter() {
+            self.latest = Some(estimate);
+        }
+        self.latest
+    }
+
+    /// The most recent estimate received by `update` or `try_iter`.
+    pub fn latest(&self) -> Option<PitchEstimate> {
+        self.latest
+    }
+
+    /// An iterator yielding all pending estimates in the order in which they were produced.
+    ///
+    /// Useful for visualising the pitch contour between frames.
+    pub fn try_iter(&mut self) -> impl Iterator<Item = PitchEstimate> + '_ {
+        let latest = &mut self.latest;
+        self.rx
+            .try_iter()
+            .inspect(move |estimate| *latest = Some(*estimate))
+    }
+}
+
+impl Builder {
+    /// The default length of the analysis window in frames.
+    pub const DEFAULT_WINDOW_LEN: usize = 2048;
+
+    /// The default number of estimates per second.
+    pub const DEFAULT_RATE: f32 = 30.0;
+
+    /// The default lowest detectable frequency in hz.
+    pub const DEFAULT_MIN_HZ: f32 = 50.0;
+
+    /// The default highest detectable frequency in hz.
+    pub const DEFAULT_MAX_HZ: f32 = 2_000.0;
+
+    /// The default RMS amplitude below which windows are considered silent.
+    pub const DEFAULT_SILENCE_RMS: f32 = 0.01;
+
+    /// The default number of estimates that may be pending within the receiver.
+    pub const DEFAULT_CAPACITY: usize = 64;
+
+    /// The algorithm used to estimate the frequency of each window.
+    ///
+    /// By default, this value is `Algorithm::yin()`, or `Algorithm::mpm()` if the `pitch-yin`
+    /// feature is disabled.
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// The length of the analysis window in frames.
+    ///
+    /// The lowest detectable frequency is limited to twice the sample rate divided by the window
+    /// length, e.g. ~47hz for a 2048 frame window at 48khz. Longer windows detect lower
+    /// frequencies at the cost of latency and CPU.
+    ///
+    /// By default, this value is `Builder::DEFAULT_WINDOW_LEN`.
+    pub fn window_len(mut self, frames: usize) -> Self {
+        self.window_len = frames;
+        self
+    }
+
+    /// The number of estimates to produce per second of processed audio.
+    ///
+    /// By default, this value is `Builder::DEFAULT_RATE`.
+    pub fn rate(mut self, hz: f32) -> Self {
+        self.rate = hz;
+        self
+    }
+
+    /// The range of frequencies within which to search for the fundamental.
+    ///
+    /// Narrowing the range to that of the expected voice or instrument reduces both CPU and the
+    /// likelihood of octave errors.
+    ///
+    /// By default, this range is `Builder::DEFAULT_MIN_HZ` to `Builder::DEFAULT_MAX_HZ`.
+    pub fn range_hz(mut self, min_hz: f32, max_hz: f32) -> Self {
+        self.min_hz = min_hz;
+        self.max_hz = max_hz;
+        self
+    }
+
+    /// The RMS amplitude below which a window is considered silent and yields no frequency.
+    ///
+    /// By default, this value is `Builder::DEFAULT_SILENCE_RMS`.
+    pub fn silence_rms(mut self, rms: f32) -> Self {
+        self.silence_rms = rms;
+        self
+    }
+
+    /// Analyse only the channel at the given index rather than the mean of all channels.
+    ///
+    /// Frames that have no channel at the given index are treated as silent.
+    ///
+    /// By default, all channels are mixed down to mono.
+    pub fn channel(mut self, index: usize) -> Self {
+        self.channel = Some(index);
+        self
+    }
+
+    /// The number of estimates that may be pending within the receiver before new estimates are
+    /// dropped.
+    ///
+    /// By default, this value is `Builder::DEFAULT_CAPACITY`.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Build the `Pitch` analyser and the receiver for its estimates.
+    ///
+    /// **Panic!**s if the window length is less than `8`, if the rate is not positive or if the
+    /// minimum frequency is not positive and less than the maximum.
+    pub fn build(self) -> (Pitch, PitchReceiver) {
+        assert!(self.window_len >= 8, "the window length must be at least 8");
+        assert!(self.rate > 0.0, "the rate must be positive");
+        assert!(
+            self.min_hz > 0.0 && self.min_hz < self.max_hz,
+            "the minimum frequency must be positive and less than the maximum"
+        );
+        let (tx, rx) = mpsc::sync_channel(self.capacity);
+        let pitch = Pitch {
+            algorithm: self.algorithm,
+            channel: self.channel,
+            rate: self.rate,
+            min_hz: self.min_hz,
+            max_hz: self.max_hz,
+            silence_rms: self.silence_rms,
+            history: vec![0.0; self.window_len],
+            write_ix: 0,
+            window: vec![0.0; self.window_len],
+            scratch: vec![0.0; self.window_len / 2 + 1],
+            frames_since_estimate: 0,
+            frame: 0,
+            tx,
+        };
+        let receiver = PitchReceiver { rx, latest: None };
+        (pitch, receiver)
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            algorithm: Algorithm::default(),
+            window_len: Self::DEFAULT_WINDOW_LEN,
+            rate: Self::DEFAULT_RATE,
+            min_hz: Self::DEFAULT_MIN_HZ,
+            max_hz: Self::DEFAULT_MAX_HZ,
+            silence_rms: Self::DEFAULT_SILENCE_RMS,
+            channel: None,
+            capacity: Self::DEFAULT_CAPACITY,
+        }
+    }
+}
+
+// Reduce a frame to a single sample, either the given channel or the mean of all channels.
+pub(super) fn mono<S>(frame: &[S], channel: Option<usize>) -> f32
+where
+    S: Sample,
+{
+    let to_f32 = |s: &S| s.to_float_sample().to_sample::<f32>();
+    match channel {
+        Some(ix) => frame.get(ix).map(to_f32).unwrap_or(0.0),
+        None if frame.is_empty() => 0.0,
+        None => frame.iter().map(to_f32).sum::<f32>() / frame.len() as f32,
+    }
+}
+
+// Estimate the period of the window in frames using YIN, returning the period if one was found
+// along with the confidence.
+//
+// `cmnd` must have a length of at least `tau_max + 1`.
+#[cfg(feature = "pitch-yin")]
+fn yin(
+    x: &[f32],
+    cmnd: &mut [f32],
+    tau_min: usize,
+    tau_max: usize,
+    threshold: f32,
+) -> (Option<f32>, f32) {
+    // The cumulative mean normalised difference function.
+    let integration_len = x.len() - tau_max;
+    let mut running_sum = 0.0;
+    cmnd[0] = 1.0;
+    for tau in 1..=tau_max {
+        let diff: f32 = (0..integration_len)
+            .map(|j| {
+                let delta = x[j] - x[j + tau];
+                delta * delta
+            })
+            .sum();
+        running_sum += diff;
+        cmnd[tau] = if running_sum > 0.0 {
+            diff * tau as f32 / running_sum
+        } else {
+            1.0
+        };
+    }
+
+    // The first dip below the threshold, descended to its local minimum.
+    let mut tau = tau_min;
+    while tau < tau_max {
+        if cmnd[tau] < threshold {
+            while tau + 1 < tau_max && cmnd[tau + 1] < cmnd[tau] {
+                tau += 1;
+            }
+            let period = parabolic_peak(cmnd, tau);
+            return (Some(period), 1.0 - cmnd[tau]);
+        }
+        tau += 1;
+    }
+
+    // No period was clear enough, though the deepest dip still indicates confidence.
+    let min = cmnd[tau_min..tau_max]
+        .iter()
+        .cloned()
+        .fold(std::f32::MAX, f32::min);
+    (None, 1.0 - min)
+}
+
+// Estimate the period of the window in frames using the McLeod Pitch Method, returning the period
+// if one was found along with the confidence.
+//
+// `nsdf` must have a length of at least `tau_max + 1`.
+#[cfg(feature = "pitch-mpm")]
+fn mpm(
+    x: &[f32],
+    nsdf: &mut [f32],
+    tau_min: usize,
+    tau_max: usize,
+    cutoff: f32,
+) -> (Option<f32>, f32) {
+    // The normalised square difference function.
+    for tau in 0..=tau_max {
+        let (mut acf, mut m) = (0.0, 0.0);
+        for j in 0..x.len() - tau {
+            acf += x[j] * x[j + tau];
+            m += x[j] * x[j] + x[j + tau] * x[j + tau];
+        }
+        nsdf[tau] = if m > 0.0 { 2.0 * acf / m } else { 0.0 };
+    }
+
+    // Find the highest key maximum, then choose the first that comes close to it.
+    let highest = KeyMaxima::new(nsdf, tau_min, tau_max)
+        .map(|tau| nsdf[tau])
+        .fold(0.0, f32::max);
+    if highest <= 0.0 {
+        return (None, 0.0);
+    }
+    let threshold = highest * cutoff;
+    match KeyMaxima::new(nsdf, tau_min, tau_max).find(|&tau| nsdf[tau] >= threshold) {
+        Some(tau) => (Some(parabolic_peak(nsdf, tau)), nsdf[tau]),
+        None => (None, 0.0),
+    }
+}
+
+// Yields the index of the maximum of each positive lobe of the NSDF following its first
+// negatively sloped zero crossing, limited to the range `tau_min..tau_max`.
+#[cfg(feature = "pitch-mpm")]
+struct KeyMaxima<'a> {
+    nsdf: &'a [f32],
+    tau: usize,
+    tau_min: usize,
+    tau_max: usize,
+}
+
+#[cfg(feature = "pitch-mpm")]
+impl<'a> KeyMaxima<'a> {
+    fn new(nsdf: &'a [f32], tau_min: usize, tau_max: usize) -> Self {
+        // Skip the lobe surrounding a lag of zero.
+        let mut tau = 1;
+        while tau < tau_max && nsdf[tau] > 0.0 {
+            tau += 1;
+        }
+        KeyMaxima {
+            nsdf,
+            tau,
+            tau_min,
+            tau_max,
+        }
+    }
+}
+
+#[cfg(feature = "pitch-mpm")]
+impl<'a> Iterator for KeyMaxima<'a> {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            while self.tau < self.tau_max && self.nsdf[self.tau] <= 0.0 {
+                self.tau += 1;
+            }
+            if self.tau >= self.tau_max {
+                return None;
+            }
+            let mut max = self.tau;
+            while self.tau < self.tau_max && self.nsdf[self.tau] > 0.0 {
+                if self.nsdf[self.tau] > self.nsdf[max] {
+                    max = self.tau;
+                }
+                self.tau += 1;
+            }
+            if max >= self.tau_min {
+                return Some(max);
+            }
+        }
+    }
+}
+
+// Refine the position of the extremum at index `i` by fitting a parabola through it and its
+// neighbours.
+pub(super) fn parabolic_peak(y: &[f32], i: usize) -> f32 {
+    let (a, b, c) = (y[i - 1], y[i], y[i + 1]);
+    let denom = a - 2.0 * b + c;
+    if denom.abs() < std::f32::EPSILON {
+        i as f32
+    } else {
+        i as f32 + 0.5 * (a - c) / denom
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    // A buffer of the given number of frames with each channel produced by the given function of
+    // the frame index and channel.
+    fn buffer<F>(frames: usize, channels: usize, sample: F) -> Buffer
+    where
+        F: Fn(usize, usize) -> f32,
+    {
+        let interleaved_samples = (0..frames * channels)
+            .map(|ix| sample(ix / channels, ix % channels))
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+        Buffer {
+            interleaved_samples,
+            channels,
+            sample_rate: SAMPLE_RATE,
+        }
+    }
+
+    fn sine(hz: f32, frame: usize) -> f32 {
+        let t = frame as f32 / SAMPLE_RATE as f32;
+        (t * hz * 2.0 * std::f32::consts::PI).sin() * 0.5
+    }
+
+    fn detect(algorithm: Algorithm, hz: f32) -> PitchEstimate {
+        let (mut pitch, mut rx) = Pitch::builder().algorithm(algorithm).build();
+        pitch.process(&buffer(SAMPLE_RATE as usize / 4, 1, |i, _| sine(hz, i)));
+        rx.update().expect("no estimate was produced")
+    }
+
+    #[cfg(feature = "pitch-yin")]
+    #[test]
+    fn yin_detects_sine_frequency() {
+        for &hz in &[110.0, 220.0, 440.0, 880.0] {
+            let estimate = detect(Algorithm::yin(), hz);
+            let detected = estimate.hz.expect("no frequency was detected");
+            assert!((detected - hz).abs() < hz * 0.01, "{} != {}", detected, hz);
+            assert!(estimate.confidence > 0.9);
+        }
+    }
+
+    #[cfg(feature = "pitch-mpm")]
+    #[test]
+    fn mpm_detects_sine_frequency() {
+        for &hz in &[110.0, 220.0, 440.0, 880.0] {
+            let estimate = detect(Algorithm::mpm(), hz);
+            let detected = estimate.hz.expect("no frequency was detected");
+            assert!((detected - hz).abs() < hz * 0.01, "{} != {}", detected, hz);
+            assert!(estimate.confidence > 0.9);
+        }
+    }
+
+    #[test]
+    fn silence_is_unpitched() {
+        let (mut pitch, mut rx) = Pitch::new();
+        pitch.process(&buffer(SAMPLE_RATE as usize / 4, 1, |_, _| 0.0));
+        let estimate = rx.update().unwrap();
+        assert_eq!(estimate.hz, None);
+        assert_eq!(estimate.confidence, 0.0);
+    }
+
+    #[test]
+    fn estimates_are_produced_at_rate_once_window_fills() {
+        let (mut pitch, mut rx) = Pitch::new();
+        let window_len = pitch.window_len();
+        let frames = Buffer::<f32>::DEFAULT_LEN_FRAMES;
+        for _ in 0..SAMPLE_RATE as usize / frames {
+            pitch.process(&buffer(frames, 1, |i, _| sine(440.0, i)));
+        }
+        let estimates: Vec<_> = rx.try_iter().collect();
+        // One estimate once the window fills, then one every hop.
+        let hop = (SAMPLE_RATE as f32 / Builder::DEFAULT_RATE).round() as u64;
+        assert_eq!(estimates[0].frame, window_len as u64);
+        for pair in estimates.windows(2) {
+            assert_eq!(pair[1].frame - pair[0].frame, hop);
+        }
+        assert_eq!(estimates.len(), 29);
+    }
+
+    #[test]
+    fn analyses_selected_channel() {
+        let stereo = buffer(SAMPLE_RATE as usize / 4, 2, |i, ch| match ch {
+            0 => 0.0,
+            _ => sine(330.0, i),
+        });
+        let (mut pitch, mut rx) = Pitch::builder().channel(0).build();
+        pitch.process(&stereo);
+        assert_eq!(rx.update().unwrap().hz, None);
+        let (mut pitch, mut rx) = Pitch::builder().channel(1).build();
+        pitch.process(&stereo);
+        let detected = rx.update().unwrap().hz.unwrap();
+        assert!((detected - 330.0).abs() < 3.3);
+    }
+
+    #[test]
+    fn reset_clears_window() {
+        let (mut pitch, mut rx) = Pitch::new();
+        pitch.process(&buffer(SAMPLE_RATE as usize / 4, 1, |i, _| sine(440.0, i)));
+        rx.update();
+        pitch.reset();
+        let frames = pitch.window_len() - 1;
+        pitch.process(&buffer(frames, 1, |i, _| sine(440.0, i)));
+        assert_eq!(rx.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn midi_note_of_a4() {
+        let estimate = PitchEstimate {
+            hz: Some(440.0),
+            confidence: 1.0,
+            frame: 0,
+        };
+        assert!((estimate.midi_note().unwrap() - 69.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,342 @@
+//! A lightweight per-channel gain, mute and solo strip applied to every stream.
+//!
+//! Each **Stream** owns a [**ChannelStrip**](./struct.ChannelStrip.html) with one
+//! [**Channel**](./struct.Channel.html) per stream channel. The parameters of each channel are
+//! stored atomically, so they may be adjusted from the UI thread without locking, e.g.
+//! `stream.channel(2).set_gain_db(-6.0)`.
+//!
+//! For output streams the strip is applied to the buffer after the render function has filled
+//! it. For input streams the strip is applied to the captured samples before they are delivered
+//! to the capture function.
+
+use dasp_sample::Sample;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// The gain, mute and solo state for each channel of a stream.
+///
+/// Cloning a `ChannelStrip` produces a new handle to the same channels.
+#[derive(Clone, Debug)]
+pub struct ChannelStrip {
+    channels: Arc<[ChannelInner]>,
+}
+
+/// A handle to the gain, mute and solo state of a single channel within a `ChannelStrip`.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    strip: ChannelStrip,
+    index: usize,
+}
+
+#[derive(Debug)]
+struct ChannelInner {
+    // The bits of the linear gain as an `f32`.
+    gain: AtomicU32,
+    muted: AtomicBool,
+    soloed: AtomicBool,
+}
+
+impl ChannelStrip {
+    /// Create a strip with the given number of channels, each at unity gain.
+    pub fn new(channels: usize) -> Self {
+        let channels = (0..channels).map(|_| ChannelInner::default()).collect();
+        ChannelStrip { channels }
+    }
+
+    /// The number of channels within the strip.
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Whether or not the strip has no channels.
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// A handle to the channel at the given index.
+    ///
+    /// Returns `None` if there is no channel at the given index.
+    pub fn get(&self, index: usize) -> Option<Channel> {
+        if index >= self.len() {
+            return None;
+        }
+        let strip = self.clone();
+        Some(Channel { strip, index })
+    }
+
+    /// A handle to the channel at the given index.
+    ///
+    /// **Panic!**s if there is no channel at the given index.
+    pub fn channel(&self, index: usize) -> Channel {
+        self.get(index).unwrap_or_else(|| {
+            panic!(
+                "no channel at index {} within strip of {} channels",
+                index,
+                self.len()
+            )
+        })
+    }
+
+    /// An iterator yielding a handle to each channel.
+    pub fn channels(&self) -> impl Iterator<Item = Channel> + '_ {
+        (0..self.len()).map(move |index| self.channel(index))
+    }
+
+    /// Whether or not any channel is currently soloed.
+    ///
+    /// While any channel is soloed, all channels that are not soloed are silenced.
+    pub fn any_soloed(&self) -> bool {
+        self.channels
+            .iter()
+            .any(|ch| ch.soloed.load(Ordering::Relaxed))
+    }
+
+    /// Reset all channels to unity gain, un-muted and un-soloed.
+    pub fn reset(&self) {
+        for channel in self.channels() {
+            channel.reset();
+        }
+    }
+
+    /// The amplitude currently applied to the channel at the given index, taking gain, mute and
+    /// solo into account.
+    ///
+    /// **Panic!**s if there is no channel at the given index.
+    pub fn amp(&self, index: usize) -> f32 {
+        self.amp_inner(index, self.any_soloed())
+    }
+
+    /// Apply the strip to the given buffer of interleaved samples.
+    ///
+    /// Channels at unity amplitude are left untouched. If the buffer has more channels than the
+    /// strip, the extra channels are left untouched.
+    ///
+    /// **Panic!**s if the strip has no channels.
+    pub fn process<S>(&self, samples: &mut [S])
+    where
+        S: Sample,
+    {
+        let n_channels = self.len();
+        assert!(
+            n_channels > 0,
+            "cannot process samples with an empty channel strip"
+        );
+        let any_soloed = self.any_soloed();
+        for index in 0..n_channels {
+            let amp = self.amp_inner(index, any_soloed);
+            if amp == 1.0 {
+                continue;
+            }
+            let channel_samples = samples.iter_mut().skip(index).step_by(n_channels);
+            if amp == 0.0 {
+                channel_samples.for_each(|s| *s = S::EQUILIBRIUM);
+            } else {
+                let amp: S::Float = amp.to_sample();
+                channel_samples.for_each(|s| *s = s.mul_amp(amp));
+            }
+        }
+    }
+
+    // The amplitude of the channel at the given index given whether or not any channel is soloed.
+    fn amp_inner(&self, index: usize, any_soloed: bool) -> f32 {
+        let ch = &self.channels[index];
+        let muted = ch.muted.load(Ordering::Relaxed);
+        let silenced = any_soloed && !ch.soloed.load(Ordering::Relaxed);
+        if muted || silenced {
+            0.0
+        } else {
+            f32::from_bits(ch.gain.load(Ordering::Relaxed))
+        }
+    }
+}
+
+impl Channel {
+    /// The index of the channel within the stream.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The linear gain of the channel.
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.inner().gain.load(Ordering::Relaxed))
+    }
+
+    /// Set the linear gain of the channel.
+    ///
+    /// Changes are applied from the next buffer without smoothing. See the `param` module for
+    /// smoothly ramping gain within the render function.
+    pub fn set_gain(&self, gain: f32) {
+        self.inner().gain.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The gain of the channel in decibels.
+    pub fn gain_db(&self) -> f32 {
+        amp_to_db(self.gain())
+    }
+
+    /// Set the gain of the channel in decibels.
+    ///
+    /// `0.0` dB is unity gain.
+    pub fn set_gain_db(&self, db: f32) {
+        self.set_gain(db_to_amp(db));
+    }
+
+    /// Whether or not the channel is muted.
+    pub fn is_muted(&self) -> bool {
+        self.inner().muted.load(Ordering::Relaxed)
+    }
+
+    /// Mute or un-mute the channel.
+    pub fn set_mute(&self, mute: bool) {
+        self.inner().muted.store(mute, Ordering::Relaxed);
+    }
+
+    /// Whether or not the channel is soloed.
+    pub fn is_soloed(&self) -> bool {
+        self.inner().soloed.load(Ordering::Relaxed)
+    }
+
+    /// Solo or un-solo the channel.
+    ///
+    /// While any channel is soloed, all channels that are not soloed are silenced.
+    pub fn set_solo(&self, solo: bool) {
+        self.inner().soloed.store(solo, Ordering::Relaxed);
+    }
+
+    /// Reset the channel to unity gain, un-muted and un-soloed.
+    pub fn reset(&self) {
+        self.set_gain(1.0);
+        self.set_mute(false);
+        self.set_solo(false);
+    }
+
+    fn inner(&self) -> &ChannelInner {
+        &self.strip.channels[self.index]
+    }
+}
+
+impl Default for ChannelInner {
+    fn default() -> Self {
+        ChannelInner {
+            gain: AtomicU32::new(1.0f32.to_bits()),
+            muted: AtomicBool::new(false),
+            soloed: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Convert the given decibels to a linear amplitude.
+pub fn db_to_amp(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Convert the given linear amplitude to decibels.
+///
+/// An amplitude of `0.0` produces negative infinity.
+pub fn amp_to_db(amp: f32) -> f32 {
+    20.0 * amp.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two frames of a three channel buffer.
+    fn frames() -> Vec<f32> {
+        vec![0.5, 0.5, 0.5, -0.5, -0.5, -0.5]
+    }
+
+    #[test]
+    fn unity_strip_leaves_samples_untouched() {
+        let strip = ChannelStrip::new(3);
+        let mut samples = frames();
+        strip.process(&mut samples);
+        assert_eq!(samples, frames());
+    }
+
+    #[test]
+    fn gain_scales_only_its_channel() {
+        let strip = ChannelStrip::new(3);
+        strip.channel(1).set_gain(0.5);
+        let mut samples = frames();
+        strip.process(&mut samples);
+        assert_eq!(samples, vec![0.5, 0.25, 0.5, -0.5, -0.25, -0.5]);
+    }
+
+    #[test]
+    fn mute_silences_its_channel() {
+        let strip = ChannelStrip::new(3);
+        strip.channel(2).set_mute(true);
+        let mut samples = frames();
+        strip.process(&mut samples);
+        assert_eq!(samples, vec![0.5, 0.5, 0.0, -0.5, -0.5, 0.0]);
+        assert_eq!(strip.amp(2), 0.0);
+    }
+
+    #[test]
+    fn solo_silences_the_other_channels() {
+        let strip = ChannelStrip::new(3);
+        strip.channel(0).set_solo(true);
+        strip.channel(0).set_gain(0.5);
+        assert!(strip.any_soloed());
+        let mut samples = frames();
+        strip.process(&mut samples);
+        assert_eq!(samples, vec![0.25, 0.0, 0.0, -0.25, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn mute_overrides_solo() {
+        let strip = ChannelStrip::new(2);
+        strip.channel(0).set_solo(true);
+        strip.channel(0).set_mute(true);
+        assert_eq!(strip.amp(0), 0.0);
+        assert_eq!(strip.amp(1), 0.0);
+    }
+
+    #[test]
+    fn integer_samples_are_muted_to_equilibrium() {
+        let strip = ChannelStrip::new(1);
+        strip.channel(0).set_mute(true);
+        let mut samples = [1_000i16, -1_000];
+        strip.process(&mut samples);
+        assert_eq!(samples, [0, 0]);
+        let mut samples = [u16::MAX, 0];
+        strip.process(&mut samples);
+        assert_eq!(samples, [u16::EQUILIBRIUM; 2]);
+    }
+
+    #[test]
+    fn reset_restores_unity() {
+        let strip = ChannelStrip::new(2);
+        strip.channel(0).set_gain(0.25);
+        strip.channel(0).set_mute(true);
+        strip.channel(1).set_solo(true);
+        strip.reset();
+        assert!(!strip.any_soloed());
+        assert!(strip
+            .channels()
+            .all(|ch| ch.gain() == 1.0 && !ch.is_muted()));
+        assert_eq!(strip.amp(0), 1.0);
+    }
+
+    #[test]
+    fn channel_out_of_range_is_none() {
+        let strip = ChannelStrip::new(2);
+        assert!(strip.get(1).is_some());
+        assert!(strip.get(2).is_none());
+    }
+
+    #[test]
+    fn decibels_round_trip() {
+        assert_eq!(db_to_amp(0.0), 1.0);
+        assert!((db_to_amp(-6.0) - 0.501_187).abs() < 1e-5);
+        assert!((db_to_amp(20.0) - 10.0).abs() < 1e-5);
+        assert_eq!(amp_to_db(0.0), std::f32::NEG_INFINITY);
+        for &db in &[-48.0, -6.0, 0.0, 12.0] {
+            assert!((amp_to_db(db_to_amp(db)) - db).abs() < 1e-4);
+        }
+        let channel = ChannelStrip::new(1).channel(0);
+        channel.set_gain_db(-6.0);
+        assert!((channel.gain_db() + 6.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,500 @@
+//! Realtime convolution with an impulse response, e.g. for convolution reverb.
+//!
+//! A [**Convolver**](./struct.Convolver.html) convolves each channel of a stream with an
+//! [**ImpulseResponse**](./struct.ImpulseResponse.html) using uniformly partitioned FFT
+//! convolution. The impulse response is split into blocks of `block_len` frames, each of which is
+//! transformed once during setup. During processing only one forward and one inverse FFT are
+//! performed per channel per block, regardless of the length of the impulse response.
+//!
+//! All buffers are allocated when the `Convolver` is created, so `process` may be called from
+//! within an output stream's render function.
+
+use dasp_sample::Sample;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// An impulse response with one or more channels of `f32` samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImpulseResponse {
+    channels: Vec<Vec<f32>>,
+    sample_rate: u32,
+}
+
+/// Convolves each channel of a buffer of interleaved samples with an impulse response.
+///
+/// Output is delayed by `latency_frames` relative to the input, i.e. one block.
+pub struct Convolver {
+    block_len: usize,
+    fft: Arc<dyn Fft<f32>>,
+    ifft: Arc<dyn Fft<f32>>,
+    scratch: Vec<Complex<f32>>,
+    channels: Vec<ChannelState>,
+    // The position of the next frame within the current block.
+    frame_ix: usize,
+    wet: f32,
+    dry: f32,
+}
+
+// The convolution state for a single channel.
+struct ChannelState {
+    // The spectrum of each zero-padded partition of the impulse response.
+    ir_partitions: Vec<Vec<Complex<f32>>>,
+    // The spectra of the most recent input windows, used as a ring buffer.
+    input_spectra: Vec<Vec<Complex<f32>>>,
+    // The index of the most recent spectrum within `input_spectra`.
+    input_spectra_ix: usize,
+    // The previous and current block of input samples.
+    input: Vec<f32>,
+    // The output of the last processed block.
+    output: Vec<f32>,
+    // Used for transforming the input window and accumulating the output spectrum.
+    window: Vec<Complex<f32>>,
+    accum: Vec<Complex<f32>>,
+}
+
+/// Errors that might occur while loading an `ImpulseResponse`.
+#[derive(Debug, Error)]
+pub enum ImpulseResponseError {
+    #[error("failed to read the WAV file: {err}")]
+    Wav {
+        #[from]
+        err: hound::Error,
+    },
+    #[error("the impulse response contains no samples")]
+    Empty,
+}
+
+impl ImpulseResponse {
+    /// Create an impulse response from the given channels of samples.
+    ///
+    /// Channels shorter than the longest channel are padded with silence.
+    ///
+    /// Returns `Err` if there are no channels or all channels are empty.
+    pub fn new(
+        mut channels: Vec<Vec<f32>>,
+        sample_rate: u32,
+    ) -> Result<Self, ImpulseResponseError> {
+        let len = channels.iter().map(|ch| ch.len()).max().unwrap_or(0);
+        if len == 0 {
+            return Err(ImpulseResponseError::Empty);
+        }
+        for channel in &mut channels {
+            channel.resize(len, 0.0);
+        }
+        Ok(ImpulseResponse {
+            channels,
+            sample_rate,
+        })
+    }
+
+    /// Load an impulse response from the WAV file at the given path.
+    ///
+    /// Both integer and floating point WAV files are supported. Integer samples are converted to
+    /// the range `-1.0..1.0`.
+    pub fn from_wav<P>(path: P) -> Result<Self, ImpulseResponseError>
+    where
+        P: AsRef<Path>,
+    {
+        let reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let n_channels = spec.channels as usize;
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|res| res.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        let mut channels = vec![Vec::with_capacity(samples.len() / n_channels); n_channels];
+        for frame in samples.chunks_exact(n_channels) {
+            for (channel, &sample) in channels.iter_mut().zip(frame) {
+                channel.push(sample);
+            }
+        }
+        Self::new(channels, spec.sample_rate)
+    }
+
+    /// The number of channels within the impulse response.
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The samples of the channel at the given index.
+    ///
+    /// **Panic!**s if there is no channel at the given index.
+    pub fn channel(&self, index: usize) -> &[f32] {
+        &self.channels[index]
+    }
+
+    /// The length of the impulse response in frames.
+    pub fn len_frames(&self) -> usize {
+        self.channels[0].len()
+    }
+
+    /// The sample rate at which the impulse response was recorded.
+    ///
+    /// Impulse responses are not resampled, so this should match the sample rate of the stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Scale all channels so that the channel with the greatest energy has unit energy.
+    ///
+    /// This keeps the loudness of the wet signal roughly consistent between impulse responses.
+    pub fn normalize(&mut self) {
+        let energy = self
+            .channels
+            .iter()
+            .map(|ch| ch.iter().map(|s| s * s).sum::<f32>())
+            .fold(0.0, f32::max);
+        if energy > 0.0 {
+            let scale = 1.0 / energy.sqrt();
+            for s in self.channels.iter_mut().flat_map(|ch| ch.iter_mut()) {
+                *s *= scale;
+            }
+        }
+    }
+}
+
+impl Convolver {
+    /// The block length used by `Convolver::new`.
+    pub const DEFAULT_BLOCK_LEN: usize = 256;
+
+    /// Create a convolver for a stream with the given number of channels.
+    ///
+    /// If the impulse response has as many channels as the stream, each stream channel is
+    /// convolved with its respective impulse response channel. Otherwise, stream channel `i` is
+    /// convolved with impulse response channel `i % ir.channels()`, e.g. a mono impulse response
+    /// is applied to all channels.
+    ///
+    /// **Panic!**s if `channels` is `0`.
+    pub fn new(ir: &ImpulseResponse, channels: usize) -> Self {
+        Self::with_block_len(ir, channels, Self::DEFAULT_BLOCK_LEN)
+    }
+
+    /// Create a convolver with the given block length in frames.
+    ///
+    /// Shorter blocks reduce latency at the cost of more CPU time. Block lengths that are a power
+    /// of two are processed most efficiently.
+    ///
+    /// **Panic!**s if `channels` or `block_len` is `0`.
+    pub fn with_block_len(ir: &ImpulseResponse, channels: usize, block_len: usize) -> Self {
+        assert!(channels > 0, "a convolver requires at least one channel");
+        assert!(
+            block_len > 0,
+            "a convolver requires a non-zero block length"
+        );
+        let fft_len = block_len * 2;
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(fft_len);
+        let ifft = planner.plan_fft_inverse(fft_len);
+        let scratch_len = std::cmp::max(
+            fft.get_inplace_scratch_len(),
+            ifft.get_inplace_scratch_len(),
+        );
+        let mut scratch = vec![Complex::default(); scratch_len];
+
+        // The inverse FFT is unnormalised, so fold the normalisation into the partitions.
+        let scale = 1.0 / fft_len as f32;
+        let n_partitions = (ir.len_frames() + block_len - 1) / block_len;
+        let channels = (0..channels)
+            .map(|ch| {
+                let ir_channel = ir.channel(ch % ir.channels());
+                let ir_partitions = ir_channel
+                    .chunks(block_len)
+                    .map(|partition| {
+                        let mut spectrum = vec![Complex::default(); fft_len];
+                        for (c, &s) in spectrum.iter_mut().zip(partition) {
+                            c.re = s * scale;
+                        }
+                        fft.process_with_scratch(&mut spectrum, &mut scratch);
+                        spectrum
+                    })
+                    .collect();
+                ChannelState {
+                    ir_partitions,
+                    input_spectra: vec![vec![Complex::default(); fft_len]; n_partitions],
+                    input_spectra_ix: 0,
+                    input: vec![0.0; fft_len],
+                    output: vec![0.0; block_len],
+                    window: vec![Complex::default(); fft_len],
+                    accum: vec![Complex::default(); fft_len],
+                }
+            })
+            .collect();
+
+        Convolver {
+            block_len,
+            fft,
+            ifft,
+            scratch,
+            channels,
+            frame_ix: 0,
+            wet: 1.0,
+            dry: 0.0,
+        }
+    }
+
+    /// The number of channels processed by the convolver.
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// The block length in frames.
+    pub fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    /// The delay of the wet signal relative to the input in frames.
+    pub fn latency_frames(&self) -> usize {
+        self.block_len
+    }
+
+    /// The amplitude of the convolved signal within the output.
+    pub fn wet(&self) -> f32 {
+        self.wet
+    }
+
+    /// The amplitude of the unprocessed input signal within the output.
+    pub fn dry(&self) -> f32 {
+        self.dry
+    }
+
+    /// Set the amplitude of the convolved signal within the output.
+    ///
+    /// By default, this value is `1.0`.
+    pub fn set_wet(&mut self, wet: f32) {
+        self.wet = wet;
+    }
+
+    /// Set the amplitude of the unprocessed input signal within the output.
+    ///
+    /// The dry signal is not delayed to match the `latency_frames` of the wet signal.
+    ///
+    /// By default, this value is `0.0`.
+    pub fn set_dry(&mut self, dry: f32) {
+        self.dry = dry;
+    }
+
+    /// Clear all buffered input and output, e.g. to silence the tail of the reverb.
+    pub fn reset(&mut self) {
+        self.frame_ix = 0;
+        for channel in &mut self.channels {
+            channel.input.iter_mut().for_each(|s| *s = 0.0);
+            channel.output.iter_mut().for_each(|s| *s = 0.0);
+            for spectrum in &mut channel.input_spectra {
+                spectrum.iter_mut().for_each(|c| *c = Complex::default());
+            }
+        }
+    }
+
+    /// Convolve the given buffer of interleaved samples in place.
+    ///
+    /// **Panic!**s if the length of the buffer is not a multiple of the number of channels.
+    pub fn process<S>(&mut self, samples: &mut [S])
+    where
+        S: Sample,
+    {
+        let n_channels = self.channels.len();
+        assert_eq!(
+            samples.len() % n_channels,
+            0,
+            "the buffer length must be a multiple of the number of channels"
+        );
+        for frame in samples.chunks_exact_mut(n_channels) {
+            for (channel, sample) in self.channels.iter_mut().zip(frame.iter_mut()) {
+                let input = sample.to_float_sample().to_sample::<f32>();
+                let wet = channel.output[self.frame_ix];
+                channel.input[self.block_len + self.frame_ix] = input;
+                let output = wet * self.wet + input * self.dry;
+                let output: S::Float = output.to_sample();
+                *sample = output.to_sample();
+            }
+            self.frame_ix += 1;
+            if self.frame_ix == self.block_len {
+                self.frame_ix = 0;
+                for channel in &mut self.channels {
+                    channel.process_block(&*self.fft, &*self.ifft, &mut self.scratch);
+                }
+            }
+        }
+    }
+}
+
+impl ChannelState {
+    // Convolve the current window of input with the impulse response, producing the next block of
+    // output via overlap-save.
+    fn process_block(
+        &mut self,
+        fft: &dyn Fft<f32>,
+        ifft: &dyn Fft<f32>,
+        scratch: &mut [Complex<f32>],
+    ) {
+        let block_len = self.output.len();
+        let n_partitions = self.ir_partitions.len();
+
+        // Transform the window of the previous and current blocks of input.
+        for (c, &s) in self.window.iter_mut().zip(&self.input) {
+            *c = Complex::new(s, 0.0);
+        }
+        fft.process_with_scratch(&mut self.window, scratch);
+        self.input_spectra_ix = (self.input_spectra_ix + 1) % n_partitions;
+        self.input_spectra[self.input_spectra_ix].copy_from_slice(&self.window);
+
+        // Multiply each partition with the spectrum of the input it is delayed by.
+        self.accum.iter_mut().for_each(|c| *c = Complex::default());
+        for (i, partition) in self.ir_partitions.iter().enumerate() {
+            let ix = (self.input_spectra_ix + n_partitions - i) % n_partitions;
+            let spectrum = &self.input_spectra[ix];
+            for ((acc, &h), &x) in self.accum.iter_mut().zip(partition).zip(spectrum) {
+                *acc += h * x;
+            }
+        }
+
+        // The second half of the circular convolution is the linear convolution of the block.
+        ifft.process_with_scratch(&mut self.accum, scratch);
+        for (out, c) in self.output.iter_mut().zip(&self.accum[block_len..]) {
+            *out = c.re;
+        }
+
+        // Shift the current block of input into the previous block.
+        self.input.copy_within(block_len.., 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    // A deterministic, non-periodic test signal.
+    fn signal(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| ((i as f32 * 0.37).sin() + (i as f32 * 0.051).cos()) * 0.5)
+            .collect()
+    }
+
+    // The linear convolution of `input` with `ir`, truncated to the length of `input`.
+    fn direct_convolution(input: &[f32], ir: &[f32]) -> Vec<f32> {
+        (0..input.len())
+            .map(|n| {
+                ir.iter()
+                    .enumerate()
+                    .take_while(|&(k, _)| k <= n)
+                    .map(|(k, &h)| h * input[n - k])
+                    .sum()
+            })
+            .collect()
+    }
+
+    // Process `input` in chunks of uneven length, as a render function might.
+    fn process_mono(convolver: &mut Convolver, input: &[f32]) -> Vec<f32> {
+        let mut output = input.to_vec();
+        for chunk in output.chunks_mut(7) {
+            convolver.process(chunk);
+        }
+        output
+    }
+
+    // Assert that `output` matches `expected` delayed by `latency` frames.
+    fn assert_delayed(output: &[f32], expected: &[f32], latency: usize) {
+        for (n, &out) in output.iter().enumerate() {
+            let expected = if n < latency {
+                0.0
+            } else {
+                expected[n - latency]
+            };
+            assert!(
+                (out - expected).abs() < 1e-4,
+                "frame {}: expected {}, got {}",
+                n,
+                expected,
+                out
+            );
+        }
+    }
+
+    #[test]
+    fn unit_impulse_passes_input_through() {
+        let ir = ImpulseResponse::new(vec![vec![1.0]], SAMPLE_RATE).unwrap();
+        let mut convolver = Convolver::with_block_len(&ir, 1, 16);
+        let input = signal(100);
+        let output = process_mono(&mut convolver, &input);
+        assert_delayed(&output, &input, convolver.latency_frames());
+    }
+
+    #[test]
+    fn delayed_impulse_shifts_input() {
+        let delay = 5;
+        let mut impulse = vec![0.0; delay + 1];
+        impulse[delay] = 1.0;
+        let ir = ImpulseResponse::new(vec![impulse], SAMPLE_RATE).unwrap();
+        let mut convolver = Convolver::with_block_len(&ir, 1, 16);
+        let input = signal(100);
+        let output = process_mono(&mut convolver, &input);
+        assert_delayed(&output, &input, convolver.latency_frames() + delay);
+    }
+
+    #[test]
+    fn multiple_partitions_match_direct_convolution() {
+        let block_len = 8;
+        let impulse: Vec<f32> = (0..29)
+            .map(|i| 0.9f32.powi(i) * if i % 2 == 0 { 1.0 } else { -0.5 })
+            .collect();
+        assert!(impulse.len() > block_len * 3);
+        let ir = ImpulseResponse::new(vec![impulse.clone()], SAMPLE_RATE).unwrap();
+        let mut convolver = Convolver::with_block_len(&ir, 1, block_len);
+        let input = signal(200);
+        let output = process_mono(&mut convolver, &input);
+        let expected = direct_convolution(&input, &impulse);
+        assert_delayed(&output, &expected, convolver.latency_frames());
+    }
+
+    #[test]
+    fn each_channel_uses_its_own_impulse_response() {
+        let ir = ImpulseResponse::new(vec![vec![1.0], vec![0.0, 0.0, 0.5]], SAMPLE_RATE).unwrap();
+        let mut convolver = Convolver::with_block_len(&ir, 2, 4);
+        let left = signal(40);
+        let mut samples: Vec<f32> = left.iter().flat_map(|&s| vec![s, s]).collect();
+        convolver.process(&mut samples);
+        let latency = convolver.latency_frames();
+        for (n, frame) in samples.chunks_exact(2).enumerate() {
+            let l = if n < latency { 0.0 } else { left[n - latency] };
+            let r = if n < latency + 2 {
+                0.0
+            } else {
+                left[n - latency - 2] * 0.5
+            };
+            assert!((frame[0] - l).abs() < 1e-4);
+            assert!((frame[1] - r).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn dry_signal_is_not_delayed() {
+        let ir = ImpulseResponse::new(vec![vec![1.0]], SAMPLE_RATE).unwrap();
+        let mut convolver = Convolver::with_block_len(&ir, 1, 16);
+        convolver.set_wet(0.0);
+        convolver.set_dry(1.0);
+        let input = signal(50);
+        let output = process_mono(&mut convolver, &input);
+        assert_delayed(&output, &input, 0);
+    }
+
+    #[test]
+    fn reset_clears_the_tail() {
+        let ir = ImpulseResponse::new(vec![vec![1.0]], SAMPLE_RATE).unwrap();
+        let mut convolver = Convolver::with_block_len(&ir, 1, 16);
+        process_mono(&mut convolver, &signal(24));
+        convolver.reset();
+        let output = process_mono(&mut convolver, &[0.0; 48]);
+        assert!(output.iter().all(|&s| s == 0.0));
+    }
+}
@@ -0,0 +1,83 @@
+//! The error type shared by the stream builders and streams of this crate.
+//!
+//! Errors produced by CPAL are wrapped along with the context in which they occurred, e.g. the
+//! name of the device and the stream config that was requested, so that failures can be diagnosed
+//! from a log without reproducing them.
+
+use crate::stream::RequestedConfig;
+use thiserror::Error;
+
+/// Errors that might occur while building or controlling a stream.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no default input device is available")]
+    DefaultInputDevice,
+    #[error("no default output device is available")]
+    DefaultOutputDevice,
+    #[error("failed to enumerate the configs supported by device `{device}`: {err}")]
+    SupportedStreamConfigs {
+        /// The name of the device.
+        device: String,
+        err: cpal::SupportedStreamConfigsError,
+    },
+    #[error("device `{device}` supports no config matching the request ({requested})")]
+    NoMatchingConfig {
+        /// The name of the device.
+        device: String,
+        /// The config requested via the stream builder.
+        requested: RequestedConfig,
+        /// The configs supported by the device.
+        supported: Vec<cpal::SupportedStreamConfigRange>,
+    },
+    #[error("failed to build stream on device `{device}` with config {config:?}: {err}")]
+    BuildStream {
+        /// The name of the device.
+        device: String,
+        /// The supported config with which the stream was built.
+        config: cpal::StreamConfig,
+        err: cpal::BuildStreamError,
+    },
+    #[error("failed to play stream: {err}")]
+    PlayStream {
+        #[from]
+        err: cpal::PlayStreamError,
+    },
+    #[error("failed to pause stream: {err}")]
+    PauseStream {
+        #[from]
+        err: cpal::PauseStreamError,
+    },
+}
+
+impl Error {
+    /// Whether or not the error may be resolved by trying again, e.g. a device that is busy or
+    /// not yet available while the system is still starting up.
+    ///
+    /// This is used to determine whether or not a stream build should be retried.
+    pub fn is_transient(&self) -> bool {
+        match *self {
+            Error::DefaultInputDevice | Error::DefaultOutputDevice => true,
+            Error::SupportedStreamConfigs { ref err, .. } => match *err {
+                cpal::SupportedStreamConfigsError::DeviceNotAvailable
+                | cpal::SupportedStreamConfigsError::BackendSpecific { .. } => true,
+                cpal::SupportedStreamConfigsError::InvalidArgument => false,
+            },
+            Error::NoMatchingConfig { .. } => false,
+            Error::BuildStream { ref err, .. } => match *err {
+                cpal::BuildStreamError::DeviceNotAvailable
+                | cpal::BuildStreamError::BackendSpecific { .. } => true,
+                cpal::BuildStreamError::StreamConfigNotSupported
+                | cpal::BuildStreamError::InvalidArgument
+                | cpal::BuildStreamError::StreamIdOverflow => false,
+            },
+            Error::PlayStream { ref err } => match *err {
+                cpal::PlayStreamError::DeviceNotAvailable
+                | cpal::PlayStreamError::BackendSpecific { .. } => true,
+            },
+            Error::PauseStream { ref err } => match *err {
+                cpal::PauseStreamError::DeviceNotAvailable
+                | cpal::PauseStreamError::BackendSpecific { .. } => true,
+            },
+        }
+    }
+}
@@ -8,16 +8,49 @@
 //! - [**Devices**](./device/struct.Devices.html) - for enumerating all audio devices on the system.
 //! - [**Device**](./device/struct.Device.html) - for querying information about supported stream
 //!   formats or for creating a stream targeted towards a specific audio device.
+//! - [**Error**](./error/enum.Error.html) - the error type produced when building or controlling a
+//!   stream, carrying the device and requested config as context.
 //! - [**Receiver**](./receiver/struct.Receiver.html) and
 //!   [**Requester**](./requester/struct.Requester.html) for buffering input and output streams that
 //!   may deliver buffers of inconsistent sizes into a stream of consistently sized buffers.
+//! - [**Param**](./param/struct.Param.html) and [**Params**](./param/struct.Params.html) for
+//!   smoothly changing control-rate parameters of a stream from the UI thread.
+//! - [**SampleRateMonitor**](./monitor/struct.SampleRateMonitor.html) and
+//!   [**RebuildingStream**](./monitor/struct.RebuildingStream.html) for detecting and recovering
+//!   from changes to a device's sample rate under a running stream.
+//! - [**ChannelStrip**](./channel/struct.ChannelStrip.html) and
+//!   [**Channel**](./channel/struct.Channel.html) for per-channel gain, mute and solo on any
+//!   stream.
+//! - [**Pitch**](./analysis/pitch/struct.Pitch.html) and
+//!   [**PitchReceiver**](./analysis/pitch/struct.PitchReceiver.html) for estimating the
+//!   fundamental frequency of an input stream and delivering the estimates to the main thread
+//!   (requires the `pitch-yin` or `pitch-mpm` feature, both enabled by the `analysis` feature).
+//! - [**Convolver**](./convolve/struct.Convolver.html) and
+//!   [**ImpulseResponse**](./convolve/struct.ImpulseResponse.html) for realtime convolution reverb
+//!   within a render function (requires the `convolve` feature).
+//! - [**ambisonic**](./spatial/ambisonic/index.html) for first-order ambisonic encoding of mono
+//!   sources and decoding to speaker layouts or, with the `binaural` feature, to headphones.
+//! - [**VirtualHost**](./virtual_host/struct.VirtualHost.html) for deterministically driving
+//!   capture and render functions with virtual devices in tests, without audio hardware (requires
+//!   the `test` feature).
+//! - [**signal**](./signal/index.html) for bridging streams with `dasp` signals (requires the
+//!   `signal` feature).
 
 use cpal::traits::HostTrait;
-use std::marker::PhantomData;
 use std::sync::Arc;
 
+#[cfg(feature = "analysis")]
+pub use self::analysis::{BeatReceiver, BeatTracker, TempoEstimate};
+#[cfg(any(feature = "pitch-yin", feature = "pitch-mpm"))]
+pub use self::analysis::{Pitch, PitchEstimate, PitchReceiver};
 pub use self::buffer::Buffer;
+pub use self::channel::{Channel, ChannelStrip};
+#[cfg(feature = "convolve")]
+pub use self::convolve::{Convolver, ImpulseResponse};
 pub use self::device::{Device, Devices};
+pub use self::error::Error;
+pub use self::monitor::{RebuildingStream, SampleRateChange, SampleRateMonitor};
+pub use self::param::{Param, Params};
 pub use self::receiver::Receiver;
 pub use self::requester::Requester;
 pub use self::stream::Stream;
@@ -31,12 +64,27 @@ pub use cpal::{
     SupportedStreamConfigsError,
 };
 pub use dasp_sample;
+#[cfg(feature = "signal")]
+pub use dasp_signal;
 
+#[cfg(any(feature = "pitch-yin", feature = "pitch-mpm"))]
+pub mod analysis;
 pub mod buffer;
+pub mod channel;
+#[cfg(feature = "convolve")]
+pub mod convolve;
 pub mod device;
+pub mod error;
+pub mod monitor;
+pub mod param;
 pub mod receiver;
 pub mod requester;
+#[cfg(feature = "signal")]
+pub mod signal;
+pub mod spatial;
 pub mod stream;
+#[cfg(feature = "test")]
+pub mod virtual_host;
 
 /// The top-level audio API, for enumerating devices and spawning input/output streams.
 pub struct Host {
@@ -123,6 +171,7 @@ impl Host {
             render: stream::output::default_render_fn,
             error: stream::default_error_fn,
             builder: self.new_stream(model),
+            auto_suspend: Default::default(),
         }
     }
 
@@ -131,16 +180,7 @@ impl Host {
     // If this is the first time a stream has been created, this method will spawn the
     // `cpal::EventLoop::run` method on its own thread, ready to run built streams.
     fn new_stream<M, S>(&self, model: M) -> stream::Builder<M, S> {
-        stream::Builder {
-            host: self.host.clone(),
-            model,
-            sample_rate: None,
-            channels: None,
-            frames_per_buffer: None,
-            device_buffer_size: None,
-            device: None,
-            sample_format: PhantomData,
-        }
+        stream::Builder::new(stream::BackendHost::Cpal(self.host.clone()), model)
     }
 }
 
@@ -0,0 +1,335 @@
+//! Items for detecting when the OS changes a device's sample rate under a running stream.
+//!
+//! CPAL does not notify streams when the sample rate of their device is changed externally (e.g.
+//! via an audio interface's control panel or the OS sound settings). Depending on the platform,
+//! the stream then either keeps running at the wrong rate, resulting in audio at the wrong pitch,
+//! or silently dies.
+//!
+//! A **SampleRateMonitor** records the device's default sample rate when it is created and
+//! periodically compares the device's current default config against it, yielding a
+//! **SampleRateChange** event on mismatch. Only changes are reported, so a stream that was
+//! deliberately created at a rate other than the device's default is not mistaken for one. A
+//! **RebuildingStream** goes one step further and automatically rebuilds the stream at the new
+//! sample rate.
+
+use crate::stream::{self, Stream};
+use crate::Device;
+use cpal::traits::DeviceTrait;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// Describes a change in a device's sample rate detected by a **SampleRateMonitor**.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SampleRateChange {
+    /// The sample rate with which the stream was created.
+    pub stream_sample_rate: u32,
+    /// The device's sample rate prior to the change.
+    pub previous_device_sample_rate: u32,
+    /// The sample rate at which the device is now running.
+    pub device_sample_rate: u32,
+}
+
+/// Periodically checks a device for changes to its sample rate.
+///
+/// Checks are performed on the calling thread during `poll`, at most once per interval. This
+/// makes it suitable for calling once per frame within an app's `update` function.
+pub struct SampleRateMonitor {
+    device: Device,
+    direction: Direction,
+    sample_rate: u32,
+    // The device's sample rate against which changes are detected.
+    device_sample_rate: Option<u32>,
+    interval: Duration,
+    last_check: Option<Instant>,
+}
+
+/// A stream that is automatically rebuilt in the case that its device's sample rate changes.
+pub struct RebuildingStream<M> {
+    stream: Stream<M>,
+    monitor: SampleRateMonitor,
+    rebuild: Box<RebuildFn<M>>,
+}
+
+/// The function used to rebuild a stream at a new sample rate.
+///
+/// This is given the model of the current stream along with the detected change and should build
+/// and return a new stream with the model, typically using the `device_sample_rate`. On failure,
+/// the model must be returned alongside the error so that it can be restored to the current
+/// stream. The stream builders' `try_build` methods return exactly this.
+pub type RebuildFn<M> =
+    dyn FnMut(M, SampleRateChange) -> Result<Stream<M>, (stream::BuildError, M)>;
+
+/// Errors that might occur while attempting to rebuild a stream.
+#[derive(Debug, Error)]
+pub enum RebuildError {
+    #[error("the stream could not be closed as other handles to it still exist")]
+    StreamInUse,
+    #[error("failed to rebuild the stream")]
+    Build(#[from] stream::BuildError),
+    #[error("failed to play the rebuilt stream")]
+    Play(#[from] cpal::PlayStreamError),
+}
+
+// Whether the monitored device is used for input or output.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+impl SampleRateMonitor {
+    /// The default interval between checks of the device's sample rate.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Monitor the given output device for changes to its sample rate.
+    ///
+    /// The device's current default sample rate is recorded as the rate from which changes are
+    /// detected. The given stream sample rate is reported alongside each change.
+    pub fn output(device: Device, stream_sample_rate: u32) -> Self {
+        Self::new(device, Direction::Output, stream_sample_rate)
+    }
+
+    /// Monitor the given input device for changes to its sample rate.
+    ///
+    /// The device's current default sample rate is recorded as the rate from which changes are
+    /// detected. The given stream sample rate is reported alongside each change.
+    pub fn input(device: Device, stream_sample_rate: u32) -> Self {
+        Self::new(device, Direction::Input, stream_sample_rate)
+    }
+
+    /// Monitor the given output device for changes to its sample rate while running the given
+    /// stream.
+    pub fn for_output_stream<M>(device: Device, stream: &Stream<M>) -> Self {
+        Self::output(device, stream.cpal_config().sample_rate.0)
+    }
+
+    /// Monitor the given input device for changes to its sample rate while running the given
+    /// stream.
+    pub fn for_input_stream<M>(device: Device, stream: &Stream<M>) -> Self {
+        Self::input(device, stream.cpal_config().sample_rate.0)
+    }
+
+    fn new(device: Device, direction: Direction, sample_rate: u32) -> Self {
+        let device_sample_rate = current_sample_rate(&device, direction);
+        SampleRateMonitor {
+            device,
+            direction,
+            sample_rate,
+            device_sample_rate,
+            interval: Self::DEFAULT_INTERVAL,
+            last_check: None,
+        }
+    }
+
+    /// Specify the minimum interval between checks of the device's sample rate.
+    ///
+    /// By default, this value is `SampleRateMonitor::DEFAULT_INTERVAL`.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// The monitored device.
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// The sample rate of the monitored stream.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Update the sample rate of the monitored stream, e.g. after rebuilding the stream in
+    /// response to a change.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// The device sample rate from which changes are detected.
+    ///
+    /// Returns `None` if the device's config could not be retrieved since the monitor was
+    /// created.
+    pub fn device_sample_rate(&self) -> Option<u32> {
+        self.device_sample_rate
+    }
+
+    /// Update the device sample rate from which changes are detected, e.g. after handling a
+    /// change.
+    pub fn set_device_sample_rate(&mut self, sample_rate: u32) {
+        self.device_sample_rate = Some(sample_rate);
+    }
+
+    /// Check the device for a change in sample rate if at least one interval has passed since the
+    /// last check.
+    ///
+    /// Returns `Some` for every check during which the device's sample rate differs from the
+    /// recorded device sample rate, until `set_device_sample_rate` is called with the new rate.
+    /// If the device's config cannot be retrieved (e.g. the device was disconnected), no change is
+    /// reported. If no device sample rate could be recorded when the monitor was created, the
+    /// first rate retrieved is recorded instead.
+    pub fn poll(&mut self) -> Option<SampleRateChange> {
+        let now = Instant::now();
+        if !is_due(self.last_check, now, self.interval) {
+            return None;
+        }
+        self.last_check = Some(now);
+        if self.device_sample_rate.is_none() {
+            self.device_sample_rate = current_sample_rate(&self.device, self.direction);
+            return None;
+        }
+        self.check()
+    }
+
+    /// Immediately check the device for a change in sample rate, regardless of the interval.
+    pub fn check(&self) -> Option<SampleRateChange> {
+        let previous_device_sample_rate = self.device_sample_rate?;
+        let device_sample_rate = current_sample_rate(&self.device, self.direction)?;
+        detect_change(
+            self.sample_rate,
+            previous_device_sample_rate,
+            device_sample_rate,
+        )
+    }
+}
+
+impl<M> RebuildingStream<M> {
+    /// Wrap the given stream so that it is rebuilt with `rebuild` whenever the given monitor
+    /// detects a change in sample rate.
+    ///
+    /// The given stream should be the only handle to the stream, as its model must be recovered
+    /// in order to rebuild it.
+    pub fn new<F>(stream: Stream<M>, mut monitor: SampleRateMonitor, rebuild: F) -> Self
+    where
+        F: 'static + FnMut(M, SampleRateChange) -> Result<Stream<M>, (stream::BuildError, M)>,
+    {
+        monitor.set_sample_rate(stream.cpal_config().sample_rate.0);
+        RebuildingStream {
+            stream,
+            monitor,
+            rebuild: Box::new(rebuild),
+        }
+    }
+
+    /// The current stream.
+    pub fn stream(&self) -> &Stream<M> {
+        &self.stream
+    }
+
+    /// The sample rate monitor.
+    pub fn monitor(&self) -> &SampleRateMonitor {
+        &self.monitor
+    }
+
+    /// Poll the monitor and rebuild the stream if the device's sample rate has changed.
+    ///
+    /// The current stream is kept until the new stream has been built. If building fails, the
+    /// model is restored to the current stream and the rebuild is attempted again on the next
+    /// poll that detects the change. The rebuilt stream is played if the previous stream was
+    /// playing. On success, returns the change that caused the stream to be rebuilt, if any.
+    pub fn poll(&mut self) -> Result<Option<SampleRateChange>, RebuildError> {
+        let change = match self.monitor.poll() {
+            None => return Ok(None),
+            Some(change) => change,
+        };
+        if !self.stream.is_unique() {
+            return Err(RebuildError::StreamInUse);
+        }
+        let was_playing = self.stream.is_playing();
+        let model = self.stream.take_model().expect("stream model was missing");
+        let stream = match (*self.rebuild)(model, change) {
+            Ok(stream) => stream,
+            Err((err, model)) => {
+                self.stream.restore_model(model);
+                return Err(RebuildError::Build(err));
+            }
+        };
+        // Only close the previous stream now that the new one exists.
+        drop(std::mem::replace(&mut self.stream, stream));
+        self.monitor
+            .set_sample_rate(self.stream.cpal_config().sample_rate.0);
+        self.monitor
+            .set_device_sample_rate(change.device_sample_rate);
+        if was_playing {
+            self.stream.play()?;
+        }
+        Ok(Some(change))
+    }
+}
+
+// The default sample rate of the device for the given direction, if it can be retrieved.
+fn current_sample_rate(device: &Device, direction: Direction) -> Option<u32> {
+    let config = match direction {
+        Direction::Input => device.device.default_input_config(),
+        Direction::Output => device.device.default_output_config(),
+    };
+    config.ok().map(|config| config.sample_rate().0)
+}
+
+// Whether or not at least one interval has passed since the last check, if any.
+fn is_due(last_check: Option<Instant>, now: Instant, interval: Duration) -> bool {
+    match last_check {
+        None => true,
+        Some(last) => now.duration_since(last) >= interval,
+    }
+}
+
+// Describe the change between the previous and current device sample rates, if any.
+fn detect_change(
+    stream_sample_rate: u32,
+    previous_device_sample_rate: u32,
+    device_sample_rate: u32,
+) -> Option<SampleRateChange> {
+    if device_sample_rate == previous_device_sample_rate {
+        return None;
+    }
+    Some(SampleRateChange {
+        stream_sample_rate,
+        previous_device_sample_rate,
+        device_sample_rate,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_poll_is_always_due() {
+        let interval = SampleRateMonitor::DEFAULT_INTERVAL;
+        assert!(is_due(None, Instant::now(), interval));
+    }
+
+    #[test]
+    fn polls_are_due_once_per_interval() {
+        let interval = Duration::from_millis(500);
+        let last = Instant::now();
+        assert!(!is_due(Some(last), last, interval));
+        assert!(!is_due(
+            Some(last),
+            last + Duration::from_millis(499),
+            interval
+        ));
+        assert!(is_due(Some(last), last + interval, interval));
+        assert!(is_due(Some(last), last + Duration::from_secs(2), interval));
+    }
+
+    #[test]
+    fn unchanged_rate_is_not_reported() {
+        assert_eq!(detect_change(44_100, 48_000, 48_000), None);
+    }
+
+    #[test]
+    fn changed_rate_is_reported_relative_to_the_device() {
+        // A stream deliberately created at a rate other than the device's default.
+        let change = detect_change(44_100, 48_000, 96_000).unwrap();
+        assert_eq!(change.stream_sample_rate, 44_100);
+        assert_eq!(change.previous_device_sample_rate, 48_000);
+        assert_eq!(change.device_sample_rate, 96_000);
+    }
+
+    #[test]
+    fn change_back_to_the_stream_rate_is_reported() {
+        let change = detect_change(44_100, 48_000, 44_100).unwrap();
+        assert_eq!(change.device_sample_rate, change.stream_sample_rate);
+    }
+}
@@ -0,0 +1,431 @@
+//! Control-rate parameters shared between the UI thread and the audio thread.
+//!
+//! A [**Param**](./struct.Param.html) holds an atomic target value that may be set from any
+//! thread without locking. The audio thread reads the target via a
+//! [**Smoother**](./struct.Smoother.html), which ramps towards the latest target over the
+//! parameter's smoothing time, avoiding the clicks caused by abrupt jumps in gain, frequency, etc.
+//! The shape of the ramp is described by a [**Ramp**](./trait.Ramp.html), e.g.
+//! [**Linear**](./struct.Linear.html) for gain or [**Exponential**](./struct.Exponential.html)
+//! for frequency.
+//!
+//! [**Params**](./struct.Params.html) is a simple registry of named parameters. Clones of a
+//! `Params` share the same underlying values, so one may live within the audio model while another
+//! is used by the UI.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A control-rate parameter with a target value and a smoothing time.
+///
+/// Cloning a `Param` produces a new handle to the same value.
+#[derive(Clone, Debug)]
+pub struct Param {
+    inner: Arc<ParamInner>,
+}
+
+#[derive(Debug)]
+struct ParamInner {
+    // The bits of the target value as an `f32`.
+    target: AtomicU32,
+    // The bits of the smoothing time in seconds as an `f32`.
+    smoothing_secs: AtomicU32,
+}
+
+/// Smooths a `Param` towards its target on the audio thread.
+///
+/// Each call to `next`, `next_block` or `fill` checks the latest target of the parameter. If the
+/// target has changed, a new ramp from the current value towards the target begins. The shape of
+/// the ramp is determined by `R`, which is `Linear` by default.
+#[derive(Clone, Debug)]
+pub struct Smoother<R = Linear> {
+    param: Param,
+    ramp: R,
+    current: f32,
+    // The value at which the current ramp began.
+    start: f32,
+    target: f32,
+    // The total length of the current ramp.
+    ramp_frames: usize,
+    remaining_frames: usize,
+}
+
+/// The shape of the ramp followed by a `Smoother` towards a new target.
+pub trait Ramp {
+    /// The value at the given `progress` through a ramp from `start` to `target`.
+    ///
+    /// `progress` is within the range `0.0..1.0`. The `Smoother` produces the `target` exactly
+    /// once the ramp completes.
+    fn interpolate(&self, start: f32, target: f32, progress: f32) -> f32;
+}
+
+/// A ramp that changes by an equal amount each frame, e.g. for gain.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Linear;
+
+/// A ramp that changes by an equal ratio each frame, e.g. for frequency.
+///
+/// Falls back to a linear ramp if `start` or `target` is zero or if their signs differ.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Exponential;
+
+/// A registry of named parameters.
+///
+/// Parameters should be added before the registry is cloned and shared with the audio thread.
+/// Parameters added to one clone afterwards are not visible to the others, though the values of
+/// all shared parameters remain in sync.
+#[derive(Clone, Debug, Default)]
+pub struct Params {
+    map: HashMap<String, Param>,
+}
+
+impl Param {
+    /// The smoothing time used by `Param::new`.
+    pub const DEFAULT_SMOOTHING: Duration = Duration::from_millis(20);
+
+    /// Create a new parameter with the given initial value.
+    ///
+    /// By default, the smoothing time is `DEFAULT_SMOOTHING`.
+    pub fn new(value: f32) -> Self {
+        Self::with_smoothing(value, Self::DEFAULT_SMOOTHING)
+    }
+
+    /// Create a new parameter with the given initial value and smoothing time.
+    pub fn with_smoothing(value: f32, smoothing: Duration) -> Self {
+        let inner = ParamInner {
+            target: AtomicU32::new(value.to_bits()),
+            smoothing_secs: AtomicU32::new(smoothing.as_secs_f32().to_bits()),
+        };
+        let inner = Arc::new(inner);
+        Param { inner }
+    }
+
+    /// The latest target value.
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.inner.target.load(Ordering::Relaxed))
+    }
+
+    /// Set the target value.
+    ///
+    /// Smoothers will ramp towards the new value over the smoothing time.
+    pub fn set(&self, value: f32) {
+        self.inner.target.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The duration over which changes to the target are smoothed.
+    pub fn smoothing(&self) -> Duration {
+        let secs = f32::from_bits(self.inner.smoothing_secs.load(Ordering::Relaxed));
+        Duration::from_secs_f32(secs)
+    }
+
+    /// Set the duration over which changes to the target are smoothed.
+    ///
+    /// Takes effect the next time the target changes.
+    pub fn set_smoothing(&self, smoothing: Duration) {
+        let bits = smoothing.as_secs_f32().to_bits();
+        self.inner.smoothing_secs.store(bits, Ordering::Relaxed);
+    }
+
+    /// Create a `Smoother` for use on the audio thread, starting at the current target.
+    pub fn smoother(&self) -> Smoother {
+        Smoother::new(self.clone())
+    }
+}
+
+impl Ramp for Linear {
+    fn interpolate(&self, start: f32, target: f32, progress: f32) -> f32 {
+        start + (target - start) * progress
+    }
+}
+
+impl Ramp for Exponential {
+    fn interpolate(&self, start: f32, target: f32, progress: f32) -> f32 {
+        let ratio = target / start;
+        if start == 0.0 || target == 0.0 || ratio < 0.0 {
+            return Linear.interpolate(start, target, progress);
+        }
+        start * ratio.powf(progress)
+    }
+}
+
+impl Smoother {
+    /// Create a new linear smoother for the given parameter, starting at its current target.
+    pub fn new(param: Param) -> Self {
+        Self::with_ramp(param, Linear)
+    }
+}
+
+impl<R> Smoother<R>
+where
+    R: Ramp,
+{
+    /// Create a new smoother for the given parameter with the given ramp shape, starting at its
+    /// current target.
+    pub fn with_ramp(param: Param, ramp: R) -> Self {
+        let current = param.get();
+        Smoother {
+            param,
+            ramp,
+            current,
+            start: current,
+            target: current,
+            ramp_frames: 0,
+            remaining_frames: 0,
+        }
+    }
+
+    /// The shape of the ramp towards new targets.
+    pub fn ramp(&self) -> &R {
+        &self.ramp
+    }
+
+    /// The parameter that is being smoothed.
+    pub fn param(&self) -> &Param {
+        &self.param
+    }
+
+    /// The current smoothed value.
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Whether or not the smoother is currently ramping towards a new target.
+    pub fn is_smoothing(&self) -> bool {
+        self.remaining_frames > 0
+    }
+
+    /// Jump directly to the latest target, skipping any smoothing.
+    pub fn reset(&mut self) {
+        self.current = self.param.get();
+        self.target = self.current;
+        self.remaining_frames = 0;
+    }
+
+    /// Advance the smoother by a single frame, returning the smoothed value.
+    pub fn next(&mut self, sample_rate: u32) -> f32 {
+        self.next_block(sample_rate, 1)
+    }
+
+    /// Advance the smoother by a block of `frames`, returning the smoothed value at the end of
+    /// the block.
+    ///
+    /// This is useful for parameters that only need updating once per buffer.
+    pub fn next_block(&mut self, sample_rate: u32, frames: usize) -> f32 {
+        self.update_target(sample_rate);
+        let frames = std::cmp::min(frames, self.remaining_frames);
+        self.advance(frames);
+        self.current
+    }
+
+    /// Fill the given slice with one smoothed value per frame.
+    pub fn fill(&mut self, sample_rate: u32, values: &mut [f32]) {
+        self.update_target(sample_rate);
+        for value in values {
+            self.advance(std::cmp::min(1, self.remaining_frames));
+            *value = self.current;
+        }
+    }
+
+    // Begin a new ramp if the parameter's target has changed.
+    fn update_target(&mut self, sample_rate: u32) {
+        let target = self.param.get();
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        let secs = self.param.smoothing().as_secs_f32();
+        let frames = (secs * sample_rate as f32).round() as usize;
+        self.start = self.current;
+        self.ramp_frames = frames;
+        self.remaining_frames = frames;
+        if frames == 0 {
+            self.current = target;
+        }
+    }
+
+    // Step the ramp forward by the given number of frames.
+    fn advance(&mut self, frames: usize) {
+        if frames == 0 {
+            return;
+        }
+        self.remaining_frames -= frames;
+        if self.remaining_frames == 0 {
+            self.current = self.target;
+        } else {
+            let elapsed = self.ramp_frames - self.remaining_frames;
+            let progress = elapsed as f32 / self.ramp_frames as f32;
+            self.current = self.ramp.interpolate(self.start, self.target, progress);
+        }
+    }
+}
+
+impl Params {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a parameter with the given name and initial value, returning a handle to it.
+    ///
+    /// Replaces any existing parameter with the same name.
+    pub fn add(&mut self, name: impl Into<String>, value: f32) -> Param {
+        let param = Param::new(value);
+        self.insert(name, param.clone());
+        param
+    }
+
+    /// Insert an existing parameter with the given name.
+    ///
+    /// Returns the parameter previously registered under the name, if any.
+    pub fn insert(&mut self, name: impl Into<String>, param: Param) -> Option<Param> {
+        self.map.insert(name.into(), param)
+    }
+
+    /// The parameter with the given name.
+    pub fn get(&self, name: &str) -> Option<&Param> {
+        self.map.get(name)
+    }
+
+    /// Set the target of the parameter with the given name.
+    ///
+    /// Returns `false` if there is no parameter with the given name.
+    pub fn set(&self, name: &str, value: f32) -> bool {
+        match self.map.get(name) {
+            Some(param) => {
+                param.set(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Create a smoother for the parameter with the given name.
+    pub fn smoother(&self, name: &str) -> Option<Smoother> {
+        self.get(name).map(Param::smoother)
+    }
+
+    /// Iterate over all parameters and their names.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Param)> {
+        self.map.iter().map(|(name, param)| (&name[..], param))
+    }
+
+    /// The number of registered parameters.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether or not the registry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 1_000;
+
+    // A parameter whose ramps last 10 frames at `SAMPLE_RATE`.
+    fn param(value: f32) -> Param {
+        Param::with_smoothing(value, Duration::from_millis(10))
+    }
+
+    #[test]
+    fn ramp_lasts_the_smoothing_time() {
+        let param = param(0.0);
+        let mut smoother = param.smoother();
+        param.set(1.0);
+        for _ in 0..9 {
+            smoother.next(SAMPLE_RATE);
+            assert!(smoother.is_smoothing());
+            assert!(smoother.current() < 1.0);
+        }
+        assert_eq!(smoother.next(SAMPLE_RATE), 1.0);
+        assert!(!smoother.is_smoothing());
+    }
+
+    #[test]
+    fn settles_on_the_target() {
+        let param = param(0.25);
+        let mut smoother = param.smoother();
+        param.set(-0.75);
+        let mut values = [0.0; 32];
+        smoother.fill(SAMPLE_RATE, &mut values);
+        assert!(values[10..].iter().all(|&v| v == -0.75));
+        assert_eq!(smoother.next_block(SAMPLE_RATE, 64), -0.75);
+    }
+
+    #[test]
+    fn linear_ramp_steps_evenly() {
+        let param = param(0.0);
+        let mut smoother = param.smoother();
+        param.set(1.0);
+        let mut values = [0.0; 10];
+        smoother.fill(SAMPLE_RATE, &mut values);
+        for (i, &v) in values.iter().enumerate() {
+            assert!((v - (i + 1) as f32 / 10.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn exponential_ramp_steps_by_ratio() {
+        let param = param(1.0);
+        let mut smoother = Smoother::with_ramp(param.clone(), Exponential);
+        param.set(100.0);
+        assert!((smoother.next_block(SAMPLE_RATE, 5) - 10.0).abs() < 1e-3);
+        assert_eq!(smoother.next_block(SAMPLE_RATE, 5), 100.0);
+    }
+
+    #[test]
+    fn exponential_ramp_through_zero_is_linear() {
+        assert_eq!(Exponential.interpolate(-1.0, 1.0, 0.5), 0.0);
+        assert_eq!(Exponential.interpolate(0.0, 1.0, 0.25), 0.25);
+    }
+
+    #[test]
+    fn block_advances_by_its_length() {
+        let param = param(0.0);
+        let mut smoother = param.smoother();
+        param.set(1.0);
+        assert!((smoother.next_block(SAMPLE_RATE, 4) - 0.4).abs() < 1e-6);
+        assert!(smoother.is_smoothing());
+    }
+
+    #[test]
+    fn new_target_ramps_from_the_current_value() {
+        let param = param(0.0);
+        let mut smoother = param.smoother();
+        param.set(1.0);
+        smoother.next_block(SAMPLE_RATE, 5);
+        param.set(0.0);
+        for _ in 0..9 {
+            smoother.next(SAMPLE_RATE);
+            assert!(smoother.is_smoothing());
+        }
+        let v = smoother.current();
+        assert!(v > 0.0 && v < 0.5);
+        assert_eq!(smoother.next(SAMPLE_RATE), 0.0);
+    }
+
+    #[test]
+    fn zero_smoothing_jumps_to_the_target() {
+        let param = Param::with_smoothing(0.0, Duration::from_secs(0));
+        let mut smoother = param.smoother();
+        param.set(1.0);
+        assert_eq!(smoother.next(SAMPLE_RATE), 1.0);
+        assert!(!smoother.is_smoothing());
+    }
+
+    #[test]
+    fn reset_skips_the_ramp() {
+        let param = param(0.0);
+        let mut smoother = param.smoother();
+        param.set(1.0);
+        smoother.next(SAMPLE_RATE);
+        smoother.reset();
+        assert_eq!(smoother.current(), 1.0);
+        assert!(!smoother.is_smoothing());
+    }
+}
@@ -154,4 +154,9 @@ where
 
         model
     }
+
+    // Discard any samples that were rendered but not yet written to output.
+    pub(crate) fn reset(&mut self) {
+        self.pending_range = None;
+    }
 }
@@ -0,0 +1,152 @@
+//! Adapters between audio streams and [**dasp**](https://docs.rs/dasp) signals.
+//!
+//! - [**fill_buffer**](./fn.fill_buffer.html) drives an output stream's render function with any
+//!   `dasp_signal::Signal`.
+//! - [**input_signal**](./fn.input_signal.html) creates a queue whose writer is fed by an input
+//!   stream's capture function and whose reader may be consumed as a `dasp_signal::Signal`.
+
+use crate::Buffer;
+use dasp_frame::Frame;
+use dasp_sample::{FromSample, Sample};
+use dasp_signal::Signal;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Writes frames captured by an input stream into the queue of an `InputSignal`.
+///
+/// Call `write` from within the input stream's capture function.
+pub struct SignalWriter<F> {
+    queue: Arc<Queue<F>>,
+}
+
+/// A `dasp_signal::Signal` yielding the frames captured by an input stream.
+///
+/// If the queue runs dry (i.e. the signal is consumed faster than the input stream captures), the
+/// signal yields `Frame::EQUILIBRIUM` and counts an underrun.
+pub struct InputSignal<F> {
+    queue: Arc<Queue<F>>,
+    frames: VecDeque<F>,
+    underruns: usize,
+}
+
+// The queue of captured frames shared between the writer and the signal.
+struct Queue<F> {
+    frames: Mutex<VecDeque<F>>,
+    capacity: usize,
+}
+
+/// Fill the given output buffer with frames yielded by the given signal.
+///
+/// Signal channels beyond the number of buffer channels are ignored, while any remaining buffer
+/// channels are filled with equilibrium.
+///
+/// This is intended to be called from within an output stream's render function, e.g.
+///
+/// ```ignore
+/// fn audio(model: &mut Audio, buffer: &mut Buffer) {
+///     nannou_audio::signal::fill_buffer(&mut model.signal, buffer);
+/// }
+/// ```
+pub fn fill_buffer<G, S>(signal: &mut G, buffer: &mut Buffer<S>)
+where
+    G: Signal,
+    S: Sample + FromSample<<G::Frame as Frame>::Sample>,
+{
+    for frame in buffer.frames_mut() {
+        let signal_frame = signal.next();
+        for (i, sample) in frame.iter_mut().enumerate() {
+            *sample = match signal_frame.channel(i) {
+                Some(&s) => s.to_sample(),
+                None => S::EQUILIBRIUM,
+            };
+        }
+    }
+}
+
+/// Create a queue for consuming input stream buffers as a `dasp_signal::Signal`.
+///
+/// The queue holds at most `capacity` frames. If the writer gets ahead of the signal by more than
+/// this, the oldest frames are dropped to keep latency bounded.
+pub fn input_signal<F>(capacity: usize) -> (SignalWriter<F>, InputSignal<F>)
+where
+    F: Frame,
+{
+    let queue = Arc::new(Queue {
+        frames: Mutex::new(VecDeque::with_capacity(capacity)),
+        capacity,
+    });
+    let writer = SignalWriter {
+        queue: queue.clone(),
+    };
+    let signal = InputSignal {
+        queue,
+        frames: VecDeque::with_capacity(capacity),
+        underruns: 0,
+    };
+    (writer, signal)
+}
+
+impl<F> SignalWriter<F>
+where
+    F: Frame,
+{
+    /// Write all frames of the given captured buffer to the queue.
+    ///
+    /// Buffer channels beyond the number of frame channels are ignored, while any remaining frame
+    /// channels are filled with equilibrium.
+    pub fn write<S>(&self, buffer: &Buffer<S>)
+    where
+        S: Sample,
+        F::Sample: FromSample<S>,
+    {
+        let mut frames = match self.queue.frames.lock() {
+            Ok(frames) => frames,
+            Err(_) => return,
+        };
+        for frame in buffer.frames() {
+            let frame = F::from_fn(|i| match frame.get(i) {
+                Some(&s) => s.to_sample(),
+                None => <F::Sample as Sample>::EQUILIBRIUM,
+            });
+            frames.push_back(frame);
+        }
+        let excess = frames.len().saturating_sub(self.queue.capacity);
+        frames.drain(..excess);
+    }
+}
+
+impl<F> InputSignal<F> {
+    /// The number of frames yielded as equilibrium due to the queue running dry.
+    pub fn underruns(&self) -> usize {
+        self.underruns
+    }
+
+    /// The number of captured frames ready to be yielded.
+    pub fn available(&self) -> usize {
+        let queued = self.queue.frames.lock().map(|f| f.len()).unwrap_or(0);
+        self.frames.len() + queued
+    }
+}
+
+impl<F> Signal for InputSignal<F>
+where
+    F: Frame,
+{
+    type Frame = F;
+
+    fn next(&mut self) -> Self::Frame {
+        // Take all queued frames at once to avoid locking for every frame.
+        if self.frames.is_empty() {
+            if let Ok(mut queued) = self.queue.frames.lock() {
+                std::mem::swap(&mut self.frames, &mut *queued);
+            }
+        }
+        match self.frames.pop_front() {
+            Some(frame) => frame,
+            None => {
+                self.underruns += 1;
+                F::EQUILIBRIUM
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! Binaural decoding of B-format for headphone listening.
+//!
+//! The sound field is decoded to a cube of virtual speakers, each of which is then rendered to
+//! both ears through a static spherical head model (Brown & Duda, 1998). The model approximates
+//! the interaural time difference with a per-ear delay and the acoustic shadow of the head with a
+//! one-pole, one-zero filter, so no measured HRTF data set is required.
+
+use super::{dot, layout, Decoder, Direction, Weighting, CHANNELS};
+use dasp_sample::Sample;
+
+// The speed of sound in metres per second.
+const SPEED_OF_SOUND: f32 = 343.0;
+// The head shadow filter's amplitude at high frequencies for a source directly opposite the ear.
+const ALPHA_MIN: f32 = 0.1;
+// The angle of incidence at which the head shadow is deepest.
+const THETA_MIN: f32 = 150.0 * std::f32::consts::PI / 180.0;
+
+/// Decodes B-format to interleaved stereo for headphones.
+#[derive(Clone, Debug)]
+pub struct BinauralDecoder {
+    decoder: Decoder,
+    head_radius: f32,
+    sample_rate: u32,
+    // The recent feeds of each virtual speaker, each used as a ring buffer.
+    delay_lines: Vec<Vec<f32>>,
+    // The mask wrapping indices into each delay line.
+    delay_mask: usize,
+    // The index of the next write within each delay line.
+    write_ix: usize,
+    // The left and right ear rendering of each virtual speaker.
+    ears: Vec<[Ear; 2]>,
+}
+
+// The rendering of a single virtual speaker to a single ear.
+#[derive(Copy, Clone, Debug)]
+struct Ear {
+    // The interaural delay in whole frames and the remaining fraction of a frame.
+    delay_frames: usize,
+    delay_fract: f32,
+    // The head shadow filter coefficients.
+    b0: f32,
+    b1: f32,
+    a1: f32,
+    // The previous filter input and output.
+    x1: f32,
+    y1: f32,
+}
+
+impl BinauralDecoder {
+    /// The radius of the modelled head in metres used by `BinauralDecoder::new`.
+    pub const DEFAULT_HEAD_RADIUS: f32 = 0.0875;
+
+    /// Create a binaural decoder for a stream with the given sample rate.
+    ///
+    /// **Panic!**s if the sample rate is `0`.
+    pub fn new(sample_rate: u32) -> Self {
+        Self::with_head_radius(sample_rate, Self::DEFAULT_HEAD_RADIUS)
+    }
+
+    /// Create a binaural decoder modelling a head with the given radius in metres.
+    ///
+    /// Larger heads produce greater interaural time differences and a lower head shadow cut-off.
+    ///
+    /// **Panic!**s if the sample rate is `0` or the head radius is not positive.
+    pub fn with_head_radius(sample_rate: u32, head_radius: f32) -> Self {
+        assert!(sample_rate > 0, "the sample rate must be greater than zero");
+        assert!(
+            head_radius > 0.0,
+            "the head radius must be greater than zero"
+        );
+        let decoder = Decoder::with_weighting(&layout::cube(), Weighting::MaxRe);
+        let rate = sample_rate as f32;
+        let left = Direction::horizontal(std::f32::consts::FRAC_PI_2).to_vector();
+        let right = Direction::horizontal(-std::f32::consts::FRAC_PI_2).to_vector();
+        let ears: Vec<[Ear; 2]> = decoder
+            .speakers()
+            .iter()
+            .map(|speaker| {
+                let v = speaker.to_vector();
+                let theta = |ear: [f32; 3]| dot3(v, ear).max(-1.0).min(1.0).acos();
+                [
+                    Ear::new(theta(left), rate, head_radius),
+                    Ear::new(theta(right), rate, head_radius),
+                ]
+            })
+            .collect();
+        let max_delay = ears
+            .iter()
+            .flat_map(|ears| ears.iter().map(|ear| ear.delay_frames + 1))
+            .max()
+            .unwrap_or(0);
+        let delay_len = (max_delay + 1).next_power_of_two();
+        let delay_lines = vec![vec![0.0; delay_len]; ears.len()];
+        BinauralDecoder {
+            decoder,
+            head_radius,
+            sample_rate,
+            delay_lines,
+            delay_mask: delay_len - 1,
+            write_ix: 0,
+            ears,
+        }
+    }
+
+    /// The sample rate for which the decoder was created.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The radius of the modelled head in metres.
+    pub fn head_radius(&self) -> f32 {
+        self.head_radius
+    }
+
+    /// The virtual speaker layout from which the ear signals are rendered.
+    pub fn virtual_speakers(&self) -> &[Direction] {
+        self.decoder.speakers()
+    }
+
+    /// Clear all delayed and filtered signal, e.g. after a discontinuity in the input.
+    pub fn reset(&mut self) {
+        for line in &mut self.delay_lines {
+            line.iter_mut().for_each(|s| *s = 0.0);
+        }
+        for ear in self.ears.iter_mut().flat_map(|ears| ears.iter_mut()) {
+            ear.x1 = 0.0;
+            ear.y1 = 0.0;
+        }
+        self.write_ix = 0;
+    }
+
+    /// Decode the given interleaved B-format buffer to the given interleaved stereo buffer.
+    ///
+    /// The output is overwritten, left channel first.
+    ///
+    /// **Panic!**s if the output does not contain one stereo frame for each B-format frame.
+    pub fn decode<S>(&mut self, input: &[f32], output: &mut [S])
+    where
+        S: Sample,
+    {
+        assert_eq!(
+            input.len() % CHANNELS,
+            0,
+            "the B-format buffer length must be a multiple of `CHANNELS`"
+        );
+        assert_eq!(
+            input.len() / CHANNELS * 2,
+            output.len(),
+            "the output must contain one stereo frame for each B-format frame"
+        );
+        let mask = self.delay_mask;
+        let frames = input.chunks_exact(CHANNELS).zip(output.chunks_exact_mut(2));
+        for (bformat, out_frame) in frames {
+            let mut sums = [0.0f32; 2];
+            let speakers = self.decoder.matrix.iter().zip(&mut self.delay_lines);
+            for ((row, line), ears) in speakers.zip(&mut self.ears) {
+                line[self.write_ix] = dot(row, bformat);
+                for (sum, ear) in sums.iter_mut().zip(ears.iter_mut()) {
+                    let a = line[self.write_ix.wrapping_sub(ear.delay_frames) & mask];
+                    let b = line[self.write_ix.wrapping_sub(ear.delay_frames + 1) & mask];
+                    *sum += ear.filter(a + (b - a) * ear.delay_fract);
+                }
+            }
+            for (out, &sum) in out_frame.iter_mut().zip(&sums) {
+                let s: S::Float = sum.to_sample();
+                *out = s.to_sample();
+            }
+            self.write_ix = (self.write_ix + 1) & mask;
+        }
+    }
+}
+
+impl Ear {
+    // The rendering of a source at the given angle of incidence in radians from the ear axis.
+    fn new(theta: f32, sample_rate: f32, head_radius: f32) -> Self {
+        // Woodworth's interaural delay, offset such that it is zero for a source at the ear.
+        let radius_time = head_radius / SPEED_OF_SOUND;
+        let delay_secs = if theta < std::f32::consts::FRAC_PI_2 {
+            radius_time * (1.0 - theta.cos())
+        } else {
+            radius_time * (1.0 + theta - std::f32::consts::FRAC_PI_2)
+        };
+        let delay = delay_secs * sample_rate;
+
+        // The head shadow `(1 + alpha s / 2w0) / (1 + s / 2w0)` via the bilinear transform.
+        let alpha = (1.0 + ALPHA_MIN / 2.0)
+            + (1.0 - ALPHA_MIN / 2.0) * (theta / THETA_MIN * std::f32::consts::PI).cos();
+        let w0 = SPEED_OF_SOUND / head_radius;
+        let k = 2.0 * sample_rate;
+        let a0 = 2.0 * w0 + k;
+        Ear {
+            delay_frames: delay.floor() as usize,
+            delay_fract: delay.fract(),
+            b0: (2.0 * w0 + alpha * k) / a0,
+            b1: (2.0 * w0 - alpha * k) / a0,
+            a1: (2.0 * w0 - k) / a0,
+            x1: 0.0,
+            y1: 0.0,
+        }
+    }
+
+    fn filter(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 - self.a1 * self.y1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+fn dot3(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
@@ -0,0 +1,61 @@
+//! Common speaker layouts for use with a `Decoder`.
+//!
+//! Each function returns the speakers in the order of their output channels.
+
+use super::Direction;
+
+/// A stereo pair at 30 degrees either side of the front, left first.
+pub fn stereo() -> Vec<Direction> {
+    vec![
+        Direction::from_degrees(30.0, 0.0),
+        Direction::from_degrees(-30.0, 0.0),
+    ]
+}
+
+/// Four speakers at the corners of a square: front left, front right, rear left and rear right.
+pub fn quad() -> Vec<Direction> {
+    vec![
+        Direction::from_degrees(45.0, 0.0),
+        Direction::from_degrees(-45.0, 0.0),
+        Direction::from_degrees(135.0, 0.0),
+        Direction::from_degrees(-135.0, 0.0),
+    ]
+}
+
+/// The five full-range speakers of a 5.1 layout in the order L, R, C, Ls, Rs.
+///
+/// The LFE channel is not included as it carries no directional information.
+pub fn five_point_zero() -> Vec<Direction> {
+    vec![
+        Direction::from_degrees(30.0, 0.0),
+        Direction::from_degrees(-30.0, 0.0),
+        Direction::from_degrees(0.0, 0.0),
+        Direction::from_degrees(110.0, 0.0),
+        Direction::from_degrees(-110.0, 0.0),
+    ]
+}
+
+/// `n` speakers spaced evenly around the horizontal plane, anti-clockwise from the front.
+///
+/// Use an `offset` of half the spacing, i.e. `PI / n`, for a pair of speakers straddling the
+/// front rather than a single speaker straight ahead.
+pub fn ring(n: usize, offset: f32) -> Vec<Direction> {
+    let step = std::f32::consts::PI * 2.0 / n as f32;
+    (0..n)
+        .map(|i| Direction::horizontal(offset + i as f32 * step))
+        .collect()
+}
+
+/// Eight speakers at the corners of a cube, the upper four followed by the lower four, each
+/// anti-clockwise from front left.
+pub fn cube() -> Vec<Direction> {
+    const AZIMUTHS: [f32; 4] = [45.0, 135.0, -135.0, -45.0];
+    let elevation = (1.0f32 / 2.0f32.sqrt()).atan();
+    let mut speakers = Vec::with_capacity(8);
+    for &el in &[elevation, -elevation] {
+        for az in AZIMUTHS.iter() {
+            speakers.push(Direction::new(az.to_radians(), el));
+        }
+    }
+    speakers
+}
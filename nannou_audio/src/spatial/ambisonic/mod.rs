@@ -0,0 +1,380 @@
+//! First-order ambisonic (FOA) encoding and decoding.
+//!
+//! An [**Encoder**](./struct.Encoder.html) pans a mono source to a direction within a
+//! four-channel B-format sound field. Any number of sources may be encoded into the same B-format
+//! buffer, which may then be decoded to an arbitrary speaker layout via a
+//! [**Decoder**](./struct.Decoder.html), or to headphones via a
+//! [**BinauralDecoder**](./struct.BinauralDecoder.html) (requires the `binaural` feature).
+//!
+//! B-format buffers are interleaved frames of `CHANNELS` samples in the AmbiX convention, i.e. ACN
+//! channel ordering (W, Y, Z, X) with SN3D normalisation. Use `convert_format` to exchange
+//! B-format with software using the older Furse-Malham convention.
+//!
+//! All processing is allocation free, so encoders and decoders may be used directly within an
+//! output stream's render function.
+//!
+//! Directions are given in radians. Azimuth is measured anti-clockwise from straight ahead, so
+//! that positive values are to the left, and elevation is measured upwards from the horizontal
+//! plane.
+
+use dasp_sample::Sample;
+
+#[cfg(feature = "binaural")]
+pub use self::binaural::BinauralDecoder;
+
+#[cfg(feature = "binaural")]
+mod binaural;
+pub mod layout;
+
+/// The number of channels within each frame of a first-order B-format buffer.
+pub const CHANNELS: usize = 4;
+
+// The index of each component within an AmbiX frame.
+const W: usize = 0;
+const Y: usize = 1;
+const Z: usize = 2;
+const X: usize = 3;
+
+// Speakers with an elevation smaller than this are considered to lie on the horizontal plane.
+const PLANAR_EPSILON: f32 = 1e-3;
+
+/// The direction of a source or speaker relative to the listener.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Direction {
+    /// Radians anti-clockwise from straight ahead.
+    pub azimuth: f32,
+    /// Radians upwards from the horizontal plane.
+    pub elevation: f32,
+}
+
+/// The channel ordering and normalisation of a B-format buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// ACN channel ordering (W, Y, Z, X) with SN3D normalisation, as used throughout this module.
+    AmbiX,
+    /// Furse-Malham channel ordering (W, X, Y, Z) with W attenuated by 3dB.
+    FuMa,
+}
+
+/// The weighting of the first-order components applied by a decoder.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Weighting {
+    /// The plain sampling decoder. Sharpest localisation for a listener at the centre of the
+    /// layout.
+    Basic,
+    /// Maximises the energy vector, widening the sweet spot at the cost of some sharpness.
+    MaxRe,
+    /// Ensures no speaker is driven out of phase with the source, best suited to large audiences
+    /// spread throughout the layout.
+    InPhase,
+}
+
+/// Encodes a mono source at a direction into B-format.
+#[derive(Clone, Debug)]
+pub struct Encoder {
+    direction: Direction,
+    gain: f32,
+    // The coefficients for the current direction and gain.
+    target: [f32; CHANNELS],
+    // The coefficients applied at the end of the last encoded buffer.
+    current: [f32; CHANNELS],
+}
+
+/// Decodes B-format to a layout of speakers.
+#[derive(Clone, Debug)]
+pub struct Decoder {
+    speakers: Vec<Direction>,
+    weighting: Weighting,
+    planar: bool,
+    // The gain applied to each component of a frame for each speaker.
+    matrix: Vec<[f32; CHANNELS]>,
+}
+
+impl Direction {
+    /// Straight ahead of the listener.
+    pub const FRONT: Self = Direction {
+        azimuth: 0.0,
+        elevation: 0.0,
+    };
+
+    /// A direction with the given azimuth and elevation in radians.
+    pub fn new(azimuth: f32, elevation: f32) -> Self {
+        Direction { azimuth, elevation }
+    }
+
+    /// A direction on the horizontal plane with the given azimuth in radians.
+    pub fn horizontal(azimuth: f32) -> Self {
+        Self::new(azimuth, 0.0)
+    }
+
+    /// A direction with the given azimuth and elevation in degrees.
+    pub fn from_degrees(azimuth: f32, elevation: f32) -> Self {
+        Self::new(azimuth.to_radians(), elevation.to_radians())
+    }
+
+    /// The direction of the given vector, where `x` points ahead, `y` to the left and `z` up.
+    ///
+    /// Returns `Direction::FRONT` for the zero vector.
+    pub fn from_vector([x, y, z]: [f32; 3]) -> Self {
+        if x == 0.0 && y == 0.0 && z == 0.0 {
+            return Self::FRONT;
+        }
+        let azimuth = y.atan2(x);
+        let elevation = z.atan2((x * x + y * y).sqrt());
+        Self::new(azimuth, elevation)
+    }
+
+    /// The unit vector pointing in this direction, where `x` points ahead, `y` to the left and `z`
+    /// up.
+    pub fn to_vector(&self) -> [f32; 3] {
+        let (sin_az, cos_az) = self.azimuth.sin_cos();
+        let (sin_el, cos_el) = self.elevation.sin_cos();
+        [cos_az * cos_el, sin_az * cos_el, sin_el]
+    }
+
+    /// The AmbiX B-format coefficients of a unit amplitude source in this direction.
+    pub fn coefficients(&self) -> [f32; CHANNELS] {
+        let [x, y, z] = self.to_vector();
+        let mut coeffs = [0.0; CHANNELS];
+        coeffs[W] = 1.0;
+        coeffs[Y] = y;
+        coeffs[Z] = z;
+        coeffs[X] = x;
+        coeffs
+    }
+}
+
+impl Encoder {
+    /// Create an encoder for a source in the given direction.
+    pub fn new(direction: Direction) -> Self {
+        let target = direction.coefficients();
+        Encoder {
+            direction,
+            gain: 1.0,
+            target,
+            current: target,
+        }
+    }
+
+    /// The direction of the source.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The amplitude applied to the source.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Move the source to the given direction.
+    ///
+    /// The move is interpolated across the next encoded buffer in order to avoid clicks.
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+        self.update_target();
+    }
+
+    /// Set the amplitude applied to the source.
+    ///
+    /// The change is interpolated across the next encoded buffer in order to avoid clicks.
+    ///
+    /// By default, this value is `1.0`.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+        self.update_target();
+    }
+
+    /// Encode the given mono samples, adding the result to the given interleaved B-format buffer.
+    ///
+    /// The output is added to rather than overwritten so that many sources may be encoded into
+    /// the same sound field. Zero the buffer before encoding the first source.
+    ///
+    /// **Panic!**s if the output does not have `CHANNELS` samples for each input sample.
+    pub fn encode<S>(&mut self, input: &[S], output: &mut [f32])
+    where
+        S: Sample,
+    {
+        assert_eq!(
+            input.len() * CHANNELS,
+            output.len(),
+            "the output must contain one B-format frame for each input sample"
+        );
+        let interpolate = self.current != self.target;
+        let n_frames = input.len() as f32;
+        for (i, (sample, frame)) in input
+            .iter()
+            .zip(output.chunks_exact_mut(CHANNELS))
+            .enumerate()
+        {
+            let s = sample.to_float_sample().to_sample::<f32>();
+            if interpolate {
+                let t = (i + 1) as f32 / n_frames;
+                for ((out, &cur), &target) in frame.iter_mut().zip(&self.current).zip(&self.target)
+                {
+                    *out += s * (cur + (target - cur) * t);
+                }
+            } else {
+                for (out, &coeff) in frame.iter_mut().zip(&self.target) {
+                    *out += s * coeff;
+                }
+            }
+        }
+        if !input.is_empty() {
+            self.current = self.target;
+        }
+    }
+
+    fn update_target(&mut self) {
+        let mut target = self.direction.coefficients();
+        target.iter_mut().for_each(|c| *c *= self.gain);
+        self.target = target;
+    }
+}
+
+impl Decoder {
+    /// Create a decoder for the given speaker layout with `Weighting::MaxRe`.
+    ///
+    /// Speakers are fed in the order given, one output channel each. See the `layout` module for
+    /// common layouts.
+    ///
+    /// **Panic!**s if there are no speakers.
+    pub fn new(speakers: &[Direction]) -> Self {
+        Self::with_weighting(speakers, Weighting::MaxRe)
+    }
+
+    /// Create a decoder for the given speaker layout with the given weighting.
+    ///
+    /// If all speakers lie on the horizontal plane, the decoder ignores the height component (Z)
+    /// and distributes energy around the circle rather than the sphere.
+    ///
+    /// **Panic!**s if there are no speakers.
+    pub fn with_weighting(speakers: &[Direction], weighting: Weighting) -> Self {
+        assert!(
+            !speakers.is_empty(),
+            "a decoder requires at least one speaker"
+        );
+        let planar = speakers.iter().all(|s| s.elevation.abs() < PLANAR_EPSILON);
+        let n = speakers.len() as f32;
+        // The normalisation of the first-order components relative to SN3D.
+        let (order_scale, first_order_weight) = match (planar, weighting) {
+            (false, Weighting::Basic) => (3.0, 1.0),
+            (false, Weighting::MaxRe) => (3.0, 1.0 / 3.0f32.sqrt()),
+            (false, Weighting::InPhase) => (3.0, 1.0 / 3.0),
+            (true, Weighting::Basic) => (2.0, 1.0),
+            (true, Weighting::MaxRe) => (2.0, std::f32::consts::FRAC_1_SQRT_2),
+            (true, Weighting::InPhase) => (2.0, 0.5),
+        };
+        let k = order_scale * first_order_weight;
+        let matrix = speakers
+            .iter()
+            .map(|speaker| {
+                let [x, y, z] = speaker.to_vector();
+                let mut row = [0.0; CHANNELS];
+                row[W] = 1.0 / n;
+                row[Y] = k * y / n;
+                row[Z] = if planar { 0.0 } else { k * z / n };
+                row[X] = k * x / n;
+                row
+            })
+            .collect();
+        Decoder {
+            speakers: speakers.to_vec(),
+            weighting,
+            planar,
+            matrix,
+        }
+    }
+
+    /// The speaker layout, in the order of the output channels.
+    pub fn speakers(&self) -> &[Direction] {
+        &self.speakers
+    }
+
+    /// The number of output channels, one for each speaker.
+    pub fn channels(&self) -> usize {
+        self.speakers.len()
+    }
+
+    /// The weighting applied by the decoder.
+    pub fn weighting(&self) -> Weighting {
+        self.weighting
+    }
+
+    /// Whether or not the layout lies on the horizontal plane, in which case height is ignored.
+    pub fn is_planar(&self) -> bool {
+        self.planar
+    }
+
+    /// The gain applied to each AmbiX component of a frame for the speaker at the given index.
+    ///
+    /// **Panic!**s if there is no speaker at the given index.
+    pub fn gains(&self, speaker: usize) -> [f32; CHANNELS] {
+        self.matrix[speaker]
+    }
+
+    /// Decode the given interleaved B-format buffer to the given interleaved speaker buffer.
+    ///
+    /// The output is overwritten.
+    ///
+    /// **Panic!**s if the output does not contain one frame of `channels()` samples for each
+    /// B-format frame.
+    pub fn decode<S>(&self, input: &[f32], output: &mut [S])
+    where
+        S: Sample,
+    {
+        let n_channels = self.channels();
+        assert_eq!(
+            input.len() % CHANNELS,
+            0,
+            "the B-format buffer length must be a multiple of `CHANNELS`"
+        );
+        assert_eq!(
+            input.len() / CHANNELS * n_channels,
+            output.len(),
+            "the output must contain one frame for each B-format frame"
+        );
+        let frames = input
+            .chunks_exact(CHANNELS)
+            .zip(output.chunks_exact_mut(n_channels));
+        for (bformat, out_frame) in frames {
+            for (out, row) in out_frame.iter_mut().zip(&self.matrix) {
+                let s: S::Float = dot(row, bformat).to_sample();
+                *out = s.to_sample();
+            }
+        }
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new(Direction::FRONT)
+    }
+}
+
+/// Convert the given interleaved B-format buffer from one format to another in place.
+///
+/// **Panic!**s if the buffer length is not a multiple of `CHANNELS`.
+pub fn convert_format(samples: &mut [f32], from: Format, to: Format) {
+    assert_eq!(
+        samples.len() % CHANNELS,
+        0,
+        "the B-format buffer length must be a multiple of `CHANNELS`"
+    );
+    if from == to {
+        return;
+    }
+    for frame in samples.chunks_exact_mut(CHANNELS) {
+        let [w, a, b, c] = [frame[0], frame[1], frame[2], frame[3]];
+        let converted = match from {
+            // AmbiX (W, Y, Z, X) to FuMa (W, X, Y, Z).
+            Format::AmbiX => [w * std::f32::consts::FRAC_1_SQRT_2, c, a, b],
+            // FuMa (W, X, Y, Z) to AmbiX (W, Y, Z, X).
+            Format::FuMa => [w * std::f32::consts::SQRT_2, b, c, a],
+        };
+        frame.copy_from_slice(&converted);
+    }
+}
+
+fn dot(a: &[f32; CHANNELS], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
@@ -0,0 +1,7 @@
+//! Helpers for positioning sounds in space across multi-speaker layouts and headphones.
+//!
+//! - [**ambisonic**](./ambisonic/index.html) for first-order ambisonic encoding of mono sources
+//!   and decoding to arbitrary speaker layouts, or to headphones via a binaural decoder (requires
+//!   the `binaural` feature).
+
+pub mod ambisonic;
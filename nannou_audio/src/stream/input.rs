@@ -1,12 +1,13 @@
 use crate::{
     stream::{self, DefaultErrorFn, ErrorFn},
-    Buffer, Device, Receiver, Stream,
+    Buffer, ChannelStrip, Device, Receiver, Stream,
 };
-use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::traits::HostTrait;
 use dasp_sample::{FromSample, Sample, ToSample};
 use std::sync::atomic::AtomicBool;
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// The function that will be called when a captured `Buffer` is ready to be read.
 pub trait CaptureFn<M, S>: Fn(&mut M, &Buffer<S>) {}
@@ -88,7 +89,51 @@ impl<M, FC, FE, S> Builder<M, FC, FE, S> {
         self
     }
 
+    /// Retry building the stream up to `retries` times in the case that it fails with a transient
+    /// error, e.g. a device that is busy or not yet available at startup.
+    ///
+    /// The first retry occurs after `delay`, with the delay growing by `DEFAULT_RETRY_BACKOFF`
+    /// for each following retry. The calling thread is blocked while waiting.
+    ///
+    /// By default, building is not retried.
+    pub fn retry(mut self, retries: u32, delay: Duration) -> Self {
+        self.builder.retry.retries = retries;
+        self.builder.retry.delay = delay;
+        self
+    }
+
+    /// The factor by which the delay between retries grows.
+    ///
+    /// By default, this value is `DEFAULT_RETRY_BACKOFF`.
+    pub fn retry_backoff(mut self, backoff: f64) -> Self {
+        assert!(backoff >= 1.0);
+        self.builder.retry.backoff = backoff;
+        self
+    }
+
+    /// Specify the policy for retrying a failed build in full.
+    pub fn retry_policy(mut self, policy: stream::RetryPolicy) -> Self {
+        self.builder.retry = policy;
+        self
+    }
+
+    /// Build the stream.
+    ///
+    /// If a retry policy was specified, building is retried while it fails with a transient error.
+    /// The model is only moved into the stream once building succeeds.
     pub fn build(self) -> std::result::Result<Stream<M>, super::BuildError>
+    where
+        S: 'static + Send + Sample + FromSample<u16> + FromSample<i16> + FromSample<f32>,
+        M: 'static + Send,
+        FC: 'static + CaptureFn<M, S> + Send,
+        FE: 'static + ErrorFn<M> + Send,
+    {
+        self.try_build().map_err(|(err, _model)| err)
+    }
+
+    /// The same as `build`, but returns the model alongside the error on failure so that it may be
+    /// reused, e.g. by a `RebuildingStream`.
+    pub fn try_build(self) -> std::result::Result<Stream<M>, (super::BuildError, M)>
     where
         S: 'static + Send + Sample + FromSample<u16> + FromSample<i16> + FromSample<f32>,
         M: 'static + Send,
@@ -107,140 +152,177 @@ impl<M, FC, FE, S> Builder<M, FC, FE, S> {
                     frames_per_buffer,
                     device_buffer_size,
                     device,
+                    retry,
                     ..
                 },
         } = self;
 
-        let device = match device {
-            None => host
-                .default_input_device()
-                .ok_or(super::BuildError::DefaultDevice)?,
-            Some(Device { device }) => device,
-        };
-
-        let desired = super::DesiredStreamConfig {
-            sample_format: super::cpal_sample_format::<S>(),
-            channels,
-            sample_rate: sample_rate.map(cpal::SampleRate),
-            device_buffer_size,
-        };
-
-        // Find the best matching config.
-        let matching = super::find_best_matching_config(
-            &device,
-            desired,
-            device.default_input_config().ok(),
-            |device| device.supported_input_configs().map(|fs| fs.collect()),
-        )?
-        .expect("no matching supported audio input formats for the target device");
-        let (update_tx, update_rx) = mpsc::channel();
+        // The user's functions are moved into the callbacks of each attempt by value, and handed
+        // back for the next attempt when a failed attempt drops its callbacks.
         let model = Arc::new(Mutex::new(Some(model)));
-        let model_render = model.clone();
-        let model_error = model.clone();
-        let num_channels = matching.config.channels as usize;
-        let sample_rate = matching.config.sample_rate.0;
-        let sample_format = matching.sample_format;
-        let stream_config = matching.config.into();
-
-        // A buffer for collecting model updates.
-        let mut pending_updates: Vec<Box<dyn FnMut(&mut M) + 'static + Send>> = Vec::new();
-
-        // Get the specified frames_per_buffer or fall back to a default.
-        let frames_per_buffer = frames_per_buffer.unwrap_or(Buffer::<S>::DEFAULT_LEN_FRAMES);
-
-        // A `Receiver` for converting audio delivered by the backend at varying buffer sizes into
-        // buffers of a fixed size.
-        let mut receiver = Receiver::new(frames_per_buffer, num_channels);
-
-        // An intermediary buffer for converting cpal samples to the target sample
-        // format.
-        let mut samples = vec![S::EQUILIBRIUM; frames_per_buffer * num_channels];
-
-        // The function used to process a buffer of samples.
-        let capture_fn = move |data: &cpal::Data, _info: &cpal::InputCallbackInfo| {
-            // Collect and process any pending updates.
-            macro_rules! process_pending_updates {
-                () => {
-                    // Collect any pending updates.
-                    pending_updates.extend(update_rx.try_iter());
-
-                    // If there are some updates available, take the lock and apply them.
-                    if !pending_updates.is_empty() {
-                        if let Ok(mut guard) = model_render.lock() {
-                            let mut model = guard.take().unwrap();
-                            for mut update in pending_updates.drain(..) {
-                                update(&mut model);
+        let capture = stream::Reclaim::new(capture);
+        let error = stream::Reclaim::new(error);
+
+        let result = retry.run(|| {
+            let default_device;
+            let device = match (device.as_ref(), &host) {
+                (Some(&Device { ref device }), _) => stream::BackendDevice::Cpal(device),
+                (None, &stream::BackendHost::Cpal(ref host)) => {
+                    default_device = host
+                        .default_input_device()
+                        .ok_or(crate::Error::DefaultInputDevice)?;
+                    stream::BackendDevice::Cpal(&default_device)
+                }
+                #[cfg(feature = "test")]
+                (None, &stream::BackendHost::Virtual(ref host)) => {
+                    let device = host
+                        .default_input_device()
+                        .ok_or(crate::Error::DefaultInputDevice)?;
+                    stream::BackendDevice::VirtualInput(device.clone())
+                }
+            };
+
+            let requested = super::RequestedConfig {
+                sample_format: super::cpal_sample_format::<S>(),
+                channels,
+                sample_rate: sample_rate.map(cpal::SampleRate),
+                device_buffer_size: device_buffer_size.clone(),
+            };
+
+            // Find the best matching config.
+            let matching = super::matching_config(
+                &device,
+                requested,
+                device.default_input_config(),
+                |device| device.supported_input_configs(),
+            )?;
+            let (update_tx, update_rx) = mpsc::channel();
+            let model_render = model.clone();
+            let model_error = model.clone();
+            let capture = capture.take();
+            let error = error.take();
+            let num_channels = matching.config.channels as usize;
+            let sample_rate = matching.config.sample_rate.0;
+            let sample_format = matching.sample_format;
+            let stream_config: cpal::StreamConfig = matching.config.into();
+
+            // A buffer for collecting model updates.
+            let mut pending_updates: Vec<Box<dyn FnMut(&mut M) + 'static + Send>> = Vec::new();
+
+            // Get the specified frames_per_buffer or fall back to a default.
+            let frames_per_buffer = frames_per_buffer.unwrap_or(Buffer::<S>::DEFAULT_LEN_FRAMES);
+
+            // A `Receiver` for converting audio delivered by the backend at varying buffer sizes into
+            // buffers of a fixed size.
+            let mut receiver = Receiver::new(frames_per_buffer, num_channels);
+
+            // An intermediary buffer for converting cpal samples to the target sample
+            // format.
+            let mut samples = vec![S::EQUILIBRIUM; frames_per_buffer * num_channels];
+
+            // The per-channel gain, mute and solo applied to each captured buffer.
+            let channel_strip = ChannelStrip::new(num_channels);
+            let channel_strip_capture = channel_strip.clone();
+
+            // The function used to process a buffer of samples.
+            let capture_fn = move |data: stream::DataRef<'_>| {
+                // Collect and process any pending updates.
+                macro_rules! process_pending_updates {
+                    () => {
+                        // Collect any pending updates.
+                        pending_updates.extend(update_rx.try_iter());
+
+                        // If there are some updates available, take the lock and apply them.
+                        if !pending_updates.is_empty() {
+                            if let Ok(mut guard) = model_render.lock() {
+                                if let Some(mut model) = guard.take() {
+                                    for mut update in pending_updates.drain(..) {
+                                        update(&mut model);
+                                    }
+                                    *guard = Some(model);
+                                }
                             }
-                            *guard = Some(model);
                         }
-                    }
-                };
-            }
-
-            process_pending_updates!();
-
-            samples.clear();
-            samples.resize(data.len(), S::EQUILIBRIUM);
-
-            // A function to simplify reading from the unknown buffer type.
-            fn fill_input<I, S>(input: &mut [I], buffer: &[S])
-            where
-                I: Sample,
-                S: Sample + ToSample<I>,
-            {
-                for (in_sample, sample) in input.iter_mut().zip(buffer) {
-                    *in_sample = sample.to_sample();
+                    };
                 }
-            }
 
-            match sample_format {
-                cpal::SampleFormat::U16 => {
-                    let input = data.as_slice::<u16>().expect("expected u16 data");
-                    fill_input(&mut samples, &input);
-                }
-                cpal::SampleFormat::I16 => {
-                    let input = data.as_slice::<i16>().expect("expected i16 data");
-                    fill_input(&mut samples, &input);
+                process_pending_updates!();
+
+                samples.clear();
+                samples.resize(data.len(), S::EQUILIBRIUM);
+
+                // A function to simplify reading from the unknown buffer type.
+                fn fill_input<I, S>(input: &mut [I], buffer: &[S])
+                where
+                    I: Sample,
+                    S: Sample + ToSample<I>,
+                {
+                    for (in_sample, sample) in input.iter_mut().zip(buffer) {
+                        *in_sample = sample.to_sample();
+                    }
                 }
-                cpal::SampleFormat::F32 => {
-                    let input = data.as_slice::<f32>().expect("expected f32 data");
-                    fill_input(&mut samples, &input);
+
+                match data {
+                    stream::DataRef::U16(input) => fill_input(&mut samples, input),
+                    stream::DataRef::I16(input) => fill_input(&mut samples, input),
+                    stream::DataRef::F32(input) => fill_input(&mut samples, input),
                 }
-            }
-
-            if let Ok(mut guard) = model_render.lock() {
-                let mut m = guard.take().unwrap();
-                m = receiver.read_buffer(m, &capture, &samples, num_channels, sample_rate);
-                *guard = Some(m);
-            }
-
-            process_pending_updates!();
-        };
-
-        // Wrap the user's error function.
-        let err_fn = move |err| {
-            if let Ok(mut guard) = model_error.lock() {
-                if let Some(ref mut model) = *guard {
-                    error(model, err);
+
+                channel_strip_capture.process(&mut samples);
+
+                if let Ok(mut guard) = model_render.lock() {
+                    if let Some(mut m) = guard.take() {
+                        m = receiver.read_buffer(m, &*capture, &samples, num_channels, sample_rate);
+                        *guard = Some(m);
+                    }
                 }
-            }
-        };
 
-        let stream =
-            device.build_input_stream_raw(&stream_config, sample_format, capture_fn, err_fn)?;
+                process_pending_updates!();
+            };
 
-        let shared = Arc::new(super::Shared {
-            stream,
-            model,
-            is_paused: AtomicBool::new(false),
+            // Wrap the user's error function.
+            let err_fn = move |err| {
+                if let Ok(mut guard) = model_error.lock() {
+                    if let Some(ref mut model) = *guard {
+                        (*error)(model, err);
+                    }
+                }
+            };
+
+            let stream = device
+                .build_input_stream(&stream_config, sample_format, capture_fn, err_fn)
+                .map_err(|err| crate::Error::BuildStream {
+                    device: device.name(),
+                    config: stream_config.clone(),
+                    err,
+                })?;
+
+            let shared = Arc::new(super::Shared {
+                stream,
+                model: model.clone(),
+                is_paused: AtomicBool::new(false),
+                is_suspended: Arc::new(AtomicBool::new(false)),
+                retired_models: None,
+            });
+
+            let stream = Stream {
+                shared,
+                update_tx,
+                cpal_config: stream_config,
+                channel_strip,
+                crossfade_tx: None,
+            };
+            Ok(stream)
         });
 
-        let stream = Stream {
-            shared,
-            update_tx,
-            cpal_config: stream_config,
-        };
-        Ok(stream)
+        // Hand the model back to the caller if building failed.
+        result.map_err(|err| {
+            let mut guard = match model.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            (err, guard.take().expect("stream model was missing"))
+        })
     }
 }
 
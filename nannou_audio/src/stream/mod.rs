@@ -1,11 +1,16 @@
-use crate::Device;
-use cpal::traits::StreamTrait;
+use crate::channel::{Channel, ChannelStrip};
+#[cfg(feature = "test")]
+use crate::virtual_host::{VirtualHost, VirtualInputDevice, VirtualOutputDevice, VirtualStream};
+use crate::{Device, Error};
+use cpal::traits::{DeviceTrait, StreamTrait};
 use std;
 use std::any::{Any, TypeId};
+use std::fmt;
 use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{self, AtomicBool};
-use std::sync::{mpsc, Arc, Mutex};
-use thiserror::Error;
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::time::Duration;
 
 /// Items related to input audio streams.
 pub mod input;
@@ -32,58 +37,178 @@ pub struct Stream<M> {
     shared: Arc<Shared<M>>,
     /// The stream config with which the stream was created.
     cpal_config: cpal::StreamConfig,
+    /// The gain, mute and solo state of each channel of the stream.
+    channel_strip: ChannelStrip,
+    /// A channel for sending crossfaded model swaps to the audio thread of an output stream.
+    crossfade_tx: Option<mpsc::SyncSender<Crossfade<M>>>,
 }
 
 // Data shared between each `Stream` handle to a single stream.
 struct Shared<M> {
-    // The CPAL stream handle.
-    stream: cpal::Stream,
+    // The backend's stream handle.
+    stream: RawStream,
     // The user's audio model
     model: Arc<Mutex<Option<M>>>,
     // Whether or not the stream is currently paused.
     is_paused: AtomicBool,
+    // Whether or not rendering is currently suspended due to silence.
+    is_suspended: Arc<AtomicBool>,
+    // Models that have been faded out by the audio thread of an output stream and are waiting to
+    // be dropped.
+    retired_models: Option<Mutex<mpsc::Receiver<M>>>,
+}
+
+// A request for the audio thread to crossfade from the current model to the given one.
+pub(crate) struct Crossfade<M> {
+    pub(crate) model: M,
+    // The duration of the crossfade in frames.
+    pub(crate) frames: usize,
+}
+
+// Holds one of the user's functions between attempts at building a stream.
+//
+// Each attempt moves the function into its CPAL callback by value. CPAL drops the callbacks of an
+// attempt that fails, at which point the function is handed back for the next attempt.
+pub(crate) struct Reclaim<T>(Arc<Mutex<Option<T>>>);
+
+// A function owned by the callback of an attempt at building a stream.
+//
+// Once building has finished, the `Reclaim` no longer exists and dropping this simply drops the
+// function.
+pub(crate) struct Reclaimed<T> {
+    value: Option<T>,
+    home: Weak<Mutex<Option<T>>>,
 }
 
 /// Stream building parameters that are common between input and output streams.
 pub struct Builder<M, S = f32> {
-    pub(crate) host: Arc<cpal::Host>,
+    pub(crate) host: BackendHost,
     pub model: M,
     pub sample_rate: Option<u32>,
     pub channels: Option<usize>,
     pub frames_per_buffer: Option<usize>,
     pub device_buffer_size: Option<cpal::BufferSize>,
     pub device: Option<Device>,
+    pub retry: RetryPolicy,
     pub(crate) sample_format: PhantomData<S>,
 }
 
 /// Errors that might occur when attempting to build a stream.
-#[derive(Debug, Error)]
-pub enum BuildError {
-    #[error("failed to get default device")]
-    DefaultDevice,
-    #[error("failed to enumerate available configs: {err}")]
-    SupportedStreamConfigs {
-        err: cpal::SupportedStreamConfigsError,
-    },
-    #[error("failed to build stream: {err}")]
-    BuildStream { err: cpal::BuildStreamError },
+pub type BuildError = Error;
+
+// The host from which a stream builder opens its default device.
+#[derive(Clone)]
+pub(crate) enum BackendHost {
+    Cpal(Arc<cpal::Host>),
+    #[cfg(feature = "test")]
+    Virtual(VirtualHost),
 }
 
-#[derive(Debug)]
-struct DesiredStreamConfig {
+// The device on which a stream is built.
+pub(crate) enum BackendDevice<'a> {
+    Cpal(&'a cpal::Device),
+    #[cfg(feature = "test")]
+    VirtualInput(VirtualInputDevice),
+    #[cfg(feature = "test")]
+    VirtualOutput(VirtualOutputDevice),
+}
+
+// The backend's handle to a running stream.
+pub(crate) enum RawStream {
+    Cpal(cpal::Stream),
+    #[cfg(feature = "test")]
+    Virtual(VirtualStream),
+}
+
+// A buffer of device samples to be written by an output stream, in the device's sample format.
+pub(crate) enum DataMut<'a> {
+    U16(&'a mut [u16]),
+    I16(&'a mut [i16]),
+    F32(&'a mut [f32]),
+}
+
+// A buffer of device samples captured by an input stream, in the device's sample format.
+pub(crate) enum DataRef<'a> {
+    U16(&'a [u16]),
+    I16(&'a [i16]),
+    F32(&'a [f32]),
+}
+
+/// The stream config requested via a stream builder.
+///
+/// Fields that are `None` were left unspecified and may be satisfied by any supported value.
+#[derive(Clone, Debug)]
+pub struct RequestedConfig {
     /// Sample format specified by the user via the `S` sample type.
-    sample_format: Option<cpal::SampleFormat>,
+    pub sample_format: Option<cpal::SampleFormat>,
     /// Channel count specified by the user.
-    channels: Option<usize>,
-    /// The user's if specified, otherwise is `DEFAULT_SAMPLE_RATE`.
-    sample_rate: Option<cpal::SampleRate>,
+    pub channels: Option<usize>,
+    /// Sample rate specified by the user.
+    pub sample_rate: Option<cpal::SampleRate>,
     /// Desired device buffer size specified by the user.
-    device_buffer_size: Option<cpal::BufferSize>,
+    pub device_buffer_size: Option<cpal::BufferSize>,
+}
+
+/// Describes how building a stream is retried in the case that it fails with a transient error.
+///
+/// This is useful for installations that launch on boot, where the audio device may still be busy
+/// or unavailable for a short while after the application starts. See `Error::is_transient`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The number of times building is retried after the first attempt fails.
+    ///
+    /// By default, this value is `0`.
+    pub retries: u32,
+    /// The delay before the first retry.
+    ///
+    /// By default, this value is 500ms.
+    pub delay: Duration,
+    /// The factor by which the delay is multiplied after each retry.
+    ///
+    /// By default, this value is `DEFAULT_RETRY_BACKOFF`.
+    pub backoff: f64,
+    /// The delay between retries never exceeds this duration.
+    ///
+    /// By default, this value is `DEFAULT_RETRY_MAX_DELAY`.
+    pub max_delay: Duration,
 }
 
+/// The default factor by which the delay between retries grows.
+pub const DEFAULT_RETRY_BACKOFF: f64 = 2.0;
+
+/// The default maximum delay between retries.
+pub const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
 /// The default sample rate used for output, input and duplex streams if possible.
 pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
 
+/// The maximum number of crossfaded model swaps that may be waiting to be received by the audio
+/// thread of an output stream.
+pub const MAX_PENDING_CROSSFADES: usize = 4;
+
+// The capacity of the channel over which the audio thread hands back retired models. Between the
+// drains performed by each call to `swap_model_crossfade`, the audio thread can retire at most the
+// current model, the outgoing model of a crossfade and the model of each pending swap, so the
+// channel never fills.
+pub(crate) const RETIRED_MODELS_CAPACITY: usize = MAX_PENDING_CROSSFADES + 2;
+
+impl<M, S> Builder<M, S> {
+    // Builder initialisation shared between input and output streams.
+    pub(crate) fn new(host: BackendHost, model: M) -> Self {
+        Builder {
+            host,
+            model,
+            sample_rate: None,
+            channels: None,
+            frames_per_buffer: None,
+            device_buffer_size: None,
+            device: None,
+            retry: Default::default(),
+            sample_format: PhantomData,
+        }
+    }
+}
+
 impl<M> Stream<M> {
     /// Command the audio device to start processing this stream.
     ///
@@ -114,6 +239,24 @@ impl<M> Stream<M> {
         self.shared.is_paused()
     }
 
+    /// Whether or not rendering is currently suspended due to silence.
+    ///
+    /// See the output stream builder's `auto_suspend` method.
+    pub fn is_suspended(&self) -> bool {
+        self.shared.is_suspended.load(atomic::Ordering::Relaxed)
+    }
+
+    /// Resume rendering after the stream was suspended due to silence.
+    ///
+    /// If the stream was also paused, `play` must be called to resume processing.
+    ///
+    /// Has no effect if the stream is not suspended.
+    pub fn resume(&self) {
+        self.shared
+            .is_suspended
+            .store(false, atomic::Ordering::Relaxed);
+    }
+
     /// Send the given model update to the audio thread to be applied ASAP.
     ///
     /// If the audio is currently rendering, the update will be applied immediately after the
@@ -165,6 +308,96 @@ impl<M> Stream<M> {
         Ok(())
     }
 
+    /// Replace the stream's model with the given one, crossfading between the two.
+    ///
+    /// For the given `duration`, the audio thread renders both the old and the new model and mixes
+    /// their output with an equal-power ramp. Once the crossfade completes, the old model is handed
+    /// back from the audio thread over a bounded channel and dropped on the calling thread during
+    /// the next call to this method, or when the stream is closed. This allows for replacing the
+    /// audio process of a running stream without clicks or restarting the stream, e.g. while live
+    /// coding.
+    ///
+    /// If a swap occurs while a previous crossfade is still in progress, the previous outgoing
+    /// model is cut off and the crossfade begins again from the current model.
+    ///
+    /// If the stream is currently paused, or if this is an input stream, the model is replaced
+    /// immediately and the old model is dropped.
+    ///
+    /// Returns `TrySendError::Full` with the given model if `MAX_PENDING_CROSSFADES` swaps are
+    /// already waiting to be received by the audio thread.
+    pub fn swap_model_crossfade(
+        &self,
+        model: M,
+        duration: Duration,
+    ) -> Result<(), mpsc::TrySendError<M>> {
+        // Drop models retired by previous swaps here rather than on the audio thread.
+        self.shared.drop_retired_models();
+
+        let crossfade_tx = match self.crossfade_tx {
+            Some(ref tx) if !self.shared.is_paused.load(atomic::Ordering::Relaxed) => tx,
+            _ => {
+                let old_model = match self.shared.model.lock() {
+                    Ok(mut guard) => guard.replace(model),
+                    Err(_) => None,
+                };
+                drop(old_model);
+                return Ok(());
+            }
+        };
+
+        let sample_rate = self.cpal_config.sample_rate.0 as f64;
+        let frames = (duration.as_secs_f64() * sample_rate).round() as usize;
+        crossfade_tx
+            .try_send(Crossfade { model, frames })
+            .map_err(|err| match err {
+                mpsc::TrySendError::Full(crossfade) => mpsc::TrySendError::Full(crossfade.model),
+                mpsc::TrySendError::Disconnected(crossfade) => {
+                    mpsc::TrySendError::Disconnected(crossfade.model)
+                }
+            })
+    }
+
+    /// Close the stream and return its model.
+    ///
+    /// The stream is only closed if this is the last handle to it. Otherwise, the stream is
+    /// returned as an `Err`.
+    pub fn into_model(self) -> Result<M, Self> {
+        if Arc::strong_count(&self.shared) > 1 {
+            return Err(self);
+        }
+        // Dropping the last handle closes the CPAL stream, ensuring the audio thread no longer
+        // has access to the model.
+        let model = self.shared.model.clone();
+        drop(self);
+        let mut guard = match model.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Ok(guard.take().expect("stream model was missing"))
+    }
+
+    // Whether or not this is the only handle to the stream.
+    pub(crate) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.shared) == 1
+    }
+
+    // Take the model out of the running stream, leaving the stream to output silence until the
+    // model is restored.
+    pub(crate) fn take_model(&self) -> Option<M> {
+        match self.shared.model.lock() {
+            Ok(mut guard) => guard.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        }
+    }
+
+    // Restore a model previously taken with `take_model`.
+    pub(crate) fn restore_model(&self, model: M) {
+        match self.shared.model.lock() {
+            Ok(mut guard) => *guard = Some(model),
+            Err(poisoned) => *poisoned.into_inner() = Some(model),
+        }
+    }
+
     /// The config with which the inner CPAL stream was created.
     ///
     /// This **should** match the actual stream config that is running. If not, there may be a bug
@@ -174,6 +407,52 @@ impl<M> Stream<M> {
     pub fn cpal_config(&self) -> &cpal::StreamConfig {
         &self.cpal_config
     }
+
+    /// The gain, mute and solo state of each channel of the stream.
+    ///
+    /// The strip is applied within the stream's callback, so changes take effect from the next
+    /// buffer.
+    pub fn channel_strip(&self) -> &ChannelStrip {
+        &self.channel_strip
+    }
+
+    /// A handle to the gain, mute and solo state of the channel at the given index.
+    ///
+    /// **Panic!**s if the stream has no channel at the given index.
+    pub fn channel(&self, index: usize) -> Channel {
+        self.channel_strip.channel(index)
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that retries building up to `retries` times, waiting for `delay` before the first
+    /// retry and backing off by `DEFAULT_RETRY_BACKOFF` thereafter.
+    pub fn new(retries: u32, delay: Duration) -> Self {
+        RetryPolicy {
+            retries,
+            delay,
+            ..Default::default()
+        }
+    }
+
+    // Call `build` until it succeeds, returns a non-transient error or the retries are exhausted.
+    pub(crate) fn run<T, F>(&self, mut build: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Result<T, Error>,
+    {
+        let mut delay = self.delay.min(self.max_delay);
+        let mut retries = 0;
+        loop {
+            match build() {
+                Err(ref err) if retries < self.retries && err.is_transient() => {
+                    std::thread::sleep(delay);
+                    delay = delay.mul_f64(self.backoff).min(self.max_delay);
+                    retries += 1;
+                }
+                res => return res,
+            }
+        }
+    }
 }
 
 impl<M> Shared<M> {
@@ -196,6 +475,255 @@ impl<M> Shared<M> {
     fn is_paused(&self) -> bool {
         self.is_paused.load(atomic::Ordering::Relaxed)
     }
+
+    // Drop any models that have been handed back by the audio thread.
+    fn drop_retired_models(&self) {
+        if let Some(ref retired_models) = self.retired_models {
+            if let Ok(retired_models) = retired_models.lock() {
+                retired_models.try_iter().for_each(drop);
+            }
+        }
+    }
+}
+
+impl<'a> BackendDevice<'a> {
+    // The name of the device for use within errors.
+    pub(crate) fn name(&self) -> String {
+        match *self {
+            BackendDevice::Cpal(device) => device
+                .name()
+                .unwrap_or_else(|_| "<unknown device>".to_string()),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(ref device) => device.name().to_string(),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(ref device) => device.name().to_string(),
+        }
+    }
+
+    pub(crate) fn default_input_config(&self) -> Option<cpal::SupportedStreamConfig> {
+        match *self {
+            BackendDevice::Cpal(device) => device.default_input_config().ok(),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(ref device) => Some(device.default_config()),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(_) => None,
+        }
+    }
+
+    pub(crate) fn default_output_config(&self) -> Option<cpal::SupportedStreamConfig> {
+        match *self {
+            BackendDevice::Cpal(device) => device.default_output_config().ok(),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(_) => None,
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(ref device) => Some(device.default_config()),
+        }
+    }
+
+    pub(crate) fn supported_input_configs(
+        &self,
+    ) -> Result<Vec<cpal::SupportedStreamConfigRange>, cpal::SupportedStreamConfigsError> {
+        match *self {
+            BackendDevice::Cpal(device) => device.supported_input_configs().map(|fs| fs.collect()),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(ref device) => Ok(vec![device.supported_config()]),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(_) => Ok(vec![]),
+        }
+    }
+
+    pub(crate) fn supported_output_configs(
+        &self,
+    ) -> Result<Vec<cpal::SupportedStreamConfigRange>, cpal::SupportedStreamConfigsError> {
+        match *self {
+            BackendDevice::Cpal(device) => device.supported_output_configs().map(|fs| fs.collect()),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(_) => Ok(vec![]),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(ref device) => Ok(vec![device.supported_config()]),
+        }
+    }
+
+    // Build an input stream that calls `data` with each buffer captured by the device.
+    pub(crate) fn build_input_stream<D, E>(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+        mut data: D,
+        error: E,
+    ) -> Result<RawStream, cpal::BuildStreamError>
+    where
+        D: 'static + Send + FnMut(DataRef<'_>),
+        E: 'static + Send + FnMut(cpal::StreamError),
+    {
+        match *self {
+            BackendDevice::Cpal(device) => {
+                let data = move |input: &cpal::Data, _info: &cpal::InputCallbackInfo| {
+                    let input = match sample_format {
+                        cpal::SampleFormat::U16 => {
+                            DataRef::U16(input.as_slice().expect("expected u16 data"))
+                        }
+                        cpal::SampleFormat::I16 => {
+                            DataRef::I16(input.as_slice().expect("expected i16 data"))
+                        }
+                        cpal::SampleFormat::F32 => {
+                            DataRef::F32(input.as_slice().expect("expected f32 data"))
+                        }
+                    };
+                    data(input);
+                };
+                device
+                    .build_input_stream_raw(config, sample_format, data, error)
+                    .map(RawStream::Cpal)
+            }
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(ref device) => {
+                let data = move |input: &[f32]| data(DataRef::F32(input));
+                device
+                    .open(config, sample_format, Box::new(data), Box::new(error))
+                    .map(RawStream::Virtual)
+            }
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(_) => {
+                Err(cpal::BuildStreamError::StreamConfigNotSupported)
+            }
+        }
+    }
+
+    // Build an output stream that calls `data` to fill each buffer requested by the device.
+    pub(crate) fn build_output_stream<D, E>(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+        mut data: D,
+        error: E,
+    ) -> Result<RawStream, cpal::BuildStreamError>
+    where
+        D: 'static + Send + FnMut(DataMut<'_>),
+        E: 'static + Send + FnMut(cpal::StreamError),
+    {
+        match *self {
+            BackendDevice::Cpal(device) => {
+                // TODO: We should notify the user of `OutputCallbackInfo`.
+                let data = move |output: &mut cpal::Data, _info: &cpal::OutputCallbackInfo| {
+                    let output = match sample_format {
+                        cpal::SampleFormat::U16 => {
+                            DataMut::U16(output.as_slice_mut().expect("expected u16 data"))
+                        }
+                        cpal::SampleFormat::I16 => {
+                            DataMut::I16(output.as_slice_mut().expect("expected i16 data"))
+                        }
+                        cpal::SampleFormat::F32 => {
+                            DataMut::F32(output.as_slice_mut().expect("expected f32 data"))
+                        }
+                    };
+                    data(output);
+                };
+                device
+                    .build_output_stream_raw(config, sample_format, data, error)
+                    .map(RawStream::Cpal)
+            }
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualInput(_) => Err(cpal::BuildStreamError::StreamConfigNotSupported),
+            #[cfg(feature = "test")]
+            BackendDevice::VirtualOutput(ref device) => {
+                let data = move |output: &mut [f32]| data(DataMut::F32(output));
+                device
+                    .open(config, sample_format, Box::new(data), Box::new(error))
+                    .map(RawStream::Virtual)
+            }
+        }
+    }
+}
+
+impl RawStream {
+    fn play(&self) -> Result<(), cpal::PlayStreamError> {
+        match *self {
+            RawStream::Cpal(ref stream) => stream.play(),
+            #[cfg(feature = "test")]
+            RawStream::Virtual(ref stream) => {
+                stream.set_playing(true);
+                Ok(())
+            }
+        }
+    }
+
+    fn pause(&self) -> Result<(), cpal::PauseStreamError> {
+        match *self {
+            RawStream::Cpal(ref stream) => stream.pause(),
+            #[cfg(feature = "test")]
+            RawStream::Virtual(ref stream) => {
+                stream.set_playing(false);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a> DataMut<'a> {
+    // The number of samples in the buffer.
+    pub(crate) fn len(&self) -> usize {
+        match *self {
+            DataMut::U16(ref data) => data.len(),
+            DataMut::I16(ref data) => data.len(),
+            DataMut::F32(ref data) => data.len(),
+        }
+    }
+}
+
+impl<'a> DataRef<'a> {
+    // The number of samples in the buffer.
+    pub(crate) fn len(&self) -> usize {
+        match *self {
+            DataRef::U16(data) => data.len(),
+            DataRef::I16(data) => data.len(),
+            DataRef::F32(data) => data.len(),
+        }
+    }
+}
+
+impl<T> Reclaim<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Reclaim(Arc::new(Mutex::new(Some(value))))
+    }
+
+    // Move the value out for use by a single attempt at building a stream.
+    //
+    // **Panic!**s if the callbacks of a previous failed attempt were not dropped.
+    pub(crate) fn take(&self) -> Reclaimed<T> {
+        let value = match self.0.lock() {
+            Ok(mut guard) => guard.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        Reclaimed {
+            value: Some(value.expect("the callbacks of a failed stream build were not dropped")),
+            home: Arc::downgrade(&self.0),
+        }
+    }
+}
+
+impl<T> Deref for Reclaimed<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        self.value.as_ref().expect("reclaimed value was missing")
+    }
+}
+
+impl<T> DerefMut for Reclaimed<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().expect("reclaimed value was missing")
+    }
+}
+
+impl<T> Drop for Reclaimed<T> {
+    fn drop(&mut self) {
+        if let (Some(value), Some(home)) = (self.value.take(), self.home.upgrade()) {
+            match home.lock() {
+                Ok(mut guard) => *guard = Some(value),
+                Err(poisoned) => *poisoned.into_inner() = Some(value),
+            }
+        }
+    }
 }
 
 impl<M, F> ErrorFn<M> for F where F: Fn(&mut M, cpal::StreamError) {}
@@ -205,10 +733,14 @@ impl<M> Clone for Stream<M> {
         let update_tx = self.update_tx.clone();
         let shared = self.shared.clone();
         let cpal_config = self.cpal_config.clone();
+        let channel_strip = self.channel_strip.clone();
+        let crossfade_tx = self.crossfade_tx.clone();
         Stream {
             update_tx,
             shared,
             cpal_config,
+            channel_strip,
+            crossfade_tx,
         }
     }
 }
@@ -218,18 +750,36 @@ impl<M> Drop for Shared<M> {
         if self.is_playing() {
             self.pause().ok();
         }
+        self.drop_retired_models();
     }
 }
 
-impl From<cpal::BuildStreamError> for BuildError {
-    fn from(err: cpal::BuildStreamError) -> Self {
-        BuildError::BuildStream { err }
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 0,
+            delay: Duration::from_millis(500),
+            backoff: DEFAULT_RETRY_BACKOFF,
+            max_delay: DEFAULT_RETRY_MAX_DELAY,
+        }
     }
 }
 
-impl From<cpal::SupportedStreamConfigsError> for BuildError {
-    fn from(err: cpal::SupportedStreamConfigsError) -> Self {
-        BuildError::SupportedStreamConfigs { err }
+impl fmt::Display for RequestedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn any<T: fmt::Debug>(opt: &Option<T>) -> String {
+            opt.as_ref()
+                .map(|t| format!("{:?}", t))
+                .unwrap_or_else(|| "any".to_string())
+        }
+        write!(
+            f,
+            "sample format: {}, channels: {}, sample rate: {}, buffer size: {}",
+            any(&self.sample_format),
+            any(&self.channels),
+            any(&self.sample_rate.map(|rate| rate.0)),
+            any(&self.device_buffer_size),
+        )
     }
 }
 
@@ -258,7 +808,7 @@ fn cpal_sample_format<S: Any>() -> Option<cpal::SampleFormat> {
 // the requested config parameters specified by the user. If the supported config matches, a
 // compatible `SupportedStreamConfig` is returned.
 fn matching_supported_config(
-    desired: &DesiredStreamConfig,
+    desired: &RequestedConfig,
     supported_stream_config_range: &cpal::SupportedStreamConfigRange,
 ) -> Option<MatchingConfig> {
     // Check for a matching buffer size.
@@ -349,7 +899,7 @@ struct MatchingConfig {
 
 // Whether or not the desired stream config matches the default one.
 fn desired_config_matches_default(
-    desired: &DesiredStreamConfig,
+    desired: &RequestedConfig,
     default: &cpal::SupportedStreamConfig,
 ) -> Option<MatchingConfig> {
     if desired.sample_format == Some(default.sample_format())
@@ -382,14 +932,14 @@ fn desired_config_matches_default(
 // Given some audio device find the supported stream config that best matches the given optional
 // config parameters (specified by the user).
 fn find_best_matching_config<F>(
-    device: &cpal::Device,
-    mut desired: DesiredStreamConfig,
+    device: &BackendDevice,
+    mut desired: RequestedConfig,
     default: Option<cpal::SupportedStreamConfig>,
     supported_configs: F,
 ) -> Result<Option<MatchingConfig>, cpal::SupportedStreamConfigsError>
 where
     F: Fn(
-        &cpal::Device,
+        &BackendDevice,
     ) -> Result<Vec<cpal::SupportedStreamConfigRange>, cpal::SupportedStreamConfigsError>,
 {
     // In the case that the user has not specified a sample rate, we want to try specifying a
@@ -444,6 +994,33 @@ where
     }
 }
 
+// Find the supported config that best matches the request, adding the device and request as
+// context to any errors.
+fn matching_config<F>(
+    device: &BackendDevice,
+    requested: RequestedConfig,
+    default: Option<cpal::SupportedStreamConfig>,
+    supported_configs: F,
+) -> Result<MatchingConfig, Error>
+where
+    F: Fn(
+        &BackendDevice,
+    ) -> Result<Vec<cpal::SupportedStreamConfigRange>, cpal::SupportedStreamConfigsError>,
+{
+    let to_error = |err| Error::SupportedStreamConfigs {
+        device: device.name(),
+        err,
+    };
+    let matching =
+        find_best_matching_config(device, requested.clone(), default, &supported_configs)
+            .map_err(to_error)?;
+    matching.ok_or_else(|| Error::NoMatchingConfig {
+        device: device.name(),
+        requested,
+        supported: supported_configs(device).unwrap_or_default(),
+    })
+}
+
 // The default error function used when unspecified.
 pub(crate) fn default_error_fn<M>(_: &mut M, err: cpal::StreamError) {
     eprintln!("A `StreamError` occurred: {}", err);
@@ -1,12 +1,13 @@
 use crate::{
     stream::{self, DefaultErrorFn, ErrorFn},
-    Buffer, Device, Requester, Stream,
+    Buffer, ChannelStrip, Device, Requester, Stream,
 };
-use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::traits::HostTrait;
 use dasp_sample::{Sample, ToSample};
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{self, AtomicBool};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// The function that will be called when a `Buffer` is ready to be rendered.
 pub trait RenderFn<M, S>: Fn(&mut M, &mut Buffer<S>) {}
@@ -17,11 +18,35 @@ pub type DefaultRenderFn<M, S> = fn(&mut M, &mut Buffer<S>);
 // The default render function used when unspecified.
 pub(crate) fn default_render_fn<M, S>(_: &mut M, _: &mut Buffer<S>) {}
 
+/// The function called on the audio thread when an output stream is suspended due to silence.
+pub type SuspendFn<M> = Box<dyn FnMut(&mut M) + 'static + Send>;
+
+/// The default amplitude at or below which samples are considered silent (roughly -80dB).
+pub const DEFAULT_SILENCE_THRESHOLD: f32 = 0.0001;
+
 /// A type used for building an output stream.
 pub struct Builder<M, FR, FE, S = f32> {
     pub builder: super::Builder<M, S>,
     pub render: FR,
     pub error: FE,
+    pub auto_suspend: AutoSuspend<M>,
+}
+
+/// Parameters for automatically suspending an output stream after a period of silence.
+///
+/// While suspended, the render function is no longer called and the stream outputs silence. Call
+/// `Stream::resume` to resume rendering.
+pub struct AutoSuspend<M> {
+    /// The duration of continuous silence after which the stream is suspended.
+    ///
+    /// If `None` (the default), the stream is never suspended.
+    pub silence_duration: Option<Duration>,
+    /// The amplitude at or below which a sample is considered silent.
+    ///
+    /// By default, this is `DEFAULT_SILENCE_THRESHOLD`.
+    pub threshold: f32,
+    /// Called on the audio thread at the moment the stream is suspended.
+    pub on_suspend: Option<SuspendFn<M>>,
 }
 
 /// The builder when first initialised.
@@ -29,6 +54,16 @@ pub type BuilderInit<M, S = f32> = Builder<M, DefaultRenderFn<M, S>, DefaultErro
 
 type OutputDevices = cpal::OutputDevices<cpal::Devices>;
 
+// A model that is being faded out on the audio thread following a call to
+// `Stream::swap_model_crossfade`.
+struct FadeOut<M> {
+    model: M,
+    // The total duration of the crossfade in frames.
+    frames: usize,
+    // The number of frames of the crossfade that have been rendered so far.
+    elapsed: usize,
+}
+
 /// An iterator yielding all available audio devices that support output streams.
 pub struct Devices {
     pub(crate) devices: OutputDevices,
@@ -39,26 +74,71 @@ impl<M, S, F> RenderFn<M, S> for F where F: Fn(&mut M, &mut Buffer<S>) {}
 impl<M, FR, FE, S> Builder<M, FR, FE, S> {
     /// Specify the render function to use for rendering the model to the buffer.
     pub fn render<GR>(self, render: GR) -> Builder<M, GR, FE, S> {
-        let Builder { builder, error, .. } = self;
+        let Builder {
+            builder,
+            error,
+            auto_suspend,
+            ..
+        } = self;
         Builder {
             builder,
             render,
             error,
+            auto_suspend,
         }
     }
 
     /// Specify a function for processing stream errors.
     pub fn error<GE>(self, error: GE) -> Builder<M, FR, GE, S> {
         let Builder {
-            builder, render, ..
+            builder,
+            render,
+            auto_suspend,
+            ..
         } = self;
         Builder {
             builder,
             render,
             error,
+            auto_suspend,
         }
     }
 
+    /// Automatically suspend rendering after the stream has produced silence for the given
+    /// duration.
+    ///
+    /// While suspended, the render function is no longer called, saving CPU for installations that
+    /// are idle most of the time. Use `Stream::is_suspended` to check for suspension and
+    /// `Stream::resume` to resume rendering. To release the device entirely, `Stream::pause` may
+    /// be called once the stream is suspended.
+    pub fn auto_suspend(mut self, silence_duration: Duration) -> Self {
+        self.auto_suspend.silence_duration = Some(silence_duration);
+        self
+    }
+
+    /// The amplitude at or below which samples are considered silent for `auto_suspend`.
+    ///
+    /// By default, this is `DEFAULT_SILENCE_THRESHOLD`.
+    pub fn silence_threshold(mut self, threshold: f32) -> Self {
+        assert!(threshold >= 0.0);
+        self.auto_suspend.threshold = threshold;
+        self
+    }
+
+    /// Specify a function to call on the audio thread when the stream is suspended due to
+    /// silence.
+    ///
+    /// **Note:** This function will be called on the real-time audio thread so users should
+    /// avoid performing any kind of I/O, locking, blocking, (de)allocations or anything that
+    /// may run for an indeterminate amount of time.
+    pub fn on_suspend<F>(mut self, on_suspend: F) -> Self
+    where
+        F: FnMut(&mut M) + 'static + Send,
+    {
+        self.auto_suspend.on_suspend = Some(Box::new(on_suspend));
+        self
+    }
+
     pub fn sample_rate(mut self, sample_rate: u32) -> Self {
         assert!(sample_rate > 0);
         self.builder.sample_rate = Some(sample_rate);
@@ -87,7 +167,51 @@ impl<M, FR, FE, S> Builder<M, FR, FE, S> {
         self
     }
 
+    /// Retry building the stream up to `retries` times in the case that it fails with a transient
+    /// error, e.g. a device that is busy or not yet available at startup.
+    ///
+    /// The first retry occurs after `delay`, with the delay growing by `DEFAULT_RETRY_BACKOFF`
+    /// for each following retry. The calling thread is blocked while waiting.
+    ///
+    /// By default, building is not retried.
+    pub fn retry(mut self, retries: u32, delay: Duration) -> Self {
+        self.builder.retry.retries = retries;
+        self.builder.retry.delay = delay;
+        self
+    }
+
+    /// The factor by which the delay between retries grows.
+    ///
+    /// By default, this value is `DEFAULT_RETRY_BACKOFF`.
+    pub fn retry_backoff(mut self, backoff: f64) -> Self {
+        assert!(backoff >= 1.0);
+        self.builder.retry.backoff = backoff;
+        self
+    }
+
+    /// Specify the policy for retrying a failed build in full.
+    pub fn retry_policy(mut self, policy: stream::RetryPolicy) -> Self {
+        self.builder.retry = policy;
+        self
+    }
+
+    /// Build the stream.
+    ///
+    /// If a retry policy was specified, building is retried while it fails with a transient error.
+    /// The model is only moved into the stream once building succeeds.
     pub fn build(self) -> std::result::Result<Stream<M>, super::BuildError>
+    where
+        S: 'static + Send + Sample + ToSample<u16> + ToSample<i16> + ToSample<f32>,
+        M: 'static + Send,
+        FR: 'static + RenderFn<M, S> + Send,
+        FE: 'static + ErrorFn<M> + Send,
+    {
+        self.try_build().map_err(|(err, _model)| err)
+    }
+
+    /// The same as `build`, but returns the model alongside the error on failure so that it may be
+    /// reused, e.g. by a `RebuildingStream`.
+    pub fn try_build(self) -> std::result::Result<Stream<M>, (super::BuildError, M)>
     where
         S: 'static + Send + Sample + ToSample<u16> + ToSample<i16> + ToSample<f32>,
         M: 'static + Send,
@@ -97,6 +221,7 @@ impl<M, FR, FE, S> Builder<M, FR, FE, S> {
         let Builder {
             render,
             error,
+            auto_suspend,
             builder:
                 stream::Builder {
                     host,
@@ -106,140 +231,301 @@ impl<M, FR, FE, S> Builder<M, FR, FE, S> {
                     frames_per_buffer,
                     device_buffer_size,
                     device,
+                    retry,
                     ..
                 },
         } = self;
 
-        let device = match device {
-            None => host
-                .default_output_device()
-                .ok_or(super::BuildError::DefaultDevice)?,
-            Some(Device { device }) => device,
-        };
-
-        let desired = super::DesiredStreamConfig {
-            sample_format: super::cpal_sample_format::<S>(),
-            channels,
-            sample_rate: sample_rate.map(cpal::SampleRate),
-            device_buffer_size,
-        };
-
-        // Find the best matching config.
-        let matching = super::find_best_matching_config(
-            &device,
-            desired,
-            device.default_output_config().ok(),
-            |device| device.supported_output_configs().map(|fs| fs.collect()),
-        )?
-        .expect("no matching supported audio output formats for the target device");
-        let (update_tx, update_rx) = mpsc::channel();
+        // The user's functions are moved into the callbacks of each attempt by value, and handed
+        // back for the next attempt when a failed attempt drops its callbacks.
         let model = Arc::new(Mutex::new(Some(model)));
-        let model_render = model.clone();
-        let model_error = model.clone();
-        let num_channels = matching.config.channels as usize;
-        let sample_rate = matching.config.sample_rate.0;
-        let sample_format = matching.sample_format;
-        let stream_config = matching.config.into();
-
-        // A buffer for collecting model updates.
-        let mut pending_updates: Vec<Box<dyn FnMut(&mut M) + 'static + Send>> = Vec::new();
-
-        // Get the specified frames_per_buffer or fall back to a default.
-        let frames_per_buffer = frames_per_buffer.unwrap_or(Buffer::<S>::DEFAULT_LEN_FRAMES);
-
-        // An audio requester which requests frames from the model+render pair with a
-        // specific buffer size, regardless of the buffer size requested by the OS.
-        let mut requester = Requester::new(frames_per_buffer, num_channels);
-
-        // An intermediary buffer for converting cpal samples to the target sample
-        // format.
-        let mut samples = vec![S::EQUILIBRIUM; frames_per_buffer * num_channels];
-
-        // The function used to process a buffer of samples.
-        // TODO: We should notify the user of `OutputCallbackInfo`.
-        let render_fn = move |data: &mut cpal::Data, _info: &cpal::OutputCallbackInfo| {
-            // Collect and process any pending updates.
-            macro_rules! process_pending_updates {
-                () => {
-                    // Collect any pending updates.
-                    pending_updates.extend(update_rx.try_iter());
-
-                    // If there are some updates available, take the lock and apply them.
-                    if !pending_updates.is_empty() {
-                        if let Ok(mut guard) = model_render.lock() {
-                            let mut model = guard.take().unwrap();
-                            for mut update in pending_updates.drain(..) {
-                                update(&mut model);
+        let render = stream::Reclaim::new(render);
+        let error = stream::Reclaim::new(error);
+        let AutoSuspend {
+            silence_duration,
+            threshold,
+            on_suspend,
+        } = auto_suspend;
+        let on_suspend = stream::Reclaim::new(on_suspend);
+
+        let result = retry.run(|| {
+            let default_device;
+            let device = match (device.as_ref(), &host) {
+                (Some(&Device { ref device }), _) => stream::BackendDevice::Cpal(device),
+                (None, &stream::BackendHost::Cpal(ref host)) => {
+                    default_device = host
+                        .default_output_device()
+                        .ok_or(crate::Error::DefaultOutputDevice)?;
+                    stream::BackendDevice::Cpal(&default_device)
+                }
+                #[cfg(feature = "test")]
+                (None, &stream::BackendHost::Virtual(ref host)) => {
+                    let device = host
+                        .default_output_device()
+                        .ok_or(crate::Error::DefaultOutputDevice)?;
+                    stream::BackendDevice::VirtualOutput(device.clone())
+                }
+            };
+
+            let requested = super::RequestedConfig {
+                sample_format: super::cpal_sample_format::<S>(),
+                channels,
+                sample_rate: sample_rate.map(cpal::SampleRate),
+                device_buffer_size: device_buffer_size.clone(),
+            };
+
+            // Find the best matching config.
+            let matching = super::matching_config(
+                &device,
+                requested,
+                device.default_output_config(),
+                |device| device.supported_output_configs(),
+            )?;
+            let (update_tx, update_rx) = mpsc::channel();
+            let model_render = model.clone();
+            let model_error = model.clone();
+            let render = render.take();
+            let error = error.take();
+            let mut on_suspend = on_suspend.take();
+            let num_channels = matching.config.channels as usize;
+            let sample_rate = matching.config.sample_rate.0;
+            let sample_format = matching.sample_format;
+            let stream_config: cpal::StreamConfig = matching.config.into();
+
+            // A buffer for collecting model updates.
+            let mut pending_updates: Vec<Box<dyn FnMut(&mut M) + 'static + Send>> = Vec::new();
+
+            // Get the specified frames_per_buffer or fall back to a default.
+            let frames_per_buffer = frames_per_buffer.unwrap_or(Buffer::<S>::DEFAULT_LEN_FRAMES);
+
+            // An audio requester which requests frames from the model+render pair with a
+            // specific buffer size, regardless of the buffer size requested by the OS.
+            let mut requester = Requester::new(frames_per_buffer, num_channels);
+
+            // An intermediary buffer for converting cpal samples to the target sample
+            // format.
+            let mut samples = vec![S::EQUILIBRIUM; frames_per_buffer * num_channels];
+
+            // State for crossfading from an outgoing model to a newly swapped one. The outgoing
+            // model has its own requester so that any of its pending samples are still written.
+            // Both channels are bounded so that sending on the audio thread never allocates.
+            let (crossfade_tx, crossfade_rx) =
+                mpsc::sync_channel::<stream::Crossfade<M>>(stream::MAX_PENDING_CROSSFADES);
+            let (retired_tx, retired_rx) = mpsc::sync_channel(stream::RETIRED_MODELS_CAPACITY);
+            let mut fade_out: Option<FadeOut<M>> = None;
+            let mut fade_requester = Requester::new(frames_per_buffer, num_channels);
+            let mut fade_samples = vec![S::EQUILIBRIUM; frames_per_buffer * num_channels];
+
+            // Track the number of consecutive silent frames for automatic suspension.
+            let suspend_frames =
+                silence_duration.map(|d| (d.as_secs_f64() * sample_rate as f64) as usize);
+            let mut silent_frames = 0;
+            let is_suspended = Arc::new(AtomicBool::new(false));
+            let is_suspended_render = is_suspended.clone();
+
+            // The per-channel gain, mute and solo applied to each rendered buffer.
+            let channel_strip = ChannelStrip::new(num_channels);
+            let channel_strip_render = channel_strip.clone();
+
+            // The function used to process a buffer of samples.
+            let render_fn = move |data: stream::DataMut<'_>| {
+                // Collect and process any pending updates.
+                macro_rules! process_pending_updates {
+                    () => {
+                        // Collect any pending updates.
+                        pending_updates.extend(update_rx.try_iter());
+
+                        // If there are some updates available, take the lock and apply them.
+                        if !pending_updates.is_empty() {
+                            if let Ok(mut guard) = model_render.lock() {
+                                if let Some(mut model) = guard.take() {
+                                    for mut update in pending_updates.drain(..) {
+                                        update(&mut model);
+                                    }
+                                    *guard = Some(model);
+                                }
                             }
-                            *guard = Some(model);
                         }
-                    }
-                };
-            }
+                    };
+                }
 
-            process_pending_updates!();
+                process_pending_updates!();
+
+                // Begin crossfading to any newly swapped models. Outgoing models are sent back to
+                // the stream handle so that they are not dropped on the audio thread.
+                for stream::Crossfade { model, frames } in crossfade_rx.try_iter() {
+                    let old_model = match model_render.lock() {
+                        Ok(mut guard) => guard.replace(model),
+                        Err(_) => None,
+                    };
+                    if let Some(fade) = fade_out.take() {
+                        retired_tx.try_send(fade.model).ok();
+                    }
+                    if let Some(old_model) = old_model {
+                        if frames == 0 {
+                            retired_tx.try_send(old_model).ok();
+                        } else {
+                            std::mem::swap(&mut requester, &mut fade_requester);
+                            requester.reset();
+                            fade_out = Some(FadeOut {
+                                model: old_model,
+                                frames,
+                                elapsed: 0,
+                            });
+                        }
+                    }
+                }
 
-            samples.clear();
-            samples.resize(data.len(), S::EQUILIBRIUM);
+                samples.clear();
+                samples.resize(data.len(), S::EQUILIBRIUM);
+
+                // Skip rendering the model while the stream is suspended due to silence.
+                if is_suspended_render.load(atomic::Ordering::Relaxed) {
+                    silent_frames = 0;
+                } else if let Ok(mut guard) = model_render.lock() {
+                    if let Some(mut m) = guard.take() {
+                        m = requester.fill_buffer(
+                            m,
+                            &*render,
+                            &mut samples,
+                            num_channels,
+                            sample_rate,
+                        );
+
+                        // Mix in the outgoing model while a crossfade is in progress.
+                        if let Some(mut fade) = fade_out.take() {
+                            fade_samples.clear();
+                            fade_samples.resize(samples.len(), S::EQUILIBRIUM);
+                            fade.model = fade_requester.fill_buffer(
+                                fade.model,
+                                &*render,
+                                &mut fade_samples,
+                                num_channels,
+                                sample_rate,
+                            );
+                            fade.mix(&mut samples, &fade_samples, num_channels);
+                            if fade.elapsed >= fade.frames {
+                                retired_tx.try_send(fade.model).ok();
+                            } else {
+                                fade_out = Some(fade);
+                            }
+                        }
 
-            if let Ok(mut guard) = model_render.lock() {
-                let mut m = guard.take().unwrap();
-                m = requester.fill_buffer(m, &render, &mut samples, num_channels, sample_rate);
-                *guard = Some(m);
-            }
+                        channel_strip_render.process(&mut samples);
+
+                        // Check whether or not the stream should be suspended due to silence.
+                        if let Some(suspend_frames) = suspend_frames {
+                            let is_silent = samples
+                                .iter()
+                                .all(|&s| s.to_sample::<f32>().abs() <= threshold);
+                            if is_silent {
+                                silent_frames += samples.len() / num_channels;
+                                if silent_frames >= suspend_frames {
+                                    is_suspended_render.store(true, atomic::Ordering::Relaxed);
+                                    if let Some(ref mut on_suspend) = *on_suspend {
+                                        on_suspend(&mut m);
+                                    }
+                                }
+                            } else {
+                                silent_frames = 0;
+                            }
+                        }
 
-            // A function to simplify filling the unknown buffer type.
-            fn fill_output<O, S>(output: &mut [O], buffer: &[S])
-            where
-                O: Sample,
-                S: Sample + ToSample<O>,
-            {
-                for (out_sample, sample) in output.iter_mut().zip(buffer) {
-                    *out_sample = sample.to_sample();
+                        *guard = Some(m);
+                    }
                 }
-            }
 
-            // Process the given buffer.
-            match sample_format {
-                cpal::SampleFormat::U16 => {
-                    let output = data.as_slice_mut::<u16>().expect("expected u16 data");
-                    fill_output(output, &samples);
-                }
-                cpal::SampleFormat::I16 => {
-                    let output = data.as_slice_mut::<i16>().expect("expected i16 data");
-                    fill_output(output, &samples);
+                // A function to simplify filling the unknown buffer type.
+                fn fill_output<O, S>(output: &mut [O], buffer: &[S])
+                where
+                    O: Sample,
+                    S: Sample + ToSample<O>,
+                {
+                    for (out_sample, sample) in output.iter_mut().zip(buffer) {
+                        *out_sample = sample.to_sample();
+                    }
                 }
-                cpal::SampleFormat::F32 => {
-                    let output = data.as_slice_mut::<f32>().expect("expected f32 data");
-                    fill_output(output, &samples);
+
+                // Process the given buffer.
+                match data {
+                    stream::DataMut::U16(output) => fill_output(output, &samples),
+                    stream::DataMut::I16(output) => fill_output(output, &samples),
+                    stream::DataMut::F32(output) => fill_output(output, &samples),
                 }
-            }
-        };
+            };
 
-        // Wrap the user's error function.
-        let err_fn = move |err| {
-            if let Ok(mut guard) = model_error.lock() {
-                if let Some(ref mut model) = *guard {
-                    error(model, err);
+            // Wrap the user's error function.
+            let err_fn = move |err| {
+                if let Ok(mut guard) = model_error.lock() {
+                    if let Some(ref mut model) = *guard {
+                        (*error)(model, err);
+                    }
                 }
-            }
-        };
+            };
+
+            let stream = device
+                .build_output_stream(&stream_config, sample_format, render_fn, err_fn)
+                .map_err(|err| crate::Error::BuildStream {
+                    device: device.name(),
+                    config: stream_config.clone(),
+                    err,
+                })?;
+
+            let shared = Arc::new(super::Shared {
+                stream,
+                model: model.clone(),
+                is_paused: AtomicBool::new(false),
+                is_suspended,
+                retired_models: Some(Mutex::new(retired_rx)),
+            });
+
+            let stream = Stream {
+                shared,
+                update_tx,
+                cpal_config: stream_config,
+                channel_strip,
+                crossfade_tx: Some(crossfade_tx),
+            };
+            Ok(stream)
+        });
 
-        let stream =
-            device.build_output_stream_raw(&stream_config, sample_format, render_fn, err_fn)?;
+        // Hand the model back to the caller if building failed.
+        result.map_err(|err| {
+            let mut guard = match model.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            (err, guard.take().expect("stream model was missing"))
+        })
+    }
+}
 
-        let shared = Arc::new(super::Shared {
-            stream,
-            model,
-            is_paused: AtomicBool::new(false),
-        });
+impl<M> FadeOut<M> {
+    // Mix the outgoing samples into the incoming samples with an equal-power ramp, advancing the
+    // crossfade by the number of frames mixed.
+    fn mix<S: Sample>(&mut self, incoming: &mut [S], outgoing: &[S], channels: usize) {
+        let frames = incoming.chunks_mut(channels).zip(outgoing.chunks(channels));
+        for (in_frame, out_frame) in frames {
+            let t = (self.elapsed as f32 / self.frames as f32).min(1.0);
+            let angle = t * std::f32::consts::FRAC_PI_2;
+            let in_gain = <S::Float as Sample>::from_sample(angle.sin());
+            let out_gain = <S::Float as Sample>::from_sample(angle.cos());
+            for (in_sample, &out_sample) in in_frame.iter_mut().zip(out_frame) {
+                let out_sample = out_sample.mul_amp(out_gain).to_signed_sample();
+                *in_sample = in_sample.mul_amp(in_gain).add_amp(out_sample);
+            }
+            self.elapsed += 1;
+        }
+    }
+}
 
-        let stream = Stream {
-            shared,
-            update_tx,
-            cpal_config: stream_config,
-        };
-        Ok(stream)
+impl<M> Default for AutoSuspend<M> {
+    fn default() -> Self {
+        AutoSuspend {
+            silence_duration: None,
+            threshold: DEFAULT_SILENCE_THRESHOLD,
+            on_suspend: None,
+        }
     }
 }
 
@@ -0,0 +1,1229 @@
+//! A virtual audio host for testing stream-handling code and DSP without audio hardware.
+//!
+//! Streams are built on virtual devices with the same input and output stream builders used for
+//! hardware devices, so the resulting **Stream** behaves exactly as it would on a real device,
+//! including channel mapping, channel strips, model updates, automatic suspension, crossfaded
+//! model swaps and build retries.
+//!
+//! Virtual devices are not driven by an audio thread. Instead, buffers are processed on the
+//! calling thread via `process_buffers`, making it possible to test capture and render functions
+//! deterministically within CI.
+//!
+//! ```
+//! use nannou_audio::virtual_host::{InputSource, VirtualHost, VirtualInputDevice};
+//!
+//! let device = VirtualInputDevice::new("sine", InputSource::sine(440.0, 0.5));
+//! let host = VirtualHost::new().with_input_device(device.clone());
+//! let stream = host
+//!     .new_input_stream(0.0f32)
+//!     .capture(|peak: &mut f32, buffer: &nannou_audio::Buffer| {
+//!         for &s in buffer.iter() {
+//!             *peak = peak.max(s.abs());
+//!         }
+//!     })
+//!     .build()
+//!     .unwrap();
+//! stream.play().unwrap();
+//! device.process_buffers(100);
+//! let peak = stream.into_model().ok().unwrap();
+//! assert!((peak - 0.5).abs() < 0.01);
+//! ```
+
+use crate::stream::{self, DEFAULT_SAMPLE_RATE};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// A host providing virtual input and output devices.
+///
+/// Streams built via the host open its default input or output device.
+#[derive(Clone, Debug)]
+pub struct VirtualHost {
+    input_devices: Vec<VirtualInputDevice>,
+    output_devices: Vec<VirtualOutputDevice>,
+}
+
+/// A virtual input device that produces samples from a deterministic `InputSource`.
+///
+/// Clones of the device are handles to the same device.
+#[derive(Clone, Debug)]
+pub struct VirtualInputDevice {
+    name: String,
+    channels: usize,
+    sample_rate: u32,
+    buffer_frames: usize,
+    source: InputSource,
+    state: Arc<Mutex<InputState>>,
+}
+
+/// A virtual output device that records the samples of all streams playing on it in memory.
+///
+/// Clones of the device are handles to the same device.
+#[derive(Clone, Debug)]
+pub struct VirtualOutputDevice {
+    name: String,
+    channels: usize,
+    sample_rate: u32,
+    buffer_frames: usize,
+    state: Arc<Mutex<OutputState>>,
+}
+
+/// The source of the samples produced by a `VirtualInputDevice`.
+#[derive(Clone, Debug)]
+pub enum InputSource {
+    /// Produces silence.
+    Silence,
+    /// A sine wave with the given frequency and amplitude, identical on all channels.
+    Sine { hz: f64, amplitude: f32 },
+    /// Uniform white noise with the given amplitude.
+    ///
+    /// The same `seed` always produces the same sequence of samples.
+    Noise { seed: u64, amplitude: f32 },
+    /// Interleaved samples, e.g. loaded from a file.
+    ///
+    /// Samples are expected to be interleaved with the channel count of the device. If `looped`
+    /// is `false`, silence is produced once all samples have been read.
+    Samples {
+        interleaved: Arc<[f32]>,
+        looped: bool,
+    },
+}
+
+/// Errors that might occur while loading a `VirtualInputDevice` from a WAV file.
+pub type WavError = hound::Error;
+
+// The backend's handle to a stream open on a virtual device.
+pub(crate) struct VirtualStream {
+    id: u64,
+    device: OpenDevice,
+}
+
+// The callback through which an input stream receives each buffer captured by a device.
+pub(crate) type InputDataFn = Box<dyn FnMut(&[f32]) + Send>;
+// The callback through which an output stream fills each buffer requested by a device.
+pub(crate) type OutputDataFn = Box<dyn FnMut(&mut [f32]) + Send>;
+// The callback through which a stream receives errors emitted by a device.
+pub(crate) type StreamErrorFn = Box<dyn FnMut(cpal::StreamError) + Send>;
+
+// The device on which a `VirtualStream` is open.
+enum OpenDevice {
+    Input(Arc<Mutex<InputState>>),
+    Output(Arc<Mutex<OutputState>>),
+}
+
+// The state of a virtual input device.
+#[derive(Debug)]
+struct InputState {
+    streams: Streams<InputDataFn>,
+    generator: Generator,
+}
+
+// The state of a virtual output device.
+#[derive(Debug)]
+struct OutputState {
+    streams: Streams<OutputDataFn>,
+    captured: Vec<f32>,
+}
+
+// The streams open on a virtual device along with the device's progress.
+struct Streams<D> {
+    open: Vec<OpenStream<D>>,
+    next_id: u64,
+    failing_opens: usize,
+    frames_processed: u64,
+}
+
+// A stream open on a virtual device.
+struct OpenStream<D> {
+    id: u64,
+    channels: usize,
+    is_playing: bool,
+    data: D,
+    error: StreamErrorFn,
+}
+
+// The state of an `InputSource` as samples are read from it.
+#[derive(Clone, Debug)]
+struct Generator {
+    // The index of the next frame to be produced.
+    frame: u64,
+    // The state of the noise generator.
+    rng: u64,
+}
+
+/// The number of channels of the default virtual devices.
+pub const DEFAULT_CHANNELS: usize = 2;
+
+/// The number of frames in each buffer processed by the default virtual devices.
+pub const DEFAULT_BUFFER_FRAMES: usize = 512;
+
+impl VirtualHost {
+    /// A host with a default input device producing a 440hz sine and a default output device.
+    ///
+    /// Both devices have `DEFAULT_CHANNELS` channels at `DEFAULT_SAMPLE_RATE`.
+    pub fn new() -> Self {
+        let input = VirtualInputDevice::new("virtual input", InputSource::sine(440.0, 1.0));
+        let output = VirtualOutputDevice::new("virtual output");
+        VirtualHost {
+            input_devices: vec![input],
+            output_devices: vec![output],
+        }
+    }
+
+    /// A host with no devices.
+    pub fn empty() -> Self {
+        VirtualHost {
+            input_devices: vec![],
+            output_devices: vec![],
+        }
+    }
+
+    /// Add the given input device to the host, making it the default input device.
+    pub fn with_input_device(mut self, device: VirtualInputDevice) -> Self {
+        self.input_devices.insert(0, device);
+        self
+    }
+
+    /// Add the given output device to the host, making it the default output device.
+    pub fn with_output_device(mut self, device: VirtualOutputDevice) -> Self {
+        self.output_devices.insert(0, device);
+        self
+    }
+
+    /// All input devices available on the host, starting with the default.
+    pub fn input_devices(&self) -> &[VirtualInputDevice] {
+        &self.input_devices
+    }
+
+    /// All output devices available on the host, starting with the default.
+    pub fn output_devices(&self) -> &[VirtualOutputDevice] {
+        &self.output_devices
+    }
+
+    /// The default input device, if any.
+    pub fn default_input_device(&self) -> Option<&VirtualInputDevice> {
+        self.input_devices.first()
+    }
+
+    /// The default output device, if any.
+    pub fn default_output_device(&self) -> Option<&VirtualOutputDevice> {
+        self.output_devices.first()
+    }
+
+    /// Begin building a new input stream on the default input device.
+    pub fn new_input_stream<M, S>(&self, model: M) -> stream::input::BuilderInit<M, S> {
+        stream::input::Builder {
+            capture: stream::input::default_capture_fn,
+            error: stream::default_error_fn,
+            builder: self.new_stream(model),
+        }
+    }
+
+    /// Begin building a new output stream on the default output device.
+    pub fn new_output_stream<M, S>(&self, model: M) -> stream::output::BuilderInit<M, S> {
+        stream::output::Builder {
+            render: stream::output::default_render_fn,
+            error: stream::default_error_fn,
+            builder: self.new_stream(model),
+            auto_suspend: Default::default(),
+        }
+    }
+
+    // Builder initialisation shared between input and output streams.
+    fn new_stream<M, S>(&self, model: M) -> stream::Builder<M, S> {
+        stream::Builder::new(stream::BackendHost::Virtual(self.clone()), model)
+    }
+}
+
+impl VirtualInputDevice {
+    /// A device producing samples from the given source.
+    ///
+    /// By default, the device has `DEFAULT_CHANNELS` channels at `DEFAULT_SAMPLE_RATE` and
+    /// processes buffers of `DEFAULT_BUFFER_FRAMES` frames.
+    pub fn new(name: impl Into<String>, source: InputSource) -> Self {
+        let generator = Generator::new(&source);
+        let state = InputState {
+            streams: Streams::new(),
+            generator,
+        };
+        VirtualInputDevice {
+            name: name.into(),
+            channels: DEFAULT_CHANNELS,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            buffer_frames: DEFAULT_BUFFER_FRAMES,
+            source,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// A device producing the samples of the WAV file at the given path.
+    ///
+    /// The channel count and sample rate of the device match those of the file.
+    pub fn from_wav<P>(path: P, looped: bool) -> Result<Self, WavError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let reader = hound::WavReader::open(path)?;
+        let spec = reader.spec();
+        let interleaved: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.into_samples::<f32>().collect::<Result<_, _>>()?,
+            hound::SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|res| res.map(|s| s as f32 * scale))
+                    .collect::<Result<_, _>>()?
+            }
+        };
+        let source = InputSource::Samples {
+            interleaved: interleaved.into(),
+            looped,
+        };
+        let name = path.display().to_string();
+        let device = Self::new(name, source)
+            .channels(spec.channels as usize)
+            .sample_rate(spec.sample_rate);
+        Ok(device)
+    }
+
+    /// Specify the number of channels produced by the device.
+    ///
+    /// By default, this value is `DEFAULT_CHANNELS`.
+    pub fn channels(mut self, channels: usize) -> Self {
+        assert!(channels > 0);
+        self.channels = channels;
+        self
+    }
+
+    /// Specify the sample rate of the device.
+    ///
+    /// By default, this value is `DEFAULT_SAMPLE_RATE`.
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        assert!(sample_rate > 0);
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Specify the number of frames in each buffer processed by the device.
+    ///
+    /// By default, this value is `DEFAULT_BUFFER_FRAMES`.
+    pub fn buffer_frames(mut self, frames: usize) -> Self {
+        assert!(frames > 0);
+        self.buffer_frames = frames;
+        self
+    }
+
+    /// The name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The source of the samples produced by the device.
+    pub fn source(&self) -> &InputSource {
+        &self.source
+    }
+
+    /// Cause the given number of following attempts to open a stream on the device to fail with
+    /// `BuildStreamError::DeviceNotAvailable`, e.g. for testing retry policies.
+    pub fn fail_next_opens(&self, count: usize) {
+        lock(&self.state).streams.failing_opens = count;
+    }
+
+    /// The number of streams currently open on the device.
+    pub fn num_streams(&self) -> usize {
+        lock(&self.state).streams.open.len()
+    }
+
+    /// The total number of frames processed by the device so far.
+    pub fn frames_processed(&self) -> u64 {
+        lock(&self.state).streams.frames_processed
+    }
+
+    /// Deliver a backend-specific error with the given description to each stream open on the
+    /// device.
+    pub fn emit_error(&self, description: impl Into<String>) {
+        lock(&self.state).streams.emit_error(description.into());
+    }
+
+    /// Produce the given number of buffers from the device's source, delivering each to all
+    /// playing streams.
+    ///
+    /// Streams with fewer channels than the device receive the device's leading channels.
+    pub fn process_buffers(&self, n: usize) {
+        let mut state = lock(&self.state);
+        let InputState {
+            ref mut streams,
+            ref mut generator,
+        } = *state;
+        let mut captured = vec![0.0; self.buffer_frames * self.channels];
+        let mut buffer = vec![];
+        for _ in 0..n {
+            for frame in captured.chunks_mut(self.channels) {
+                generator.next_frame(&self.source, self.channels, self.sample_rate, frame);
+            }
+            for stream in streams.open.iter_mut().filter(|stream| stream.is_playing) {
+                let channels = stream.channels;
+                buffer.clear();
+                for frame in captured.chunks(self.channels) {
+                    buffer.extend_from_slice(&frame[..channels]);
+                }
+                (stream.data)(&buffer);
+            }
+            streams.frames_processed += self.buffer_frames as u64;
+        }
+    }
+
+    /// Process as many buffers as are required to cover the given duration of audio.
+    pub fn process_duration(&self, duration: Duration) {
+        let n = buffers_for_duration(duration, self.sample_rate, self.buffer_frames);
+        self.process_buffers(n);
+    }
+
+    pub(crate) fn default_config(&self) -> cpal::SupportedStreamConfig {
+        default_config(self.channels, self.sample_rate)
+    }
+
+    pub(crate) fn supported_config(&self) -> cpal::SupportedStreamConfigRange {
+        supported_config(self.channels, self.sample_rate)
+    }
+
+    // Open a stream on the device.
+    pub(crate) fn open(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+        data: InputDataFn,
+        error: StreamErrorFn,
+    ) -> Result<VirtualStream, cpal::BuildStreamError> {
+        let channels = check_config(config, sample_format, self.channels, self.sample_rate)?;
+        let id = lock(&self.state).streams.open(channels, data, error)?;
+        let device = OpenDevice::Input(self.state.clone());
+        Ok(VirtualStream { id, device })
+    }
+}
+
+impl VirtualOutputDevice {
+    /// A new output device.
+    ///
+    /// By default, the device has `DEFAULT_CHANNELS` channels at `DEFAULT_SAMPLE_RATE` and
+    /// processes buffers of `DEFAULT_BUFFER_FRAMES` frames.
+    pub fn new(name: impl Into<String>) -> Self {
+        let state = OutputState {
+            streams: Streams::new(),
+            captured: vec![],
+        };
+        VirtualOutputDevice {
+            name: name.into(),
+            channels: DEFAULT_CHANNELS,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            buffer_frames: DEFAULT_BUFFER_FRAMES,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Specify the number of channels of the device.
+    ///
+    /// By default, this value is `DEFAULT_CHANNELS`.
+    pub fn channels(mut self, channels: usize) -> Self {
+        assert!(channels > 0);
+        self.channels = channels;
+        self
+    }
+
+    /// Specify the sample rate of the device.
+    ///
+    /// By default, this value is `DEFAULT_SAMPLE_RATE`.
+    pub fn sample_rate(mut self, sample_rate: u32) -> Self {
+        assert!(sample_rate > 0);
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Specify the number of frames in each buffer processed by the device.
+    ///
+    /// By default, this value is `DEFAULT_BUFFER_FRAMES`.
+    pub fn buffer_frames(mut self, frames: usize) -> Self {
+        assert!(frames > 0);
+        self.buffer_frames = frames;
+        self
+    }
+
+    /// The name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Cause the given number of following attempts to open a stream on the device to fail with
+    /// `BuildStreamError::DeviceNotAvailable`, e.g. for testing retry policies.
+    pub fn fail_next_opens(&self, count: usize) {
+        lock(&self.state).streams.failing_opens = count;
+    }
+
+    /// The number of streams currently open on the device.
+    pub fn num_streams(&self) -> usize {
+        lock(&self.state).streams.open.len()
+    }
+
+    /// The total number of frames processed by the device so far.
+    pub fn frames_processed(&self) -> u64 {
+        lock(&self.state).streams.frames_processed
+    }
+
+    /// Deliver a backend-specific error with the given description to each stream open on the
+    /// device.
+    pub fn emit_error(&self, description: impl Into<String>) {
+        lock(&self.state).streams.emit_error(description.into());
+    }
+
+    /// Request the given number of buffers from all playing streams, recording the mix of their
+    /// output.
+    ///
+    /// Streams with fewer channels than the device are written to the device's leading channels.
+    /// Buffers are recorded even while no streams are playing, in which case they are silent.
+    pub fn process_buffers(&self, n: usize) {
+        let mut state = lock(&self.state);
+        let OutputState {
+            ref mut streams,
+            ref mut captured,
+        } = *state;
+        let mut buffer = vec![];
+        for _ in 0..n {
+            let start = captured.len();
+            captured.resize(start + self.buffer_frames * self.channels, 0.0);
+            let mixed = &mut captured[start..];
+            for stream in streams.open.iter_mut().filter(|stream| stream.is_playing) {
+                let channels = stream.channels;
+                buffer.clear();
+                buffer.resize(self.buffer_frames * channels, 0.0);
+                (stream.data)(&mut buffer);
+                for (out, frame) in mixed.chunks_mut(self.channels).zip(buffer.chunks(channels)) {
+                    for (out_sample, &sample) in out.iter_mut().zip(frame) {
+                        *out_sample += sample;
+                    }
+                }
+            }
+            streams.frames_processed += self.buffer_frames as u64;
+        }
+    }
+
+    /// Process as many buffers as are required to cover the given duration of audio.
+    pub fn process_duration(&self, duration: Duration) {
+        let n = buffers_for_duration(duration, self.sample_rate, self.buffer_frames);
+        self.process_buffers(n);
+    }
+
+    /// All interleaved samples recorded by the device so far.
+    pub fn captured(&self) -> Vec<f32> {
+        lock(&self.state).captured.clone()
+    }
+
+    /// Take all interleaved samples recorded so far, clearing the device's record of them.
+    pub fn take_captured(&self) -> Vec<f32> {
+        std::mem::take(&mut lock(&self.state).captured)
+    }
+
+    pub(crate) fn default_config(&self) -> cpal::SupportedStreamConfig {
+        default_config(self.channels, self.sample_rate)
+    }
+
+    pub(crate) fn supported_config(&self) -> cpal::SupportedStreamConfigRange {
+        supported_config(self.channels, self.sample_rate)
+    }
+
+    // Open a stream on the device.
+    pub(crate) fn open(
+        &self,
+        config: &cpal::StreamConfig,
+        sample_format: cpal::SampleFormat,
+        data: OutputDataFn,
+        error: StreamErrorFn,
+    ) -> Result<VirtualStream, cpal::BuildStreamError> {
+        let channels = check_config(config, sample_format, self.channels, self.sample_rate)?;
+        let id = lock(&self.state).streams.open(channels, data, error)?;
+        let device = OpenDevice::Output(self.state.clone());
+        Ok(VirtualStream { id, device })
+    }
+}
+
+impl InputSource {
+    /// A sine wave with the given frequency and amplitude.
+    pub fn sine(hz: f64, amplitude: f32) -> Self {
+        InputSource::Sine { hz, amplitude }
+    }
+
+    /// White noise with the given amplitude and a fixed seed.
+    pub fn noise(amplitude: f32) -> Self {
+        InputSource::Noise {
+            seed: 0x2545_F491_4F6C_DD1D,
+            amplitude,
+        }
+    }
+
+    /// The given interleaved samples, played once.
+    pub fn samples(interleaved: impl Into<Arc<[f32]>>) -> Self {
+        InputSource::Samples {
+            interleaved: interleaved.into(),
+            looped: false,
+        }
+    }
+}
+
+impl VirtualStream {
+    // Play or pause the stream.
+    pub(crate) fn set_playing(&self, playing: bool) {
+        match self.device {
+            OpenDevice::Input(ref state) => lock(state).streams.set_playing(self.id, playing),
+            OpenDevice::Output(ref state) => lock(state).streams.set_playing(self.id, playing),
+        }
+    }
+}
+
+impl<D> Streams<D> {
+    fn new() -> Self {
+        Streams {
+            open: vec![],
+            next_id: 0,
+            failing_opens: 0,
+            frames_processed: 0,
+        }
+    }
+
+    fn open(
+        &mut self,
+        channels: usize,
+        data: D,
+        error: StreamErrorFn,
+    ) -> Result<u64, cpal::BuildStreamError> {
+        if self.failing_opens > 0 {
+            self.failing_opens -= 1;
+            return Err(cpal::BuildStreamError::DeviceNotAvailable);
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.open.push(OpenStream {
+            id,
+            channels,
+            is_playing: false,
+            data,
+            error,
+        });
+        Ok(id)
+    }
+
+    fn close(&mut self, id: u64) -> Option<OpenStream<D>> {
+        let ix = self.open.iter().position(|stream| stream.id == id)?;
+        Some(self.open.remove(ix))
+    }
+
+    fn set_playing(&mut self, id: u64, playing: bool) {
+        if let Some(stream) = self.open.iter_mut().find(|stream| stream.id == id) {
+            stream.is_playing = playing;
+        }
+    }
+
+    fn emit_error(&mut self, description: String) {
+        for stream in &mut self.open {
+            let err = cpal::BackendSpecificError {
+                description: description.clone(),
+            };
+            (stream.error)(cpal::StreamError::BackendSpecific { err });
+        }
+    }
+}
+
+impl Default for VirtualHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for VirtualStream {
+    fn drop(&mut self) {
+        // Close the stream, dropping its callbacks only once the device is unlocked.
+        match self.device {
+            OpenDevice::Input(ref state) => drop(lock(state).streams.close(self.id)),
+            OpenDevice::Output(ref state) => drop(lock(state).streams.close(self.id)),
+        }
+    }
+}
+
+impl<D> fmt::Debug for Streams<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Streams")
+            .field("open", &self.open.len())
+            .field("failing_opens", &self.failing_opens)
+            .field("frames_processed", &self.frames_processed)
+            .finish()
+    }
+}
+
+impl Generator {
+    fn new(source: &InputSource) -> Self {
+        let rng = match *source {
+            // Xorshift requires a non-zero state.
+            InputSource::Noise { seed, .. } => std::cmp::max(seed, 1),
+            _ => 1,
+        };
+        Generator { frame: 0, rng }
+    }
+
+    // Write the next frame from the source to the given frame.
+    fn next_frame(&mut self, source: &InputSource, channels: usize, hz: u32, frame: &mut [f32]) {
+        match *source {
+            InputSource::Silence => {
+                for s in frame.iter_mut() {
+                    *s = 0.0;
+                }
+            }
+            InputSource::Sine {
+                hz: freq,
+                amplitude,
+            } => {
+                let phase = (self.frame as f64 * freq / hz as f64).fract();
+                let v = (phase * 2.0 * std::f64::consts::PI).sin() as f32 * amplitude;
+                for s in frame.iter_mut() {
+                    *s = v;
+                }
+            }
+            InputSource::Noise { amplitude, .. } => {
+                for s in frame.iter_mut() {
+                    // xorshift64
+                    self.rng ^= self.rng << 13;
+                    self.rng ^= self.rng >> 7;
+                    self.rng ^= self.rng << 17;
+                    let unit = (self.rng >> 40) as f32 / (1u64 << 24) as f32;
+                    *s = (unit * 2.0 - 1.0) * amplitude;
+                }
+            }
+            InputSource::Samples {
+                ref interleaved,
+                looped,
+            } => {
+                let len_frames = (interleaved.len() / channels) as u64;
+                let ix = match (looped, len_frames) {
+                    (_, 0) => None,
+                    (true, _) => Some(self.frame % len_frames),
+                    (false, _) if self.frame < len_frames => Some(self.frame),
+                    (false, _) => None,
+                };
+                match ix {
+                    Some(ix) => {
+                        let start = ix as usize * channels;
+                        let src = &interleaved[start..start + channels];
+                        frame.copy_from_slice(&src[..frame.len()]);
+                    }
+                    None => {
+                        for s in frame.iter_mut() {
+                            *s = 0.0;
+                        }
+                    }
+                }
+            }
+        }
+        self.frame += 1;
+    }
+}
+
+// Lock the given device state, recovering it if a callback panicked while it was locked.
+fn lock<T>(state: &Mutex<T>) -> MutexGuard<T> {
+    match state.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// The only config supported by a virtual device.
+fn default_config(channels: usize, sample_rate: u32) -> cpal::SupportedStreamConfig {
+    cpal::SupportedStreamConfig::new(
+        channels as cpal::ChannelCount,
+        cpal::SampleRate(sample_rate),
+        cpal::SupportedBufferSize::Unknown,
+        cpal::SampleFormat::F32,
+    )
+}
+
+// The range of configs supported by a virtual device, containing only the default config.
+fn supported_config(channels: usize, sample_rate: u32) -> cpal::SupportedStreamConfigRange {
+    cpal::SupportedStreamConfigRange::new(
+        channels as cpal::ChannelCount,
+        cpal::SampleRate(sample_rate),
+        cpal::SampleRate(sample_rate),
+        cpal::SupportedBufferSize::Unknown,
+        cpal::SampleFormat::F32,
+    )
+}
+
+// Check that the given config may be opened on a device, returning the stream's channel count.
+fn check_config(
+    config: &cpal::StreamConfig,
+    sample_format: cpal::SampleFormat,
+    channels: usize,
+    sample_rate: u32,
+) -> Result<usize, cpal::BuildStreamError> {
+    let stream_channels = config.channels as usize;
+    if sample_format != cpal::SampleFormat::F32
+        || stream_channels == 0
+        || stream_channels > channels
+        || config.sample_rate.0 != sample_rate
+    {
+        return Err(cpal::BuildStreamError::StreamConfigNotSupported);
+    }
+    Ok(stream_channels)
+}
+
+// The number of buffers of the given length required to cover the given duration.
+fn buffers_for_duration(duration: Duration, sample_rate: u32, frames_per_buffer: usize) -> usize {
+    let frames = (duration.as_secs_f64() * sample_rate as f64).ceil() as usize;
+    (frames + frames_per_buffer - 1) / frames_per_buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Buffer, Error};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    fn render_model(model: &mut f32, buffer: &mut Buffer) {
+        for sample in buffer.iter_mut() {
+            *sample = *model;
+        }
+    }
+
+    // A model that counts the number of times it has been dropped.
+    struct Tracked {
+        value: f32,
+        drops: Arc<AtomicUsize>,
+    }
+
+    impl Tracked {
+        fn new(value: f32, drops: &Arc<AtomicUsize>) -> Self {
+            let drops = drops.clone();
+            Tracked { value, drops }
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.drops.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn render_tracked(model: &mut Tracked, buffer: &mut Buffer) {
+        for sample in buffer.iter_mut() {
+            *sample = model.value;
+        }
+    }
+
+    // The duration of the given number of buffers of the default virtual device.
+    fn buffers_duration(buffers: usize) -> Duration {
+        let frames = (buffers * DEFAULT_BUFFER_FRAMES) as f64;
+        Duration::from_secs_f64(frames / DEFAULT_SAMPLE_RATE as f64)
+    }
+
+    fn tracked_stream(host: &VirtualHost, drops: &Arc<AtomicUsize>) -> stream::Stream<Tracked> {
+        host.new_output_stream(Tracked::new(1.0, drops))
+            .render(render_tracked)
+            .build()
+            .unwrap()
+    }
+
+    fn output_host() -> (VirtualHost, VirtualOutputDevice) {
+        let device = VirtualOutputDevice::new("out");
+        let host = VirtualHost::empty().with_output_device(device.clone());
+        (host, device)
+    }
+
+    fn input_host(device: VirtualInputDevice) -> VirtualHost {
+        VirtualHost::empty().with_input_device(device)
+    }
+
+    #[test]
+    fn output_renders_through_stream() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(0.5f32)
+            .render(render_model)
+            .frames_per_buffer(48)
+            .build()
+            .unwrap();
+        assert_eq!(device.num_streams(), 1);
+        stream.play().unwrap();
+        device.process_buffers(3);
+        let captured = device.captured();
+        assert_eq!(captured.len(), 3 * DEFAULT_BUFFER_FRAMES * DEFAULT_CHANNELS);
+        assert!(captured.iter().all(|&s| s == 0.5));
+        assert_eq!(device.frames_processed(), 3 * DEFAULT_BUFFER_FRAMES as u64);
+    }
+
+    #[test]
+    fn output_is_silent_until_played_and_closed_on_drop() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(1.0f32)
+            .render(render_model)
+            .build()
+            .unwrap();
+        device.process_buffers(1);
+        assert!(device.take_captured().iter().all(|&s| s == 0.0));
+        stream.play().unwrap();
+        stream.pause().unwrap();
+        device.process_buffers(1);
+        assert!(device.take_captured().iter().all(|&s| s == 0.0));
+        drop(stream);
+        assert_eq!(device.num_streams(), 0);
+    }
+
+    #[test]
+    fn output_maps_stream_channels_to_device() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(1.0f32)
+            .render(render_model)
+            .channels(1)
+            .build()
+            .unwrap();
+        assert_eq!(stream.cpal_config().channels, 1);
+        stream.play().unwrap();
+        device.process_buffers(1);
+        for frame in device.captured().chunks(DEFAULT_CHANNELS) {
+            assert_eq!(frame, &[1.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn output_applies_updates_and_channel_strip() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(1.0f32)
+            .render(render_model)
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        stream.send(|model| *model = 0.25).unwrap();
+        stream.channel_strip().channel(1).set_mute(true);
+        device.process_buffers(1);
+        for frame in device.captured().chunks(DEFAULT_CHANNELS) {
+            assert_eq!(frame, &[0.25, 0.0]);
+        }
+    }
+
+    #[test]
+    fn output_auto_suspends_after_silence() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(false)
+            .auto_suspend(Duration::from_millis(50))
+            .on_suspend(|suspended: &mut bool| *suspended = true)
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        device.process_buffers(1);
+        assert!(!stream.is_suspended());
+        device.process_duration(Duration::from_millis(100));
+        assert!(stream.is_suspended());
+        stream.resume();
+        assert!(!stream.is_suspended());
+        assert_eq!(stream.into_model().ok(), Some(true));
+    }
+
+    #[test]
+    fn output_skips_render_while_suspended_until_resumed() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(0.0f32)
+            .render(render_model)
+            .auto_suspend(buffers_duration(2))
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        device.process_buffers(2);
+        assert!(stream.is_suspended());
+        stream.send(|model| *model = 1.0).unwrap();
+        device.process_buffers(1);
+        assert!(device.take_captured().iter().all(|&s| s == 0.0));
+        stream.resume();
+        device.process_buffers(1);
+        assert!(device.take_captured().iter().all(|&s| s == 1.0));
+        assert!(!stream.is_suspended());
+    }
+
+    #[test]
+    fn output_sound_restarts_the_silence_count() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(0.0f32)
+            .render(render_model)
+            .auto_suspend(buffers_duration(3))
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        device.process_buffers(2);
+        stream.send(|model| *model = 1.0).unwrap();
+        device.process_buffers(1);
+        stream.send(|model| *model = 0.0).unwrap();
+        device.process_buffers(2);
+        assert!(!stream.is_suspended());
+        device.process_buffers(1);
+        assert!(stream.is_suspended());
+    }
+
+    #[test]
+    fn output_suspends_below_the_silence_threshold() {
+        let (host, device) = output_host();
+        let build = |threshold| {
+            host.new_output_stream(0.001f32)
+                .render(render_model)
+                .auto_suspend(buffers_duration(1))
+                .silence_threshold(threshold)
+                .build()
+                .unwrap()
+        };
+        let stream = build(stream::output::DEFAULT_SILENCE_THRESHOLD);
+        stream.play().unwrap();
+        device.process_buffers(4);
+        assert!(!stream.is_suspended());
+        drop(stream);
+        let stream = build(0.01);
+        stream.play().unwrap();
+        device.process_buffers(1);
+        assert!(stream.is_suspended());
+    }
+
+    #[test]
+    fn output_crossfades_swapped_models() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(1.0f32)
+            .render(render_model)
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        device.process_buffers(1);
+        device.take_captured();
+        let frames = DEFAULT_BUFFER_FRAMES as f64;
+        let duration = Duration::from_secs_f64(frames / DEFAULT_SAMPLE_RATE as f64);
+        stream.swap_model_crossfade(0.0, duration).unwrap();
+        device.process_buffers(2);
+        let captured = device.captured();
+        let (fade, after) = captured.split_at(DEFAULT_BUFFER_FRAMES * DEFAULT_CHANNELS);
+        assert!(fade[0] > 0.99);
+        assert!(fade.windows(2).all(|w| w[1] <= w[0]));
+        assert!(after.iter().all(|&s| s == 0.0));
+        assert_eq!(stream.into_model().ok(), Some(0.0));
+    }
+
+    #[test]
+    fn output_crossfade_retires_models_to_the_handle() {
+        let (host, device) = output_host();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stream = tracked_stream(&host, &drops);
+        stream.play().unwrap();
+        device.process_buffers(1);
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.5, &drops), buffers_duration(1))
+            .is_ok());
+        device.process_buffers(3);
+
+        // The outgoing model is held until the next swap rather than dropped by the audio thread.
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.25, &drops), Duration::ZERO)
+            .is_ok());
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        device.process_buffers(1);
+        let captured = device.take_captured();
+        assert!(captured.iter().all(|&s| s == 0.25));
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn output_crossfade_retired_models_are_dropped_on_close() {
+        let (host, device) = output_host();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stream = tracked_stream(&host, &drops);
+        stream.play().unwrap();
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.5, &drops), buffers_duration(1))
+            .is_ok());
+        device.process_buffers(2);
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        let model = stream.into_model().ok().unwrap();
+        assert_eq!(model.value, 0.5);
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn output_crossfade_restarts_when_swapped_mid_fade() {
+        let (host, device) = output_host();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stream = tracked_stream(&host, &drops);
+        stream.play().unwrap();
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.5, &drops), buffers_duration(4))
+            .is_ok());
+        device.process_buffers(1);
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.0, &drops), buffers_duration(1))
+            .is_ok());
+        device.take_captured();
+        device.process_buffers(2);
+
+        // The first outgoing model is cut off and the fade continues from the second.
+        let captured = device.captured();
+        let (fade, after) = captured.split_at(DEFAULT_BUFFER_FRAMES * DEFAULT_CHANNELS);
+        assert!(fade[0] > 0.49 && fade[0] <= 0.5);
+        assert!(fade.windows(2).all(|w| w[1] <= w[0]));
+        assert!(after.iter().all(|&s| s == 0.0));
+
+        // Both outgoing models are returned and dropped by the next swap.
+        assert_eq!(drops.load(Ordering::SeqCst), 0);
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.0, &drops), Duration::ZERO)
+            .is_ok());
+        assert_eq!(drops.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn output_crossfade_while_paused_replaces_immediately() {
+        let (host, _device) = output_host();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stream = tracked_stream(&host, &drops);
+        stream.play().unwrap();
+        stream.pause().unwrap();
+        assert!(stream
+            .swap_model_crossfade(Tracked::new(0.5, &drops), buffers_duration(1))
+            .is_ok());
+        assert_eq!(drops.load(Ordering::SeqCst), 1);
+        assert_eq!(stream.into_model().ok().map(|m| m.value), Some(0.5));
+    }
+
+    #[test]
+    fn output_crossfade_queue_is_bounded() {
+        let (host, _device) = output_host();
+        let drops = Arc::new(AtomicUsize::new(0));
+        let stream = tracked_stream(&host, &drops);
+        stream.play().unwrap();
+        for _ in 0..stream::MAX_PENDING_CROSSFADES {
+            let model = Tracked::new(0.5, &drops);
+            assert!(stream.swap_model_crossfade(model, Duration::ZERO).is_ok());
+        }
+        let model = Tracked::new(0.75, &drops);
+        match stream.swap_model_crossfade(model, Duration::ZERO) {
+            Err(mpsc::TrySendError::Full(model)) => assert_eq!(model.value, 0.75),
+            _ => panic!("expected the crossfade queue to be full"),
+        }
+    }
+
+    #[test]
+    fn output_delivers_device_errors_to_error_fn() {
+        let (host, device) = output_host();
+        let stream = host
+            .new_output_stream(0u32)
+            .error(|errors: &mut u32, _err| *errors += 1)
+            .build()
+            .unwrap();
+        device.emit_error("unplugged");
+        device.emit_error("unplugged");
+        assert_eq!(stream.into_model().ok(), Some(2));
+    }
+
+    #[test]
+    fn output_build_retries_transient_failures() {
+        let (host, device) = output_host();
+        device.fail_next_opens(2);
+        let stream = host
+            .new_output_stream(0.5f32)
+            .render(render_model)
+            .retry(2, Duration::from_millis(1))
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        device.process_buffers(1);
+        assert!(device.captured().iter().all(|&s| s == 0.5));
+    }
+
+    #[test]
+    fn output_build_returns_model_after_retries_are_exhausted() {
+        let (host, device) = output_host();
+        device.fail_next_opens(3);
+        let result = host
+            .new_output_stream(0.5f32)
+            .render(render_model)
+            .retry(1, Duration::from_millis(1))
+            .try_build();
+        match result {
+            Err((Error::BuildStream { .. }, model)) => assert_eq!(model, 0.5),
+            _ => panic!("expected the build to fail"),
+        }
+        assert_eq!(device.num_streams(), 0);
+    }
+
+    #[test]
+    fn output_build_fails_without_device_or_matching_config() {
+        let result = VirtualHost::empty().new_output_stream(()).build();
+        assert!(matches!(result, Err(Error::DefaultOutputDevice)));
+        let (host, _device) = output_host();
+        let result = host.new_output_stream(()).sample_rate(48_000).build();
+        assert!(matches!(result, Err(Error::NoMatchingConfig { .. })));
+    }
+
+    #[test]
+    fn input_captures_through_stream() {
+        let device = VirtualInputDevice::new("sine", InputSource::sine(440.0, 0.5));
+        let host = input_host(device.clone());
+        let stream = host
+            .new_input_stream(0.0f32)
+            .capture(|peak: &mut f32, buffer: &Buffer| {
+                for &s in buffer.iter() {
+                    *peak = peak.max(s.abs());
+                }
+            })
+            .build()
+            .unwrap();
+        stream.play().unwrap();
+        device.process_duration(Duration::from_millis(100));
+        let peak = stream.into_model().ok().unwrap();
+        assert!((peak - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn input_maps_device_channels_and_applies_channel_strip() {
+        let source = InputSource::Samples {
+            interleaved: vec![0.25, 0.75].into(),
+            looped: true,
+        };
+        let device = VirtualInputDevice::new("samples", source);
+        let host = input_host(device.clone());
+        let stream = host
+            .new_input_stream(vec![])
+            .capture(|captured: &mut Vec<f32>, buffer: &Buffer| {
+                captured.extend(buffer.iter().cloned());
+            })
+            .channels(1)
+            .build()
+            .unwrap();
+        stream.channel_strip().channel(0).set_gain(0.5);
+        stream.play().unwrap();
+        device.process_buffers(2);
+        let captured = stream.into_model().ok().unwrap();
+        assert_eq!(captured.len(), 2 * DEFAULT_BUFFER_FRAMES);
+        assert!(captured.iter().all(|&s| s == 0.125));
+    }
+
+    #[test]
+    fn input_is_not_delivered_buffers_until_played() {
+        let device = VirtualInputDevice::new("noise", InputSource::noise(1.0));
+        let host = input_host(device.clone());
+        let stream = host
+            .new_input_stream(0usize)
+            .capture(|buffers: &mut usize, _buffer: &Buffer| *buffers += 1)
+            .build()
+            .unwrap();
+        device.process_buffers(4);
+        assert_eq!(stream.into_model().ok(), Some(0));
+        assert_eq!(device.num_streams(), 0);
+    }
+}
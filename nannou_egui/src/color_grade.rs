@@ -0,0 +1,798 @@
+//! A colour grading editor producing a lookup texture for use within a shader.
+//!
+//! A `ColorGrade` describes a master tone curve, a curve per RGB channel and a saturation
+//! adjustment. The `ColorGradeEditor` widget edits a `ColorGrade` in place, and a `LutTexture`
+//! bakes it into a 3D lookup table stored within a 2D texture, ready to be bound to a
+//! `wgpu::FullScreenPass` and sampled via the `APPLY_LUT_WGSL` function.
+//!
+//! `ColorGrade` implements `serde`'s `Serialize` and `Deserialize`, so grades may be saved and
+//! loaded as presets, e.g. via `nannou::io::save_to_json`.
+//!
+//! ```no_run
+//! use nannou::wgpu;
+//! use nannou_egui::{color_grade::{ColorGrade, ColorGradeEditor, LutTexture}, egui};
+//!
+//! fn ui(ctx: &egui::Context, queue: &wgpu::Queue, grade: &mut ColorGrade, lut: &LutTexture) {
+//!     egui::Window::new("Grade").show(ctx, |ui| {
+//!         if ColorGradeEditor::new("grade").show(ui, grade).changed {
+//!             lut.write(queue, grade);
+//!         }
+//!     });
+//! }
+//! ```
+
+use nannou::wgpu;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+/// A tone curve mapping input values in the range `0.0..=1.0` to output values in the same range.
+///
+/// The curve passes through each of its control points, interpolated with a monotone cubic
+/// spline so that it never overshoots between them.
+///
+/// Deserialized curves are constructed via `Curve::new` and must have at least two points.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "CurveData")]
+pub struct Curve {
+    // The control points sorted by their input value.
+    points: Vec<[f32; 2]>,
+}
+
+// The serialized form of a `Curve`, validated before constructing the curve.
+#[derive(Deserialize)]
+struct CurveData {
+    points: Vec<[f32; 2]>,
+}
+
+/// A complete colour grade: tone curves followed by a saturation adjustment.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorGrade {
+    /// Applied to all three channels before the per-channel curves.
+    pub master: Curve,
+    pub red: Curve,
+    pub green: Curve,
+    pub blue: Curve,
+    /// Scales the distance of each colour from its luma, where `1.0` leaves colours unchanged and
+    /// `0.0` produces greyscale.
+    pub saturation: f32,
+}
+
+/// One of the curves of a `ColorGrade`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum CurveChannel {
+    Master,
+    Red,
+    Green,
+    Blue,
+}
+
+/// A widget for editing the curves and saturation of a `ColorGrade`.
+///
+/// Drag a control point to move it, drag elsewhere or double-click to add a point and
+/// right-click a point to remove it.
+#[derive(Clone, Debug)]
+pub struct ColorGradeEditor {
+    id_source: egui::Id,
+    size: egui::Vec2,
+    saturation_range: std::ops::RangeInclusive<f32>,
+}
+
+/// The result of showing a `ColorGradeEditor`.
+pub struct ColorGradeOutput {
+    /// The response of the editor as a whole.
+    pub response: egui::Response,
+    /// Whether or not the grade was edited this frame, in which case any `LutTexture` baked from
+    /// it should be rewritten.
+    pub changed: bool,
+}
+
+/// A 3D lookup table baked from a `ColorGrade` and stored within a 2D texture.
+///
+/// A LUT of size `N` holds `N` slices of `N * N` texels laid side by side, one slice per blue
+/// value, with red increasing along each slice and green increasing downwards. The texture is
+/// `Rgba8Unorm`, storing the graded values as they are. Sample it with `APPLY_LUT_WGSL` using a
+/// linear filtering sampler.
+#[derive(Debug)]
+pub struct LutTexture {
+    texture: wgpu::Texture,
+    size: u32,
+}
+
+// The state of an editor that persists between frames.
+#[derive(Clone, Default)]
+struct EditorState {
+    channel: CurveChannel,
+    // The index of the control point being dragged.
+    dragging: Option<usize>,
+}
+
+/// A WGSL function applying a `LutTexture` to a colour with components in the range `0.0..=1.0`.
+///
+/// Append this to a fragment shader and call it as `apply_lut(lut, lut_sampler, color.rgb)`.
+pub const APPLY_LUT_WGSL: &str = r#"
+fn apply_lut(lut: texture_2d<f32>, lut_sampler: sampler, color: vec3<f32>) -> vec3<f32> {
+    let size = f32(textureDimensions(lut).y);
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)) * (size - 1.0);
+    let b0 = floor(c.b);
+    let b1 = min(b0 + 1.0, size - 1.0);
+    let width = size * size;
+    let v = (c.g + 0.5) / size;
+    let lo_uv = vec2<f32>((b0 * size + c.r + 0.5) / width, v);
+    let hi_uv = vec2<f32>((b1 * size + c.r + 0.5) / width, v);
+    let lo = textureSampleLevel(lut, lut_sampler, lo_uv, 0.0);
+    let hi = textureSampleLevel(lut, lut_sampler, hi_uv, 0.0);
+    return mix(lo.rgb, hi.rgb, c.b - b0);
+}
+"#;
+
+// The radius within which a pointer grabs a control point.
+const GRAB_RADIUS: f32 = 8.0;
+// The radius with which control points are drawn.
+const POINT_RADIUS: f32 = 4.0;
+// The minimum distance between the input values of neighbouring control points.
+const MIN_POINT_SPACING: f32 = 1.0 / 256.0;
+// The number of segments with which curves are drawn.
+const CURVE_SEGMENTS: usize = 128;
+
+impl Curve {
+    /// The curve through `(0, 0)` and `(1, 1)` that leaves values unchanged.
+    pub fn identity() -> Self {
+        Curve {
+            points: vec![[0.0, 0.0], [1.0, 1.0]],
+        }
+    }
+
+    /// A curve through the given control points.
+    ///
+    /// Points are clamped to the unit square and sorted by their input value. Returns the identity
+    /// curve if no points are given.
+    pub fn new(points: Vec<[f32; 2]>) -> Self {
+        let mut points: Vec<_> = points.into_iter().map(clamp_point).collect();
+        if points.is_empty() {
+            return Self::identity();
+        }
+        points.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap_or(std::cmp::Ordering::Equal));
+        Curve { points }
+    }
+
+    /// The control points sorted by their input value.
+    pub fn points(&self) -> &[[f32; 2]] {
+        &self.points
+    }
+
+    /// Whether or not the curve leaves values unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.points.iter().all(|[x, y]| x == y)
+            && self.points.first().map_or(false, |p| p[0] == 0.0)
+            && self.points.last().map_or(false, |p| p[0] == 1.0)
+    }
+
+    /// Insert the given control point, returning its index.
+    pub fn insert(&mut self, point: [f32; 2]) -> usize {
+        let point = clamp_point(point);
+        let ix = self.points.iter().position(|p| p[0] > point[0]);
+        let ix = ix.unwrap_or(self.points.len());
+        self.points.insert(ix, point);
+        ix
+    }
+
+    /// Move the control point at the given index, constraining it to lie between its neighbours.
+    ///
+    /// **Panic!**s if there is no point at the given index.
+    pub fn move_point(&mut self, ix: usize, point: [f32; 2]) {
+        let [x, y] = clamp_point(point);
+        let min = match ix {
+            0 => 0.0,
+            _ => self.points[ix - 1][0] + MIN_POINT_SPACING,
+        };
+        let max = match self.points.get(ix + 1) {
+            None => 1.0,
+            Some(next) => next[0] - MIN_POINT_SPACING,
+        };
+        let x = if min <= max {
+            x.max(min).min(max)
+        } else {
+            self.points[ix][0]
+        };
+        self.points[ix] = [x, y];
+    }
+
+    /// Remove the control point at the given index.
+    ///
+    /// Curves always retain at least two points, so returns `false` without removing if only two
+    /// remain.
+    pub fn remove(&mut self, ix: usize) -> bool {
+        if self.points.len() <= 2 || ix >= self.points.len() {
+            return false;
+        }
+        self.points.remove(ix);
+        true
+    }
+
+    /// The output value for the given input value.
+    ///
+    /// Inputs beyond the first and last control points produce the value of that point.
+    pub fn eval(&self, x: f32) -> f32 {
+        let points = &self.points;
+        match points.len() {
+            0 => return x,
+            1 => return points[0][1],
+            _ => (),
+        }
+        let first = points[0];
+        let last = points[points.len() - 1];
+        if x <= first[0] {
+            return first[1];
+        } else if x >= last[0] {
+            return last[1];
+        }
+        let ix = points.windows(2).position(|w| x < w[1][0]).unwrap_or(0);
+        let tangents = self.tangents();
+        let ([x0, y0], [x1, y1]) = (points[ix], points[ix + 1]);
+        let h = x1 - x0;
+        if h <= 0.0 {
+            return y1;
+        }
+        // Cubic Hermite interpolation between the two points.
+        let t = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+        let y = h00 * y0 + h10 * h * tangents[ix] + h01 * y1 + h11 * h * tangents[ix + 1];
+        y.max(0.0).min(1.0)
+    }
+
+    // The tangent at each control point, limited per Fritsch-Carlson to preserve monotonicity.
+    fn tangents(&self) -> Vec<f32> {
+        let points = &self.points;
+        let n = points.len();
+        let secants: Vec<f32> = points
+            .windows(2)
+            .map(|w| {
+                let dx = w[1][0] - w[0][0];
+                if dx > 0.0 {
+                    (w[1][1] - w[0][1]) / dx
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let mut tangents = vec![0.0; n];
+        tangents[0] = secants[0];
+        tangents[n - 1] = secants[n - 2];
+        for k in 1..n - 1 {
+            let (a, b) = (secants[k - 1], secants[k]);
+            tangents[k] = if a * b <= 0.0 { 0.0 } else { (a + b) / 2.0 };
+        }
+        for (k, &d) in secants.iter().enumerate() {
+            if d == 0.0 {
+                tangents[k] = 0.0;
+                tangents[k + 1] = 0.0;
+                continue;
+            }
+            let a = tangents[k] / d;
+            let b = tangents[k + 1] / d;
+            let len_sq = a * a + b * b;
+            if len_sq > 9.0 {
+                let t = 3.0 / len_sq.sqrt();
+                tangents[k] = t * a * d;
+                tangents[k + 1] = t * b * d;
+            }
+        }
+        tangents
+    }
+}
+
+impl TryFrom<CurveData> for Curve {
+    type Error = String;
+    fn try_from(data: CurveData) -> Result<Self, Self::Error> {
+        if data.points.len() < 2 {
+            let n = data.points.len();
+            return Err(format!("a curve requires at least 2 points, found {}", n));
+        }
+        Ok(Curve::new(data.points))
+    }
+}
+
+impl ColorGrade {
+    /// The grade that leaves colours unchanged.
+    pub fn identity() -> Self {
+        ColorGrade {
+            master: Curve::identity(),
+            red: Curve::identity(),
+            green: Curve::identity(),
+            blue: Curve::identity(),
+            saturation: 1.0,
+        }
+    }
+
+    /// The curve for the given channel.
+    pub fn curve(&self, channel: CurveChannel) -> &Curve {
+        match channel {
+            CurveChannel::Master => &self.master,
+            CurveChannel::Red => &self.red,
+            CurveChannel::Green => &self.green,
+            CurveChannel::Blue => &self.blue,
+        }
+    }
+
+    /// Mutable access to the curve for the given channel.
+    pub fn curve_mut(&mut self, channel: CurveChannel) -> &mut Curve {
+        match channel {
+            CurveChannel::Master => &mut self.master,
+            CurveChannel::Red => &mut self.red,
+            CurveChannel::Green => &mut self.green,
+            CurveChannel::Blue => &mut self.blue,
+        }
+    }
+
+    /// Apply the grade to the given colour with components in the range `0.0..=1.0`.
+    pub fn apply(&self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+        let r = self.red.eval(self.master.eval(r));
+        let g = self.green.eval(self.master.eval(g));
+        let b = self.blue.eval(self.master.eval(b));
+        saturate([r, g, b], self.saturation)
+    }
+
+    /// Bake the grade into a lookup table of the given size in the layout of a `LutTexture`.
+    ///
+    /// Returns `size * size * size` RGBA texels of 8 bits per component.
+    ///
+    /// **Panic!**s if `size` is less than `2`.
+    pub fn bake_lut(&self, size: u32) -> Vec<u8> {
+        assert!(size >= 2, "a LUT requires a size of at least 2");
+        let n = size as usize;
+        // The curves are applied per channel, so only need evaluating once per grid value.
+        let table = |curve: &Curve| -> Vec<f32> {
+            (0..n)
+                .map(|i| curve.eval(self.master.eval(i as f32 / (n - 1) as f32)))
+                .collect()
+        };
+        let (reds, greens, blues) = (table(&self.red), table(&self.green), table(&self.blue));
+        let mut data = vec![0u8; n * n * n * 4];
+        for (g_ix, &g) in greens.iter().enumerate() {
+            for (b_ix, &b) in blues.iter().enumerate() {
+                for (r_ix, &r) in reds.iter().enumerate() {
+                    let [r, g, b] = saturate([r, g, b], self.saturation);
+                    let texel = (g_ix * n * n + b_ix * n + r_ix) * 4;
+                    data[texel] = unorm8(r);
+                    data[texel + 1] = unorm8(g);
+                    data[texel + 2] = unorm8(b);
+                    data[texel + 3] = 255;
+                }
+            }
+        }
+        data
+    }
+}
+
+impl CurveChannel {
+    /// All channels in the order in which they are presented by the editor.
+    pub const ALL: [Self; 4] = [Self::Master, Self::Red, Self::Green, Self::Blue];
+
+    /// A short name for the channel.
+    pub fn label(&self) -> &'static str {
+        match *self {
+            CurveChannel::Master => "RGB",
+            CurveChannel::Red => "R",
+            CurveChannel::Green => "G",
+            CurveChannel::Blue => "B",
+        }
+    }
+
+    // The colour with which the channel's curve is drawn.
+    fn color(&self, visuals: &egui::Visuals) -> egui::Color32 {
+        match *self {
+            CurveChannel::Master => visuals.strong_text_color(),
+            CurveChannel::Red => egui::Color32::from_rgb(230, 80, 80),
+            CurveChannel::Green => egui::Color32::from_rgb(80, 200, 100),
+            CurveChannel::Blue => egui::Color32::from_rgb(90, 140, 240),
+        }
+    }
+}
+
+impl ColorGradeEditor {
+    /// The default size of the curve plot in points.
+    pub const DEFAULT_SIZE: egui::Vec2 = egui::Vec2::new(256.0, 256.0);
+    /// The default range of the saturation slider.
+    pub const DEFAULT_SATURATION_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+
+    /// An editor whose state is identified by the given source, which must be unique within the
+    /// parent `Ui`.
+    pub fn new(id_source: impl Hash) -> Self {
+        ColorGradeEditor {
+            id_source: egui::Id::new(id_source),
+            size: Self::DEFAULT_SIZE,
+            saturation_range: Self::DEFAULT_SATURATION_RANGE,
+        }
+    }
+
+    /// The size of the curve plot in points.
+    ///
+    /// By default, this is `ColorGradeEditor::DEFAULT_SIZE`.
+    pub fn size(mut self, size: egui::Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// The range of the saturation slider.
+    ///
+    /// By default, this is `ColorGradeEditor::DEFAULT_SATURATION_RANGE`.
+    pub fn saturation_range(mut self, range: std::ops::RangeInclusive<f32>) -> Self {
+        self.saturation_range = range;
+        self
+    }
+
+    /// Show the editor within the given `Ui`, applying all edits to the given grade.
+    pub fn show(&self, ui: &mut egui::Ui, grade: &mut ColorGrade) -> ColorGradeOutput {
+        let id = ui.make_persistent_id(self.id_source);
+        let mut state: EditorState = ui.data_mut(|data| data.get_temp(id)).unwrap_or_default();
+        let mut changed = false;
+        let response = ui.vertical(|ui| {
+            // The channel selector.
+            ui.horizontal(|ui| {
+                for &channel in CurveChannel::ALL.iter() {
+                    let label = ui.selectable_label(state.channel == channel, channel.label());
+                    if label.clicked() {
+                        state.channel = channel;
+                        state.dragging = None;
+                    }
+                }
+                ui.separator();
+                let reset = ui.button("Reset").on_hover_text("Reset the selected curve");
+                let curve = grade.curve_mut(state.channel);
+                if reset.clicked() && !curve.is_identity() {
+                    *curve = Curve::identity();
+                    state.dragging = None;
+                    changed = true;
+                }
+            });
+
+            // The curve plot.
+            let curve = grade.curve_mut(state.channel);
+            changed |= self.curve_ui(ui, curve, state.channel, &mut state.dragging);
+
+            // The saturation.
+            let slider = egui::Slider::new(&mut grade.saturation, self.saturation_range.clone())
+                .text("Saturation");
+            changed |= ui.add(slider).changed();
+        });
+        ui.data_mut(|data| data.insert_temp(id, state));
+        ColorGradeOutput {
+            response: response.response,
+            changed,
+        }
+    }
+
+    // Show the plot of the given curve, returning whether or not it was edited.
+    fn curve_ui(
+        &self,
+        ui: &mut egui::Ui,
+        curve: &mut Curve,
+        channel: CurveChannel,
+        dragging: &mut Option<usize>,
+    ) -> bool {
+        let (response, painter) = ui.allocate_painter(self.size, egui::Sense::click_and_drag());
+        let rect = response.rect;
+        let to_screen = |[x, y]: [f32; 2]| {
+            egui::pos2(
+                rect.left() + x * rect.width(),
+                rect.bottom() - y * rect.height(),
+            )
+        };
+        let from_screen = |pos: egui::Pos2| {
+            let x = (pos.x - rect.left()) / rect.width();
+            let y = (rect.bottom() - pos.y) / rect.height();
+            clamp_point([x, y])
+        };
+        // The index of the point within grabbing distance of the given position, if any.
+        let grabbed = |curve: &Curve, pos: egui::Pos2| {
+            curve
+                .points()
+                .iter()
+                .map(|&p| to_screen(p).distance(pos))
+                .enumerate()
+                .filter(|&(_, dist)| dist <= GRAB_RADIUS)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(ix, _)| ix)
+        };
+
+        // Apply interactions.
+        let mut changed = false;
+        let pointer = response.interact_pointer_pos();
+        if response.drag_started() {
+            if let Some(pos) = pointer {
+                *dragging = match grabbed(curve, pos) {
+                    Some(ix) => Some(ix),
+                    None => {
+                        changed = true;
+                        Some(curve.insert(from_screen(pos)))
+                    }
+                };
+            }
+        }
+        if response.dragged() {
+            if let (Some(ix), Some(pos)) = (*dragging, pointer) {
+                if ix < curve.points().len() {
+                    curve.move_point(ix, from_screen(pos));
+                    changed = true;
+                }
+            }
+        }
+        if response.drag_released() {
+            *dragging = None;
+        }
+        if response.double_clicked() {
+            if let Some(pos) = pointer {
+                if grabbed(curve, pos).is_none() {
+                    curve.insert(from_screen(pos));
+                    changed = true;
+                }
+            }
+        }
+        if response.secondary_clicked() {
+            if let Some(ix) = pointer.and_then(|pos| grabbed(curve, pos)) {
+                changed |= curve.remove(ix);
+                *dragging = None;
+            }
+        }
+
+        // The background, grid and identity line.
+        let visuals = ui.visuals();
+        let grid_stroke = visuals.widgets.noninteractive.bg_stroke;
+        painter.rect_filled(rect, 0.0, visuals.extreme_bg_color);
+        for i in 1..4 {
+            let t = i as f32 / 4.0;
+            painter.line_segment([to_screen([t, 0.0]), to_screen([t, 1.0])], grid_stroke);
+            painter.line_segment([to_screen([0.0, t]), to_screen([1.0, t])], grid_stroke);
+        }
+        painter.line_segment([to_screen([0.0, 0.0]), to_screen([1.0, 1.0])], grid_stroke);
+        painter.rect_stroke(rect, 0.0, grid_stroke);
+
+        // The curve and its control points.
+        let color = channel.color(visuals);
+        let line = (0..=CURVE_SEGMENTS)
+            .map(|i| {
+                let x = i as f32 / CURVE_SEGMENTS as f32;
+                to_screen([x, curve.eval(x)])
+            })
+            .collect();
+        painter.add(egui::Shape::line(line, egui::Stroke::new(1.5, color)));
+        for (ix, &p) in curve.points().iter().enumerate() {
+            let fill = match *dragging == Some(ix) {
+                true => color,
+                false => visuals.extreme_bg_color,
+            };
+            painter.circle(
+                to_screen(p),
+                POINT_RADIUS,
+                fill,
+                egui::Stroke::new(1.5, color),
+            );
+        }
+
+        changed
+    }
+}
+
+impl LutTexture {
+    /// The default size of each dimension of the lookup table.
+    pub const DEFAULT_SIZE: u32 = 32;
+    /// The format of the lookup texture.
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    /// Create a lookup texture of `DEFAULT_SIZE` baked from the given grade.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, grade: &ColorGrade) -> Self {
+        Self::with_size(device, queue, grade, Self::DEFAULT_SIZE)
+    }
+
+    /// Create a lookup texture of the given size baked from the given grade.
+    ///
+    /// Larger sizes reproduce sharp curves more faithfully at the cost of memory.
+    ///
+    /// **Panic!**s if `size` is less than `2`.
+    pub fn with_size(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        grade: &ColorGrade,
+        size: u32,
+    ) -> Self {
+        assert!(size >= 2, "a LUT requires a size of at least 2");
+        let texture = wgpu::TextureBuilder::new()
+            .size([size * size, size])
+            .format(Self::FORMAT)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+            .build(device);
+        let lut = LutTexture { texture, size };
+        lut.write(queue, grade);
+        lut
+    }
+
+    /// Re-bake the given grade into the texture, e.g. after it was changed by an editor.
+    pub fn write(&self, queue: &wgpu::Queue, grade: &ColorGrade) {
+        let data = grade.bake_lut(self.size);
+        let width = self.size * self.size;
+        let layout = wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(width * 4),
+            rows_per_image: None,
+        };
+        let extent = wgpu::Extent3d {
+            width,
+            height: self.size,
+            depth_or_array_layers: 1,
+        };
+        queue.write_texture(self.texture.as_image_copy(), &data, layout, extent);
+    }
+
+    /// The size of each dimension of the lookup table.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// The texture holding the lookup table.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Default for ColorGrade {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Default for CurveChannel {
+    fn default() -> Self {
+        CurveChannel::Master
+    }
+}
+
+fn clamp_point([x, y]: [f32; 2]) -> [f32; 2] {
+    [x.max(0.0).min(1.0), y.max(0.0).min(1.0)]
+}
+
+// Scale the distance of the given colour from its Rec. 709 luma.
+fn saturate([r, g, b]: [f32; 3], saturation: f32) -> [f32; 3] {
+    if saturation == 1.0 {
+        return [r, g, b];
+    }
+    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let s = |c: f32| (luma + (c - luma) * saturation).max(0.0).min(1.0);
+    [s(r), s(g), s(b)]
+}
+
+fn unorm8(v: f32) -> u8 {
+    (v.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    // The RGB of the texel at the given grid indices of a baked LUT of the given size.
+    fn texel(data: &[u8], size: u32, [r, g, b]: [usize; 3]) -> [u8; 3] {
+        let n = size as usize;
+        let ix = (g * n * n + b * n + r) * 4;
+        [data[ix], data[ix + 1], data[ix + 2]]
+    }
+
+    #[test]
+    fn identity_eval() {
+        let curve = Curve::identity();
+        assert!(curve.is_identity());
+        for i in 0..=10 {
+            let x = i as f32 / 10.0;
+            assert!(approx_eq(curve.eval(x), x));
+        }
+    }
+
+    #[test]
+    fn eval_passes_through_points() {
+        let points = vec![[0.0, 0.1], [0.3, 0.6], [0.7, 0.65], [1.0, 0.9]];
+        let curve = Curve::new(points.clone());
+        for [x, y] in points {
+            assert!(approx_eq(curve.eval(x), y), "{} -> {}", x, curve.eval(x));
+        }
+    }
+
+    #[test]
+    fn eval_holds_beyond_end_points() {
+        let curve = Curve::new(vec![[0.2, 0.3], [0.8, 0.7]]);
+        assert_eq!(curve.eval(0.0), 0.3);
+        assert_eq!(curve.eval(-1.0), 0.3);
+        assert_eq!(curve.eval(1.0), 0.7);
+        assert_eq!(curve.eval(2.0), 0.7);
+    }
+
+    #[test]
+    fn eval_is_monotone_between_points() {
+        let curve = Curve::new(vec![[0.0, 0.0], [0.1, 0.8], [0.2, 0.85], [1.0, 1.0]]);
+        let mut prev = curve.eval(0.0);
+        for i in 1..=1_000 {
+            let y = curve.eval(i as f32 / 1_000.0);
+            assert!(y >= prev - 1e-6, "overshoot at {}", i);
+            assert!(y <= 1.0);
+            prev = y;
+        }
+    }
+
+    #[test]
+    fn new_clamps_and_sorts_points() {
+        let curve = Curve::new(vec![[1.5, 0.5], [-1.0, 2.0], [0.5, 0.25]]);
+        assert_eq!(curve.points(), &[[0.0, 1.0], [0.5, 0.25], [1.0, 0.5]]);
+        assert_eq!(Curve::new(vec![]), Curve::identity());
+    }
+
+    #[test]
+    fn deserialize_goes_through_new() {
+        let json = r#"{"points": [[1.0, 1.0], [0.5, 2.0], [-0.5, 0.0]]}"#;
+        let curve: Curve = serde_json::from_str(json).unwrap();
+        assert_eq!(curve, Curve::new(vec![[1.0, 1.0], [0.5, 2.0], [-0.5, 0.0]]));
+        assert_eq!(curve.points()[1], [0.5, 1.0]);
+        assert!(serde_json::from_str::<Curve>(r#"{"points": [[0.5, 0.5]]}"#).is_err());
+        assert!(serde_json::from_str::<Curve>(r#"{"points": []}"#).is_err());
+    }
+
+    #[test]
+    fn grade_round_trip() {
+        let mut grade = ColorGrade::identity();
+        grade.red = Curve::new(vec![[0.0, 0.1], [1.0, 0.9]]);
+        grade.saturation = 0.5;
+        let json = serde_json::to_string(&grade).unwrap();
+        let parsed: ColorGrade = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, grade);
+    }
+
+    #[test]
+    fn identity_lut() {
+        let size = 4;
+        let data = ColorGrade::identity().bake_lut(size);
+        assert_eq!(data.len(), 4 * 4 * 4 * 4);
+        let unit = |i: usize| unorm8(i as f32 / 3.0);
+        for r in 0..4 {
+            for g in 0..4 {
+                for b in 0..4 {
+                    let expected = [unit(r), unit(g), unit(b)];
+                    assert_eq!(texel(&data, size, [r, g, b]), expected);
+                }
+            }
+        }
+        assert!(data.chunks(4).all(|texel| texel[3] == 255));
+    }
+
+    #[test]
+    fn lut_applies_curves_and_saturation() {
+        let mut grade = ColorGrade::identity();
+        grade.red = Curve::new(vec![[0.0, 1.0], [1.0, 0.0]]);
+        let data = grade.bake_lut(2);
+        assert_eq!(texel(&data, 2, [0, 0, 0]), [255, 0, 0]);
+        assert_eq!(texel(&data, 2, [1, 1, 1]), [0, 255, 255]);
+
+        grade.red = Curve::identity();
+        grade.saturation = 0.0;
+        let data = grade.bake_lut(2);
+        let luma = unorm8(0.2126);
+        assert_eq!(texel(&data, 2, [1, 0, 0]), [luma; 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn lut_requires_size_of_two() {
+        ColorGrade::identity().bake_lut(1);
+    }
+}
@@ -0,0 +1,455 @@
+//! A headless harness for driving a UI with synthetic input in automated tests.
+//!
+//! A `Harness` runs a UI closure against an `egui::Context` without a window. Input is described
+//! via methods such as `click`, `type_text` and `key_press`, and is delivered to the UI on the
+//! next call to `run`. Each run yields a `FrameOutput` with the tessellated primitives, which is
+//! often enough to check that a control panel lays out and responds as expected.
+//!
+//! When a GPU device is available, `render_to_texture` renders the most recent frame to an
+//! offscreen texture which may be read back, e.g. via `wgpu::TextureCapturer`, for comparison
+//! against a reference image.
+//!
+//! ```
+//! use nannou_egui::{egui, harness::Harness};
+//!
+//! let mut harness = Harness::new([320.0, 240.0]);
+//! let mut button_rect = egui::Rect::NOTHING;
+//! let mut clicked = false;
+//!
+//! // Lay out the UI once, then click the button on the second run.
+//! for run in 0..2 {
+//!     if run == 1 {
+//!         harness.click(button_rect.center());
+//!     }
+//!     harness.run(|ctx| {
+//!         egui::CentralPanel::default().show(ctx, |ui| {
+//!             let response = ui.button("Reset");
+//!             button_rect = response.rect;
+//!             clicked |= response.clicked();
+//!         });
+//!     });
+//! }
+//! assert!(clicked);
+//! ```
+
+use crate::{keyboard, Input, Renderer};
+use egui::{ClippedPrimitive, Event, Key, Modifiers, PlatformOutput, PointerButton, Pos2, Vec2};
+use nannou::wgpu;
+use std::time::Duration;
+
+/// Runs a UI against synthetic input without a window.
+pub struct Harness {
+    context: egui::Context,
+    input: Input,
+    elapsed: Duration,
+    frame_delta: Duration,
+    // The primitives of the most recent frame, retained for rendering.
+    paint_jobs: Vec<ClippedPrimitive>,
+    // All texture changes since the last render.
+    textures_delta: egui::TexturesDelta,
+    // Created on the first render and recreated if the target format changes.
+    renderer: Option<(wgpu::TextureFormat, Renderer)>,
+}
+
+/// The output of a single run of the UI.
+pub struct FrameOutput {
+    /// Output for the platform, e.g. copied text, cursor icon and opened URLs.
+    pub platform_output: PlatformOutput,
+    /// The tessellated primitives of the frame.
+    pub clipped_primitives: Vec<ClippedPrimitive>,
+    /// The delay after which the UI requested to be run again.
+    pub repaint_after: Duration,
+}
+
+impl Harness {
+    /// The default time between runs.
+    pub const DEFAULT_FRAME_DELTA: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    /// Create a harness for a screen of the given size in points with a scale factor of `1.0`.
+    pub fn new(size_points: [f32; 2]) -> Self {
+        Self::with_scale_factor(size_points, 1.0)
+    }
+
+    /// Create a harness for a screen of the given size in points and the given scale factor.
+    pub fn with_scale_factor([w, h]: [f32; 2], scale_factor: f32) -> Self {
+        let size_pixels = [
+            (w * scale_factor).round() as u32,
+            (h * scale_factor).round() as u32,
+        ];
+        Harness {
+            context: Default::default(),
+            input: Input::new(scale_factor, size_pixels),
+            elapsed: Duration::from_secs(0),
+            frame_delta: Self::DEFAULT_FRAME_DELTA,
+            paint_jobs: vec![],
+            textures_delta: Default::default(),
+            renderer: None,
+        }
+    }
+
+    /// Specify the time by which the clock advances on each run.
+    ///
+    /// The clock is advanced deterministically, regardless of the real time between runs, in order
+    /// to keep animations reproducible.
+    ///
+    /// By default, this value is `Harness::DEFAULT_FRAME_DELTA`.
+    pub fn frame_delta(mut self, delta: Duration) -> Self {
+        self.frame_delta = delta;
+        self
+    }
+
+    /// Access to the inner `egui::Context`, e.g. for setting the style before the first run.
+    pub fn ctx(&self) -> &egui::Context {
+        &self.context
+    }
+
+    /// Access to the currently tracked input state.
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    /// The time of the next run since the first run.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Handle a raw window event as though it was received by a window.
+    pub fn handle_raw_event(&mut self, event: &winit::event::WindowEvent) {
+        self.input.handle_raw_event(event);
+    }
+
+    /// Queue an event for delivery on the next run.
+    pub fn event(&mut self, event: Event) {
+        self.input.raw.events.push(event);
+    }
+
+    /// Set the modifiers held for all following events.
+    pub fn set_modifiers(&mut self, modifiers: Modifiers) {
+        self.input.raw.modifiers = modifiers;
+    }
+
+    /// Move the pointer to the given position in points.
+    pub fn pointer_move(&mut self, pos: Pos2) {
+        self.input.pointer_pos = pos;
+        self.event(Event::PointerMoved(pos));
+    }
+
+    /// Press or release the given pointer button at the current pointer position.
+    pub fn pointer_button(&mut self, button: PointerButton, pressed: bool) {
+        self.event(Event::PointerButton {
+            pos: self.input.pointer_pos,
+            button,
+            pressed,
+            modifiers: self.input.raw.modifiers,
+        });
+    }
+
+    /// Move the pointer to the given position, then press and release the primary button.
+    pub fn click(&mut self, pos: Pos2) {
+        self.pointer_move(pos);
+        self.pointer_button(PointerButton::Primary, true);
+        self.pointer_button(PointerButton::Primary, false);
+    }
+
+    /// Remove the pointer from the screen.
+    pub fn pointer_gone(&mut self) {
+        self.event(Event::PointerGone);
+    }
+
+    /// Scroll by the given delta in points.
+    pub fn scroll(&mut self, delta: Vec2) {
+        self.event(Event::Scroll(delta));
+    }
+
+    /// Type the given text into the focused widget.
+    pub fn type_text(&mut self, text: &str) {
+        self.event(Event::Text(text.to_string()));
+    }
+
+    /// Press and release the given key.
+    pub fn key_press(&mut self, key: Key) {
+        for &pressed in &[true, false] {
+            self.event(Event::Key {
+                key,
+                pressed,
+                repeat: false,
+                modifiers: self.input.raw.modifiers,
+            });
+        }
+    }
+
+    /// Run the UI once, delivering all queued input.
+    pub fn run<F>(&mut self, ui_fn: F) -> FrameOutput
+    where
+        F: FnOnce(&egui::Context),
+    {
+        self.input.set_elapsed_time(self.elapsed);
+        keyboard::begin_frame(&self.context, self.input.raw.take());
+        ui_fn(&self.context);
+        let egui::FullOutput {
+            platform_output,
+            repaint_after,
+            textures_delta,
+            shapes,
+            ..
+        } = self.context.end_frame();
+        self.elapsed += self.frame_delta;
+
+        let clipped_primitives = self.context.tessellate(shapes);
+        self.paint_jobs = clipped_primitives.clone();
+        self.textures_delta.append(textures_delta);
+        FrameOutput {
+            platform_output,
+            clipped_primitives,
+            repaint_after,
+        }
+    }
+
+    /// Run the UI the given number of times, returning the output of the last run.
+    ///
+    /// Queued input is delivered on the first run. This is useful for letting layouts settle or
+    /// animations complete.
+    ///
+    /// **Panic!**s if `runs` is `0`.
+    pub fn run_n<F>(&mut self, runs: usize, mut ui_fn: F) -> FrameOutput
+    where
+        F: FnMut(&egui::Context),
+    {
+        assert!(runs > 0, "the UI must be run at least once");
+        for _ in 1..runs {
+            self.run(&mut ui_fn);
+        }
+        self.run(ui_fn)
+    }
+
+    /// Render the most recent frame to a new texture with the given format.
+    ///
+    /// The texture has the size of the harness screen in pixels and is cleared to transparent
+    /// before rendering. It is created with the `COPY_SRC` usage so that it may be read back.
+    pub fn render_to_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::Texture {
+        let size_pixels = self.input.window_size_pixels;
+        let texture = wgpu::TextureBuilder::new()
+            .label("nannou_egui_harness_texture")
+            .size(size_pixels)
+            .format(format)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC)
+            .build(device);
+        let view = texture.view().build();
+
+        if self.renderer.as_ref().map_or(true, |&(f, _)| f != format) {
+            self.renderer = Some((format, Renderer::new(device, format, 1)));
+        }
+        let (_, renderer) = self.renderer.as_mut().expect("renderer was created above");
+        renderer.paint_jobs = self.paint_jobs.clone();
+        renderer.textures_delta = std::mem::take(&mut self.textures_delta);
+
+        let desc = wgpu::CommandEncoderDescriptor {
+            label: Some("nannou_egui_harness_encoder"),
+        };
+        let mut encoder = device.create_command_encoder(&desc);
+        wgpu::clear_texture(&view, wgpu::Color::TRANSPARENT, &mut encoder);
+        renderer
+            .encode_render_pass(
+                device,
+                queue,
+                &mut encoder,
+                size_pixels,
+                self.input.window_scale_factor,
+                &view,
+            )
+            .expect("failed to encode the egui render pass");
+        queue.submit(Some(encoder.finish()));
+        texture
+    }
+}
+
+impl FrameOutput {
+    /// The number of clipped primitives produced by the frame.
+    pub fn primitive_count(&self) -> usize {
+        self.clipped_primitives.len()
+    }
+
+    /// The total number of vertices across all meshes of the frame.
+    pub fn vertex_count(&self) -> usize {
+        self.clipped_primitives
+            .iter()
+            .map(|prim| match prim.primitive {
+                egui::epaint::Primitive::Mesh(ref mesh) => mesh.vertices.len(),
+                egui::epaint::Primitive::Callback(_) => 0,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Transport, TransportBar, TransportEvent, UndoStack};
+
+    fn harness() -> Harness {
+        Harness::new([320.0, 240.0])
+    }
+
+    fn touch(
+        phase: winit::event::TouchPhase,
+        id: u64,
+        pos: Pos2,
+    ) -> winit::event::WindowEvent<'static> {
+        winit::event::WindowEvent::Touch(winit::event::Touch {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            phase,
+            location: winit::dpi::PhysicalPosition::new(pos.x as f64, pos.y as f64),
+            force: None,
+            id,
+        })
+    }
+
+    // Show a single button, returning its rect and whether it was clicked.
+    fn run_button(harness: &mut Harness) -> (egui::Rect, bool) {
+        let mut result = (egui::Rect::NOTHING, false);
+        harness.run(|ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let response = ui.button("Reset");
+                result = (response.rect, response.clicked());
+            });
+        });
+        result
+    }
+
+    // Show a slider for the given value, recording changes to the undo history.
+    fn run_slider(harness: &mut Harness, value: &mut f32, undo: &mut UndoStack<f32>) -> egui::Rect {
+        let mut rect = egui::Rect::NOTHING;
+        harness.run(|ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let slider = egui::Slider::new(&mut *value, 0.0..=1.0).show_value(false);
+                rect = ui.add(slider).rect;
+            });
+            undo.update(ctx, value);
+        });
+        rect
+    }
+
+    // Show a transport bar, returning its rect and events.
+    fn run_transport(
+        harness: &mut Harness,
+        transport: &mut Transport,
+    ) -> (egui::Rect, Vec<TransportEvent>) {
+        let mut output = (egui::Rect::NOTHING, vec![]);
+        harness.run(|ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let bar = TransportBar::new().show(ui, transport);
+                output = (bar.response.rect, bar.events);
+            });
+        });
+        output
+    }
+
+    #[test]
+    fn undo_and_redo_shortcuts() {
+        let mut harness = harness();
+        let mut undo = UndoStack::new();
+        let mut value = 0;
+        harness.run(|ctx| undo.update(ctx, &mut value));
+        value = 1;
+        harness.run(|ctx| undo.update(ctx, &mut value));
+        assert!(undo.can_undo());
+
+        harness.set_modifiers(Modifiers::COMMAND);
+        harness.key_press(Key::Z);
+        harness.run(|ctx| undo.update(ctx, &mut value));
+        assert_eq!(value, 0);
+        assert!(undo.can_redo());
+
+        harness.key_press(Key::Y);
+        harness.run(|ctx| undo.update(ctx, &mut value));
+        assert_eq!(value, 1);
+        assert!(!undo.can_redo());
+    }
+
+    #[test]
+    fn undo_groups_a_slider_drag() {
+        let mut harness = harness();
+        let mut undo = UndoStack::new();
+        let mut value = 0.5f32;
+        let rect = run_slider(&mut harness, &mut value, &mut undo);
+
+        // Drag across the slider over several runs with the button held.
+        harness.pointer_move(rect.center());
+        harness.pointer_button(PointerButton::Primary, true);
+        run_slider(&mut harness, &mut value, &mut undo);
+        harness.pointer_move(rect.right_center());
+        run_slider(&mut harness, &mut value, &mut undo);
+        assert!(value > 0.5);
+        assert!(!undo.can_undo());
+
+        harness.pointer_button(PointerButton::Primary, false);
+        run_slider(&mut harness, &mut value, &mut undo);
+        assert!(undo.can_undo());
+        assert!(undo.undo(&mut value));
+        assert_eq!(value, 0.5);
+        assert!(!undo.can_undo());
+    }
+
+    #[test]
+    fn transport_play_and_pause() {
+        let mut harness = harness();
+        let mut transport = Transport::default();
+        let (rect, events) = run_transport(&mut harness, &mut transport);
+        assert!(events.is_empty());
+
+        // The play/pause button is the first widget within the bar.
+        let play = rect.left_center() + egui::vec2(3.0, 0.0);
+        harness.click(play);
+        let (_, events) = run_transport(&mut harness, &mut transport);
+        assert_eq!(events, [TransportEvent::Play]);
+        assert!(transport.playing);
+
+        harness.click(play);
+        let (_, events) = run_transport(&mut harness, &mut transport);
+        assert_eq!(events, [TransportEvent::Pause]);
+        assert!(!transport.playing);
+    }
+
+    #[test]
+    fn touch_emulates_a_click() {
+        use winit::event::TouchPhase;
+        let mut harness = harness();
+        let (rect, _) = run_button(&mut harness);
+
+        harness.handle_raw_event(&touch(TouchPhase::Started, 1, rect.center()));
+        let (_, clicked) = run_button(&mut harness);
+        assert!(!clicked);
+        assert_eq!(harness.input().pointer_touch_id, Some(1));
+
+        harness.handle_raw_event(&touch(TouchPhase::Ended, 1, rect.center()));
+        let (_, clicked) = run_button(&mut harness);
+        assert!(clicked);
+        assert_eq!(harness.input().pointer_touch_id, None);
+    }
+
+    #[test]
+    fn only_the_first_touch_moves_the_pointer() {
+        use winit::event::TouchPhase;
+        let mut harness = harness();
+        let first = egui::pos2(10.0, 10.0);
+        let second = egui::pos2(100.0, 100.0);
+        harness.handle_raw_event(&touch(TouchPhase::Started, 1, first));
+        harness.handle_raw_event(&touch(TouchPhase::Started, 2, second));
+        harness.handle_raw_event(&touch(TouchPhase::Moved, 2, second + egui::vec2(5.0, 0.0)));
+        harness.run(|_| ());
+        assert_eq!(harness.input().pointer_pos, first);
+        assert_eq!(harness.input().pointer_touch_id, Some(1));
+
+        // A new touch may emulate the pointer once the first has ended.
+        harness.handle_raw_event(&touch(TouchPhase::Ended, 1, first));
+        harness.handle_raw_event(&touch(TouchPhase::Started, 3, second));
+        harness.run(|_| ());
+        assert_eq!(harness.input().pointer_pos, second);
+        assert_eq!(harness.input().pointer_touch_id, Some(3));
+    }
+}
@@ -0,0 +1,392 @@
+//! An on-screen keyboard for text input on touchscreens without a physical keyboard.
+//!
+//! A `VirtualKeyboard` draws a grid of keys described by a `KeyboardLayout`. Pressing a key queues
+//! the equivalent text or key event, which is injected into the context's input at the beginning
+//! of the next frame as though it came from a physical keyboard. This allows regular widgets such
+//! as `egui::TextEdit` to be used unchanged.
+//!
+//! When driving an `egui::Context` manually rather than via `Egui::begin_frame`, use
+//! `keyboard::begin_frame` in place of `egui::Context::begin_frame`.
+
+use egui::{Event, Key, Modifiers};
+
+/// An on-screen keyboard that injects text input into the egui context.
+///
+/// Keys do not take keyboard focus when pressed. Instead, the keyboard remembers the widget that
+/// most recently had focus and returns focus to it when its events are injected.
+#[derive(Clone, Debug)]
+pub struct VirtualKeyboard {
+    layout: KeyboardLayout,
+    key_size: egui::Vec2,
+    shift: bool,
+    caps_lock: bool,
+    target: Option<egui::Id>,
+}
+
+/// The arrangement of keys shown by a `VirtualKeyboard`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyboardLayout {
+    /// The rows of keys from top to bottom.
+    pub rows: Vec<Vec<VirtualKey>>,
+}
+
+/// A single key of a `KeyboardLayout`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VirtualKey {
+    /// Produces the `base` character, or the `shifted` character while shift is active.
+    Char {
+        base: char,
+        shifted: char,
+    },
+    /// Produces the given text with the given label, e.g. for common suffixes such as `".com"`.
+    Text {
+        label: String,
+        text: String,
+    },
+    /// Shifts the next key press. Pressing twice enables caps lock.
+    Shift,
+    Space,
+    Backspace,
+    Enter,
+    Tab,
+    ArrowLeft,
+    ArrowRight,
+}
+
+// Input awaiting injection at the beginning of the next frame, stored within the context.
+#[derive(Clone, Debug, Default)]
+struct InjectedInput {
+    events: Vec<Event>,
+    focus: Option<egui::Id>,
+}
+
+// The ID under which the `InjectedInput` is stored within the context.
+const INJECTED_INPUT_ID: &str = "nannou_egui::keyboard::injected_input";
+
+impl VirtualKeyboard {
+    /// The default size of a single key in points.
+    pub const DEFAULT_KEY_SIZE: egui::Vec2 = egui::vec2(40.0, 40.0);
+
+    /// Create a keyboard with the given layout.
+    pub fn new(layout: KeyboardLayout) -> Self {
+        VirtualKeyboard {
+            layout,
+            key_size: Self::DEFAULT_KEY_SIZE,
+            shift: false,
+            caps_lock: false,
+            target: None,
+        }
+    }
+
+    /// The size of a single character key in points.
+    ///
+    /// Wider keys such as `Space` are sized as a multiple of this.
+    ///
+    /// By default, this value is `VirtualKeyboard::DEFAULT_KEY_SIZE`.
+    pub fn key_size(mut self, size: egui::Vec2) -> Self {
+        self.key_size = size;
+        self
+    }
+
+    /// The layout of the keyboard.
+    pub fn layout(&self) -> &KeyboardLayout {
+        &self.layout
+    }
+
+    /// Replace the layout of the keyboard, e.g. when the user switches language.
+    pub fn set_layout(&mut self, layout: KeyboardLayout) {
+        self.layout = layout;
+    }
+
+    /// Whether or not the next character will be shifted.
+    pub fn is_shifted(&self) -> bool {
+        self.shift || self.caps_lock
+    }
+
+    /// Show the keyboard within the given `Ui`.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> egui::Response {
+        let ctx = ui.ctx().clone();
+        if let Some(id) = ctx.memory(|mem| mem.focus()) {
+            self.target = Some(id);
+        }
+
+        let max_width = self
+            .layout
+            .rows
+            .iter()
+            .map(|row| row_width(row))
+            .fold(0.0, f32::max);
+        let response = ui.vertical(|ui| {
+            for row in 0..self.layout.rows.len() {
+                ui.horizontal(|ui| {
+                    // Centre each row relative to the widest row.
+                    let width = row_width(&self.layout.rows[row]);
+                    ui.add_space((max_width - width) * self.key_size.x * 0.5);
+                    for ix in 0..self.layout.rows[row].len() {
+                        let key = self.layout.rows[row][ix].clone();
+                        if self.key_button(ui, &key).clicked() {
+                            self.press(&ctx, &key);
+                        }
+                    }
+                });
+            }
+        });
+        response.response
+    }
+
+    /// Show the keyboard within a panel along the bottom of the window while any widget wants
+    /// keyboard input, e.g. while a text field is focused.
+    pub fn show_panel(&mut self, ctx: &egui::Context) {
+        if !ctx.wants_keyboard_input() {
+            return;
+        }
+        egui::TopBottomPanel::bottom("nannou_egui_virtual_keyboard").show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                self.show(ui);
+            });
+        });
+    }
+
+    // Add the button for the given key.
+    fn key_button(&self, ui: &mut egui::Ui, key: &VirtualKey) -> egui::Response {
+        let label = match key {
+            VirtualKey::Char { base, shifted } => {
+                let c = if self.is_shifted() { shifted } else { base };
+                c.to_string()
+            }
+            VirtualKey::Text { label, .. } => label.clone(),
+            VirtualKey::Shift if self.caps_lock => "⇪".to_string(),
+            VirtualKey::Shift => "⇧".to_string(),
+            VirtualKey::Space => "␣".to_string(),
+            VirtualKey::Backspace => "⌫".to_string(),
+            VirtualKey::Enter => "⏎".to_string(),
+            VirtualKey::Tab => "⇥".to_string(),
+            VirtualKey::ArrowLeft => "⏴".to_string(),
+            VirtualKey::ArrowRight => "⏵".to_string(),
+        };
+        let selected = matches!(key, VirtualKey::Shift) && self.is_shifted();
+        let size = egui::vec2(self.key_size.x * key.width(), self.key_size.y);
+        // Keys must not take focus from the widget receiving the text.
+        let sense = egui::Sense {
+            click: true,
+            drag: false,
+            focusable: false,
+        };
+        let button = egui::Button::new(label).selected(selected).sense(sense);
+        ui.add_sized(size, button)
+    }
+
+    // Queue the events for the given key along with the widget that should receive them.
+    fn press(&mut self, ctx: &egui::Context, key: &VirtualKey) {
+        let mut events = vec![];
+        match key {
+            VirtualKey::Char { base, shifted } => {
+                let c = if self.is_shifted() { shifted } else { base };
+                events.push(Event::Text(c.to_string()));
+                self.shift = false;
+            }
+            VirtualKey::Text { text, .. } => {
+                events.push(Event::Text(text.clone()));
+                self.shift = false;
+            }
+            VirtualKey::Shift => {
+                if self.caps_lock {
+                    self.caps_lock = false;
+                } else if self.shift {
+                    self.shift = false;
+                    self.caps_lock = true;
+                } else {
+                    self.shift = true;
+                }
+            }
+            VirtualKey::Space => events.push(Event::Text(" ".to_string())),
+            VirtualKey::Backspace => push_key_press(&mut events, Key::Backspace),
+            VirtualKey::Enter => push_key_press(&mut events, Key::Enter),
+            VirtualKey::Tab => push_key_press(&mut events, Key::Tab),
+            VirtualKey::ArrowLeft => push_key_press(&mut events, Key::ArrowLeft),
+            VirtualKey::ArrowRight => push_key_press(&mut events, Key::ArrowRight),
+        }
+        if events.is_empty() {
+            return;
+        }
+        let focus = self.target;
+        ctx.data_mut(|data| {
+            let injected = data.get_temp_mut_or_default::<InjectedInput>(injected_input_id());
+            injected.events.extend(events);
+            injected.focus = focus;
+        });
+        ctx.request_repaint();
+    }
+}
+
+impl KeyboardLayout {
+    /// A layout with a character key for each character of each row.
+    ///
+    /// Shifted characters are the uppercase equivalent of each character. The `control_row` is
+    /// appended to the bottom of the layout.
+    pub fn from_rows(rows: &[&str]) -> Self {
+        let rows = rows
+            .iter()
+            .map(|row| row.chars().map(VirtualKey::char).collect())
+            .collect();
+        KeyboardLayout { rows }.with_control_row()
+    }
+
+    /// A layout with a character key for each pair of characters within each row.
+    ///
+    /// The first string of each row provides the base characters and the second string provides
+    /// the shifted characters. The `control_row` is appended to the bottom of the layout.
+    ///
+    /// **Panic!**s if the strings of any row contain a different number of characters.
+    pub fn from_shifted_rows(rows: &[(&str, &str)]) -> Self {
+        let rows = rows
+            .iter()
+            .map(|(base, shifted)| {
+                assert_eq!(
+                    base.chars().count(),
+                    shifted.chars().count(),
+                    "the base and shifted rows `{}` and `{}` differ in length",
+                    base,
+                    shifted,
+                );
+                base.chars()
+                    .zip(shifted.chars())
+                    .map(|(base, shifted)| VirtualKey::Char { base, shifted })
+                    .collect()
+            })
+            .collect();
+        KeyboardLayout { rows }.with_control_row()
+    }
+
+    /// The row of shift, arrow, space, backspace and enter keys shared by the built-in layouts.
+    pub fn control_row() -> Vec<VirtualKey> {
+        vec![
+            VirtualKey::Shift,
+            VirtualKey::ArrowLeft,
+            VirtualKey::Space,
+            VirtualKey::ArrowRight,
+            VirtualKey::Backspace,
+            VirtualKey::Enter,
+        ]
+    }
+
+    /// A US English QWERTY layout.
+    pub fn qwerty() -> Self {
+        Self::from_shifted_rows(&[
+            ("1234567890-", "!@#$%^&*()_"),
+            ("qwertyuiop", "QWERTYUIOP"),
+            ("asdfghjkl'", "ASDFGHJKL\""),
+            ("zxcvbnm,.?", "ZXCVBNM;:!"),
+        ])
+    }
+
+    /// A German QWERTZ layout.
+    pub fn qwertz() -> Self {
+        Self::from_shifted_rows(&[
+            ("1234567890ß", "!\"§$%&/()=?"),
+            ("qwertzuiopü", "QWERTZUIOPÜ"),
+            ("asdfghjklöä", "ASDFGHJKLÖÄ"),
+            ("yxcvbnm,.-", "YXCVBNM;:_"),
+        ])
+    }
+
+    /// A French AZERTY layout.
+    pub fn azerty() -> Self {
+        Self::from_shifted_rows(&[
+            ("&é\"'(-è_çà", "1234567890"),
+            ("azertyuiop", "AZERTYUIOP"),
+            ("qsdfghjklm", "QSDFGHJKLM"),
+            ("wxcvbn,;:!", "WXCVBN?./§"),
+        ])
+    }
+
+    /// A numeric keypad layout without shift or arrow keys.
+    pub fn numeric() -> Self {
+        let rows = ["789", "456", "123", "0.-"]
+            .iter()
+            .map(|row| row.chars().map(VirtualKey::char).collect())
+            .chain(Some(vec![VirtualKey::Backspace, VirtualKey::Enter]))
+            .collect();
+        KeyboardLayout { rows }
+    }
+
+    // Append the `control_row` to the layout.
+    fn with_control_row(mut self) -> Self {
+        self.rows.push(Self::control_row());
+        self
+    }
+}
+
+impl VirtualKey {
+    /// A character key whose shifted character is the uppercase equivalent of `c`.
+    pub fn char(c: char) -> Self {
+        let mut upper = c.to_uppercase();
+        let shifted = match (upper.next(), upper.next()) {
+            (Some(u), None) => u,
+            _ => c,
+        };
+        VirtualKey::Char { base: c, shifted }
+    }
+
+    /// The width of the key as a multiple of the keyboard's key size.
+    pub fn width(&self) -> f32 {
+        match self {
+            VirtualKey::Char { .. } | VirtualKey::ArrowLeft | VirtualKey::ArrowRight => 1.0,
+            VirtualKey::Text { label, .. } => label.chars().count().max(1) as f32 * 0.5 + 0.5,
+            VirtualKey::Shift | VirtualKey::Backspace | VirtualKey::Enter | VirtualKey::Tab => 1.5,
+            VirtualKey::Space => 5.0,
+        }
+    }
+}
+
+impl Default for VirtualKeyboard {
+    fn default() -> Self {
+        Self::new(KeyboardLayout::default())
+    }
+}
+
+impl Default for KeyboardLayout {
+    fn default() -> Self {
+        Self::qwerty()
+    }
+}
+
+/// Begin a frame on the given context, injecting all events queued by `VirtualKeyboard`s since
+/// the previous frame.
+///
+/// Focus is returned to the widget that had focus before the key was pressed so that it receives
+/// the injected events.
+///
+/// `Egui::begin_frame` calls this automatically. When driving an `egui::Context` manually, call
+/// this in place of `egui::Context::begin_frame`.
+pub fn begin_frame(ctx: &egui::Context, mut raw: egui::RawInput) {
+    let injected = ctx.data_mut(|data| {
+        std::mem::take(data.get_temp_mut_or_default::<InjectedInput>(injected_input_id()))
+    });
+    raw.events.extend(injected.events);
+    ctx.begin_frame(raw);
+    if let Some(id) = injected.focus {
+        ctx.memory_mut(|mem| mem.request_focus(id));
+    }
+}
+
+// The total width of the given row as a multiple of the key size.
+fn row_width(row: &[VirtualKey]) -> f32 {
+    row.iter().map(VirtualKey::width).sum()
+}
+
+// Push the press and release events of the given key.
+fn push_key_press(events: &mut Vec<Event>, key: Key) {
+    for &pressed in &[true, false] {
+        events.push(Event::Key {
+            key,
+            pressed,
+            repeat: false,
+            modifiers: Modifiers::NONE,
+        });
+    }
+}
+
+fn injected_input_id() -> egui::Id {
+    egui::Id::new(INJECTED_INPUT_ID)
+}
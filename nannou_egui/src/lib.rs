@@ -1,13 +1,28 @@
+pub use color_grade::{ColorGrade, ColorGradeEditor, LutTexture};
 pub use egui;
 pub use egui::color_picker;
 pub use egui_wgpu;
+pub use harness::Harness;
+pub use keyboard::{KeyboardLayout, VirtualKey, VirtualKeyboard};
+pub use theme::{theme_from_colors, Theme};
+pub use transport::{Transport, TransportBar, TransportEvent};
+pub use undo::UndoStack;
 
 use egui::{pos2, ClippedPrimitive, PlatformOutput};
 use egui_wgpu::renderer::ScreenDescriptor;
 use nannou::wgpu::ToTextureView;
 use nannou::{wgpu, winit::event::VirtualKeyCode, winit::event::WindowEvent::*};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::{cell::RefCell, ops::Deref, time::Duration};
 
+pub mod color_grade;
+pub mod harness;
+pub mod keyboard;
+pub mod theme;
+pub mod transport;
+pub mod undo;
+
 /// All `egui`-related state for a single window.
 ///
 /// Includes the context, a renderer, and an input tracker.
@@ -35,6 +50,8 @@ pub struct Input {
     pub raw: egui::RawInput,
     pub window_size_pixels: [u32; 2],
     pub window_scale_factor: f32,
+    // The ID of the touch currently being translated into emulated pointer events, if any.
+    pointer_touch_id: Option<u64>,
 }
 
 /// A wrapper around a `CtxRef` on which `begin_frame` was called.
@@ -156,7 +173,7 @@ impl Egui {
     }
 
     fn begin_frame_inner(&mut self) {
-        self.context.begin_frame(self.input.raw.take());
+        keyboard::begin_frame(&self.context, self.input.raw.take());
     }
 
     fn end_frame_inner(&mut self) -> egui::PlatformOutput {
@@ -181,11 +198,13 @@ impl Input {
             ..Default::default()
         };
         let pointer_pos = Default::default();
+        let pointer_touch_id = None;
         let mut input = Self {
             raw,
             pointer_pos,
             window_scale_factor,
             window_size_pixels,
+            pointer_touch_id,
         };
         input.raw.screen_rect = Some(input.egui_window_rect());
         input
@@ -252,6 +271,14 @@ impl Input {
             CursorLeft { .. } => {
                 self.raw.events.push(egui::Event::PointerGone);
             }
+            Touch(touch) => {
+                self.handle_touch(touch);
+            }
+            TouchpadMagnify { delta, .. } => {
+                // Positive delta values indicate magnification (zooming in).
+                let zoom_factor = (*delta as f32).exp();
+                self.raw.events.push(egui::Event::Zoom(zoom_factor));
+            }
             ModifiersChanged(input) => {
                 self.raw.modifiers = winit_to_egui_modifiers(*input);
             }
@@ -282,6 +309,61 @@ impl Input {
         self.raw.time = Some(elapsed.as_secs_f64());
     }
 
+    /// Translates a winit touch event (from a touch screen, pen or tablet) into egui touch events.
+    ///
+    /// The first active touch is also used to emulate the pointer so that widgets that only
+    /// respond to pointer input remain usable. Multiple simultaneous touches allow egui to detect
+    /// gestures such as pinch-zoom.
+    fn handle_touch(&mut self, touch: &winit::event::Touch) {
+        let pos = pos2(
+            touch.location.x as f32 / self.window_scale_factor,
+            touch.location.y as f32 / self.window_scale_factor,
+        );
+        self.raw.events.push(egui::Event::Touch {
+            device_id: egui::TouchDeviceId(hash(&touch.device_id)),
+            id: egui::TouchId(touch.id),
+            phase: winit_to_egui_touch_phase(touch.phase),
+            pos,
+            force: touch.force.map(|force| force.normalized() as f32),
+        });
+
+        // Only emulate the pointer with the first touch.
+        if self.pointer_touch_id.map_or(false, |id| id != touch.id) {
+            return;
+        }
+        match touch.phase {
+            winit::event::TouchPhase::Started => {
+                self.pointer_touch_id = Some(touch.id);
+                self.pointer_pos = pos;
+                self.raw.events.push(egui::Event::PointerMoved(pos));
+                self.raw.events.push(egui::Event::PointerButton {
+                    pos,
+                    button: egui::PointerButton::Primary,
+                    pressed: true,
+                    modifiers: self.raw.modifiers,
+                });
+            }
+            winit::event::TouchPhase::Moved => {
+                self.pointer_pos = pos;
+                self.raw.events.push(egui::Event::PointerMoved(pos));
+            }
+            winit::event::TouchPhase::Ended => {
+                self.pointer_touch_id = None;
+                self.raw.events.push(egui::Event::PointerButton {
+                    pos,
+                    button: egui::PointerButton::Primary,
+                    pressed: false,
+                    modifiers: self.raw.modifiers,
+                });
+                self.raw.events.push(egui::Event::PointerGone);
+            }
+            winit::event::TouchPhase::Cancelled => {
+                self.pointer_touch_id = None;
+                self.raw.events.push(egui::Event::PointerGone);
+            }
+        }
+    }
+
     /// Small helper for the common task of producing an `egui::Rect` describing the window.
     fn egui_window_rect(&self) -> egui::Rect {
         let [w, h] = self.window_size_pixels;
@@ -477,6 +559,24 @@ fn winit_to_egui_key_code(key: VirtualKeyCode) -> Option<egui::Key> {
     })
 }
 
+/// Translates winit to egui touch phases.
+#[inline]
+fn winit_to_egui_touch_phase(phase: winit::event::TouchPhase) -> egui::TouchPhase {
+    match phase {
+        winit::event::TouchPhase::Started => egui::TouchPhase::Start,
+        winit::event::TouchPhase::Moved => egui::TouchPhase::Move,
+        winit::event::TouchPhase::Ended => egui::TouchPhase::End,
+        winit::event::TouchPhase::Cancelled => egui::TouchPhase::Cancel,
+    }
+}
+
+/// Produces a `u64` identifier from any hashable value (e.g. a winit `DeviceId`).
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// Translates winit to egui modifier keys.
 #[inline]
 fn winit_to_egui_modifiers(modifiers: winit::event::ModifiersState) -> egui::Modifiers {
@@ -0,0 +1,185 @@
+//! Helpers for deriving egui styles from nannou colors.
+//!
+//! egui's `Style` contains many colors across its widget states. A `Theme` derives all of these
+//! from a background, an accent and a text color, making it easy to match the control UI to the
+//! palette of the artwork.
+
+use egui::{Color32, Stroke, Visuals};
+use nannou::color::{IntoLinSrgba, Srgb};
+
+/// A minimal description of a UI palette from which complete egui visuals are derived.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Theme {
+    /// The fill color of panels and windows.
+    pub background: Color32,
+    /// The color used for selections, hovered and active widgets and hyperlinks.
+    pub accent: Color32,
+    /// The color of all text and widget foregrounds.
+    pub text: Color32,
+}
+
+/// Construct an `egui::Style` from the given background, accent and text colors.
+///
+/// This is short-hand for `Theme::new(background, accent, text).style()`.
+pub fn theme_from_colors<B, A, T>(background: B, accent: A, text: T) -> egui::Style
+where
+    B: IntoLinSrgba<f32>,
+    A: IntoLinSrgba<f32>,
+    T: IntoLinSrgba<f32>,
+{
+    Theme::new(background, accent, text).style()
+}
+
+impl Theme {
+    /// Create a theme from any nannou colors.
+    pub fn new<B, A, T>(background: B, accent: A, text: T) -> Self
+    where
+        B: IntoLinSrgba<f32>,
+        A: IntoLinSrgba<f32>,
+        T: IntoLinSrgba<f32>,
+    {
+        Theme {
+            background: to_color32(background),
+            accent: to_color32(accent),
+            text: to_color32(text),
+        }
+    }
+
+    /// Whether or not the background is darker than the text.
+    pub fn is_dark(&self) -> bool {
+        luminance(self.background) < luminance(self.text)
+    }
+
+    /// The dark variant of this theme.
+    ///
+    /// If the theme is light, the background and text colors are swapped.
+    pub fn dark(self) -> Self {
+        self.variant(true)
+    }
+
+    /// The light variant of this theme.
+    ///
+    /// If the theme is dark, the background and text colors are swapped.
+    pub fn light(self) -> Self {
+        self.variant(false)
+    }
+
+    /// The dark or light variant of this theme.
+    pub fn variant(self, dark: bool) -> Self {
+        if self.is_dark() == dark {
+            return self;
+        }
+        Theme {
+            background: self.text,
+            text: self.background,
+            ..self
+        }
+    }
+
+    /// Derive the full set of egui visuals from the theme.
+    pub fn visuals(&self) -> Visuals {
+        let Theme {
+            background: bg,
+            accent,
+            text,
+        } = *self;
+        let dark = self.is_dark();
+        let mut visuals = if dark {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        };
+        let border = mix(bg, text, 0.2);
+        let on_accent = contrasting(accent);
+
+        visuals.override_text_color = Some(text);
+        visuals.hyperlink_color = accent;
+        visuals.panel_fill = bg;
+        visuals.window_fill = bg;
+        visuals.window_stroke.color = border;
+        visuals.faint_bg_color = mix(bg, text, 0.04);
+        visuals.extreme_bg_color = mix(bg, contrasting(text), 0.5);
+        visuals.code_bg_color = mix(bg, text, 0.08);
+        visuals.selection.bg_fill = mix(bg, accent, 0.6);
+        visuals.selection.stroke = Stroke::new(visuals.selection.stroke.width, text);
+
+        let widgets = &mut visuals.widgets;
+        widgets.noninteractive.bg_fill = bg;
+        widgets.noninteractive.weak_bg_fill = bg;
+        widgets.noninteractive.bg_stroke.color = border;
+        widgets.noninteractive.fg_stroke.color = text;
+        widgets.inactive.bg_fill = mix(bg, text, 0.12);
+        widgets.inactive.weak_bg_fill = mix(bg, text, 0.08);
+        widgets.inactive.fg_stroke.color = text;
+        widgets.hovered.bg_fill = mix(bg, accent, 0.5);
+        widgets.hovered.weak_bg_fill = mix(bg, accent, 0.35);
+        widgets.hovered.bg_stroke.color = accent;
+        widgets.hovered.fg_stroke.color = text;
+        widgets.active.bg_fill = accent;
+        widgets.active.weak_bg_fill = accent;
+        widgets.active.bg_stroke.color = accent;
+        widgets.active.fg_stroke.color = on_accent;
+        widgets.open.bg_fill = mix(bg, text, 0.16);
+        widgets.open.weak_bg_fill = mix(bg, text, 0.12);
+        widgets.open.bg_stroke.color = border;
+        widgets.open.fg_stroke.color = text;
+
+        visuals
+    }
+
+    /// A default `egui::Style` using the visuals derived from the theme.
+    pub fn style(&self) -> egui::Style {
+        egui::Style {
+            visuals: self.visuals(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply the theme's visuals to the given context, leaving the rest of its style untouched.
+    ///
+    /// This may be called at any time to live-switch between themes or variants.
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.set_visuals(self.visuals());
+    }
+}
+
+// Convert any nannou color to an egui color.
+fn to_color32<C>(color: C) -> Color32
+where
+    C: IntoLinSrgba<f32>,
+{
+    let lin = color.into_lin_srgba();
+    let srgb = Srgb::from_linear(lin.color);
+    let u8_channel = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+    Color32::from_rgba_unmultiplied(
+        u8_channel(srgb.red),
+        u8_channel(srgb.green),
+        u8_channel(srgb.blue),
+        u8_channel(lin.alpha),
+    )
+}
+
+// Linearly interpolate from `a` to `b` in gamma space.
+fn mix(a: Color32, b: Color32, t: f32) -> Color32 {
+    let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color32::from_rgba_premultiplied(
+        lerp(a.r(), b.r()),
+        lerp(a.g(), b.g()),
+        lerp(a.b(), b.b()),
+        lerp(a.a(), b.a()),
+    )
+}
+
+// The approximate perceived brightness of the color within the range `0.0..=1.0`.
+fn luminance(c: Color32) -> f32 {
+    (0.299 * c.r() as f32 + 0.587 * c.g() as f32 + 0.114 * c.b() as f32) / 255.0
+}
+
+// Black or white, whichever is most readable against the given color.
+fn contrasting(c: Color32) -> Color32 {
+    if luminance(c) > 0.5 {
+        Color32::BLACK
+    } else {
+        Color32::WHITE
+    }
+}
@@ -0,0 +1,404 @@
+//! A transport bar for controlling the playback of a timeline, sequencer or audio stream.
+//!
+//! The `TransportBar` provides play/pause, stop and loop buttons, a seekable time display and a
+//! BPM field. The playback state lives within a `Transport` owned by the app. Each interaction both
+//! updates the `Transport` and produces a `TransportEvent`, so that the events may be forwarded to
+//! wherever playback actually occurs, e.g. an audio thread or a timeline's playhead.
+//!
+//! ```no_run
+//! use nannou_egui::{egui, transport::{Transport, TransportBar, TransportEvent}};
+//!
+//! fn ui(ctx: &egui::Context, transport: &mut Transport) {
+//!     egui::TopBottomPanel::top("transport").show(ctx, |ui| {
+//!         let output = TransportBar::new().length(60.0).show(ui, transport);
+//!         for event in output.events {
+//!             if let TransportEvent::Seek(secs) = event {
+//!                 println!("seeked to {}s", secs);
+//!             }
+//!         }
+//!     });
+//! }
+//! ```
+
+use std::ops::RangeInclusive;
+
+/// The playback state controlled by a `TransportBar`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transport {
+    /// Whether or not playback is running.
+    pub playing: bool,
+    /// Whether or not playback wraps to the start upon reaching the end.
+    pub looping: bool,
+    /// The position of the playhead in seconds.
+    pub position: f64,
+    /// The tempo in beats per minute.
+    pub bpm: f64,
+}
+
+/// An interaction with a `TransportBar`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TransportEvent {
+    Play,
+    Pause,
+    /// Playback was stopped and the playhead returned to the start.
+    Stop,
+    /// Looping was enabled or disabled.
+    Loop(bool),
+    /// The playhead was moved to the given position in seconds.
+    Seek(f64),
+    /// The tempo was changed to the given number of beats per minute.
+    Bpm(f64),
+}
+
+/// The units in which a `TransportBar` displays and edits the playhead position.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum TimeDisplay {
+    /// Minutes, seconds and milliseconds, e.g. `1:02.500`.
+    Seconds,
+    /// Bars, beats and sixteenths counting from one, e.g. `3.2.1`.
+    Beats,
+}
+
+/// A widget with play/pause, stop and loop buttons, a seekable time display and a BPM field.
+#[derive(Clone, Debug)]
+pub struct TransportBar {
+    display: TimeDisplay,
+    beats_per_bar: u32,
+    bpm_range: RangeInclusive<f64>,
+    length: Option<f64>,
+}
+
+/// The result of showing a `TransportBar`.
+pub struct TransportOutput {
+    /// The response of the bar as a whole.
+    pub response: egui::Response,
+    /// The interactions that occurred this frame, in order. These have already been applied to
+    /// the `Transport`.
+    pub events: Vec<TransportEvent>,
+}
+
+impl Transport {
+    /// The default tempo in beats per minute.
+    pub const DEFAULT_BPM: f64 = 120.0;
+
+    /// A stopped transport at the start with the given tempo.
+    pub fn new(bpm: f64) -> Self {
+        Transport {
+            playing: false,
+            looping: false,
+            position: 0.0,
+            bpm,
+        }
+    }
+
+    /// The position of the playhead in beats.
+    pub fn beats(&self) -> f64 {
+        self.position * self.bpm / 60.0
+    }
+
+    /// Advance the playhead by the given number of seconds if playing.
+    ///
+    /// If a `length` is given, the playhead wraps to the start upon reaching it while looping, or
+    /// otherwise stops at the end.
+    pub fn advance(&mut self, secs: f64, length: Option<f64>) {
+        if !self.playing {
+            return;
+        }
+        self.position += secs;
+        if let Some(length) = length {
+            if self.position >= length {
+                if self.looping && length > 0.0 {
+                    self.position %= length;
+                } else {
+                    self.position = length;
+                    self.playing = false;
+                }
+            }
+        }
+    }
+
+    /// Apply the given event to the transport.
+    pub fn apply(&mut self, event: TransportEvent) {
+        match event {
+            TransportEvent::Play => self.playing = true,
+            TransportEvent::Pause => self.playing = false,
+            TransportEvent::Stop => {
+                self.playing = false;
+                self.position = 0.0;
+            }
+            TransportEvent::Loop(looping) => self.looping = looping,
+            TransportEvent::Seek(position) => self.position = position.max(0.0),
+            TransportEvent::Bpm(bpm) => self.bpm = bpm,
+        }
+    }
+}
+
+impl TransportBar {
+    /// The default number of beats within each bar.
+    pub const DEFAULT_BEATS_PER_BAR: u32 = 4;
+    /// The default range of tempos that may be entered.
+    pub const DEFAULT_BPM_RANGE: RangeInclusive<f64> = 20.0..=300.0;
+
+    /// A transport bar displaying time in seconds.
+    pub fn new() -> Self {
+        TransportBar {
+            display: TimeDisplay::Seconds,
+            beats_per_bar: Self::DEFAULT_BEATS_PER_BAR,
+            bpm_range: Self::DEFAULT_BPM_RANGE,
+            length: None,
+        }
+    }
+
+    /// The units in which the playhead position is displayed and edited.
+    ///
+    /// By default, this is `TimeDisplay::Seconds`.
+    pub fn display(mut self, display: TimeDisplay) -> Self {
+        self.display = display;
+        self
+    }
+
+    /// The number of beats within each bar when displaying `TimeDisplay::Beats`.
+    ///
+    /// By default, this value is `TransportBar::DEFAULT_BEATS_PER_BAR`.
+    pub fn beats_per_bar(mut self, beats_per_bar: u32) -> Self {
+        assert!(beats_per_bar > 0);
+        self.beats_per_bar = beats_per_bar;
+        self
+    }
+
+    /// The range of tempos that may be entered.
+    ///
+    /// By default, this is `TransportBar::DEFAULT_BPM_RANGE`.
+    pub fn bpm_range(mut self, range: RangeInclusive<f64>) -> Self {
+        self.bpm_range = range;
+        self
+    }
+
+    /// The total length of the material in seconds.
+    ///
+    /// When specified, a slider is shown for seeking and the playhead may not be moved beyond
+    /// the end.
+    ///
+    /// By default, the length is unknown.
+    pub fn length(mut self, secs: f64) -> Self {
+        self.length = Some(secs);
+        self
+    }
+
+    /// Show the transport bar within the given `Ui`, applying all interactions to the given
+    /// `Transport`.
+    pub fn show(&self, ui: &mut egui::Ui, transport: &mut Transport) -> TransportOutput {
+        let mut events = vec![];
+        let max_position = self.length.unwrap_or(f64::INFINITY);
+        let response = ui.horizontal(|ui| {
+            // Play, stop and loop buttons.
+            let (label, hover, event) = if transport.playing {
+                ("⏸", "Pause", TransportEvent::Pause)
+            } else {
+                ("⏵", "Play", TransportEvent::Play)
+            };
+            if ui.button(label).on_hover_text(hover).clicked() {
+                events.push(event);
+            }
+            if ui.button("⏹").on_hover_text("Stop").clicked() {
+                events.push(TransportEvent::Stop);
+            }
+            let loop_label = ui.selectable_label(transport.looping, "🔁");
+            if loop_label.on_hover_text("Loop").clicked() {
+                events.push(TransportEvent::Loop(!transport.looping));
+            }
+            ui.separator();
+
+            // The seekable time display.
+            let spb = 60.0 / transport.bpm;
+            let beats_per_bar = self.beats_per_bar;
+            let mut position = transport.position;
+            let drag = match self.display {
+                TimeDisplay::Seconds => egui::DragValue::new(&mut position)
+                    .speed(0.01)
+                    .clamp_range(0.0..=max_position)
+                    .custom_formatter(|secs, _| format_seconds(secs))
+                    .custom_parser(parse_seconds),
+                TimeDisplay::Beats => egui::DragValue::from_get_set(|v| match v {
+                    Some(beats) => {
+                        position = (beats * spb).min(max_position).max(0.0);
+                        beats
+                    }
+                    None => position / spb,
+                })
+                .speed(0.05)
+                .custom_formatter(move |beats, _| format_beats(beats, beats_per_bar))
+                .custom_parser(move |s| parse_beats(s, beats_per_bar)),
+            };
+            ui.add(drag).on_hover_text("Position");
+            if let Some(length) = self.length {
+                let slider = egui::Slider::new(&mut position, 0.0..=length).show_value(false);
+                ui.add(slider);
+            }
+            if position != transport.position {
+                events.push(TransportEvent::Seek(position));
+            }
+            ui.separator();
+
+            // The tempo.
+            let mut bpm = transport.bpm;
+            let drag = egui::DragValue::new(&mut bpm)
+                .speed(0.1)
+                .clamp_range(self.bpm_range.clone())
+                .max_decimals(2)
+                .suffix(" BPM");
+            ui.add(drag);
+            if bpm != transport.bpm {
+                events.push(TransportEvent::Bpm(bpm));
+            }
+        });
+
+        for &event in &events {
+            transport.apply(event);
+        }
+        TransportOutput {
+            response: response.response,
+            events,
+        }
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BPM)
+    }
+}
+
+impl Default for TransportBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Format the given seconds as `m:ss.mmm`.
+fn format_seconds(secs: f64) -> String {
+    let millis = (secs.max(0.0) * 1_000.0).round() as u64;
+    let (mins, millis) = (millis / 60_000, millis % 60_000);
+    format!("{}:{:02}.{:03}", mins, millis / 1_000, millis % 1_000)
+}
+
+// Parse seconds given as either `m:ss.mmm` or a plain number of seconds.
+//
+// Returns `None` for more than one `:`, or for seconds outside `0..60` when minutes are given.
+fn parse_seconds(s: &str) -> Option<f64> {
+    let mut parts = s.trim().split(':');
+    let first: f64 = parts.next()?.trim().parse().ok()?;
+    let secs = match parts.next() {
+        None => return Some(first),
+        Some(secs) => secs.trim().parse::<f64>().ok()?,
+    };
+    if parts.next().is_some() || first < 0.0 || !(0.0..60.0).contains(&secs) {
+        return None;
+    }
+    Some(first * 60.0 + secs)
+}
+
+// Format the given beats as `bar.beat.sixteenth`, each counting from one.
+fn format_beats(beats: f64, beats_per_bar: u32) -> String {
+    let sixteenths = (beats.max(0.0) * 4.0).floor() as u64;
+    let per_bar = beats_per_bar as u64 * 4;
+    let bar = sixteenths / per_bar + 1;
+    let beat = (sixteenths % per_bar) / 4 + 1;
+    let sixteenth = sixteenths % 4 + 1;
+    format!("{}.{}.{}", bar, beat, sixteenth)
+}
+
+// Parse beats given as `bar.beat.sixteenth`, where trailing parts may be omitted.
+//
+// Returns `None` for more than three parts, or for a beat or sixteenth beyond the end of its bar
+// or beat respectively.
+fn parse_beats(s: &str, beats_per_bar: u32) -> Option<f64> {
+    let mut parts = s.trim().split('.').map(|part| part.trim().parse::<u64>());
+    let mut next = |max: u64| match parts.next() {
+        None => Some(1),
+        Some(Ok(n)) if n > 0 && n <= max => Some(n),
+        Some(_) => None,
+    };
+    let bar = next(u64::MAX)?;
+    let beat = next(beats_per_bar as u64)?;
+    let sixteenth = next(4)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let beats = (bar - 1) * beats_per_bar as u64 + (beat - 1);
+    Some(beats as f64 + (sixteenth - 1) as f64 / 4.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seconds_round_trip() {
+        for &secs in &[0.0, 0.5, 1.25, 59.999, 60.0, 62.5, 3_599.001, 7_200.0] {
+            let s = format_seconds(secs);
+            let parsed = parse_seconds(&s).unwrap();
+            assert!(
+                (parsed - secs).abs() < 1e-9,
+                "{} -> {} -> {}",
+                secs,
+                s,
+                parsed
+            );
+        }
+    }
+
+    #[test]
+    fn seconds_formats() {
+        assert_eq!(format_seconds(62.5), "1:02.500");
+        assert_eq!(format_seconds(-1.0), "0:00.000");
+        assert_eq!(parse_seconds("1:02.5"), Some(62.5));
+        assert_eq!(parse_seconds(" 2 : 30 "), Some(150.0));
+        assert_eq!(parse_seconds("90.5"), Some(90.5));
+    }
+
+    #[test]
+    fn invalid_seconds() {
+        assert_eq!(parse_seconds(""), None);
+        assert_eq!(parse_seconds("abc"), None);
+        assert_eq!(parse_seconds("1:"), None);
+        assert_eq!(parse_seconds("1:2:03"), None);
+        assert_eq!(parse_seconds("1:60"), None);
+        assert_eq!(parse_seconds("1:-5"), None);
+        assert_eq!(parse_seconds("-1:05"), None);
+    }
+
+    #[test]
+    fn beats_round_trip() {
+        for &beats_per_bar in &[3, 4, 7] {
+            for sixteenths in 0..64 {
+                let beats = sixteenths as f64 / 4.0;
+                let s = format_beats(beats, beats_per_bar);
+                assert_eq!(parse_beats(&s, beats_per_bar), Some(beats), "{}", s);
+            }
+        }
+    }
+
+    #[test]
+    fn beats_formats() {
+        assert_eq!(format_beats(0.0, 4), "1.1.1");
+        assert_eq!(format_beats(5.25, 4), "2.2.2");
+        assert_eq!(format_beats(5.3, 4), "2.2.2");
+        assert_eq!(parse_beats("2", 4), Some(4.0));
+        assert_eq!(parse_beats("2.3", 4), Some(6.0));
+        assert_eq!(parse_beats(" 1 . 2 . 4 ", 4), Some(1.75));
+    }
+
+    #[test]
+    fn invalid_beats() {
+        assert_eq!(parse_beats("", 4), None);
+        assert_eq!(parse_beats("0.1.1", 4), None);
+        assert_eq!(parse_beats("1.0.1", 4), None);
+        assert_eq!(parse_beats("1.5.1", 4), None);
+        assert_eq!(parse_beats("1.4.1", 3), None);
+        assert_eq!(parse_beats("1.1.5", 4), None);
+        assert_eq!(parse_beats("1.1.1.1", 4), None);
+        assert_eq!(parse_beats("1:1.1", 4), None);
+        assert_eq!(parse_beats("1.x", 4), None);
+    }
+}
@@ -0,0 +1,302 @@
+//! A simple undo/redo history for apps exposing many tweakable parameters.
+//!
+//! An `UndoStack` tracks snapshots of some user state (typically a struct of all parameters
+//! exposed via the UI). Changes made during a single interaction, e.g. dragging a slider or
+//! editing a text field, are grouped into a single history entry once the interaction ends.
+
+use egui::{Key, KeyboardShortcut, Modifiers};
+use std::collections::VecDeque;
+
+/// An undo/redo history of snapshots of some state `T`.
+///
+/// Call `update` once per frame after describing the UI. This handles the undo and redo keyboard
+/// shortcuts and records any changes to the state.
+#[derive(Clone, Debug)]
+pub struct UndoStack<T> {
+    undos: VecDeque<T>,
+    redos: Vec<T>,
+    settled: Option<T>,
+    max_len: usize,
+}
+
+impl<T> UndoStack<T>
+where
+    T: Clone + PartialEq,
+{
+    /// The default maximum number of entries retained within the undo history.
+    pub const DEFAULT_MAX_LEN: usize = 100;
+    /// The shortcut used to undo the last change.
+    pub const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
+    /// The shortcut used to redo the last undone change.
+    pub const REDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(COMMAND_SHIFT, Key::Z);
+    /// An alternative shortcut used to redo the last undone change.
+    pub const REDO_SHORTCUT_ALT: KeyboardShortcut =
+        KeyboardShortcut::new(Modifiers::COMMAND, Key::Y);
+
+    /// Create an empty undo history.
+    ///
+    /// The first state given to `update` or `record` becomes the initial state of the history.
+    pub fn new() -> Self {
+        UndoStack {
+            undos: VecDeque::new(),
+            redos: Vec::new(),
+            settled: None,
+            max_len: Self::DEFAULT_MAX_LEN,
+        }
+    }
+
+    /// The maximum number of entries retained within the undo history.
+    ///
+    /// Once exceeded, the oldest entries are discarded.
+    ///
+    /// By default, this value is `UndoStack::DEFAULT_MAX_LEN`.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self.truncate();
+        self
+    }
+
+    /// Whether or not there are any changes that can be undone.
+    pub fn can_undo(&self) -> bool {
+        !self.undos.is_empty()
+    }
+
+    /// Whether or not there are any undone changes that can be redone.
+    pub fn can_redo(&self) -> bool {
+        !self.redos.is_empty()
+    }
+
+    /// Discard all history, treating the given state as the new initial state.
+    pub fn clear(&mut self, state: &T) {
+        self.undos.clear();
+        self.redos.clear();
+        self.settled = Some(state.clone());
+    }
+
+    /// Handle keyboard shortcuts and record any changes to the state.
+    ///
+    /// This should be called once per frame, after the UI has been described.
+    pub fn update(&mut self, ctx: &egui::Context, state: &mut T) {
+        self.handle_shortcuts(ctx, state);
+        self.record(ctx, state);
+    }
+
+    /// Undo or redo in response to the undo and redo keyboard shortcuts.
+    ///
+    /// Shortcuts are ignored while a widget such as a text field has keyboard focus, allowing the
+    /// widget to handle them itself.
+    ///
+    /// Returns `true` if the state was modified.
+    pub fn handle_shortcuts(&mut self, ctx: &egui::Context, state: &mut T) -> bool {
+        if ctx.wants_keyboard_input() {
+            return false;
+        }
+        let (undo, redo) = ctx.input_mut(|input| {
+            // Check redo first as its shortcut is a superset of the undo shortcut.
+            let redo = input.consume_shortcut(&Self::REDO_SHORTCUT)
+                || input.consume_shortcut(&Self::REDO_SHORTCUT_ALT);
+            let undo = !redo && input.consume_shortcut(&Self::UNDO_SHORTCUT);
+            (undo, redo)
+        });
+        if undo {
+            self.undo(state)
+        } else if redo {
+            self.redo(state)
+        } else {
+            false
+        }
+    }
+
+    /// Record the given state if it has changed since the last recorded state.
+    ///
+    /// While the user is interacting with the UI, i.e. a pointer button is held or a widget has
+    /// keyboard focus, recording is deferred so that all changes made during the interaction form
+    /// a single history entry.
+    pub fn record(&mut self, ctx: &egui::Context, state: &T) {
+        let interacting = ctx.input(|input| input.pointer.any_down()) || ctx.wants_keyboard_input();
+        if !interacting {
+            self.commit(state);
+        }
+    }
+
+    /// Immediately record the given state if it has changed since the last recorded state,
+    /// regardless of any ongoing interaction.
+    pub fn commit(&mut self, state: &T) {
+        match self.settled {
+            None => self.settled = Some(state.clone()),
+            Some(ref settled) if settled == state => (),
+            Some(ref mut settled) => {
+                let prev = std::mem::replace(settled, state.clone());
+                self.undos.push_back(prev);
+                self.redos.clear();
+                self.truncate();
+            }
+        }
+    }
+
+    /// Restore the state prior to the last recorded change.
+    ///
+    /// Any unrecorded changes to the state are recorded first.
+    ///
+    /// Returns `true` if the state was modified.
+    pub fn undo(&mut self, state: &mut T) -> bool {
+        self.commit(state);
+        let prev = match self.undos.pop_back() {
+            None => return false,
+            Some(prev) => prev,
+        };
+        if let Some(settled) = self.settled.replace(prev.clone()) {
+            self.redos.push(settled);
+        }
+        *state = prev;
+        true
+    }
+
+    /// Restore the state prior to the last undo.
+    ///
+    /// If the state has changed since the last undo, the redo history is discarded and nothing
+    /// is restored.
+    ///
+    /// Returns `true` if the state was modified.
+    pub fn redo(&mut self, state: &mut T) -> bool {
+        self.commit(state);
+        let next = match self.redos.pop() {
+            None => return false,
+            Some(next) => next,
+        };
+        if let Some(settled) = self.settled.replace(next.clone()) {
+            self.undos.push_back(settled);
+        }
+        *state = next;
+        true
+    }
+
+    // Discard the oldest entries beyond the maximum length.
+    fn truncate(&mut self) {
+        while self.undos.len() > self.max_len {
+            self.undos.pop_front();
+        }
+    }
+}
+
+impl<T> Default for UndoStack<T>
+where
+    T: Clone + PartialEq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The modifiers of the redo shortcut.
+const COMMAND_SHIFT: Modifiers = Modifiers {
+    alt: false,
+    ctrl: false,
+    shift: true,
+    mac_cmd: false,
+    command: true,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A history with the given states committed in order.
+    fn history(states: &[i32]) -> UndoStack<i32> {
+        let mut undo = UndoStack::new();
+        for state in states {
+            undo.commit(state);
+        }
+        undo
+    }
+
+    #[test]
+    fn first_commit_is_the_initial_state() {
+        let undo = history(&[0]);
+        assert!(!undo.can_undo());
+        assert!(!undo.can_redo());
+    }
+
+    #[test]
+    fn unchanged_commits_are_ignored() {
+        let mut undo = history(&[0, 0, 1, 1]);
+        let mut state = 1;
+        assert!(undo.undo(&mut state));
+        assert_eq!(state, 0);
+        assert!(!undo.can_undo());
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut undo = history(&[0, 1, 2]);
+        let mut state = 2;
+        assert!(undo.undo(&mut state));
+        assert_eq!(state, 1);
+        assert!(undo.undo(&mut state));
+        assert_eq!(state, 0);
+        assert!(!undo.undo(&mut state));
+        assert_eq!(state, 0);
+
+        assert!(undo.redo(&mut state));
+        assert_eq!(state, 1);
+        assert!(undo.redo(&mut state));
+        assert_eq!(state, 2);
+        assert!(!undo.redo(&mut state));
+        assert_eq!(state, 2);
+    }
+
+    #[test]
+    fn undo_records_pending_changes() {
+        let mut undo = history(&[0]);
+        let mut state = 1;
+        assert!(undo.undo(&mut state));
+        assert_eq!(state, 0);
+        assert!(undo.redo(&mut state));
+        assert_eq!(state, 1);
+    }
+
+    #[test]
+    fn commit_discards_redo_history() {
+        let mut undo = history(&[0, 1, 2]);
+        let mut state = 2;
+        undo.undo(&mut state);
+        assert!(undo.can_redo());
+        state = 5;
+        undo.commit(&state);
+        assert!(!undo.can_redo());
+        assert!(!undo.redo(&mut state));
+        assert_eq!(state, 5);
+        assert!(undo.undo(&mut state));
+        assert_eq!(state, 1);
+    }
+
+    #[test]
+    fn redo_after_an_unrecorded_change_restores_nothing() {
+        let mut undo = history(&[0, 1]);
+        let mut state = 1;
+        undo.undo(&mut state);
+        state = 7;
+        assert!(!undo.redo(&mut state));
+        assert_eq!(state, 7);
+    }
+
+    #[test]
+    fn max_len_discards_the_oldest_entries() {
+        let mut undo = history(&[0, 1, 2, 3]).max_len(2);
+        let mut state = 3;
+        assert!(undo.undo(&mut state));
+        assert!(undo.undo(&mut state));
+        assert_eq!(state, 1);
+        assert!(!undo.undo(&mut state));
+    }
+
+    #[test]
+    fn clear_sets_the_initial_state() {
+        let mut undo = history(&[0, 1]);
+        undo.clear(&4);
+        assert!(!undo.can_undo());
+        let mut state = 4;
+        assert!(!undo.undo(&mut state));
+        assert_eq!(state, 4);
+    }
+}
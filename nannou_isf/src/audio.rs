@@ -0,0 +1,257 @@
+//! Driving the `Audio` and `AudioFft` inputs of an ISF shader with a `nannou_audio` input stream.
+//!
+//! An `AudioInput` captures the default (or a given) input device into a ring buffer of mono
+//! samples. Calling `IsfPipeline::encode_audio_update` once per frame uploads the most recent
+//! samples to every `audio` input texture and their magnitude spectrum to every `audioFFT` input
+//! texture.
+
+use nannou_audio as audio;
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Captures an audio input stream for use by ISF `Audio` and `AudioFft` inputs.
+pub struct AudioInput {
+    stream: audio::Stream<Capture>,
+    history: Arc<Mutex<VecDeque<f32>>>,
+    gain: f32,
+    // Caches the FFT plan for each spectrum size.
+    planner: Mutex<FftPlanner<f32>>,
+}
+
+/// Errors that might occur while starting an `AudioInput`.
+#[derive(Debug, Error)]
+pub enum AudioInputError {
+    #[error("failed to build the audio input stream: {err}")]
+    Build {
+        #[from]
+        err: audio::stream::BuildError,
+    },
+    #[error("failed to play the audio input stream: {err}")]
+    Play {
+        #[from]
+        err: audio::PlayStreamError,
+    },
+}
+
+// The model of the input stream.
+struct Capture {
+    history: Arc<Mutex<VecDeque<f32>>>,
+    capacity: usize,
+}
+
+impl AudioInput {
+    /// The default number of most recent samples retained for the waveform and FFT.
+    pub const DEFAULT_HISTORY: usize = 2048;
+
+    /// Start capturing the host's default input device.
+    pub fn new(host: &audio::Host) -> Result<Self, AudioInputError> {
+        Self::build(host, None, Self::DEFAULT_HISTORY)
+    }
+
+    /// Start capturing the given input device.
+    pub fn from_device(host: &audio::Host, device: audio::Device) -> Result<Self, AudioInputError> {
+        Self::build(host, Some(device), Self::DEFAULT_HISTORY)
+    }
+
+    /// Start capturing the given device, or the default input device if `None`, retaining the
+    /// given number of most recent samples.
+    ///
+    /// The history should be at least twice the greatest number of `audioFFT` columns in order
+    /// for the full spectrum resolution to be available.
+    ///
+    /// The history is allocated up front. The audio thread never blocks on it: if the history is
+    /// being read at the moment a buffer is captured, that buffer is skipped.
+    pub fn build(
+        host: &audio::Host,
+        device: Option<audio::Device>,
+        history: usize,
+    ) -> Result<Self, AudioInputError> {
+        let history_buffer = Arc::new(Mutex::new(VecDeque::with_capacity(history)));
+        let model = Capture {
+            history: history_buffer.clone(),
+            capacity: history,
+        };
+        let builder = host.new_input_stream(model).capture(capture);
+        let builder = match device {
+            Some(device) => builder.device(device),
+            None => builder,
+        };
+        let stream = builder.build()?;
+        stream.play()?;
+        Ok(AudioInput {
+            stream,
+            history: history_buffer,
+            gain: 1.0,
+            planner: Mutex::new(FftPlanner::new()),
+        })
+    }
+
+    /// The gain applied to all samples before they are uploaded.
+    ///
+    /// By default, this value is `1.0`.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Set the gain applied to all samples before they are uploaded.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    /// Resume capturing after a call to `pause`.
+    pub fn play(&self) -> Result<(), audio::PlayStreamError> {
+        self.stream.play()
+    }
+
+    /// Pause capturing. The captured history is retained.
+    pub fn pause(&self) -> Result<(), audio::PauseStreamError> {
+        self.stream.pause()
+    }
+
+    /// Fill `samples` with the most recent captured samples, oldest first.
+    ///
+    /// If fewer samples have been captured than requested, the start is padded with silence.
+    pub fn waveform(&self, samples: &mut [f32]) {
+        for s in samples.iter_mut() {
+            *s = 0.0;
+        }
+        let history = match self.history.lock() {
+            Ok(history) => history,
+            Err(_) => return,
+        };
+        let n = std::cmp::min(samples.len(), history.len());
+        let skip = history.len() - n;
+        let offset = samples.len() - n;
+        for (s, h) in samples[offset..].iter_mut().zip(history.iter().skip(skip)) {
+            *s = *h * self.gain;
+        }
+    }
+
+    /// Fill `columns` with the magnitude spectrum of the most recent captured samples.
+    ///
+    /// The spectrum is computed over the next power of two at least twice the number of columns,
+    /// with a Hann window applied. Each column holds the magnitude of one frequency bin, from DC
+    /// upwards.
+    pub fn fft(&self, columns: &mut [f32]) {
+        if columns.is_empty() {
+            return;
+        }
+        let size = (columns.len() * 2).next_power_of_two();
+        let mut samples = vec![0.0; size];
+        self.waveform(&mut samples);
+        let fft = match self.planner.lock() {
+            Ok(mut planner) => planner.plan_fft_forward(size),
+            Err(_) => return,
+        };
+        let magnitudes = magnitude_spectrum(fft.as_ref(), &samples);
+        for (c, m) in columns.iter_mut().zip(magnitudes) {
+            *c = m;
+        }
+    }
+}
+
+// Mix each captured frame to mono and push it onto the history.
+//
+// Uses `try_lock` so that the audio thread never waits on a reader of the history.
+fn capture(model: &mut Capture, buffer: &audio::Buffer) {
+    let mut history = match model.history.try_lock() {
+        Ok(history) => history,
+        Err(_) => return,
+    };
+    let channels = buffer.channels() as f32;
+    let mono = buffer
+        .frames()
+        .map(|frame| frame.iter().sum::<f32>() / channels);
+    push_history(&mut history, model.capacity, mono);
+}
+
+// Push the given samples onto the history, retaining at most `capacity` of the most recent.
+//
+// The oldest samples are trimmed before pushing so that the history never grows beyond the
+// capacity with which it was allocated.
+fn push_history<I>(history: &mut VecDeque<f32>, capacity: usize, samples: I)
+where
+    I: ExactSizeIterator<Item = f32>,
+{
+    let skip = samples.len().saturating_sub(capacity);
+    let pushed = samples.len() - skip;
+    let excess = (history.len() + pushed).saturating_sub(capacity);
+    history.drain(..excess);
+    history.extend(samples.skip(skip));
+}
+
+// The normalised magnitudes of the first half of the DFT of the Hann-windowed samples.
+//
+// `fft` must be a forward transform of `samples.len()`.
+fn magnitude_spectrum(fft: &dyn rustfft::Fft<f32>, samples: &[f32]) -> Vec<f32> {
+    let n = samples.len();
+    let mut bins: Vec<Complex<f32>> = samples
+        .iter()
+        .enumerate()
+        .map(|(i, &s)| {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / n as f32).cos();
+            Complex::new(s * window, 0.0)
+        })
+        .collect();
+    fft.process(&mut bins);
+    // The Hann window halves the amplitude on average, so scale by 4/n rather than 2/n.
+    let scale = 4.0 / n as f32;
+    bins[..n / 2].iter().map(|c| c.norm() * scale).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(samples: &[f32]) -> Vec<f32> {
+        let fft = FftPlanner::new().plan_fft_forward(samples.len());
+        magnitude_spectrum(fft.as_ref(), samples)
+    }
+
+    #[test]
+    fn push_history_retains_most_recent() {
+        let mut history = VecDeque::with_capacity(4);
+        push_history(&mut history, 4, [1.0, 2.0, 3.0].iter().cloned());
+        assert_eq!(history, [1.0, 2.0, 3.0]);
+        push_history(&mut history, 4, [4.0, 5.0].iter().cloned());
+        assert_eq!(history, [2.0, 3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn push_history_never_exceeds_capacity() {
+        let mut history = VecDeque::with_capacity(4);
+        let allocated = history.capacity();
+        push_history(&mut history, 4, [1.0, 2.0].iter().cloned());
+        push_history(&mut history, 4, (0..10).map(|i| i as f32));
+        assert_eq!(history, [6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(history.capacity(), allocated);
+    }
+
+    #[test]
+    fn spectrum_of_sine_peaks_at_its_bin() {
+        let n = 256;
+        let bin = 16;
+        let samples: Vec<f32> = (0..n)
+            .map(|i| (2.0 * PI * bin as f32 * i as f32 / n as f32).sin())
+            .collect();
+        let magnitudes = spectrum(&samples);
+        assert_eq!(magnitudes.len(), n / 2);
+        assert!((magnitudes[bin] - 1.0).abs() < 1e-3);
+        let (peak, _) =
+            magnitudes.iter().enumerate().fold(
+                (0, 0.0),
+                |acc, (i, &m)| if m > acc.1 { (i, m) } else { acc },
+            );
+        assert_eq!(peak, bin);
+    }
+
+    #[test]
+    fn spectrum_of_silence_is_zero() {
+        let magnitudes = spectrum(&[0.0; 64]);
+        assert!(magnitudes.iter().all(|&m| m == 0.0));
+    }
+}
@@ -0,0 +1,511 @@
+//! Parsing of OSC address and MIDI CC bindings declared by ISF inputs.
+//!
+//! Many ISF hosts support a vendor extension in which inputs declare the OSC address and/or MIDI
+//! CC that should control them within the shader's JSON metadata. For example:
+//!
+//! ```json
+//! {
+//!     "NAME": "speed",
+//!     "TYPE": "float",
+//!     "MIN": 0.0,
+//!     "MAX": 4.0,
+//!     "OSC": "/shader/speed",
+//!     "MIDI": { "CC": 21, "CHANNEL": 1 }
+//! }
+//! ```
+//!
+//! `MIDI` may also be given as a lone CC number, in which case the CC is matched on any channel.
+//!
+//! Incoming MIDI CC values are mapped onto the range of the input. OSC arguments are applied as
+//! they are, clamped to the range of the input where one is declared. The arguments of a message
+//! bound to a `float` or `long` array are applied to its elements in order.
+
+use crate::pipeline::{InputName, IsfInputData};
+use nannou::prelude::*;
+use thiserror::Error;
+
+/// The OSC and MIDI bindings declared by a single ISF input.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InputBinding {
+    /// The name of the bound input.
+    pub input: InputName,
+    /// The OSC address that controls the input, if any.
+    pub osc: Option<String>,
+    /// The MIDI CC that controls the input, if any.
+    pub midi: Option<MidiCc>,
+}
+
+/// A MIDI control change number, optionally restricted to a single channel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MidiCc {
+    /// The MIDI channel in the range `1..=16`, or `None` to match any channel.
+    pub channel: Option<u8>,
+    /// The control change number in the range `0..=127`.
+    pub cc: u8,
+}
+
+/// Errors that might occur while parsing input bindings.
+#[derive(Debug, Error)]
+pub enum BindingError {
+    #[error("failed to parse the ISF JSON metadata: {err}")]
+    Json {
+        #[from]
+        err: serde_json::Error,
+    },
+    #[error("invalid binding for input `{input}`: {msg}")]
+    Invalid { input: InputName, msg: String },
+}
+
+/// The JSON key under which inputs declare their OSC address.
+pub const OSC_KEY: &str = "OSC";
+
+/// The JSON key under which inputs declare their MIDI CC.
+pub const MIDI_KEY: &str = "MIDI";
+
+impl MidiCc {
+    /// Whether or not a CC message on the given channel and CC number matches this binding.
+    ///
+    /// The `channel` is in the range `1..=16`.
+    pub fn matches(&self, channel: u8, cc: u8) -> bool {
+        self.cc == cc && self.channel.map_or(true, |ch| ch == channel)
+    }
+}
+
+/// Parse the input bindings declared within the JSON metadata of the given ISF shader source.
+///
+/// Only inputs that declare an OSC address or MIDI CC are included. Returns an empty list if the
+/// shader has no JSON metadata.
+pub fn input_bindings_from_glsl(glsl: &str) -> Result<Vec<InputBinding>, BindingError> {
+    let json = match metadata_str(glsl) {
+        None => return Ok(vec![]),
+        Some(json) => json,
+    };
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let inputs = match value.get("INPUTS").and_then(|inputs| inputs.as_array()) {
+        None => return Ok(vec![]),
+        Some(inputs) => inputs,
+    };
+    let mut bindings = vec![];
+    for input in inputs {
+        let name = match input.get("NAME").and_then(|name| name.as_str()) {
+            None => continue,
+            Some(name) => name.to_string(),
+        };
+        let invalid = |msg: &str| BindingError::Invalid {
+            input: name.clone(),
+            msg: msg.to_string(),
+        };
+        let osc = match input.get(OSC_KEY) {
+            None => None,
+            Some(serde_json::Value::String(addr)) if addr.starts_with('/') => Some(addr.clone()),
+            Some(_) => return Err(invalid("`OSC` must be an address string starting with `/`")),
+        };
+        let midi = match input.get(MIDI_KEY) {
+            None => None,
+            Some(midi) => Some(parse_midi_cc(midi).map_err(|msg| invalid(&msg))?),
+        };
+        if osc.is_some() || midi.is_some() {
+            bindings.push(InputBinding {
+                input: name,
+                osc,
+                midi,
+            });
+        }
+    }
+    Ok(bindings)
+}
+
+// Apply a MIDI CC value in the range `0..=127` to the given input data, mapped onto the range of
+// its type.
+//
+// Returns `false` if the input type cannot be controlled by a single CC.
+pub(crate) fn apply_midi_cc(data: &mut IsfInputData, ty: &isf::InputType, value: u8) -> bool {
+    let v = value.min(127) as f32 / 127.0;
+    match (data, ty) {
+        (IsfInputData::Event { happening }, isf::InputType::Event) => *happening = v >= 0.5,
+        (IsfInputData::Bool(b), isf::InputType::Bool(_)) => *b = v >= 0.5,
+        (IsfInputData::Float(f), isf::InputType::Float(desc)) => {
+            let min = desc.min.unwrap_or(0.0);
+            let max = desc.max.unwrap_or(1.0);
+            *f = min + v * (max - min);
+        }
+        (IsfInputData::Long(n), isf::InputType::Long(desc)) => {
+            if !desc.values.is_empty() {
+                let ix = (v * (desc.values.len() - 1) as f32).round() as usize;
+                *n = desc.values[ix];
+            } else {
+                let min = desc.min.unwrap_or(0) as f32;
+                let max = desc.max.unwrap_or(127) as f32;
+                *n = (min + v * (max - min)).round() as i32;
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+// Apply the given OSC arguments to the given input data.
+//
+// Returns `false` if the arguments do not suit the type of the input.
+pub(crate) fn apply_osc_args(data: &mut IsfInputData, ty: &isf::InputType, args: &[f32]) -> bool {
+    match (data, ty) {
+        (IsfInputData::Event { happening }, isf::InputType::Event) => {
+            *happening = args.first().map_or(true, |&v| v != 0.0);
+        }
+        (IsfInputData::Bool(b), isf::InputType::Bool(_)) => match args.first() {
+            Some(&v) => *b = v != 0.0,
+            None => return false,
+        },
+        (IsfInputData::Float(f), isf::InputType::Float(desc)) => match args.first() {
+            Some(&v) => *f = clamp_opt(v, desc.min, desc.max),
+            None => return false,
+        },
+        (IsfInputData::Long(n), isf::InputType::Long(desc)) => match args.first() {
+            Some(&v) => *n = long_from_f32(v, desc),
+            None => return false,
+        },
+        (IsfInputData::FloatArray(elems), isf::InputType::Float(desc)) => {
+            if args.is_empty() {
+                return false;
+            }
+            for (elem, &v) in elems.iter_mut().zip(args) {
+                *elem = clamp_opt(v, desc.min, desc.max);
+            }
+        }
+        (IsfInputData::LongArray(elems), isf::InputType::Long(desc)) => {
+            if args.is_empty() {
+                return false;
+            }
+            for (elem, &v) in elems.iter_mut().zip(args) {
+                *elem = long_from_f32(v, desc);
+            }
+        }
+        (IsfInputData::Point2d(p), isf::InputType::Point2d(desc)) => match *args {
+            [x, y, ..] => {
+                let min = desc.min.map(|[x, y]| (Some(x), Some(y)));
+                let max = desc.max.map(|[x, y]| (Some(x), Some(y)));
+                let (min_x, min_y) = min.unwrap_or((None, None));
+                let (max_x, max_y) = max.unwrap_or((None, None));
+                *p = pt2(clamp_opt(x, min_x, max_x), clamp_opt(y, min_y, max_y));
+            }
+            _ => return false,
+        },
+        (IsfInputData::Color(c), isf::InputType::Color(_)) => match *args {
+            [r, g, b] => *c = lin_srgba(r, g, b, 1.0),
+            [r, g, b, a, ..] => *c = lin_srgba(r, g, b, a),
+            _ => return false,
+        },
+        _ => return false,
+    }
+    true
+}
+
+// The JSON metadata within the leading comment of an ISF shader.
+pub(crate) fn metadata_str(glsl: &str) -> Option<&str> {
+    let start = glsl.find("/*")? + 2;
+    let len = glsl[start..].find("*/")?;
+    Some(&glsl[start..start + len])
+}
+
+// Parse a MIDI CC binding, either a lone CC number or an object with `CC` and `CHANNEL` fields.
+fn parse_midi_cc(value: &serde_json::Value) -> Result<MidiCc, String> {
+    let (cc, channel) = match value {
+        serde_json::Value::Number(_) => (value, None),
+        serde_json::Value::Object(obj) => {
+            let cc = obj.get("CC").ok_or("`MIDI` must declare a `CC`")?;
+            (cc, obj.get("CHANNEL"))
+        }
+        _ => return Err("`MIDI` must be a CC number or an object".to_string()),
+    };
+    let cc = match cc.as_u64() {
+        Some(cc) if cc <= 127 => cc as u8,
+        _ => return Err("`CC` must be in the range 0..=127".to_string()),
+    };
+    let channel = match channel.map(|ch| ch.as_u64()) {
+        None => None,
+        Some(Some(ch)) if (1..=16).contains(&ch) => Some(ch as u8),
+        Some(_) => return Err("`CHANNEL` must be in the range 1..=16".to_string()),
+    };
+    Ok(MidiCc { channel, cc })
+}
+
+// Round the value to the nearest of the input's listed values, or clamp it to its range if it has
+// none.
+fn long_from_f32(v: f32, desc: &isf::InputLong) -> i32 {
+    let n = v.round() as i32;
+    crate::input::nearest_long_value(desc, n).unwrap_or_else(|| clamp_opt(n, desc.min, desc.max))
+}
+
+// Clamp the value to the given bounds where they are specified.
+fn clamp_opt<T: PartialOrd>(mut v: T, min: Option<T>, max: Option<T>) -> T {
+    if let Some(min) = min {
+        if v < min {
+            v = min;
+        }
+    }
+    if let Some(max) = max {
+        if v > max {
+            v = max;
+        }
+    }
+    v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wrap the given JSON inputs within an ISF shader source.
+    fn glsl(inputs: &str) -> String {
+        format!(
+            "/*{{\n\"ISFVSN\": \"2\",\n\"INPUTS\": [{}]\n}}*/\nvoid main() {{}}\n",
+            inputs
+        )
+    }
+
+    // The type of the single input declared by the given JSON.
+    fn input_ty(input: &str) -> isf::InputType {
+        isf::parse(&glsl(input)).unwrap().inputs.remove(0).ty
+    }
+
+    // The error message produced by the bindings of the given JSON input.
+    fn binding_error(input: &str) -> String {
+        match input_bindings_from_glsl(&glsl(input)) {
+            Err(BindingError::Invalid { msg, .. }) => msg,
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    fn float(data: &IsfInputData) -> f32 {
+        match *data {
+            IsfInputData::Float(f) => f,
+            _ => panic!("expected a float"),
+        }
+    }
+
+    fn long(data: &IsfInputData) -> i32 {
+        match *data {
+            IsfInputData::Long(n) => n,
+            _ => panic!("expected a long"),
+        }
+    }
+
+    fn boolean(data: &IsfInputData) -> bool {
+        match *data {
+            IsfInputData::Bool(b) => b,
+            IsfInputData::Event { happening } => happening,
+            _ => panic!("expected a bool or event"),
+        }
+    }
+
+    const SPEED: &str = r#"{"NAME": "speed", "TYPE": "float", "MIN": 0.0, "MAX": 4.0}"#;
+    const STEPS: &str = r#"{"NAME": "steps", "TYPE": "long", "MIN": 0, "MAX": 10}"#;
+    const MODE: &str =
+        r#"{"NAME": "mode", "TYPE": "long", "VALUES": [1, 2, 4], "LABELS": ["a", "b", "c"]}"#;
+
+    #[test]
+    fn parses_osc_and_midi_bindings() {
+        let inputs = [
+            r#"{"NAME": "a", "TYPE": "float", "OSC": "/a", "MIDI": {"CC": 21, "CHANNEL": 2}}"#,
+            r#"{"NAME": "b", "TYPE": "float"}"#,
+            r#"{"NAME": "c", "TYPE": "float", "MIDI": 7}"#,
+        ];
+        let bindings = input_bindings_from_glsl(&glsl(&inputs.join(","))).unwrap();
+        let expected = vec![
+            InputBinding {
+                input: "a".to_string(),
+                osc: Some("/a".to_string()),
+                midi: Some(MidiCc {
+                    channel: Some(2),
+                    cc: 21,
+                }),
+            },
+            InputBinding {
+                input: "c".to_string(),
+                osc: None,
+                midi: Some(MidiCc {
+                    channel: None,
+                    cc: 7,
+                }),
+            },
+        ];
+        assert_eq!(bindings, expected);
+    }
+
+    #[test]
+    fn no_metadata_has_no_bindings() {
+        assert!(input_bindings_from_glsl("void main() {}")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_bindings() {
+        let osc = r#"{"NAME": "a", "TYPE": "float", "OSC": "speed"}"#;
+        assert!(binding_error(osc).contains("`OSC`"));
+        let cc = r#"{"NAME": "a", "TYPE": "float", "MIDI": 128}"#;
+        assert!(binding_error(cc).contains("`CC`"));
+        let missing = r#"{"NAME": "a", "TYPE": "float", "MIDI": {"CHANNEL": 1}}"#;
+        assert!(binding_error(missing).contains("must declare a `CC`"));
+        for &channel in &["0", "17", "\"1\""] {
+            let midi = format!(
+                r#"{{"NAME": "a", "TYPE": "float", "MIDI": {{"CC": 1, "CHANNEL": {}}}}}"#,
+                channel
+            );
+            assert!(binding_error(&midi).contains("`CHANNEL`"));
+        }
+        let kind = r#"{"NAME": "a", "TYPE": "float", "MIDI": "21"}"#;
+        assert!(binding_error(kind).contains("CC number or an object"));
+    }
+
+    #[test]
+    fn midi_cc_matches_channel() {
+        let any = MidiCc {
+            channel: None,
+            cc: 21,
+        };
+        assert!(any.matches(1, 21) && any.matches(16, 21));
+        assert!(!any.matches(1, 22));
+        let ch2 = MidiCc {
+            channel: Some(2),
+            cc: 21,
+        };
+        assert!(ch2.matches(2, 21));
+        assert!(!ch2.matches(1, 21));
+    }
+
+    #[test]
+    fn midi_cc_scales_floats_onto_their_range() {
+        let ty = input_ty(SPEED);
+        let mut data = IsfInputData::Float(1.0);
+        for &(value, expected) in &[(0, 0.0), (127, 4.0), (64, 64.0 / 127.0 * 4.0)] {
+            assert!(apply_midi_cc(&mut data, &ty, value));
+            assert!((float(&data) - expected).abs() < 1e-6);
+        }
+        // Without a declared range floats are mapped onto `0.0..=1.0`.
+        let ty = input_ty(r#"{"NAME": "level", "TYPE": "float"}"#);
+        assert!(apply_midi_cc(&mut data, &ty, 127));
+        assert_eq!(float(&data), 1.0);
+    }
+
+    #[test]
+    fn midi_cc_scales_longs_onto_their_range_or_values() {
+        let ty = input_ty(STEPS);
+        let mut data = IsfInputData::Long(0);
+        for &(value, expected) in &[(0, 0), (64, 5), (127, 10)] {
+            assert!(apply_midi_cc(&mut data, &ty, value));
+            assert_eq!(long(&data), expected);
+        }
+        let ty = input_ty(MODE);
+        for &(value, expected) in &[(0, 1), (64, 2), (127, 4)] {
+            assert!(apply_midi_cc(&mut data, &ty, value));
+            assert_eq!(long(&data), expected);
+        }
+    }
+
+    #[test]
+    fn midi_cc_switches_bools_at_the_midpoint() {
+        let ty = input_ty(r#"{"NAME": "on", "TYPE": "bool"}"#);
+        let mut data = IsfInputData::Bool(true);
+        assert!(apply_midi_cc(&mut data, &ty, 63));
+        assert!(!boolean(&data));
+        assert!(apply_midi_cc(&mut data, &ty, 64));
+        assert!(boolean(&data));
+    }
+
+    #[test]
+    fn midi_cc_rejects_unsuitable_inputs() {
+        let ty = input_ty(r#"{"NAME": "pos", "TYPE": "point2D"}"#);
+        let mut data = IsfInputData::Point2d(pt2(0.0, 0.0));
+        assert!(!apply_midi_cc(&mut data, &ty, 127));
+        // Data that does not match the type of the input is left untouched.
+        let mut data = IsfInputData::Bool(false);
+        assert!(!apply_midi_cc(&mut data, &input_ty(SPEED), 127));
+        assert!(!boolean(&data));
+    }
+
+    #[test]
+    fn osc_args_are_clamped_to_the_range() {
+        let ty = input_ty(SPEED);
+        let mut data = IsfInputData::Float(0.0);
+        for &(value, expected) in &[(2.5, 2.5), (10.0, 4.0), (-1.0, 0.0)] {
+            assert!(apply_osc_args(&mut data, &ty, &[value]));
+            assert_eq!(float(&data), expected);
+        }
+        let mut data = IsfInputData::Long(0);
+        assert!(apply_osc_args(&mut data, &input_ty(STEPS), &[20.0]));
+        assert_eq!(long(&data), 10);
+        assert!(apply_osc_args(&mut data, &input_ty(MODE), &[3.6]));
+        assert_eq!(long(&data), 4);
+    }
+
+    #[test]
+    fn osc_args_are_applied_to_array_elements_in_order() {
+        let ty = input_ty(SPEED);
+        let mut data = IsfInputData::FloatArray(vec![1.0; 3]);
+        assert!(apply_osc_args(&mut data, &ty, &[0.5, 9.0]));
+        match data {
+            IsfInputData::FloatArray(ref elems) => assert_eq!(elems[..], [0.5, 4.0, 1.0]),
+            _ => unreachable!(),
+        }
+        let mut data = IsfInputData::LongArray(vec![0; 2]);
+        assert!(apply_osc_args(
+            &mut data,
+            &input_ty(STEPS),
+            &[3.0, 4.0, 5.0]
+        ));
+        match data {
+            IsfInputData::LongArray(ref elems) => assert_eq!(elems[..], [3, 4]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn osc_args_set_points_and_colors() {
+        let ty =
+            input_ty(r#"{"NAME": "pos", "TYPE": "point2D", "MIN": [0.0, 0.0], "MAX": [1.0, 2.0]}"#);
+        let mut data = IsfInputData::Point2d(pt2(0.0, 0.0));
+        assert!(apply_osc_args(&mut data, &ty, &[0.5, 5.0]));
+        match data {
+            IsfInputData::Point2d(p) => assert_eq!(p, pt2(0.5, 2.0)),
+            _ => unreachable!(),
+        }
+        let ty = input_ty(r#"{"NAME": "tint", "TYPE": "color"}"#);
+        let mut data = IsfInputData::Color(lin_srgba(0.0, 0.0, 0.0, 0.0));
+        assert!(apply_osc_args(&mut data, &ty, &[0.1, 0.2, 0.3]));
+        match data {
+            IsfInputData::Color(c) => assert_eq!(c, lin_srgba(0.1, 0.2, 0.3, 1.0)),
+            _ => unreachable!(),
+        }
+        assert!(apply_osc_args(&mut data, &ty, &[0.1, 0.2, 0.3, 0.4]));
+        match data {
+            IsfInputData::Color(c) => assert_eq!(c, lin_srgba(0.1, 0.2, 0.3, 0.4)),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn osc_events_default_to_happening() {
+        let ty = input_ty(r#"{"NAME": "go", "TYPE": "event"}"#);
+        let mut data = IsfInputData::Event { happening: false };
+        assert!(apply_osc_args(&mut data, &ty, &[]));
+        assert!(boolean(&data));
+        assert!(apply_osc_args(&mut data, &ty, &[0.0]));
+        assert!(!boolean(&data));
+    }
+
+    #[test]
+    fn osc_args_reject_mismatched_arguments() {
+        let mut data = IsfInputData::Float(1.0);
+        assert!(!apply_osc_args(&mut data, &input_ty(SPEED), &[]));
+        let ty = input_ty(r#"{"NAME": "pos", "TYPE": "point2D"}"#);
+        let mut point = IsfInputData::Point2d(pt2(0.0, 0.0));
+        assert!(!apply_osc_args(&mut point, &ty, &[1.0]));
+        let ty = input_ty(r#"{"NAME": "tint", "TYPE": "color"}"#);
+        let mut color = IsfInputData::Color(lin_srgba(0.0, 0.0, 0.0, 0.0));
+        assert!(!apply_osc_args(&mut color, &ty, &[1.0, 1.0]));
+        // Data that does not match the type of the input is left untouched.
+        assert!(!apply_osc_args(&mut data, &input_ty(STEPS), &[5.0]));
+        assert_eq!(float(&data), 1.0);
+    }
+}
@@ -0,0 +1,328 @@
+//! A cache of compiled SPIR-V for ISF fragment shaders.
+//!
+//! Compiling an ISF shader from GLSL to SPIR-V can take hundreds of milliseconds. While a
+//! **ShaderCache** is set via `set_shader_cache`, all ISF fragment shaders compiled by the crate
+//! first consult the cache, keyed by a hash of the generated GLSL source. Persistent caches also
+//! write compiled SPIR-V to a directory so that the cache remains warm across app restarts.
+//!
+//! `precompile_isf_dir` may be used to compile a whole folder of shaders in parallel ahead of
+//! time, e.g. while the app is starting up, so that switching between them during a performance
+//! does not stall.
+
+use crate::pipeline::{self, ShaderError};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use threadpool::ThreadPool;
+
+/// A cache of compiled SPIR-V keyed by a hash of the GLSL source.
+#[derive(Debug, Default)]
+pub struct ShaderCache {
+    dir: Option<PathBuf>,
+    memory: Mutex<HashMap<u64, Vec<u8>>>,
+}
+
+/// The file extension used for ISF fragment shaders.
+pub const ISF_FRAGMENT_EXTENSION: &str = "fs";
+
+/// The file extension used for SPIR-V files within a persistent cache directory.
+pub const SPIRV_EXTENSION: &str = "spv";
+
+// The cache used by the crate's ISF shader compilation functions, if any.
+static SHADER_CACHE: Mutex<Option<Arc<ShaderCache>>> = Mutex::new(None);
+
+// The first word of every SPIR-V module.
+const SPIRV_MAGIC_NUMBER: u32 = 0x0723_0203;
+
+impl ShaderCache {
+    /// A cache that only lives for the duration of the process.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// A cache that also persists compiled SPIR-V within the given directory.
+    ///
+    /// The directory is created if it does not yet exist.
+    pub fn persistent<P>(dir: P) -> io::Result<Self>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let memory = Default::default();
+        let dir = Some(dir);
+        Ok(ShaderCache { dir, memory })
+    }
+
+    /// The directory in which compiled SPIR-V is persisted, if any.
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Retrieve the SPIR-V for the given GLSL source if it was previously compiled.
+    ///
+    /// SPIR-V found within the cache directory is loaded into memory for subsequent requests.
+    pub fn get(&self, glsl: &str) -> Option<Vec<u8>> {
+        let key = cache_key(glsl);
+        if let Some(spirv) = self.lock_memory().get(&key) {
+            return Some(spirv.clone());
+        }
+        let path = self.file_path(key)?;
+        let spirv = std::fs::read(path).ok().filter(|b| is_spirv(b))?;
+        self.lock_memory().insert(key, spirv.clone());
+        Some(spirv)
+    }
+
+    /// Insert the SPIR-V that was compiled from the given GLSL source.
+    ///
+    /// For persistent caches, the SPIR-V is also written to the cache directory. Failing to do so
+    /// is not considered an error, as the SPIR-V remains cached in memory.
+    pub fn insert(&self, glsl: &str, spirv: Vec<u8>) {
+        let key = cache_key(glsl);
+        if let Some(path) = self.file_path(key) {
+            // Write to a temporary file first so that readers never observe a partial file.
+            let tmp_path = path.with_extension("tmp");
+            let written =
+                std::fs::write(&tmp_path, &spirv).and_then(|_| std::fs::rename(&tmp_path, &path));
+            if written.is_err() {
+                std::fs::remove_file(&tmp_path).ok();
+            }
+        }
+        self.lock_memory().insert(key, spirv);
+    }
+
+    /// Retrieve the SPIR-V for the given GLSL source, compiling it with `compile` if it is not yet
+    /// cached.
+    pub fn get_or_compile<F, E>(&self, glsl: &str, compile: F) -> Result<Vec<u8>, E>
+    where
+        F: FnOnce(&str) -> Result<Vec<u8>, E>,
+    {
+        if let Some(spirv) = self.get(glsl) {
+            return Ok(spirv);
+        }
+        let spirv = compile(glsl)?;
+        self.insert(glsl, spirv.clone());
+        Ok(spirv)
+    }
+
+    /// Remove all SPIR-V from memory, leaving the cache directory untouched.
+    pub fn clear_memory(&self) {
+        self.lock_memory().clear();
+    }
+
+    fn lock_memory(&self) -> std::sync::MutexGuard<HashMap<u64, Vec<u8>>> {
+        match self.memory.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn file_path(&self, key: u64) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        let file_name = format!("{:016x}.{}", key, SPIRV_EXTENSION);
+        Some(dir.join(file_name))
+    }
+}
+
+/// Set the cache consulted when compiling ISF fragment shaders.
+///
+/// By default, no cache is used and shaders are always compiled from source.
+pub fn set_shader_cache(cache: Option<Arc<ShaderCache>>) {
+    let mut guard = match SHADER_CACHE.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *guard = cache;
+}
+
+/// The cache consulted when compiling ISF fragment shaders, if any.
+pub fn shader_cache() -> Option<Arc<ShaderCache>> {
+    let guard = match SHADER_CACHE.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    guard.clone()
+}
+
+/// Compile all ISF fragment shaders within the given directory into the given cache.
+///
+/// Shaders are found by their `ISF_FRAGMENT_EXTENSION` and compiled in parallel on a pool of
+/// worker threads, one per CPU. This blocks until all shaders have been compiled, so consider
+/// calling it from a separate thread.
+///
+/// Returns the path and error of each shader that failed to compile.
+pub fn precompile_isf_dir(cache: &Arc<ShaderCache>, dir: &Path) -> Vec<(PathBuf, ShaderError)> {
+    let threadpool = ThreadPool::default();
+    let (tx, rx) = mpsc::channel();
+    let mut count = 0;
+    for path in isf_fragment_paths(dir) {
+        let cache = cache.clone();
+        let tx = tx.clone();
+        threadpool.execute(move || {
+            let res = pipeline::compile_isf_spirv_with_cache(&path, Some(&cache));
+            tx.send((path, res.err())).ok();
+        });
+        count += 1;
+    }
+    drop(tx);
+    rx.iter()
+        .take(count)
+        .filter_map(|(path, err)| err.map(|err| (path, err)))
+        .collect()
+}
+
+// Given a path to a directory, produces the paths of all ISF fragment shaders within it.
+pub(crate) fn isf_fragment_paths(dir: &Path) -> impl Iterator<Item = PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|res| res.ok())
+        .map(|entry| entry.path().to_path_buf())
+        .filter(|path| {
+            path.extension().and_then(|ext| ext.to_str()) == Some(ISF_FRAGMENT_EXTENSION)
+        })
+}
+
+// A hash of the GLSL source that is stable across runs and platforms (64-bit FNV-1a).
+//
+// The crate version is included so that upgrading invalidates previously persisted SPIR-V.
+fn cache_key(glsl: &str) -> u64 {
+    let version = env!("CARGO_PKG_VERSION").as_bytes();
+    fnv1a(version.iter().chain(&[0]).chain(glsl.as_bytes()))
+}
+
+// The 64-bit FNV-1a hash of the given bytes.
+fn fnv1a<'a, I>(bytes: I) -> u64
+where
+    I: IntoIterator<Item = &'a u8>,
+{
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+// Whether or not the given bytes appear to be a valid SPIR-V module.
+fn is_spirv(bytes: &[u8]) -> bool {
+    if bytes.len() < 4 || bytes.len() % 4 != 0 {
+        return false;
+    }
+    let magic = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    u32::from_le_bytes(magic) == SPIRV_MAGIC_NUMBER
+        || u32::from_be_bytes(magic) == SPIRV_MAGIC_NUMBER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLSL: &str = "void main() { gl_FragColor = vec4(1.0); }";
+
+    // A minimal SPIR-V header followed by a single word.
+    fn spirv() -> Vec<u8> {
+        let mut bytes = SPIRV_MAGIC_NUMBER.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&[0, 1, 0, 0]);
+        bytes
+    }
+
+    // A fresh directory for a persistent cache, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir_name = format!("nannou_isf_cache_{}_{}", std::process::id(), name);
+            let dir = std::env::temp_dir().join(dir_name);
+            std::fs::remove_dir_all(&dir).ok();
+            TempDir(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn fnv1a_reference_values() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn cache_key_is_versioned_hash_of_source() {
+        let mut bytes = env!("CARGO_PKG_VERSION").as_bytes().to_vec();
+        bytes.push(0);
+        bytes.extend_from_slice(GLSL.as_bytes());
+        assert_eq!(cache_key(GLSL), fnv1a(&bytes));
+        assert_eq!(cache_key(GLSL), cache_key(&GLSL.to_string()));
+        assert_ne!(cache_key(GLSL), cache_key(&GLSL.replace("1.0", "0.5")));
+        assert_ne!(cache_key(GLSL), fnv1a(GLSL.as_bytes()));
+    }
+
+    #[test]
+    fn spirv_magic_number() {
+        let le = spirv();
+        let be = SPIRV_MAGIC_NUMBER.to_be_bytes();
+        assert!(is_spirv(&le));
+        assert!(is_spirv(&be));
+        assert!(!is_spirv(&le[..3]));
+        assert!(!is_spirv(&le[..6]));
+        assert!(!is_spirv(b"not spirv"));
+        assert!(!is_spirv(&[0; 8]));
+    }
+
+    #[test]
+    fn in_memory_cache() {
+        let cache = ShaderCache::in_memory();
+        assert_eq!(cache.dir(), None);
+        assert_eq!(cache.get(GLSL), None);
+        cache.insert(GLSL, spirv());
+        assert_eq!(cache.get(GLSL), Some(spirv()));
+        cache.clear_memory();
+        assert_eq!(cache.get(GLSL), None);
+    }
+
+    #[test]
+    fn get_or_compile_compiles_once() {
+        let cache = ShaderCache::in_memory();
+        let compiles = std::cell::Cell::new(0);
+        let compile = |_: &str| -> Result<Vec<u8>, ()> {
+            compiles.set(compiles.get() + 1);
+            Ok(spirv())
+        };
+        assert_eq!(cache.get_or_compile(GLSL, compile), Ok(spirv()));
+        assert_eq!(cache.get_or_compile(GLSL, compile), Ok(spirv()));
+        assert_eq!(compiles.get(), 1);
+        let failed = cache.get_or_compile("", |_| Err::<Vec<u8>, _>("failed"));
+        assert_eq!(failed, Err("failed"));
+        assert_eq!(cache.get(""), None);
+    }
+
+    #[test]
+    fn persistent_cache_survives_restart() {
+        let tmp = TempDir::new("survives_restart");
+        let cache = ShaderCache::persistent(&tmp.0).unwrap();
+        cache.insert(GLSL, spirv());
+        let file_name = format!("{:016x}.{}", cache_key(GLSL), SPIRV_EXTENSION);
+        assert!(tmp.0.join(file_name).is_file());
+
+        // A new cache, or one whose memory was cleared, loads the SPIR-V from the directory.
+        cache.clear_memory();
+        assert_eq!(cache.get(GLSL), Some(spirv()));
+        let restarted = ShaderCache::persistent(&tmp.0).unwrap();
+        assert_eq!(restarted.dir(), Some(tmp.0.as_path()));
+        assert_eq!(restarted.get(GLSL), Some(spirv()));
+    }
+
+    #[test]
+    fn persistent_cache_ignores_invalid_files() {
+        let tmp = TempDir::new("invalid_files");
+        let cache = ShaderCache::persistent(&tmp.0).unwrap();
+        let path = cache.file_path(cache_key(GLSL)).unwrap();
+        std::fs::write(path, b"truncated").unwrap();
+        assert_eq!(cache.get(GLSL), None);
+    }
+}
@@ -0,0 +1,303 @@
+//! Extended metadata for ISF inputs: uniform arrays and `long` option lists.
+//!
+//! Many published ISF shaders declare `float` and `long` inputs that are indexed as arrays within
+//! the shader. These declare the length of the array with a `COUNT` field within their JSON
+//! metadata. For example:
+//!
+//! ```json
+//! {
+//!     "NAME": "weights",
+//!     "TYPE": "float",
+//!     "COUNT": 8,
+//!     "DEFAULT": 0.5,
+//!     "MIN": 0.0,
+//!     "MAX": 1.0
+//! }
+//! ```
+//!
+//! The input is declared as `float weights[8]` within the `IsfDataInputs` uniform block, with each
+//! element initialised to the `DEFAULT`. `long` arrays are declared as `int` arrays.
+//!
+//! `long` inputs may also declare a list of `VALUES` with associated `LABELS`, presented by most
+//! hosts as a dropdown. `long_options` pairs each value with its label for use within a UI.
+
+use crate::pipeline::{InputName, ISF_INPUT_UNIFORMS_LEN};
+use thiserror::Error;
+
+/// The array length declared by a single ISF input.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InputArray {
+    /// The name of the array input.
+    pub input: InputName,
+    /// The number of elements within the array.
+    pub len: usize,
+}
+
+/// A single option of a `long` input that declares a list of `VALUES`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LongOption {
+    /// The value written to the shader when the option is selected.
+    pub value: i32,
+    /// The label describing the option, or the value itself where no label is declared.
+    pub label: String,
+}
+
+/// Errors that might occur while parsing input arrays.
+#[derive(Debug, Error)]
+pub enum ArrayError {
+    #[error("failed to parse the ISF JSON metadata: {err}")]
+    Json {
+        #[from]
+        err: serde_json::Error,
+    },
+    #[error("invalid array for input `{input}`: {msg}")]
+    Invalid { input: InputName, msg: String },
+}
+
+/// The JSON key under which inputs declare their array length.
+pub const COUNT_KEY: &str = "COUNT";
+
+/// The maximum length of an input array.
+///
+/// Each element of a uniform array occupies 16 bytes under the std140 layout, so arrays are
+/// limited in order to fit within the `IsfDataInputs` uniform block. The block as a whole is also
+/// limited to 4096 bytes, so several long arrays may still exceed it.
+pub const MAX_ARRAY_LEN: usize = 64;
+
+/// Parse the arrays declared within the JSON metadata of the given ISF shader source.
+///
+/// Only inputs that declare a `COUNT` are included. Returns an empty list if the shader has no
+/// JSON metadata.
+///
+/// Returns an `ArrayError::Invalid` for the first input that does not fit within the
+/// `IsfDataInputs` uniform block under the std140 layout.
+pub fn input_arrays_from_glsl(glsl: &str) -> Result<Vec<InputArray>, ArrayError> {
+    let json = match crate::binding::metadata_str(glsl) {
+        None => return Ok(vec![]),
+        Some(json) => json,
+    };
+    let value: serde_json::Value = serde_json::from_str(json)?;
+    let inputs = match value.get("INPUTS").and_then(|inputs| inputs.as_array()) {
+        None => return Ok(vec![]),
+        Some(inputs) => inputs,
+    };
+    let mut arrays = vec![];
+    let mut offset = 0;
+    for input in inputs {
+        let name = match input.get("NAME").and_then(|name| name.as_str()) {
+            None => continue,
+            Some(name) => name.to_string(),
+        };
+        let ty = input.get("TYPE").and_then(|ty| ty.as_str());
+        let invalid = |msg: &str| ArrayError::Invalid {
+            input: name.clone(),
+            msg: msg.to_string(),
+        };
+        let len = match input.get(COUNT_KEY) {
+            None => None,
+            Some(count) => {
+                match ty {
+                    Some("float") | Some("long") => (),
+                    _ => {
+                        return Err(invalid(
+                            "`COUNT` is only supported by `float` and `long` inputs",
+                        ))
+                    }
+                }
+                match count.as_u64() {
+                    Some(len) if len >= 1 && len as usize <= MAX_ARRAY_LEN => Some(len as usize),
+                    _ => {
+                        let msg = format!("`COUNT` must be in the range 1..={}", MAX_ARRAY_LEN);
+                        return Err(invalid(&msg));
+                    }
+                }
+            }
+        };
+        // Track the std140 layout of the `IsfDataInputs` block, matching `pack_std140`.
+        let (align, words) = match (ty, len) {
+            (_, Some(len)) => (4, 4 * len),
+            (Some("event"), None)
+            | (Some("bool"), None)
+            | (Some("long"), None)
+            | (Some("float"), None) => (1, 1),
+            (Some("point2D"), None) => (2, 2),
+            (Some("color"), None) => (4, 4),
+            _ => (1, 0),
+        };
+        offset = std140_align(offset, align) + words;
+        if offset > ISF_INPUT_UNIFORMS_LEN {
+            let msg = format!(
+                "the inputs exceed the {} byte capacity of the `IsfDataInputs` uniform block",
+                ISF_INPUT_UNIFORMS_LEN * 4,
+            );
+            return Err(invalid(&msg));
+        }
+        if let Some(len) = len {
+            arrays.push(InputArray { input: name, len });
+        }
+    }
+    Ok(arrays)
+}
+
+/// The options of the given `long` input, pairing each of its `VALUES` with its label.
+///
+/// Values without a corresponding label are labelled with the value itself. Returns an empty list
+/// if the input declares no `VALUES`.
+pub fn long_options(long: &isf::InputLong) -> Vec<LongOption> {
+    long.values
+        .iter()
+        .enumerate()
+        .map(|(ix, &value)| {
+            let label = long
+                .labels
+                .get(ix)
+                .cloned()
+                .unwrap_or_else(|| value.to_string());
+            LongOption { value, label }
+        })
+        .collect()
+}
+
+/// The option closest to the given value, or `None` if the input declares no `VALUES`.
+pub fn nearest_long_value(long: &isf::InputLong, value: i32) -> Option<i32> {
+    long.values
+        .iter()
+        .cloned()
+        .min_by_key(|&v| (v as i64 - value as i64).abs())
+}
+
+// The length of the array declared by the input with the given name, if any.
+pub(crate) fn array_len(arrays: &[InputArray], name: &str) -> Option<usize> {
+    arrays.iter().find(|a| a.input == name).map(|a| a.len)
+}
+
+// Round the given offset in words up to the given std140 alignment in words.
+pub(crate) fn std140_align(offset: usize, align: usize) -> usize {
+    (offset + align - 1) / align * align
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wrap the given JSON inputs within an ISF shader source.
+    fn glsl(inputs: &str) -> String {
+        format!(
+            "/*{{\n\"ISFVSN\": \"2\",\n\"INPUTS\": [{}]\n}}*/\nvoid main() {{}}\n",
+            inputs
+        )
+    }
+
+    fn float_array(name: &str, count: usize) -> String {
+        format!(
+            r#"{{"NAME": "{}", "TYPE": "float", "COUNT": {}}}"#,
+            name, count
+        )
+    }
+
+    #[test]
+    fn no_metadata_has_no_arrays() {
+        let arrays = input_arrays_from_glsl("void main() {}").unwrap();
+        assert!(arrays.is_empty());
+        let arrays = input_arrays_from_glsl("/*{\"ISFVSN\": \"2\"}*/").unwrap();
+        assert!(arrays.is_empty());
+    }
+
+    #[test]
+    fn parses_counts_in_declaration_order() {
+        let inputs = [
+            float_array("weights", 8),
+            r#"{"NAME": "level", "TYPE": "float"}"#.to_string(),
+            r#"{"NAME": "steps", "TYPE": "long", "COUNT": 3}"#.to_string(),
+        ];
+        let arrays = input_arrays_from_glsl(&glsl(&inputs.join(","))).unwrap();
+        let expected = vec![
+            InputArray {
+                input: "weights".to_string(),
+                len: 8,
+            },
+            InputArray {
+                input: "steps".to_string(),
+                len: 3,
+            },
+        ];
+        assert_eq!(arrays, expected);
+        assert_eq!(array_len(&arrays, "steps"), Some(3));
+        assert_eq!(array_len(&arrays, "level"), None);
+    }
+
+    #[test]
+    fn rejects_count_on_unsupported_type() {
+        let inputs = r#"{"NAME": "tint", "TYPE": "color", "COUNT": 2}"#;
+        match input_arrays_from_glsl(&glsl(inputs)) {
+            Err(ArrayError::Invalid { input, .. }) => assert_eq!(input, "tint"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_count_out_of_range() {
+        for &count in &["0", "-1", "1.5", "65", "\"8\""] {
+            let inputs = format!(r#"{{"NAME": "w", "TYPE": "float", "COUNT": {}}}"#, count);
+            assert!(matches!(
+                input_arrays_from_glsl(&glsl(&inputs)),
+                Err(ArrayError::Invalid { .. })
+            ));
+        }
+        let inputs = float_array("w", MAX_ARRAY_LEN);
+        assert!(input_arrays_from_glsl(&glsl(&inputs)).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        let glsl = "/*{\"INPUTS\": [}*/";
+        assert!(matches!(
+            input_arrays_from_glsl(glsl),
+            Err(ArrayError::Json { .. })
+        ));
+    }
+
+    #[test]
+    fn accepts_arrays_that_fill_the_uniform_block() {
+        // Each max length array occupies a quarter of the block.
+        let n = ISF_INPUT_UNIFORMS_LEN / (4 * MAX_ARRAY_LEN);
+        let inputs: Vec<_> = (0..n)
+            .map(|i| float_array(&format!("a{}", i), MAX_ARRAY_LEN))
+            .collect();
+        let arrays = input_arrays_from_glsl(&glsl(&inputs.join(","))).unwrap();
+        assert_eq!(arrays.len(), n);
+    }
+
+    #[test]
+    fn rejects_inputs_exceeding_the_uniform_block() {
+        let n = ISF_INPUT_UNIFORMS_LEN / (4 * MAX_ARRAY_LEN);
+        let mut inputs: Vec<_> = (0..n)
+            .map(|i| float_array(&format!("a{}", i), MAX_ARRAY_LEN))
+            .collect();
+        inputs.insert(0, r#"{"NAME": "speed", "TYPE": "float"}"#.to_string());
+        match input_arrays_from_glsl(&glsl(&inputs.join(","))) {
+            Err(ArrayError::Invalid { input, .. }) => assert_eq!(input, format!("a{}", n - 1)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn textures_occupy_no_uniform_space() {
+        let n = ISF_INPUT_UNIFORMS_LEN / (4 * MAX_ARRAY_LEN);
+        let mut inputs: Vec<_> = (0..n)
+            .map(|i| float_array(&format!("a{}", i), MAX_ARRAY_LEN))
+            .collect();
+        inputs.push(r#"{"NAME": "img", "TYPE": "image"}"#.to_string());
+        inputs.push(r#"{"NAME": "snd", "TYPE": "audioFFT"}"#.to_string());
+        assert!(input_arrays_from_glsl(&glsl(&inputs.join(","))).is_ok());
+    }
+
+    #[test]
+    fn std140_align_rounds_up() {
+        assert_eq!(std140_align(0, 4), 0);
+        assert_eq!(std140_align(1, 4), 4);
+        assert_eq!(std140_align(4, 4), 4);
+        assert_eq!(std140_align(3, 2), 4);
+        assert_eq!(std140_align(3, 1), 3);
+    }
+}
@@ -1,9 +1,28 @@
 //! A crate aimed at making it easy to set up an ISF hot-loading environment with nannou.
 
-pub use crate::pipeline::{IsfPipeline, IsfTime};
+#[cfg(feature = "audio")]
+pub use crate::audio::{AudioInput, AudioInputError};
+pub use crate::binding::{input_bindings_from_glsl, BindingError, InputBinding, MidiCc};
+pub use crate::cache::{precompile_isf_dir, set_shader_cache, shader_cache, ShaderCache};
+pub use crate::input::{input_arrays_from_glsl, long_options, ArrayError, InputArray, LongOption};
+pub use crate::pipeline::{IsfError, IsfPipeline, IsfTime, ShaderError};
+pub use crate::transition::{
+    is_transition, Transition, TRANSITION_CATEGORY, TRANSITION_END_IMAGE, TRANSITION_PROGRESS,
+    TRANSITION_START_IMAGE,
+};
+pub use crate::validate::{
+    validate_isf_dir, validate_isf_file, ShaderReport, ValidationError, DEFAULT_VALIDATION_SIZE,
+};
 use std::path::Path;
 
+#[cfg(feature = "audio")]
+mod audio;
+pub mod binding;
+mod cache;
+pub mod input;
 mod pipeline;
+mod transition;
+pub mod validate;
 
 /// Read the ISF from the shader file at the given path.
 ///
@@ -18,6 +37,14 @@ pub fn isf_from_shader_path(path: &Path) -> Result<isf::Isf, isf::ParseError> {
 ///
 /// This string should be inserted directly after the version preprocessor.
 pub fn glsl_string_from_isf(isf: &isf::Isf) -> String {
+    glsl_string_from_isf_with_arrays(isf, &[])
+}
+
+/// Generate the necessary GLSL declarations from the given ISF, declaring the given inputs as
+/// arrays.
+///
+/// The arrays may be parsed from the shader source via `input_arrays_from_glsl`.
+pub fn glsl_string_from_isf_with_arrays(isf: &isf::Isf, arrays: &[InputArray]) -> String {
     // The normalised coords passed through from the vertex shader.
     let frag_norm_coord_str = "
         layout(location = 0) in vec2 isf_FragNormCoord;
@@ -55,7 +82,11 @@ pub fn glsl_string_from_isf(isf: &isf::Isf) -> String {
                     | isf::InputType::Audio(_)
                     | isf::InputType::AudioFft(_) => continue,
                 };
-                isf_data_input_string.push_str(&format!("{} {};\n", ty_str, input.name));
+                let decl = match input::array_len(arrays, &input.name) {
+                    None => format!("{} {};\n", ty_str, input.name),
+                    Some(len) => format!("{} {}[{}];\n", ty_str, input.name, len),
+                };
+                isf_data_input_string.push_str(&decl);
             }
             isf_data_input_string.push_str("};\n");
             Some(isf_data_input_string)
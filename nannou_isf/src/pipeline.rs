@@ -1,3 +1,6 @@
+use crate::binding::{self, BindingError, InputBinding};
+use crate::cache::ShaderCache;
+use crate::input::{self, ArrayError, InputArray, LongOption};
 use nannou::image;
 use nannou::prelude::*;
 use nannou::wgpu::BufferInitDescriptor;
@@ -12,6 +15,8 @@ pub struct IsfPipeline {
     isf: Option<isf::Isf>,
     pub isf_data: IsfData,
     isf_err: Option<IsfError>,
+    input_bindings: Vec<InputBinding>,
+    input_arrays: Vec<InputArray>,
     image_loader: ImageLoader,
     vs: Shader,
     fs: Shader,
@@ -74,6 +79,8 @@ pub enum ImageState {
     None,
     Loading(mpsc::Receiver<Result<image::RgbaImage, ImageLoadError>>),
     Ready(Result<ImageData, ImageLoadError>),
+    /// A texture provided directly by the user, e.g. the output of another render pass.
+    Texture(wgpu::Texture),
 }
 
 /// Handles to both the cpu and gpu representations of the image.
@@ -94,6 +101,10 @@ pub enum IsfInputData {
     Bool(bool),
     Long(i32),
     Float(f32),
+    /// A `float` input declared as an array via `COUNT`.
+    FloatArray(Vec<f32>),
+    /// A `long` input declared as an array via `COUNT`.
+    LongArray(Vec<i32>),
     Point2d(Point2),
     Color(LinSrgba),
     Image(ImageState),
@@ -107,7 +118,10 @@ pub enum IsfInputData {
     },
 }
 
-type IsfInputUniforms = [u32; 128];
+// The capacity of the `IsfDataInputs` uniform block in 32-bit words.
+pub(crate) const ISF_INPUT_UNIFORMS_LEN: usize = 1024;
+
+type IsfInputUniforms = [u32; ISF_INPUT_UNIFORMS_LEN];
 
 // #[derive(Clone, Debug)]
 // struct IsfInputUniforms {
@@ -153,6 +167,16 @@ pub enum IsfError {
         #[from]
         err: std::io::Error,
     },
+    #[error("{err}")]
+    Binding {
+        #[from]
+        err: BindingError,
+    },
+    #[error("{err}")]
+    Array {
+        #[from]
+        err: ArrayError,
+    },
 }
 
 /// Errors that might occur while loading an image.
@@ -188,6 +212,11 @@ pub enum ShaderError {
         #[from]
         err: hotglsl::CompileError,
     },
+    #[error("an error occurred while parsing ISF input arrays: {err}")]
+    Array {
+        #[from]
+        err: ArrayError,
+    },
 }
 
 const VERTICES: [Vertex; 4] = [
@@ -257,7 +286,7 @@ impl ImageState {
                 }
                 _ => return,
             },
-            ImageState::Ready(_) => return,
+            ImageState::Ready(_) | ImageState::Texture(_) => return,
         };
     }
 }
@@ -277,16 +306,26 @@ impl IsfData {
     pub fn passes(&self) -> &[wgpu::Texture] {
         &self.passes
     }
+
+    /// Mutable access to the data for the input with the given name.
+    ///
+    /// Values written here are uploaded to the shader on the next render pass.
+    pub fn input_mut(&mut self, name: &str) -> Option<&mut IsfInputData> {
+        self.inputs.get_mut(name)
+    }
 }
 
 impl IsfInputData {
     /// Initialise a new `IsfInputData` instance.
+    ///
+    /// `array_len` is the length declared for array inputs via `COUNT`.
     fn new(
         device: &wgpu::Device,
         encoder: &mut wgpu::CommandEncoder,
         image_loader: &ImageLoader,
         images_path: &Path,
         input: &isf::Input,
+        array_len: Option<usize>,
     ) -> Self {
         match &input.ty {
             isf::InputType::Event => IsfInputData::Event { happening: false },
@@ -297,11 +336,22 @@ impl IsfInputData {
                     .or(n.min)
                     .or(n.values.first().cloned())
                     .unwrap_or_default();
-                IsfInputData::Long(init)
+                // Listed values must be one of the options.
+                let init = match n.values.contains(&init) {
+                    true => init,
+                    false => input::nearest_long_value(n, init).unwrap_or(init),
+                };
+                match array_len {
+                    None => IsfInputData::Long(init),
+                    Some(len) => IsfInputData::LongArray(vec![init; len]),
+                }
             }
             isf::InputType::Float(f) => {
                 let init = f.default.or(f.min).unwrap_or_default();
-                IsfInputData::Float(init)
+                match array_len {
+                    None => IsfInputData::Float(init),
+                    Some(len) => IsfInputData::FloatArray(vec![init; len]),
+                }
             }
             isf::InputType::Point2d(p) => {
                 let [x, y] = p.default.or(p.min).unwrap_or_default();
@@ -357,6 +407,8 @@ impl IsfInputData {
             | (IsfInputData::Bool(_), isf::InputType::Bool(_))
             | (IsfInputData::Long(_), isf::InputType::Long(_))
             | (IsfInputData::Float(_), isf::InputType::Float(_))
+            | (IsfInputData::FloatArray(_), isf::InputType::Float(_))
+            | (IsfInputData::LongArray(_), isf::InputType::Long(_))
             | (IsfInputData::Point2d(_), isf::InputType::Point2d(_))
             | (IsfInputData::Color(_), isf::InputType::Color(_))
             | (IsfInputData::Image(_), isf::InputType::Image)
@@ -374,12 +426,17 @@ impl IsfInputData {
         image_loader: &ImageLoader,
         images_path: &Path,
         input: &isf::Input,
+        array_len: Option<usize>,
     ) {
         match (self, &input.ty) {
             (IsfInputData::Event { .. }, isf::InputType::Event) => (),
             (IsfInputData::Bool(_), isf::InputType::Bool(_)) => (),
-            (IsfInputData::Long(_), isf::InputType::Long(_)) => {}
-            (IsfInputData::Float(_), isf::InputType::Float(_)) => {}
+            (IsfInputData::Long(_), isf::InputType::Long(_)) if array_len.is_none() => {}
+            (IsfInputData::Float(_), isf::InputType::Float(_)) if array_len.is_none() => {}
+            (IsfInputData::FloatArray(v), isf::InputType::Float(_))
+                if array_len == Some(v.len()) => {}
+            (IsfInputData::LongArray(v), isf::InputType::Long(_)) if array_len == Some(v.len()) => {
+            }
             (IsfInputData::Point2d(_), isf::InputType::Point2d(_)) => {}
             (IsfInputData::Color(_), isf::InputType::Color(_)) => {}
             (IsfInputData::Image(ref mut state), isf::InputType::Image) => {
@@ -389,7 +446,9 @@ impl IsfInputData {
             }
             (IsfInputData::Audio { .. }, isf::InputType::Audio(_)) => {}
             (IsfInputData::AudioFft { .. }, isf::InputType::AudioFft(_)) => {}
-            (data, _) => *data = Self::new(device, encoder, image_loader, images_path, input),
+            (data, _) => {
+                *data = Self::new(device, encoder, image_loader, images_path, input, array_len)
+            }
         }
     }
 }
@@ -406,24 +465,41 @@ impl ShaderSource {
 /// Compile an ISF fragment shader.
 ///
 /// This is used for compiling the ISF fragment shader.
+///
+/// If a shader cache was set via `set_shader_cache`, previously compiled SPIR-V is retrieved from
+/// the cache rather than compiling the shader again.
 pub fn compile_isf_shader(
     device: &wgpu::Device,
     path: &Path,
 ) -> (Option<wgpu::ShaderModule>, Option<ShaderError>) {
-    let res = std::fs::read_to_string(&path)
-        .map_err(ShaderError::from)
-        .and_then(|s| isf::parse(&s).map(|isf| (s, isf)).map_err(From::from))
-        .and_then(|(old_str, isf)| {
-            let isf_str = crate::glsl_string_from_isf(&isf);
-            let new_str = crate::prefix_isf_glsl_str(&isf_str, old_str);
-            let ty = hotglsl::ShaderType::Fragment;
-            hotglsl::compile_str(&new_str, ty).map_err(From::from)
-        });
+    let cache = crate::cache::shader_cache();
+    let res = compile_isf_spirv_with_cache(path, cache.as_deref());
     let (bytes, error) = split_result(res);
     let module = bytes.map(|b| wgpu::shader_from_spirv_bytes(device, &b));
     (module, error)
 }
 
+// Read the ISF fragment shader at the given path and compile it to SPIR-V, consulting the given
+// cache first if there is one.
+pub(crate) fn compile_isf_spirv_with_cache(
+    path: &Path,
+    cache: Option<&ShaderCache>,
+) -> Result<Vec<u8>, ShaderError> {
+    let old_str = std::fs::read_to_string(&path)?;
+    let isf = isf::parse(&old_str)?;
+    let arrays = input::input_arrays_from_glsl(&old_str)?;
+    let isf_str = crate::glsl_string_from_isf_with_arrays(&isf, &arrays);
+    let new_str = crate::prefix_isf_glsl_str(&isf_str, old_str);
+    let compile = |glsl: &str| {
+        let ty = hotglsl::ShaderType::Fragment;
+        hotglsl::compile_str(glsl, ty).map_err(ShaderError::from)
+    };
+    match cache {
+        None => compile(&new_str),
+        Some(cache) => cache.get_or_compile(&new_str, compile),
+    }
+}
+
 /// Compile a regular, non-ISF shader.
 ///
 /// This is used for compiling the vertex shaders.
@@ -493,6 +569,13 @@ impl IsfPipeline {
         // Retrieve the `Isf` instance.
         let isf_res = read_isf_from_path(&fs_path);
         let (isf, isf_err) = split_result(isf_res);
+        let bindings_res = read_input_bindings_from_path(&fs_path);
+        let (input_bindings, bindings_err) = split_result(bindings_res);
+        let arrays_res = read_input_arrays_from_path(&fs_path);
+        let (input_arrays, arrays_err) = split_result(arrays_res);
+        let isf_err = isf_err.or(bindings_err).or(arrays_err);
+        let input_bindings = input_bindings.unwrap_or_default();
+        let input_arrays = input_arrays.unwrap_or_default();
 
         // Create the shaders.
         let vs = match vs_path {
@@ -515,6 +598,7 @@ impl IsfPipeline {
                 device,
                 encoder,
                 isf,
+                &input_arrays,
                 dst_texture_size,
                 &image_loader,
                 &images_path,
@@ -533,7 +617,7 @@ impl IsfPipeline {
             frame_index: 0,
         };
         let isf_uniforms_bytes = isf_uniforms_as_bytes(&isf_uniforms);
-        let isf_input_uniforms: IsfInputUniforms = [0u32; 128];
+        let isf_input_uniforms: IsfInputUniforms = [0u32; ISF_INPUT_UNIFORMS_LEN];
         let isf_input_uniforms_bytes = isf_input_uniforms_as_bytes(&isf_input_uniforms);
         let uniforms_usage = wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST;
         let isf_uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -610,6 +694,8 @@ impl IsfPipeline {
             isf,
             isf_data,
             isf_err,
+            input_bindings,
+            input_arrays,
             image_loader,
             vs,
             fs,
@@ -677,7 +763,17 @@ impl IsfPipeline {
                 // Update the `Isf` instance.
                 let isf_res = read_isf_from_path(&path);
                 let (new_isf, new_isf_err) = split_result(isf_res);
-                self.isf_err = new_isf_err;
+                let bindings_res = read_input_bindings_from_path(&path);
+                let (new_bindings, new_bindings_err) = split_result(bindings_res);
+                let arrays_res = read_input_arrays_from_path(&path);
+                let (new_arrays, new_arrays_err) = split_result(arrays_res);
+                self.isf_err = new_isf_err.or(new_bindings_err).or(new_arrays_err);
+                if let Some(bindings) = new_bindings {
+                    self.input_bindings = bindings;
+                }
+                if let Some(arrays) = new_arrays {
+                    self.input_arrays = arrays;
+                }
                 if self.isf.is_none() {
                     self.isf = new_isf;
                 }
@@ -701,6 +797,7 @@ impl IsfPipeline {
             device,
             encoder,
             isf,
+            &self.input_arrays,
             self.dst_texture_size,
             &self.image_loader,
             images_path,
@@ -786,10 +883,24 @@ impl IsfPipeline {
             let size = isf_uniforms_bytes.len() as wgpu::BufferAddress;
             encoder.copy_buffer_to_buffer(&new_buffer, 0, &self.isf_uniform_buffer, 0, size);
 
-            // TODO: Update the inputs.
-            let _ = &self.isf_inputs_uniform_buffer;
-            //let size = std::mem::size_of::<IsfInputUniforms>() as wgpu::BufferAddress;
-            //encoder.copy_buffer_to_buffer(&new_buffer, 0, &self.isf_inputs_uniform_buffer, 0, size);
+            // Encode an update for the ISF inputs uniform buffer.
+            if let Some(ref isf) = self.isf {
+                let isf_input_uniforms = isf_input_uniforms(isf, &self.isf_data);
+                let isf_input_uniforms_bytes = isf_input_uniforms_as_bytes(&isf_input_uniforms);
+                let new_buffer = device.create_buffer_init(&BufferInitDescriptor {
+                    label: None,
+                    contents: &isf_input_uniforms_bytes,
+                    usage,
+                });
+                let size = isf_input_uniforms_bytes.len() as wgpu::BufferAddress;
+                encoder.copy_buffer_to_buffer(
+                    &new_buffer,
+                    0,
+                    &self.isf_inputs_uniform_buffer,
+                    0,
+                    size,
+                );
+            }
 
             // Encode the render pass.
             let mut render_pass = wgpu::RenderPassBuilder::new()
@@ -815,6 +926,237 @@ impl IsfPipeline {
         self.encode_render_pass(device, &mut *encoder, frame.texture_view(), isf_time);
     }
 
+    /// Whether or not the render pipeline has been created, i.e. all shaders have compiled
+    /// successfully at least once.
+    ///
+    /// `encode_render_pass` encodes nothing until this is `true`.
+    pub fn is_ready(&self) -> bool {
+        self.render_pipeline.is_some()
+    }
+
+    /// Returns the current error encountered while reading the ISF or its input bindings, if
+    /// there is one.
+    pub fn isf_err(&self) -> Option<&IsfError> {
+        self.isf_err.as_ref()
+    }
+
+    /// Whether or not the loaded ISF describes a transition shader.
+    ///
+    /// See `nannou_isf::is_transition` for details.
+    pub fn is_transition(&self) -> bool {
+        self.isf.as_ref().map(crate::is_transition).unwrap_or(false)
+    }
+
+    /// Set the texture used for the image input with the given name.
+    ///
+    /// The texture replaces any image loaded from the images directory and remains bound until
+    /// replaced. Returns `false` if there is no image input with the given name.
+    ///
+    /// The texture bind group is recreated. If the number or sample types of the bound textures
+    /// changed, the render pipeline is also recreated.
+    pub fn set_input_texture(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        texture: wgpu::Texture,
+    ) -> bool {
+        let sample_types = isf_data_texture_sample_types(&self.isf_data);
+        match self.isf_data.inputs.get_mut(name) {
+            Some(IsfInputData::Image(state)) => *state = ImageState::Texture(texture),
+            _ => return false,
+        }
+        let layout_changed = sample_types != isf_data_texture_sample_types(&self.isf_data);
+        if layout_changed {
+            self.isf_textures_bind_group_layout = create_isf_textures_bind_group_layout(
+                device,
+                self.sampler_filtering,
+                &self.isf_data,
+            );
+            self.layout = create_pipeline_layout(
+                device,
+                &[
+                    &self.isf_bind_group_layout,
+                    &self.isf_inputs_bind_group_layout,
+                    &self.isf_textures_bind_group_layout,
+                ],
+            );
+            if let (Some(vs_mod), Some(fs_mod)) = (self.vs.module.as_ref(), self.fs.module.as_ref())
+            {
+                self.render_pipeline = Some(create_render_pipeline(
+                    device,
+                    &self.layout,
+                    vs_mod,
+                    fs_mod,
+                    self.dst_format,
+                    self.dst_sample_count,
+                ));
+            }
+        }
+        self.isf_textures_bind_group = create_isf_textures_bind_group(
+            device,
+            &self.isf_textures_bind_group_layout,
+            &self.sampler,
+            &self.isf_data,
+        );
+        true
+    }
+
+    /// Set the `startImage` and `endImage` textures of a transition shader.
+    ///
+    /// Returns `false` if either input is not declared by the shader.
+    pub fn set_transition_textures(
+        &mut self,
+        device: &wgpu::Device,
+        start: wgpu::Texture,
+        end: wgpu::Texture,
+    ) -> bool {
+        let start_set = self.set_input_texture(device, crate::TRANSITION_START_IMAGE, start);
+        let end_set = self.set_input_texture(device, crate::TRANSITION_END_IMAGE, end);
+        start_set && end_set
+    }
+
+    /// Set the `progress` input of a transition shader.
+    ///
+    /// The value is clamped to the range `0.0..=1.0`. Returns `false` if the shader declares no
+    /// `progress` float input.
+    pub fn set_transition_progress(&mut self, progress: f32) -> bool {
+        match self.isf_data.input_mut(crate::TRANSITION_PROGRESS) {
+            Some(IsfInputData::Float(f)) => {
+                *f = progress.max(0.0).min(1.0);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Upload the latest audio captured by the given input to the textures of all `audio` and
+    /// `audioFFT` inputs.
+    ///
+    /// This should be called once per frame prior to `encode_render_pass`.
+    #[cfg(feature = "audio")]
+    pub fn encode_audio_update(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        audio: &crate::AudioInput,
+    ) {
+        for data in self.isf_data.inputs.values_mut() {
+            match data {
+                IsfInputData::Audio { samples, texture } => {
+                    audio.waveform(samples);
+                    texture.upload_data(device, encoder, audio_samples_as_bytes(samples));
+                }
+                IsfInputData::AudioFft { columns, texture } => {
+                    audio.fft(columns);
+                    texture.upload_data(device, encoder, audio_samples_as_bytes(columns));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// The OSC addresses and MIDI CCs declared by the shader's inputs.
+    ///
+    /// See the `binding` module docs for the supported metadata.
+    pub fn input_bindings(&self) -> &[InputBinding] {
+        &self.input_bindings
+    }
+
+    /// The lengths of the inputs declared as arrays via `COUNT`.
+    ///
+    /// See the `input` module docs for the supported metadata.
+    pub fn input_arrays(&self) -> &[InputArray] {
+        &self.input_arrays
+    }
+
+    /// The options of the `long` input with the given name, e.g. for presenting as a dropdown.
+    ///
+    /// Returns `None` if there is no `long` input with the given name. Returns an empty list if
+    /// the input declares no `VALUES`.
+    pub fn long_options(&self, name: &str) -> Option<Vec<LongOption>> {
+        let isf = self.isf.as_ref()?;
+        let input = isf.inputs.iter().find(|input| input.name == name)?;
+        match input.ty {
+            isf::InputType::Long(ref long) => Some(input::long_options(long)),
+            _ => None,
+        }
+    }
+
+    /// Apply a MIDI control change to all inputs bound to it.
+    ///
+    /// The `channel` is in the range `1..=16`. The `value` is mapped onto the range of each input.
+    /// Returns the number of inputs updated.
+    pub fn handle_midi_cc(&mut self, channel: u8, cc: u8, value: u8) -> usize {
+        let isf = match self.isf {
+            None => return 0,
+            Some(ref isf) => isf,
+        };
+        let mut updated = 0;
+        for b in &self.input_bindings {
+            if !b.midi.map_or(false, |midi| midi.matches(channel, cc)) {
+                continue;
+            }
+            let input = isf.inputs.iter().find(|input| input.name == b.input);
+            let data = self.isf_data.inputs.get_mut(&b.input);
+            if let (Some(input), Some(data)) = (input, data) {
+                if binding::apply_midi_cc(data, &input.ty, value) {
+                    updated += 1;
+                }
+            }
+        }
+        updated
+    }
+
+    /// Apply a raw MIDI message, e.g. as delivered by a MIDI input library.
+    ///
+    /// Messages other than control changes are ignored. Returns the number of inputs updated.
+    pub fn handle_midi_message(&mut self, bytes: &[u8]) -> usize {
+        match *bytes {
+            [status, cc, value, ..] if status & 0xF0 == 0xB0 => {
+                let channel = (status & 0x0F) + 1;
+                self.handle_midi_cc(channel, cc & 0x7F, value & 0x7F)
+            }
+            _ => 0,
+        }
+    }
+
+    /// Apply the arguments of the given OSC message to all inputs bound to its address.
+    ///
+    /// Numeric and boolean arguments are supported. `point2D` inputs expect two arguments and
+    /// `color` inputs expect three or four. Returns the number of inputs updated.
+    #[cfg(feature = "osc")]
+    pub fn handle_osc_message(&mut self, msg: &nannou_osc::Message) -> usize {
+        let isf = match self.isf {
+            None => return 0,
+            Some(ref isf) => isf,
+        };
+        let args: Vec<f32> = msg.args.iter().filter_map(osc_arg_to_f32).collect();
+        let mut updated = 0;
+        for b in &self.input_bindings {
+            if b.osc.as_deref() != Some(&msg.addr[..]) {
+                continue;
+            }
+            let input = isf.inputs.iter().find(|input| input.name == b.input);
+            let data = self.isf_data.inputs.get_mut(&b.input);
+            if let (Some(input), Some(data)) = (input, data) {
+                if binding::apply_osc_args(data, &input.ty, &args) {
+                    updated += 1;
+                }
+            }
+        }
+        updated
+    }
+
+    /// Apply all messages of the given OSC packet to their bound inputs.
+    ///
+    /// Returns the total number of inputs updated.
+    #[cfg(feature = "osc")]
+    pub fn handle_osc_packet(&mut self, packet: nannou_osc::Packet) -> usize {
+        let mut msgs = vec![];
+        packet.unfold(&mut msgs);
+        msgs.iter().map(|msg| self.handle_osc_message(msg)).sum()
+    }
+
     /// Returns the current compilation error for the vertex shader if there is one.
     ///
     /// Returns `Some` if the last call to `update_shaders` contained a compilation error for the
@@ -830,6 +1172,13 @@ impl IsfPipeline {
     pub fn fs_err(&self) -> Option<&ShaderError> {
         self.fs.error.as_ref()
     }
+
+    // Consume the pipeline, producing the ISF, vertex shader and fragment shader errors.
+    pub(crate) fn into_errors(
+        self,
+    ) -> (Option<IsfError>, Option<ShaderError>, Option<ShaderError>) {
+        (self.isf_err, self.vs.error, self.fs.error)
+    }
 }
 
 fn split_result<T, E>(res: Result<T, E>) -> (Option<T>, Option<E>) {
@@ -920,6 +1269,7 @@ fn isf_data_textures(isf_data: &IsfData) -> impl Iterator<Item = &wgpu::Texture>
         .filter_map(|input_data| match input_data {
             IsfInputData::Image(ref img_state) => match *img_state {
                 ImageState::Ready(Ok(ref data)) => Some(&data.texture),
+                ImageState::Texture(ref texture) => Some(texture),
                 _ => None,
             },
             IsfInputData::Audio { ref texture, .. }
@@ -930,11 +1280,81 @@ fn isf_data_textures(isf_data: &IsfData) -> impl Iterator<Item = &wgpu::Texture>
     imported.chain(inputs).chain(passes)
 }
 
+// The sample type of each texture in the order expected by the isf textures bind group layout.
+fn isf_data_texture_sample_types(isf_data: &IsfData) -> Vec<wgpu::TextureSampleType> {
+    isf_data_textures(isf_data)
+        .map(|tex| tex.sample_type())
+        .collect()
+}
+
+// Pack the non-texture inputs into the `IsfDataInputs` uniform block.
+//
+// Fields are laid out in declaration order following the std140 alignment rules, matching the
+// block generated by `glsl_string_from_isf`.
+fn isf_input_uniforms(isf: &isf::Isf, isf_data: &IsfData) -> IsfInputUniforms {
+    let data = isf
+        .inputs
+        .iter()
+        .filter_map(|input| isf_data.inputs.get(&input.name));
+    pack_std140(data)
+}
+
+// Pack the given input data in order following the std140 alignment rules.
+//
+// `input_arrays_from_glsl` rejects shaders whose inputs exceed the capacity of the block, so the
+// fields always fit for shaders that compile.
+fn pack_std140<'a, I>(data: I) -> IsfInputUniforms
+where
+    I: IntoIterator<Item = &'a IsfInputData>,
+{
+    let mut uniforms: IsfInputUniforms = [0u32; ISF_INPUT_UNIFORMS_LEN];
+    let mut offset = 0;
+    for data in data {
+        let (align, words): (usize, Vec<u32>) = match *data {
+            IsfInputData::Event { happening } => (1, vec![happening as u32]),
+            IsfInputData::Bool(b) => (1, vec![b as u32]),
+            IsfInputData::Long(n) => (1, vec![n as u32]),
+            IsfInputData::Float(f) => (1, vec![f.to_bits()]),
+            // Under std140, each array element is aligned to and occupies 16 bytes.
+            IsfInputData::FloatArray(ref elems) => {
+                let words = elems.iter().flat_map(|f| vec![f.to_bits(), 0, 0, 0]);
+                (4, words.collect())
+            }
+            IsfInputData::LongArray(ref elems) => {
+                let words = elems.iter().flat_map(|&n| vec![n as u32, 0, 0, 0]);
+                (4, words.collect())
+            }
+            IsfInputData::Point2d(p) => (2, vec![p.x.to_bits(), p.y.to_bits()]),
+            IsfInputData::Color(c) => {
+                let words = vec![
+                    c.red.to_bits(),
+                    c.green.to_bits(),
+                    c.blue.to_bits(),
+                    c.alpha.to_bits(),
+                ];
+                (4, words)
+            }
+            IsfInputData::Image(_) | IsfInputData::Audio { .. } | IsfInputData::AudioFft { .. } => {
+                continue
+            }
+        };
+        offset = input::std140_align(offset, align);
+        assert!(
+            offset + words.len() <= uniforms.len(),
+            "inputs exceed the capacity of the `IsfDataInputs` uniform block",
+        );
+        uniforms[offset..offset + words.len()].copy_from_slice(&words);
+        offset += words.len();
+    }
+    uniforms
+}
+
 // Ensure the image state map is up to date.
 fn sync_isf_data(
     device: &wgpu::Device,
     encoder: &mut wgpu::CommandEncoder,
     isf: &isf::Isf,
+    arrays: &[InputArray],
     output_attachment_size: [u32; 2],
     image_loader: &ImageLoader,
     images_path: &Path,
@@ -957,13 +1377,14 @@ fn sync_isf_data(
         .inputs
         .retain(|key, _| isf.inputs.iter().map(|i| &i.name).any(|n| n == key));
     for input in &isf.inputs {
+        let array_len = input::array_len(arrays, &input.name);
         let input_data = isf_data
             .inputs
             .entry(input.name.clone())
             .or_insert_with(|| {
-                IsfInputData::new(device, encoder, image_loader, images_path, input)
+                IsfInputData::new(device, encoder, image_loader, images_path, input, array_len)
             });
-        input_data.update(device, encoder, image_loader, images_path, input);
+        input_data.update(device, encoder, image_loader, images_path, input, array_len);
     }
 
     // Prepare the textures that will be written to for passes.
@@ -1005,6 +1426,31 @@ fn read_isf_from_path(path: &Path) -> Result<isf::Isf, IsfError> {
         .and_then(|s| isf::parse(&s).map_err(From::from))
 }
 
+fn read_input_bindings_from_path(path: &Path) -> Result<Vec<InputBinding>, IsfError> {
+    let glsl = std::fs::read_to_string(path)?;
+    let bindings = binding::input_bindings_from_glsl(&glsl)?;
+    Ok(bindings)
+}
+
+fn read_input_arrays_from_path(path: &Path) -> Result<Vec<InputArray>, IsfError> {
+    let glsl = std::fs::read_to_string(path)?;
+    let arrays = input::input_arrays_from_glsl(&glsl)?;
+    Ok(arrays)
+}
+
+// Convert a numeric or boolean OSC argument to a float.
+#[cfg(feature = "osc")]
+fn osc_arg_to_f32(arg: &nannou_osc::Type) -> Option<f32> {
+    match *arg {
+        nannou_osc::Type::Float(f) => Some(f),
+        nannou_osc::Type::Double(d) => Some(d as f32),
+        nannou_osc::Type::Int(i) => Some(i as f32),
+        nannou_osc::Type::Long(l) => Some(l as f32),
+        nannou_osc::Type::Bool(b) => Some(if b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
 /// Given a path to a directory, produces the paths of all images within it.
 fn image_paths(dir: &Path) -> impl Iterator<Item = PathBuf> {
     walkdir::WalkDir::new(dir)
@@ -1024,6 +1470,129 @@ fn isf_input_uniforms_as_bytes(data: &[u32]) -> &[u8] {
     unsafe { wgpu::bytes::from_slice(data) }
 }
 
+#[cfg(feature = "audio")]
+fn audio_samples_as_bytes(data: &[f32]) -> &[u8] {
+    unsafe { wgpu::bytes::from_slice(data) }
+}
+
 fn vertices_as_bytes(data: &[Vertex]) -> &[u8] {
     unsafe { wgpu::bytes::from_slice(data) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_scalars_contiguously() {
+        let data = [
+            IsfInputData::Event { happening: true },
+            IsfInputData::Bool(false),
+            IsfInputData::Long(-2),
+            IsfInputData::Float(0.5),
+        ];
+        let uniforms = pack_std140(&data);
+        assert_eq!(uniforms[..4], [1, 0, -2i32 as u32, 0.5f32.to_bits()]);
+        assert!(uniforms[4..].iter().all(|&w| w == 0));
+    }
+
+    #[test]
+    fn aligns_vec2_to_two_words() {
+        let data = [
+            IsfInputData::Float(1.0),
+            IsfInputData::Point2d(pt2(2.0, 3.0)),
+        ];
+        let uniforms = pack_std140(&data);
+        let expected = [1.0f32.to_bits(), 0, 2.0f32.to_bits(), 3.0f32.to_bits()];
+        assert_eq!(uniforms[..4], expected);
+    }
+
+    #[test]
+    fn aligns_vec4_to_four_words() {
+        let data = [
+            IsfInputData::Bool(true),
+            IsfInputData::Color(lin_srgba(0.1, 0.2, 0.3, 0.4)),
+            IsfInputData::Float(5.0),
+        ];
+        let uniforms = pack_std140(&data);
+        let expected = [
+            1,
+            0,
+            0,
+            0,
+            0.1f32.to_bits(),
+            0.2f32.to_bits(),
+            0.3f32.to_bits(),
+            0.4f32.to_bits(),
+            5.0f32.to_bits(),
+        ];
+        assert_eq!(uniforms[..9], expected);
+    }
+
+    #[test]
+    fn pads_array_elements_to_sixteen_bytes() {
+        let data = [
+            IsfInputData::Float(1.0),
+            IsfInputData::FloatArray(vec![2.0, 3.0]),
+            IsfInputData::LongArray(vec![4]),
+            IsfInputData::Long(5),
+        ];
+        let uniforms = pack_std140(&data);
+        let expected = [
+            1.0f32.to_bits(),
+            0,
+            0,
+            0,
+            2.0f32.to_bits(),
+            0,
+            0,
+            0,
+            3.0f32.to_bits(),
+            0,
+            0,
+            0,
+            4,
+            0,
+            0,
+            0,
+            5,
+        ];
+        assert_eq!(uniforms[..17], expected);
+    }
+
+    #[test]
+    fn fills_the_uniform_block() {
+        let len = ISF_INPUT_UNIFORMS_LEN / 4;
+        let data = [IsfInputData::LongArray((0..len as i32).collect())];
+        let uniforms = pack_std140(&data);
+        assert_eq!(uniforms[ISF_INPUT_UNIFORMS_LEN - 4], len as u32 - 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_when_exceeding_the_uniform_block() {
+        let len = ISF_INPUT_UNIFORMS_LEN / 4;
+        let data = [
+            IsfInputData::Float(0.0),
+            IsfInputData::LongArray((0..len as i32).collect()),
+        ];
+        pack_std140(&data);
+    }
+
+    #[cfg(feature = "osc")]
+    #[test]
+    fn osc_args_of_other_types_are_skipped() {
+        use nannou_osc::Type;
+        let args = [
+            Type::Float(0.5),
+            Type::String("0.5".into()),
+            Type::Int(2),
+            Type::Bool(true),
+            Type::Double(0.25),
+            Type::Nil,
+            Type::Long(-3),
+        ];
+        let floats: Vec<f32> = args.iter().filter_map(osc_arg_to_f32).collect();
+        assert_eq!(floats, vec![0.5, 2.0, 1.0, 0.25, -3.0]);
+    }
+}
@@ -0,0 +1,94 @@
+//! Support for ISF transition shaders.
+//!
+//! Transition shaders belong to the `TRANSITION` category and blend from a `startImage` to an
+//! `endImage` as their `progress` input moves from `0.0` to `1.0`.
+
+/// The category declared by ISF transition shaders.
+pub const TRANSITION_CATEGORY: &str = "TRANSITION";
+/// The name of the image input that is shown when `progress` is `0.0`.
+pub const TRANSITION_START_IMAGE: &str = "startImage";
+/// The name of the image input that is shown when `progress` is `1.0`.
+pub const TRANSITION_END_IMAGE: &str = "endImage";
+/// The name of the float input describing how far through the transition we are.
+pub const TRANSITION_PROGRESS: &str = "progress";
+
+/// Drives the `progress` of a transition over a duration.
+///
+/// All times are in seconds and are expected to be measured from the same clock as the `time`
+/// passed to the `IsfPipeline` via `IsfTime`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transition {
+    start: f32,
+    duration: f32,
+}
+
+/// Whether or not the given ISF describes a transition shader.
+///
+/// Returns `true` if the shader declares the `TRANSITION` category or if it declares the
+/// `startImage`, `endImage` and `progress` inputs expected of a transition.
+pub fn is_transition(isf: &isf::Isf) -> bool {
+    if isf
+        .categories
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case(TRANSITION_CATEGORY))
+    {
+        return true;
+    }
+    let has_input = |name: &str, is_ty: fn(&isf::InputType) -> bool| {
+        isf.inputs.iter().any(|i| i.name == name && is_ty(&i.ty))
+    };
+    let is_image = |ty: &isf::InputType| matches!(*ty, isf::InputType::Image);
+    let is_float = |ty: &isf::InputType| matches!(*ty, isf::InputType::Float(_));
+    has_input(TRANSITION_START_IMAGE, is_image)
+        && has_input(TRANSITION_END_IMAGE, is_image)
+        && has_input(TRANSITION_PROGRESS, is_float)
+}
+
+impl Transition {
+    /// The duration used by `Transition::default`.
+    pub const DEFAULT_DURATION_SECS: f32 = 1.0;
+
+    /// Begin a transition at the given time that lasts for the given duration.
+    ///
+    /// Negative durations are treated as zero, producing an instantaneous cut.
+    pub fn new(start_secs: f32, duration_secs: f32) -> Self {
+        Transition {
+            start: start_secs,
+            duration: duration_secs.max(0.0),
+        }
+    }
+
+    /// The time at which the transition begins.
+    pub fn start(&self) -> f32 {
+        self.start
+    }
+
+    /// The duration of the transition.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// The progress of the transition at the given time within the range `0.0..=1.0`.
+    pub fn progress(&self, time_secs: f32) -> f32 {
+        if self.duration == 0.0 {
+            return if time_secs < self.start { 0.0 } else { 1.0 };
+        }
+        ((time_secs - self.start) / self.duration).max(0.0).min(1.0)
+    }
+
+    /// Whether or not the transition has completed at the given time.
+    pub fn is_complete(&self, time_secs: f32) -> bool {
+        time_secs >= self.start + self.duration
+    }
+
+    /// Restart the transition at the given time, keeping the same duration.
+    pub fn restart(&mut self, start_secs: f32) {
+        self.start = start_secs;
+    }
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Transition::new(0.0, Self::DEFAULT_DURATION_SECS)
+    }
+}
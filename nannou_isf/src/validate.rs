@@ -0,0 +1,164 @@
+//! Validation of ISF shaders against nannou's ISF pipeline.
+//!
+//! Shader pack maintainers may use `validate_isf_dir` to check that every shader within a pack
+//! parses, compiles and renders, along with how long each takes. Each shader is run through an
+//! `IsfPipeline` targeting a small offscreen texture so that the results reflect exactly what a
+//! nannou app would experience when loading the shader.
+//!
+//! Validating a shader blocks the calling thread while the device renders the first frame.
+
+use crate::pipeline::{IsfError, IsfPipeline, IsfTime, ShaderError};
+use nannou::wgpu;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// The default size of the texture to which each shader is rendered during validation.
+pub const DEFAULT_VALIDATION_SIZE: [u32; 2] = [64, 64];
+
+/// The format of the texture to which each shader is rendered during validation.
+pub const VALIDATION_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// The result of validating a single ISF shader.
+#[derive(Debug)]
+pub struct ShaderReport {
+    /// The path of the ISF fragment shader.
+    pub path: PathBuf,
+    /// The time taken to parse the ISF, compile the shaders and create the pipeline.
+    ///
+    /// If a shader cache was set via `set_shader_cache`, cached shaders report the time taken to
+    /// retrieve them instead.
+    pub compile_time: Duration,
+    /// The time taken to render and wait for the first frame, if the pipeline could be created.
+    pub render_time: Option<Duration>,
+    /// All errors that occurred while validating the shader.
+    pub errors: Vec<ValidationError>,
+}
+
+/// An error that occurred while validating an ISF shader.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    /// The ISF metadata or input bindings could not be read.
+    #[error("invalid ISF: {0}")]
+    Isf(IsfError),
+    /// The vertex shader failed to compile.
+    #[error("vertex shader: {0}")]
+    VertexShader(ShaderError),
+    /// The fragment shader failed to compile.
+    #[error("fragment shader: {0}")]
+    FragmentShader(ShaderError),
+}
+
+impl ShaderReport {
+    /// Whether or not the shader parsed, compiled and rendered without error.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.render_time.is_some()
+    }
+}
+
+/// Validate the ISF fragment shader at the given path, rendering its first frame to a texture of
+/// the given size.
+///
+/// Image inputs are loaded from the shader's own directory where possible. As images load
+/// asynchronously, the first frame is usually rendered without them.
+pub fn validate_isf_file(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    path: &Path,
+    size: [u32; 2],
+) -> ShaderReport {
+    let images_path = path.parent().unwrap_or_else(|| Path::new("."));
+    let desc = wgpu::CommandEncoderDescriptor {
+        label: Some("nannou_isf_validate_pipeline_new"),
+    };
+
+    // Create the pipeline, compiling all shaders.
+    let start = Instant::now();
+    let mut encoder = device.create_command_encoder(&desc);
+    let pipeline = IsfPipeline::new(
+        device,
+        &mut encoder,
+        None,
+        path.to_path_buf(),
+        VALIDATION_TEXTURE_FORMAT,
+        size,
+        1,
+        images_path,
+    );
+    queue.submit(Some(encoder.finish()));
+    let compile_time = start.elapsed();
+
+    // Render the first frame and wait for the device to finish.
+    let render_time = if pipeline.is_ready() {
+        let texture = wgpu::TextureBuilder::new()
+            .size(size)
+            .format(VALIDATION_TEXTURE_FORMAT)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+            .build(device);
+        let view = texture.view().build();
+        let desc = wgpu::CommandEncoderDescriptor {
+            label: Some("nannou_isf_validate_render"),
+        };
+        let start = Instant::now();
+        let mut encoder = device.create_command_encoder(&desc);
+        pipeline.encode_render_pass(device, &mut encoder, &view, IsfTime::default());
+        queue.submit(Some(encoder.finish()));
+        device.poll(wgpu::Maintain::Wait);
+        Some(start.elapsed())
+    } else {
+        None
+    };
+
+    let (isf_err, vs_err, fs_err) = pipeline.into_errors();
+    let errors = isf_err
+        .map(ValidationError::Isf)
+        .into_iter()
+        .chain(vs_err.map(ValidationError::VertexShader))
+        .chain(fs_err.map(ValidationError::FragmentShader))
+        .collect();
+
+    ShaderReport {
+        path: path.to_path_buf(),
+        compile_time,
+        render_time,
+        errors,
+    }
+}
+
+/// Validate every ISF fragment shader within the given directory and its subdirectories.
+///
+/// Shaders are found by their `ISF_FRAGMENT_EXTENSION` and validated one at a time, so that the
+/// reported times are not skewed by one another. Reports are ordered by path.
+pub fn validate_isf_dir(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    dir: &Path,
+    size: [u32; 2],
+) -> Vec<ShaderReport> {
+    let mut paths: Vec<PathBuf> = crate::cache::isf_fragment_paths(dir).collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| validate_isf_file(device, queue, path, size))
+        .collect()
+}
+
+impl fmt::Display for ShaderReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: compiled in {:.2?}",
+            self.path.display(),
+            self.compile_time
+        )?;
+        match self.render_time {
+            Some(render_time) => write!(f, ", rendered in {:.2?}", render_time)?,
+            None => write!(f, ", not rendered")?,
+        }
+        for err in &self.errors {
+            write!(f, "\n  {}", err)?;
+        }
+        Ok(())
+    }
+}
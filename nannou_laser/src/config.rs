@@ -0,0 +1,646 @@
+//! A persistent registry of per-DAC configuration.
+//!
+//! Multi-projector rigs typically require each projector to be calibrated individually. A
+//! **ConfigStore** maps each DAC's persistent `Id` to a **DacConfig** describing its colour
+//! calibration, geometric correction, safety zones and latency settings.
+//!
+//! Once a store is set via `Api::set_config_store`, streams automatically apply the
+//! configuration of the DAC with which they connect. Stores may be saved to and loaded from a
+//! simple human-readable text file so that settings survive app restarts.
+
+use crate::point::{Position, RawPoint, Rgb};
+use crate::DacId;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// A registry of configuration keyed by DAC `Id`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigStore {
+    configs: HashMap<DacId, DacConfig>,
+}
+
+/// The configuration associated with a single DAC.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DacConfig {
+    /// The rate at which the DAC should process points per second.
+    ///
+    /// Only applied if no `point_hz` was specified when building the stream.
+    pub point_hz: Option<u32>,
+    /// The maximum latency specified as a number of points.
+    ///
+    /// Only applied if no `latency_points` was specified when building the stream.
+    pub latency_points: Option<u32>,
+    /// The latency of the projector's output path relative to the audio output path.
+    ///
+    /// Only applied to frame streams built for a specific DAC with no `audio_sync_offset`.
+    pub audio_sync_offset: Option<Duration>,
+    /// Per-channel colour calibration.
+    pub color: ColorCalibration,
+    /// Correction applied to the position of each point.
+    pub geometry: GeometricCorrection,
+    /// Regions of the projection area in which output brightness is limited, e.g. to avoid
+    /// scanning an audience.
+    pub safety_zones: Vec<SafetyZone>,
+}
+
+/// Per-channel colour calibration, useful for matching the output of different projectors.
+///
+/// Each non-zero channel value `c` is mapped to `min + (1 - min) * gain * c.powf(gamma)`. Zero
+/// values remain zero so that blank points stay blank.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorCalibration {
+    /// The exponent applied to each channel.
+    ///
+    /// By default, this value is `1.0` for each channel.
+    pub gamma: Rgb,
+    /// The multiplier applied to each channel.
+    ///
+    /// By default, this value is `1.0` for each channel.
+    pub gain: Rgb,
+    /// The minimum output of each channel for it to be visible, e.g. the diode threshold.
+    ///
+    /// By default, this value is `0.0` for each channel.
+    pub min: Rgb,
+}
+
+/// Geometric correction applied to the position of each point.
+///
+/// Points are flipped, then scaled, then rotated about the origin and finally offset.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GeometricCorrection {
+    /// Whether or not to mirror points along the `x` axis.
+    pub flip_x: bool,
+    /// Whether or not to mirror points along the `y` axis.
+    pub flip_y: bool,
+    /// The scale applied to each axis.
+    ///
+    /// By default, this value is `[1.0, 1.0]`.
+    pub scale: [f32; 2],
+    /// The rotation about the origin in radians.
+    ///
+    /// By default, this value is `0.0`.
+    pub rotation: f32,
+    /// The offset applied after scaling and rotation.
+    ///
+    /// By default, this value is `[0.0, 0.0]`.
+    pub offset: [f32; 2],
+}
+
+/// A rectangular region of the projection area in which brightness is limited.
+///
+/// Zones are specified in the DAC's coordinate space, i.e. after geometric correction.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SafetyZone {
+    /// The bottom left corner of the zone.
+    pub min: Position,
+    /// The top right corner of the zone.
+    pub max: Position,
+    /// The multiplier applied to the colour of points within the zone.
+    ///
+    /// `0.0` blanks all points within the zone.
+    pub brightness: f32,
+}
+
+/// Errors that might occur while loading a `ConfigStore`.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read or write the config file: {err}")]
+    Io {
+        #[from]
+        err: io::Error,
+    },
+    #[error("failed to parse line {line} of the config file: {msg}")]
+    Parse { line: usize, msg: String },
+}
+
+// The name used for ether dream DACs within config section headers.
+const ETHER_DREAM_SECTION: &str = "ether-dream";
+
+// The name used for IDN DACs within config section headers, followed by the hex unit ID.
+const IDN_SECTION: &str = "idn";
+
+impl ConfigStore {
+    /// An empty config store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a config store from the file at the given path.
+    pub fn load<P>(path: P) -> Result<Self, ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        let s = std::fs::read_to_string(path)?;
+        Self::parse(&s)
+    }
+
+    /// Load a config store from the file at the given path, or produce an empty store if no file
+    /// exists yet.
+    pub fn load_or_default<P>(path: P) -> Result<Self, ConfigError>
+    where
+        P: AsRef<Path>,
+    {
+        match Self::load(path) {
+            Err(ConfigError::Io { err }) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::default())
+            }
+            res => res,
+        }
+    }
+
+    /// Save the config store to the file at the given path.
+    ///
+    /// The store is written to a temporary file first so that an interrupted save never leaves
+    /// behind a partial file.
+    pub fn save<P>(&self, path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, self.to_string())?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    /// Parse a config store from the text format produced by `save`.
+    ///
+    /// Returns a `ConfigError::Parse` with the line number of the first invalid line, including
+    /// any section header that repeats the `Id` of an earlier section.
+    pub fn parse(s: &str) -> Result<Self, ConfigError> {
+        let mut store = Self::new();
+        let mut current: Option<(DacId, DacConfig)> = None;
+        for (ix, line) in s.lines().enumerate() {
+            let line_no = ix + 1;
+            let err = |msg: String| ConfigError::Parse { line: line_no, msg };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                let header = line
+                    .strip_prefix('[')
+                    .and_then(|l| l.strip_suffix(']'))
+                    .ok_or_else(|| err(format!("unterminated section header `{}`", line)))?;
+                let id = parse_dac_id(header).map_err(err)?;
+                if let Some((id, config)) = current.take() {
+                    store.insert(id, config);
+                }
+                if store.get(&id).is_some() {
+                    let msg = format!("duplicate section `[{}]`", DisplayDacId(&id));
+                    return Err(err(msg));
+                }
+                current = Some((id, DacConfig::default()));
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(err(format!("expected `key = value`, found `{}`", line))),
+            };
+            let config = match current {
+                Some((_, ref mut config)) => config,
+                None => return Err(err(format!("`{}` does not belong to a DAC section", key))),
+            };
+            config.parse_entry(key, value).map_err(err)?;
+        }
+        if let Some((id, config)) = current.take() {
+            store.insert(id, config);
+        }
+        Ok(store)
+    }
+
+    /// The configuration for the DAC with the given `Id`.
+    pub fn get(&self, id: &DacId) -> Option<&DacConfig> {
+        self.configs.get(id)
+    }
+
+    /// The configuration for the DAC with the given `Id`.
+    pub fn get_mut(&mut self, id: &DacId) -> Option<&mut DacConfig> {
+        self.configs.get_mut(id)
+    }
+
+    /// Insert the configuration for the DAC with the given `Id`.
+    ///
+    /// Returns the previous configuration for the DAC, if any.
+    pub fn insert(&mut self, id: DacId, config: DacConfig) -> Option<DacConfig> {
+        self.configs.insert(id, config)
+    }
+
+    /// Remove the configuration for the DAC with the given `Id`.
+    pub fn remove(&mut self, id: &DacId) -> Option<DacConfig> {
+        self.configs.remove(id)
+    }
+
+    /// An iterator yielding each DAC `Id` along with its configuration.
+    pub fn iter(&self) -> impl Iterator<Item = (&DacId, &DacConfig)> {
+        self.configs.iter()
+    }
+
+    /// The number of DACs within the store.
+    pub fn len(&self) -> usize {
+        self.configs.len()
+    }
+
+    /// Whether or not the store contains no DACs.
+    pub fn is_empty(&self) -> bool {
+        self.configs.is_empty()
+    }
+}
+
+impl DacConfig {
+    /// Apply the colour calibration, geometric correction and safety zones to the given points.
+    ///
+    /// Geometric correction is applied first, followed by safety zones and finally colour
+    /// calibration.
+    pub fn apply(&self, points: &mut [RawPoint]) {
+        let apply_geometry = !self.geometry.is_identity();
+        let apply_color = !self.color.is_identity();
+        for point in points {
+            if apply_geometry {
+                point.position = self.geometry.apply(point.position);
+            }
+            for zone in &self.safety_zones {
+                if zone.contains(point.position) {
+                    point.color = point.color.map(|c| c * zone.brightness);
+                }
+            }
+            if apply_color {
+                point.color = self.color.apply(point.color);
+            }
+        }
+    }
+
+    // Parse a single `key = value` entry of the config file.
+    fn parse_entry(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "point_hz" => self.point_hz = Some(parse_value(value)?),
+            "latency_points" => self.latency_points = Some(parse_value(value)?),
+            "audio_sync_offset_ms" => {
+                let ms: f64 = parse_value(value)?;
+                if !ms.is_finite() || ms < 0.0 {
+                    return Err(format!(
+                        "`audio_sync_offset_ms` must be non-negative, found {}",
+                        ms
+                    ));
+                }
+                // Round to the nearest nanosecond so that written offsets are read back exactly.
+                let nanos = (ms * 1_000_000.0).round() as u64;
+                self.audio_sync_offset = Some(Duration::from_nanos(nanos));
+            }
+            "color_gamma" => self.color.gamma = parse_array(value)?,
+            "color_gain" => self.color.gain = parse_array(value)?,
+            "color_min" => self.color.min = parse_array(value)?,
+            "flip_x" => self.geometry.flip_x = parse_value(value)?,
+            "flip_y" => self.geometry.flip_y = parse_value(value)?,
+            "scale" => self.geometry.scale = parse_array(value)?,
+            "rotation" => self.geometry.rotation = parse_value(value)?,
+            "offset" => self.geometry.offset = parse_array(value)?,
+            "safety_zone" => {
+                let [min_x, min_y, max_x, max_y, brightness] = parse_array(value)?;
+                self.safety_zones.push(SafetyZone {
+                    min: [min_x, min_y],
+                    max: [max_x, max_y],
+                    brightness,
+                });
+            }
+            _ => return Err(format!("unknown key `{}`", key)),
+        }
+        Ok(())
+    }
+}
+
+impl ColorCalibration {
+    /// Whether or not the calibration leaves colours unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the calibration to the given colour.
+    pub fn apply(&self, color: Rgb) -> Rgb {
+        let mut out = [0.0; 3];
+        for ch in 0..3 {
+            let c = color[ch];
+            if c > 0.0 {
+                let c = self.gain[ch] * c.powf(self.gamma[ch]);
+                out[ch] = self.min[ch] + (1.0 - self.min[ch]) * c;
+            }
+        }
+        out
+    }
+}
+
+impl GeometricCorrection {
+    /// Whether or not the correction leaves positions unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the correction to the given position.
+    pub fn apply(&self, position: Position) -> Position {
+        let [mut x, mut y] = position;
+        if self.flip_x {
+            x = -x;
+        }
+        if self.flip_y {
+            y = -y;
+        }
+        x *= self.scale[0];
+        y *= self.scale[1];
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (x * cos - y * sin, x * sin + y * cos);
+        [x + self.offset[0], y + self.offset[1]]
+    }
+}
+
+impl SafetyZone {
+    /// Whether or not the given position lies within the zone.
+    pub fn contains(&self, position: Position) -> bool {
+        let [x, y] = position;
+        x >= self.min[0] && x <= self.max[0] && y >= self.min[1] && y <= self.max[1]
+    }
+}
+
+impl Default for ColorCalibration {
+    fn default() -> Self {
+        ColorCalibration {
+            gamma: [1.0; 3],
+            gain: [1.0; 3],
+            min: [0.0; 3],
+        }
+    }
+}
+
+impl Default for GeometricCorrection {
+    fn default() -> Self {
+        GeometricCorrection {
+            flip_x: false,
+            flip_y: false,
+            scale: [1.0; 2],
+            rotation: 0.0,
+            offset: [0.0; 2],
+        }
+    }
+}
+
+impl fmt::Display for ConfigStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Sort by ID so that saving the same store always produces the same file.
+        let mut entries: Vec<_> = self.configs.iter().collect();
+        entries.sort_by_key(|(id, _)| DisplayDacId(id).to_string());
+        for (ix, (id, config)) in entries.into_iter().enumerate() {
+            if ix > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", DisplayDacId(id))?;
+            write!(f, "{}", config)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DacConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(hz) = self.point_hz {
+            writeln!(f, "point_hz = {}", hz)?;
+        }
+        if let Some(points) = self.latency_points {
+            writeln!(f, "latency_points = {}", points)?;
+        }
+        if let Some(offset) = self.audio_sync_offset {
+            let ms = offset.as_nanos() as f64 / 1_000_000.0;
+            writeln!(f, "audio_sync_offset_ms = {}", ms)?;
+        }
+        let [r, g, b] = self.color.gamma;
+        writeln!(f, "color_gamma = {} {} {}", r, g, b)?;
+        let [r, g, b] = self.color.gain;
+        writeln!(f, "color_gain = {} {} {}", r, g, b)?;
+        let [r, g, b] = self.color.min;
+        writeln!(f, "color_min = {} {} {}", r, g, b)?;
+        writeln!(f, "flip_x = {}", self.geometry.flip_x)?;
+        writeln!(f, "flip_y = {}", self.geometry.flip_y)?;
+        let [x, y] = self.geometry.scale;
+        writeln!(f, "scale = {} {}", x, y)?;
+        writeln!(f, "rotation = {}", self.geometry.rotation)?;
+        let [x, y] = self.geometry.offset;
+        writeln!(f, "offset = {} {}", x, y)?;
+        for zone in &self.safety_zones {
+            let [min_x, min_y] = zone.min;
+            let [max_x, max_y] = zone.max;
+            writeln!(
+                f,
+                "safety_zone = {} {} {} {} {}",
+                min_x, min_y, max_x, max_y, zone.brightness
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Formats a DAC ID for use as a config section header, e.g. `ether-dream 00:11:22:33:44:55`.
+struct DisplayDacId<'a>(&'a DacId);
+
+impl<'a> fmt::Display for DisplayDacId<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            DacId::EtherDream { mac_address } => {
+                let [a, b, c, d, e, g] = mac_address;
+                write!(
+                    f,
+                    "{} {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                    ETHER_DREAM_SECTION, a, b, c, d, e, g
+                )
+            }
+            DacId::Idn { unit_id } => {
+                write!(f, "{} ", IDN_SECTION)?;
+                for byte in unit_id {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Parse a DAC ID from a config section header.
+fn parse_dac_id(s: &str) -> Result<DacId, String> {
+    let (kind, addr) = match s.trim().split_once(char::is_whitespace) {
+        Some((kind, addr)) => (kind, addr.trim()),
+        None => return Err(format!("expected `<dac-kind> <address>`, found `{}`", s)),
+    };
+    match kind {
+        ETHER_DREAM_SECTION => {
+            let mut mac_address = [0u8; 6];
+            let mut bytes = addr.split(':');
+            for byte in mac_address.iter_mut() {
+                *byte = bytes
+                    .next()
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+                    .ok_or_else(|| format!("invalid MAC address `{}`", addr))?;
+            }
+            if bytes.next().is_some() {
+                return Err(format!("invalid MAC address `{}`", addr));
+            }
+            Ok(DacId::EtherDream { mac_address })
+        }
+        IDN_SECTION => {
+            let mut unit_id = [0u8; crate::idn::UNIT_ID_LEN];
+            if !addr.is_ascii() || addr.len() != unit_id.len() * 2 {
+                return Err(format!("invalid IDN unit ID `{}`", addr));
+            }
+            for (ix, byte) in unit_id.iter_mut().enumerate() {
+                let hex = &addr[ix * 2..ix * 2 + 2];
+                *byte = u8::from_str_radix(hex, 16)
+                    .map_err(|_| format!("invalid IDN unit ID `{}`", addr))?;
+            }
+            Ok(DacId::Idn { unit_id })
+        }
+        _ => Err(format!("unknown DAC kind `{}`", kind)),
+    }
+}
+
+// Parse a single value.
+fn parse_value<T>(s: &str) -> Result<T, String>
+where
+    T: std::str::FromStr,
+{
+    s.parse().map_err(|_| format!("invalid value `{}`", s))
+}
+
+// Parse a whitespace-separated array of values.
+fn parse_array<const N: usize>(s: &str) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    let mut words = s.split_whitespace();
+    for value in values.iter_mut() {
+        let word = words
+            .next()
+            .ok_or_else(|| format!("expected {} values, found `{}`", N, s))?;
+        *value = parse_value(word)?;
+    }
+    if words.next().is_some() {
+        return Err(format!("expected {} values, found `{}`", N, s));
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0xaa, 0xbb, 0xff];
+
+    fn idn_id() -> DacId {
+        let mut unit_id = [0u8; crate::idn::UNIT_ID_LEN];
+        for (ix, byte) in unit_id.iter_mut().enumerate() {
+            *byte = ix as u8 * 17;
+        }
+        DacId::Idn { unit_id }
+    }
+
+    fn parse_err_line(s: &str) -> usize {
+        match ConfigStore::parse(s) {
+            Err(ConfigError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, found {:?}", other),
+        }
+    }
+
+    fn calibrated() -> DacConfig {
+        DacConfig {
+            point_hz: Some(30_000),
+            latency_points: Some(1_500),
+            audio_sync_offset: Some(Duration::from_micros(12_345)),
+            color: ColorCalibration {
+                gamma: [2.2, 1.8, 1.0],
+                gain: [0.9, 1.0, 0.75],
+                min: [0.1, 0.05, 0.0],
+            },
+            geometry: GeometricCorrection {
+                flip_x: true,
+                flip_y: false,
+                scale: [0.8, 0.9],
+                rotation: 0.1,
+                offset: [-0.05, 0.2],
+            },
+            safety_zones: vec![SafetyZone {
+                min: [-1.0, -1.0],
+                max: [1.0, -0.5],
+                brightness: 0.0,
+            }],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut store = ConfigStore::new();
+        store.insert(DacId::EtherDream { mac_address: MAC }, calibrated());
+        store.insert(idn_id(), DacConfig::default());
+        let text = store.to_string();
+        let parsed = ConfigStore::parse(&text).unwrap();
+        assert_eq!(parsed, store);
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        let store = ConfigStore::new();
+        assert_eq!(store.to_string(), "");
+        assert_eq!(ConfigStore::parse("").unwrap(), store);
+    }
+
+    #[test]
+    fn ether_dream_header() {
+        let store =
+            ConfigStore::parse("[ether-dream 00:11:22:AA:bb:ff]\npoint_hz = 20000").unwrap();
+        let config = store.get(&DacId::EtherDream { mac_address: MAC }).unwrap();
+        assert_eq!(config.point_hz, Some(20_000));
+        let text = store.to_string();
+        assert!(text.starts_with("[ether-dream 00:11:22:aa:bb:ff]\n"));
+    }
+
+    #[test]
+    fn idn_header() {
+        let header = "[idn 00112233445566778899aabbccddeeff]";
+        let store = ConfigStore::parse(header).unwrap();
+        assert!(store.get(&idn_id()).is_some());
+        assert!(store.to_string().starts_with(header));
+    }
+
+    #[test]
+    fn invalid_headers() {
+        assert_eq!(parse_err_line("[ether-dream 00:11:22:33:44]"), 1);
+        assert_eq!(parse_err_line("[ether-dream 00:11:22:33:44:55:66]"), 1);
+        assert_eq!(parse_err_line("[ether-dream 00:11:22:33:44:zz]"), 1);
+        assert_eq!(parse_err_line("[idn 0011]"), 1);
+        assert_eq!(parse_err_line("[idn 00112233445566778899aabbccddeegg]"), 1);
+        assert_eq!(parse_err_line("[helios 1234]"), 1);
+        assert_eq!(parse_err_line("[ether-dream]"), 1);
+        assert_eq!(parse_err_line("[ether-dream 00:11:22:33:44:55"), 1);
+    }
+
+    #[test]
+    fn error_line_numbers() {
+        let s = "# comment\n\n[ether-dream 00:11:22:33:44:55]\npoint_hz = 20000\nscale = 1.0\n";
+        assert_eq!(parse_err_line(s), 5);
+        assert_eq!(parse_err_line("point_hz = 20000"), 1);
+        let s = "[ether-dream 00:11:22:33:44:55]\n\nbogus = 1";
+        assert_eq!(parse_err_line(s), 3);
+        let s = "[ether-dream 00:11:22:33:44:55]\nflip_x";
+        assert_eq!(parse_err_line(s), 2);
+        let s = "[ether-dream 00:11:22:33:44:55]\naudio_sync_offset_ms = -1";
+        assert_eq!(parse_err_line(s), 2);
+    }
+
+    #[test]
+    fn duplicate_sections_are_rejected() {
+        let s = "[ether-dream 00:11:22:33:44:55]\n\
+                 point_hz = 20000\n\
+                 [idn 00112233445566778899aabbccddeeff]\n\
+                 [ether-dream 00:11:22:33:44:55]\n\
+                 point_hz = 30000\n";
+        assert_eq!(parse_err_line(s), 4);
+        let s = "[idn 00112233445566778899aabbccddeeff]\n[idn 00112233445566778899AABBCCDDEEFF]";
+        assert_eq!(parse_err_line(s), 2);
+    }
+}
@@ -15,7 +15,12 @@ impl<F> DetectedDacCallback for F where F: FnMut(io::Result<DetectedDac>) {}
 /// It should be possible to use this to uniquely identify the same DAC on different occasions.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Id {
-    EtherDream { mac_address: [u8; 6] },
+    EtherDream {
+        mac_address: [u8; 6],
+    },
+    Idn {
+        unit_id: [u8; crate::idn::UNIT_ID_LEN],
+    },
 }
 
 /// An available DAC detected on the system.
@@ -26,6 +31,11 @@ pub enum DetectedDac {
         broadcast: ether_dream::protocol::DacBroadcast,
         source_addr: std::net::SocketAddr,
     },
+    /// A network laser projector or software console discovered via an IDN-Hello scan.
+    Idn {
+        scan: crate::idn::ScanResponse,
+        source_addr: std::net::SocketAddr,
+    },
 }
 
 /// An iterator yielding laser DACs available on the system as they are discovered.
@@ -52,6 +62,7 @@ impl DetectedDac {
     pub fn max_point_hz(&self) -> u32 {
         match self {
             DetectedDac::EtherDream { ref broadcast, .. } => broadcast.max_point_rate as _,
+            DetectedDac::Idn { .. } => crate::idn::MAX_POINT_HZ,
         }
     }
 
@@ -59,6 +70,7 @@ impl DetectedDac {
     pub fn buffer_capacity(&self) -> u32 {
         match self {
             DetectedDac::EtherDream { ref broadcast, .. } => broadcast.buffer_capacity as _,
+            DetectedDac::Idn { .. } => crate::idn::BUFFER_CAPACITY,
         }
     }
 
@@ -70,6 +82,9 @@ impl DetectedDac {
             DetectedDac::EtherDream { ref broadcast, .. } => Id::EtherDream {
                 mac_address: broadcast.mac_address,
             },
+            DetectedDac::Idn { ref scan, .. } => Id::Idn {
+                unit_id: scan.unit_id,
+            },
         }
     }
 }
@@ -128,6 +143,15 @@ pub(crate) fn detect_dacs() -> io::Result<DetectDacs> {
     Ok(DetectDacs { dac_broadcasts })
 }
 
+/// Scan the local network for IDN devices, collecting all that respond within the given timeout.
+pub(crate) fn detect_idn_dacs(timeout: Duration) -> io::Result<Vec<DetectedDac>> {
+    let dacs = crate::idn::scan(timeout)?
+        .into_iter()
+        .map(|(scan, source_addr)| DetectedDac::Idn { scan, source_addr })
+        .collect();
+    Ok(dacs)
+}
+
 /// Spawn a thread for DAC detection.
 ///
 /// Calls the given `callback` with broadcasts as they are received.
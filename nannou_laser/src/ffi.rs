@@ -27,15 +27,27 @@ pub struct DetectDacsAsync {
 #[derive(Clone, Copy)]
 pub struct DetectedDac {
     pub kind: DetectedDacKind,
+    /// The protocol spoken by the DAC, indicating which field of `kind` is valid.
+    ///
+    /// This field follows `kind` so that the layout of `kind` is unchanged from earlier versions.
+    pub protocol: DacProtocol,
+}
+
+/// The protocol spoken by a detected DAC.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DacProtocol {
+    EtherDream,
+    Idn,
 }
 
-/// A union for distinguishing between the kind of LASER DAC that was detected. Currently, only
-/// EtherDream is supported, however this will gain more variants as more protocols are added (e.g.
-/// AVB).
+/// A union for distinguishing between the kind of LASER DAC that was detected. The `protocol` of
+/// the `DetectedDac` indicates which field is valid.
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub union DetectedDacKind {
     pub ether_dream: DacEtherDream,
+    pub idn: DacIdn,
 }
 
 /// An Ether Dream DAC that was detected on the network.
@@ -46,6 +58,14 @@ pub struct DacEtherDream {
     pub source_addr: SocketAddr,
 }
 
+/// An IDN DAC that was detected on the network.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct DacIdn {
+    pub scan: crate::idn::ScanResponse,
+    pub source_addr: SocketAddr,
+}
+
 /// A set of stream configuration parameters applied to the initialisation of both `Raw` and
 /// `Frame` streams.
 #[repr(C)]
@@ -135,6 +155,10 @@ pub enum StreamErrorKind {
     EtherDreamFailedToSubmitData,
     EtherDreamFailedToSubmitPointRate,
     EtherDreamFailedToStopStream,
+    IdnFailedToDetectDacs,
+    IdnFailedToConnectStream,
+    IdnFailedToSubmitData,
+    IdnFailedToCloseStream,
 }
 
 #[repr(C)]
@@ -919,10 +943,11 @@ pub unsafe extern "C" fn stream_error_kind(err: *const StreamError) -> StreamErr
 /// Retrieve the number of attempts from the stream error.
 ///
 /// If the error is `EtherDreamFailedToConnectStream`, this refers to the consecutive number of
-/// failed attempts to establish a TCP connection with the DAC.
+/// failed attempts to establish a TCP connection with the DAC. Likewise for
+/// `IdnFailedToConnectStream` and the UDP socket.
 ///
-/// If the error is `EtherDreamFailedToDetectDac`, this refers to the consecutive number of failed
-/// attempts to detect the requested DAC.
+/// If the error is `EtherDreamFailedToDetectDac` or `IdnFailedToDetectDacs`, this refers to the
+/// consecutive number of failed attempts to detect the requested DAC.
 #[no_mangle]
 pub unsafe extern "C" fn stream_error_attempts(err: *const StreamError) -> u32 {
     let err: &crate::StreamError = &*(*(*err).inner).0;
@@ -1103,24 +1128,41 @@ fn detected_dac_to_ffi(dac: crate::DetectedDac) -> DetectedDac {
                 source_addr,
             };
             let kind = DetectedDacKind { ether_dream };
-            DetectedDac { kind }
+            let protocol = DacProtocol::EtherDream;
+            DetectedDac { kind, protocol }
+        }
+        crate::DetectedDac::Idn { scan, source_addr } => {
+            let source_addr = socket_addr_to_ffi(source_addr);
+            let idn = DacIdn { scan, source_addr };
+            let kind = DetectedDacKind { idn };
+            let protocol = DacProtocol::Idn;
+            DetectedDac { kind, protocol }
         }
     }
 }
 
 fn detected_dac_from_ffi(ffi_dac: DetectedDac) -> crate::DetectedDac {
     unsafe {
-        let broadcast = ffi_dac.kind.ether_dream.broadcast.clone();
-        let source_addr = socket_addr_from_ffi(ffi_dac.kind.ether_dream.source_addr);
-        crate::DetectedDac::EtherDream {
-            broadcast,
-            source_addr,
+        match ffi_dac.protocol {
+            DacProtocol::EtherDream => {
+                let broadcast = ffi_dac.kind.ether_dream.broadcast.clone();
+                let source_addr = socket_addr_from_ffi(ffi_dac.kind.ether_dream.source_addr);
+                crate::DetectedDac::EtherDream {
+                    broadcast,
+                    source_addr,
+                }
+            }
+            DacProtocol::Idn => {
+                let scan = ffi_dac.kind.idn.scan;
+                let source_addr = socket_addr_from_ffi(ffi_dac.kind.idn.source_addr);
+                crate::DetectedDac::Idn { scan, source_addr }
+            }
         }
     }
 }
 
 fn stream_error_to_kind(err: &crate::StreamError) -> StreamErrorKind {
-    use crate::stream::raw::EtherDreamStreamError;
+    use crate::stream::raw::{EtherDreamStreamError, IdnStreamError};
     match *err {
         crate::StreamError::EtherDreamStream { ref err } => match *err {
             EtherDreamStreamError::FailedToDetectDacs { .. } => {
@@ -1145,16 +1187,29 @@ fn stream_error_to_kind(err: &crate::StreamError) -> StreamErrorKind {
                 StreamErrorKind::EtherDreamFailedToStopStream
             }
         },
+        crate::StreamError::IdnStream { ref err } => match *err {
+            IdnStreamError::FailedToDetectDacs { .. } => StreamErrorKind::IdnFailedToDetectDacs,
+            IdnStreamError::FailedToConnectStream { .. } => {
+                StreamErrorKind::IdnFailedToConnectStream
+            }
+            IdnStreamError::FailedToSubmitData { .. } => StreamErrorKind::IdnFailedToSubmitData,
+            IdnStreamError::FailedToCloseStream { .. } => StreamErrorKind::IdnFailedToCloseStream,
+        },
     }
 }
 
 fn stream_error_to_attempts(err: &crate::StreamError) -> u32 {
-    use crate::stream::raw::EtherDreamStreamError;
+    use crate::stream::raw::{EtherDreamStreamError, IdnStreamError};
     match *err {
         crate::StreamError::EtherDreamStream { ref err } => match *err {
             EtherDreamStreamError::FailedToDetectDacs { attempts, .. }
             | EtherDreamStreamError::FailedToConnectStream { attempts, .. } => attempts,
             _ => 0,
         },
+        crate::StreamError::IdnStream { ref err } => match *err {
+            IdnStreamError::FailedToDetectDacs { attempts, .. }
+            | IdnStreamError::FailedToConnectStream { attempts, .. } => attempts,
+            _ => 0,
+        },
     }
 }
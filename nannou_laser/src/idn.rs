@@ -0,0 +1,321 @@
+//! An implementation of the ILDA Digital Network (IDN) protocol.
+//!
+//! IDN projectors and software consoles are discovered via IDN-Hello scan requests broadcast on
+//! the local network. Points are then sent via IDN-Stream channel messages over UDP.
+//!
+//! Unlike the Ether Dream protocol, IDN provides no feedback on the fullness of the projector's
+//! buffer. Streams instead pace the points they send against the system clock, aiming to stay
+//! `latency_points` ahead of playback.
+//!
+//! See the [ILDA technical standards](https://www.ilda.com/technical.htm) for the protocol
+//! specifications.
+
+use crate::point::RawPoint;
+use crate::util::{clamp, map_range};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// The UDP port on which IDN-Hello and IDN-Stream messages are received.
+pub const PORT: u16 = 7255;
+
+/// The maximum point rate assumed for IDN devices, as the protocol does not report one.
+pub const MAX_POINT_HZ: u32 = 100_000;
+
+/// The maximum number of points a stream will send ahead of playback.
+pub const BUFFER_CAPACITY: u32 = 8_192;
+
+/// The maximum size of a single UDP packet sent by a stream, avoiding IP fragmentation on
+/// typical networks.
+pub const MAX_PACKET_SIZE: usize = 1_454;
+
+/// The interval at which the channel configuration is re-sent, allowing devices that join or
+/// restart mid-stream to interpret the samples.
+pub const CONFIG_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The length of the unit ID field of a scan response in bytes.
+pub const UNIT_ID_LEN: usize = 16;
+
+/// The length of the host name field of a scan response in bytes.
+pub const HOST_NAME_LEN: usize = 20;
+
+/// The response of an IDN device to a scan request.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScanResponse {
+    /// The IDN protocol version, with the major version in the upper 4 bits.
+    pub protocol_version: u8,
+    /// Status flags of the unit, e.g. whether it is currently in use.
+    pub status: u8,
+    /// The unique ID of the unit. The first byte is the length of the ID that follows.
+    pub unit_id: [u8; UNIT_ID_LEN],
+    /// The name of the unit, padded with zeros.
+    pub host_name: [u8; HOST_NAME_LEN],
+}
+
+/// A UDP stream of points to a single IDN device.
+pub struct Stream {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+    sequence: u16,
+    // The time at which the stream began, used for the timestamp of each channel message.
+    start: Instant,
+    // The timestamp of the next channel message in microseconds.
+    timestamp: u64,
+    // When the channel configuration was last sent, or `None` if it has not yet been sent.
+    last_config: Option<Instant>,
+    // Whether or not the channel has been closed.
+    is_closed: bool,
+}
+
+// IDN-Hello commands.
+const CMD_SCAN_REQUEST: u8 = 0x10;
+const CMD_SCAN_RESPONSE: u8 = 0x11;
+const CMD_RT_CNLMSG: u8 = 0x40;
+const CMD_RT_CNLMSG_CLOSE: u8 = 0x44;
+
+// Channel message content ID flags.
+const CONTENT_ID_CHANNEL_MSG: u16 = 0x8000;
+const CONTENT_ID_CONFIG_LSTFRG: u16 = 0x4000;
+const CHUNK_TYPE_LPGRF_WAVE: u16 = 0x01;
+
+// Channel configuration values.
+const CHANNEL_CONFIG_ROUTING: u8 = 0x01;
+const SERVICE_MODE_LPGRF_CONTINUOUS: u8 = 0x01;
+
+// The sample descriptors for 16-bit X and Y positions followed by 8-bit red, green and blue.
+const DESCRIPTORS: [u16; 8] = [
+    0x4200, // X
+    0x4010, // 16-bit precision
+    0x4210, // Y
+    0x4010, // 16-bit precision
+    0x527E, // Red, 638nm
+    0x5214, // Green, 532nm
+    0x51CC, // Blue, 460nm
+    0x0000, // Void, for alignment
+];
+
+// The size of each encoded sample in bytes.
+const SAMPLE_SIZE: usize = 7;
+
+// The size of the packet, channel message, channel config and sample chunk headers in bytes.
+const PACKET_HEADER_SIZE: usize = 4;
+const CHANNEL_MSG_HEADER_SIZE: usize = 8;
+const CONFIG_HEADER_SIZE: usize = 4 + DESCRIPTORS.len() * 2;
+const CHUNK_HEADER_SIZE: usize = 4;
+
+// The size of a scan response payload in bytes.
+const SCAN_RESPONSE_SIZE: usize = 4 + UNIT_ID_LEN + HOST_NAME_LEN;
+
+impl ScanResponse {
+    /// The unique ID of the unit, excluding the length prefix.
+    pub fn unit_id(&self) -> &[u8] {
+        let len = std::cmp::min(self.unit_id[0] as usize, UNIT_ID_LEN - 1);
+        &self.unit_id[1..1 + len]
+    }
+
+    /// The name of the unit.
+    pub fn host_name(&self) -> String {
+        let len = self
+            .host_name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(HOST_NAME_LEN);
+        String::from_utf8_lossy(&self.host_name[..len]).into_owned()
+    }
+
+    // Parse a scan response from the payload following the packet header.
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < SCAN_RESPONSE_SIZE {
+            return None;
+        }
+        let mut unit_id = [0; UNIT_ID_LEN];
+        unit_id.copy_from_slice(&bytes[4..4 + UNIT_ID_LEN]);
+        let mut host_name = [0; HOST_NAME_LEN];
+        host_name.copy_from_slice(&bytes[4 + UNIT_ID_LEN..SCAN_RESPONSE_SIZE]);
+        Some(ScanResponse {
+            protocol_version: bytes[1],
+            status: bytes[2],
+            unit_id,
+            host_name,
+        })
+    }
+}
+
+impl Stream {
+    /// Open a stream to the IDN device at the given address.
+    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let local_addr: SocketAddr = match addr {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.connect(addr)?;
+        Ok(Stream {
+            socket,
+            buffer: Vec::with_capacity(MAX_PACKET_SIZE),
+            sequence: 0,
+            start: Instant::now(),
+            timestamp: 0,
+            last_config: None,
+            is_closed: false,
+        })
+    }
+
+    /// The time elapsed since the stream was opened.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Send the given points for playback at the given rate.
+    ///
+    /// Points are split across as many channel messages as necessary to keep each packet within
+    /// `MAX_PACKET_SIZE`.
+    pub fn send_points(&mut self, points: &[RawPoint], point_hz: u32) -> io::Result<()> {
+        let max_samples = (MAX_PACKET_SIZE
+            - PACKET_HEADER_SIZE
+            - CHANNEL_MSG_HEADER_SIZE
+            - CONFIG_HEADER_SIZE
+            - CHUNK_HEADER_SIZE)
+            / SAMPLE_SIZE;
+        for chunk in points.chunks(max_samples) {
+            self.send_chunk(chunk, point_hz)?;
+        }
+        Ok(())
+    }
+
+    /// Close the channel, indicating to the device that no more points will follow.
+    ///
+    /// This is called automatically when the stream is dropped if it has not already been closed.
+    pub fn close(&mut self) -> io::Result<()> {
+        self.is_closed = true;
+        self.buffer.clear();
+        self.write_packet_header(CMD_RT_CNLMSG_CLOSE);
+        self.socket.send(&self.buffer)?;
+        Ok(())
+    }
+
+    // Send a single channel message containing the given points.
+    fn send_chunk(&mut self, points: &[RawPoint], point_hz: u32) -> io::Result<()> {
+        let now = Instant::now();
+        let send_config = self
+            .last_config
+            .map_or(true, |last| now.duration_since(last) >= CONFIG_INTERVAL);
+        let duration_us = points.len() as u64 * 1_000_000 / std::cmp::max(point_hz, 1) as u64;
+
+        self.buffer.clear();
+        self.write_packet_header(CMD_RT_CNLMSG);
+
+        // The channel message header. The total size is written once the message is complete.
+        let msg_start = self.buffer.len();
+        let mut content_id = CONTENT_ID_CHANNEL_MSG | CHUNK_TYPE_LPGRF_WAVE;
+        if send_config {
+            content_id |= CONTENT_ID_CONFIG_LSTFRG;
+        }
+        self.buffer.extend_from_slice(&[0, 0]);
+        self.buffer.extend_from_slice(&content_id.to_be_bytes());
+        self.buffer
+            .extend_from_slice(&(self.timestamp as u32).to_be_bytes());
+
+        if send_config {
+            let word_count = (DESCRIPTORS.len() / 2) as u8;
+            let service_id = 0;
+            self.buffer.extend_from_slice(&[
+                word_count,
+                CHANNEL_CONFIG_ROUTING,
+                service_id,
+                SERVICE_MODE_LPGRF_CONTINUOUS,
+            ]);
+            for descriptor in &DESCRIPTORS {
+                self.buffer.extend_from_slice(&descriptor.to_be_bytes());
+            }
+            self.last_config = Some(now);
+        }
+
+        // The sample chunk header, with the chunk flags in the upper 8 bits.
+        let flags_duration = (duration_us as u32) & 0x00FF_FFFF;
+        self.buffer.extend_from_slice(&flags_duration.to_be_bytes());
+        for point in points {
+            write_sample(&mut self.buffer, point);
+        }
+
+        let msg_len = (self.buffer.len() - msg_start) as u16;
+        self.buffer[msg_start..msg_start + 2].copy_from_slice(&msg_len.to_be_bytes());
+        self.socket.send(&self.buffer)?;
+        self.timestamp += duration_us;
+        Ok(())
+    }
+
+    // Write the packet header for the given command to the buffer.
+    fn write_packet_header(&mut self, command: u8) {
+        let flags = 0;
+        self.buffer.extend_from_slice(&[command, flags]);
+        self.buffer.extend_from_slice(&self.sequence.to_be_bytes());
+        self.sequence = self.sequence.wrapping_add(1);
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        if !self.is_closed {
+            self.close().ok();
+        }
+    }
+}
+
+/// Broadcast a scan request to the local network and collect all responses received within the
+/// given timeout.
+///
+/// Returns the address and scan response of each device that responded.
+pub fn scan(timeout: Duration) -> io::Result<Vec<(ScanResponse, SocketAddr)>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+    let sequence: u16 = 0;
+    let mut request = vec![CMD_SCAN_REQUEST, 0];
+    request.extend_from_slice(&sequence.to_be_bytes());
+    socket.send_to(&request, (Ipv4Addr::BROADCAST, PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut responses: Vec<(ScanResponse, SocketAddr)> = vec![];
+    let mut buffer = [0u8; 512];
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        socket.set_read_timeout(Some(deadline - now))?;
+        let (len, addr) = match socket.recv_from(&mut buffer) {
+            Ok(res) => res,
+            Err(err) => match err.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => break,
+                _ => return Err(err),
+            },
+        };
+        let bytes = &buffer[..len];
+        if bytes.len() < PACKET_HEADER_SIZE || bytes[0] != CMD_SCAN_RESPONSE {
+            continue;
+        }
+        if let Some(response) = ScanResponse::from_bytes(&bytes[PACKET_HEADER_SIZE..]) {
+            let addr = SocketAddr::new(addr.ip(), PORT);
+            // Devices may respond on multiple interfaces.
+            if !responses.iter().any(|(r, _)| r.unit_id == response.unit_id) {
+                responses.push((response, addr));
+            }
+        }
+    }
+    Ok(responses)
+}
+
+// Write the given point as a sample matching the `DESCRIPTORS`.
+fn write_sample(buffer: &mut Vec<u8>, point: &RawPoint) {
+    let [px, py] = point.position;
+    let min = std::i16::MIN;
+    let max = std::i16::MAX;
+    let x = map_range(clamp(px, -1.0, 1.0), -1.0, 1.0, min as f64, max as f64) as i16;
+    let y = map_range(clamp(py, -1.0, 1.0), -1.0, 1.0, min as f64, max as f64) as i16;
+    buffer.extend_from_slice(&x.to_be_bytes());
+    buffer.extend_from_slice(&y.to_be_bytes());
+    for &c in &point.color {
+        buffer.push((clamp(c, 0.0, 1.0) * std::u8::MAX as f32) as u8);
+    }
+}
@@ -2,11 +2,16 @@
 //! process of reading the ILDA IDTF format into frames of points that are compatible with the
 //! `nannou_laser` API.
 //!
+//! A **Player** plays back the frames of an ILDA file and may be used directly as the model and
+//! render function of a laser frame stream.
+//!
 //! See the extensive, top-level `ilda-idtf` API docs [here](https://docs.rs/ilda-idtf).
 
+use crate::stream::{self, frame::Frame};
 use crate::{point, Point};
 use std::io;
 use std::path::Path;
+use std::time::Duration;
 
 #[doc(inline)]
 pub use ilda_idtf::*;
@@ -22,6 +27,31 @@ pub struct FrameReader<R> {
 /// A `FrameReader` that reads from a buffered file.
 pub type BufFileFrameReader = FrameReader<io::BufReader<std::fs::File>>;
 
+/// Plays back the frames of an ILDA file at a fixed frame rate.
+///
+/// The player is designed to be used as the model of a laser frame stream, with `Player::render`
+/// as the render function:
+///
+/// ```ignore
+/// let player = Player::open("show.ild")?.frame_hz(30);
+/// let stream = api.new_frame_stream(player, Player::render).build()?;
+/// ```
+///
+/// Playback advances by one period of the stream's frame rate each time the stream requests a
+/// frame. When the player's frame rate differs from that of the stream, ILDA frames are repeated
+/// or skipped so that the file plays back at the player's rate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    frames: Vec<Vec<Point>>,
+    frame_hz: u32,
+    looping: bool,
+    playing: bool,
+    // The index of the frame to be emitted next.
+    index: usize,
+    // The progress towards the next frame, in units of `1 / stream_hz` of a frame.
+    phase: u64,
+}
+
 impl<R> FrameReader<R>
 where
     R: io::Read,
@@ -113,6 +143,208 @@ impl BufFileFrameReader {
     }
 }
 
+impl Player {
+    /// Read all frames of the ILDA file at the given path.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_frame_reader(BufFileFrameReader::open(path)?)
+    }
+
+    /// Read all remaining frames from the given `FrameReader`.
+    pub fn from_frame_reader<R>(mut reader: FrameReader<R>) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        let mut frames = vec![];
+        while let Some(points) = reader.next()? {
+            frames.push(points.to_vec());
+        }
+        Ok(Self::from_frames(frames))
+    }
+
+    /// Create a player from frames that have already been read, each a list of points
+    /// representing consecutive lines.
+    pub fn from_frames(frames: Vec<Vec<Point>>) -> Self {
+        Player {
+            frames,
+            frame_hz: stream::DEFAULT_FRAME_HZ,
+            looping: true,
+            playing: true,
+            index: 0,
+            phase: 0,
+        }
+    }
+
+    /// The rate at which frames of the file are played back.
+    ///
+    /// By default this value is `stream::DEFAULT_FRAME_HZ`.
+    ///
+    /// **Panic!**s if `hz` is `0`.
+    pub fn frame_hz(mut self, hz: u32) -> Self {
+        self.set_frame_hz(hz);
+        self
+    }
+
+    /// Whether or not playback should wrap to the first frame upon reaching the end of the file.
+    ///
+    /// By default this value is `true`.
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Whether or not playback begins as soon as the stream requests its first frame.
+    ///
+    /// By default this value is `true`.
+    pub fn playing(mut self, playing: bool) -> Self {
+        self.playing = playing;
+        self
+    }
+
+    /// Change the rate at which frames of the file are played back.
+    ///
+    /// The current frame is preserved.
+    ///
+    /// **Panic!**s if `hz` is `0`.
+    pub fn set_frame_hz(&mut self, hz: u32) {
+        assert!(hz > 0, "the frame rate must be greater than zero");
+        let index = self.frame_index();
+        self.frame_hz = hz;
+        self.seek_frame(index);
+    }
+
+    /// Change whether or not playback wraps to the first frame upon reaching the end.
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    /// All frames of the file in order.
+    pub fn frames(&self) -> &[Vec<Point>] {
+        &self.frames
+    }
+
+    /// The number of frames in the file.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether or not the file contained no frames.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The total duration of the file at the current frame rate.
+    pub fn duration(&self) -> Duration {
+        frames_to_duration(self.frames.len(), self.frame_hz)
+    }
+
+    /// The current playback position, i.e. the start of the frame at `frame_index`.
+    pub fn position(&self) -> Duration {
+        frames_to_duration(self.index, self.frame_hz)
+    }
+
+    /// The index of the frame at the current playback position.
+    ///
+    /// This is equal to `len` once playback reaches the end of a file that is not looping.
+    pub fn frame_index(&self) -> usize {
+        self.index
+    }
+
+    /// Whether or not the player is currently playing.
+    ///
+    /// This becomes `false` once playback reaches the end of a file that is not looping.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Start or resume playback from the current position.
+    ///
+    /// If playback had reached the end of the file, it restarts from the first frame.
+    pub fn play(&mut self) {
+        if self.index >= self.frames.len() {
+            self.seek_frame(0);
+        }
+        self.playing = true;
+    }
+
+    /// Pause playback at the current frame.
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Stop playback and return to the first frame.
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.seek_frame(0);
+    }
+
+    /// Move the playback position to the start of the frame at the given index.
+    pub fn seek_frame(&mut self, index: usize) {
+        self.index = index;
+        self.phase = 0;
+    }
+
+    /// Move the playback position to the start of the frame nearest to the given position.
+    pub fn seek(&mut self, position: Duration) {
+        let frames = position.as_nanos() * self.frame_hz as u128;
+        let index = (frames + NANOS_PER_SEC / 2) / NANOS_PER_SEC;
+        self.seek_frame(index as usize);
+    }
+
+    /// The points of the frame at the current playback position, if any.
+    pub fn current_frame(&self) -> Option<&[Point]> {
+        self.frames.get(self.index).map(|f| &f[..])
+    }
+
+    /// Produce the points to display for the next period of a stream running at `stream_hz`
+    /// frames per second, advancing playback by one period.
+    ///
+    /// Returns `None` while playback is paused or stopped, or once playback reaches the end of a
+    /// file that is not looping.
+    ///
+    /// **Panic!**s if `stream_hz` is `0`.
+    pub fn next_frame(&mut self, stream_hz: u32) -> Option<&[Point]> {
+        assert!(
+            stream_hz > 0,
+            "the stream frame rate must be greater than zero"
+        );
+        if !self.playing || self.frames.is_empty() {
+            return None;
+        }
+
+        // Handle reaching the end of the file.
+        if self.index >= self.frames.len() {
+            if self.looping {
+                self.index %= self.frames.len();
+            } else {
+                self.playing = false;
+                return None;
+            }
+        }
+
+        // Advance by `frame_hz / stream_hz` frames, carrying the remainder to the next period.
+        let index = self.index;
+        let stream_hz = stream_hz as u64;
+        self.phase += self.frame_hz as u64;
+        self.index += (self.phase / stream_hz) as usize;
+        self.phase %= stream_hz;
+
+        Some(&self.frames[index][..])
+    }
+
+    /// Add the current frame to the given laser frame and advance playback.
+    ///
+    /// This may be passed directly to `Api::new_frame_stream` as the render function. Nothing is
+    /// emitted while playback is paused or stopped. See `next_frame`.
+    pub fn render(&mut self, frame: &mut Frame) {
+        if let Some(points) = self.next_frame(frame.frame_hz()) {
+            frame.add_lines(points);
+        }
+    }
+}
+
 impl<R> From<SectionReader<R>> for FrameReader<R>
 where
     R: io::Read,
@@ -131,6 +363,15 @@ where
     }
 }
 
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+// The duration of the given number of frames at the given rate, rounded to the nearest nanosecond.
+fn frames_to_duration(frames: usize, hz: u32) -> Duration {
+    let hz = hz as u128;
+    let nanos = (frames as u128 * NANOS_PER_SEC + hz / 2) / hz;
+    Duration::from_nanos(nanos as u64)
+}
+
 fn normalise_coord(c: i16) -> f32 {
     c as f32 / std::i16::MAX as f32
 }
@@ -204,3 +445,123 @@ fn point_from_coords_2d_true_color(p: layout::Coords2dTrueColor) -> Point {
     };
     Point::new(position, color)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A player with `n` frames, each a single point whose x coordinate is its index.
+    fn player(n: usize) -> Player {
+        let frames = (0..n)
+            .map(|i| vec![Point::new([i as f32, 0.0], [1.0; 3])])
+            .collect();
+        Player::from_frames(frames)
+    }
+
+    fn next_index(player: &mut Player, stream_hz: u32) -> Option<usize> {
+        player
+            .next_frame(stream_hz)
+            .map(|points| points[0].position[0] as usize)
+    }
+
+    fn play(player: &mut Player, stream_hz: u32, periods: usize) -> Vec<Option<usize>> {
+        (0..periods)
+            .map(|_| next_index(player, stream_hz))
+            .collect()
+    }
+
+    #[test]
+    fn plays_each_frame_once_in_order() {
+        let n = 90;
+        let mut p = player(n).frame_hz(30).looping(false);
+        let played: Vec<_> = play(&mut p, 30, n)
+            .into_iter()
+            .map(Option::unwrap)
+            .collect();
+        assert_eq!(played, (0..n).collect::<Vec<_>>());
+        assert_eq!(next_index(&mut p, 30), None);
+        assert!(!p.is_playing());
+    }
+
+    #[test]
+    fn loops_after_last_frame() {
+        let mut p = player(3).frame_hz(30);
+        let expected: Vec<_> = [0, 1, 2, 0, 1, 2, 0].iter().map(|&i| Some(i)).collect();
+        assert_eq!(play(&mut p, 30, 7), expected);
+    }
+
+    #[test]
+    fn repeats_frames_for_faster_streams() {
+        let mut p = player(3).frame_hz(30);
+        let expected: Vec<_> = [0, 0, 1, 1, 2, 2].iter().map(|&i| Some(i)).collect();
+        assert_eq!(play(&mut p, 60, 6), expected);
+    }
+
+    #[test]
+    fn skips_frames_for_slower_streams() {
+        let mut p = player(6).frame_hz(60).looping(false);
+        let expected = vec![Some(0), Some(2), Some(4), None];
+        assert_eq!(play(&mut p, 30, 4), expected);
+    }
+
+    #[test]
+    fn uneven_rates_play_every_frame_once() {
+        let mut p = player(25).frame_hz(25).looping(false);
+        let played: Vec<_> = play(&mut p, 60, 60).into_iter().flatten().collect();
+        let mut unique = played.clone();
+        unique.dedup();
+        assert_eq!(unique, (0..25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn seek_frame_lands_on_frame() {
+        for &hz in &[24, 25, 30, 60, 7] {
+            let mut p = player(100).frame_hz(hz);
+            for n in 0..100 {
+                p.seek_frame(n);
+                assert_eq!(p.frame_index(), n);
+                assert_eq!(next_index(&mut p, hz), Some(n));
+            }
+        }
+    }
+
+    #[test]
+    fn seek_rounds_to_nearest_frame() {
+        let mut p = player(10).frame_hz(30);
+        for n in 0..10u32 {
+            p.seek(Duration::from_secs(1) / 30 * n);
+            assert_eq!(p.frame_index(), n as usize);
+        }
+        p.seek(p.duration() / 2);
+        assert_eq!(p.frame_index(), 5);
+    }
+
+    #[test]
+    fn set_frame_hz_preserves_frame() {
+        let mut p = player(10).frame_hz(30);
+        p.seek_frame(4);
+        for &hz in &[25, 30, 60, 24, 30] {
+            p.set_frame_hz(hz);
+            assert_eq!(p.frame_index(), 4);
+        }
+    }
+
+    #[test]
+    fn duration_and_position_are_exact() {
+        let mut p = player(30).frame_hz(30);
+        assert_eq!(p.duration(), Duration::from_secs(1));
+        p.seek_frame(15);
+        assert_eq!(p.position(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn paused_player_emits_nothing() {
+        let mut p = player(3).frame_hz(30).playing(false);
+        assert_eq!(next_index(&mut p, 30), None);
+        p.play();
+        assert_eq!(next_index(&mut p, 30), Some(0));
+        p.stop();
+        assert_eq!(p.frame_index(), 0);
+        assert_eq!(next_index(&mut p, 30), None);
+    }
+}
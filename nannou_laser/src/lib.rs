@@ -2,25 +2,37 @@
 
 pub extern crate ether_dream;
 
+pub mod config;
 pub mod dac;
 #[cfg(feature = "ffi")]
 pub mod ffi;
+pub mod idn;
 #[cfg(feature = "ilda-idtf")]
 pub mod ilda_idtf;
+pub mod morph;
 pub mod point;
+pub mod projection;
+pub mod show;
 pub mod stream;
 pub mod util;
 
+pub use config::{ConfigError, ConfigStore, DacConfig};
 pub use dac::{DetectDacs, DetectDacsAsync, DetectedDac, DetectedDacCallback, Id as DacId};
 pub use point::{Point, RawPoint};
+pub use projection::{Camera, DepthAttenuation, Point3};
 pub use stream::frame::Frame;
 pub use stream::frame::Stream as FrameStream;
+pub use stream::frame::{TapPoint, TapPointKind};
 pub use stream::raw::Stream as RawStream;
-pub use stream::raw::{Buffer, StreamError, StreamErrorAction};
+pub use stream::raw::{Buffer, StreamError, StreamErrorAction, StreamWarning};
+pub use stream::thermal::ThermalGuard;
 
 use std::io;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// How long to wait for responses to each scan while waiting for a specific IDN DAC.
+const IDN_SCAN_INTERVAL: Duration = Duration::from_secs(1);
 
 /// A general API that allows for detecting and enumerating laser DACs on a network and
 /// establishing new streams of communication with them.
@@ -32,20 +44,44 @@ pub struct Api {
 //
 // This is useful for allowing streams to re-scan and find their associated DAC in the case it
 // drops out for some reason.
-pub(crate) struct Inner;
+pub(crate) struct Inner {
+    // The per-DAC configuration applied to streams as they connect, if any.
+    config_store: Mutex<Option<Arc<ConfigStore>>>,
+}
 
 impl Api {
     /// Instantiate the laser API.
     pub fn new() -> Self {
         Api {
-            inner: Arc::new(Inner),
+            inner: Arc::new(Inner {
+                config_store: Mutex::new(None),
+            }),
         }
     }
 
+    /// Set the per-DAC configuration applied to streams as they connect.
+    ///
+    /// Each time a stream connects to a DAC, the configuration associated with the DAC's `Id` is
+    /// looked up within the store. Its colour calibration, geometric correction and safety zones
+    /// are applied to all points before they are submitted, and its latency settings are applied
+    /// unless they were specified explicitly when building the stream.
+    ///
+    /// Changes only take effect for streams the next time they connect.
+    ///
+    /// By default, no store is set and points are submitted unchanged.
+    pub fn set_config_store(&self, store: Option<Arc<ConfigStore>>) {
+        *self.inner.lock_config_store() = store;
+    }
+
+    /// The per-DAC configuration applied to streams as they connect, if any.
+    pub fn config_store(&self) -> Option<Arc<ConfigStore>> {
+        self.inner.lock_config_store().clone()
+    }
+
     /// An iterator yielding laser DACs available on the system as they are discovered.
     ///
-    /// Currently, the only laser protocol supported is the ether dream protocol. Thus, this
-    /// enumerates ether dream DACs that are discovered on the LAN.
+    /// This enumerates ether dream DACs that are discovered on the LAN via their broadcasts. IDN
+    /// DACs do not broadcast and must instead be found via `detect_idn_dacs`.
     ///
     /// **Note** that the produced iterator will iterate forever and never terminate unless
     /// `set_timeout` is called on the returned `DetectDacs` instance.
@@ -53,9 +89,18 @@ impl Api {
         self.inner.detect_dacs()
     }
 
+    /// Scan the LAN for DACs that speak the ILDA Digital Network (IDN) protocol.
+    ///
+    /// Unlike ether dream DACs, IDN devices do not advertise themselves and must be queried.
+    /// This broadcasts a single scan request and collects all devices that respond within the
+    /// given `timeout`.
+    pub fn detect_idn_dacs(&self, timeout: Duration) -> io::Result<Vec<DetectedDac>> {
+        self.inner.detect_idn_dacs(timeout)
+    }
+
     /// Block and wait until the DAC with the given `Id` is detected.
     pub fn detect_dac(&self, id: DacId) -> io::Result<DetectedDac> {
-        self.inner.detect_dac(id)
+        self.inner.detect_dac(id, None)
     }
 
     /// Spawn a thread for DAC detection.
@@ -87,10 +132,16 @@ impl Api {
         let builder = Default::default();
         let frame_hz = None;
         let interpolation_conf = Default::default();
+        let auto_dwell = None;
+        let occlusion = stream::frame::Occlusion::Disabled;
         let enable_optimisations = stream::DEFAULT_ENABLE_OPTIMISATIONS;
         let enable_draw_reorder = stream::DEFAULT_ENABLE_DRAW_REORDER;
+        let clock = None;
+        let audio_sync_offset = None;
+        let point_fn = None;
         let process_raw = stream::frame::default_process_raw_fn;
         let stream_error = stream::raw::default_stream_error_fn;
+        let stream_warning = stream::raw::default_stream_warning_fn;
         stream::frame::Builder {
             api_inner,
             builder,
@@ -98,13 +149,26 @@ impl Api {
             render,
             process_raw,
             stream_error,
+            stream_warning,
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
         }
     }
 
+    /// Begin building a player for headless playback of the given show.
+    ///
+    /// The player drives its own frame stream, so no render function is required.
+    pub fn new_show_player(&self, show: show::Show) -> show::Builder {
+        show::Builder::new(self.inner.clone(), show)
+    }
+
     /// Begin building a new laser raw stream.
     ///
     /// The raw stream will call the given `render` function with a request for as many points as
@@ -116,12 +180,14 @@ impl Api {
         let api_inner = self.inner.clone();
         let builder = Default::default();
         let stream_error = stream::raw::default_stream_error_fn;
+        let stream_warning = stream::raw::default_stream_warning_fn;
         stream::raw::Builder {
             api_inner,
             builder,
             model,
             render,
             stream_error,
+            stream_warning,
         }
     }
 }
@@ -133,14 +199,54 @@ impl Inner {
     }
 
     /// Block and wait until the DAC with the given `Id` is detected.
-    pub(crate) fn detect_dac(&self, id: DacId) -> io::Result<DetectedDac> {
-        for res in self.detect_dacs()? {
-            let dac = res?;
+    ///
+    /// If a `timeout` is given, returns an error of kind `TimedOut` if the DAC is not detected
+    /// before it elapses.
+    pub(crate) fn detect_dac(
+        &self,
+        id: DacId,
+        timeout: Option<Duration>,
+    ) -> io::Result<DetectedDac> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let remaining = || match deadline {
+            None => Ok(None),
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if remaining > Duration::from_secs(0) => Ok(Some(remaining)),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out while waiting for the DAC to be detected",
+                )),
+            },
+        };
+        if let DacId::Idn { .. } = id {
+            loop {
+                let scan_timeout = match remaining()? {
+                    Some(remaining) => remaining.min(IDN_SCAN_INTERVAL),
+                    None => IDN_SCAN_INTERVAL,
+                };
+                let dacs = self.detect_idn_dacs(scan_timeout)?;
+                if let Some(dac) = dacs.into_iter().find(|dac| dac.id() == id) {
+                    return Ok(dac);
+                }
+            }
+        }
+        let mut dacs = self.detect_dacs()?;
+        loop {
+            // Shrink the timeout as broadcasts from other DACs arrive to honour the deadline.
+            dacs.set_timeout(remaining()?)?;
+            let dac = match dacs.next() {
+                Some(res) => res?,
+                None => unreachable!("DAC detection iterator should never return `None`"),
+            };
             if dac.id() == id {
                 return Ok(dac);
             }
         }
-        unreachable!("DAC detection iterator should never return `None`")
+    }
+
+    /// See the `Api::detect_idn_dacs` docs.
+    pub(crate) fn detect_idn_dacs(&self, timeout: Duration) -> io::Result<Vec<DetectedDac>> {
+        dac::detect_idn_dacs(timeout)
     }
 
     /// See the `Api::detect_dacs_async` docs.
@@ -154,6 +260,20 @@ impl Inner {
     {
         dac::detect_dacs_async(timeout, callback)
     }
+
+    /// The configuration for the DAC with the given `Id` within the config store, if any.
+    pub(crate) fn dac_config(&self, id: &DacId) -> Option<DacConfig> {
+        self.lock_config_store()
+            .as_ref()
+            .and_then(|store| store.get(id).cloned())
+    }
+
+    fn lock_config_store(&self) -> std::sync::MutexGuard<Option<Arc<ConfigStore>>> {
+        match self.config_store.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
 }
 
 impl AsRef<Point> for Point {
@@ -0,0 +1,324 @@
+//! Interpolation between two frames of paths for smoothly morphing between laser patterns.
+//!
+//! Each path of the previous frame is matched with a path of the next frame. Both paths are
+//! resampled to the same number of points by arc length and then their positions, colours and
+//! weights are interpolated. Paths that have no match fade out towards (or in from) their
+//! centroid.
+
+use crate::Point;
+
+/// Describes how paths of the previous frame are paired with paths of the next frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PathMatching {
+    /// The path at each index is paired with the path at the same index of the other frame.
+    Index,
+    /// Each path, in order, is paired with the unmatched path of the other frame whose centroid is
+    /// nearest to its own.
+    NearestCentroid,
+}
+
+impl Default for PathMatching {
+    fn default() -> Self {
+        PathMatching::Index
+    }
+}
+
+/// Interpolate between the paths of two frames.
+///
+/// `t` is clamped to the range `0.0..=1.0` where `0.0` produces the `prev` paths and `1.0`
+/// produces the `next` paths.
+///
+/// Returns one path for each matched pair, followed by the unmatched paths.
+pub fn morph<A, B>(prev: &[A], next: &[B], t: f32, matching: PathMatching) -> Vec<Vec<Point>>
+where
+    A: AsRef<[Point]>,
+    B: AsRef<[Point]>,
+{
+    let t = crate::util::clamp(t, 0.0, 1.0);
+    let prev: Vec<&[Point]> = prev.iter().map(|p| p.as_ref()).collect();
+    let next: Vec<&[Point]> = next.iter().map(|p| p.as_ref()).collect();
+    let pairs = match_paths(&prev, &next, matching);
+    let mut paths = Vec::with_capacity(pairs.len());
+    for pair in pairs {
+        let path = match pair {
+            (Some(a), Some(b)) => morph_path(prev[a], next[b], t),
+            (Some(a), None) => {
+                let b = collapsed(prev[a]);
+                morph_path(prev[a], &b, t)
+            }
+            (None, Some(b)) => {
+                let a = collapsed(next[b]);
+                morph_path(&a, next[b], t)
+            }
+            (None, None) => continue,
+        };
+        paths.push(path);
+    }
+    paths
+}
+
+/// Interpolate between two paths.
+///
+/// Both paths are resampled by arc length to the greater of their two point counts before their
+/// points are interpolated.
+pub fn morph_path(a: &[Point], b: &[Point], t: f32) -> Vec<Point> {
+    if a.is_empty() {
+        return b.to_vec();
+    }
+    if b.is_empty() {
+        return a.to_vec();
+    }
+    let n = std::cmp::max(a.len(), b.len());
+    let a = resample(a, n);
+    let b = resample(b, n);
+    a.iter().zip(&b).map(|(a, b)| lerp_point(a, b, t)).collect()
+}
+
+/// The mean position of all points in the path.
+pub fn centroid(path: &[Point]) -> [f32; 2] {
+    if path.is_empty() {
+        return [0.0, 0.0];
+    }
+    let [x, y] = path.iter().fold([0.0, 0.0], |[x, y], p| {
+        [x + p.position[0], y + p.position[1]]
+    });
+    let n = path.len() as f32;
+    [x / n, y / n]
+}
+
+// Pair the indices of the paths of each frame.
+fn match_paths(
+    prev: &[&[Point]],
+    next: &[&[Point]],
+    matching: PathMatching,
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut pairs = vec![];
+    match matching {
+        PathMatching::Index => {
+            for i in 0..std::cmp::max(prev.len(), next.len()) {
+                let a = if i < prev.len() { Some(i) } else { None };
+                let b = if i < next.len() { Some(i) } else { None };
+                pairs.push((a, b));
+            }
+        }
+        PathMatching::NearestCentroid => {
+            let next_centroids: Vec<_> = next.iter().map(|p| centroid(p)).collect();
+            let mut matched = vec![false; next.len()];
+            for (i, path) in prev.iter().enumerate() {
+                let [x, y] = centroid(path);
+                let nearest = next_centroids
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| !matched[j])
+                    .map(|(j, &[nx, ny])| (j, (nx - x).powi(2) + (ny - y).powi(2)))
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).expect("centroid distance was NaN"))
+                    .map(|(j, _)| j);
+                if let Some(j) = nearest {
+                    matched[j] = true;
+                }
+                pairs.push((Some(i), nearest));
+            }
+            for (j, _) in matched.iter().enumerate().filter(|&(_, &m)| !m) {
+                pairs.push((None, Some(j)));
+            }
+        }
+    }
+    pairs
+}
+
+// A blank path at the centroid of the given path with the same number of points.
+fn collapsed(path: &[Point]) -> Vec<Point> {
+    let position = centroid(path);
+    path.iter()
+        .map(|p| Point::with_weight(position, [0.0; 3], p.weight))
+        .collect()
+}
+
+// Resample the path to `n` points spaced evenly along its length.
+fn resample(path: &[Point], n: usize) -> Vec<Point> {
+    if path.len() == n {
+        return path.to_vec();
+    }
+    if path.len() == 1 || n == 1 {
+        return vec![path[0]; n];
+    }
+
+    // The cumulative distance along the path at each point.
+    let mut distances = Vec::with_capacity(path.len());
+    let mut total = 0.0;
+    distances.push(total);
+    for w in path.windows(2) {
+        let [ax, ay] = w[0].position;
+        let [bx, by] = w[1].position;
+        total += ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt();
+        distances.push(total);
+    }
+
+    // If the path has no length, resample by index instead.
+    if total == 0.0 {
+        return (0..n)
+            .map(|i| path[i * (path.len() - 1) / (n - 1)])
+            .collect();
+    }
+
+    let mut seg = 0;
+    (0..n)
+        .map(|i| {
+            let d = total * i as f32 / (n - 1) as f32;
+            while seg < path.len() - 2 && distances[seg + 1] < d {
+                seg += 1;
+            }
+            let len = distances[seg + 1] - distances[seg];
+            let t = if len > 0.0 {
+                (d - distances[seg]) / len
+            } else {
+                0.0
+            };
+            lerp_point(&path[seg], &path[seg + 1], t)
+        })
+        .collect()
+}
+
+fn lerp_point(a: &Point, b: &Point, t: f32) -> Point {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    let position = [
+        lerp(a.position[0], b.position[0]),
+        lerp(a.position[1], b.position[1]),
+    ];
+    let color = [
+        lerp(a.color[0], b.color[0]),
+        lerp(a.color[1], b.color[1]),
+        lerp(a.color[2], b.color[2]),
+    ];
+    let weight = lerp(a.weight as f32, b.weight as f32).round() as u32;
+    Point::with_weight(position, color, weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(from: [f32; 2], to: [f32; 2], color: [f32; 3]) -> Vec<Point> {
+        vec![Point::new(from, color), Point::new(to, color)]
+    }
+
+    fn positions(path: &[Point]) -> Vec<[f32; 2]> {
+        path.iter().map(|p| p.position).collect()
+    }
+
+    #[test]
+    fn endpoints_produce_each_frame() {
+        let prev = [line([-1.0, 0.0], [1.0, 0.0], [1.0; 3])];
+        let next = [line([0.0, -1.0], [0.0, 1.0], [0.5; 3])];
+        let m = PathMatching::Index;
+        assert_eq!(morph(&prev, &next, 0.0, m), vec![prev[0].clone()]);
+        assert_eq!(morph(&prev, &next, 1.0, m), vec![next[0].clone()]);
+        assert_eq!(morph(&prev, &next, 2.0, m), vec![next[0].clone()]);
+        assert_eq!(morph(&prev, &next, -1.0, m), vec![prev[0].clone()]);
+    }
+
+    #[test]
+    fn interpolates_points() {
+        let a = line([-1.0, 0.0], [1.0, 0.0], [1.0; 3]);
+        let b = line([0.0, -1.0], [0.0, 1.0], [0.0; 3]);
+        let path = morph_path(&a, &b, 0.5);
+        assert_eq!(positions(&path), vec![[-0.5, -0.5], [0.5, 0.5]]);
+        assert!(path.iter().all(|p| p.color == [0.5; 3]));
+    }
+
+    #[test]
+    fn interpolates_weight() {
+        let a = [Point::with_weight([0.0; 2], [1.0; 3], 0)];
+        let b = [Point::with_weight([0.0; 2], [1.0; 3], 4)];
+        assert_eq!(morph_path(&a, &b, 0.25)[0].weight, 1);
+        assert_eq!(morph_path(&a, &b, 0.75)[0].weight, 3);
+    }
+
+    #[test]
+    fn resamples_to_longest_path() {
+        let a = line([-1.0, 0.0], [1.0, 0.0], [1.0; 3]);
+        let mut b = line([0.0, 0.0], [0.5, 0.0], [1.0; 3]);
+        b.push(Point::new([1.0, 0.0], [1.0; 3]));
+        let path = morph_path(&a, &b, 0.0);
+        assert_eq!(positions(&path), vec![[-1.0, 0.0], [0.0, 0.0], [1.0, 0.0]]);
+    }
+
+    #[test]
+    fn resamples_by_arc_length() {
+        let path = [
+            Point::new([0.0, 0.0], [1.0; 3]),
+            Point::new([1.0, 0.0], [1.0; 3]),
+            Point::new([1.0, 1.0], [1.0; 3]),
+        ];
+        let expected = vec![[0.0, 0.0], [0.5, 0.0], [1.0, 0.0], [1.0, 0.5], [1.0, 1.0]];
+        assert_eq!(positions(&resample(&path, 5)), expected);
+    }
+
+    #[test]
+    fn resamples_zero_length_path_by_index() {
+        let path = [Point::new([0.5, 0.5], [1.0; 3]); 2];
+        let resampled = resample(&path, 4);
+        assert_eq!(resampled.len(), 4);
+        assert!(resampled.iter().all(|p| p.position == [0.5, 0.5]));
+    }
+
+    #[test]
+    fn empty_paths_yield_other_path() {
+        let a = line([-1.0, 0.0], [1.0, 0.0], [1.0; 3]);
+        assert_eq!(morph_path(&a, &[], 0.5), a);
+        assert_eq!(morph_path(&[], &a, 0.5), a);
+    }
+
+    #[test]
+    fn unmatched_paths_fade_to_centroid() {
+        let prev = [
+            line([-1.0, 0.0], [1.0, 0.0], [1.0; 3]),
+            line([0.0, 0.5], [1.0, 0.5], [1.0; 3]),
+        ];
+        let next = [line([-1.0, 0.0], [1.0, 0.0], [1.0; 3])];
+        let paths = morph(&prev, &next, 1.0, PathMatching::Index);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(positions(&paths[1]), vec![[0.5, 0.5]; 2]);
+        assert!(paths[1].iter().all(|p| p.color == [0.0; 3]));
+    }
+
+    #[test]
+    fn unmatched_paths_fade_in_from_centroid() {
+        let prev: [Vec<Point>; 0] = [];
+        let next = [line([0.0, 0.5], [1.0, 0.5], [1.0; 3])];
+        let paths = morph(&prev, &next, 0.0, PathMatching::Index);
+        assert_eq!(positions(&paths[0]), vec![[0.5, 0.5]; 2]);
+        assert!(paths[0].iter().all(|p| p.color == [0.0; 3]));
+    }
+
+    #[test]
+    fn nearest_centroid_matching() {
+        let left = line([-1.0, 0.0], [-0.5, 0.0], [1.0; 3]);
+        let right = line([0.5, 0.0], [1.0, 0.0], [1.0; 3]);
+        let prev = [left.clone(), right.clone()];
+        let next = [right, left];
+        let pairs = match_paths(
+            &[&prev[0][..], &prev[1][..]],
+            &[&next[0][..], &next[1][..]],
+            PathMatching::NearestCentroid,
+        );
+        assert_eq!(pairs, vec![(Some(0), Some(1)), (Some(1), Some(0))]);
+        let paths = morph(&prev, &next, 0.5, PathMatching::NearestCentroid);
+        assert_eq!(paths, vec![prev[0].clone(), prev[1].clone()]);
+    }
+
+    #[test]
+    fn nearest_centroid_leaves_extra_paths_unmatched() {
+        let a = line([0.0, 0.0], [0.5, 0.0], [1.0; 3]);
+        let b = line([0.0, 0.5], [0.5, 0.5], [1.0; 3]);
+        let pairs = match_paths(&[&a[..]], &[&b[..], &a[..]], PathMatching::NearestCentroid);
+        assert_eq!(pairs, vec![(Some(0), Some(1)), (None, Some(0))]);
+    }
+
+    #[test]
+    fn centroid_is_mean_position() {
+        assert_eq!(centroid(&[]), [0.0, 0.0]);
+        let path = line([-1.0, 0.5], [0.0, 1.5], [1.0; 3]);
+        assert_eq!(centroid(&path), [-0.5, 1.0]);
+    }
+}
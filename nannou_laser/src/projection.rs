@@ -0,0 +1,456 @@
+//! Perspective projection of 3D points for authoring volumetric laser content.
+//!
+//! Points are transformed into camera space by a `Camera`'s view matrix and projected onto the
+//! laser's `-1.0..=1.0` coordinate space. Segments crossing the camera's near plane are clipped at
+//! the plane, splitting a path wherever it passes behind the camera, and the colour of each point
+//! may be attenuated by its depth so that distant lines appear dimmer.
+//!
+//! Projection happens as points are added to a `Frame` via `add_points_3d` and `add_lines_3d`,
+//! before any of the stream's path optimisation is applied.
+
+use crate::point::{Position, Rgb};
+use crate::Point;
+
+/// A position in 3D space represented by x, y and z coordinates.
+pub type Position3 = [f32; 3];
+
+/// A column-major 4x4 matrix, e.g. as produced by `glam::Mat4::to_cols_array_2d`.
+pub type Mat4 = [[f32; 4]; 4];
+
+/// A point in 3D space to be projected by a `Camera`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Point3 {
+    /// The position of the point in world space.
+    pub position: Position3,
+    /// Red, green and blue channels of the point's colour.
+    pub color: Rgb,
+    /// The number of times the projected point should be drawn. See `Point::weight`.
+    pub weight: u32,
+}
+
+/// Describes how the intensity of points falls off with their distance from the camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepthAttenuation {
+    /// The depth at and before which points are drawn at full intensity.
+    pub start: f32,
+    /// The depth at and beyond which points are drawn at `min_intensity`.
+    pub end: f32,
+    /// The intensity multiplier applied to the colour of points at and beyond `end`.
+    pub min_intensity: f32,
+}
+
+/// A perspective camera used to project 3D points onto the laser's 2D coordinate space.
+///
+/// The camera follows the right-handed convention where, in camera space, `+x` is right, `+y` is
+/// up and the camera looks towards `-z`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    /// Transforms points from world space into camera space.
+    pub view: Mat4,
+    /// The vertical field of view in radians.
+    pub fov_y: f32,
+    /// The ratio of the horizontal to the vertical extent of the projection.
+    pub aspect: f32,
+    /// The distance from the camera to the near clipping plane. Geometry closer than this is
+    /// clipped.
+    pub near: f32,
+    /// The optional attenuation of colour by depth.
+    pub depth_attenuation: Option<DepthAttenuation>,
+}
+
+// A point transformed into camera space along with its depth in front of the camera.
+#[derive(Copy, Clone, Debug)]
+struct ViewPoint {
+    position: Position3,
+    depth: f32,
+    color: Rgb,
+    weight: u32,
+}
+
+impl Point3 {
+    /// Create a **Point3** at the given position with the given colour with a default weight.
+    pub fn new(position: Position3, color: Rgb) -> Self {
+        Self::with_weight(position, color, Point::DEFAULT_LINE_POINT_WEIGHT)
+    }
+
+    /// The same as `Point3::new` but allows for specifying the weight of the point.
+    pub fn with_weight(position: Position3, color: Rgb, weight: u32) -> Self {
+        Point3 {
+            position,
+            color,
+            weight,
+        }
+    }
+
+    /// Returns a point with the same position as `self` but with a black (blank) color.
+    pub fn blanked(&self) -> Self {
+        let mut blanked = *self;
+        blanked.color = [0.0, 0.0, 0.0];
+        blanked
+    }
+}
+
+impl AsRef<Point3> for Point3 {
+    fn as_ref(&self) -> &Point3 {
+        self
+    }
+}
+
+impl DepthAttenuation {
+    /// Attenuate linearly from full intensity at `start` to `min_intensity` at `end`.
+    pub fn linear(start: f32, end: f32, min_intensity: f32) -> Self {
+        DepthAttenuation {
+            start,
+            end,
+            min_intensity,
+        }
+    }
+
+    /// The intensity multiplier for a point at the given depth in front of the camera.
+    pub fn intensity(&self, depth: f32) -> f32 {
+        let min = crate::util::clamp(self.min_intensity, 0.0, 1.0);
+        if self.end <= self.start {
+            return if depth < self.start { 1.0 } else { min };
+        }
+        let t = crate::util::clamp((depth - self.start) / (self.end - self.start), 0.0, 1.0);
+        1.0 + (min - 1.0) * t
+    }
+}
+
+impl Camera {
+    /// The vertical field of view used by default, 90 degrees.
+    pub const DEFAULT_FOV_Y: f32 = std::f32::consts::FRAC_PI_2;
+    /// The distance to the near clipping plane used by default.
+    pub const DEFAULT_NEAR: f32 = 0.01;
+    /// The identity matrix, placing the camera at the origin looking towards `-z`.
+    pub const IDENTITY: Mat4 = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    /// Create a camera with the given world-to-camera view transform.
+    pub fn new(view: Mat4) -> Self {
+        Camera {
+            view,
+            fov_y: Self::DEFAULT_FOV_Y,
+            aspect: 1.0,
+            near: Self::DEFAULT_NEAR,
+            depth_attenuation: None,
+        }
+    }
+
+    /// Create a camera at `eye` looking towards `target` with the given `up` direction.
+    pub fn look_at(eye: Position3, target: Position3, up: Position3) -> Self {
+        let f = normalize(sub(target, eye));
+        let s = normalize(cross(f, up));
+        let u = cross(s, f);
+        let view = [
+            [s[0], u[0], -f[0], 0.0],
+            [s[1], u[1], -f[1], 0.0],
+            [s[2], u[2], -f[2], 0.0],
+            [-dot(s, eye), -dot(u, eye), dot(f, eye), 1.0],
+        ];
+        Self::new(view)
+    }
+
+    /// The vertical field of view in radians.
+    ///
+    /// By default, this value is `Camera::DEFAULT_FOV_Y`.
+    pub fn fov_y(mut self, fov_y: f32) -> Self {
+        self.fov_y = fov_y;
+        self
+    }
+
+    /// The ratio of the horizontal to the vertical extent of the projection.
+    ///
+    /// By default, this value is `1.0`, matching the square coordinate space of the laser.
+    pub fn aspect(mut self, aspect: f32) -> Self {
+        self.aspect = aspect;
+        self
+    }
+
+    /// The distance from the camera to the near clipping plane.
+    ///
+    /// By default, this value is `Camera::DEFAULT_NEAR`.
+    pub fn near(mut self, near: f32) -> Self {
+        self.near = near;
+        self
+    }
+
+    /// Attenuate the colour of points by their depth in front of the camera.
+    ///
+    /// By default, no attenuation is applied.
+    pub fn depth_attenuation(mut self, attenuation: DepthAttenuation) -> Self {
+        self.depth_attenuation = Some(attenuation);
+        self
+    }
+
+    /// Transform the given world space position into camera space.
+    pub fn to_camera_space(&self, p: Position3) -> Position3 {
+        let m = &self.view;
+        let mut out = [0.0; 3];
+        for (i, o) in out.iter_mut().enumerate() {
+            *o = m[0][i] * p[0] + m[1][i] * p[1] + m[2][i] * p[2] + m[3][i];
+        }
+        out
+    }
+
+    /// Project the given world space position onto the laser's 2D coordinate space.
+    ///
+    /// Returns `None` if the position is closer to the camera than the near plane.
+    pub fn project_position(&self, p: Position3) -> Option<Position> {
+        let v = self.to_camera_space(p);
+        let depth = -v[2];
+        if depth < self.near {
+            return None;
+        }
+        Some(self.project_view(v, depth))
+    }
+
+    /// Project the given point, attenuating its colour by depth.
+    ///
+    /// Returns `None` if the point is closer to the camera than the near plane.
+    pub fn project_point(&self, p: &Point3) -> Option<Point> {
+        let v = self.view_point(p);
+        if v.depth < self.near {
+            return None;
+        }
+        Some(self.project_view_point(&v))
+    }
+
+    /// Project a sequence of consecutive lines, clipping segments at the near plane.
+    ///
+    /// Returns one path for each run of the sequence that is visible to the camera. A new path
+    /// begins each time the sequence re-emerges from behind the near plane.
+    pub fn project_lines<I>(&self, points: I) -> Vec<Vec<Point>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Point3>,
+    {
+        let mut paths = vec![];
+        let mut path = vec![];
+        let mut prev: Option<ViewPoint> = None;
+        for p in points {
+            let v = self.view_point(p.as_ref());
+            let visible = v.depth >= self.near;
+            match prev {
+                None if visible => path.push(self.project_view_point(&v)),
+                None => (),
+                Some(a) => match (a.depth >= self.near, visible) {
+                    (true, true) => path.push(self.project_view_point(&v)),
+                    (true, false) => {
+                        path.push(self.project_view_point(&self.clip(&a, &v)));
+                        paths.push(std::mem::replace(&mut path, vec![]));
+                    }
+                    (false, true) => {
+                        path.push(self.project_view_point(&self.clip(&a, &v)));
+                        path.push(self.project_view_point(&v));
+                    }
+                    (false, false) => (),
+                },
+            }
+            prev = Some(v);
+        }
+        if !path.is_empty() {
+            paths.push(path);
+        }
+        paths
+    }
+
+    // Transform the point into camera space.
+    fn view_point(&self, p: &Point3) -> ViewPoint {
+        let position = self.to_camera_space(p.position);
+        ViewPoint {
+            position,
+            depth: -position[2],
+            color: p.color,
+            weight: p.weight,
+        }
+    }
+
+    // The point at which the segment `a` -> `b` crosses the near plane.
+    fn clip(&self, a: &ViewPoint, b: &ViewPoint) -> ViewPoint {
+        let t = (self.near - a.depth) / (b.depth - a.depth);
+        let lerp = |a: f32, b: f32| a + (b - a) * t;
+        let pa = a.position;
+        let pb = b.position;
+        let ca = a.color;
+        let cb = b.color;
+        ViewPoint {
+            position: [lerp(pa[0], pb[0]), lerp(pa[1], pb[1]), lerp(pa[2], pb[2])],
+            depth: self.near,
+            color: [lerp(ca[0], cb[0]), lerp(ca[1], cb[1]), lerp(ca[2], cb[2])],
+            weight: 0,
+        }
+    }
+
+    // Project a camera space position with the given depth.
+    fn project_view(&self, v: Position3, depth: f32) -> Position {
+        let f = 1.0 / (self.fov_y * 0.5).tan();
+        [v[0] * f / (self.aspect * depth), v[1] * f / depth]
+    }
+
+    // Project a visible camera space point, attenuating its colour.
+    fn project_view_point(&self, v: &ViewPoint) -> Point {
+        let position = self.project_view(v.position, v.depth);
+        let intensity = self
+            .depth_attenuation
+            .map(|a| a.intensity(v.depth))
+            .unwrap_or(1.0);
+        let [r, g, b] = v.color;
+        let color = [r * intensity, g * intensity, b * intensity];
+        Point::with_weight(position, color, v.weight)
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(Self::IDENTITY)
+    }
+}
+
+fn sub(a: Position3, b: Position3) -> Position3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: Position3, b: Position3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Position3, b: Position3) -> Position3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: Position3) -> Position3 {
+    let len = dot(a, a).sqrt();
+    if len == 0.0 {
+        return a;
+    }
+    [a[0] / len, a[1] / len, a[2] / len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+    }
+
+    #[test]
+    fn projects_by_depth() {
+        let camera = Camera::default();
+        let p = camera.project_position([0.5, 0.25, -1.0]).unwrap();
+        assert!(approx_eq(&p, &[0.5, 0.25]));
+        let p = camera.project_position([1.0, 1.0, -2.0]).unwrap();
+        assert!(approx_eq(&p, &[0.5, 0.5]));
+    }
+
+    #[test]
+    fn field_of_view_and_aspect_scale_projection() {
+        let camera = Camera::default().fov_y(2.0 * (0.5f32).atan()).aspect(2.0);
+        let p = camera.project_position([0.5, 0.5, -1.0]).unwrap();
+        assert!(approx_eq(&p, &[0.5, 1.0]));
+    }
+
+    #[test]
+    fn positions_before_near_plane_are_not_projected() {
+        let camera = Camera::default().near(0.5);
+        assert_eq!(camera.project_position([0.0, 0.0, 1.0]), None);
+        assert_eq!(camera.project_position([0.0, 0.0, -0.25]), None);
+        assert!(camera.project_position([0.0, 0.0, -0.5]).is_some());
+        assert_eq!(camera.project_point(&Point3::new([0.0; 3], [1.0; 3])), None);
+    }
+
+    #[test]
+    fn look_at_transforms_into_camera_space() {
+        let camera = Camera::look_at([0.0, 0.0, 5.0], [0.0; 3], [0.0, 1.0, 0.0]);
+        assert!(approx_eq(
+            &camera.to_camera_space([0.0; 3]),
+            &[0.0, 0.0, -5.0]
+        ));
+        let camera = Camera::look_at([5.0, 0.0, 0.0], [0.0; 3], [0.0, 1.0, 0.0]);
+        let v = camera.to_camera_space([0.0, 1.0, -1.0]);
+        assert!(approx_eq(&v, &[1.0, 1.0, -5.0]));
+    }
+
+    #[test]
+    fn linear_attenuation() {
+        let a = DepthAttenuation::linear(1.0, 3.0, 0.25);
+        assert_eq!(a.intensity(0.5), 1.0);
+        assert_eq!(a.intensity(1.0), 1.0);
+        assert_eq!(a.intensity(2.0), 0.625);
+        assert_eq!(a.intensity(3.0), 0.25);
+        assert_eq!(a.intensity(5.0), 0.25);
+    }
+
+    #[test]
+    fn degenerate_attenuation_steps_at_start() {
+        let a = DepthAttenuation::linear(2.0, 2.0, -1.0);
+        assert_eq!(a.intensity(1.0), 1.0);
+        assert_eq!(a.intensity(2.0), 0.0);
+    }
+
+    #[test]
+    fn project_point_attenuates_colour() {
+        let attenuation = DepthAttenuation::linear(1.0, 3.0, 0.0);
+        let camera = Camera::default().depth_attenuation(attenuation);
+        let p = Point3::with_weight([0.0, 0.0, -2.0], [1.0, 0.5, 0.0], 3);
+        let p = camera.project_point(&p).unwrap();
+        assert!(approx_eq(&p.color, &[0.5, 0.25, 0.0]));
+        assert_eq!(p.weight, 3);
+    }
+
+    #[test]
+    fn visible_lines_form_one_path() {
+        let camera = Camera::default();
+        let points = [
+            Point3::new([-1.0, 0.0, -1.0], [1.0; 3]),
+            Point3::new([1.0, 0.0, -1.0], [1.0; 3]),
+            Point3::new([1.0, 1.0, -2.0], [1.0; 3]),
+        ];
+        let paths = camera.project_lines(&points);
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].len(), 3);
+        assert!(approx_eq(&paths[0][2].position, &[0.5, 0.5]));
+    }
+
+    #[test]
+    fn lines_behind_camera_are_clipped_and_split() {
+        let camera = Camera::default();
+        let points = [
+            Point3::with_weight([0.0, 0.0, -1.0], [1.0; 3], 2),
+            Point3::new([0.0, 0.0, 1.0], [0.0; 3]),
+            Point3::with_weight([0.0, 0.0, -1.0], [1.0; 3], 2),
+        ];
+        let paths = camera.project_lines(&points);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].len(), 2);
+        assert_eq!(paths[1].len(), 2);
+        // The clipped points lie on the near plane with their colour interpolated.
+        let t = (1.0 - Camera::DEFAULT_NEAR) / 2.0;
+        let clipped = [paths[0][1], paths[1][0]];
+        for p in clipped.iter() {
+            assert!(approx_eq(&p.color, &[1.0 - t; 3]));
+            assert_eq!(p.weight, 0);
+        }
+        assert_eq!(paths[0][0].weight, 2);
+        assert_eq!(paths[1][1].weight, 2);
+    }
+
+    #[test]
+    fn hidden_lines_produce_no_paths() {
+        let camera = Camera::default();
+        let points = [
+            Point3::new([0.0, 0.0, 1.0], [1.0; 3]),
+            Point3::new([1.0, 0.0, 2.0], [1.0; 3]),
+        ];
+        assert!(camera.project_lines(&points).is_empty());
+    }
+}
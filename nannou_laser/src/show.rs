@@ -0,0 +1,481 @@
+//! Headless playback of pre-recorded laser shows.
+//!
+//! A **Show** is a timed sequence of frames. Shows may be assembled in code, or loaded from ILDA
+//! files and show playlists when the `ilda-idtf` feature is enabled.
+//!
+//! A **Player** plays a show to a DAC on the laser stream's own thread, requiring no window, event
+//! loop or render callback. This makes it suitable for fixed installations that simply run a show
+//! from the command line.
+
+use crate::stream::{self, frame::Frame};
+use crate::Point;
+use std::io;
+use std::sync::atomic::{self, AtomicBool, AtomicU64};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+/// A timed sequence of frames.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Show {
+    frames: Vec<ShowFrame>,
+    // The start time of each frame within the show.
+    starts: Vec<Duration>,
+    duration: Duration,
+}
+
+/// A single frame of a show.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShowFrame {
+    /// The points of the frame, representing consecutive lines.
+    pub points: Vec<Point>,
+    /// How long the frame is displayed for.
+    pub duration: Duration,
+}
+
+/// Plays a **Show** to a laser DAC.
+///
+/// Playback is driven by the laser stream thread, advancing by one frame period each time the
+/// stream requests a new frame. The stream is closed when the player is dropped.
+pub struct Player {
+    stream: stream::frame::Stream<Playback>,
+    status: Arc<Status>,
+    duration: Duration,
+}
+
+/// A builder for a show **Player**.
+pub struct Builder {
+    api_inner: Arc<crate::Inner>,
+    show: Show,
+    dac: Option<crate::DetectedDac>,
+    point_hz: Option<u32>,
+    frame_hz: Option<u32>,
+    looping: bool,
+    playing: bool,
+}
+
+// The state of playback living on the laser thread.
+struct Playback {
+    show: Show,
+    position: Duration,
+    looping: bool,
+    status: Arc<Status>,
+}
+
+// Playback status shared with the `Player` handle.
+struct Status {
+    // The playback position in nanoseconds.
+    position_nanos: AtomicU64,
+    playing: AtomicBool,
+}
+
+impl Show {
+    /// Create an empty show.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a frame consisting of the given points, displayed for the given duration.
+    ///
+    /// The points are treated as consecutive lines, as with `Frame::add_lines`.
+    pub fn push_frame<I>(&mut self, points: I, duration: Duration)
+    where
+        I: IntoIterator,
+        I::Item: Into<Point>,
+    {
+        let points = points.into_iter().map(Into::into).collect();
+        self.starts.push(self.duration);
+        self.duration += duration;
+        self.frames.push(ShowFrame { points, duration });
+    }
+
+    /// Append all frames of the given show.
+    pub fn extend(&mut self, show: Show) {
+        for frame in show.frames {
+            self.push_frame(frame.points, frame.duration);
+        }
+    }
+
+    /// All frames of the show in order.
+    pub fn frames(&self) -> &[ShowFrame] {
+        &self.frames
+    }
+
+    /// The total duration of the show.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// The frame displayed at the given position within the show.
+    ///
+    /// A frame is displayed from its start up to but excluding the start of the next frame. Frames
+    /// with a zero duration are never displayed, as they share their start with the frame that
+    /// follows them.
+    ///
+    /// Returns `None` if the position is beyond the end of the show.
+    pub fn frame_at(&self, position: Duration) -> Option<&ShowFrame> {
+        if position >= self.duration {
+            return None;
+        }
+        // The last frame starting at or before the position, skipping zero duration frames.
+        let ix = self.starts.partition_point(|&start| start <= position) - 1;
+        self.frames.get(ix)
+    }
+
+    /// Append all frames of the ILDA file at the given path, each displayed for `frame_duration`.
+    #[cfg(feature = "ilda-idtf")]
+    pub fn push_ilda_file<P>(&mut self, path: P, frame_duration: Duration) -> io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let mut reader = crate::ilda_idtf::BufFileFrameReader::open(path)?;
+        while let Some(points) = reader.next()? {
+            self.push_frame(points.iter().cloned(), frame_duration);
+        }
+        Ok(())
+    }
+
+    /// Load a show from the playlist file at the given path.
+    ///
+    /// Each non-empty line of a playlist names an ILDA file, optionally followed by the number of
+    /// seconds for which each of its frames is displayed. Relative paths are relative to the
+    /// directory containing the playlist. Lines beginning with `#` are ignored. For example:
+    ///
+    /// ```text
+    /// # Opening
+    /// intro.ild 0.04
+    /// loop.ild
+    /// ```
+    ///
+    /// If no frame duration is given, frames are displayed for one period of
+    /// `stream::DEFAULT_FRAME_HZ`.
+    #[cfg(feature = "ilda-idtf")]
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+        let playlist = std::fs::read_to_string(path)?;
+        let mut show = Show::new();
+        for (i, line) in playlist.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |msg: &str| {
+                let msg = format!("{}:{}: {}", path.display(), i + 1, msg);
+                io::Error::new(io::ErrorKind::InvalidData, msg)
+            };
+            // Split a trailing duration from the file path, allowing for spaces in the path.
+            let (file, frame_duration) = match line.rsplit_once(char::is_whitespace) {
+                Some((file, secs)) if secs.parse::<f64>().is_ok() => {
+                    let secs: f64 = secs.parse().expect("checked above");
+                    if !secs.is_finite() || secs < 0.0 {
+                        return Err(invalid("frame duration must be a positive number"));
+                    }
+                    (file.trim_end(), Duration::from_secs_f64(secs))
+                }
+                _ => (line, default_frame_duration()),
+            };
+            show.push_ilda_file(dir.join(file), frame_duration)?;
+        }
+        Ok(show)
+    }
+}
+
+impl Player {
+    /// Whether or not the show is currently playing.
+    ///
+    /// This becomes `false` once playback reaches the end of a show that is not looping.
+    pub fn is_playing(&self) -> bool {
+        self.status.playing.load(atomic::Ordering::Relaxed)
+    }
+
+    /// The current playback position within the show.
+    pub fn position(&self) -> Duration {
+        let nanos = self.status.position_nanos.load(atomic::Ordering::Relaxed);
+        Duration::from_nanos(nanos)
+    }
+
+    /// The total duration of the show.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Start or resume playback from the current position.
+    ///
+    /// If playback had reached the end of the show, it restarts from the beginning.
+    pub fn play(&self) -> Result<(), mpsc::SendError<()>> {
+        self.send(|pb| {
+            if pb.position >= pb.show.duration() {
+                pb.seek(Duration::from_secs(0));
+            }
+            pb.set_playing(true);
+        })
+    }
+
+    /// Pause playback at the current position.
+    pub fn pause(&self) -> Result<(), mpsc::SendError<()>> {
+        self.send(|pb| pb.set_playing(false))
+    }
+
+    /// Stop playback and return to the start of the show.
+    pub fn stop(&self) -> Result<(), mpsc::SendError<()>> {
+        self.send(|pb| {
+            pb.set_playing(false);
+            pb.seek(Duration::from_secs(0));
+        })
+    }
+
+    /// Move the playback position to the given position within the show.
+    pub fn seek(&self, position: Duration) -> Result<(), mpsc::SendError<()>> {
+        self.send(move |pb| pb.seek(position))
+    }
+
+    /// Whether or not playback should wrap to the start upon reaching the end of the show.
+    pub fn set_looping(&self, looping: bool) -> Result<(), mpsc::SendError<()>> {
+        self.send(move |pb| pb.looping = looping)
+    }
+
+    /// Replace the show being played. Playback continues from the start of the new show.
+    pub fn set_show(&mut self, show: Show) -> Result<(), mpsc::SendError<()>> {
+        self.duration = show.duration();
+        self.send(move |pb| {
+            pb.show = show;
+            pb.seek(Duration::from_secs(0));
+        })
+    }
+
+    /// The underlying laser frame stream, e.g. for adjusting the point rate or interpolation.
+    pub fn stream(&self) -> &stream::frame::Stream<impl Send> {
+        &self.stream
+    }
+
+    /// Close the stream and wait for the laser thread to finish.
+    pub fn close(self) -> Option<std::thread::Result<Result<(), crate::StreamError>>> {
+        self.stream.close()
+    }
+
+    // Send an update to the playback state on the laser thread.
+    fn send<F>(&self, update: F) -> Result<(), mpsc::SendError<()>>
+    where
+        F: 'static + FnOnce(&mut Playback) + Send,
+    {
+        self.stream.send(update).map_err(|_| mpsc::SendError(()))
+    }
+}
+
+impl Builder {
+    // Begin building a player. See `Api::new_show_player`.
+    pub(crate) fn new(api_inner: Arc<crate::Inner>, show: Show) -> Self {
+        Builder {
+            api_inner,
+            show,
+            dac: None,
+            point_hz: None,
+            frame_hz: None,
+            looping: false,
+            playing: true,
+        }
+    }
+
+    /// The DAC to which the show should be played.
+    ///
+    /// If unspecified, `build` blocks until the first DAC is detected.
+    pub fn detected_dac(mut self, dac: crate::DetectedDac) -> Self {
+        self.dac = Some(dac);
+        self
+    }
+
+    /// The rate at which the DAC should process points per second.
+    ///
+    /// By default this value is `stream::DEFAULT_POINT_HZ`.
+    pub fn point_hz(mut self, point_hz: u32) -> Self {
+        self.point_hz = Some(point_hz);
+        self
+    }
+
+    /// The rate at which frames of the show are requested and playback advances.
+    ///
+    /// By default this value is `stream::DEFAULT_FRAME_HZ`.
+    pub fn frame_hz(mut self, frame_hz: u32) -> Self {
+        self.frame_hz = Some(frame_hz);
+        self
+    }
+
+    /// Whether or not playback should wrap to the start upon reaching the end of the show.
+    ///
+    /// By default this value is `false`.
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Whether or not playback begins as soon as the stream is established.
+    ///
+    /// By default this value is `true`.
+    pub fn playing(mut self, playing: bool) -> Self {
+        self.playing = playing;
+        self
+    }
+
+    /// Establish the stream and begin playback.
+    ///
+    /// **Note:** If no DAC was specified, this method will block until a DAC is detected.
+    pub fn build(self) -> io::Result<Player> {
+        let Builder {
+            api_inner,
+            show,
+            dac,
+            point_hz,
+            frame_hz,
+            looping,
+            playing,
+        } = self;
+        let status = Arc::new(Status {
+            position_nanos: AtomicU64::new(0),
+            playing: AtomicBool::new(playing),
+        });
+        let duration = show.duration();
+        let model = Playback {
+            show,
+            position: Duration::from_secs(0),
+            looping,
+            status: status.clone(),
+        };
+        let api = crate::Api { inner: api_inner };
+        let mut builder = api.new_frame_stream(model, render);
+        if let Some(dac) = dac {
+            builder = builder.detected_dac(dac);
+        }
+        if let Some(hz) = point_hz {
+            builder = builder.point_hz(hz);
+        }
+        if let Some(hz) = frame_hz {
+            builder = builder.frame_hz(hz);
+        }
+        let stream = builder.build()?;
+        Ok(Player {
+            stream,
+            status,
+            duration,
+        })
+    }
+}
+
+impl Playback {
+    fn set_playing(&mut self, playing: bool) {
+        self.status
+            .playing
+            .store(playing, atomic::Ordering::Relaxed);
+    }
+
+    fn seek(&mut self, position: Duration) {
+        self.position = position;
+        let nanos = position.as_nanos() as u64;
+        self.status
+            .position_nanos
+            .store(nanos, atomic::Ordering::Relaxed);
+    }
+}
+
+// The frame period of the default frame rate.
+#[cfg(feature = "ilda-idtf")]
+fn default_frame_duration() -> Duration {
+    Duration::from_secs(1) / stream::DEFAULT_FRAME_HZ
+}
+
+// Emit the current frame of the show and advance playback by one frame period.
+fn render(pb: &mut Playback, frame: &mut Frame) {
+    if !pb.status.playing.load(atomic::Ordering::Relaxed) {
+        return;
+    }
+
+    // Handle reaching the end of the show.
+    let duration = pb.show.duration();
+    if pb.position >= duration {
+        if pb.looping && duration > Duration::from_secs(0) {
+            let nanos = pb.position.as_nanos() % duration.as_nanos();
+            pb.seek(Duration::from_nanos(nanos as u64));
+        } else {
+            pb.set_playing(false);
+            return;
+        }
+    }
+
+    if let Some(show_frame) = pb.show.frame_at(pb.position) {
+        frame.add_lines(&show_frame.points);
+    }
+
+    let frame_period = Duration::from_secs(1) / frame.frame_hz();
+    let position = pb.position + frame_period;
+    pb.seek(position);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A show of frames with the given durations in milliseconds, where frame `i` has `i + 1`
+    // points so that frames may be told apart.
+    fn show(durations_ms: &[u64]) -> Show {
+        let mut show = Show::new();
+        for (i, &ms) in durations_ms.iter().enumerate() {
+            let points = vec![Point::new([0.0, 0.0], [1.0; 3]); i + 1];
+            show.push_frame(points, Duration::from_millis(ms));
+        }
+        show
+    }
+
+    // The index of the frame displayed at the given position in microseconds.
+    fn frame_ix(show: &Show, us: u64) -> Option<usize> {
+        let position = Duration::from_micros(us);
+        show.frame_at(position).map(|frame| frame.points.len() - 1)
+    }
+
+    #[test]
+    fn frames_cover_their_start_up_to_the_next() {
+        let show = show(&[10, 20]);
+        assert_eq!(show.duration(), Duration::from_millis(30));
+        assert_eq!(frame_ix(&show, 0), Some(0));
+        assert_eq!(frame_ix(&show, 9_999), Some(0));
+        assert_eq!(frame_ix(&show, 10_000), Some(1));
+        assert_eq!(frame_ix(&show, 29_999), Some(1));
+        assert_eq!(frame_ix(&show, 30_000), None);
+    }
+
+    #[test]
+    fn zero_duration_frames_are_skipped() {
+        let show = show(&[10, 0, 0, 10]);
+        assert_eq!(frame_ix(&show, 9_999), Some(0));
+        assert_eq!(frame_ix(&show, 10_000), Some(3));
+        assert_eq!(frame_ix(&show, 19_999), Some(3));
+    }
+
+    #[test]
+    fn leading_and_trailing_zero_duration_frames_are_skipped() {
+        let show = show(&[0, 0, 10, 0]);
+        assert_eq!(frame_ix(&show, 0), Some(2));
+        assert_eq!(frame_ix(&show, 9_999), Some(2));
+        assert_eq!(frame_ix(&show, 10_000), None);
+    }
+
+    #[test]
+    fn empty_shows_have_no_frames() {
+        assert!(Show::new().frame_at(Duration::from_secs(0)).is_none());
+        assert!(show(&[0, 0]).frame_at(Duration::from_secs(0)).is_none());
+    }
+
+    #[test]
+    fn extend_appends_frames_after_the_end() {
+        let mut a = show(&[10]);
+        a.extend(show(&[0, 5]));
+        assert_eq!(a.duration(), Duration::from_millis(15));
+        // The frames of the appended show keep their point counts of 1 and 2.
+        assert_eq!(
+            a.frame_at(Duration::from_millis(12)).unwrap().points.len(),
+            2
+        );
+        assert_eq!(frame_ix(&a, 9_999), Some(0));
+    }
+}
@@ -1,8 +1,11 @@
+use crate::morph::{self, PathMatching};
+use crate::point::Rgb;
+use crate::projection::{Camera, Point3};
 use crate::stream;
 use crate::stream::raw::{self, Buffer, StreamError};
 use crate::{Point, RawPoint};
 use std::io;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 use std::sync::{mpsc, Arc, Mutex};
 use std::time::Duration;
 
@@ -12,6 +15,57 @@ pub use lasy::InterpolationConfig;
 pub trait RenderFn<M>: Fn(&mut M, &mut Frame) {}
 impl<M, F> RenderFn<M> for F where F: Fn(&mut M, &mut Frame) {}
 
+/// A host-provided clock against which frames are synchronised, e.g. the playback position of an
+/// audio stream.
+///
+/// See `Builder::clock` and `Frame::time`.
+pub type ClockFn = dyn Fn() -> Duration + 'static + Send + Sync;
+
+/// A function that may modulate the colour of each optimised, interpolated point before
+/// submission to the DAC, e.g. for strobe, shimmer or audio-modulated brightness effects.
+///
+/// See `Builder::point_fn`.
+pub type PointFn<M> = dyn Fn(&mut M, &PointInfo, &mut Rgb) + 'static + Send;
+
+/// Describes a point passed to a `PointFn`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointInfo {
+    /// The position of the point, which may not be modified by the `PointFn`.
+    pub position: crate::point::Position,
+    /// The index of the point, counted from the first point emitted by the stream.
+    pub index: u64,
+    /// The time at which the point is emitted relative to the first point emitted by the stream.
+    pub time: Duration,
+    /// The rate at which points are being emitted by the DAC.
+    pub point_hz: u32,
+}
+
+/// A function that receives a copy of each optimised, interpolated frame of points, e.g. for
+/// drawing the exact scanner path within a preview window.
+///
+/// See `Stream::debug_tap`.
+pub type DebugTapFn = dyn Fn(&[TapPoint]) + 'static + Send + Sync;
+
+/// A single point of a frame delivered to a `DebugTapFn`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TapPoint {
+    /// The point as it will be submitted to the DAC, prior to any `point_fn` or `process_raw`.
+    pub point: RawPoint,
+    /// The role of the point within the scanner path.
+    pub kind: TapPointKind,
+}
+
+/// The role of a point within the scanner path, e.g. for colour-coding a preview.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TapPointKind {
+    /// A lit point that moves the scanners.
+    Lit,
+    /// A blank point, e.g. travelling between paths or waiting for the laser to switch off.
+    Blank,
+    /// A lit point at the same position as the previous point, e.g. dwelling on a corner.
+    Dwell,
+}
+
 /// A clone-able handle around a laser stream of frames.
 pub struct Stream<M> {
     // A handle to the inner raw stream that drives this frame stream.
@@ -20,13 +74,54 @@ pub struct Stream<M> {
     state_update_tx: mpsc::Sender<StateUpdate>,
 }
 
+/// Describes how quickly a projector's galvanometer scanners can change direction.
+///
+/// Used to automatically determine how many dwell points to insert at each corner based on the
+/// interior angle of the corner and the rate at which the DAC emits points. See
+/// `InterpolationConfigExt::auto_dwell`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScannerProfile {
+    /// The angular change in direction (in radians) that the scanners can settle per second.
+    pub radians_per_second: f32,
+}
+
+/// Extends the `InterpolationConfig` with constructors derived from scanner characteristics.
+pub trait InterpolationConfigExt {
+    /// An interpolation config whose corner dwell is derived from the given scanner profile.
+    ///
+    /// The number of dwell points inserted at each corner is proportional to the corner's angle
+    /// and inversely proportional to the speed of the scanners. As the dwell is measured in points,
+    /// the `point_hz` at which the DAC emits points must also be known.
+    fn auto_dwell(profile: ScannerProfile, point_hz: u32) -> Self;
+}
+
+/// How paths on higher layers of a `Frame` occlude the paths on lower layers.
+///
+/// Only closed paths (those whose last point returns to the position of their first) occlude
+/// other paths. Closed paths submitted with blank points act as invisible masks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Occlusion {
+    /// All layers are drawn as submitted.
+    Disabled,
+    /// Segments of lower layers that fall within the closed paths of higher layers are blanked.
+    Drop,
+    /// Segments of lower layers that fall within the closed paths of higher layers have their
+    /// colour scaled by the given factor within the range `0.0..=1.0`.
+    Dim(f32),
+}
+
 // State associated with the frame stream shared between the handle and laser stream.
 #[derive(Clone)]
 struct State {
     frame_hz: u32,
     interpolation_conf: lasy::InterpolationConfig,
+    auto_dwell: Option<ScannerProfile>,
+    occlusion: Occlusion,
     enable_optimisations: bool,
     enable_draw_reorder: bool,
+    clock: Option<Arc<ClockFn>>,
+    audio_sync_offset: Duration,
+    debug_tap: Option<Arc<DebugTapFn>>,
 }
 
 // Updates for the interpolation config sent from the stream handle to the laser thread.
@@ -37,25 +132,49 @@ type StateUpdate = Box<dyn FnMut(&mut State) + 'static + Send>;
 /// Provides a suite of methods that ease the process of submitting points.
 ///
 /// Segments that contain more than one blank point in a row will be considered a blank segment.
+///
+/// Paths are added to the current layer, `0` by default. See `Frame::set_layer` and `Occlusion`.
 pub struct Frame {
     frame_hz: u32,
     point_hz: u32,
     latency_points: u32,
+    time: Option<Duration>,
+    emit_delay: Duration,
+    layer: u32,
+    layer_paths: Vec<LayerPath>,
     points: Vec<Point>,
 }
 
+// The range of points within a frame that were submitted as a single path on a layer.
+struct LayerPath {
+    layer: u32,
+    range: Range<usize>,
+}
+
 // A type used for requesting frames from the user and feeding them to the raw buffer.
 struct Requester {
     last_frame_point: Option<RawPoint>,
     raw_points: Vec<RawPoint>,
     blank_points: Vec<RawPoint>,
+    // The index of the next point passed to the `point_fn`.
+    point_index: u64,
+    // The point rate of the last buffer, along with the index and emission time of the point at
+    // which it came into effect. Emission times are derived from these rather than accumulated.
+    point_hz: u32,
+    point_clock_base: (u64, Duration),
 }
 
 // The type of the default function used for the `process_raw` function if none is specified.
 type DefaultProcessRawFn<M> = fn(&mut M, &mut Buffer);
 
 /// A type allowing to build a raw laser stream.
-pub struct Builder<M, F, R = DefaultProcessRawFn<M>, E = raw::DefaultStreamErrorFn<M>> {
+pub struct Builder<
+    M,
+    F,
+    R = DefaultProcessRawFn<M>,
+    E = raw::DefaultStreamErrorFn<M>,
+    W = raw::DefaultStreamWarningFn<M>,
+> {
     /// The laser API inner state, used to find a DAC during `build` if one isn't specified.
     pub(crate) api_inner: Arc<crate::Inner>,
     pub builder: stream::Builder,
@@ -63,10 +182,58 @@ pub struct Builder<M, F, R = DefaultProcessRawFn<M>, E = raw::DefaultStreamError
     pub render: F,
     pub process_raw: R,
     pub stream_error: E,
+    pub stream_warning: W,
     pub frame_hz: Option<u32>,
     pub interpolation_conf: lasy::InterpolationConfig,
+    pub auto_dwell: Option<ScannerProfile>,
+    pub occlusion: Occlusion,
     pub enable_optimisations: bool,
     pub enable_draw_reorder: bool,
+    pub clock: Option<Arc<ClockFn>>,
+    pub audio_sync_offset: Option<Duration>,
+    pub point_fn: Option<Box<PointFn<M>>>,
+}
+
+impl ScannerProfile {
+    /// The speed of a scanner with an ILDA rating of 30K points per second.
+    ///
+    /// The `InterpolationConfig::DEFAULT_RADIANS_PER_POINT` is tuned for such a scanner driven at
+    /// the `stream::DEFAULT_POINT_HZ`, which is lower than the scanner's rating. The speed is
+    /// therefore derived from the default point rate rather than from the 30K rating itself.
+    pub const DEFAULT_RADIANS_PER_SECOND: f32 =
+        InterpolationConfig::DEFAULT_RADIANS_PER_POINT * stream::DEFAULT_POINT_HZ as f32;
+
+    // The ILDA rating (in thousands of points per second) of the scanner whose speed is
+    // `DEFAULT_RADIANS_PER_SECOND`.
+    const DEFAULT_KPPS: f32 = 30.0;
+
+    /// Approximate a scanner profile from the scanner's ILDA speed rating in thousands of points
+    /// per second (kpps), e.g. `30.0` for a "30K" scanner.
+    pub fn from_kpps(kpps: f32) -> Self {
+        let radians_per_second = Self::DEFAULT_RADIANS_PER_SECOND * kpps / Self::DEFAULT_KPPS;
+        ScannerProfile { radians_per_second }
+    }
+
+    /// The radians per dwell point that should be used for this scanner at the given point rate.
+    pub fn radians_per_point(&self, point_hz: u32) -> f32 {
+        self.radians_per_second / point_hz as f32
+    }
+}
+
+impl Default for ScannerProfile {
+    fn default() -> Self {
+        let radians_per_second = Self::DEFAULT_RADIANS_PER_SECOND;
+        ScannerProfile { radians_per_second }
+    }
+}
+
+impl InterpolationConfigExt for InterpolationConfig {
+    fn auto_dwell(profile: ScannerProfile, point_hz: u32) -> Self {
+        InterpolationConfig {
+            radians_per_point: profile.radians_per_point(point_hz),
+            ..Default::default()
+        }
+    }
 }
 
 impl<M> Stream<M> {
@@ -100,6 +267,32 @@ impl<M> Stream<M> {
             .map_err(|_| mpsc::SendError(()))
     }
 
+    /// Enable or disable automatic corner dwell based on the given scanner profile.
+    ///
+    /// While enabled, the `radians_per_point` field of the interpolation configuration is derived
+    /// from the profile and the current point rate, overriding any manually specified value.
+    ///
+    /// The value will be updated on the laser thread prior to requesting the next frame.
+    ///
+    /// Returns an `Err` if communication with the laser thread has been closed.
+    pub fn set_auto_dwell(
+        &self,
+        profile: Option<ScannerProfile>,
+    ) -> Result<(), mpsc::SendError<()>> {
+        self.send_frame_state_update(move |state| state.auto_dwell = profile)
+            .map_err(|_| mpsc::SendError(()))
+    }
+
+    /// Update how paths on higher layers of each frame occlude those on lower layers.
+    ///
+    /// The value will be updated on the laser thread prior to requesting the next frame.
+    ///
+    /// Returns an `Err` if communication with the laser thread has been closed.
+    pub fn set_occlusion(&self, occlusion: Occlusion) -> Result<(), mpsc::SendError<()>> {
+        self.send_frame_state_update(move |state| state.occlusion = occlusion)
+            .map_err(|_| mpsc::SendError(()))
+    }
+
     /// Update the rate at which the stream will attempt to present images via the DAC.
     ///
     /// The value will be updated on the laser thread prior to requesting the next frame.
@@ -130,6 +323,65 @@ impl<M> Stream<M> {
             .map_err(|_| mpsc::SendError(()))
     }
 
+    /// Update the host-provided clock against which frame times are reported.
+    ///
+    /// See `Builder::clock`.
+    ///
+    /// The value will be updated on the laser thread prior to requesting the next frame.
+    ///
+    /// Returns an `Err` if communication with the laser thread has been closed.
+    pub fn set_clock<C>(&self, clock: Option<C>) -> Result<(), mpsc::SendError<()>>
+    where
+        C: 'static + Fn() -> Duration + Send + Sync,
+    {
+        let clock = clock.map(|c| Arc::new(c) as Arc<ClockFn>);
+        self.send_frame_state_update(move |state| state.clock = clock)
+            .map_err(|_| mpsc::SendError(()))
+    }
+
+    /// Update the latency of the laser output path relative to the audio output path.
+    ///
+    /// See `Builder::audio_sync_offset`.
+    ///
+    /// The value will be updated on the laser thread prior to requesting the next frame.
+    ///
+    /// Returns an `Err` if communication with the laser thread has been closed.
+    pub fn set_audio_sync_offset(&self, offset: Duration) -> Result<(), mpsc::SendError<()>> {
+        self.send_frame_state_update(move |state| state.audio_sync_offset = offset)
+            .map_err(|_| mpsc::SendError(()))
+    }
+
+    /// Deliver a copy of each optimised, interpolated frame of points to the given function.
+    ///
+    /// The points of each frame are delivered in the order in which they will be emitted, with
+    /// each point classified as lit, blank or dwelling. This allows for drawing the exact path of
+    /// the scanners in a preview window, which is invaluable when diagnosing unexpected output.
+    /// Colours are those prior to any `point_fn` or `process_raw` function.
+    ///
+    /// The function is called on the laser thread each time a frame is produced, so should
+    /// return quickly, e.g. by sending the points to the main thread via a channel. Any previous
+    /// debug tap is replaced.
+    ///
+    /// The value will be updated on the laser thread prior to requesting the next frame.
+    ///
+    /// Returns an `Err` if communication with the laser thread has been closed.
+    pub fn debug_tap<T>(&self, tap: T) -> Result<(), mpsc::SendError<()>>
+    where
+        T: 'static + Fn(&[TapPoint]) + Send + Sync,
+    {
+        let tap = Arc::new(tap) as Arc<DebugTapFn>;
+        self.send_frame_state_update(move |state| state.debug_tap = Some(tap))
+            .map_err(|_| mpsc::SendError(()))
+    }
+
+    /// Remove any function registered via `debug_tap`.
+    ///
+    /// Returns an `Err` if communication with the laser thread has been closed.
+    pub fn clear_debug_tap(&self) -> Result<(), mpsc::SendError<()>> {
+        self.send_frame_state_update(move |state| state.debug_tap = None)
+            .map_err(|_| mpsc::SendError(()))
+    }
+
     /// Close the TCP communication thread and wait for the thread to join.
     ///
     /// This consumes and drops the `Stream`, returning the result produced by joining the thread.
@@ -158,7 +410,7 @@ impl<M> Stream<M> {
     }
 }
 
-impl<M, F, R, E> Builder<M, F, R, E> {
+impl<M, F, R, E, W> Builder<M, F, R, E, W> {
     /// The DAC with which the stream should be established.
     pub fn detected_dac(mut self, dac: crate::DetectedDac) -> Self {
         self.builder.dac = Some(dac);
@@ -210,6 +462,48 @@ impl<M, F, R, E> Builder<M, F, R, E> {
         self
     }
 
+    /// Limit the average power of each colour channel to protect the projector's diodes.
+    ///
+    /// See `raw::Builder::thermal_guard` for details.
+    ///
+    /// By default, no guard is applied.
+    pub fn thermal_guard(mut self, guard: stream::thermal::ThermalGuard) -> Self {
+        self.builder.thermal_guard = Some(guard);
+        self
+    }
+
+    /// A host-provided clock against which frames are synchronised, e.g. the playback position of
+    /// an audio stream.
+    ///
+    /// When specified, each `Frame` reports the `time` according to this clock at which its points
+    /// are expected to be seen. Rendering the frame's content for this time, rather than the time
+    /// at which the render function is called, keeps the laser in sync with the clock despite the
+    /// buffering of points.
+    ///
+    /// By default, no clock is used.
+    pub fn clock<C>(mut self, clock: C) -> Self
+    where
+        C: 'static + Fn() -> Duration + Send + Sync,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// The latency of the laser output path relative to the audio output path, e.g. due to
+    /// projector processing and scanner response.
+    ///
+    /// This is added to the `time` reported by each `Frame`, so that content rendered for that
+    /// time lands on the beat rather than consistently late. If the audio output path has the
+    /// greater latency, the `clock` should instead be offset by the difference.
+    ///
+    /// By default, this value is the DAC's configured `audio_sync_offset` if a `detected_dac` was
+    /// specified and a config store is set on the `Api`, or `Duration::ZERO` otherwise. An offset
+    /// specified here, including `Duration::ZERO`, always takes precedence over the DAC's config.
+    pub fn audio_sync_offset(mut self, offset: Duration) -> Self {
+        self.audio_sync_offset = Some(offset);
+        self
+    }
+
     /// The minimum distance the interpolator can travel along an edge before a new point is
     /// required.
     ///
@@ -241,6 +535,30 @@ impl<M, F, R, E> Builder<M, F, R, E> {
         self
     }
 
+    /// Automatically determine the corner dwell from the given scanner profile.
+    ///
+    /// The `radians_per_point` is derived from the profile and the current point rate, overriding
+    /// any value specified via `radians_per_point`. The dwell is re-derived whenever the point rate
+    /// changes.
+    ///
+    /// By default, this is disabled.
+    ///
+    /// This parameter is only meaningful while optimisations are enabled (the default).
+    pub fn auto_dwell(mut self, profile: ScannerProfile) -> Self {
+        self.auto_dwell = Some(profile);
+        self
+    }
+
+    /// How paths on higher layers of each frame occlude those on lower layers.
+    ///
+    /// Occlusion is applied to the submitted frame before optimisation and interpolation.
+    ///
+    /// By default, this value is `Occlusion::Disabled`.
+    pub fn occlusion(mut self, occlusion: Occlusion) -> Self {
+        self.occlusion = occlusion;
+        self
+    }
+
     /// Whether or not to enable the optimisations.
     ///
     /// By default, this value is `true`.
@@ -264,6 +582,25 @@ impl<M, F, R, E> Builder<M, F, R, E> {
         self
     }
 
+    /// Specify a function that may modulate the colour of each point after optimisation and
+    /// interpolation.
+    ///
+    /// The function is called with each lit point along with its `PointInfo` and may modify the
+    /// point's colour, but not its position, so the work of the path optimiser is preserved. Blank
+    /// points are not passed to the function so that travel between paths always remains blank.
+    /// Resulting colour channels are clamped to the range `0.0..=1.0`.
+    ///
+    /// The function runs before any `process_raw` function.
+    ///
+    /// By default, no function is used.
+    pub fn point_fn<P>(mut self, point_fn: P) -> Self
+    where
+        P: 'static + Fn(&mut M, &PointInfo, &mut Rgb) + Send,
+    {
+        self.point_fn = Some(Box::new(point_fn));
+        self
+    }
+
     /// Specify a function that allows for processing the raw points before submission to the DAC.
     ///
     /// This might be useful for:
@@ -274,17 +611,23 @@ impl<M, F, R, E> Builder<M, F, R, E> {
     ///
     /// The given function will get called right before submission of the optimised, interpolated
     /// buffer.
-    pub fn process_raw<R2>(self, process_raw: R2) -> Builder<M, F, R2, E> {
+    pub fn process_raw<R2>(self, process_raw: R2) -> Builder<M, F, R2, E, W> {
         let Builder {
             api_inner,
             builder,
             model,
             render,
             stream_error,
+            stream_warning,
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
             ..
         } = self;
         Builder {
@@ -294,27 +637,83 @@ impl<M, F, R, E> Builder<M, F, R, E> {
             render,
             process_raw,
             stream_error,
+            stream_warning,
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
         }
     }
 
     /// Specify a function that allows for handling errors that occur on the TCP stream thread.
     ///
     /// If this method is not called, the `stream::raw::default_stream_error_fn` is used by default.
-    pub fn stream_error<E2>(self, stream_error: E2) -> Builder<M, F, R, E2> {
+    pub fn stream_error<E2>(self, stream_error: E2) -> Builder<M, F, R, E2, W> {
+        let Builder {
+            api_inner,
+            builder,
+            model,
+            render,
+            process_raw,
+            stream_warning,
+            frame_hz,
+            interpolation_conf,
+            auto_dwell,
+            occlusion,
+            enable_optimisations,
+            enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
+            ..
+        } = self;
+        Builder {
+            api_inner,
+            builder,
+            model,
+            render,
+            process_raw,
+            stream_error,
+            stream_warning,
+            frame_hz,
+            interpolation_conf,
+            auto_dwell,
+            occlusion,
+            enable_optimisations,
+            enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
+        }
+    }
+
+    /// Specify a function that allows for handling non-fatal warnings that occur on the stream
+    /// thread, e.g. `StreamWarning::ThermalBudgetExceeded`.
+    ///
+    /// If this method is not called, the `stream::raw::default_stream_warning_fn` is used by
+    /// default.
+    pub fn stream_warning<W2>(self, stream_warning: W2) -> Builder<M, F, R, E, W2> {
         let Builder {
             api_inner,
             builder,
             model,
             render,
             process_raw,
+            stream_error,
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
             ..
         } = self;
         Builder {
@@ -324,10 +723,16 @@ impl<M, F, R, E> Builder<M, F, R, E> {
             render,
             process_raw,
             stream_error,
+            stream_warning,
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
         }
     }
 
@@ -341,6 +746,7 @@ impl<M, F, R, E> Builder<M, F, R, E> {
         F: 'static + RenderFn<M> + Send,
         R: 'static + raw::RenderFn<M> + Send,
         E: 'static + raw::StreamErrorFn<M> + Send,
+        W: 'static + raw::StreamWarningFn<M> + Send,
     {
         let Builder {
             api_inner,
@@ -349,20 +755,39 @@ impl<M, F, R, E> Builder<M, F, R, E> {
             render,
             process_raw,
             stream_error,
+            stream_warning,
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            point_fn,
         } = self;
 
         // Retrieve the frame rate to initialise the stream with.
         let frame_hz = frame_hz.unwrap_or(stream::DEFAULT_FRAME_HZ);
 
+        // Fall back to the offset within the DAC's config if none was specified.
+        let audio_sync_offset = match (audio_sync_offset, builder.dac.as_ref()) {
+            (Some(offset), _) => offset,
+            (None, Some(dac)) => api_inner
+                .dac_config(&dac.id())
+                .and_then(|config| config.audio_sync_offset)
+                .unwrap_or(Duration::ZERO),
+            (None, None) => Duration::ZERO,
+        };
+
         // The type used for buffering frames and using them to serve points to the raw stream.
         let requester = Requester {
             last_frame_point: None,
             raw_points: vec![],
             blank_points: vec![],
+            point_index: 0,
+            point_hz: 0,
+            point_clock_base: (0, Duration::ZERO),
         };
         let requester = Arc::new(Mutex::new(requester));
 
@@ -374,8 +799,13 @@ impl<M, F, R, E> Builder<M, F, R, E> {
         let state = Arc::new(Mutex::new(State {
             frame_hz,
             interpolation_conf,
+            auto_dwell,
+            occlusion,
             enable_optimisations,
             enable_draw_reorder,
+            clock,
+            audio_sync_offset,
+            debug_tap: None,
         }));
 
         // A render function for the inner raw stream.
@@ -391,6 +821,9 @@ impl<M, F, R, E> Builder<M, F, R, E> {
 
             let mut guard = requester.lock().expect("failed to lock frame requester");
             guard.fill_buffer(model, &render, buffer, &state);
+            if let Some(ref point_fn) = point_fn {
+                guard.apply_point_fn(model, &**point_fn, buffer);
+            }
             process_raw(model, buffer);
         };
 
@@ -401,6 +834,7 @@ impl<M, F, R, E> Builder<M, F, R, E> {
             model,
             render: raw_render,
             stream_error,
+            stream_warning,
         };
         let raw_stream = raw_builder.build()?;
         let stream = Stream {
@@ -432,6 +866,38 @@ impl Frame {
         self.point_hz / self.frame_hz
     }
 
+    /// The approximate duration between the rendering of this frame and the emission of its
+    /// first point by the DAC, based on the number of points buffered ahead of it.
+    pub fn emit_delay(&self) -> Duration {
+        self.emit_delay
+    }
+
+    /// The time according to the stream's clock at which the points of this frame are expected
+    /// to be seen.
+    ///
+    /// This is the clock's current time plus the `emit_delay` and the stream's audio sync offset.
+    /// Returns `None` if the stream has no clock.
+    pub fn time(&self) -> Option<Duration> {
+        self.time
+    }
+
+    /// The layer to which subsequently added points and lines belong.
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+
+    /// Set the layer to which subsequently added points and lines belong.
+    ///
+    /// Paths on higher layers are considered to be in front of those on lower layers. When the
+    /// stream's `Occlusion` is enabled, closed paths occlude the parts of lower layers that fall
+    /// within them.
+    ///
+    /// Only points added via `add_points` and `add_lines` are tracked. Points pushed onto the
+    /// inner `Vec` directly are never occluded.
+    pub fn set_layer(&mut self, layer: u32) {
+        self.layer = layer;
+    }
+
     /// Add a sequence of consecutive points separated by blank space.
     ///
     /// If some points already exist in the frame, this method will create a blank segment between
@@ -457,15 +923,64 @@ impl Frame {
         I::Item: AsRef<Point>,
     {
         let mut points = points.into_iter();
+        let mut start = self.points.len();
         if let Some(&last) = self.points.last() {
             if let Some(next) = points.next() {
                 let next = next.as_ref();
                 self.points.push(last.blanked());
                 self.points.push(next.blanked());
+                start = self.points.len();
                 self.points.push(*next);
             }
         }
         self.points.extend(points.map(|p| p.as_ref().clone()));
+        let range = start..self.points.len();
+        if !range.is_empty() {
+            let layer = self.layer;
+            self.layer_paths.push(LayerPath { layer, range });
+        }
+    }
+
+    /// Add the paths produced by morphing from the `prev` paths to the `next` paths.
+    ///
+    /// Each resulting path is added as a sequence of lines. See `morph::morph` for details.
+    pub fn add_morph<A, B>(&mut self, prev: &[A], next: &[B], t: f32, matching: PathMatching)
+    where
+        A: AsRef<[Point]>,
+        B: AsRef<[Point]>,
+    {
+        for path in morph::morph(prev, next, t, matching) {
+            self.add_lines(&path);
+        }
+    }
+
+    /// Project the given 3D points through the `camera` and add them separated by blank space.
+    ///
+    /// Points closer to the camera than its near plane are skipped. See `add_points`.
+    pub fn add_points_3d<I>(&mut self, camera: &Camera, points: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Point3>,
+    {
+        let points = points
+            .into_iter()
+            .filter_map(|p| camera.project_point(p.as_ref()));
+        self.add_points(points.collect::<Vec<_>>());
+    }
+
+    /// Project a sequence of consecutive 3D lines through the `camera` and add them to the frame.
+    ///
+    /// Segments crossing the camera's near plane are clipped, and each run of the sequence that is
+    /// visible to the camera is added as its own sequence of lines. See `add_lines` and
+    /// `Camera::project_lines`.
+    pub fn add_lines_3d<I>(&mut self, camera: &Camera, points: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Point3>,
+    {
+        for path in camera.project_lines(points) {
+            self.add_lines(&path);
+        }
     }
 }
 
@@ -528,15 +1043,30 @@ impl Requester {
             // Determine how many points to fill this pass.
             let num_points_to_fill = std::cmp::min(points_per_frame as usize, num_points_remaining);
 
+            // Determine when the frame will be emitted, compensating for the audio sync offset.
+            let delay_points = latency_points as u64 + start as u64;
+            let emit_delay = Duration::from_secs_f64(delay_points as f64 / point_hz as f64);
+            let time = state
+                .clock
+                .as_ref()
+                .map(|clock| clock() + emit_delay + state.audio_sync_offset);
+
             // Render a frame of points.
             let mut frame = Frame {
                 point_hz,
                 latency_points,
+                time,
+                emit_delay,
                 frame_hz: state.frame_hz,
+                layer: 0,
+                layer_paths: vec![],
                 points: vec![], // TODO: Reuse this buffer rather than allocating every loop.
             };
             render(model, &mut frame);
 
+            // Apply occlusion between layers.
+            occlude_layers(&mut frame.points, &frame.layer_paths, state.occlusion);
+
             if state.enable_optimisations {
                 // If we were given no points, the user must be expecting an empty frame.
                 if frame.points.is_empty() {
@@ -580,8 +1110,14 @@ impl Requester {
                         0
                     };
 
+                    // Derive the corner dwell from the scanner profile if necessary.
+                    let mut interp_conf = state.interpolation_conf.clone();
+                    if let Some(profile) = state.auto_dwell {
+                        interp_conf.radians_per_point = profile.radians_per_point(point_hz);
+                    }
+
                     // Join the inter-frame points with the interpolated frame.
-                    let interp_conf = &state.interpolation_conf;
+                    let interp_conf = &interp_conf;
                     let mut interpolated = vec![];
                     lasy::interpolate_path(
                         &frame,
@@ -630,6 +1166,12 @@ impl Requester {
                 self.raw_points.extend(frame_points);
             }
 
+            // Deliver a copy of the frame to the debug tap. At this point, `self.raw_points` only
+            // contains the points of this frame.
+            if let Some(ref debug_tap) = state.debug_tap {
+                debug_tap(&tap_points(&self.raw_points));
+            }
+
             // Update the last frame point.
             self.last_frame_point = self.raw_points.last().map(|&p| p);
 
@@ -648,6 +1190,40 @@ impl Requester {
             start = end;
         }
     }
+
+    // Pass each lit point of the buffer to the `point_fn`, advancing the point clock.
+    fn apply_point_fn<M>(&mut self, model: &mut M, point_fn: &PointFn<M>, buffer: &mut Buffer) {
+        let point_hz = buffer.point_hz();
+        if point_hz != self.point_hz {
+            self.point_clock_base = (self.point_index, self.point_time(self.point_index));
+            self.point_hz = point_hz;
+        }
+        for point in buffer.iter_mut() {
+            if !point.is_blank() {
+                let info = PointInfo {
+                    position: point.position,
+                    index: self.point_index,
+                    time: self.point_time(self.point_index),
+                    point_hz,
+                };
+                point_fn(model, &info, &mut point.color);
+                for channel in point.color.iter_mut() {
+                    *channel = channel.max(0.0).min(1.0);
+                }
+            }
+            self.point_index += 1;
+        }
+    }
+
+    // The emission time of the point at the given index under the current point rate.
+    fn point_time(&self, index: u64) -> Duration {
+        let (base_index, base_time) = self.point_clock_base;
+        if self.point_hz == 0 {
+            return base_time;
+        }
+        let nanos = (index - base_index) as u128 * 1_000_000_000 / self.point_hz as u128;
+        base_time + Duration::from_nanos(nanos as u64)
+    }
 }
 
 impl Deref for Frame {
@@ -670,6 +1246,28 @@ impl<M> Deref for Stream<M> {
     }
 }
 
+// Classify each point of a frame for delivery to a debug tap.
+fn tap_points(points: &[RawPoint]) -> Vec<TapPoint> {
+    let mut prev: Option<&RawPoint> = None;
+    points
+        .iter()
+        .map(|point| {
+            let kind = if point.is_blank() {
+                TapPointKind::Blank
+            } else if prev.map_or(false, |prev| prev.position == point.position) {
+                TapPointKind::Dwell
+            } else {
+                TapPointKind::Lit
+            };
+            prev = Some(point);
+            TapPoint {
+                point: *point,
+                kind,
+            }
+        })
+        .collect()
+}
+
 // Given the last point of the previous frame and the first of the next, produce
 // the points necessary to blank from one to the other.
 //
@@ -693,5 +1291,459 @@ fn inter_frame_blank_points(
     points.extend(lasy::blank_segment_points(a, b, blank_delay_points));
 }
 
+// Occlude the parts of each path that fall within closed paths on higher layers.
+fn occlude_layers(points: &mut Vec<Point>, paths: &[LayerPath], occlusion: Occlusion) {
+    let scale = match occlusion {
+        Occlusion::Disabled => return,
+        Occlusion::Drop => 0.0,
+        Occlusion::Dim(scale) => scale.max(0.0).min(1.0),
+    };
+
+    // Ignore any paths invalidated by modifying the points directly.
+    let mut end = 0;
+    let paths: Vec<&LayerPath> = paths
+        .iter()
+        .take_while(|path| {
+            let valid = path.range.start >= end && path.range.end <= points.len();
+            end = path.range.end;
+            valid
+        })
+        .collect();
+
+    // Closed paths are the only ones that may occlude others.
+    let occluders: Vec<(u32, &[Point])> = paths
+        .iter()
+        .map(|path| (path.layer, &points[path.range.clone()]))
+        .filter(|&(_, path)| is_closed_path(path))
+        .collect();
+    let any_occluded = occluders
+        .iter()
+        .any(|&(layer, _)| paths.iter().any(|path| path.layer < layer));
+    if !any_occluded {
+        return;
+    }
+
+    let mut output = Vec::with_capacity(points.len());
+    let mut next = 0;
+    for path in paths {
+        let polygons: Vec<&[Point]> = occluders
+            .iter()
+            .filter(|&&(layer, _)| layer > path.layer)
+            .map(|&(_, polygon)| polygon)
+            .collect();
+        output.extend_from_slice(&points[next..path.range.start]);
+        occlude_path(&points[path.range.clone()], &polygons, scale, &mut output);
+        next = path.range.end;
+    }
+    output.extend_from_slice(&points[next..]);
+    *points = output;
+}
+
+// Write the given path to `output`, scaling the colour of each part within any of the polygons.
+//
+// Segments are split where they cross polygon edges.
+fn occlude_path(path: &[Point], polygons: &[&[Point]], scale: f32, output: &mut Vec<Point>) {
+    if polygons.is_empty() || path.is_empty() {
+        output.extend_from_slice(path);
+        return;
+    }
+    output.push(path[0]);
+    let mut cuts = vec![];
+    for seg in path.windows(2) {
+        let (a, b) = (seg[0], seg[1]);
+        cuts.clear();
+        cuts.push(0.0);
+        for polygon in polygons {
+            for edge in polygon.windows(2) {
+                let (c, d) = (edge[0].position, edge[1].position);
+                cuts.extend(segment_intersection(a.position, b.position, c, d));
+            }
+        }
+        cuts.push(1.0);
+        cuts.sort_by(|a, b| a.partial_cmp(b).expect("intersection was NaN"));
+        for ts in cuts.windows(2) {
+            let (t0, t1) = (ts[0], ts[1]);
+            let mid = lerp_point(&a, &b, (t0 + t1) * 0.5).position;
+            let occluded = polygons
+                .iter()
+                .any(|polygon| polygon_contains(polygon, mid));
+            let factor = if occluded { scale } else { 1.0 };
+            let mut start = lerp_point(&a, &b, t0);
+            let mut end = lerp_point(&a, &b, t1);
+            start.color = scale_color(start.color, factor);
+            end.color = scale_color(end.color, factor);
+            if t1 == 1.0 {
+                end.weight = b.weight;
+            }
+            let last = output.last().expect("output contains at least one point");
+            if last.position != start.position || last.color != start.color {
+                output.push(start);
+            }
+            output.push(end);
+        }
+    }
+}
+
+// Whether or not the path returns to its starting position, enclosing an area.
+fn is_closed_path(path: &[Point]) -> bool {
+    path.len() >= 4 && path.first().map(|p| p.position) == path.last().map(|p| p.position)
+}
+
+// The parameter along `a -> b` at which it crosses `c -> d`, if it does so between its ends.
+fn segment_intersection(a: [f32; 2], b: [f32; 2], c: [f32; 2], d: [f32; 2]) -> Option<f32> {
+    let r = [b[0] - a[0], b[1] - a[1]];
+    let s = [d[0] - c[0], d[1] - c[1]];
+    let denom = r[0] * s[1] - r[1] * s[0];
+    if denom == 0.0 {
+        return None;
+    }
+    let ac = [c[0] - a[0], c[1] - a[1]];
+    let t = (ac[0] * s[1] - ac[1] * s[0]) / denom;
+    let u = (ac[0] * r[1] - ac[1] * r[0]) / denom;
+    if t > 0.0 && t < 1.0 && u >= 0.0 && u <= 1.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+// Even-odd test for whether the position lies within the closed polygon.
+fn polygon_contains(polygon: &[Point], [x, y]: [f32; 2]) -> bool {
+    let mut inside = false;
+    for edge in polygon.windows(2) {
+        let [ax, ay] = edge[0].position;
+        let [bx, by] = edge[1].position;
+        if (ay > y) != (by > y) && x < (bx - ax) * (y - ay) / (by - ay) + ax {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// The point at `t` along `a -> b`. The resulting point has no weight.
+fn lerp_point(a: &Point, b: &Point, t: f32) -> Point {
+    let lerp = |a: f32, b: f32| a + (b - a) * t;
+    let position = [
+        lerp(a.position[0], b.position[0]),
+        lerp(a.position[1], b.position[1]),
+    ];
+    let color = [
+        lerp(a.color[0], b.color[0]),
+        lerp(a.color[1], b.color[1]),
+        lerp(a.color[2], b.color[2]),
+    ];
+    Point::new(position, color)
+}
+
+fn scale_color([r, g, b]: [f32; 3], scale: f32) -> [f32; 3] {
+    [r * scale, g * scale, b * scale]
+}
+
 // The default function used for the `process_raw` function if none is specified.
 pub(crate) fn default_process_raw_fn<M>(_model: &mut M, _buffer: &mut Buffer) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 3] = [1.0; 3];
+
+    fn frame() -> Frame {
+        Frame {
+            frame_hz: stream::DEFAULT_FRAME_HZ,
+            point_hz: stream::DEFAULT_POINT_HZ,
+            latency_points: raw::default_latency_points(stream::DEFAULT_POINT_HZ),
+            time: None,
+            emit_delay: Duration::ZERO,
+            layer: 0,
+            layer_paths: vec![],
+            points: vec![],
+        }
+    }
+
+    // A closed square centred on the origin with the given half-width.
+    fn square(half: f32) -> Vec<Point> {
+        [
+            [-1.0, -1.0],
+            [1.0, -1.0],
+            [1.0, 1.0],
+            [-1.0, 1.0],
+            [-1.0, -1.0],
+        ]
+        .iter()
+        .map(|&[x, y]| Point::new([x * half, y * half], WHITE))
+        .collect()
+    }
+
+    fn line() -> [Point; 2] {
+        [
+            Point::new([-1.0, 0.0], WHITE),
+            Point::new([1.0, 0.0], WHITE),
+        ]
+    }
+
+    fn occlude(frame: &Frame, occlusion: Occlusion) -> Vec<Point> {
+        let mut points = frame.points.clone();
+        occlude_layers(&mut points, &frame.layer_paths, occlusion);
+        points
+    }
+
+    // A line on layer `0` beneath a square on layer `1`.
+    fn line_beneath_square() -> Frame {
+        let mut frame = frame();
+        frame.add_lines(&line());
+        frame.set_layer(1);
+        frame.add_lines(&square(0.5));
+        frame
+    }
+
+    // The line of `line_beneath_square` with its middle half coloured `color`.
+    fn split_line(color: [f32; 3]) -> Vec<Point> {
+        vec![
+            Point::new([-1.0, 0.0], WHITE),
+            Point::new([-0.5, 0.0], WHITE),
+            Point::new([-0.5, 0.0], color),
+            Point::new([0.5, 0.0], color),
+            Point::new([0.5, 0.0], WHITE),
+            Point::new([1.0, 0.0], WHITE),
+        ]
+    }
+
+    #[test]
+    fn layers_track_added_paths() {
+        let frame = line_beneath_square();
+        assert_eq!(frame.layer(), 1);
+        let paths: Vec<_> = frame
+            .layer_paths
+            .iter()
+            .map(|p| (p.layer, p.range.clone()))
+            .collect();
+        // The blank points between the paths belong to neither.
+        assert_eq!(paths, vec![(0, 0..2), (1, 4..9)]);
+    }
+
+    #[test]
+    fn disabled_occlusion_leaves_points() {
+        let frame = line_beneath_square();
+        assert_eq!(occlude(&frame, Occlusion::Disabled), frame.points);
+    }
+
+    #[test]
+    fn drop_blanks_occluded_segments() {
+        let frame = line_beneath_square();
+        let points = occlude(&frame, Occlusion::Drop);
+        assert_eq!(&points[..6], &split_line([0.0; 3])[..]);
+        // The square itself is unaffected.
+        assert_eq!(&points[6..], &frame.points[2..]);
+    }
+
+    #[test]
+    fn dim_scales_occluded_segments() {
+        let frame = line_beneath_square();
+        let points = occlude(&frame, Occlusion::Dim(0.5));
+        assert_eq!(&points[..6], &split_line([0.5; 3])[..]);
+    }
+
+    #[test]
+    fn open_paths_do_not_occlude() {
+        let mut frame = frame();
+        frame.add_lines(&line());
+        frame.set_layer(1);
+        frame.add_lines(&square(0.5)[..4]);
+        assert_eq!(occlude(&frame, Occlusion::Drop), frame.points);
+    }
+
+    #[test]
+    fn lower_layers_do_not_occlude() {
+        let mut frame = frame();
+        frame.set_layer(1);
+        frame.add_lines(&line());
+        frame.set_layer(0);
+        frame.add_lines(&square(0.5));
+        assert_eq!(occlude(&frame, Occlusion::Drop), frame.points);
+    }
+
+    #[test]
+    fn modified_paths_are_ignored() {
+        let mut frame = line_beneath_square();
+        frame.truncate(6);
+        assert_eq!(occlude(&frame, Occlusion::Drop), frame.points);
+    }
+
+    #[test]
+    fn closed_paths() {
+        assert!(is_closed_path(&square(0.5)));
+        assert!(!is_closed_path(&square(0.5)[..4]));
+        assert!(!is_closed_path(&[Point::new([0.0; 2], WHITE); 3]));
+    }
+
+    #[test]
+    fn segment_intersections() {
+        let hit = segment_intersection([-1.0, 0.0], [1.0, 0.0], [0.5, -1.0], [0.5, 1.0]);
+        assert_eq!(hit, Some(0.75));
+        let parallel = segment_intersection([-1.0, 0.0], [1.0, 0.0], [-1.0, 1.0], [1.0, 1.0]);
+        assert_eq!(parallel, None);
+        let short = segment_intersection([-1.0, 0.0], [1.0, 0.0], [0.5, 0.5], [0.5, 1.0]);
+        assert_eq!(short, None);
+    }
+
+    #[test]
+    fn polygon_containment() {
+        let square = square(0.5);
+        assert!(polygon_contains(&square, [0.0, 0.0]));
+        assert!(polygon_contains(&square, [0.25, -0.25]));
+        assert!(!polygon_contains(&square, [0.75, 0.0]));
+    }
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-6 * a.abs().max(b.abs())
+    }
+
+    #[test]
+    fn default_profile_matches_default_dwell() {
+        let profile = ScannerProfile::default();
+        let radians = profile.radians_per_point(stream::DEFAULT_POINT_HZ);
+        assert!(approx_eq(
+            radians,
+            InterpolationConfig::DEFAULT_RADIANS_PER_POINT
+        ));
+        assert_eq!(ScannerProfile::from_kpps(30.0), profile);
+    }
+
+    #[test]
+    fn default_speed_is_the_default_dwell_at_the_default_point_rate() {
+        let expected =
+            InterpolationConfig::DEFAULT_RADIANS_PER_POINT * stream::DEFAULT_POINT_HZ as f32;
+        assert_eq!(ScannerProfile::DEFAULT_RADIANS_PER_SECOND, expected);
+        assert_eq!(ScannerProfile::DEFAULT_KPPS, 30.0);
+
+        // Driving a 30K scanner at its full rating reduces the dwell per point accordingly.
+        let profile = ScannerProfile::from_kpps(30.0);
+        let radians = profile.radians_per_point(30_000);
+        let ratio = stream::DEFAULT_POINT_HZ as f32 / 30_000.0;
+        assert!(approx_eq(
+            radians,
+            InterpolationConfig::DEFAULT_RADIANS_PER_POINT * ratio
+        ));
+    }
+
+    #[test]
+    fn faster_scanners_dwell_less() {
+        let slow = ScannerProfile::from_kpps(15.0);
+        let fast = ScannerProfile::from_kpps(60.0);
+        let hz = stream::DEFAULT_POINT_HZ;
+        let slow_radians = slow.radians_per_point(hz);
+        let fast_radians = fast.radians_per_point(hz);
+        assert!(approx_eq(fast_radians, slow_radians * 4.0));
+    }
+
+    #[test]
+    fn higher_point_rates_dwell_more() {
+        let profile = ScannerProfile::default();
+        let radians = profile.radians_per_point(stream::DEFAULT_POINT_HZ);
+        let doubled = profile.radians_per_point(stream::DEFAULT_POINT_HZ * 2);
+        assert!(approx_eq(doubled * 2.0, radians));
+    }
+
+    #[test]
+    fn auto_dwell_config_keeps_other_defaults() {
+        let profile = ScannerProfile::from_kpps(45.0);
+        let hz = 45_000;
+        let conf = InterpolationConfig::auto_dwell(profile, hz);
+        let default = InterpolationConfig::default();
+        assert_eq!(conf.radians_per_point, profile.radians_per_point(hz));
+        assert_eq!(conf.distance_per_point, default.distance_per_point);
+        assert_eq!(conf.blank_delay_points, default.blank_delay_points);
+    }
+
+    fn requester() -> Requester {
+        Requester {
+            last_frame_point: None,
+            raw_points: vec![],
+            blank_points: vec![],
+            point_index: 0,
+            point_hz: 0,
+            point_clock_base: (0, Duration::ZERO),
+        }
+    }
+
+    fn buffer(point_hz: u32, points: &[RawPoint]) -> Buffer {
+        Buffer {
+            point_hz,
+            latency_points: points.len() as u32,
+            points: points.to_vec().into_boxed_slice(),
+        }
+    }
+
+    // Apply a `point_fn` that records each `PointInfo` and sets each colour to `color`.
+    fn apply_recording(
+        requester: &mut Requester,
+        buffer: &mut Buffer,
+        color: Rgb,
+    ) -> Vec<PointInfo> {
+        let mut infos = vec![];
+        let point_fn = move |infos: &mut Vec<PointInfo>, info: &PointInfo, rgb: &mut Rgb| {
+            infos.push(*info);
+            *rgb = color;
+        };
+        requester.apply_point_fn(&mut infos, &point_fn, buffer);
+        infos
+    }
+
+    #[test]
+    fn point_fn_skips_blank_points() {
+        let lit = RawPoint::new([0.5, 0.0], WHITE);
+        let blank = RawPoint::centered_blank();
+        let mut buffer = buffer(1_000, &[blank, lit, blank, lit]);
+        let infos = apply_recording(&mut requester(), &mut buffer, [0.5; 3]);
+        let indices: Vec<_> = infos.iter().map(|info| info.index).collect();
+        assert_eq!(indices, [1, 3]);
+        assert_eq!(infos[0].position, [0.5, 0.0]);
+        assert!(buffer[0].is_blank());
+        assert!(buffer[2].is_blank());
+        assert_eq!(buffer[1].color, [0.5; 3]);
+    }
+
+    #[test]
+    fn point_fn_colours_are_clamped() {
+        let lit = RawPoint::new([0.0, 0.0], WHITE);
+        let mut buffer = buffer(1_000, &[lit]);
+        apply_recording(&mut requester(), &mut buffer, [2.0, -1.0, 0.25]);
+        assert_eq!(buffer[0].color, [1.0, 0.0, 0.25]);
+    }
+
+    #[test]
+    fn point_clock_advances_across_buffers() {
+        let lit = RawPoint::new([0.0, 0.0], WHITE);
+        let mut requester = requester();
+        let mut first = buffer(30_000, &[lit; 3]);
+        let mut second = buffer(30_000, &[lit; 3]);
+        apply_recording(&mut requester, &mut first, WHITE);
+        let infos = apply_recording(&mut requester, &mut second, WHITE);
+        let indices: Vec<_> = infos.iter().map(|info| info.index).collect();
+        assert_eq!(indices, [3, 4, 5]);
+        assert_eq!(infos[0].time, Duration::from_micros(100));
+        assert!(infos.iter().all(|info| info.point_hz == 30_000));
+    }
+
+    #[test]
+    fn point_time_does_not_drift() {
+        // 1s / 30_000 is not a whole number of nanoseconds, so accumulating it would drift.
+        let mut requester = requester();
+        requester.point_hz = 30_000;
+        assert_eq!(requester.point_time(30_000 * 60), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn point_time_continues_across_rate_changes() {
+        let lit = RawPoint::new([0.0, 0.0], WHITE);
+        let mut requester = requester();
+        let mut slow = buffer(1_000, &[lit; 10]);
+        let mut fast = buffer(2_000, &[lit; 2]);
+        apply_recording(&mut requester, &mut slow, WHITE);
+        let infos = apply_recording(&mut requester, &mut fast, WHITE);
+        assert_eq!(infos[0].time, Duration::from_millis(10));
+        assert_eq!(infos[1].time, Duration::from_micros(10_500));
+        assert_eq!(infos[1].point_hz, 2_000);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod frame;
 pub mod raw;
+pub mod thermal;
 
 /// The default rate at which the DAC should request points per second.
 pub const DEFAULT_POINT_HZ: u32 = 10_000;
@@ -31,6 +32,10 @@ pub struct Builder {
     ///
     /// If this value is `None`, no timeout will be applied and the stream will wait forever.
     pub tcp_timeout: Option<std::time::Duration>,
+    /// Limits the average power of each colour channel to protect the projector's diodes.
+    ///
+    /// By default, no guard is applied.
+    pub thermal_guard: Option<thermal::ThermalGuard>,
 }
 
 /// Given a DAC point rate and a desired frame rate, determine how many points to generate per
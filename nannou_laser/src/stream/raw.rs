@@ -1,7 +1,9 @@
+use crate::stream::thermal::{self, ThermalGuard, ThermalTracker};
 use crate::util::{clamp, map_range};
 use crate::Inner as ApiInner;
-use crate::{DetectedDac, RawPoint};
+use crate::{idn, DacConfig, DacId, DetectedDac, RawPoint};
 use std::io;
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
 use std::sync::atomic::{self, AtomicBool};
 use std::sync::{mpsc, Arc, Mutex};
@@ -19,6 +21,12 @@ impl<M, F> RenderFn<M> for F where F: Fn(&mut M, &mut Buffer) {}
 pub trait StreamErrorFn<M>: Fn(&mut M, &StreamError, &mut StreamErrorAction) {}
 impl<M, F> StreamErrorFn<M> for F where F: Fn(&mut M, &StreamError, &mut StreamErrorAction) {}
 
+/// The function called when a non-fatal warning occurs on the stream thread.
+///
+/// Warnings have no effect on the stream, which continues uninterrupted.
+pub trait StreamWarningFn<M>: Fn(&mut M, &StreamWarning) {}
+impl<M, F> StreamWarningFn<M> for F where F: Fn(&mut M, &StreamWarning) {}
+
 /// A clone-able handle around a raw laser stream.
 #[derive(Clone)]
 pub struct Stream<M> {
@@ -35,6 +43,11 @@ pub struct Stream<M> {
 struct State {
     point_hz: u32,
     latency_points: u32,
+    // Whether the `point_hz` and `latency_points` were specified by the user, in which case they
+    // take precedence over the DAC's config.
+    point_hz_specified: bool,
+    latency_points_specified: bool,
+    thermal_guard: Option<ThermalGuard>,
 }
 
 // Data shared between each `Stream` handle to a single stream.
@@ -63,13 +76,14 @@ pub struct Buffer {
 }
 
 /// A type allowing to build a raw laser stream.
-pub struct Builder<M, F, E = DefaultStreamErrorFn<M>> {
+pub struct Builder<M, F, E = DefaultStreamErrorFn<M>, W = DefaultStreamWarningFn<M>> {
     /// The laser API inner state, used to find a DAC during `build` if one isn't specified.
     pub(crate) api_inner: Arc<super::super::Inner>,
     pub builder: super::Builder,
     pub model: M,
     pub render: F,
     pub stream_error: E,
+    pub stream_warning: W,
 }
 
 /// The default stream error function type expected if none are specified.
@@ -77,6 +91,9 @@ pub struct Builder<M, F, E = DefaultStreamErrorFn<M>> {
 /// By default, a stream will attempt to reconnect three times before closing the thread.
 pub type DefaultStreamErrorFn<M> = fn(&mut M, &StreamError, &mut StreamErrorAction);
 
+/// The default stream warning function type expected if none are specified.
+pub type DefaultStreamWarningFn<M> = fn(&mut M, &StreamWarning);
+
 // The type used for sending state updates from the stream handle thread to the laser thread.
 type StateUpdate = Box<dyn FnMut(&mut State) + 'static + Send>;
 
@@ -91,6 +108,31 @@ pub enum StreamError {
         #[from]
         err: EtherDreamStreamError,
     },
+    #[error("an IDN DAC stream error occurred: {err}")]
+    IdnStream {
+        #[from]
+        err: IdnStreamError,
+    },
+}
+
+/// Non-fatal warnings that may occur while running a laser stream.
+#[derive(Debug, Error)]
+pub enum StreamWarning {
+    /// The `ThermalGuard` has begun limiting the brightness of a channel.
+    #[error(
+        "the {channel} channel exceeded its duty-cycle budget \
+         (average {average:.3} > {budget:.3}), scaling brightness by {scale:.3}"
+    )]
+    ThermalBudgetExceeded {
+        /// The channel whose brightness is being limited.
+        channel: thermal::Channel,
+        /// The average power requested of the channel over the guard's window.
+        average: f32,
+        /// The channel's duty-cycle budget.
+        budget: f32,
+        /// The scale now applied to the channel's brightness.
+        scale: f32,
+    },
 }
 
 /// Errors that may occur while creating a node crate.
@@ -137,6 +179,35 @@ pub enum EtherDreamStreamError {
     },
 }
 
+/// Errors that may occur while streaming to an IDN DAC.
+#[derive(Debug, Error)]
+pub enum IdnStreamError {
+    #[error("IDN DAC detection failed: {err}")]
+    FailedToDetectDacs {
+        #[source]
+        err: io::Error,
+        /// The number of DAC detection attempts so far.
+        attempts: u32,
+    },
+    #[error("failed to connect the IDN stream (attempt {attempts}): {err}")]
+    FailedToConnectStream {
+        #[source]
+        err: io::Error,
+        /// The number of connection attempts so far.
+        attempts: u32,
+    },
+    #[error("failed to submit data over the IDN stream: {err}")]
+    FailedToSubmitData {
+        #[source]
+        err: io::Error,
+    },
+    #[error("failed to close the IDN stream: {err}")]
+    FailedToCloseStream {
+        #[source]
+        err: io::Error,
+    },
+}
+
 /// An action to perform in response to a `StreamError` occurring.
 #[derive(Clone, Debug)]
 pub enum StreamErrorAction {
@@ -163,8 +234,11 @@ impl<M> Stream<M> {
     ///
     /// By default this value is `stream::DEFAULT_POINT_HZ`.
     pub fn set_point_hz(&self, point_hz: u32) -> Result<(), mpsc::SendError<()>> {
-        self.send_raw_state_update(move |state| state.point_hz = point_hz)
-            .map_err(|_| mpsc::SendError(()))
+        self.send_raw_state_update(move |state| {
+            state.point_hz = point_hz;
+            state.point_hz_specified = true;
+        })
+        .map_err(|_| mpsc::SendError(()))
     }
 
     /// The maximum latency specified as a number of points.
@@ -174,7 +248,21 @@ impl<M> Stream<M> {
     ///
     /// This value should be no greaterthan the DAC's `buffer_capacity`.
     pub fn set_latency_points(&self, points: u32) -> Result<(), mpsc::SendError<()>> {
-        self.send_raw_state_update(move |state| state.latency_points = points)
+        self.send_raw_state_update(move |state| {
+            state.latency_points = points;
+            state.latency_points_specified = true;
+        })
+        .map_err(|_| mpsc::SendError(()))
+    }
+
+    /// Limit the average power of each colour channel to protect the projector's diodes.
+    ///
+    /// Specify `None` to disable the guard.
+    pub fn set_thermal_guard(
+        &self,
+        guard: Option<ThermalGuard>,
+    ) -> Result<(), mpsc::SendError<()>> {
+        self.send_raw_state_update(move |state| state.thermal_guard = guard)
             .map_err(|_| mpsc::SendError(()))
     }
 
@@ -306,7 +394,7 @@ impl Buffer {
     }
 }
 
-impl<M, F, E> Builder<M, F, E> {
+impl<M, F, E, W> Builder<M, F, E, W> {
     /// The DAC with which the stream should be established.
     ///
     /// If none is specified, the stream will associate itself with the first DAC detecged on the
@@ -351,15 +439,28 @@ impl<M, F, E> Builder<M, F, E> {
         self
     }
 
+    /// Limit the average power of each colour channel to protect the projector's diodes.
+    ///
+    /// Each channel whose average power over the guard's window exceeds its duty-cycle budget has
+    /// its brightness scaled down, and a `StreamWarning::ThermalBudgetExceeded` warning is
+    /// delivered to the stream warning function.
+    ///
+    /// By default, no guard is applied.
+    pub fn thermal_guard(mut self, guard: ThermalGuard) -> Self {
+        self.builder.thermal_guard = Some(guard);
+        self
+    }
+
     /// Specify a function that allows for handling errors that occur on the TCP stream thread.
     ///
     /// If this method is not called, the `default_stream_error_fn` is used by default.
-    pub fn stream_error<E2>(self, stream_error: E2) -> Builder<M, F, E2> {
+    pub fn stream_error<E2>(self, stream_error: E2) -> Builder<M, F, E2, W> {
         let Builder {
             api_inner,
             builder,
             model,
             render,
+            stream_warning,
             ..
         } = self;
         Builder {
@@ -368,6 +469,32 @@ impl<M, F, E> Builder<M, F, E> {
             model,
             render,
             stream_error,
+            stream_warning,
+        }
+    }
+
+    /// Specify a function that allows for handling non-fatal warnings that occur on the stream
+    /// thread, e.g. `StreamWarning::ThermalBudgetExceeded`.
+    ///
+    /// Warnings have no effect on the stream, which continues uninterrupted.
+    ///
+    /// If this method is not called, the `default_stream_warning_fn` is used by default.
+    pub fn stream_warning<W2>(self, stream_warning: W2) -> Builder<M, F, E, W2> {
+        let Builder {
+            api_inner,
+            builder,
+            model,
+            render,
+            stream_error,
+            ..
+        } = self;
+        Builder {
+            api_inner,
+            builder,
+            model,
+            render,
+            stream_error,
+            stream_warning,
         }
     }
 
@@ -380,6 +507,7 @@ impl<M, F, E> Builder<M, F, E> {
         M: 'static + Send,
         F: 'static + RenderFn<M> + Send,
         E: 'static + StreamErrorFn<M> + Send,
+        W: 'static + StreamWarningFn<M> + Send,
     {
         let Builder {
             api_inner,
@@ -387,6 +515,7 @@ impl<M, F, E> Builder<M, F, E> {
             model,
             render,
             stream_error,
+            stream_warning,
         } = self;
 
         // Prepare the model for sharing between the laser thread and stream handle.
@@ -408,6 +537,9 @@ impl<M, F, E> Builder<M, F, E> {
         let state = Arc::new(Mutex::new(State {
             point_hz,
             latency_points,
+            point_hz_specified: builder.point_hz.is_some(),
+            latency_points_specified: builder.latency_points.is_some(),
+            thermal_guard: builder.thermal_guard,
         }));
 
         // Retrieve whether or not the user specified a detected DAC.
@@ -433,6 +565,7 @@ impl<M, F, E> Builder<M, F, E> {
                     &model_2,
                     render,
                     stream_error,
+                    stream_warning,
                     &s_rx,
                     &m_rx,
                     &is_closed2,
@@ -490,7 +623,7 @@ pub fn default_latency_points(point_hz: u32) -> u32 {
 }
 
 // The function to run on the laser stream thread.
-fn run_laser_stream<M, F, E>(
+fn run_laser_stream<M, F, E, W>(
     api_inner: &ApiInner,
     mut maybe_dac: Option<DetectedDac>,
     tcp_timeout: Option<Duration>,
@@ -498,6 +631,7 @@ fn run_laser_stream<M, F, E>(
     model: &Arc<Mutex<Option<M>>>,
     render: F,
     stream_error: E,
+    stream_warning: W,
     state_update_rx: &mpsc::Receiver<StateUpdate>,
     model_update_rx: &mpsc::Receiver<ModelUpdate<M>>,
     is_closed: &AtomicBool,
@@ -505,6 +639,7 @@ fn run_laser_stream<M, F, E>(
 where
     F: RenderFn<M>,
     E: StreamErrorFn<M>,
+    W: StreamWarningFn<M>,
 {
     // A small macro that locks a mutex and evaluates to its guard.
     // Returns from the function with the given error if the lock cannot be acquired.
@@ -521,6 +656,8 @@ where
     let mut detect_attempts = 0;
     let mut detect_timeout = tcp_timeout;
     let mut redetect_dac = false;
+    // Tracks the power of each channel across reconnections.
+    let mut thermal = ThermalTracker::default();
     while !is_closed.load(atomic::Ordering::Relaxed) {
         // If the stream action signalled to redetect the DAC, try to redetect the DAC if a
         // specific DAC was specified by the user.
@@ -529,15 +666,21 @@ where
             if let Some(ref mut dac) = maybe_dac {
                 let dac_id = dac.id();
                 detect_attempts += 1;
-                *dac = match api_inner.detect_dac(dac_id) {
+                *dac = match api_inner.detect_dac(dac_id, detect_timeout) {
                     Ok(dac) => {
                         detect_attempts = 0;
                         dac
                     }
                     Err(err) => {
                         let attempts = detect_attempts;
-                        let err = EtherDreamStreamError::FailedToDetectDacs { err, attempts };
-                        let err = StreamError::from(err);
+                        let err: StreamError = match dac_id {
+                            DacId::EtherDream { .. } => {
+                                EtherDreamStreamError::FailedToDetectDacs { err, attempts }.into()
+                            }
+                            DacId::Idn { .. } => {
+                                IdnStreamError::FailedToDetectDacs { err, attempts }.into()
+                            }
+                        };
                         let mut guard = lock_or_return_err!(model, err);
                         let mut model = guard.take().unwrap();
                         let mut action = StreamErrorAction::default();
@@ -603,17 +746,38 @@ where
         };
 
         // Connect and run the laser stream.
-        match run_laser_stream_tcp_loop(
-            &dac,
-            tcp_timeout,
-            &state,
-            &model,
-            &render,
-            &state_update_rx,
-            &model_update_rx,
-            &is_closed,
-            &mut connect_attempts,
-        ) {
+        let config = api_inner.dac_config(&dac.id());
+        let result = match dac {
+            DetectedDac::EtherDream { .. } => run_laser_stream_tcp_loop(
+                &dac,
+                config,
+                tcp_timeout,
+                &state,
+                &model,
+                &render,
+                &stream_warning,
+                &state_update_rx,
+                &model_update_rx,
+                &is_closed,
+                &mut connect_attempts,
+                &mut thermal,
+            ),
+            DetectedDac::Idn { source_addr, .. } => run_laser_stream_idn_loop(
+                &dac,
+                source_addr,
+                config,
+                &state,
+                &model,
+                &render,
+                &stream_warning,
+                &state_update_rx,
+                &model_update_rx,
+                &is_closed,
+                &mut connect_attempts,
+                &mut thermal,
+            ),
+        };
+        match result {
             Ok(()) => break,
             Err(err) => {
                 let mut guard = lock_or_return_err!(model, err);
@@ -637,26 +801,30 @@ where
 }
 
 // Attempts to connect to the DAC via TCP and enters the stream loop.
-fn run_laser_stream_tcp_loop<M, F>(
+fn run_laser_stream_tcp_loop<M, F, W>(
     dac: &DetectedDac,
+    config: Option<DacConfig>,
     tcp_timeout: Option<Duration>,
     state: &Arc<Mutex<State>>,
     model: &Arc<Mutex<Option<M>>>,
     render: F,
+    stream_warning: W,
     state_update_rx: &mpsc::Receiver<StateUpdate>,
     model_update_rx: &mpsc::Receiver<ModelUpdate<M>>,
     is_closed: &AtomicBool,
     connection_attempts: &mut u32,
+    thermal: &mut ThermalTracker,
 ) -> Result<(), StreamError>
 where
     F: RenderFn<M>,
+    W: StreamWarningFn<M>,
 {
-    // Currently only ether dream is supported, so retrieve the broadcast and addr.
     let (broadcast, src_addr) = match dac {
         DetectedDac::EtherDream {
             broadcast,
             source_addr,
         } => (broadcast, source_addr),
+        _ => unreachable!("the TCP loop only supports ether dream DACs"),
     };
 
     // A buffer for collecting model updates.
@@ -689,6 +857,11 @@ where
         .submit()
         .map_err(|err| EtherDreamStreamError::FailedToPrepareStream { err })?;
 
+    // Apply the DAC's latency settings where the user has not specified their own.
+    if let Some(ref config) = config {
+        apply_config_latency(state, config);
+    }
+
     let dac_max_point_hz = dac.max_point_hz();
 
     // Get the initial point hz by clamping via the DAC's maximum point rate.
@@ -774,6 +947,17 @@ where
             *guard = Some(m);
         }
 
+        // Apply the DAC's calibration.
+        if let Some(ref config) = config {
+            config.apply(&mut buffer);
+        }
+
+        // Limit the power of each channel to protect the diodes.
+        if let Some(ref guard) = state.thermal_guard {
+            let warnings = thermal.apply(guard, point_hz, &mut buffer);
+            emit_warnings(model, &stream_warning, warnings);
+        }
+
         // Retrieve the points.
         ether_dream_points.extend(buffer.iter().cloned().map(point_to_ether_dream_point));
 
@@ -799,6 +983,162 @@ where
     Ok(())
 }
 
+// Opens a UDP stream to the IDN DAC and enters the stream loop.
+//
+// IDN devices provide no feedback on their buffer fullness, so the number of points queued on the
+// device is estimated from the number of points sent and the time elapsed since.
+fn run_laser_stream_idn_loop<M, F, W>(
+    dac: &DetectedDac,
+    src_addr: SocketAddr,
+    config: Option<DacConfig>,
+    state: &Arc<Mutex<State>>,
+    model: &Arc<Mutex<Option<M>>>,
+    render: F,
+    stream_warning: W,
+    state_update_rx: &mpsc::Receiver<StateUpdate>,
+    model_update_rx: &mpsc::Receiver<ModelUpdate<M>>,
+    is_closed: &AtomicBool,
+    connection_attempts: &mut u32,
+    thermal: &mut ThermalTracker,
+) -> Result<(), StreamError>
+where
+    F: RenderFn<M>,
+    W: StreamWarningFn<M>,
+{
+    // A buffer for collecting model updates.
+    let mut pending_model_updates: Vec<ModelUpdate<M>> = Vec::new();
+
+    let mut stream = match idn::Stream::connect(src_addr) {
+        Ok(stream) => stream,
+        Err(err) => {
+            *connection_attempts += 1;
+            let attempts = *connection_attempts;
+            return Err(IdnStreamError::FailedToConnectStream { err, attempts }.into());
+        }
+    };
+    *connection_attempts = 0;
+
+    // Apply the DAC's latency settings where the user has not specified their own.
+    if let Some(ref config) = config {
+        apply_config_latency(state, config);
+    }
+
+    // The estimated number of points queued on the device and when it was last updated.
+    let mut queued_points = 0.0;
+    let mut last_update = stream.elapsed();
+
+    while !is_closed.load(atomic::Ordering::Relaxed) {
+        // Collect any pending updates.
+        pending_model_updates.extend(model_update_rx.try_iter());
+        // If there are some updates available, take the lock and apply them.
+        if !pending_model_updates.is_empty() {
+            if let Ok(mut guard) = model.lock() {
+                let mut model = guard.take().unwrap();
+                for mut update in pending_model_updates.drain(..) {
+                    update(&mut model);
+                }
+                *guard = Some(model);
+            }
+        }
+
+        // Check for updates and retrieve a copy of the state.
+        let state = {
+            let mut state = state.lock().expect("failed to acquare raw state lock");
+            for mut state_update in state_update_rx.try_iter() {
+                (*state_update)(&mut state);
+            }
+            state.clone()
+        };
+
+        // Clamp the point hz and latency by the DAC's limits.
+        let point_hz = std::cmp::min(state.point_hz, dac.max_point_hz());
+        let latency_points = std::cmp::min(state.latency_points, dac.buffer_capacity());
+
+        // Estimate how many of the previously sent points have since been played.
+        let now = stream.elapsed();
+        let played = (now - last_update).as_secs_f64() * point_hz as f64;
+        queued_points = (queued_points - played).max(0.0);
+        last_update = now;
+
+        // Wait until at least a millisecond's worth of points are needed to avoid flooding the
+        // network with tiny packets.
+        let n_points = latency_points.saturating_sub(queued_points.ceil() as u32) as usize;
+        let min_points = std::cmp::max(point_hz as usize / 1_000, 1);
+        if n_points < min_points {
+            std::thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+
+        // The buffer that the user will write to. TODO: Re-use this points buffer.
+        let mut buffer = Buffer {
+            point_hz,
+            latency_points: latency_points as _,
+            points: vec![RawPoint::centered_blank(); n_points].into_boxed_slice(),
+        };
+
+        // Request the points from the user.
+        if let Ok(mut guard) = model.lock() {
+            let mut m = guard.take().unwrap();
+            render(&mut m, &mut buffer);
+            *guard = Some(m);
+        }
+
+        // Apply the DAC's calibration.
+        if let Some(ref config) = config {
+            config.apply(&mut buffer);
+        }
+
+        // Limit the power of each channel to protect the diodes.
+        if let Some(ref guard) = state.thermal_guard {
+            let warnings = thermal.apply(guard, point_hz, &mut buffer);
+            emit_warnings(model, &stream_warning, warnings);
+        }
+
+        // Submit the points.
+        stream
+            .send_points(&buffer, point_hz)
+            .map_err(|err| IdnStreamError::FailedToSubmitData { err })?;
+        queued_points += buffer.len() as f64;
+    }
+
+    stream
+        .close()
+        .map_err(|err| IdnStreamError::FailedToCloseStream { err })?;
+
+    Ok(())
+}
+
+// Deliver the given warnings to the stream warning function.
+fn emit_warnings<M, W>(model: &Mutex<Option<M>>, stream_warning: &W, warnings: Vec<StreamWarning>)
+where
+    W: StreamWarningFn<M>,
+{
+    if warnings.is_empty() {
+        return;
+    }
+    if let Ok(mut guard) = model.lock() {
+        let mut m = guard.take().unwrap();
+        for warning in &warnings {
+            stream_warning(&mut m, warning);
+        }
+        *guard = Some(m);
+    }
+}
+
+// Apply the DAC's configured latency settings where the user has not specified their own.
+fn apply_config_latency(state: &Mutex<State>, config: &DacConfig) {
+    let mut state = state.lock().expect("failed to acquire raw state lock");
+    if let (Some(hz), false) = (config.point_hz, state.point_hz_specified) {
+        state.point_hz = hz;
+        if !state.latency_points_specified {
+            state.latency_points = default_latency_points(hz);
+        }
+    }
+    if let (Some(points), false) = (config.latency_points, state.latency_points_specified) {
+        state.latency_points = points;
+    }
+}
+
 // The number of remaining points in the DAC.
 fn dac_remaining_buffer_capacity(dac: &ether_dream::dac::Dac) -> u16 {
     dac.buffer_capacity - 1 - dac.status.buffer_fullness
@@ -884,26 +1224,46 @@ pub fn default_stream_error_fn<M>(
         let timeout = Some(Duration::from_secs(2));
         StreamErrorAction::RedetectDac { timeout }
     }
-    let ether_dream_err = match *err {
-        StreamError::EtherDreamStream { ref err } => err,
-    };
-    *action = match *ether_dream_err {
-        EtherDreamStreamError::FailedToDetectDacs { attempts, .. } if attempts < 3 => {
-            redetect_dac_action()
-        }
-        EtherDreamStreamError::FailedToConnectStream { attempts, .. } if attempts < 3 => {
-            std::thread::sleep(std::time::Duration::from_millis(16));
-            StreamErrorAction::ReattemptConnect
-        }
-        EtherDreamStreamError::FailedToConnectStream { attempts, .. } if attempts == 3 => {
-            redetect_dac_action()
-        }
-        EtherDreamStreamError::FailedToPrepareStream { .. }
-        | EtherDreamStreamError::FailedToBeginStream { .. }
-        | EtherDreamStreamError::FailedToSubmitData { .. }
-        | EtherDreamStreamError::FailedToSubmitPointRate { .. } => {
-            StreamErrorAction::ReattemptConnect
-        }
-        _ => StreamErrorAction::CloseThread,
+    fn reattempt_connect_action() -> StreamErrorAction {
+        std::thread::sleep(std::time::Duration::from_millis(16));
+        StreamErrorAction::ReattemptConnect
+    }
+    *action = match *err {
+        StreamError::EtherDreamStream { ref err } => match *err {
+            EtherDreamStreamError::FailedToDetectDacs { attempts, .. } if attempts < 3 => {
+                redetect_dac_action()
+            }
+            EtherDreamStreamError::FailedToConnectStream { attempts, .. } if attempts < 3 => {
+                reattempt_connect_action()
+            }
+            EtherDreamStreamError::FailedToConnectStream { attempts, .. } if attempts == 3 => {
+                redetect_dac_action()
+            }
+            EtherDreamStreamError::FailedToPrepareStream { .. }
+            | EtherDreamStreamError::FailedToBeginStream { .. }
+            | EtherDreamStreamError::FailedToSubmitData { .. }
+            | EtherDreamStreamError::FailedToSubmitPointRate { .. } => {
+                StreamErrorAction::ReattemptConnect
+            }
+            _ => StreamErrorAction::CloseThread,
+        },
+        StreamError::IdnStream { ref err } => match *err {
+            IdnStreamError::FailedToDetectDacs { attempts, .. } if attempts < 3 => {
+                redetect_dac_action()
+            }
+            IdnStreamError::FailedToConnectStream { attempts, .. } if attempts < 3 => {
+                reattempt_connect_action()
+            }
+            IdnStreamError::FailedToConnectStream { attempts, .. } if attempts == 3 => {
+                redetect_dac_action()
+            }
+            IdnStreamError::FailedToSubmitData { .. } => StreamErrorAction::ReattemptConnect,
+            _ => StreamErrorAction::CloseThread,
+        },
     };
 }
+
+/// The default function used for the `stream_warning` function if none is specified.
+///
+/// Warnings are ignored.
+pub fn default_stream_warning_fn<M>(_model: &mut M, _warning: &StreamWarning) {}
@@ -0,0 +1,315 @@
+//! A guard against exceeding the duty-cycle of a projector's laser diodes.
+//!
+//! Laser diodes are often rated for a maximum average output power that is lower than their peak
+//! output power. Content that holds a channel at full brightness for minutes at a time, e.g. a
+//! static beam left running during a long unattended installation, may overheat a diode that would
+//! otherwise cope fine with the same peak brightness for shorter periods.
+//!
+//! The `ThermalGuard` tracks the average power requested of each colour channel over a sliding
+//! window and scales the channel's brightness down so that its average does not exceed the
+//! configured budget. A `StreamWarning::ThermalBudgetExceeded` warning is delivered to the stream
+//! warning callback each time a channel becomes limited.
+
+use crate::point::Rgb;
+use crate::stream::raw::StreamWarning;
+use crate::RawPoint;
+use std::fmt;
+use std::time::Duration;
+
+/// The default duration over which the average power of each channel is measured.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The default lowest scale that the guard will apply to a channel's brightness.
+pub const DEFAULT_MIN_SCALE: f32 = 0.0;
+
+// The number of segments into which the window is divided.
+//
+// The window slides one segment at a time.
+const SEGMENTS: usize = 32;
+
+/// Limits the average power of each colour channel over a sliding window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThermalGuard {
+    /// The duration over which the average power of each channel is measured.
+    pub window: Duration,
+    /// The maximum average power of the red, green and blue channels respectively as a fraction
+    /// of full brightness in the range `0.0..=1.0`.
+    pub duty_cycle: Rgb,
+    /// The lowest scale that will be applied to a channel's brightness, in the range
+    /// `0.0..=1.0`.
+    ///
+    /// Values above `0.0` keep content visible at the cost of allowing the budget to be exceeded.
+    pub min_scale: f32,
+}
+
+/// One of the colour channels of a laser projector.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+// Tracks the recent power of each channel on the laser thread.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ThermalTracker {
+    // The sum of requested power and number of points within each segment.
+    segments: [Segment; SEGMENTS],
+    // The index of the segment currently being written.
+    segment: usize,
+    // The number of points per segment, or `0` if the tracker has yet to be initialised.
+    segment_points: u32,
+    // The sum of all segments.
+    total: Segment,
+    // The scale last applied to each channel.
+    scale: Rgb,
+    // The window and point rate with which the segments were initialised.
+    window: Duration,
+    point_hz: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Segment {
+    power: [f64; 3],
+    points: u32,
+}
+
+impl ThermalGuard {
+    /// A guard limiting the average power of all channels to the given duty-cycle, in the range
+    /// `0.0..=1.0`.
+    pub fn new(duty_cycle: f32) -> Self {
+        ThermalGuard {
+            window: DEFAULT_WINDOW,
+            duty_cycle: [duty_cycle; 3],
+            min_scale: DEFAULT_MIN_SCALE,
+        }
+    }
+
+    /// The duration over which the average power of each channel is measured.
+    ///
+    /// By default, this value is `thermal::DEFAULT_WINDOW`.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// The maximum average power of the red, green and blue channels respectively.
+    pub fn duty_cycle(mut self, rgb: Rgb) -> Self {
+        self.duty_cycle = rgb;
+        self
+    }
+
+    /// The lowest scale that will be applied to a channel's brightness.
+    ///
+    /// By default, this value is `thermal::DEFAULT_MIN_SCALE`.
+    pub fn min_scale(mut self, min_scale: f32) -> Self {
+        self.min_scale = min_scale;
+        self
+    }
+}
+
+impl Channel {
+    /// All channels in the order in which they appear within an `Rgb` colour.
+    pub const ALL: [Channel; 3] = [Channel::Red, Channel::Green, Channel::Blue];
+}
+
+impl ThermalTracker {
+    // Record the given points and scale their colours to keep each channel within budget.
+    //
+    // Returns a warning for each channel that has become limited since the previous call.
+    pub(crate) fn apply(
+        &mut self,
+        guard: &ThermalGuard,
+        point_hz: u32,
+        points: &mut [RawPoint],
+    ) -> Vec<StreamWarning> {
+        // Restart the measurement if the window or point rate changed.
+        if self.segment_points == 0 || self.window != guard.window || self.point_hz != point_hz {
+            let window_points = guard.window.as_secs_f64() * point_hz as f64;
+            *self = ThermalTracker {
+                segment_points: std::cmp::max((window_points / SEGMENTS as f64) as u32, 1),
+                scale: [1.0; 3],
+                window: guard.window,
+                point_hz,
+                ..Default::default()
+            };
+        }
+
+        // Record the requested power, sliding the window along one segment at a time.
+        for p in points.iter() {
+            if self.segments[self.segment].points == self.segment_points {
+                self.segment = (self.segment + 1) % SEGMENTS;
+                let old = std::mem::take(&mut self.segments[self.segment]);
+                self.total.points -= old.points;
+                for ch in 0..3 {
+                    self.total.power[ch] -= old.power[ch];
+                }
+            }
+            let segment = &mut self.segments[self.segment];
+            segment.points += 1;
+            self.total.points += 1;
+            for ch in 0..3 {
+                let power = p.color[ch].max(0.0) as f64;
+                segment.power[ch] += power;
+                self.total.power[ch] += power;
+            }
+        }
+
+        // Determine the scale that brings each channel's average within budget.
+        let mut warnings = Vec::new();
+        if self.total.points == 0 {
+            return warnings;
+        }
+        for (ch, &channel) in Channel::ALL.iter().enumerate() {
+            let average = (self.total.power[ch] / self.total.points as f64) as f32;
+            let budget = guard.duty_cycle[ch];
+            let scale = if average > budget {
+                (budget / average).max(guard.min_scale).min(1.0)
+            } else {
+                1.0
+            };
+            if scale < 1.0 && self.scale[ch] >= 1.0 {
+                warnings.push(StreamWarning::ThermalBudgetExceeded {
+                    channel,
+                    average,
+                    budget,
+                    scale,
+                });
+            }
+            self.scale[ch] = scale;
+        }
+
+        // Apply the scale to all points.
+        if self.scale != [1.0; 3] {
+            for p in points.iter_mut() {
+                for ch in 0..3 {
+                    p.color[ch] *= self.scale[ch];
+                }
+            }
+        }
+
+        warnings
+    }
+}
+
+impl Default for ThermalGuard {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Channel::Red => "red",
+            Channel::Green => "green",
+            Channel::Blue => "blue",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A rate at which a one second window spans 100 points per segment.
+    const POINT_HZ: u32 = SEGMENTS as u32 * 100;
+
+    fn guard(duty_cycle: f32) -> ThermalGuard {
+        ThermalGuard::new(duty_cycle).window(Duration::from_secs(1))
+    }
+
+    fn points(n: usize, color: Rgb) -> Vec<RawPoint> {
+        vec![RawPoint::new([0.0; 2], color); n]
+    }
+
+    #[test]
+    fn within_budget_is_unchanged() {
+        let mut tracker = ThermalTracker::default();
+        let mut buffer = points(POINT_HZ as usize, [0.4; 3]);
+        let warnings = tracker.apply(&guard(0.5), POINT_HZ, &mut buffer);
+        assert!(warnings.is_empty());
+        assert!(buffer.iter().all(|p| p.color == [0.4; 3]));
+    }
+
+    #[test]
+    fn exceeding_budget_scales_channel() {
+        let mut tracker = ThermalTracker::default();
+        let guard = guard(1.0).duty_cycle([0.5, 1.0, 1.0]);
+        let mut buffer = points(POINT_HZ as usize, [1.0; 3]);
+        let warnings = tracker.apply(&guard, POINT_HZ, &mut buffer);
+        assert_eq!(warnings.len(), 1);
+        match warnings[0] {
+            StreamWarning::ThermalBudgetExceeded {
+                channel,
+                average,
+                budget,
+                scale,
+            } => {
+                assert_eq!(channel, Channel::Red);
+                assert_eq!(average, 1.0);
+                assert_eq!(budget, 0.5);
+                assert_eq!(scale, 0.5);
+            }
+        }
+        assert!(buffer.iter().all(|p| p.color == [0.5, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn warns_once_while_limited() {
+        let mut tracker = ThermalTracker::default();
+        let guard = guard(0.5);
+        let mut buffer = points(POINT_HZ as usize, [1.0; 3]);
+        assert_eq!(tracker.apply(&guard, POINT_HZ, &mut buffer).len(), 3);
+        let mut buffer = points(100, [1.0; 3]);
+        assert!(tracker.apply(&guard, POINT_HZ, &mut buffer).is_empty());
+        assert!(buffer.iter().all(|p| p.color == [0.5; 3]));
+    }
+
+    #[test]
+    fn min_scale_bounds_scale() {
+        let mut tracker = ThermalTracker::default();
+        let guard = guard(0.25).min_scale(0.5);
+        let mut buffer = points(POINT_HZ as usize, [1.0; 3]);
+        tracker.apply(&guard, POINT_HZ, &mut buffer);
+        assert!(buffer.iter().all(|p| p.color == [0.5; 3]));
+    }
+
+    #[test]
+    fn recovers_once_window_slides_past() {
+        let mut tracker = ThermalTracker::default();
+        let guard = guard(0.5);
+        let mut buffer = points(POINT_HZ as usize, [1.0; 3]);
+        assert_eq!(tracker.apply(&guard, POINT_HZ, &mut buffer).len(), 3);
+
+        // A full window of darkness brings the average back within budget.
+        let mut buffer = points(POINT_HZ as usize * 2, [0.0; 3]);
+        assert!(tracker.apply(&guard, POINT_HZ, &mut buffer).is_empty());
+        let mut buffer = points(10, [1.0; 3]);
+        assert!(tracker.apply(&guard, POINT_HZ, &mut buffer).is_empty());
+        assert!(buffer.iter().all(|p| p.color == [1.0; 3]));
+
+        // Exceeding the budget again produces new warnings.
+        let mut buffer = points(POINT_HZ as usize, [1.0; 3]);
+        assert_eq!(tracker.apply(&guard, POINT_HZ, &mut buffer).len(), 3);
+    }
+
+    #[test]
+    fn restarts_measurement_on_window_change() {
+        let mut tracker = ThermalTracker::default();
+        let mut buffer = points(POINT_HZ as usize, [1.0; 3]);
+        tracker.apply(&guard(0.5), POINT_HZ, &mut buffer);
+        let guard = guard(0.5).window(Duration::from_secs(2));
+        let mut buffer = points(10, [0.4; 3]);
+        assert!(tracker.apply(&guard, POINT_HZ, &mut buffer).is_empty());
+        assert!(buffer.iter().all(|p| p.color == [0.4; 3]));
+    }
+
+    #[test]
+    fn negative_colours_draw_no_power() {
+        let mut tracker = ThermalTracker::default();
+        let mut buffer = points(POINT_HZ as usize, [-1.0; 3]);
+        assert!(tracker.apply(&guard(0.0), POINT_HZ, &mut buffer).is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+//! Transparent compression of large OSC blob arguments.
+//!
+//! Blobs larger than a given threshold are compressed with LZ4 and prefixed with the `MAGIC` tag.
+//! A `Receiver` with decompression enabled via `Receiver::set_decompress_blobs` recognises the tag
+//! and decompresses these blobs automatically, so the application only ever sees the original
+//! bytes. Other receivers will see the compressed bytes as a regular blob.
+//!
+//! **Note:** An uncompressed blob whose bytes happen to begin with `MAGIC` is indistinguishable
+//! from a compressed blob. A receiver with decompression enabled will attempt to decompress it,
+//! most likely failing with a `DecompressError`. For this reason decompression is opt-in and
+//! should only be enabled for receivers whose senders compress their blobs.
+//!
+//! The decompressed size of each blob is declared by the sender. Blobs declaring a size greater
+//! than the receiver's limit are rejected before any memory is allocated for them.
+//!
+//! This is useful when streaming large payloads such as framebuffers or point clouds between
+//! machines.
+
+use super::{Packet, Type};
+use rosc::OscPacket;
+
+/// The tag prepended to every compressed blob.
+pub const MAGIC: &[u8; 4] = b"nlz4";
+
+/// The default blob size in bytes above which blobs are compressed by a `Sender`.
+pub const DEFAULT_THRESHOLD: usize = 1024;
+
+/// The default maximum size in bytes to which a single blob may decompress, 16 MiB.
+pub const DEFAULT_MAX_DECOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+
+// The size of the little-endian decompressed size that follows the `MAGIC` tag.
+const SIZE_PREFIX_LEN: usize = 4;
+
+/// Errors that might occur while decompressing a tagged blob.
+#[derive(Debug)]
+pub enum DecompressError {
+    /// The blob is too short to contain the decompressed size.
+    Truncated,
+    /// The blob declares a decompressed size greater than the maximum.
+    TooLarge { len: usize, max_len: usize },
+    /// The compressed bytes are invalid.
+    Lz4(lz4_flex::block::DecompressError),
+    /// The blob decompressed to a different size than it declared.
+    LenMismatch { expected: usize, actual: usize },
+}
+
+/// Whether or not the given blob bytes are tagged as compressed.
+pub fn is_compressed(blob: &[u8]) -> bool {
+    blob.starts_with(MAGIC)
+}
+
+/// Compress the given blob bytes, prefixing them with the `MAGIC` tag.
+///
+/// Returns `None` if compression would not reduce the size of the blob.
+pub fn compress_blob(blob: &[u8]) -> Option<Vec<u8>> {
+    let compressed = lz4_flex::block::compress_prepend_size(blob);
+    if MAGIC.len() + compressed.len() >= blob.len() {
+        return None;
+    }
+    let mut tagged = Vec::with_capacity(MAGIC.len() + compressed.len());
+    tagged.extend_from_slice(MAGIC);
+    tagged.extend_from_slice(&compressed);
+    Some(tagged)
+}
+
+/// Decompress the given blob bytes if they are tagged as compressed.
+///
+/// Returns `Ok(None)` if the blob is not tagged, in which case it should be used as is.
+///
+/// Returns an error without allocating if the blob declares a decompressed size greater than
+/// `max_len` bytes.
+pub fn decompress_blob(blob: &[u8], max_len: usize) -> Result<Option<Vec<u8>>, DecompressError> {
+    if !is_compressed(blob) {
+        return Ok(None);
+    }
+    let tagged = &blob[MAGIC.len()..];
+    if tagged.len() < SIZE_PREFIX_LEN {
+        return Err(DecompressError::Truncated);
+    }
+    let (prefix, compressed) = tagged.split_at(SIZE_PREFIX_LEN);
+    let mut len_bytes = [0u8; SIZE_PREFIX_LEN];
+    len_bytes.copy_from_slice(prefix);
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    if len > max_len {
+        return Err(DecompressError::TooLarge { len, max_len });
+    }
+    let mut decompressed = vec![0; len];
+    let actual = lz4_flex::block::decompress_into(compressed, &mut decompressed)?;
+    if actual != len {
+        return Err(DecompressError::LenMismatch {
+            expected: len,
+            actual,
+        });
+    }
+    Ok(Some(decompressed))
+}
+
+/// Compress all blob arguments within the packet whose size exceeds `threshold` bytes.
+///
+/// Blobs nested within bundles and arrays are also compressed.
+pub fn compress_packet(packet: &mut Packet, threshold: usize) {
+    match *packet {
+        Packet::Message(ref mut msg) => compress_args(&mut msg.args, threshold),
+        Packet::Bundle(ref mut bundle) => {
+            for packet in &mut bundle.content {
+                compress_rosc_packet(packet, threshold);
+            }
+        }
+    }
+}
+
+/// Decompress all tagged blob arguments within the packet.
+///
+/// Blobs nested within bundles and arrays are also decompressed. See `decompress_blob` for
+/// details on `max_len`.
+pub fn decompress_packet(packet: &mut Packet, max_len: usize) -> Result<(), DecompressError> {
+    match *packet {
+        Packet::Message(ref mut msg) => decompress_args(&mut msg.args, max_len),
+        Packet::Bundle(ref mut bundle) => {
+            for packet in &mut bundle.content {
+                decompress_rosc_packet(packet, max_len)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+impl From<lz4_flex::block::DecompressError> for DecompressError {
+    fn from(err: lz4_flex::block::DecompressError) -> Self {
+        DecompressError::Lz4(err)
+    }
+}
+
+impl std::error::Error for DecompressError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            DecompressError::Lz4(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DecompressError::Truncated => write!(f, "compressed blob is missing its size prefix"),
+            DecompressError::TooLarge { len, max_len } => write!(
+                f,
+                "compressed blob declares {} bytes, exceeding the maximum of {}",
+                len, max_len
+            ),
+            DecompressError::Lz4(ref err) => std::fmt::Display::fmt(err, f),
+            DecompressError::LenMismatch { expected, actual } => write!(
+                f,
+                "compressed blob declared {} bytes but decompressed to {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+fn compress_rosc_packet(packet: &mut OscPacket, threshold: usize) {
+    match *packet {
+        OscPacket::Message(ref mut msg) => compress_args(&mut msg.args, threshold),
+        OscPacket::Bundle(ref mut bundle) => {
+            for packet in &mut bundle.content {
+                compress_rosc_packet(packet, threshold);
+            }
+        }
+    }
+}
+
+fn decompress_rosc_packet(packet: &mut OscPacket, max_len: usize) -> Result<(), DecompressError> {
+    match *packet {
+        OscPacket::Message(ref mut msg) => decompress_args(&mut msg.args, max_len),
+        OscPacket::Bundle(ref mut bundle) => {
+            for packet in &mut bundle.content {
+                decompress_rosc_packet(packet, max_len)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn compress_args(args: &mut [Type], threshold: usize) {
+    for arg in args {
+        match *arg {
+            Type::Blob(ref mut blob) if blob.len() > threshold => {
+                if let Some(compressed) = compress_blob(blob) {
+                    *blob = compressed;
+                }
+            }
+            Type::Array(ref mut array) => compress_args(&mut array.content, threshold),
+            _ => (),
+        }
+    }
+}
+
+fn decompress_args(args: &mut [Type], max_len: usize) -> Result<(), DecompressError> {
+    for arg in args {
+        match *arg {
+            Type::Blob(ref mut blob) => {
+                if let Some(decompressed) = decompress_blob(blob, max_len)? {
+                    *blob = decompressed;
+                }
+            }
+            Type::Array(ref mut array) => decompress_args(&mut array.content, max_len)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Array, Bundle, Message, Time};
+
+    fn compressible(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 16) as u8).collect()
+    }
+
+    fn tagged(len: u32, compressed: &[u8]) -> Vec<u8> {
+        let mut blob = MAGIC.to_vec();
+        blob.extend_from_slice(&len.to_le_bytes());
+        blob.extend_from_slice(compressed);
+        blob
+    }
+
+    #[test]
+    fn blob_round_trip() {
+        let blob = compressible(4096);
+        let compressed = compress_blob(&blob).expect("blob should compress");
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < blob.len());
+        let decompressed = decompress_blob(&compressed, DEFAULT_MAX_DECOMPRESSED_LEN).unwrap();
+        assert_eq!(decompressed, Some(blob));
+    }
+
+    #[test]
+    fn incompressible_blob_is_not_compressed() {
+        assert_eq!(compress_blob(&[1, 2, 3, 4, 5, 6, 7, 8]), None);
+    }
+
+    #[test]
+    fn untagged_blob_is_left_as_is() {
+        let blob = compressible(64);
+        assert_eq!(decompress_blob(&blob, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn oversized_prefix_is_rejected() {
+        let blob = tagged(u32::MAX, &[0; 8]);
+        match decompress_blob(&blob, DEFAULT_MAX_DECOMPRESSED_LEN) {
+            Err(DecompressError::TooLarge { len, max_len }) => {
+                assert_eq!(len, u32::MAX as usize);
+                assert_eq!(max_len, DEFAULT_MAX_DECOMPRESSED_LEN);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn size_above_limit_is_rejected() {
+        let blob = compressible(4096);
+        let compressed = compress_blob(&blob).unwrap();
+        assert!(matches!(
+            decompress_blob(&compressed, 4095),
+            Err(DecompressError::TooLarge { .. })
+        ));
+        assert!(decompress_blob(&compressed, 4096).is_ok());
+    }
+
+    #[test]
+    fn truncated_prefix_is_rejected() {
+        let mut blob = MAGIC.to_vec();
+        blob.extend_from_slice(&[0, 1]);
+        assert!(matches!(
+            decompress_blob(&blob, DEFAULT_MAX_DECOMPRESSED_LEN),
+            Err(DecompressError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn malformed_body_is_rejected() {
+        let blob = tagged(64, &[0xff; 8]);
+        assert!(decompress_blob(&blob, DEFAULT_MAX_DECOMPRESSED_LEN).is_err());
+    }
+
+    #[test]
+    fn declared_size_mismatch_is_rejected() {
+        let original = compressible(4096);
+        let compressed = lz4_flex::block::compress(&original);
+        let blob = tagged(8192, &compressed);
+        assert!(decompress_blob(&blob, DEFAULT_MAX_DECOMPRESSED_LEN).is_err());
+    }
+
+    #[test]
+    fn nested_packet_round_trip() {
+        let large = compressible(4096);
+        let small = vec![1, 2, 3];
+        let msg = Message {
+            addr: "/blob".into(),
+            args: vec![
+                Type::Blob(large.clone()),
+                Type::Blob(small.clone()),
+                Type::Array(Array {
+                    content: vec![Type::Blob(large.clone())],
+                }),
+            ],
+        };
+        let bundle = Bundle {
+            timetag: Time {
+                seconds: 0,
+                fractional: 1,
+            },
+            content: vec![OscPacket::Message(msg.clone())],
+        };
+        let original = Packet::Bundle(bundle);
+        let mut packet = original.clone();
+        compress_packet(&mut packet, DEFAULT_THRESHOLD);
+        assert_ne!(packet, original);
+        if let Packet::Bundle(ref bundle) = packet {
+            if let OscPacket::Message(ref msg) = bundle.content[0] {
+                assert!(matches!(msg.args[0], Type::Blob(ref b) if is_compressed(b)));
+                assert_eq!(msg.args[1], Type::Blob(small));
+            }
+        }
+        decompress_packet(&mut packet, DEFAULT_MAX_DECOMPRESSED_LEN).unwrap();
+        assert_eq!(packet, original);
+    }
+}
@@ -0,0 +1,455 @@
+//! A lenient decoder that salvages what it can from slightly out-of-spec packets.
+//!
+//! Real-world hardware controllers frequently emit OSC that does not quite conform to the spec,
+//! e.g. datagrams with trailing padding, bundles in which one element is malformed or element
+//! sizes written in little-endian byte order. The strict `decode` function rejects these packets
+//! entirely. `decode_lenient` instead decodes each element of a bundle independently, skipping
+//! those that cannot be decoded, and reports each deviation from the spec as a `DecodeWarning`.
+
+use super::{Bundle, Error, Message, Packet, Time};
+use std::fmt;
+
+// The string with which every bundle begins.
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+// The length of the bundle tag followed by the time tag.
+const BUNDLE_HEADER_LEN: usize = 16;
+
+/// The maximum depth to which bundles and arrays may be nested within a packet.
+///
+/// Deeper bundles are skipped and messages containing deeper arrays are decoded with their address
+/// alone, each with a `DecodeWarning::NestingTooDeep`. This bounds the recursion of the decoder
+/// for packets crafted to nest indefinitely.
+pub const MAX_NESTING_DEPTH: usize = 16;
+
+/// A deviation from the OSC spec that was recovered from while decoding a packet.
+///
+/// All offsets are in bytes from the start of the datagram.
+#[derive(Debug)]
+pub enum DecodeWarning {
+    /// Bytes following the end of a message or the last element of a bundle were ignored.
+    TrailingBytes { offset: usize, len: usize },
+    /// A bundle element could not be decoded and was skipped.
+    MalformedElement {
+        offset: usize,
+        len: usize,
+        err: Error,
+    },
+    /// A bundle element had a size of zero and was skipped.
+    EmptyElement { offset: usize },
+    /// The size of a bundle element exceeded the remaining bytes of the bundle, in which case the
+    /// remainder of the bundle was ignored.
+    TruncatedElement {
+        offset: usize,
+        size: usize,
+        remaining: usize,
+    },
+    /// The size of a bundle element was written in little-endian byte order.
+    LittleEndianSize { offset: usize, size: usize },
+    /// A message had no type tag string. As its arguments cannot be interpreted, the message was
+    /// decoded with its address alone.
+    MissingTypeTags { offset: usize, addr: String },
+    /// A bundle element or the arguments of a message were nested more deeply than
+    /// `MAX_NESTING_DEPTH`. Nested bundles are skipped, while messages are decoded with their
+    /// address alone.
+    NestingTooDeep { offset: usize, len: usize },
+}
+
+/// Decodes the given slice of `bytes` into a `Packet`, salvaging the valid messages of packets
+/// that do not conform to the spec.
+///
+/// Returns the packet along with a warning for each deviation that was recovered from. Bundle
+/// elements that cannot be decoded are skipped, so a bundle may be returned with fewer elements
+/// than were sent.
+///
+/// Returns an `Error` only if the slice does not contain anything resembling an OSC packet.
+pub fn decode_lenient(bytes: &[u8]) -> Result<(Packet, Vec<DecodeWarning>), Error> {
+    let mut warnings = vec![];
+    let packet = decode_packet(bytes, 0, 0, &mut warnings)?;
+    Ok((packet, warnings))
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeWarning::TrailingBytes { offset, len } => {
+                write!(f, "ignored {} trailing byte(s) at offset {}", len, offset)
+            }
+            DecodeWarning::MalformedElement {
+                offset,
+                len,
+                ref err,
+            } => write!(
+                f,
+                "skipped malformed {} byte bundle element at offset {}: {:?}",
+                len, offset, err
+            ),
+            DecodeWarning::EmptyElement { offset } => {
+                write!(f, "skipped empty bundle element at offset {}", offset)
+            }
+            DecodeWarning::TruncatedElement {
+                offset,
+                size,
+                remaining,
+            } => write!(
+                f,
+                "bundle element at offset {} has size {} but only {} byte(s) remain",
+                offset, size, remaining
+            ),
+            DecodeWarning::LittleEndianSize { offset, size } => write!(
+                f,
+                "bundle element at offset {} has little-endian size {}",
+                offset, size
+            ),
+            DecodeWarning::MissingTypeTags { offset, ref addr } => write!(
+                f,
+                "message `{}` at offset {} has no type tag string",
+                addr, offset
+            ),
+            DecodeWarning::NestingTooDeep { offset, len } => write!(
+                f,
+                "skipped {} byte(s) at offset {} nested deeper than {} levels",
+                len, offset, MAX_NESTING_DEPTH
+            ),
+        }
+    }
+}
+
+// Decode either a bundle or a message beginning at the given offset within the datagram.
+//
+// The `depth` is the number of bundles enclosing the packet.
+fn decode_packet(
+    bytes: &[u8],
+    offset: usize,
+    depth: usize,
+    warnings: &mut Vec<DecodeWarning>,
+) -> Result<Packet, Error> {
+    if is_bundle(bytes) {
+        Ok(decode_bundle(bytes, offset, depth + 1, warnings))
+    } else {
+        decode_message(bytes, offset, warnings)
+    }
+}
+
+// Decode a message, falling back to its address alone if it has no type tag string.
+fn decode_message(
+    bytes: &[u8],
+    offset: usize,
+    warnings: &mut Vec<DecodeWarning>,
+) -> Result<Packet, Error> {
+    // Avoid handing arrays nested deeper than the limit to the decoder.
+    if let Some((addr, end)) = read_address(bytes) {
+        let type_tags = &bytes[end..];
+        if type_tags.starts_with(b",") && array_depth(type_tags) > MAX_NESTING_DEPTH {
+            let len = bytes.len();
+            warnings.push(DecodeWarning::NestingTooDeep { offset, len });
+            let args = vec![];
+            return Ok(Packet::Message(Message { addr, args }));
+        }
+    }
+    match rosc::decoder::decode_udp(bytes) {
+        Ok((rest, packet)) => {
+            if !rest.is_empty() {
+                let len = rest.len();
+                let offset = offset + bytes.len() - len;
+                warnings.push(DecodeWarning::TrailingBytes { offset, len });
+            }
+            Ok(packet.into())
+        }
+        Err(err) => match read_address(bytes) {
+            Some((addr, end)) if !bytes[end..].starts_with(b",") => {
+                let warning = DecodeWarning::MissingTypeTags {
+                    offset,
+                    addr: addr.clone(),
+                };
+                warnings.push(warning);
+                let args = vec![];
+                Ok(Packet::Message(Message { addr, args }))
+            }
+            _ => Err(err),
+        },
+    }
+}
+
+// Decode each element of the bundle independently, skipping those that cannot be decoded.
+//
+// The given bytes must begin with a complete bundle header. The `depth` of a bundle that is not
+// nested within another is `1`.
+fn decode_bundle(
+    bytes: &[u8],
+    offset: usize,
+    depth: usize,
+    warnings: &mut Vec<DecodeWarning>,
+) -> Packet {
+    let timetag = Time {
+        seconds: read_u32_be(&bytes[8..12]),
+        fractional: read_u32_be(&bytes[12..16]),
+    };
+    let mut content = vec![];
+    let mut pos = BUNDLE_HEADER_LEN;
+    while pos < bytes.len() {
+        let remaining = bytes.len() - pos;
+        if remaining < 4 {
+            let offset = offset + pos;
+            warnings.push(DecodeWarning::TrailingBytes {
+                offset,
+                len: remaining,
+            });
+            break;
+        }
+
+        // Read the element size, allowing for little-endian sizes that fit where big-endian
+        // sizes do not.
+        let size_bytes = &bytes[pos..pos + 4];
+        let available = remaining - 4;
+        let mut size = read_u32_be(size_bytes) as usize;
+        if size > available {
+            let le_size = read_u32_le(size_bytes) as usize;
+            if le_size <= available {
+                let offset = offset + pos;
+                warnings.push(DecodeWarning::LittleEndianSize {
+                    offset,
+                    size: le_size,
+                });
+                size = le_size;
+            } else {
+                warnings.push(DecodeWarning::TruncatedElement {
+                    offset: offset + pos,
+                    size,
+                    remaining: available,
+                });
+                break;
+            }
+        }
+
+        let start = pos + 4;
+        pos = start + size;
+        if size == 0 {
+            let offset = offset + start;
+            warnings.push(DecodeWarning::EmptyElement { offset });
+            continue;
+        }
+        let element = &bytes[start..pos];
+        if is_bundle(element) && depth >= MAX_NESTING_DEPTH {
+            let offset = offset + start;
+            warnings.push(DecodeWarning::NestingTooDeep { offset, len: size });
+            continue;
+        }
+        match decode_packet(element, offset + start, depth, warnings) {
+            Ok(packet) => content.push(packet.into()),
+            Err(err) => warnings.push(DecodeWarning::MalformedElement {
+                offset: offset + start,
+                len: size,
+                err,
+            }),
+        }
+    }
+    Packet::Bundle(Bundle { timetag, content })
+}
+
+// Read the null terminated address at the start of a message, returning it along with the index
+// of the first byte following its padding.
+fn read_address(bytes: &[u8]) -> Option<(String, usize)> {
+    if bytes.first() != Some(&b'/') {
+        return None;
+    }
+    let nul = bytes.iter().position(|&b| b == 0)?;
+    let addr = std::str::from_utf8(&bytes[..nul]).ok()?;
+    let end = std::cmp::min((nul + 4) & !3, bytes.len());
+    Some((addr.to_string(), end))
+}
+
+// Whether or not the given bytes begin with a complete bundle header.
+fn is_bundle(bytes: &[u8]) -> bool {
+    bytes.starts_with(BUNDLE_TAG) && bytes.len() >= BUNDLE_HEADER_LEN
+}
+
+// The maximum depth to which arrays are nested within the given type tag string.
+fn array_depth(type_tags: &[u8]) -> usize {
+    let mut depth = 0usize;
+    let mut max_depth = 0;
+    for &tag in type_tags.iter().take_while(|&&b| b != 0) {
+        match tag {
+            b'[' => {
+                depth += 1;
+                max_depth = std::cmp::max(depth, max_depth);
+            }
+            b']' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    max_depth
+}
+
+fn read_u32_be(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Type;
+    use rosc::OscPacket;
+
+    fn message(addr: &str) -> Vec<u8> {
+        let msg = crate::msg(addr, vec![Type::Int(1)]);
+        crate::encode(Packet::Message(msg)).unwrap()
+    }
+
+    fn bundle(elements: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = BUNDLE_TAG.to_vec();
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in elements {
+            bytes.extend_from_slice(&(element.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(element);
+        }
+        bytes
+    }
+
+    fn nested_bundles(depth: usize) -> Vec<u8> {
+        (0..depth).fold(message("/inner"), |element, _| bundle(&[element]))
+    }
+
+    // The number of bundles enclosing the innermost element of the packet.
+    fn bundle_depth(packet: &Packet) -> usize {
+        match *packet {
+            Packet::Message(_) => 0,
+            Packet::Bundle(ref bundle) => match bundle.content.first() {
+                Some(&OscPacket::Bundle(ref inner)) => {
+                    1 + bundle_depth(&Packet::Bundle(inner.clone()))
+                }
+                _ => 1,
+            },
+        }
+    }
+
+    fn contents(packet: &Packet) -> &[OscPacket] {
+        match *packet {
+            Packet::Bundle(ref bundle) => &bundle.content,
+            Packet::Message(_) => panic!("expected a bundle"),
+        }
+    }
+
+    #[test]
+    fn conforming_packets_produce_no_warnings() {
+        let bytes = bundle(&[message("/a"), message("/b")]);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(contents(&packet).len(), 2);
+    }
+
+    #[test]
+    fn unrecognisable_bytes_are_an_error() {
+        assert!(decode_lenient(b"garbage!").is_err());
+    }
+
+    #[test]
+    fn truncated_element_ignores_remainder_of_bundle() {
+        let mut bytes = bundle(&[message("/a")]);
+        let element = message("/b");
+        bytes.extend_from_slice(&[0x7f, 0, 0, 0x7f]);
+        bytes.extend_from_slice(&element);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert_eq!(contents(&packet).len(), 1);
+        match warnings[..] {
+            [DecodeWarning::TruncatedElement {
+                size, remaining, ..
+            }] => {
+                assert_eq!(size, 0x7f00_007f);
+                assert_eq!(remaining, element.len());
+            }
+            _ => panic!("unexpected warnings: {:?}", warnings),
+        }
+    }
+
+    #[test]
+    fn misaligned_trailing_bytes_are_ignored() {
+        let mut bytes = bundle(&[message("/a")]);
+        bytes.extend_from_slice(&[0, 0]);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert_eq!(contents(&packet).len(), 1);
+        assert!(matches!(
+            warnings[..],
+            [DecodeWarning::TrailingBytes { len: 2, .. }]
+        ));
+    }
+
+    #[test]
+    fn little_endian_sizes_are_recovered() {
+        let element = message("/a");
+        let mut bytes = bundle(&[]);
+        bytes.extend_from_slice(&(element.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&element);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert_eq!(contents(&packet).len(), 1);
+        assert!(matches!(
+            warnings[..],
+            [DecodeWarning::LittleEndianSize { offset: 16, .. }]
+        ));
+    }
+
+    #[test]
+    fn malformed_and_empty_elements_are_skipped() {
+        let bytes = bundle(&[b"bad\0".to_vec(), vec![], message("/a")]);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert_eq!(contents(&packet).len(), 1);
+        assert!(matches!(
+            warnings[..],
+            [
+                DecodeWarning::MalformedElement { len: 4, .. },
+                DecodeWarning::EmptyElement { .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn nested_bundles_are_decoded() {
+        let bytes = nested_bundles(3);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(bundle_depth(&packet), 3);
+    }
+
+    #[test]
+    fn bundles_nested_beyond_limit_are_skipped() {
+        let bytes = nested_bundles(MAX_NESTING_DEPTH);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(bundle_depth(&packet), MAX_NESTING_DEPTH);
+
+        let bytes = nested_bundles(MAX_NESTING_DEPTH + 1);
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        assert_eq!(bundle_depth(&packet), MAX_NESTING_DEPTH);
+        assert!(matches!(
+            warnings[..],
+            [DecodeWarning::NestingTooDeep { .. }]
+        ));
+    }
+
+    #[test]
+    fn arrays_nested_beyond_limit_keep_address() {
+        let depth = MAX_NESTING_DEPTH + 1;
+        let mut bytes = b"/deep\0\0\0,".to_vec();
+        bytes.extend(std::iter::repeat(b'[').take(depth));
+        bytes.extend(std::iter::repeat(b']').take(depth));
+        bytes.push(0);
+        while bytes.len() % 4 != 0 {
+            bytes.push(0);
+        }
+        let (packet, warnings) = decode_lenient(&bytes).unwrap();
+        match packet {
+            Packet::Message(msg) => {
+                assert_eq!(msg.addr, "/deep");
+                assert!(msg.args.is_empty());
+            }
+            _ => panic!("expected a message"),
+        }
+        assert!(matches!(
+            warnings[..],
+            [DecodeWarning::NestingTooDeep { offset: 0, .. }]
+        ));
+    }
+}
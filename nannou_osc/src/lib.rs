@@ -1,5 +1,8 @@
 //! Tools for working with OSC. [**sender()**](./fn.sender.html) creates an OSC sender,
 //! [**receiver(port)**](./fn.receiver.html) creates an OSC receiver.
+//!
+//! A [**Queue**](./queue/struct.Queue.html) receives on a background thread, collecting packets
+//! to be handled once per frame.
 
 pub use rosc;
 
@@ -7,20 +10,30 @@ pub use rosc;
 //
 // Remove `Osc` prefix as items are already namespaced via a module, e.g. `OscMessage` becomes
 // `nannou_osc::Message`.
-pub use self::recv::Receiver;
+pub use self::lenient::{decode_lenient, DecodeWarning};
+pub use self::queue::{Overflow, Queue, Received};
+pub use self::recv::{BundleFn, DecodeWarningFn, InvalidFn, Receiver};
 #[doc(inline)]
 pub use self::rosc::{
     address, decoder, encoder, OscArray as Array, OscBundle as Bundle, OscColor as Color,
     OscError as Error, OscMessage as Message, OscMidiMessage as MidiMessage, OscTime as Time,
     OscTimeError as TimeError, OscType as Type,
 };
-pub use self::send::Sender;
+pub use self::schema::{Schema, ValidationError};
+pub use self::send::{MultiSender, Sender, TargetStats};
 
 use std;
 use std::net::{Ipv4Addr, SocketAddr};
 
+#[cfg(feature = "compression")]
+pub mod compress;
+pub mod lenient;
+pub mod queue;
 pub mod recv;
+pub mod schema;
 pub mod send;
+#[cfg(feature = "websocket")]
+pub mod ws;
 
 /// Indicates that a `Sender` is not currently connected to a target address, and that the target
 /// address will have to be supplied manually when sending packets.
@@ -78,6 +91,24 @@ impl Into<rosc::OscPacket> for Packet {
     }
 }
 
+/// All messages of a single received packet, along with the bundle's time tag.
+///
+/// Unlike a stream of unfolded messages, a group is intended to be applied atomically, e.g. in
+/// order to update multiple parameters of a scene consistently between frames.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageGroup {
+    /// The time tag of the outermost bundle, or `None` if the packet was a lone message.
+    pub time: Option<Time>,
+    /// All messages contained within the packet, in order, including those of nested bundles.
+    pub messages: Vec<Message>,
+}
+
+impl From<Packet> for MessageGroup {
+    fn from(packet: Packet) -> Self {
+        packet.into_group()
+    }
+}
+
 impl Packet {
     /// A recursive function that unfolds the packet into the end of the given buffer of messages.
     pub fn unfold(self, msgs: &mut Vec<Message>) {
@@ -91,6 +122,17 @@ impl Packet {
         }
     }
 
+    /// Convert the `Packet` into a `MessageGroup` containing all `Message`s contained within
+    /// along with the time tag of the bundle.
+    pub fn into_group(self) -> MessageGroup {
+        let time = match self {
+            Packet::Message(_) => None,
+            Packet::Bundle(ref bundle) => Some(bundle.timetag),
+        };
+        let messages = self.into_msgs();
+        MessageGroup { time, messages }
+    }
+
     /// Convert the `Packet` into a Vec containing all `Message`s contained within.
     ///
     /// This uses the `unfold` method internally.
@@ -146,6 +188,8 @@ pub enum CommunicationError {
     Io(std::io::Error),
     Osc(Error),
     Poisoned,
+    #[cfg(feature = "compression")]
+    Decompress(compress::DecompressError),
 }
 
 impl From<std::io::Error> for CommunicationError {
@@ -160,6 +204,13 @@ impl From<Error> for CommunicationError {
     }
 }
 
+#[cfg(feature = "compression")]
+impl From<compress::DecompressError> for CommunicationError {
+    fn from(err: compress::DecompressError) -> Self {
+        CommunicationError::Decompress(err)
+    }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for CommunicationError {
     fn from(_: std::sync::PoisonError<T>) -> Self {
         CommunicationError::Poisoned
@@ -170,6 +221,8 @@ impl std::error::Error for CommunicationError {
     fn cause(&self) -> Option<&dyn std::error::Error> {
         match *self {
             CommunicationError::Io(ref err) => Some(err),
+            #[cfg(feature = "compression")]
+            CommunicationError::Decompress(ref err) => Some(err),
             // TODO: Error isn't implemented for OscError - should fix this upstream.
             CommunicationError::Osc(ref _err) => None,
             // CommunicationError::Osc(ref err) => Some(err),
@@ -185,6 +238,8 @@ impl std::fmt::Display for CommunicationError {
             // TODO: Error isn't implemented for OscError - should fix this upstream.
             CommunicationError::Osc(ref _err) => write!(f, "Failed to decode the OSC packet"),
             CommunicationError::Poisoned => write!(f, "The inner buffer's mutex was poisoned"),
+            #[cfg(feature = "compression")]
+            CommunicationError::Decompress(ref err) => std::fmt::Display::fmt(err, f),
         }
     }
 }
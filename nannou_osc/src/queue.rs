@@ -0,0 +1,348 @@
+//! A queue of received packets, filled by a `Receiver` on a background thread.
+//!
+//! Most OSC-controlled sketches only need to handle the packets received since the previous
+//! frame. The `Queue` owns a `Receiver` and receives on its own thread, timestamping each packet
+//! on arrival. The application then drains the queue once per frame via `try_iter`, without
+//! configuring the socket or risking a blocking `recv` within `update`.
+//!
+//! The queue holds at most `capacity` packets. If the application stops draining it, packets
+//! received while it is full are dropped according to its `Overflow` policy.
+//!
+//! ```no_run
+//! use nannou_osc as osc;
+//!
+//! let queue = osc::Queue::bind(34254).expect("failed to bind the OSC queue");
+//! for received in queue.try_iter() {
+//!     for msg in received.packet.into_msgs() {
+//!         println!("{}: {:?}", msg.addr, msg.args);
+//!     }
+//! }
+//! ```
+
+use super::recv::Receiver;
+use super::{CommunicationError, Packet};
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// The interval at which the receiving thread checks whether the queue was closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A queue of packets received by a `Receiver` on a background thread.
+///
+/// The background thread is closed when the queue is dropped.
+pub struct Queue {
+    receiver: Arc<Receiver>,
+    shared: Arc<Shared>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+/// A packet received by a `Queue`, along with its source address and arrival time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Received {
+    /// The received packet.
+    pub packet: Packet,
+    /// The address from which the packet was sent.
+    pub addr: SocketAddr,
+    /// The moment at which the packet was received by the background thread.
+    pub time: Instant,
+}
+
+/// Describes which packets are dropped when a packet is received while the `Queue` is full.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drop the oldest pending packet to make room, keeping the most recent state.
+    DropOldest,
+    /// Drop the newly received packet, keeping the pending packets.
+    DropNewest,
+}
+
+/// A function registered via `Queue::on_error`, called on the background thread with each error
+/// that occurs while receiving.
+pub type ErrorFn = dyn FnMut(CommunicationError) + Send;
+
+// State shared between the queue handle and its thread.
+struct Shared {
+    closed: AtomicBool,
+    error_fn: Mutex<Option<Box<ErrorFn>>>,
+    capacity: usize,
+    overflow: Overflow,
+    pending: Mutex<VecDeque<Received>>,
+    // The number of packets dropped due to overflow.
+    dropped: AtomicU64,
+}
+
+impl Queue {
+    /// The capacity used by `Queue::new`.
+    pub const DEFAULT_CAPACITY: usize = 1024;
+
+    /// The overflow policy used by `Queue::new`.
+    pub const DEFAULT_OVERFLOW: Overflow = Overflow::DropOldest;
+
+    /// Bind a `Receiver` to the given port of the default IPv4 address and begin receiving on a
+    /// background thread.
+    pub fn bind(port: u16) -> Result<Self, io::Error> {
+        Self::new(Receiver::bind(port)?)
+    }
+
+    /// Bind a `Receiver` to the given address and begin receiving on a background thread.
+    pub fn bind_to<A>(addr: A) -> Result<Self, io::Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::new(Receiver::bind_to(addr)?)
+    }
+
+    /// Begin receiving on a background thread with the given `Receiver`.
+    ///
+    /// The receiver's schema and bundle function continue to apply. Bundles delivered to the
+    /// bundle function are not queued.
+    ///
+    /// The queue holds up to `DEFAULT_CAPACITY` packets and overflows with `DEFAULT_OVERFLOW`.
+    pub fn new(receiver: Receiver) -> Result<Self, io::Error> {
+        Self::with_capacity(receiver, Self::DEFAULT_CAPACITY, Self::DEFAULT_OVERFLOW)
+    }
+
+    /// The same as `new`, but holds at most `capacity` pending packets, dropping packets
+    /// according to `overflow` once full.
+    ///
+    /// **Panic!**s if `capacity` is `0`.
+    pub fn with_capacity(
+        receiver: Receiver,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> Result<Self, io::Error> {
+        assert!(capacity > 0, "a queue requires a non-zero capacity");
+        receiver.set_read_timeout(Some(POLL_INTERVAL))?;
+        let receiver = Arc::new(receiver);
+        let shared = Arc::new(Shared {
+            closed: AtomicBool::new(false),
+            error_fn: Mutex::new(None),
+            capacity,
+            overflow,
+            pending: Mutex::new(VecDeque::with_capacity(capacity)),
+            dropped: AtomicU64::new(0),
+        });
+        let thread_receiver = receiver.clone();
+        let thread_shared = shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("nannou_osc_queue".into())
+            .spawn(move || recv_loop(&thread_receiver, &thread_shared))?;
+        Ok(Queue {
+            receiver,
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// The inner `Receiver`, e.g. for setting a schema or registering a bundle function.
+    ///
+    /// **Note:** The receiving methods of the receiver should not be called, as any packets
+    /// received this way are not queued.
+    pub fn receiver(&self) -> &Receiver {
+        &self.receiver
+    }
+
+    /// The socket address on which packets are received.
+    pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+        self.receiver.local_addr()
+    }
+
+    /// Register a function to be called on the background thread with each error that occurs
+    /// while receiving, e.g. a packet that could not be decoded.
+    ///
+    /// By default, errors are ignored and receiving continues.
+    pub fn on_error<F>(&self, error_fn: F) -> Result<(), CommunicationError>
+    where
+        F: 'static + FnMut(CommunicationError) + Send,
+    {
+        *self.shared.error_fn.lock()? = Some(Box::new(error_fn));
+        Ok(())
+    }
+
+    /// The maximum number of pending packets.
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// The policy applied to packets received while the queue is full.
+    pub fn overflow(&self) -> Overflow {
+        self.shared.overflow
+    }
+
+    /// The number of packets pending.
+    pub fn len(&self) -> usize {
+        self.shared.pending.lock().map(|p| p.len()).unwrap_or(0)
+    }
+
+    /// Whether or not there are no pending packets.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The total number of packets dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the next pending packet if there is one.
+    pub fn try_recv(&self) -> Option<Received> {
+        self.shared.pending.lock().ok()?.pop_front()
+    }
+
+    /// An iterator yielding all pending packets in the order in which they were received.
+    ///
+    /// This is intended to be called once per frame, e.g. within `update`.
+    pub fn try_iter(&self) -> impl Iterator<Item = Received> + '_ {
+        std::iter::from_fn(move || self.try_recv())
+    }
+}
+
+impl Drop for Queue {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+// Receive packets until the queue is closed.
+fn recv_loop(receiver: &Receiver, shared: &Shared) {
+    while !shared.closed.load(Ordering::Relaxed) {
+        let (packet, addr) = match receiver.recv() {
+            Ok(received) => received,
+            // The read timed out, giving us a chance to check whether the queue was closed.
+            Err(CommunicationError::Io(ref err))
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                continue;
+            }
+            // A poisoned receiver will never recover.
+            Err(CommunicationError::Poisoned) => break,
+            Err(err) => {
+                if let Ok(mut error_fn) = shared.error_fn.lock() {
+                    if let Some(ref mut error_fn) = *error_fn {
+                        error_fn(err);
+                    }
+                }
+                continue;
+            }
+        };
+        let time = Instant::now();
+        let received = Received { packet, addr, time };
+        let mut pending = match shared.pending.lock() {
+            Ok(pending) => pending,
+            Err(_) => break,
+        };
+        if pending.len() >= shared.capacity {
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+            match shared.overflow {
+                Overflow::DropOldest => {
+                    pending.pop_front();
+                }
+                Overflow::DropNewest => continue,
+            }
+        }
+        pending.push_back(received);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Sender, Type};
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn queue(capacity: usize, overflow: Overflow) -> Queue {
+        let receiver = Receiver::bind_to("127.0.0.1:0").unwrap();
+        Queue::with_capacity(receiver, capacity, overflow).unwrap()
+    }
+
+    // Send a message for each of the given indices and wait for the queue to handle them all.
+    fn send(queue: &Queue, indices: std::ops::Range<i32>) -> SocketAddr {
+        let sender = Sender::bind_to("127.0.0.1:0").unwrap();
+        let addr = queue.local_addr().unwrap();
+        let handled = queue.len() as u64 + queue.dropped() + indices.len() as u64;
+        for i in indices {
+            sender
+                .send(crate::msg("/n", vec![Type::Int(i)]), addr)
+                .unwrap();
+        }
+        let start = Instant::now();
+        while (queue.len() as u64 + queue.dropped()) < handled {
+            assert!(start.elapsed() < TIMEOUT, "packets were never received");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        sender.local_addr().unwrap()
+    }
+
+    // The indices of the pending messages.
+    fn drain(queue: &Queue) -> Vec<i32> {
+        queue
+            .try_iter()
+            .flat_map(|received| received.packet.into_msgs())
+            .map(|msg| match msg.args[..] {
+                [Type::Int(i)] => i,
+                ref args => panic!("unexpected args: {:?}", args),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packets_are_queued_in_order() {
+        let queue = queue(8, Overflow::DropOldest);
+        let sender_addr = send(&queue, 0..3);
+        let received = queue.try_recv().unwrap();
+        assert_eq!(received.addr, sender_addr);
+        assert_eq!(drain(&queue), vec![1, 2]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_most_recent_packets() {
+        let queue = queue(2, Overflow::DropOldest);
+        send(&queue, 0..5);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(drain(&queue), vec![3, 4]);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_pending_packets() {
+        let queue = queue(2, Overflow::DropNewest);
+        send(&queue, 0..5);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(drain(&queue), vec![0, 1]);
+    }
+
+    #[test]
+    fn drained_queue_accepts_packets_again() {
+        let queue = queue(1, Overflow::DropNewest);
+        send(&queue, 0..2);
+        assert_eq!(drain(&queue), vec![0]);
+        send(&queue, 2..3);
+        assert_eq!(drain(&queue), vec![2]);
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[test]
+    fn default_capacity_and_overflow() {
+        let queue = Queue::bind_to("127.0.0.1:0").unwrap();
+        assert_eq!(queue.capacity(), Queue::DEFAULT_CAPACITY);
+        assert_eq!(queue.overflow(), Overflow::DropOldest);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_capacity_panics() {
+        queue(0, Overflow::DropOldest);
+    }
+}
@@ -1,10 +1,15 @@
 //! Items related to the `osc::Receiver` implementation.
 
-use super::{decode, rosc, CommunicationError, Connected, Packet, Unconnected};
+use super::lenient::{decode_lenient, DecodeWarning};
+use super::schema::{Schema, ValidationError};
+use super::{
+    decode, rosc, CommunicationError, Connected, Message, MessageGroup, Packet, Unconnected,
+};
 use std;
 use std::net::{SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket};
 use std::sync::atomic::{self, AtomicBool};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// The default "maximum transmission unit" size as a number of bytes.
 ///
@@ -19,9 +24,33 @@ pub struct Receiver<M = Unconnected> {
     buffer: Mutex<Vec<u8>>,
     socket: UdpSocket,
     non_blocking: AtomicBool,
+    // Registered functions are shared so that they may be called without holding the lock on their
+    // slot, allowing them to re-register or clear functions, or to set the schema.
+    bundle_fn: Mutex<Option<Arc<Mutex<Box<BundleFn>>>>>,
+    schema: Mutex<Option<Arc<Schema>>>,
+    invalid_fn: Mutex<Option<Arc<Mutex<Box<InvalidFn>>>>>,
+    lenient: AtomicBool,
+    decode_warning_fn: Mutex<Option<Arc<Mutex<Box<DecodeWarningFn>>>>>,
+    #[cfg(feature = "compression")]
+    decompress_max_len: Mutex<Option<usize>>,
     mode: M,
 }
 
+/// A function registered via `Receiver::on_bundle`, called with all messages of each received
+/// bundle along with the source address.
+pub type BundleFn = dyn FnMut(MessageGroup, SocketAddr) + Send;
+
+/// A function registered via `Receiver::on_invalid`, called with each received message that fails
+/// to conform to the receiver's schema along with the error and the source address.
+pub type InvalidFn = dyn FnMut(Message, ValidationError, SocketAddr) + Send;
+
+/// A function registered via `Receiver::on_decode_warning`, called with each deviation from the
+/// OSC spec recovered from while decoding a packet in lenient mode along with the source address.
+pub type DecodeWarningFn = dyn FnMut(DecodeWarning, SocketAddr) + Send;
+
+// A decoded packet along with any warnings produced while decoding it leniently.
+type Decoded = (Packet, Vec<DecodeWarning>);
+
 /// An iterator that calls `recv` on the inner `Receiver` and yields the results.
 ///
 /// If the `Receiver` is `Connected`, this will yield `Packet`s.
@@ -58,6 +87,179 @@ impl<M> Receiver<M> {
         self.socket.local_addr()
     }
 
+    /// Register a function to be called with each received bundle.
+    ///
+    /// While a function is registered, bundles are no longer yielded by the `recv` and `try_recv`
+    /// methods or their iterators. Instead, all messages of each bundle are delivered to the
+    /// function at once as a single `MessageGroup`, along with the bundle's time tag. This allows
+    /// multi-parameter updates to be applied atomically rather than interleaved with other
+    /// messages. Lone messages continue to be yielded as usual.
+    ///
+    /// The function is called on the thread calling `recv` or `try_recv`. It may register or clear
+    /// any of the receiver's functions or set its schema, but must not receive from the receiver.
+    pub fn on_bundle<F>(&self, bundle_fn: F) -> Result<(), CommunicationError>
+    where
+        F: 'static + FnMut(MessageGroup, SocketAddr) + Send,
+    {
+        *self.bundle_fn.lock()? = Some(Arc::new(Mutex::new(Box::new(bundle_fn))));
+        Ok(())
+    }
+
+    /// Remove any function registered via `on_bundle`, returning bundles to the packet stream.
+    pub fn clear_on_bundle(&self) -> Result<(), CommunicationError> {
+        *self.bundle_fn.lock()? = None;
+        Ok(())
+    }
+
+    /// Validate all received messages against the given schema.
+    ///
+    /// Messages are conformed according to the schema's policy before they are yielded or
+    /// delivered to the bundle function. Messages that fail to conform are removed, including
+    /// from within bundles, and passed to the function registered via `on_invalid` if there is
+    /// one.
+    pub fn set_schema(&self, schema: Schema) -> Result<(), CommunicationError> {
+        *self.schema.lock()? = Some(Arc::new(schema));
+        Ok(())
+    }
+
+    /// Remove any schema set via `set_schema`, no longer validating received messages.
+    pub fn clear_schema(&self) -> Result<(), CommunicationError> {
+        *self.schema.lock()? = None;
+        Ok(())
+    }
+
+    /// Register a function to be called with each received message that fails to conform to the
+    /// receiver's schema.
+    ///
+    /// The function is called on the thread calling `recv` or `try_recv`.
+    pub fn on_invalid<F>(&self, invalid_fn: F) -> Result<(), CommunicationError>
+    where
+        F: 'static + FnMut(Message, ValidationError, SocketAddr) + Send,
+    {
+        *self.invalid_fn.lock()? = Some(Arc::new(Mutex::new(Box::new(invalid_fn))));
+        Ok(())
+    }
+
+    /// Whether or not to salvage the valid messages of packets that do not conform to the OSC
+    /// spec rather than returning an error.
+    ///
+    /// While lenient, packets are decoded via `decode_lenient` and each deviation from the spec is
+    /// passed to the function registered via `on_decode_warning` if there is one.
+    ///
+    /// By default, the receiver is strict.
+    pub fn set_lenient(&self, lenient: bool) {
+        self.lenient.store(lenient, atomic::Ordering::Relaxed);
+    }
+
+    /// Register a function to be called with each deviation from the OSC spec recovered from while
+    /// decoding packets in lenient mode.
+    ///
+    /// The function is called on the thread calling `recv` or `try_recv`.
+    pub fn on_decode_warning<F>(&self, decode_warning_fn: F) -> Result<(), CommunicationError>
+    where
+        F: 'static + FnMut(DecodeWarning, SocketAddr) + Send,
+    {
+        *self.decode_warning_fn.lock()? = Some(Arc::new(Mutex::new(Box::new(decode_warning_fn))));
+        Ok(())
+    }
+
+    /// Decompress blobs compressed by a `Sender` via `Sender::compress_blobs`.
+    ///
+    /// Blobs that declare a decompressed size greater than `max_len` bytes are rejected with a
+    /// `CommunicationError::Decompress`. `compress::DEFAULT_MAX_DECOMPRESSED_LEN` is a reasonable
+    /// default. Pass `None` to stop decompressing blobs.
+    ///
+    /// Only enable this for receivers whose senders compress their blobs. Uncompressed blobs that
+    /// happen to begin with `compress::MAGIC` are otherwise mistaken for compressed blobs. See the
+    /// `compress` module for details.
+    ///
+    /// By default, blobs are not decompressed.
+    #[cfg(feature = "compression")]
+    pub fn set_decompress_blobs(&self, max_len: Option<usize>) -> Result<(), CommunicationError> {
+        *self.decompress_max_len.lock()? = max_len;
+        Ok(())
+    }
+
+    // Decode the received bytes, leniently if enabled, decompressing any compressed blobs if
+    // enabled.
+    //
+    // Returns the packet along with any warnings produced while decoding leniently. These are
+    // delivered by `dispatch` once the receive buffer is no longer locked.
+    fn decode_packet(&self, bytes: &[u8]) -> Result<Decoded, CommunicationError> {
+        #[allow(unused_mut)]
+        let (mut packet, warnings) = match self.lenient.load(atomic::Ordering::Relaxed) {
+            false => (decode(bytes)?, vec![]),
+            true => decode_lenient(bytes)?,
+        };
+        #[cfg(feature = "compression")]
+        {
+            let max_len = *self.decompress_max_len.lock()?;
+            if let Some(max_len) = max_len {
+                super::compress::decompress_packet(&mut packet, max_len)?;
+            }
+        }
+        Ok((packet, warnings))
+    }
+
+    // Conform the packet to the schema if one is set.
+    //
+    // Returns `None` if the packet was a lone message that failed to conform.
+    fn conform(
+        &self,
+        packet: Packet,
+        addr: SocketAddr,
+    ) -> Result<Option<Packet>, CommunicationError> {
+        let schema = match self.schema.lock()?.clone() {
+            None => return Ok(Some(packet)),
+            Some(schema) => schema,
+        };
+        let mut invalid = vec![];
+        let packet = schema.conform_packet(packet, |msg, err| invalid.push((msg, err)));
+        if let Some(invalid_fn) = registered(&self.invalid_fn)? {
+            let mut invalid_fn = invalid_fn.lock()?;
+            for (msg, err) in invalid {
+                (*invalid_fn)(msg, err, addr);
+            }
+        }
+        Ok(packet)
+    }
+
+    // Deliver any decode warnings, conform the packet to the schema, then deliver it to the
+    // bundle function if it is a bundle and a function is registered.
+    //
+    // Returns the packet if it was not delivered or removed.
+    fn dispatch(
+        &self,
+        (packet, warnings): Decoded,
+        addr: SocketAddr,
+    ) -> Result<Option<Packet>, CommunicationError> {
+        if !warnings.is_empty() {
+            if let Some(decode_warning_fn) = registered(&self.decode_warning_fn)? {
+                let mut decode_warning_fn = decode_warning_fn.lock()?;
+                for warning in warnings {
+                    (*decode_warning_fn)(warning, addr);
+                }
+            }
+        }
+        let packet = match self.conform(packet, addr)? {
+            None => return Ok(None),
+            Some(packet) => packet,
+        };
+        if let Packet::Bundle(_) = packet {
+            if let Some(bundle_fn) = registered(&self.bundle_fn)? {
+                (*bundle_fn.lock()?)(packet.into_group(), addr);
+                return Ok(None);
+            }
+        }
+        Ok(Some(packet))
+    }
+
+    // Set the read timeout of the inner socket, allowing a blocking `recv` to return periodically.
+    // This is used by the `Queue` to check whether it has been closed.
+    pub(crate) fn set_read_timeout(&self, dur: Option<Duration>) -> Result<(), std::io::Error> {
+        self.socket.set_read_timeout(dur)
+    }
+
     // Switch the `Receiver`'s inner socket to blocking mode.
     // This is for internal use only - the `recv` methods will call this automatically.
     fn switch_to_blocking(&self) -> Result<(), std::io::Error> {
@@ -120,10 +322,22 @@ impl Receiver<Unconnected> {
         let socket = UdpSocket::bind(addr)?;
         let non_blocking = AtomicBool::new(DEFAULT_NON_BLOCKING);
         let mode = Unconnected;
+        let bundle_fn = Mutex::new(None);
+        let schema = Mutex::new(None);
+        let invalid_fn = Mutex::new(None);
+        let lenient = AtomicBool::new(false);
+        let decode_warning_fn = Mutex::new(None);
         let receiver = Receiver {
             buffer,
             socket,
             non_blocking,
+            bundle_fn,
+            schema,
+            invalid_fn,
+            lenient,
+            decode_warning_fn,
+            #[cfg(feature = "compression")]
+            decompress_max_len: Mutex::new(None),
             mode,
         };
         Ok(receiver)
@@ -185,6 +399,13 @@ impl Receiver<Unconnected> {
             buffer,
             socket,
             non_blocking,
+            bundle_fn,
+            schema,
+            invalid_fn,
+            lenient,
+            decode_warning_fn,
+            #[cfg(feature = "compression")]
+            decompress_max_len,
             ..
         } = self;
         let mut addrs = addr.to_socket_addrs()?;
@@ -195,6 +416,13 @@ impl Receiver<Unconnected> {
             buffer,
             socket,
             non_blocking,
+            bundle_fn,
+            schema,
+            invalid_fn,
+            lenient,
+            decode_warning_fn,
+            #[cfg(feature = "compression")]
+            decompress_max_len,
             mode,
         })
     }
@@ -213,10 +441,16 @@ impl Receiver<Unconnected> {
     /// - The socket received some bytes that could not be decoded into an OSC `Packet`.
     pub fn recv(&self) -> Result<(Packet, SocketAddr), CommunicationError> {
         self.switch_to_blocking()?;
-        let mut buffer = self.buffer.lock()?;
-        let (len, addr) = self.socket.recv_from(&mut buffer)?;
-        let packet = decode(&buffer[..len])?;
-        Ok((packet, addr))
+        loop {
+            let (decoded, addr) = {
+                let mut buffer = self.buffer.lock()?;
+                let (len, addr) = self.socket.recv_from(&mut buffer)?;
+                (self.decode_packet(&buffer[..len])?, addr)
+            };
+            if let Some(packet) = self.dispatch(decoded, addr)? {
+                return Ok((packet, addr));
+            }
+        }
     }
 
     /// Checks for a pending OSC packet and returns `Ok(Some)` if there is one waiting along with
@@ -238,16 +472,23 @@ impl Receiver<Unconnected> {
     /// - The socket received some bytes that could not be decoded into an OSC `Packet`.
     pub fn try_recv(&self) -> Result<Option<(Packet, SocketAddr)>, CommunicationError> {
         self.switch_to_non_blocking()?;
-        let mut buffer = self.buffer.lock()?;
-        let (len, addr) = match self.socket.recv_from(&mut buffer) {
-            Ok(tuple) => tuple,
-            // TODO: Don't know how to check for the specific error that is returned when the
-            // non_blocking socket has no bytes waiting, so we just always assume that's what the
-            // error was. This should probably be fixed somehow to distinguish between errors.
-            Err(_) => return Ok(None),
-        };
-        let packet = decode(&buffer[..len])?;
-        Ok(Some((packet, addr)))
+        loop {
+            let (decoded, addr) = {
+                let mut buffer = self.buffer.lock()?;
+                let (len, addr) = match self.socket.recv_from(&mut buffer) {
+                    Ok(tuple) => tuple,
+                    // TODO: Don't know how to check for the specific error that is returned when
+                    // the non_blocking socket has no bytes waiting, so we just always assume
+                    // that's what the error was. This should probably be fixed somehow to
+                    // distinguish between errors.
+                    Err(_) => return Ok(None),
+                };
+                (self.decode_packet(&buffer[..len])?, addr)
+            };
+            if let Some(packet) = self.dispatch(decoded, addr)? {
+                return Ok(Some((packet, addr)));
+            }
+        }
     }
 
     /// An iterator yielding OSC `Packet`s along with their source address.
@@ -287,10 +528,16 @@ impl Receiver<Connected> {
     /// - The socket received some bytes that could not be decoded into an OSC `Packet`.
     pub fn recv(&self) -> Result<Packet, CommunicationError> {
         self.switch_to_blocking()?;
-        let mut buffer = self.buffer.lock()?;
-        let len = self.socket.recv(&mut buffer)?;
-        let packet = decode(&buffer[..len])?;
-        Ok(packet)
+        loop {
+            let decoded = {
+                let mut buffer = self.buffer.lock()?;
+                let len = self.socket.recv(&mut buffer)?;
+                self.decode_packet(&buffer[..len])?
+            };
+            if let Some(packet) = self.dispatch(decoded, self.mode.addr)? {
+                return Ok(packet);
+            }
+        }
     }
 
     /// Checks for a pending OSC packet and returns `Ok(Some)` if there is one waiting.
@@ -308,16 +555,23 @@ impl Receiver<Connected> {
     /// - The socket received some bytes that could not be decoded into an OSC `Packet`.
     pub fn try_recv(&self) -> Result<Option<Packet>, CommunicationError> {
         self.switch_to_non_blocking()?;
-        let mut buffer = self.buffer.lock()?;
-        let len = match self.socket.recv(&mut buffer) {
-            Ok(len) => len,
-            // TODO: Don't know how to check for the specific error that is returned when the
-            // non_blocking socket has no bytes waiting, so we just always assume that's what the
-            // error was. This should probably be fixed somehow to distinguish between errors.
-            Err(_) => return Ok(None),
-        };
-        let packet = decode(&buffer[..len])?;
-        Ok(Some(packet))
+        loop {
+            let decoded = {
+                let mut buffer = self.buffer.lock()?;
+                let len = match self.socket.recv(&mut buffer) {
+                    Ok(len) => len,
+                    // TODO: Don't know how to check for the specific error that is returned when
+                    // the non_blocking socket has no bytes waiting, so we just always assume
+                    // that's what the error was. This should probably be fixed somehow to
+                    // distinguish between errors.
+                    Err(_) => return Ok(None),
+                };
+                self.decode_packet(&buffer[..len])?
+            };
+            if let Some(packet) = self.dispatch(decoded, self.mode.addr)? {
+                return Ok(Some(packet));
+            }
+        }
     }
 
     /// An iterator yielding OSC `Packet`s.
@@ -364,3 +618,139 @@ impl<'a> Iterator for TryIter<'a, Unconnected> {
         self.receiver.try_recv().ok().and_then(|p| p)
     }
 }
+
+// Clone the function registered in the given slot, if any, so that it may be called without
+// holding the lock on the slot.
+fn registered<F>(
+    slot: &Mutex<Option<Arc<Mutex<Box<F>>>>>,
+) -> Result<Option<Arc<Mutex<Box<F>>>>, CommunicationError>
+where
+    F: ?Sized,
+{
+    Ok(slot.lock()?.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{Address, Arg};
+    use crate::{Bundle, Sender, Time, Type};
+    use std::sync::mpsc;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn receiver() -> Receiver {
+        let receiver = Receiver::bind_to("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(TIMEOUT)).unwrap();
+        receiver
+    }
+
+    fn msg(addr: &str, value: i32) -> Message {
+        crate::msg(addr, vec![Type::Int(value)])
+    }
+
+    fn bundle(seconds: u32, content: Vec<Packet>) -> Packet {
+        let timetag = Time {
+            seconds,
+            fractional: 0,
+        };
+        let content = content.into_iter().map(Into::into).collect();
+        Bundle { timetag, content }.into()
+    }
+
+    // Dispatch the packet as though it were received from the receiver's own address.
+    fn dispatch(receiver: &Receiver, packet: Packet) -> Option<Packet> {
+        let addr = receiver.local_addr().unwrap();
+        receiver.dispatch((packet, vec![]), addr).unwrap()
+    }
+
+    // Register a bundle function that forwards each group to the returned channel.
+    fn forward_bundles(receiver: &Receiver) -> mpsc::Receiver<MessageGroup> {
+        let (tx, rx) = mpsc::channel();
+        receiver
+            .on_bundle(move |group, _addr| tx.send(group).unwrap())
+            .unwrap();
+        rx
+    }
+
+    #[test]
+    fn bundles_are_yielded_without_a_bundle_fn() {
+        let receiver = receiver();
+        let packet = bundle(1, vec![msg("/a", 1).into()]);
+        assert_eq!(dispatch(&receiver, packet.clone()), Some(packet));
+    }
+
+    #[test]
+    fn bundles_are_delivered_to_the_bundle_fn() {
+        let receiver = receiver();
+        let groups = forward_bundles(&receiver);
+        let packet = bundle(1, vec![msg("/a", 1).into(), msg("/b", 2).into()]);
+        assert_eq!(dispatch(&receiver, packet), None);
+        let group = groups.try_recv().unwrap();
+        assert_eq!(group.time.map(|t| t.seconds), Some(1));
+        assert_eq!(group.messages, vec![msg("/a", 1), msg("/b", 2)]);
+    }
+
+    #[test]
+    fn lone_messages_bypass_the_bundle_fn() {
+        let receiver = receiver();
+        let groups = forward_bundles(&receiver);
+        let packet: Packet = msg("/a", 1).into();
+        assert_eq!(dispatch(&receiver, packet.clone()), Some(packet));
+        assert!(groups.try_recv().is_err());
+    }
+
+    #[test]
+    fn nested_bundles_are_flattened_with_the_outer_time() {
+        let receiver = receiver();
+        let groups = forward_bundles(&receiver);
+        let inner = bundle(2, vec![msg("/b", 2).into(), msg("/c", 3).into()]);
+        let packet = bundle(1, vec![msg("/a", 1).into(), inner, msg("/d", 4).into()]);
+        assert_eq!(dispatch(&receiver, packet), None);
+        let group = groups.try_recv().unwrap();
+        assert_eq!(group.time.map(|t| t.seconds), Some(1));
+        let expected = vec![msg("/a", 1), msg("/b", 2), msg("/c", 3), msg("/d", 4)];
+        assert_eq!(group.messages, expected);
+        assert!(groups.try_recv().is_err());
+    }
+
+    #[test]
+    fn schema_removes_invalid_messages_from_bundles() {
+        let receiver = receiver();
+        let schema = Schema::new().address("/a", Address::new().arg(Arg::int()));
+        receiver.set_schema(schema).unwrap();
+        let groups = forward_bundles(&receiver);
+        let inner = bundle(2, vec![msg("/a", 2).into(), msg("/unknown", 3).into()]);
+        let packet = bundle(1, vec![msg("/a", 1).into(), inner]);
+        dispatch(&receiver, packet);
+        let group = groups.try_recv().unwrap();
+        assert_eq!(group.messages, vec![msg("/a", 1), msg("/a", 2)]);
+        assert_eq!(dispatch(&receiver, msg("/unknown", 1).into()), None);
+    }
+
+    #[test]
+    fn clear_on_bundle_returns_bundles_to_the_stream() {
+        let receiver = receiver();
+        let groups = forward_bundles(&receiver);
+        receiver.clear_on_bundle().unwrap();
+        let packet = bundle(1, vec![msg("/a", 1).into()]);
+        assert_eq!(dispatch(&receiver, packet.clone()), Some(packet));
+        assert!(groups.try_recv().is_err());
+    }
+
+    #[test]
+    fn received_bundles_are_delivered_before_the_next_message() {
+        let receiver = receiver();
+        let groups = forward_bundles(&receiver);
+        let sender = Sender::bind_to("127.0.0.1:0").unwrap();
+        let addr = receiver.local_addr().unwrap();
+        sender
+            .send(bundle(1, vec![msg("/a", 1).into()]), addr)
+            .unwrap();
+        sender.send(msg("/b", 2), addr).unwrap();
+        let (packet, from) = receiver.recv().unwrap();
+        assert_eq!(packet, Packet::from(msg("/b", 2)));
+        assert_eq!(from, sender.local_addr().unwrap());
+        assert_eq!(groups.try_recv().unwrap().messages, vec![msg("/a", 1)]);
+    }
+}
@@ -0,0 +1,680 @@
+//! Declaration and validation of the address space expected by a receiver.
+//!
+//! A [**Schema**](./struct.Schema.html) describes the arguments expected at each address,
+//! including their types and optionally the range of numeric values. Incoming messages may be
+//! checked against the schema in order to catch remotes that send ints instead of floats, too few
+//! arguments or values outside of the expected range.
+//!
+//! Depending on the schema's [**Policy**](./enum.Policy.html), non-conforming messages either
+//! produce a [**ValidationError**](./enum.ValidationError.html) or are adjusted to conform where
+//! possible, e.g. by clamping values into range.
+//!
+//! A schema may be set on a **Receiver** via `Receiver::set_schema`. The `Display` implementation
+//! produces a human-readable list of all addresses, useful for documenting the OSC interface of
+//! an app.
+//!
+//! ```
+//! use nannou_osc::schema::{Address, Arg, Schema};
+//!
+//! let schema = Schema::new()
+//!     .address(
+//!         "/synth/freq",
+//!         Address::new()
+//!             .arg(Arg::float().name("hz").range(20.0, 20_000.0))
+//!             .description("The oscillator frequency."),
+//!     )
+//!     .address("/synth/gate", Address::new().arg(Arg::bool()));
+//!
+//! let msg = nannou_osc::msg("/synth/freq", vec![nannou_osc::Type::Int(440)]);
+//! assert!(schema.validate(&msg).is_err());
+//! ```
+
+use crate::{Message, Packet, Type};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The expected arguments of each address within an OSC address space.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schema {
+    addresses: BTreeMap<String, Address>,
+    policy: Policy,
+    allow_unknown: bool,
+}
+
+/// The expected arguments of a single address.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Address {
+    /// The expected arguments in order.
+    pub args: Vec<Arg>,
+    /// A description of the address for documentation.
+    pub description: Option<String>,
+}
+
+/// The expected type and range of a single argument.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arg {
+    /// A name for the argument for documentation.
+    pub name: Option<String>,
+    /// The expected type of the argument.
+    pub kind: ArgKind,
+    /// The inclusive range of expected values for a numeric argument.
+    pub range: Option<(f64, f64)>,
+}
+
+/// The type of an OSC argument.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArgKind {
+    Int,
+    Float,
+    String,
+    Blob,
+    Time,
+    Long,
+    Double,
+    Char,
+    Color,
+    Midi,
+    Bool,
+    Array,
+    Nil,
+    Inf,
+}
+
+/// How non-conforming messages are handled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Non-conforming messages produce an error.
+    Reject,
+    /// Messages are adjusted to conform where possible.
+    ///
+    /// Numeric arguments are converted to the expected numeric type, values outside of the
+    /// expected range are clamped and unexpected trailing arguments are removed. Messages that
+    /// still do not conform, e.g. due to missing arguments, produce an error.
+    Clamp,
+}
+
+/// The ways in which a message might fail to conform to a schema.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationError {
+    /// The address is not declared within the schema.
+    UnknownAddress { addr: String },
+    /// The message has an unexpected number of arguments.
+    ArgCount {
+        addr: String,
+        expected: usize,
+        found: usize,
+    },
+    /// An argument has an unexpected type.
+    ArgType {
+        addr: String,
+        index: usize,
+        expected: ArgKind,
+        found: ArgKind,
+    },
+    /// A numeric argument lies outside of the expected range.
+    OutOfRange {
+        addr: String,
+        index: usize,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+}
+
+impl Schema {
+    /// An empty schema that rejects non-conforming messages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare the expected arguments of the given address.
+    ///
+    /// Replaces any existing declaration for the address.
+    pub fn address<A>(mut self, addr: A, address: Address) -> Self
+    where
+        A: Into<String>,
+    {
+        self.addresses.insert(addr.into(), address);
+        self
+    }
+
+    /// How non-conforming messages are handled.
+    ///
+    /// By default, this value is `Policy::Reject`.
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Whether or not messages to addresses that are not declared within the schema are accepted
+    /// without validation.
+    ///
+    /// By default, this value is `false`.
+    pub fn allow_unknown_addresses(mut self, allow: bool) -> Self {
+        self.allow_unknown = allow;
+        self
+    }
+
+    /// The declaration for the given address, if there is one.
+    pub fn get(&self, addr: &str) -> Option<&Address> {
+        self.addresses.get(addr)
+    }
+
+    /// All declared addresses in alphabetical order.
+    pub fn addresses(&self) -> impl Iterator<Item = (&str, &Address)> {
+        self.addresses.iter().map(|(addr, a)| (&addr[..], a))
+    }
+
+    /// Check that the message conforms to the schema, regardless of the schema's policy.
+    pub fn validate(&self, msg: &Message) -> Result<(), ValidationError> {
+        self.check(msg, Policy::Reject).map(|_| ())
+    }
+
+    /// Check the message against the schema, adjusting it to conform if the schema's policy is
+    /// `Policy::Clamp`.
+    pub fn conform(&self, msg: &mut Message) -> Result<(), ValidationError> {
+        if let Some(args) = self.check(msg, self.policy)? {
+            msg.args = args;
+        }
+        Ok(())
+    }
+
+    /// Conform all messages within the packet, including those of nested bundles.
+    ///
+    /// Messages that fail to conform are removed from the packet and passed to `invalid` along
+    /// with the error. Returns `None` if the packet was a lone message that failed to conform.
+    pub fn conform_packet<F>(&self, packet: Packet, mut invalid: F) -> Option<Packet>
+    where
+        F: FnMut(Message, ValidationError),
+    {
+        self.conform_packet_inner(packet, &mut invalid)
+    }
+
+    // Recursively conform the packet.
+    fn conform_packet_inner<F>(&self, packet: Packet, invalid: &mut F) -> Option<Packet>
+    where
+        F: FnMut(Message, ValidationError),
+    {
+        match packet {
+            Packet::Message(mut msg) => match self.conform(&mut msg) {
+                Ok(()) => Some(Packet::Message(msg)),
+                Err(err) => {
+                    invalid(msg, err);
+                    None
+                }
+            },
+            Packet::Bundle(mut bundle) => {
+                bundle.content = bundle
+                    .content
+                    .into_iter()
+                    .filter_map(|p| self.conform_packet_inner(p.into(), invalid))
+                    .map(Into::into)
+                    .collect();
+                Some(Packet::Bundle(bundle))
+            }
+        }
+    }
+
+    // Check the message with the given policy, returning the adjusted arguments if they differ.
+    fn check(&self, msg: &Message, policy: Policy) -> Result<Option<Vec<Type>>, ValidationError> {
+        let address = match self.addresses.get(&msg.addr) {
+            Some(address) => address,
+            None if self.allow_unknown => return Ok(None),
+            None => {
+                return Err(ValidationError::UnknownAddress {
+                    addr: msg.addr.clone(),
+                })
+            }
+        };
+
+        let expected = address.args.len();
+        let found = msg.args.len();
+        if found < expected || (found > expected && policy == Policy::Reject) {
+            return Err(ValidationError::ArgCount {
+                addr: msg.addr.clone(),
+                expected,
+                found,
+            });
+        }
+
+        let mut adjusted: Option<Vec<Type>> = None;
+        if found > expected {
+            adjusted = Some(msg.args[..expected].to_vec());
+        }
+        for (index, (arg, spec)) in msg.args.iter().zip(&address.args).enumerate() {
+            let replacement = spec
+                .check(arg, policy)
+                .map_err(|err| err.at(&msg.addr, index))?;
+            if let Some(replacement) = replacement {
+                let args = adjusted.get_or_insert_with(|| msg.args[..expected].to_vec());
+                args[index] = replacement;
+            }
+        }
+        Ok(adjusted)
+    }
+}
+
+impl Address {
+    /// An address that expects no arguments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an expected argument.
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// A description of the address for documentation.
+    pub fn description<S>(mut self, description: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+impl Arg {
+    /// An argument of the given kind.
+    pub fn new(kind: ArgKind) -> Self {
+        Arg {
+            name: None,
+            kind,
+            range: None,
+        }
+    }
+
+    /// An `i32` argument.
+    pub fn int() -> Self {
+        Self::new(ArgKind::Int)
+    }
+
+    /// An `f32` argument.
+    pub fn float() -> Self {
+        Self::new(ArgKind::Float)
+    }
+
+    /// An `i64` argument.
+    pub fn long() -> Self {
+        Self::new(ArgKind::Long)
+    }
+
+    /// An `f64` argument.
+    pub fn double() -> Self {
+        Self::new(ArgKind::Double)
+    }
+
+    /// A string argument.
+    pub fn string() -> Self {
+        Self::new(ArgKind::String)
+    }
+
+    /// A boolean argument.
+    pub fn bool() -> Self {
+        Self::new(ArgKind::Bool)
+    }
+
+    /// A blob argument.
+    pub fn blob() -> Self {
+        Self::new(ArgKind::Blob)
+    }
+
+    /// A name for the argument for documentation.
+    pub fn name<S>(mut self, name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The inclusive range of expected values for a numeric argument.
+    ///
+    /// The range is ignored for non-numeric arguments.
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    // Check the argument with the given policy, returning a replacement if it was adjusted.
+    //
+    // The `addr` and `index` of the returned error are filled in by the caller.
+    fn check(&self, arg: &Type, policy: Policy) -> Result<Option<Type>, ValidationError> {
+        let found = ArgKind::of(arg);
+        let mut replacement = None;
+        if found != self.kind {
+            match (policy, numeric_value(arg)) {
+                (Policy::Clamp, Some(value)) if self.kind.is_numeric() => {
+                    replacement = Some(numeric_arg(self.kind, value));
+                }
+                _ => {
+                    return Err(ValidationError::ArgType {
+                        addr: String::new(),
+                        index: 0,
+                        expected: self.kind,
+                        found,
+                    })
+                }
+            }
+        }
+        if let Some((min, max)) = self.range {
+            let value = replacement.as_ref().unwrap_or(arg);
+            if let Some(value) = numeric_value(value) {
+                if !(min..=max).contains(&value) {
+                    match policy {
+                        Policy::Clamp => {
+                            let clamped = value.max(min).min(max);
+                            replacement = Some(numeric_arg(self.kind, clamped));
+                        }
+                        Policy::Reject => {
+                            return Err(ValidationError::OutOfRange {
+                                addr: String::new(),
+                                index: 0,
+                                value,
+                                min,
+                                max,
+                            })
+                        }
+                    }
+                }
+            }
+        }
+        Ok(replacement)
+    }
+}
+
+impl ArgKind {
+    /// The kind of the given argument.
+    pub fn of(arg: &Type) -> Self {
+        match *arg {
+            Type::Int(_) => ArgKind::Int,
+            Type::Float(_) => ArgKind::Float,
+            Type::String(_) => ArgKind::String,
+            Type::Blob(_) => ArgKind::Blob,
+            Type::Time(_) => ArgKind::Time,
+            Type::Long(_) => ArgKind::Long,
+            Type::Double(_) => ArgKind::Double,
+            Type::Char(_) => ArgKind::Char,
+            Type::Color(_) => ArgKind::Color,
+            Type::Midi(_) => ArgKind::Midi,
+            Type::Bool(_) => ArgKind::Bool,
+            Type::Array(_) => ArgKind::Array,
+            Type::Nil => ArgKind::Nil,
+            Type::Inf => ArgKind::Inf,
+        }
+    }
+
+    /// Whether or not the kind is one of the numeric kinds `Int`, `Float`, `Long` or `Double`.
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            *self,
+            ArgKind::Int | ArgKind::Float | ArgKind::Long | ArgKind::Double
+        )
+    }
+
+    /// The name of the kind as used within documentation, e.g. `"float"`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ArgKind::Int => "int",
+            ArgKind::Float => "float",
+            ArgKind::String => "string",
+            ArgKind::Blob => "blob",
+            ArgKind::Time => "time",
+            ArgKind::Long => "long",
+            ArgKind::Double => "double",
+            ArgKind::Char => "char",
+            ArgKind::Color => "color",
+            ArgKind::Midi => "midi",
+            ArgKind::Bool => "bool",
+            ArgKind::Array => "array",
+            ArgKind::Nil => "nil",
+            ArgKind::Inf => "inf",
+        }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::Reject
+    }
+}
+
+impl ValidationError {
+    /// The address of the message that failed to conform.
+    pub fn addr(&self) -> &str {
+        match *self {
+            ValidationError::UnknownAddress { ref addr }
+            | ValidationError::ArgCount { ref addr, .. }
+            | ValidationError::ArgType { ref addr, .. }
+            | ValidationError::OutOfRange { ref addr, .. } => addr,
+        }
+    }
+
+    // Fill in the address and argument index of an argument error.
+    fn at(mut self, msg_addr: &str, arg_index: usize) -> Self {
+        match self {
+            ValidationError::ArgType {
+                ref mut addr,
+                ref mut index,
+                ..
+            }
+            | ValidationError::OutOfRange {
+                ref mut addr,
+                ref mut index,
+                ..
+            } => {
+                *addr = msg_addr.to_string();
+                *index = arg_index;
+            }
+            _ => (),
+        }
+        self
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::UnknownAddress { ref addr } => {
+                write!(f, "unknown address `{}`", addr)
+            }
+            ValidationError::ArgCount {
+                ref addr,
+                expected,
+                found,
+            } => write!(
+                f,
+                "`{}` expects {} argument(s) but received {}",
+                addr, expected, found
+            ),
+            ValidationError::ArgType {
+                ref addr,
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "argument {} of `{}` must be {} but received {}",
+                index,
+                addr,
+                expected.name(),
+                found.name()
+            ),
+            ValidationError::OutOfRange {
+                ref addr,
+                index,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "argument {} of `{}` must lie within [{}, {}] but received {}",
+                index, addr, min, max, value
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref name) = self.name {
+            write!(f, "{}:", name)?;
+        }
+        write!(f, "{}", self.kind.name())?;
+        if let Some((min, max)) = self.range {
+            write!(f, "[{}, {}]", min, max)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists each address on its own line along with its expected arguments and description.
+///
+/// ```text
+/// /synth/freq hz:float[20, 20000] - The oscillator frequency.
+/// /synth/gate bool
+/// ```
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (addr, address) in &self.addresses {
+            write!(f, "{}", addr)?;
+            for arg in &address.args {
+                write!(f, " {}", arg)?;
+            }
+            if let Some(ref description) = address.description {
+                write!(f, " - {}", description)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// The value of a numeric argument.
+fn numeric_value(arg: &Type) -> Option<f64> {
+    match *arg {
+        Type::Int(i) => Some(i as f64),
+        Type::Float(f) => Some(f as f64),
+        Type::Long(l) => Some(l as f64),
+        Type::Double(d) => Some(d),
+        _ => None,
+    }
+}
+
+// Produce a numeric argument of the given kind, rounding for integer kinds.
+fn numeric_arg(kind: ArgKind, value: f64) -> Type {
+    match kind {
+        ArgKind::Int => Type::Int(value.round() as i32),
+        ArgKind::Long => Type::Long(value.round() as i64),
+        ArgKind::Double => Type::Double(value),
+        _ => Type::Float(value as f32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rosc::OscPacket;
+
+    fn freq_schema(policy: Policy) -> Schema {
+        Schema::new()
+            .address(
+                "/freq",
+                Address::new().arg(Arg::float().range(20.0, 20_000.0)),
+            )
+            .policy(policy)
+    }
+
+    #[test]
+    fn validate_accepts_conforming_message() {
+        let schema = freq_schema(Policy::Reject);
+        let msg = crate::msg("/freq", vec![Type::Float(440.0)]);
+        assert!(schema.validate(&msg).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_address() {
+        let schema = freq_schema(Policy::Reject);
+        let msg = crate::msg("/gain", vec![Type::Float(1.0)]);
+        assert!(matches!(
+            schema.validate(&msg),
+            Err(ValidationError::UnknownAddress { .. })
+        ));
+        let schema = schema.allow_unknown_addresses(true);
+        assert!(schema.validate(&msg).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_wrong_arg_count_and_type() {
+        let schema = freq_schema(Policy::Reject);
+        let msg = crate::msg("/freq", vec![]);
+        assert!(matches!(
+            schema.validate(&msg),
+            Err(ValidationError::ArgCount { .. })
+        ));
+        let msg = crate::msg("/freq", vec![Type::String("440".into())]);
+        assert!(matches!(
+            schema.validate(&msg),
+            Err(ValidationError::ArgType { .. })
+        ));
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_and_nan() {
+        let schema = freq_schema(Policy::Reject);
+        for &value in &[10.0, 30_000.0, std::f32::NAN] {
+            let msg = crate::msg("/freq", vec![Type::Float(value)]);
+            assert!(matches!(
+                schema.validate(&msg),
+                Err(ValidationError::OutOfRange { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn conform_clamps_values_into_range() {
+        let schema = freq_schema(Policy::Clamp);
+        let mut msg = crate::msg("/freq", vec![Type::Float(30_000.0)]);
+        schema.conform(&mut msg).unwrap();
+        assert_eq!(msg.args, vec![Type::Float(20_000.0)]);
+        let mut msg = crate::msg("/freq", vec![Type::Double(std::f64::NAN)]);
+        schema.conform(&mut msg).unwrap();
+        assert_eq!(msg.args, vec![Type::Float(20.0)]);
+    }
+
+    #[test]
+    fn conform_converts_numeric_types_and_drops_extra_args() {
+        let schema = freq_schema(Policy::Clamp);
+        let mut msg = crate::msg("/freq", vec![Type::Int(440), Type::Int(1)]);
+        schema.conform(&mut msg).unwrap();
+        assert_eq!(msg.args, vec![Type::Float(440.0)]);
+    }
+
+    #[test]
+    fn conform_packet_removes_invalid_messages_from_bundles() {
+        let schema = freq_schema(Policy::Reject);
+        let bundle = crate::Bundle {
+            timetag: crate::Time {
+                seconds: 0,
+                fractional: 1,
+            },
+            content: vec![
+                OscPacket::Message(crate::msg("/freq", vec![Type::Float(440.0)])),
+                OscPacket::Message(crate::msg("/freq", vec![Type::Float(1.0)])),
+            ],
+        };
+        let mut invalid = vec![];
+        let packet = schema
+            .conform_packet(Packet::Bundle(bundle), |msg, _err| invalid.push(msg))
+            .unwrap();
+        match packet {
+            Packet::Bundle(bundle) => assert_eq!(bundle.content.len(), 1),
+            _ => panic!("expected a bundle"),
+        }
+        assert_eq!(invalid.len(), 1);
+    }
+}
@@ -3,6 +3,7 @@
 use super::{encode, CommunicationError, Connected, Packet, Unconnected};
 use std;
 use std::net::{SocketAddr, SocketAddrV4, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
 
 /// The default port bound to by the `Sender`.
 ///
@@ -14,6 +15,8 @@ pub const DEFAULT_PORT: u16 = 0;
 pub struct Sender<M = Unconnected> {
     socket: UdpSocket,
     mode: M,
+    #[cfg(feature = "compression")]
+    compression_threshold: Option<usize>,
 }
 
 /// The default socket address bound to by the `Sender`.
@@ -28,6 +31,33 @@ impl<M> Sender<M> {
     pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
         self.socket.local_addr()
     }
+
+    /// Compress all blob arguments larger than `threshold` bytes before sending.
+    ///
+    /// Compressed blobs are tagged so that a `Receiver` with decompression enabled via
+    /// `Receiver::set_decompress_blobs` transparently decompresses them. See the `compress` module
+    /// for details.
+    ///
+    /// `compress::DEFAULT_THRESHOLD` is a reasonable default.
+    #[cfg(feature = "compression")]
+    pub fn compress_blobs(mut self, threshold: usize) -> Self {
+        self.compression_threshold = Some(threshold);
+        self
+    }
+
+    // Encode the given packet, compressing its blobs first if necessary.
+    fn encode_packet(&self, packet: Packet) -> Result<Vec<u8>, CommunicationError> {
+        #[cfg(feature = "compression")]
+        let packet = {
+            let mut packet = packet;
+            if let Some(threshold) = self.compression_threshold {
+                super::compress::compress_packet(&mut packet, threshold);
+            }
+            packet
+        };
+        let bytes = encode(packet)?;
+        Ok(bytes)
+    }
 }
 
 impl Sender<Unconnected> {
@@ -55,7 +85,12 @@ impl Sender<Unconnected> {
     {
         let socket = UdpSocket::bind(addr)?;
         let mode = Unconnected;
-        let sender = Sender { socket, mode };
+        let sender = Sender {
+            socket,
+            mode,
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+        };
         Ok(sender)
     }
 
@@ -87,12 +122,22 @@ impl Sender<Unconnected> {
     where
         A: ToSocketAddrs,
     {
-        let Sender { socket, .. } = self;
+        let Sender {
+            socket,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+            ..
+        } = self;
         let mut addrs = addr.to_socket_addrs()?;
         let addr = addrs.next().expect("could not resolve any `SocketAddr`s");
         socket.connect(addr)?;
         let mode = Connected { addr };
-        Ok(Sender { socket, mode })
+        Ok(Sender {
+            socket,
+            mode,
+            #[cfg(feature = "compression")]
+            compression_threshold,
+        })
     }
 
     /// Sends the given packet on the `Sender`s socket to the given address.
@@ -113,7 +158,7 @@ impl Sender<Unconnected> {
         P: Into<Packet>,
         A: ToSocketAddrs,
     {
-        let bytes = encode(packet.into())?;
+        let bytes = self.encode_packet(packet.into())?;
         let bytes_written = self.socket.send_to(&bytes, addr)?;
         Ok(bytes_written)
     }
@@ -138,8 +183,267 @@ impl Sender<Connected> {
     where
         P: Into<Packet>,
     {
-        let bytes = encode(packet.into())?;
+        let bytes = self.encode_packet(packet.into())?;
         let bytes_written = self.socket.send(&bytes)?;
         Ok(bytes_written)
     }
 }
+
+/// A type used for sending each OSC packet to a set of target addresses over a single socket.
+///
+/// Targets may be added and removed at any time, including from other threads while packets are
+/// being sent. Each packet is encoded once and then sent to every target. A failure to reach one
+/// target does not prevent sending to the others, and is instead recorded within that target's
+/// `TargetStats`.
+pub struct MultiSender {
+    sender: Sender<Unconnected>,
+    targets: Mutex<Vec<(SocketAddr, TargetStats)>>,
+}
+
+/// Statistics tracked for each target of a `MultiSender`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetStats {
+    /// The number of packets successfully sent to the target.
+    pub packets_sent: u64,
+    /// The total number of failed attempts to send a packet to the target.
+    pub failures: u64,
+    /// The number of failed attempts since the last successfully sent packet.
+    pub consecutive_failures: u32,
+    /// The kind of the most recent error that occurred while sending to the target, if any.
+    pub last_error: Option<std::io::ErrorKind>,
+}
+
+impl MultiSender {
+    /// Creates a new `MultiSender` with no targets from the given `Sender`.
+    pub fn new(sender: Sender<Unconnected>) -> Self {
+        let targets = Mutex::new(Vec::new());
+        MultiSender { sender, targets }
+    }
+
+    /// The same as `new` but binds a new `Sender` to the `default_sender_socket_addr_v4`.
+    pub fn bind() -> Result<Self, std::io::Error> {
+        Sender::bind().map(Self::new)
+    }
+
+    /// The inner `Sender` whose socket is used to send packets.
+    pub fn sender(&self) -> &Sender<Unconnected> {
+        &self.sender
+    }
+
+    /// Adds the given target address.
+    ///
+    /// Returns `true` if the target was added or `false` if the target was already present.
+    ///
+    /// Returns an error if the given `addr` fails to resolve, or an `io::Error` of kind
+    /// `InvalidInput` if it resolves to no `SocketAddr`s.
+    pub fn add_target<A>(&self, addr: A) -> Result<bool, CommunicationError>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "could not resolve any `SocketAddr`s",
+            )
+        })?;
+        let mut targets = self.targets.lock()?;
+        if targets.iter().any(|(a, _)| *a == addr) {
+            return Ok(false);
+        }
+        targets.push((addr, TargetStats::default()));
+        Ok(true)
+    }
+
+    /// Removes the given target address.
+    ///
+    /// Returns `true` if the target was present.
+    pub fn remove_target(&self, addr: SocketAddr) -> Result<bool, CommunicationError> {
+        let mut targets = self.targets.lock()?;
+        let len = targets.len();
+        targets.retain(|(a, _)| *a != addr);
+        Ok(targets.len() != len)
+    }
+
+    /// Removes all targets whose number of consecutive failures has reached the given limit.
+    ///
+    /// Returns the addresses of the removed targets.
+    pub fn remove_failing_targets(
+        &self,
+        max_consecutive_failures: u32,
+    ) -> Result<Vec<SocketAddr>, CommunicationError> {
+        let mut targets = self.targets.lock()?;
+        let mut removed = vec![];
+        targets.retain(|(addr, stats)| {
+            let failing = stats.consecutive_failures >= max_consecutive_failures;
+            if failing {
+                removed.push(*addr);
+            }
+            !failing
+        });
+        Ok(removed)
+    }
+
+    /// Removes all targets.
+    pub fn clear_targets(&self) -> Result<(), CommunicationError> {
+        self.targets.lock()?.clear();
+        Ok(())
+    }
+
+    /// The addresses of all targets in the order in which they were added.
+    pub fn targets(&self) -> Result<Vec<SocketAddr>, CommunicationError> {
+        let targets = self.targets.lock()?;
+        Ok(targets.iter().map(|(addr, _)| *addr).collect())
+    }
+
+    /// The statistics tracked for the given target, or `None` if there is no such target.
+    pub fn target_stats(
+        &self,
+        addr: SocketAddr,
+    ) -> Result<Option<TargetStats>, CommunicationError> {
+        let targets = self.targets.lock()?;
+        Ok(targets.iter().find(|(a, _)| *a == addr).map(|(_, s)| *s))
+    }
+
+    /// Sends the given packet to all targets.
+    ///
+    /// The given `packet` can be of any type that can be converted directly into a `Packet`.
+    ///
+    /// On success, returns the number of targets to which the packet was sent. Failures to send
+    /// to individual targets are recorded within their `TargetStats` rather than returned.
+    ///
+    /// This will return a `CommunicationError` if:
+    ///
+    /// - The given packet fails to be encoded to bytes or
+    /// - The inner targets mutex is poisoned.
+    pub fn send<P>(&self, packet: P) -> Result<usize, CommunicationError>
+    where
+        P: Into<Packet>,
+    {
+        let bytes = self.sender.encode_packet(packet.into())?;
+        let mut targets = self.targets.lock()?;
+        let mut sent = 0;
+        for (addr, stats) in targets.iter_mut() {
+            match self.sender.socket.send_to(&bytes, *addr) {
+                Ok(_) => {
+                    stats.packets_sent += 1;
+                    stats.consecutive_failures = 0;
+                    sent += 1;
+                }
+                Err(err) => {
+                    stats.failures += 1;
+                    stats.consecutive_failures = stats.consecutive_failures.saturating_add(1);
+                    stats.last_error = Some(err.kind());
+                }
+            }
+        }
+        Ok(sent)
+    }
+}
+
+impl From<Sender<Unconnected>> for MultiSender {
+    fn from(sender: Sender<Unconnected>) -> Self {
+        Self::new(sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Receiver, Type};
+    use std::net::{Ipv6Addr, SocketAddrV6};
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn packet() -> Packet {
+        crate::msg("/test", vec![Type::Int(7), Type::String("multi".into())]).into()
+    }
+
+    fn multi_sender() -> MultiSender {
+        MultiSender::new(Sender::bind_to("127.0.0.1:0").unwrap())
+    }
+
+    fn receiver() -> Receiver {
+        let receiver = Receiver::bind_to("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(TIMEOUT)).unwrap();
+        receiver
+    }
+
+    // An address that the IPv4 socket of a `MultiSender` is unable to send to.
+    fn unreachable_addr() -> SocketAddr {
+        SocketAddrV6::new(Ipv6Addr::LOCALHOST, 9, 0, 0).into()
+    }
+
+    #[test]
+    fn sends_to_each_target() {
+        let multi = multi_sender();
+        let (a, b) = (receiver(), receiver());
+        assert!(multi.add_target(a.local_addr().unwrap()).unwrap());
+        assert!(multi.add_target(b.local_addr().unwrap()).unwrap());
+        assert_eq!(multi.send(packet()).unwrap(), 2);
+        let sender_addr = multi.sender().local_addr().unwrap();
+        for receiver in &[a, b] {
+            let (received, addr) = receiver.recv().unwrap();
+            assert_eq!(received, packet());
+            assert_eq!(addr, sender_addr);
+        }
+    }
+
+    #[test]
+    fn duplicate_targets_are_ignored() {
+        let multi = multi_sender();
+        let addr = receiver().local_addr().unwrap();
+        assert!(multi.add_target(addr).unwrap());
+        assert!(!multi.add_target(addr).unwrap());
+        assert_eq!(multi.targets().unwrap(), vec![addr]);
+        assert!(multi.remove_target(addr).unwrap());
+        assert!(!multi.remove_target(addr).unwrap());
+        assert!(multi.targets().unwrap().is_empty());
+    }
+
+    #[test]
+    fn failing_target_does_not_block_the_others() {
+        let multi = multi_sender();
+        let receiver = receiver();
+        let good = receiver.local_addr().unwrap();
+        let bad = unreachable_addr();
+        multi.add_target(bad).unwrap();
+        multi.add_target(good).unwrap();
+        assert_eq!(multi.send(packet()).unwrap(), 1);
+        assert_eq!(multi.send(packet()).unwrap(), 1);
+        for _ in 0..2 {
+            assert_eq!(receiver.recv().unwrap().0, packet());
+        }
+
+        let good_stats = multi.target_stats(good).unwrap().unwrap();
+        assert_eq!(good_stats.packets_sent, 2);
+        assert_eq!(good_stats.failures, 0);
+        assert_eq!(good_stats.last_error, None);
+        let bad_stats = multi.target_stats(bad).unwrap().unwrap();
+        assert_eq!(bad_stats.packets_sent, 0);
+        assert_eq!(bad_stats.failures, 2);
+        assert_eq!(bad_stats.consecutive_failures, 2);
+        assert!(bad_stats.last_error.is_some());
+    }
+
+    #[test]
+    fn remove_failing_targets_at_the_limit() {
+        let multi = multi_sender();
+        let good = receiver().local_addr().unwrap();
+        let bad = unreachable_addr();
+        multi.add_target(good).unwrap();
+        multi.add_target(bad).unwrap();
+        multi.send(packet()).unwrap();
+        assert!(multi.remove_failing_targets(2).unwrap().is_empty());
+        multi.send(packet()).unwrap();
+        assert_eq!(multi.remove_failing_targets(2).unwrap(), vec![bad]);
+        assert_eq!(multi.targets().unwrap(), vec![good]);
+        assert_eq!(multi.target_stats(bad).unwrap(), None);
+    }
+
+    #[test]
+    fn send_without_targets_sends_nothing() {
+        let multi = multi_sender();
+        assert_eq!(multi.send(packet()).unwrap(), 0);
+    }
+}
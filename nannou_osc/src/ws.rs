@@ -0,0 +1,457 @@
+//! A bridge for communicating with browser clients via OSC over WebSocket.
+//!
+//! Browsers cannot send or receive UDP packets, so browser based control surfaces typically
+//! transmit OSC over WebSockets instead. The `Bridge` serves WebSocket connections where each
+//! binary WebSocket message contains a single encoded OSC packet.
+//!
+//! Packets received from clients are queued for the application, and packets sent via the bridge
+//! are broadcast to all connected clients. A bridge may also `relay` to a regular UDP OSC peer,
+//! forwarding packets in both directions.
+
+use super::{decode, encode, CommunicationError, Packet};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+
+/// The default port on which the `Bridge` listens for WebSocket connections.
+pub const DEFAULT_PORT: u16 = 8080;
+
+// The interval at which threads check for outgoing packets and for whether the bridge was closed.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The duration within which a connecting client must complete the WebSocket handshake.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves OSC-over-WebSocket connections from browser clients.
+///
+/// All threads spawned by the bridge are closed and joined when it is dropped. Clients that are
+/// mid-handshake may delay the drop by up to the `HANDSHAKE_TIMEOUT`.
+pub struct Bridge {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+    packet_rx: mpsc::Receiver<(Packet, SocketAddr)>,
+    // Held so that `recv` never observes a disconnected channel.
+    _packet_tx: mpsc::Sender<(Packet, SocketAddr)>,
+    relay: Option<Relay>,
+    // The accept thread, which joins the client threads before returning.
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+// State shared between the bridge handle and its threads.
+struct Shared {
+    // Outgoing message queues for each connected client.
+    clients: Mutex<Vec<Client>>,
+    closed: AtomicBool,
+}
+
+struct Client {
+    addr: SocketAddr,
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+// Forwards packets between the websocket clients and a UDP peer.
+struct Relay {
+    socket: UdpSocket,
+    target: SocketAddr,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Bridge {
+    /// Listen for WebSocket connections on the given port of the default IPv4 address.
+    pub fn bind(port: u16) -> Result<Self, io::Error> {
+        Self::bind_to(SocketAddr::new(super::default_ipv4_addr().into(), port))
+    }
+
+    /// Listen for WebSocket connections on the given address.
+    pub fn bind_to<A>(addr: A) -> Result<Self, io::Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            clients: Mutex::new(vec![]),
+            closed: AtomicBool::new(false),
+        });
+        let (packet_tx, packet_rx) = mpsc::channel();
+
+        // Accept new connections on their own thread.
+        let accept_shared = shared.clone();
+        let accept_tx = packet_tx.clone();
+        let accept_thread = std::thread::Builder::new()
+            .name("nannou_osc_ws_accept".into())
+            .spawn(move || accept_loop(listener, accept_shared, accept_tx))?;
+
+        Ok(Bridge {
+            local_addr,
+            shared,
+            packet_rx,
+            _packet_tx: packet_tx,
+            relay: None,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    /// Relay packets between the WebSocket clients and a UDP OSC peer.
+    ///
+    /// Packets received on the given local UDP `port` are broadcast to all clients. Packets
+    /// received from clients are forwarded to the `target` address as they are retrieved via
+    /// `recv`, `try_recv` or `try_iter`, so these must still be called regularly.
+    pub fn relay<A>(mut self, port: u16, target: A) -> Result<Self, io::Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let target = target.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no target address given")
+        })?;
+        let socket = UdpSocket::bind(SocketAddr::new(super::default_ipv4_addr().into(), port))?;
+        socket.set_read_timeout(Some(POLL_INTERVAL * 10))?;
+
+        // Forward packets from the UDP socket to the websocket clients.
+        let udp_socket = socket.try_clone()?;
+        let udp_shared = self.shared.clone();
+        let thread = std::thread::Builder::new()
+            .name("nannou_osc_ws_relay".into())
+            .spawn(move || relay_loop(udp_socket, udp_shared))?;
+
+        let thread = Some(thread);
+        self.relay = Some(Relay {
+            socket,
+            target,
+            thread,
+        });
+        Ok(self)
+    }
+
+    /// The address on which the bridge is listening for WebSocket connections.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The addresses of all currently connected clients.
+    pub fn clients(&self) -> Vec<SocketAddr> {
+        match self.shared.clients.lock() {
+            Ok(clients) => clients.iter().map(|c| c.addr).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Broadcast the given packet to all connected clients.
+    ///
+    /// On success, returns the number of clients to which the packet was queued.
+    pub fn send<P>(&self, packet: P) -> Result<usize, CommunicationError>
+    where
+        P: Into<Packet>,
+    {
+        let bytes = encode(packet.into())?;
+        let count = broadcast(&self.shared, bytes)?;
+        Ok(count)
+    }
+
+    /// Checks for a pending packet from a client and returns `Some` if there is one along with the
+    /// source address.
+    ///
+    /// If the bridge relays to a UDP peer, the packet is forwarded before it is returned.
+    pub fn try_recv(&self) -> Result<Option<(Packet, SocketAddr)>, CommunicationError> {
+        match self.packet_rx.try_recv() {
+            Ok((packet, addr)) => {
+                self.forward(&packet)?;
+                Ok(Some((packet, addr)))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Waits for the next packet from a client and returns it along with the source address.
+    ///
+    /// If the bridge relays to a UDP peer, the packet is forwarded before it is returned.
+    pub fn recv(&self) -> Result<(Packet, SocketAddr), CommunicationError> {
+        // The bridge holds a sender, so this can never disconnect.
+        let (packet, addr) = self.packet_rx.recv().expect("packet channel disconnected");
+        self.forward(&packet)?;
+        Ok((packet, addr))
+    }
+
+    /// An iterator yielding all pending packets from clients along with their source address.
+    ///
+    /// Packets that fail to be relayed to the UDP peer are skipped.
+    pub fn try_iter(&self) -> impl Iterator<Item = (Packet, SocketAddr)> + '_ {
+        std::iter::from_fn(move || loop {
+            match self.try_recv() {
+                Ok(Some(packet)) => return Some(packet),
+                Ok(None) => return None,
+                Err(_) => continue,
+            }
+        })
+    }
+
+    // Forward a packet from a client to the UDP peer if there is one.
+    fn forward(&self, packet: &Packet) -> Result<(), CommunicationError> {
+        if let Some(ref relay) = self.relay {
+            let bytes = encode(packet.clone())?;
+            relay.socket.send_to(&bytes, relay.target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.accept_thread.take() {
+            let _ = thread.join();
+        }
+        if let Some(thread) = self.relay.as_mut().and_then(|relay| relay.thread.take()) {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Queue the given bytes for sending to every client, removing any that have disconnected.
+fn broadcast(shared: &Shared, bytes: Vec<u8>) -> Result<usize, CommunicationError> {
+    let mut clients = shared.clients.lock()?;
+    clients.retain(|client| client.tx.send(bytes.clone()).is_ok());
+    Ok(clients.len())
+}
+
+// Accept new websocket connections until the bridge is closed, then join all client threads.
+fn accept_loop(
+    listener: TcpListener,
+    shared: Arc<Shared>,
+    packet_tx: mpsc::Sender<(Packet, SocketAddr)>,
+) {
+    let mut client_threads: Vec<JoinHandle<()>> = vec![];
+    while !shared.closed.load(Ordering::Relaxed) {
+        client_threads.retain(|thread| !thread.is_finished());
+        let (stream, addr) = match listener.accept() {
+            Ok(conn) => conn,
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL * 10);
+                continue;
+            }
+            Err(_) => continue,
+        };
+        let client_shared = shared.clone();
+        let client_tx = packet_tx.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("nannou_osc_ws_client_{}", addr))
+            .spawn(move || {
+                let _ = client_loop(stream, addr, client_shared, client_tx);
+            });
+        if let Ok(thread) = spawned {
+            client_threads.push(thread);
+        }
+    }
+    for thread in client_threads {
+        let _ = thread.join();
+    }
+}
+
+// Perform the websocket handshake and then service the client until it disconnects.
+fn client_loop(
+    stream: TcpStream,
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    packet_tx: mpsc::Sender<(Packet, SocketAddr)>,
+) -> Result<(), tungstenite::Error> {
+    // Bound the handshake so that a client that never completes it cannot hold the thread.
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut ws: WebSocket<TcpStream> = tungstenite::accept(stream).map_err(|err| match err {
+        tungstenite::HandshakeError::Failure(err) => err,
+        tungstenite::HandshakeError::Interrupted(_) => {
+            tungstenite::Error::Io(io::ErrorKind::WouldBlock.into())
+        }
+    })?;
+    ws.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    // Register the client for broadcasts.
+    let (tx, rx) = mpsc::channel();
+    if let Ok(mut clients) = shared.clients.lock() {
+        clients.push(Client { addr, tx });
+    }
+
+    let res = loop {
+        if shared.closed.load(Ordering::Relaxed) {
+            let _ = ws.close(None);
+            break Ok(());
+        }
+
+        // Deliver incoming packets.
+        match ws.read() {
+            Ok(Message::Binary(bytes)) => {
+                if let Ok(packet) = decode(&bytes) {
+                    if packet_tx.send((packet, addr)).is_err() {
+                        break Ok(());
+                    }
+                }
+            }
+            Ok(Message::Close(_)) => break Ok(()),
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(ref err))
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut => {}
+            Err(err) => break Err(err),
+        }
+
+        // Send outgoing packets.
+        let mut result = Ok(());
+        for bytes in rx.try_iter() {
+            result = ws.send(Message::Binary(bytes));
+            if result.is_err() {
+                break;
+            }
+        }
+        if let Err(err) = result {
+            break Err(err);
+        }
+    };
+
+    // Unregister the client.
+    if let Ok(mut clients) = shared.clients.lock() {
+        clients.retain(|client| client.addr != addr);
+    }
+    res
+}
+
+// Broadcast packets received on the UDP socket to all clients until the bridge is closed.
+fn relay_loop(socket: UdpSocket, shared: Arc<Shared>) {
+    let mut buffer = vec![0; super::recv::DEFAULT_MTU];
+    while !shared.closed.load(Ordering::Relaxed) {
+        let len = match socket.recv_from(&mut buffer) {
+            Ok((len, _addr)) => len,
+            Err(_) => continue,
+        };
+        // Only relay valid OSC packets.
+        if decode(&buffer[..len]).is_ok() {
+            let _ = broadcast(&shared, buffer[..len].to_vec());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Type;
+    use std::time::Instant;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn packet() -> Packet {
+        crate::msg("/test", vec![Type::Int(42), Type::String("ws".into())]).into()
+    }
+
+    fn bridge() -> Bridge {
+        Bridge::bind_to("127.0.0.1:0").unwrap()
+    }
+
+    // Connect a client and wait for the bridge to register it.
+    fn connect(bridge: &Bridge) -> WebSocket<TcpStream> {
+        let n_clients = bridge.clients().len();
+        let stream = TcpStream::connect(bridge.local_addr()).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let url = format!("ws://{}/", bridge.local_addr());
+        let (ws, _response) = tungstenite::client(url.as_str(), stream).unwrap();
+        let start = Instant::now();
+        while bridge.clients().len() == n_clients {
+            assert!(start.elapsed() < TIMEOUT, "client was never registered");
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        ws
+    }
+
+    fn read_packet(ws: &mut WebSocket<TcpStream>) -> Packet {
+        match ws.read().unwrap() {
+            Message::Binary(bytes) => decode(&bytes).unwrap(),
+            msg => panic!("unexpected message: {:?}", msg),
+        }
+    }
+
+    fn recv_timeout(bridge: &Bridge) -> (Packet, SocketAddr) {
+        let start = Instant::now();
+        loop {
+            if let Some(received) = bridge.try_recv().unwrap() {
+                return received;
+            }
+            assert!(start.elapsed() < TIMEOUT, "no packet was received");
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    // A local UDP port that is not currently in use.
+    fn unused_udp_port() -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap().port()
+    }
+
+    #[test]
+    fn send_broadcasts_to_clients() {
+        let bridge = bridge();
+        let mut a = connect(&bridge);
+        let mut b = connect(&bridge);
+        assert_eq!(bridge.send(packet()).unwrap(), 2);
+        assert_eq!(read_packet(&mut a), packet());
+        assert_eq!(read_packet(&mut b), packet());
+    }
+
+    #[test]
+    fn receives_from_clients() {
+        let bridge = bridge();
+        let mut ws = connect(&bridge);
+        let bytes = encode(packet()).unwrap();
+        ws.send(Message::Binary(bytes)).unwrap();
+        let (received, addr) = recv_timeout(&bridge);
+        assert_eq!(received, packet());
+        assert_eq!(addr, ws.get_ref().local_addr().unwrap());
+    }
+
+    #[test]
+    fn ignores_invalid_packets() {
+        let bridge = bridge();
+        let mut ws = connect(&bridge);
+        ws.send(Message::Binary(vec![1, 2, 3])).unwrap();
+        ws.send(Message::Text("/test".into())).unwrap();
+        ws.send(Message::Binary(encode(packet()).unwrap())).unwrap();
+        let (received, _addr) = recv_timeout(&bridge);
+        assert_eq!(received, packet());
+        assert!(bridge.try_recv().unwrap().is_none());
+    }
+
+    #[test]
+    fn relays_to_and_from_udp_peer() {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let port = unused_udp_port();
+        let bridge = bridge().relay(port, peer.local_addr().unwrap()).unwrap();
+        let mut ws = connect(&bridge);
+
+        // Client packets are forwarded to the peer as they are received.
+        ws.send(Message::Binary(encode(packet()).unwrap())).unwrap();
+        recv_timeout(&bridge);
+        let mut buffer = [0; 1024];
+        let (len, _addr) = peer.recv_from(&mut buffer).unwrap();
+        assert_eq!(decode(&buffer[..len]).unwrap(), packet());
+
+        // Peer packets are broadcast to the clients.
+        let bytes = encode(packet()).unwrap();
+        peer.send_to(&bytes, ("127.0.0.1", port)).unwrap();
+        assert_eq!(read_packet(&mut ws), packet());
+    }
+
+    #[test]
+    fn drop_closes_clients() {
+        let bridge = bridge();
+        let mut ws = connect(&bridge);
+        drop(bridge);
+        match ws.read() {
+            Ok(Message::Close(_)) | Err(_) => (),
+            Ok(msg) => panic!("unexpected message: {:?}", msg),
+        }
+    }
+}
@@ -1,25 +1,40 @@
+use std::num::NonZeroU32;
 use wgpu_upstream::{BufferBinding, SamplerBindingType};
 
 use crate as wgpu;
 
 /// A type aimed at simplifying the creation of a bind group layout.
 #[derive(Debug, Default)]
-pub struct LayoutBuilder {
-    bindings: Vec<(wgpu::ShaderStages, wgpu::BindingType)>,
+pub struct LayoutBuilder<'a> {
+    label: Option<&'a str>,
+    bindings: Vec<(wgpu::ShaderStages, wgpu::BindingType, Option<NonZeroU32>)>,
 }
 
 /// Simplified creation of a bind group.
 #[derive(Debug, Default)]
 pub struct Builder<'a> {
+    label: Option<&'a str>,
     resources: Vec<wgpu::BindingResource<'a>>,
 }
 
-impl LayoutBuilder {
+impl<'a> LayoutBuilder<'a> {
+    pub const DEFAULT_LABEL: &'static str = "nannou bind group layout";
+
     /// Begin building the bind group layout.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Debug label of the bind group layout.
+    ///
+    /// This will show up in graphics debuggers for easy identification.
+    ///
+    /// By default, this is `"nannou bind group layout"`.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
     /// Specify a new binding.
     ///
     /// The `binding` position of each binding will be inferred as the index within the order that
@@ -27,7 +42,24 @@ impl LayoutBuilder {
     /// location, you may be better off not using the `BindGroupLayoutBuilder` and instead
     /// constructing the `BindGroupLayout` and `BindGroup` manually.
     pub fn binding(mut self, visibility: wgpu::ShaderStages, ty: wgpu::BindingType) -> Self {
-        self.bindings.push((visibility, ty));
+        self.bindings.push((visibility, ty, None));
+        self
+    }
+
+    /// Specify a new binding array of `count` elements of the given type.
+    ///
+    /// Binding arrays of textures require `wgpu::Features::TEXTURE_BINDING_ARRAY`. See
+    /// `wgpu::TextureArray` for a type that falls back to an atlas when unsupported.
+    ///
+    /// **Panic!**s if `count` is `0`.
+    pub fn binding_array(
+        mut self,
+        visibility: wgpu::ShaderStages,
+        ty: wgpu::BindingType,
+        count: u32,
+    ) -> Self {
+        let count = NonZeroU32::new(count).expect("binding array count must be non-zero");
+        self.bindings.push((visibility, ty, Some(count)));
         self
     }
 
@@ -100,6 +132,24 @@ impl LayoutBuilder {
         self.binding(visibility, ty)
     }
 
+    /// Add a binding array of `count` non-multisampled textures to the layout.
+    ///
+    /// Requires `wgpu::Features::TEXTURE_BINDING_ARRAY`.
+    pub fn texture_array(
+        self,
+        visibility: wgpu::ShaderStages,
+        view_dimension: wgpu::TextureViewDimension,
+        sample_type: wgpu::TextureSampleType,
+        count: u32,
+    ) -> Self {
+        let ty = wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type,
+        };
+        self.binding_array(visibility, ty, count)
+    }
+
     /// Short-hand for adding a texture binding for a full view of the given texture to the layout.
     ///
     /// The `multisampled` and `dimension` parameters are retrieved from the `Texture` itself.
@@ -153,17 +203,17 @@ impl LayoutBuilder {
     /// Build the bind group layout from the specified parameters.
     pub fn build(self, device: &wgpu::Device) -> wgpu::BindGroupLayout {
         let mut entries = Vec::with_capacity(self.bindings.len());
-        for (i, (visibility, ty)) in self.bindings.into_iter().enumerate() {
+        for (i, (visibility, ty, count)) in self.bindings.into_iter().enumerate() {
             let layout_binding = wgpu::BindGroupLayoutEntry {
                 binding: i as u32,
                 visibility,
                 ty,
-                count: None,
+                count,
             };
             entries.push(layout_binding);
         }
         let descriptor = wgpu::BindGroupLayoutDescriptor {
-            label: Some("nannou bind group layout"),
+            label: Some(self.label.unwrap_or(Self::DEFAULT_LABEL)),
             entries: &entries,
         };
         device.create_bind_group_layout(&descriptor)
@@ -171,11 +221,23 @@ impl LayoutBuilder {
 }
 
 impl<'a> Builder<'a> {
+    pub const DEFAULT_LABEL: &'static str = "nannou bind group";
+
     /// Begin building the bind group.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Debug label of the bind group.
+    ///
+    /// This will show up in graphics debuggers for easy identification.
+    ///
+    /// By default, this is `"nannou bind group"`.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
     /// Specify a new binding.
     ///
     /// The `binding` position of each binding will be inferred as the index within the order that
@@ -234,6 +296,14 @@ impl<'a> Builder<'a> {
         self.binding(resource)
     }
 
+    /// Specify an array of texture views to be bound to a binding array.
+    ///
+    /// The length of the slice must match the `count` of the binding array within the layout.
+    pub fn texture_view_array(self, views: &'a [&'a wgpu::TextureViewHandle]) -> Self {
+        let resource = wgpu::BindingResource::TextureViewArray(views);
+        self.binding(resource)
+    }
+
     /// Build the bind group with the specified resources.
     pub fn build(self, device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> wgpu::BindGroup {
         let mut entries = Vec::with_capacity(self.resources.len());
@@ -245,7 +315,7 @@ impl<'a> Builder<'a> {
             entries.push(binding);
         }
         let descriptor = wgpu::BindGroupDescriptor {
-            label: Some("nannou bind group"),
+            label: Some(self.label.unwrap_or(Self::DEFAULT_LABEL)),
             layout,
             entries: &entries,
         };
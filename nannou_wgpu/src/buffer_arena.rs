@@ -0,0 +1,518 @@
+//! Sub-allocation of many small uniform, vertex or storage allocations from large buffers.
+//!
+//! Creating a new buffer for every small piece of per-draw data is slow and, over the lifetime of
+//! a long-running sketch, fragments GPU memory. A `BufferArena` instead hands out aligned ranges
+//! of a few large blocks. All allocations made during a frame are recycled together once the
+//! frame is finished, so the same blocks are reused frame after frame.
+//!
+//! Each `Allocation` refers to its block along with its offset and size, and may be bound via a
+//! dynamic offset so that a single bind group serves every allocation within a block.
+
+use crate as wgpu;
+use std::collections::VecDeque;
+use std::num::NonZeroU64;
+use std::sync::Arc;
+
+/// Sub-allocates aligned ranges of large buffers, recycling them each frame.
+#[derive(Debug)]
+pub struct BufferArena {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    block_size: wgpu::BufferAddress,
+    alignment: wgpu::BufferAddress,
+    frames_in_flight: usize,
+    // All blocks that have been created, in order of creation.
+    blocks: Vec<Arc<wgpu::Buffer>>,
+    // The indices of blocks available for allocation.
+    free: Vec<usize>,
+    // The blocks in use by the current frame, the last of which is allocated from.
+    current: Vec<usize>,
+    // The offset of the next allocation within the last of the `current` blocks.
+    offset: wgpu::BufferAddress,
+    // The blocks used by finished frames that may still be awaiting the GPU.
+    finished: VecDeque<Vec<usize>>,
+    // The total size of all allocations made during the current frame.
+    allocated: wgpu::BufferAddress,
+}
+
+/// A builder for a `BufferArena`.
+#[derive(Debug)]
+pub struct Builder {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    block_size: wgpu::BufferAddress,
+    alignment: Option<wgpu::BufferAddress>,
+    frames_in_flight: usize,
+}
+
+/// A range of a block allocated by a `BufferArena`.
+///
+/// The range remains valid until the `BufferArena::finish_frame` call following its allocation.
+#[derive(Clone, Debug)]
+pub struct Allocation {
+    /// The block from which the range was allocated.
+    pub buffer: Arc<wgpu::Buffer>,
+    /// The offset of the range in bytes, a multiple of the arena's alignment.
+    pub offset: wgpu::BufferAddress,
+    /// The size of the range in bytes.
+    pub size: wgpu::BufferAddress,
+}
+
+impl BufferArena {
+    /// Begin building a `BufferArena` for allocations with the given usage.
+    pub fn builder(usage: wgpu::BufferUsages) -> Builder {
+        Builder::new(usage)
+    }
+
+    /// The usage of the arena's blocks.
+    pub fn usage(&self) -> wgpu::BufferUsages {
+        self.usage
+    }
+
+    /// The size of each block in bytes.
+    ///
+    /// Allocations larger than this are given a block of their own.
+    pub fn block_size(&self) -> wgpu::BufferAddress {
+        self.block_size
+    }
+
+    /// The alignment of the offset of each allocation in bytes.
+    pub fn alignment(&self) -> wgpu::BufferAddress {
+        self.alignment
+    }
+
+    /// The number of blocks that have been created.
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The total size of all allocations made during the current frame in bytes, excluding
+    /// alignment padding.
+    pub fn allocated_bytes(&self) -> wgpu::BufferAddress {
+        self.allocated
+    }
+
+    /// Allocate a range of the given size in bytes, creating a new block if necessary.
+    ///
+    /// The contents of the range are undefined until written.
+    ///
+    /// **Panic!**s if the size is zero.
+    pub fn alloc(&mut self, device: &wgpu::Device, size: wgpu::BufferAddress) -> Allocation {
+        assert!(size > 0, "allocations must not be empty");
+        // Pad the size so that the range may be written via `Queue::write_buffer`.
+        let padded = align_up(size, wgpu::COPY_BUFFER_ALIGNMENT);
+
+        // Allocate from the current block if there is room.
+        let offset = align_up(self.offset, self.alignment);
+        let fits = match self.current.last() {
+            Some(&block) => offset + padded <= self.blocks[block].size(),
+            None => false,
+        };
+        let (block, offset) = match fits {
+            true => (*self.current.last().unwrap(), offset),
+            false => {
+                let block = self.take_block(device, padded);
+                self.current.push(block);
+                (block, 0)
+            }
+        };
+
+        self.offset = offset + padded;
+        self.allocated += size;
+        Allocation {
+            buffer: self.blocks[block].clone(),
+            offset,
+            size,
+        }
+    }
+
+    /// Allocate a range for the given data and write the data to it via the queue.
+    ///
+    /// **Panic!**s if the data is empty.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) -> Allocation {
+        let allocation = self.alloc(device, data.len() as wgpu::BufferAddress);
+        allocation.write(queue, data);
+        allocation
+    }
+
+    /// Allocate a range for the given value and write its bytes to it via the queue.
+    ///
+    /// The type `T` *must* be `#[repr(C)]` with a layout matching its use within the shader.
+    pub fn write_value<T>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        value: &T,
+    ) -> Allocation
+    where
+        T: Copy,
+    {
+        let bytes = unsafe { wgpu::bytes::from(value) };
+        self.write(device, queue, bytes)
+    }
+
+    /// Finish the current frame.
+    ///
+    /// This should be called once the commands using the frame's allocations have been
+    /// submitted. The blocks used by the frame are recycled once `frames_in_flight` further frames
+    /// have been finished.
+    pub fn finish_frame(&mut self) {
+        let current = std::mem::replace(&mut self.current, Vec::new());
+        self.finished.push_back(current);
+        while self.finished.len() > self.frames_in_flight {
+            if let Some(blocks) = self.finished.pop_front() {
+                self.free.extend(blocks);
+            }
+        }
+        self.offset = 0;
+        self.allocated = 0;
+    }
+
+    /// Drop all blocks that are not currently in use, returning their memory to the device.
+    ///
+    /// Useful after a spike in allocations that is unlikely to recur.
+    pub fn shrink(&mut self) {
+        if self.free.is_empty() {
+            return;
+        }
+        let in_use: Vec<bool> = {
+            let mut in_use = vec![false; self.blocks.len()];
+            let used = self.current.iter().chain(self.finished.iter().flatten());
+            used.for_each(|&block| in_use[block] = true);
+            in_use
+        };
+        // Re-index the remaining blocks.
+        let mut indices = vec![0; self.blocks.len()];
+        let mut blocks = Vec::with_capacity(self.blocks.len() - self.free.len());
+        for (ix, block) in self.blocks.drain(..).enumerate() {
+            if in_use[ix] {
+                indices[ix] = blocks.len();
+                blocks.push(block);
+            }
+        }
+        self.blocks = blocks;
+        self.free.clear();
+        self.current.iter_mut().for_each(|ix| *ix = indices[*ix]);
+        for frame in self.finished.iter_mut() {
+            frame.iter_mut().for_each(|ix| *ix = indices[*ix]);
+        }
+    }
+
+    // Take a free block with room for the given size, creating one if there are none.
+    fn take_block(&mut self, device: &wgpu::Device, size: wgpu::BufferAddress) -> usize {
+        let blocks = &self.blocks;
+        if let Some(ix) = self.free.iter().position(|&b| blocks[b].size() >= size) {
+            return self.free.swap_remove(ix);
+        }
+        let desc = wgpu::BufferDescriptor {
+            label: Some(self.label),
+            size: std::cmp::max(self.block_size, size),
+            usage: self.usage,
+            mapped_at_creation: false,
+        };
+        self.blocks.push(Arc::new(device.create_buffer(&desc)));
+        self.blocks.len() - 1
+    }
+}
+
+impl Allocation {
+    /// The offset of the range for use as a dynamic offset when setting a bind group.
+    pub fn dynamic_offset(&self) -> wgpu::DynamicOffset {
+        self.offset as wgpu::DynamicOffset
+    }
+
+    /// The size of the range for use within a bind group entry.
+    pub fn binding_size(&self) -> NonZeroU64 {
+        NonZeroU64::new(self.size).expect("allocations are never empty")
+    }
+
+    /// A binding of the range, e.g. for a bind group entry without a dynamic offset.
+    pub fn binding(&self) -> wgpu::BufferBinding {
+        wgpu::BufferBinding {
+            buffer: &self.buffer,
+            offset: self.offset,
+            size: Some(self.binding_size()),
+        }
+    }
+
+    /// A slice of the range, e.g. for setting a vertex or index buffer.
+    pub fn slice(&self) -> wgpu::BufferSlice {
+        self.buffer.slice(self.offset..self.offset + self.size)
+    }
+
+    /// Write the given data to the start of the range via the queue.
+    ///
+    /// **Panic!**s if the data is larger than the range.
+    pub fn write(&self, queue: &wgpu::Queue, data: &[u8]) {
+        assert!(
+            data.len() as wgpu::BufferAddress <= self.size,
+            "data is larger than the allocation"
+        );
+        // `Queue::write_buffer` requires a multiple of `COPY_BUFFER_ALIGNMENT` bytes.
+        let rem = data.len() % wgpu::COPY_BUFFER_ALIGNMENT as usize;
+        if rem == 0 {
+            queue.write_buffer(&self.buffer, self.offset, data);
+        } else {
+            let mut padded = data.to_vec();
+            padded.resize(data.len() + wgpu::COPY_BUFFER_ALIGNMENT as usize - rem, 0);
+            queue.write_buffer(&self.buffer, self.offset, &padded);
+        }
+    }
+}
+
+impl Builder {
+    /// The default debug label of the arena's blocks.
+    pub const DEFAULT_LABEL: &'static str = "nannou_buffer_arena";
+    /// The default size of each block in bytes.
+    pub const DEFAULT_BLOCK_SIZE: wgpu::BufferAddress = 1 << 20;
+    /// The default number of finished frames whose blocks are kept before recycling.
+    pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 1;
+
+    /// Begin building a `BufferArena` for allocations with the given usage.
+    ///
+    /// `BufferUsages::COPY_DST` is always added so that allocations may be written via the queue.
+    pub fn new(usage: wgpu::BufferUsages) -> Self {
+        Builder {
+            label: Self::DEFAULT_LABEL,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            block_size: Self::DEFAULT_BLOCK_SIZE,
+            alignment: None,
+            frames_in_flight: Self::DEFAULT_FRAMES_IN_FLIGHT,
+        }
+    }
+
+    /// Debug label of the arena's blocks.
+    ///
+    /// By default, this is `"nannou_buffer_arena"`.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// The size of each block in bytes.
+    ///
+    /// By default, this value is `Builder::DEFAULT_BLOCK_SIZE`.
+    pub fn block_size(mut self, size: wgpu::BufferAddress) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// The alignment of the offset of each allocation in bytes.
+    ///
+    /// By default, this is the device's minimum uniform or storage buffer offset alignment for
+    /// arenas with those usages, or `COPY_BUFFER_ALIGNMENT` otherwise.
+    ///
+    /// **Panic!**s if the alignment is not a power of two.
+    pub fn alignment(mut self, alignment: wgpu::BufferAddress) -> Self {
+        assert!(
+            alignment.is_power_of_two(),
+            "alignment must be a power of two"
+        );
+        self.alignment = Some(alignment);
+        self
+    }
+
+    /// The number of finished frames whose blocks are kept before being recycled.
+    ///
+    /// Writes via the queue are ordered before the following submission, so blocks may safely be
+    /// reused as soon as the frame's commands have been submitted. Increase this if allocations
+    /// are read back or otherwise used beyond the frame in which they were made.
+    ///
+    /// By default, this value is `Builder::DEFAULT_FRAMES_IN_FLIGHT`.
+    pub fn frames_in_flight(mut self, frames: usize) -> Self {
+        self.frames_in_flight = frames;
+        self
+    }
+
+    /// Build the arena. No blocks are created until the first allocation.
+    pub fn build(self, device: &wgpu::Device) -> BufferArena {
+        let Builder {
+            label,
+            usage,
+            block_size,
+            alignment,
+            frames_in_flight,
+        } = self;
+        let alignment = alignment.unwrap_or_else(|| default_alignment(device, usage));
+        BufferArena {
+            label,
+            usage,
+            block_size,
+            alignment,
+            frames_in_flight,
+            blocks: vec![],
+            free: vec![],
+            current: vec![],
+            offset: 0,
+            finished: VecDeque::new(),
+            allocated: 0,
+        }
+    }
+}
+
+// The strictest offset alignment required by the given usage.
+fn default_alignment(device: &wgpu::Device, usage: wgpu::BufferUsages) -> wgpu::BufferAddress {
+    let limits = device.limits();
+    let mut alignment = wgpu::COPY_BUFFER_ALIGNMENT;
+    if usage.contains(wgpu::BufferUsages::UNIFORM) {
+        let uniform = limits.min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        alignment = std::cmp::max(alignment, uniform);
+    }
+    if usage.contains(wgpu::BufferUsages::STORAGE) {
+        let storage = limits.min_storage_buffer_offset_alignment as wgpu::BufferAddress;
+        alignment = std::cmp::max(alignment, storage);
+    }
+    alignment
+}
+
+fn align_up(value: wgpu::BufferAddress, alignment: wgpu::BufferAddress) -> wgpu::BufferAddress {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Request a device from any available adapter, or `None` on machines without one.
+    fn device() -> Option<wgpu::Device> {
+        let instance = wgpu::Instance::default();
+        let options = wgpu::RequestAdapterOptions {
+            power_preference: wgpu::DEFAULT_POWER_PREFERENCE,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        };
+        let adapter = futures::executor::block_on(instance.request_adapter(&options))?;
+        let desc = wgpu::default_device_descriptor();
+        let (device, _queue) =
+            futures::executor::block_on(adapter.request_device(&desc, None)).ok()?;
+        Some(device)
+    }
+
+    fn arena(device: &wgpu::Device) -> BufferArena {
+        BufferArena::builder(wgpu::BufferUsages::UNIFORM)
+            .block_size(1024)
+            .alignment(256)
+            .build(device)
+    }
+
+    #[test]
+    fn align_up_rounds_to_multiple() {
+        assert_eq!(align_up(0, 256), 0);
+        assert_eq!(align_up(1, 256), 256);
+        assert_eq!(align_up(256, 256), 256);
+        assert_eq!(align_up(257, 256), 512);
+        assert_eq!(align_up(5, wgpu::COPY_BUFFER_ALIGNMENT), 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "alignment must be a power of two")]
+    fn alignment_must_be_power_of_two() {
+        BufferArena::builder(wgpu::BufferUsages::UNIFORM).alignment(48);
+    }
+
+    #[test]
+    fn usage_includes_copy_dst() {
+        let builder = BufferArena::builder(wgpu::BufferUsages::VERTEX);
+        assert!(builder.usage.contains(wgpu::BufferUsages::COPY_DST));
+    }
+
+    #[test]
+    fn allocations_are_aligned_within_block() {
+        let device = match device() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut arena = arena(&device);
+        assert_eq!(arena.block_count(), 0);
+        let a = arena.alloc(&device, 100);
+        let b = arena.alloc(&device, 4);
+        let c = arena.alloc(&device, 300);
+        assert_eq!((a.offset, b.offset, c.offset), (0, 256, 512));
+        assert!(Arc::ptr_eq(&a.buffer, &b.buffer) && Arc::ptr_eq(&b.buffer, &c.buffer));
+        assert_eq!(arena.block_count(), 1);
+        assert_eq!(arena.allocated_bytes(), 404);
+    }
+
+    #[test]
+    fn full_block_starts_new_block() {
+        let device = match device() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut arena = arena(&device);
+        let a = arena.alloc(&device, 768);
+        let b = arena.alloc(&device, 512);
+        assert!(!Arc::ptr_eq(&a.buffer, &b.buffer));
+        assert_eq!(b.offset, 0);
+        assert_eq!(arena.block_count(), 2);
+    }
+
+    #[test]
+    fn large_allocation_gets_own_block() {
+        let device = match device() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut arena = arena(&device);
+        let a = arena.alloc(&device, 4000);
+        assert_eq!(a.offset, 0);
+        assert_eq!(a.buffer.size(), 4000);
+        assert_eq!(a.size, 4000);
+    }
+
+    #[test]
+    fn finished_frames_recycle_blocks() {
+        let device = match device() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut arena = arena(&device);
+        for _ in 0..4 {
+            arena.alloc(&device, 1024);
+            arena.finish_frame();
+            assert_eq!(arena.allocated_bytes(), 0);
+        }
+        // The single frame in flight holds one block while the other is reused.
+        assert_eq!(arena.block_count(), 2);
+    }
+
+    #[test]
+    fn frames_in_flight_delays_recycling() {
+        let device = match device() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut arena = BufferArena::builder(wgpu::BufferUsages::UNIFORM)
+            .block_size(1024)
+            .frames_in_flight(3)
+            .build(&device);
+        for _ in 0..8 {
+            arena.alloc(&device, 1024);
+            arena.finish_frame();
+        }
+        assert_eq!(arena.block_count(), 4);
+    }
+
+    #[test]
+    fn shrink_drops_free_blocks() {
+        let device = match device() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut arena = arena(&device);
+        for _ in 0..3 {
+            arena.alloc(&device, 1024);
+        }
+        arena.finish_frame();
+        arena.finish_frame();
+        assert_eq!(arena.block_count(), 3);
+        let a = arena.alloc(&device, 1024);
+        arena.shrink();
+        // Only the block in use by the current frame remains.
+        assert_eq!(arena.block_count(), 1);
+        assert!(Arc::ptr_eq(&a.buffer, &arena.blocks[0]));
+        let b = arena.alloc(&device, 4);
+        assert!(!Arc::ptr_eq(&a.buffer, &b.buffer));
+        assert_eq!(arena.block_count(), 2);
+    }
+}
@@ -0,0 +1,788 @@
+//! Arc-ball and fly camera controllers for custom 3D render pipelines.
+//!
+//! Both controllers produce view and projection matrices along with a ready-to-upload
+//! `CameraUniforms` value. A `CameraUniformBuffer` holds the uniforms on the GPU along with a bind
+//! group that may be bound directly within a render pass.
+//!
+//! The controllers are driven by calling their input methods, e.g. `ArcBallCamera::orbit`. When
+//! the `winit` feature is enabled, `handle_window_event` may instead be called with each window
+//! event.
+//!
+//! All matrices are column major and follow the wgpu conventions of a right-handed coordinate
+//! system with clip space depth in the range `0.0..=1.0`.
+
+use crate as wgpu;
+use std::time::Duration;
+
+/// A 4x4 column major matrix.
+pub type Mat4 = [[f32; 4]; 4];
+
+/// A 3D point or direction.
+pub type Vec3 = [f32; 3];
+
+/// Items shared by all camera controllers.
+pub trait Camera {
+    /// The position of the camera in world space.
+    fn eye(&self) -> Vec3;
+
+    /// The matrix transforming world space into view space.
+    fn view(&self) -> Mat4;
+
+    /// The projection used to transform view space into clip space.
+    fn projection(&self) -> &Projection;
+
+    /// The uniforms describing the camera, ready to be written to a `CameraUniformBuffer`.
+    fn uniforms(&self) -> CameraUniforms {
+        let view = self.view();
+        let proj = self.projection().matrix();
+        let view_proj = mat4_mul(&proj, &view);
+        let [x, y, z] = self.eye();
+        CameraUniforms {
+            view,
+            proj,
+            view_proj,
+            eye: [x, y, z, 1.0],
+        }
+    }
+}
+
+/// A perspective projection.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projection {
+    /// The vertical field of view in radians.
+    pub fov_y: f32,
+    /// The ratio of the viewport width to its height.
+    pub aspect: f32,
+    /// The distance to the near clipping plane.
+    pub near: f32,
+    /// The distance to the far clipping plane.
+    pub far: f32,
+}
+
+/// The layout of the camera uniforms as expected by shaders.
+///
+/// This matches the following WGSL struct:
+///
+/// ```wgsl
+/// struct Camera {
+///     view: mat4x4<f32>,
+///     proj: mat4x4<f32>,
+///     view_proj: mat4x4<f32>,
+///     eye: vec4<f32>,
+/// };
+/// ```
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CameraUniforms {
+    pub view: Mat4,
+    pub proj: Mat4,
+    pub view_proj: Mat4,
+    /// The camera position in world space. The `w` component is always `1.0`.
+    pub eye: [f32; 4],
+}
+
+/// A uniform buffer holding `CameraUniforms` along with a bind group for binding it.
+///
+/// The buffer is bound at binding `0` and is visible to both the vertex and fragment stages.
+#[derive(Debug)]
+pub struct CameraUniformBuffer {
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A camera that orbits around a target point, typical for inspecting a model or scene.
+///
+/// When driven by window events, dragging with the left mouse button orbits, dragging with the
+/// right or middle mouse button pans and scrolling zooms.
+#[derive(Clone, Debug)]
+pub struct ArcBallCamera {
+    /// The point around which the camera orbits.
+    pub target: Vec3,
+    /// The distance from the target to the camera.
+    pub distance: f32,
+    /// The rotation around the `y` axis in radians.
+    pub yaw: f32,
+    /// The elevation above the `xz` plane in radians.
+    pub pitch: f32,
+    /// The projection used by the camera.
+    pub projection: Projection,
+    /// The radians rotated per pixel dragged.
+    pub orbit_sensitivity: f32,
+    /// The fraction of the distance travelled per pixel dragged while panning.
+    pub pan_sensitivity: f32,
+    /// The fraction of the distance zoomed per scrolled line.
+    pub zoom_sensitivity: f32,
+    /// The closest the camera may zoom towards the target.
+    pub min_distance: f32,
+    #[cfg(feature = "winit")]
+    input: PointerInput,
+}
+
+/// A first-person camera that flies freely through the scene.
+///
+/// When driven by window events, the `W`, `A`, `S` and `D` keys move the camera, `E` and `Q`
+/// move it up and down, and dragging with the right mouse button looks around.
+#[derive(Clone, Debug)]
+pub struct FlyCamera {
+    /// The position of the camera in world space.
+    pub position: Vec3,
+    /// The rotation around the `y` axis in radians.
+    pub yaw: f32,
+    /// The elevation above the `xz` plane in radians.
+    pub pitch: f32,
+    /// The projection used by the camera.
+    pub projection: Projection,
+    /// The distance travelled per second while moving.
+    pub speed: f32,
+    /// The radians rotated per pixel dragged.
+    pub look_sensitivity: f32,
+    moving: [bool; 6],
+    #[cfg(feature = "winit")]
+    input: PointerInput,
+}
+
+/// The directions in which a `FlyCamera` may move.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FlyDirection {
+    Forward,
+    Back,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// The state of the pointer used to track dragging between cursor events.
+#[cfg(feature = "winit")]
+#[derive(Copy, Clone, Debug, Default)]
+struct PointerInput {
+    cursor: Option<[f64; 2]>,
+    primary_down: bool,
+    secondary_down: bool,
+}
+
+// The furthest the pitch may approach the poles, avoiding a degenerate view matrix.
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.001;
+
+// The world space up direction.
+const UP: Vec3 = [0.0, 1.0, 0.0];
+
+impl Projection {
+    /// The default vertical field of view of 45 degrees.
+    pub const DEFAULT_FOV_Y: f32 = std::f32::consts::FRAC_PI_4;
+    /// The default distance to the near clipping plane.
+    pub const DEFAULT_NEAR: f32 = 0.01;
+    /// The default distance to the far clipping plane.
+    pub const DEFAULT_FAR: f32 = 1_000.0;
+
+    /// A projection with the default field of view and clipping planes and the given aspect ratio.
+    pub fn new(aspect: f32) -> Self {
+        Projection {
+            fov_y: Self::DEFAULT_FOV_Y,
+            aspect,
+            near: Self::DEFAULT_NEAR,
+            far: Self::DEFAULT_FAR,
+        }
+    }
+
+    /// Update the aspect ratio for a viewport of the given size.
+    ///
+    /// Sizes with a zero dimension, e.g. of a minimised window, are ignored.
+    pub fn set_viewport_size(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    /// The projection matrix.
+    pub fn matrix(&self) -> Mat4 {
+        let h = 1.0 / (self.fov_y * 0.5).tan();
+        let w = h / self.aspect;
+        let r = self.far / (self.near - self.far);
+        [
+            [w, 0.0, 0.0, 0.0],
+            [0.0, h, 0.0, 0.0],
+            [0.0, 0.0, r, -1.0],
+            [0.0, 0.0, r * self.near, 0.0],
+        ]
+    }
+}
+
+impl CameraUniformBuffer {
+    /// Debug label of the buffer, bind group layout and bind group.
+    pub const LABEL: &'static str = "nannou_camera_uniforms";
+
+    /// Create the uniform buffer, initialised with the given uniforms.
+    pub fn new(device: &wgpu::Device, uniforms: &CameraUniforms) -> Self {
+        let buffer = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::BufferInitDescriptor {
+                label: Some(Self::LABEL),
+                contents: unsafe { wgpu::bytes::from(uniforms) },
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+        let visibility = wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT;
+        let bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .label(Self::LABEL)
+            .uniform_buffer(visibility, false)
+            .build(device);
+        let bind_group = wgpu::BindGroupBuilder::new()
+            .label(Self::LABEL)
+            .buffer_bytes(&buffer, 0, None)
+            .build(device, &bind_group_layout);
+        CameraUniformBuffer {
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// Write the given uniforms to the buffer.
+    ///
+    /// This is typically called once per frame with `camera.uniforms()`.
+    pub fn write(&self, queue: &wgpu::Queue, uniforms: &CameraUniforms) {
+        let bytes = unsafe { wgpu::bytes::from(uniforms) };
+        queue.write_buffer(&self.buffer, 0, bytes);
+    }
+
+    /// The buffer holding the uniforms.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    /// The layout of the bind group, for use when creating a pipeline layout.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// The bind group binding the buffer.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+impl ArcBallCamera {
+    /// The default distance from the target.
+    pub const DEFAULT_DISTANCE: f32 = 5.0;
+    /// The default radians rotated per pixel dragged.
+    pub const DEFAULT_ORBIT_SENSITIVITY: f32 = 0.005;
+    /// The default fraction of the distance travelled per pixel dragged while panning.
+    pub const DEFAULT_PAN_SENSITIVITY: f32 = 0.001;
+    /// The default fraction of the distance zoomed per scrolled line.
+    pub const DEFAULT_ZOOM_SENSITIVITY: f32 = 0.1;
+    /// The default closest distance to the target.
+    pub const DEFAULT_MIN_DISTANCE: f32 = 0.01;
+
+    /// A camera looking at the origin from `DEFAULT_DISTANCE` along the `z` axis.
+    pub fn new(projection: Projection) -> Self {
+        ArcBallCamera {
+            target: [0.0; 3],
+            distance: Self::DEFAULT_DISTANCE,
+            yaw: 0.0,
+            pitch: 0.0,
+            projection,
+            orbit_sensitivity: Self::DEFAULT_ORBIT_SENSITIVITY,
+            pan_sensitivity: Self::DEFAULT_PAN_SENSITIVITY,
+            zoom_sensitivity: Self::DEFAULT_ZOOM_SENSITIVITY,
+            min_distance: Self::DEFAULT_MIN_DISTANCE,
+            #[cfg(feature = "winit")]
+            input: PointerInput::default(),
+        }
+    }
+
+    /// Orbit around the target by the given number of pixels dragged.
+    pub fn orbit(&mut self, dx: f32, dy: f32) {
+        self.yaw -= dx * self.orbit_sensitivity;
+        self.pitch = (self.pitch + dy * self.orbit_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Move the target within the view plane by the given number of pixels dragged.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = vec3_normalize(vec3_sub(self.target, self.eye()));
+        let right = vec3_normalize(vec3_cross(forward, UP));
+        let up = vec3_cross(right, forward);
+        let scale = self.distance * self.pan_sensitivity;
+        let delta = vec3_add(vec3_scale(right, -dx * scale), vec3_scale(up, dy * scale));
+        self.target = vec3_add(self.target, delta);
+    }
+
+    /// Zoom towards the target by the given number of scrolled lines.
+    ///
+    /// Positive values zoom in.
+    pub fn zoom(&mut self, lines: f32) {
+        let scale = (1.0 - self.zoom_sensitivity).powf(lines);
+        self.distance = (self.distance * scale).max(self.min_distance);
+    }
+
+    /// Update the camera in response to the given window event.
+    ///
+    /// Returns `true` if the event was used by the camera.
+    #[cfg(feature = "winit")]
+    pub fn handle_window_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        use winit::event::WindowEvent;
+        match *event {
+            WindowEvent::Resized(size) => {
+                self.projection.set_viewport_size(size.width, size.height);
+                false
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.zoom(scroll_lines(delta));
+                true
+            }
+            _ => match self.input.handle_window_event(event) {
+                PointerDrag::Primary([dx, dy]) => {
+                    self.orbit(dx, dy);
+                    true
+                }
+                PointerDrag::Secondary([dx, dy]) => {
+                    self.pan(dx, dy);
+                    true
+                }
+                PointerDrag::Button => true,
+                PointerDrag::None => false,
+            },
+        }
+    }
+}
+
+impl FlyCamera {
+    /// The default distance travelled per second.
+    pub const DEFAULT_SPEED: f32 = 2.0;
+    /// The default radians rotated per pixel dragged.
+    pub const DEFAULT_LOOK_SENSITIVITY: f32 = 0.003;
+
+    /// A camera at the given position looking along the negative `z` axis.
+    pub fn new(position: Vec3, projection: Projection) -> Self {
+        FlyCamera {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            projection,
+            speed: Self::DEFAULT_SPEED,
+            look_sensitivity: Self::DEFAULT_LOOK_SENSITIVITY,
+            moving: [false; 6],
+            #[cfg(feature = "winit")]
+            input: PointerInput::default(),
+        }
+    }
+
+    /// The direction in which the camera is facing.
+    pub fn forward(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        [cos_pitch * sin_yaw, sin_pitch, -cos_pitch * cos_yaw]
+    }
+
+    /// Rotate the camera by the given number of pixels dragged.
+    pub fn look(&mut self, dx: f32, dy: f32) {
+        self.yaw += dx * self.look_sensitivity;
+        self.pitch = (self.pitch - dy * self.look_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Start or stop moving in the given direction.
+    ///
+    /// The camera moves while any direction is held each time `update` is called.
+    pub fn set_moving(&mut self, direction: FlyDirection, moving: bool) {
+        self.moving[direction as usize] = moving;
+    }
+
+    /// Whether or not the camera is currently moving in the given direction.
+    pub fn is_moving(&self, direction: FlyDirection) -> bool {
+        self.moving[direction as usize]
+    }
+
+    /// Move the camera in all held directions for the given duration.
+    ///
+    /// This should be called once per frame.
+    pub fn update(&mut self, since_last: Duration) {
+        let forward = self.forward();
+        let right = vec3_normalize(vec3_cross(forward, UP));
+        let dirs = [
+            (FlyDirection::Forward, forward),
+            (FlyDirection::Back, vec3_scale(forward, -1.0)),
+            (FlyDirection::Left, vec3_scale(right, -1.0)),
+            (FlyDirection::Right, right),
+            (FlyDirection::Up, UP),
+            (FlyDirection::Down, vec3_scale(UP, -1.0)),
+        ];
+        let velocity = dirs
+            .iter()
+            .filter(|(dir, _)| self.is_moving(*dir))
+            .fold([0.0; 3], |acc, &(_, v)| vec3_add(acc, v));
+        if velocity != [0.0; 3] {
+            let distance = self.speed * since_last.as_secs_f32();
+            let delta = vec3_scale(vec3_normalize(velocity), distance);
+            self.position = vec3_add(self.position, delta);
+        }
+    }
+
+    /// Update the camera in response to the given window event.
+    ///
+    /// Returns `true` if the event was used by the camera.
+    #[cfg(feature = "winit")]
+    pub fn handle_window_event(&mut self, event: &winit::event::WindowEvent) -> bool {
+        use winit::event::{ElementState, VirtualKeyCode, WindowEvent};
+        match *event {
+            WindowEvent::Resized(size) => {
+                self.projection.set_viewport_size(size.width, size.height);
+                false
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                let direction = match input.virtual_keycode {
+                    Some(VirtualKeyCode::W) => FlyDirection::Forward,
+                    Some(VirtualKeyCode::S) => FlyDirection::Back,
+                    Some(VirtualKeyCode::A) => FlyDirection::Left,
+                    Some(VirtualKeyCode::D) => FlyDirection::Right,
+                    Some(VirtualKeyCode::E) => FlyDirection::Up,
+                    Some(VirtualKeyCode::Q) => FlyDirection::Down,
+                    _ => return false,
+                };
+                self.set_moving(direction, input.state == ElementState::Pressed);
+                true
+            }
+            WindowEvent::Focused(false) => {
+                self.moving = [false; 6];
+                false
+            }
+            _ => match self.input.handle_window_event(event) {
+                PointerDrag::Secondary([dx, dy]) => {
+                    self.look(dx, dy);
+                    true
+                }
+                PointerDrag::Button => true,
+                PointerDrag::Primary(_) | PointerDrag::None => false,
+            },
+        }
+    }
+}
+
+impl Camera for ArcBallCamera {
+    fn eye(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let dir = [cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw];
+        vec3_add(self.target, vec3_scale(dir, self.distance))
+    }
+
+    fn view(&self) -> Mat4 {
+        look_at(self.eye(), self.target, UP)
+    }
+
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+}
+
+impl Camera for FlyCamera {
+    fn eye(&self) -> Vec3 {
+        self.position
+    }
+
+    fn view(&self) -> Mat4 {
+        let target = vec3_add(self.position, self.forward());
+        look_at(self.position, target, UP)
+    }
+
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Default for ArcBallCamera {
+    fn default() -> Self {
+        Self::new(Projection::default())
+    }
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        let position = [0.0, 0.0, ArcBallCamera::DEFAULT_DISTANCE];
+        Self::new(position, Projection::default())
+    }
+}
+
+// The result of passing a window event to the `PointerInput`.
+#[cfg(feature = "winit")]
+enum PointerDrag {
+    // The cursor was dragged by the given number of pixels with the given button held.
+    Primary([f32; 2]),
+    Secondary([f32; 2]),
+    // A relevant mouse button was pressed or released.
+    Button,
+    // The event was not relevant.
+    None,
+}
+
+#[cfg(feature = "winit")]
+impl PointerInput {
+    // Track the mouse buttons and cursor, producing the drag delta for cursor events.
+    fn handle_window_event(&mut self, event: &winit::event::WindowEvent) -> PointerDrag {
+        use winit::event::{ElementState, MouseButton, WindowEvent};
+        match *event {
+            WindowEvent::MouseInput { state, button, .. } => {
+                let down = state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left => self.primary_down = down,
+                    MouseButton::Right | MouseButton::Middle => self.secondary_down = down,
+                    MouseButton::Other(_) => return PointerDrag::None,
+                }
+                PointerDrag::Button
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = [position.x, position.y];
+                let delta = self.cursor.replace(cursor).map(|[x, y]| {
+                    let dx = (cursor[0] - x) as f32;
+                    let dy = (cursor[1] - y) as f32;
+                    [dx, dy]
+                });
+                match delta {
+                    Some(delta) if self.primary_down => PointerDrag::Primary(delta),
+                    Some(delta) if self.secondary_down => PointerDrag::Secondary(delta),
+                    _ => PointerDrag::None,
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                PointerDrag::None
+            }
+            WindowEvent::Focused(false) => {
+                *self = PointerInput::default();
+                PointerDrag::None
+            }
+            _ => PointerDrag::None,
+        }
+    }
+}
+
+// The number of lines scrolled by the given scroll delta.
+#[cfg(feature = "winit")]
+fn scroll_lines(delta: winit::event::MouseScrollDelta) -> f32 {
+    // The number of pixels treated as a single line for touchpads.
+    const PIXELS_PER_LINE: f64 = 20.0;
+    match delta {
+        winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+        winit::event::MouseScrollDelta::PixelDelta(pos) => (pos.y / PIXELS_PER_LINE) as f32,
+    }
+}
+
+// A right-handed view matrix looking from `eye` towards `target`.
+fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+    let f = vec3_normalize(vec3_sub(target, eye));
+    let s = vec3_normalize(vec3_cross(f, up));
+    let u = vec3_cross(s, f);
+    [
+        [s[0], u[0], -f[0], 0.0],
+        [s[1], u[1], -f[1], 0.0],
+        [s[2], u[2], -f[2], 0.0],
+        [-vec3_dot(s, eye), -vec3_dot(u, eye), vec3_dot(f, eye), 1.0],
+    ]
+}
+
+// The product of the two column major matrices `a * b`.
+fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut out = [[0.0; 4]; 4];
+    for (col, out_col) in out.iter_mut().enumerate() {
+        for (row, out) in out_col.iter_mut().enumerate() {
+            *out = (0..4).map(|k| a[k][row] * b[col][k]).sum();
+        }
+    }
+    out
+}
+
+fn vec3_add(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn vec3_sub(a: Vec3, b: Vec3) -> Vec3 {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn vec3_scale(v: Vec3, s: f32) -> Vec3 {
+    [v[0] * s, v[1] * s, v[2] * s]
+}
+
+fn vec3_dot(a: Vec3, b: Vec3) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn vec3_cross(a: Vec3, b: Vec3) -> Vec3 {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn vec3_normalize(v: Vec3) -> Vec3 {
+    let len = vec3_dot(v, v).sqrt();
+    if len > 0.0 {
+        vec3_scale(v, 1.0 / len)
+    } else {
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    const IDENTITY: Mat4 = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    // Transform the given homogeneous point by the column major matrix.
+    fn transform(m: &Mat4, v: [f32; 4]) -> [f32; 4] {
+        let mut out = [0.0; 4];
+        for (row, out) in out.iter_mut().enumerate() {
+            *out = (0..4).map(|k| m[k][row] * v[k]).sum();
+        }
+        out
+    }
+
+    fn assert_approx_eq(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    fn assert_mat_eq(a: &Mat4, b: &Mat4) {
+        for (a, b) in a.iter().zip(b) {
+            assert_approx_eq(a, b);
+        }
+    }
+
+    #[test]
+    fn projection_matrix_matches_known_values() {
+        let projection = Projection {
+            fov_y: FRAC_PI_2,
+            aspect: 2.0,
+            near: 1.0,
+            far: 11.0,
+        };
+        let expected = [
+            [0.5, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, -1.1, -1.0],
+            [0.0, 0.0, -1.1, 0.0],
+        ];
+        assert_mat_eq(&projection.matrix(), &expected);
+    }
+
+    #[test]
+    fn projection_maps_clip_planes_to_zero_and_one() {
+        let projection = Projection::new(1.5);
+        let m = projection.matrix();
+        for &(z, depth) in &[(projection.near, 0.0), (projection.far, 1.0)] {
+            let [_, _, clip_z, clip_w] = transform(&m, [0.0, 0.0, -z, 1.0]);
+            assert!((clip_z / clip_w - depth).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn viewport_size_sets_aspect_unless_empty() {
+        let mut projection = Projection::default();
+        projection.set_viewport_size(1920, 1080);
+        assert_eq!(projection.aspect, 1920.0 / 1080.0);
+        projection.set_viewport_size(0, 1080);
+        assert_eq!(projection.aspect, 1920.0 / 1080.0);
+    }
+
+    #[test]
+    fn look_at_down_negative_z_is_a_translation() {
+        let view = look_at([0.0, 0.0, 5.0], [0.0; 3], UP);
+        let mut expected = IDENTITY;
+        expected[3] = [0.0, 0.0, -5.0, 1.0];
+        assert_mat_eq(&view, &expected);
+    }
+
+    #[test]
+    fn look_at_along_positive_x_rotates_the_axes() {
+        let view = look_at([0.0; 3], [1.0, 0.0, 0.0], UP);
+        let expected = [
+            [0.0, 0.0, -1.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        assert_mat_eq(&view, &expected);
+    }
+
+    #[test]
+    fn look_at_places_the_target_in_front_of_the_eye() {
+        let (eye, target) = ([3.0, 4.0, 5.0], [1.0, -2.0, 0.0]);
+        let view = look_at(eye, target, UP);
+        let [x, y, z] = eye;
+        assert_approx_eq(&transform(&view, [x, y, z, 1.0]), &[0.0, 0.0, 0.0, 1.0]);
+        let distance = vec3_dot(vec3_sub(target, eye), vec3_sub(target, eye)).sqrt();
+        let [x, y, z] = target;
+        let expected = [0.0, 0.0, -distance, 1.0];
+        assert_approx_eq(&transform(&view, [x, y, z, 1.0]), &expected);
+    }
+
+    #[test]
+    fn mat4_mul_applies_the_right_matrix_first() {
+        let mut translate = IDENTITY;
+        translate[3] = [1.0, 2.0, 3.0, 1.0];
+        let mut scale = IDENTITY;
+        for i in 0..3 {
+            scale[i][i] = 2.0;
+        }
+        assert_eq!(mat4_mul(&IDENTITY, &translate), translate);
+        assert_eq!(mat4_mul(&translate, &IDENTITY), translate);
+        let mut expected = scale;
+        expected[3] = [1.0, 2.0, 3.0, 1.0];
+        assert_eq!(mat4_mul(&translate, &scale), expected);
+        expected[3] = [2.0, 4.0, 6.0, 1.0];
+        assert_eq!(mat4_mul(&scale, &translate), expected);
+    }
+
+    #[test]
+    fn uniforms_combine_the_projection_and_view() {
+        let camera = ArcBallCamera::default();
+        let uniforms = camera.uniforms();
+        assert_eq!(
+            uniforms.eye,
+            [0.0, 0.0, ArcBallCamera::DEFAULT_DISTANCE, 1.0]
+        );
+        let point = [0.5, -0.25, 1.0, 1.0];
+        let expected = transform(&uniforms.proj, transform(&uniforms.view, point));
+        assert_approx_eq(&transform(&uniforms.view_proj, point), &expected);
+    }
+
+    #[test]
+    fn arc_ball_orbits_around_the_target() {
+        let mut camera = ArcBallCamera::default();
+        camera.target = [1.0, 0.0, 0.0];
+        camera.yaw = FRAC_PI_2;
+        assert_approx_eq(&camera.eye(), &[6.0, 0.0, 0.0]);
+        camera.orbit(0.0, 1.0e6);
+        assert_eq!(camera.pitch, MAX_PITCH);
+        camera.zoom(1.0e3);
+        assert_eq!(camera.distance, camera.min_distance);
+    }
+
+    #[test]
+    fn fly_camera_moves_in_held_directions() {
+        let mut camera = FlyCamera::new([0.0; 3], Projection::default());
+        assert_approx_eq(&camera.forward(), &[0.0, 0.0, -1.0]);
+        camera.set_moving(FlyDirection::Forward, true);
+        camera.update(Duration::from_millis(500));
+        assert_approx_eq(&camera.position, &[0.0, 0.0, -1.0]);
+        camera.set_moving(FlyDirection::Back, true);
+        camera.update(Duration::from_secs(1));
+        assert_approx_eq(&camera.position, &[0.0, 0.0, -1.0]);
+    }
+}
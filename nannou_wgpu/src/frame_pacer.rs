@@ -0,0 +1,487 @@
+//! Frame pacing and latency statistics for diagnosing stutter.
+//!
+//! A `FramePacer` records a set of timings for each frame:
+//!
+//! - **Encode**: the CPU time from `begin_frame` until command encoding is complete.
+//! - **Submit**: the CPU time spent within `Queue::submit`.
+//! - **Present interval**: the time between consecutive calls to `presented`.
+//! - **GPU**: the time between the timestamps written by `begin_gpu` and `end_gpu`. This requires
+//!   the `Features::TIMESTAMP_QUERY` feature to be enabled on the device and is otherwise `None`.
+//!
+//! The general flow for each frame is:
+//!
+//! 1. `begin_frame`, then `begin_gpu` on the encoder.
+//! 2. Encode commands, then `end_gpu` on the encoder.
+//! 3. Submit the encoder via `FramePacer::submit`, present, then call `presented`.
+//! 4. `poll` to progress the read back of GPU timestamps.
+//!
+//! The most recent timings are retained and summarised as percentiles via `summary` and
+//! `report`. Comparing them helps to determine whether stutter is caused by the CPU, the GPU or
+//! presentation, e.g. a steady encode and GPU time with a spiky present interval points towards
+//! the compositor or display.
+//!
+//! Like the queries within the `query` module, GPU timestamps are read back asynchronously and
+//! frames resolved while a previous read back is still in progress have no GPU timing.
+
+use crate as wgpu;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Records per-frame CPU, GPU and presentation timings.
+#[derive(Debug)]
+pub struct FramePacer {
+    capacity: usize,
+    gpu: Option<GpuTimer>,
+    // The instant at which the current frame began.
+    frame_start: Option<Instant>,
+    // The timings recorded for the current frame so far.
+    current: FrameTimings,
+    last_present: Option<Instant>,
+    frames: VecDeque<FrameTimings>,
+    gpu_times: VecDeque<Duration>,
+}
+
+/// The CPU and presentation timings recorded for a single frame.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameTimings {
+    /// The time from `begin_frame` until command encoding completed.
+    pub encode: Option<Duration>,
+    /// The time spent submitting command buffers to the queue.
+    pub submit: Option<Duration>,
+    /// The time since the previous frame was presented.
+    pub present_interval: Option<Duration>,
+}
+
+/// The timings that may be summarised by a `FramePacer`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameMetric {
+    Encode,
+    Submit,
+    PresentInterval,
+    Gpu,
+}
+
+/// A summary of the distribution of a set of timings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Percentiles {
+    /// The number of timings summarised.
+    pub samples: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// A summary of each metric recorded by a `FramePacer`.
+///
+/// Metrics for which no timings have been recorded are `None`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FramePacerReport {
+    pub encode: Option<Percentiles>,
+    pub submit: Option<Percentiles>,
+    pub present_interval: Option<Percentiles>,
+    pub gpu: Option<Percentiles>,
+}
+
+// Timestamp queries written at the start and end of each frame's GPU work.
+#[derive(Debug)]
+struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    read_buffer: wgpu::Buffer,
+    // The number of nanoseconds per timestamp tick.
+    period: f32,
+    // Whether or not the start timestamp has been written for the current frame.
+    began: bool,
+    readback: Readback,
+}
+
+// The state of the asynchronous read back of resolved timestamps.
+#[derive(Debug)]
+enum Readback {
+    // The read buffer is free to be copied into.
+    Idle,
+    // A copy of the timestamps has been encoded but not yet mapped.
+    Resolved,
+    // The read buffer is being mapped.
+    Mapping(Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>),
+}
+
+// The number of timestamps written per frame.
+const TIMESTAMP_COUNT: u32 = 2;
+const TIMESTAMPS_SIZE: wgpu::BufferAddress =
+    TIMESTAMP_COUNT as wgpu::BufferAddress * std::mem::size_of::<u64>() as wgpu::BufferAddress;
+
+impl FramePacer {
+    /// The number of frames of timings retained by `FramePacer::new`.
+    pub const DEFAULT_CAPACITY: usize = 240;
+
+    /// Create a frame pacer retaining the timings of the `DEFAULT_CAPACITY` most recent frames.
+    ///
+    /// GPU timings are recorded if the device has the `Features::TIMESTAMP_QUERY` feature enabled.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::with_capacity(device, queue, Self::DEFAULT_CAPACITY)
+    }
+
+    /// Create a frame pacer retaining the timings of the `capacity` most recent frames.
+    ///
+    /// **Panic!**s if `capacity` is `0`.
+    pub fn with_capacity(device: &wgpu::Device, queue: &wgpu::Queue, capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity must be greater than zero");
+        let gpu = if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            Some(GpuTimer::new(device, queue))
+        } else {
+            None
+        };
+        FramePacer {
+            capacity,
+            gpu,
+            frame_start: None,
+            current: FrameTimings::default(),
+            last_present: None,
+            frames: VecDeque::with_capacity(capacity),
+            gpu_times: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// The number of frames of timings retained.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether or not GPU timings are recorded, i.e. whether timestamp queries are supported.
+    pub fn has_gpu_timing(&self) -> bool {
+        self.gpu.is_some()
+    }
+
+    /// Mark the beginning of the frame's CPU work.
+    pub fn begin_frame(&mut self) {
+        self.frame_start = Some(Instant::now());
+        self.current.encode = None;
+        self.current.submit = None;
+    }
+
+    /// Write a timestamp marking the start of the frame's GPU work.
+    ///
+    /// Does nothing if GPU timing is not supported.
+    pub fn begin_gpu(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(gpu) = self.gpu.as_mut() {
+            encoder.write_timestamp(&gpu.query_set, 0);
+            gpu.began = true;
+        }
+    }
+
+    /// Write a timestamp marking the end of the frame's GPU work and resolve both timestamps.
+    ///
+    /// Does nothing if GPU timing is not supported or `begin_gpu` was not called this frame.
+    pub fn end_gpu(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Some(gpu) = self.gpu.as_mut() {
+            if std::mem::replace(&mut gpu.began, false) {
+                encoder.write_timestamp(&gpu.query_set, 1);
+                gpu.resolve(encoder);
+            }
+        }
+    }
+
+    /// Mark the end of the frame's command encoding.
+    ///
+    /// This is called automatically by `submit` if it has not been called since `begin_frame`.
+    pub fn end_encode(&mut self) {
+        if let Some(start) = self.frame_start {
+            self.current.encode = Some(start.elapsed());
+        }
+    }
+
+    /// Submit the given command buffers to the queue, recording the time taken.
+    pub fn submit<I>(&mut self, queue: &wgpu::Queue, command_buffers: I) -> wgpu::SubmissionIndex
+    where
+        I: IntoIterator<Item = wgpu::CommandBuffer>,
+    {
+        if self.current.encode.is_none() {
+            self.end_encode();
+        }
+        let start = Instant::now();
+        let index = queue.submit(command_buffers);
+        let elapsed = start.elapsed();
+        let submit = self.current.submit.get_or_insert(Duration::ZERO);
+        *submit += elapsed;
+        index
+    }
+
+    /// Mark the frame as presented, completing its timings.
+    pub fn presented(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_present.replace(now) {
+            self.current.present_interval = Some(now - last);
+        }
+        let timings = std::mem::take(&mut self.current);
+        push_bounded(&mut self.frames, timings, self.capacity);
+        self.frame_start = None;
+    }
+
+    /// Progress the read back of GPU timestamps.
+    ///
+    /// This must only be called after the encoder passed to `end_gpu` has been submitted. Returns
+    /// `true` if a new GPU timing became available.
+    pub fn poll(&mut self, device: &wgpu::Device) -> bool {
+        let gpu = match self.gpu.as_mut() {
+            None => return false,
+            Some(gpu) => gpu,
+        };
+        match gpu.poll(device) {
+            None => false,
+            Some(time) => {
+                push_bounded(&mut self.gpu_times, time, self.capacity);
+                true
+            }
+        }
+    }
+
+    /// Clear all recorded timings, e.g. after a pause or a change of resolution.
+    pub fn reset(&mut self) {
+        self.frame_start = None;
+        self.current = FrameTimings::default();
+        self.last_present = None;
+        self.frames.clear();
+        self.gpu_times.clear();
+    }
+
+    /// The timings of the most recently presented frames, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &FrameTimings> {
+        self.frames.iter()
+    }
+
+    /// The most recently read back GPU timings, oldest first.
+    pub fn gpu_times(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.gpu_times.iter().cloned()
+    }
+
+    /// All retained timings for the given metric, oldest first.
+    pub fn samples(&self, metric: FrameMetric) -> Vec<Duration> {
+        let frames = self.frames.iter();
+        match metric {
+            FrameMetric::Encode => frames.filter_map(|f| f.encode).collect(),
+            FrameMetric::Submit => frames.filter_map(|f| f.submit).collect(),
+            FrameMetric::PresentInterval => frames.filter_map(|f| f.present_interval).collect(),
+            FrameMetric::Gpu => self.gpu_times().collect(),
+        }
+    }
+
+    /// The given percentile of the retained timings for the given metric.
+    ///
+    /// `p` is clamped to the range `0.0..=100.0`. Returns `None` if no timings are available.
+    pub fn percentile(&self, metric: FrameMetric, p: f32) -> Option<Duration> {
+        let mut samples = self.samples(metric);
+        samples.sort();
+        percentile(&samples, p)
+    }
+
+    /// Summarise the retained timings for the given metric.
+    ///
+    /// Returns `None` if no timings are available.
+    pub fn summary(&self, metric: FrameMetric) -> Option<Percentiles> {
+        Percentiles::from_samples(self.samples(metric))
+    }
+
+    /// Summarise the retained timings of all metrics.
+    pub fn report(&self) -> FramePacerReport {
+        FramePacerReport {
+            encode: self.summary(FrameMetric::Encode),
+            submit: self.summary(FrameMetric::Submit),
+            present_interval: self.summary(FrameMetric::PresentInterval),
+            gpu: self.summary(FrameMetric::Gpu),
+        }
+    }
+}
+
+impl Percentiles {
+    /// Summarise the given timings.
+    ///
+    /// Returns `None` if `samples` is empty.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let total: Duration = samples.iter().sum();
+        Some(Percentiles {
+            samples: samples.len(),
+            min: samples[0],
+            mean: total / samples.len() as u32,
+            p50: percentile(&samples, 50.0)?,
+            p90: percentile(&samples, 90.0)?,
+            p99: percentile(&samples, 99.0)?,
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+impl FrameMetric {
+    /// All metrics in the order they occur within a frame.
+    pub const ALL: [FrameMetric; 4] = [
+        FrameMetric::Encode,
+        FrameMetric::Submit,
+        FrameMetric::Gpu,
+        FrameMetric::PresentInterval,
+    ];
+
+    /// A short human-readable name for the metric.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            FrameMetric::Encode => "encode",
+            FrameMetric::Submit => "submit",
+            FrameMetric::PresentInterval => "present interval",
+            FrameMetric::Gpu => "gpu",
+        }
+    }
+}
+
+impl FramePacerReport {
+    /// The summary of the given metric.
+    pub fn get(&self, metric: FrameMetric) -> Option<&Percentiles> {
+        match metric {
+            FrameMetric::Encode => self.encode.as_ref(),
+            FrameMetric::Submit => self.submit.as_ref(),
+            FrameMetric::PresentInterval => self.present_interval.as_ref(),
+            FrameMetric::Gpu => self.gpu.as_ref(),
+        }
+    }
+}
+
+impl fmt::Display for Percentiles {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1_000.0;
+        write!(
+            f,
+            "min {:.2}ms, mean {:.2}ms, p50 {:.2}ms, p90 {:.2}ms, p99 {:.2}ms, \
+             max {:.2}ms ({} samples)",
+            ms(self.min),
+            ms(self.mean),
+            ms(self.p50),
+            ms(self.p90),
+            ms(self.p99),
+            ms(self.max),
+            self.samples,
+        )
+    }
+}
+
+impl fmt::Display for FramePacerReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, metric) in FrameMetric::ALL.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match self.get(*metric) {
+                None => write!(f, "{}: n/a", metric.name())?,
+                Some(summary) => write!(f, "{}: {}", metric.name(), summary)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GpuTimer {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("nannou_frame_pacer_timestamps"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("nannou_frame_pacer_resolve_buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("nannou_frame_pacer_read_buffer"),
+            size: TIMESTAMPS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        GpuTimer {
+            query_set,
+            resolve_buffer,
+            read_buffer,
+            period: queue.get_timestamp_period(),
+            began: false,
+            readback: Readback::Idle,
+        }
+    }
+
+    // Encode the resolve of this frame's timestamps unless a read back is in progress.
+    fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        if let Readback::Idle = self.readback {
+            encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(
+                &self.resolve_buffer,
+                0,
+                &self.read_buffer,
+                0,
+                TIMESTAMPS_SIZE,
+            );
+            self.readback = Readback::Resolved;
+        }
+    }
+
+    // Progress the read back, returning the elapsed GPU time if it completed.
+    fn poll(&mut self, device: &wgpu::Device) -> Option<Duration> {
+        if let Readback::Resolved = self.readback {
+            let status = Arc::new(Mutex::new(None));
+            let status2 = status.clone();
+            self.read_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |res| {
+                    *status2.lock().expect("failed to lock map status") = Some(res);
+                });
+            self.readback = Readback::Mapping(status);
+        }
+        device.poll(wgpu::Maintain::Poll);
+        let res = match self.readback {
+            Readback::Mapping(ref status) => {
+                status.lock().expect("failed to lock map status").take()?
+            }
+            _ => return None,
+        };
+        self.readback = Readback::Idle;
+        res.ok()?;
+        let ticks = {
+            let view = self.read_buffer.slice(..).get_mapped_range();
+            let mut stamps = view.chunks_exact(8).map(|b| {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(b);
+                u64::from_ne_bytes(bytes)
+            });
+            let start = stamps.next().unwrap_or(0);
+            let end = stamps.next().unwrap_or(0);
+            end.saturating_sub(start)
+        };
+        self.read_buffer.unmap();
+        let nanos = ticks as f64 * self.period as f64;
+        Some(Duration::from_nanos(nanos as u64))
+    }
+}
+
+// The nearest-rank percentile of the given sorted samples.
+fn percentile(sorted: &[Duration], p: f32) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let p = p.max(0.0).min(100.0) as f64;
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    let index = rank.max(1) - 1;
+    Some(sorted[index.min(sorted.len() - 1)])
+}
+
+// Push the value onto the back of the queue, removing the oldest values beyond `capacity`.
+fn push_bounded<T>(queue: &mut VecDeque<T>, value: T, capacity: usize) {
+    while queue.len() >= capacity {
+        queue.pop_front();
+    }
+    queue.push_back(value);
+}
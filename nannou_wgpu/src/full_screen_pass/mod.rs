@@ -0,0 +1,335 @@
+//! A render pass that runs a fragment shader over the entirety of a texture.
+//!
+//! Post-processing effects such as blur, bloom or colour grading typically sample one or more
+//! input textures and write every pixel of an output texture. A `FullScreenPass` provides the
+//! vertex shader, pipeline and bind group layout for such effects so that only the fragment
+//! shader needs to be written.
+
+use crate as wgpu;
+use std::num::NonZeroU64;
+
+/// A render pipeline that covers the whole destination texture with a single triangle, shading
+/// every pixel with a user fragment shader.
+///
+/// The fragment shader receives the normalised texture coordinates of the pixel at location `0`,
+/// with `(0, 0)` at the top-left corner. All resources are bound within bind group `0` in the
+/// following order:
+///
+/// - Binding `0..N`: the `N` input textures.
+/// - Binding `N`: a sampler for the input textures.
+/// - Binding `N + 1`: the uniform buffer, if a uniform type was specified.
+///
+/// For example, a pass with a single input texture and uniforms might declare:
+///
+/// ```wgsl
+/// @group(0) @binding(0) var tex: texture_2d<f32>;
+/// @group(0) @binding(1) var tex_sampler: sampler;
+/// @group(0) @binding(2) var<uniform> uniforms: Uniforms;
+///
+/// @fragment
+/// fn main(@location(0) tex_coords: vec2<f32>) -> @location(0) vec4<f32> {
+///     return textureSample(tex, tex_sampler, tex_coords);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FullScreenPass {
+    label: String,
+    _vs_mod: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    render_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    uniform_buffer: Option<wgpu::Buffer>,
+    texture_count: usize,
+}
+
+/// A builder for a `FullScreenPass`.
+#[derive(Debug)]
+pub struct Builder<'a> {
+    label: &'a str,
+    fs_mod: &'a wgpu::ShaderModule,
+    fs_entry_point: &'a str,
+    textures: Vec<wgpu::TextureSampleType>,
+    uniforms_size: Option<NonZeroU64>,
+    sampler_desc: wgpu::SamplerDescriptor<'static>,
+    color_format: wgpu::TextureFormat,
+    blend: Option<wgpu::BlendState>,
+    sample_count: u32,
+}
+
+impl FullScreenPass {
+    /// Begin building a full screen pass with the given fragment shader.
+    pub fn builder(fs_mod: &wgpu::ShaderModule) -> Builder {
+        Builder::new(fs_mod)
+    }
+
+    /// The layout of the bind group expected by the pass.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// The number of input textures expected by the pass.
+    pub fn texture_count(&self) -> usize {
+        self.texture_count
+    }
+
+    /// The buffer holding the pass's uniforms, if a uniform type was specified.
+    pub fn uniform_buffer(&self) -> Option<&wgpu::Buffer> {
+        self.uniform_buffer.as_ref()
+    }
+
+    /// Create a bind group for the given input textures along with the pass's sampler and
+    /// uniform buffer.
+    ///
+    /// The bind group only needs to be recreated when the input textures change.
+    ///
+    /// **Panic!**s if the number of textures does not match the number specified when building.
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        textures: &[&wgpu::TextureViewHandle],
+    ) -> wgpu::BindGroup {
+        assert_eq!(
+            textures.len(),
+            self.texture_count,
+            "expected {} input textures for the full screen pass",
+            self.texture_count,
+        );
+        let mut builder = wgpu::BindGroupBuilder::new();
+        for texture in textures {
+            builder = builder.texture_view(texture);
+        }
+        builder = builder.sampler(&self.sampler);
+        if let Some(ref buffer) = self.uniform_buffer {
+            builder = builder.buffer_bytes(buffer, 0, None);
+        }
+        builder.build(device, &self.bind_group_layout)
+    }
+
+    /// Write the given uniforms to the pass's uniform buffer.
+    ///
+    /// The type `U` *must* be the type specified via `Builder::uniforms` and be `#[repr(C)]`
+    /// with a layout matching the shader's uniform struct.
+    ///
+    /// **Panic!**s if no uniform type was specified when building.
+    pub fn write_uniforms<U>(&self, queue: &wgpu::Queue, uniforms: &U)
+    where
+        U: Copy,
+    {
+        let buffer = self
+            .uniform_buffer
+            .as_ref()
+            .expect("no uniforms were specified for the full screen pass");
+        let bytes = unsafe { wgpu::bytes::from(uniforms) };
+        assert_eq!(bytes.len() as wgpu::BufferAddress, buffer.size());
+        queue.write_buffer(buffer, 0, bytes);
+    }
+
+    /// Encode a render pass that shades every pixel of the `dst_texture` using the given bind
+    /// group.
+    ///
+    /// The existing contents of the destination are overwritten unless a blend state was
+    /// specified.
+    pub fn encode_render_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        dst_texture: &wgpu::TextureViewHandle,
+    ) {
+        let mut render_pass = wgpu::RenderPassBuilder::new()
+            .label(&self.label)
+            .color_attachment(dst_texture, |color| color)
+            .begin(encoder);
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Short-hand for creating a bind group for the given textures and encoding a render pass
+    /// with it.
+    ///
+    /// Prefer `bind_group` and `encode_render_pass` when the inputs do not change each frame.
+    pub fn encode(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        textures: &[&wgpu::TextureViewHandle],
+        dst_texture: &wgpu::TextureViewHandle,
+    ) {
+        let bind_group = self.bind_group(device, textures);
+        self.encode_render_pass(encoder, &bind_group, dst_texture);
+    }
+}
+
+impl<'a> Builder<'a> {
+    /// The default entry point of the fragment shader.
+    pub const DEFAULT_FRAGMENT_ENTRY_POINT: &'static str = "main";
+    /// The default debug label of the pass's pipeline and render pass.
+    pub const DEFAULT_LABEL: &'static str = "nannou_full_screen_pass";
+    /// The default format of the destination texture.
+    pub const DEFAULT_COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// Begin building a full screen pass with the given fragment shader.
+    pub fn new(fs_mod: &'a wgpu::ShaderModule) -> Self {
+        Builder {
+            label: Self::DEFAULT_LABEL,
+            fs_mod,
+            fs_entry_point: Self::DEFAULT_FRAGMENT_ENTRY_POINT,
+            textures: vec![],
+            uniforms_size: None,
+            sampler_desc: wgpu::SamplerBuilder::new().into_descriptor(),
+            color_format: Self::DEFAULT_COLOR_FORMAT,
+            blend: None,
+            sample_count: 1,
+        }
+    }
+
+    /// Debug label of the pass's pipeline and render pass.
+    ///
+    /// This will show up in graphics debuggers for easy identification.
+    ///
+    /// By default, this is `"nannou_full_screen_pass"`.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// The name of the fragment shader's entry point.
+    ///
+    /// By default, this is `"main"`.
+    pub fn fragment_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.fs_entry_point = entry_point;
+        self
+    }
+
+    /// Add an input texture slot with the given sample type.
+    ///
+    /// Textures are bound in the order in which they are added.
+    pub fn texture(mut self, sample_type: wgpu::TextureSampleType) -> Self {
+        self.textures.push(sample_type);
+        self
+    }
+
+    /// Add `count` filterable float input texture slots.
+    pub fn textures(mut self, count: usize) -> Self {
+        let sample_type = wgpu::TextureSampleType::Float { filterable: true };
+        self.textures
+            .extend(std::iter::repeat(sample_type).take(count));
+        self
+    }
+
+    /// Specify the type of the uniforms passed to the fragment shader.
+    ///
+    /// Type `U` *must* be `#[repr(C)]` and match the layout of the shader's uniform struct.
+    pub fn uniforms<U>(mut self) -> Self
+    where
+        U: Copy,
+    {
+        let size = std::mem::size_of::<U>() as u64;
+        self.uniforms_size = Some(NonZeroU64::new(size).expect("uniform type must not be empty"));
+        self
+    }
+
+    /// The sampler used to sample the input textures.
+    ///
+    /// By default, this is the `SamplerBuilder` default.
+    pub fn sampler(mut self, desc: wgpu::SamplerDescriptor<'static>) -> Self {
+        self.sampler_desc = desc;
+        self
+    }
+
+    /// The format of the destination texture.
+    ///
+    /// By default, this is `Builder::DEFAULT_COLOR_FORMAT`.
+    pub fn color_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.color_format = format;
+        self
+    }
+
+    /// Blend the output with the existing contents of the destination texture.
+    ///
+    /// By default, the output replaces the contents of the destination.
+    pub fn blend(mut self, blend: wgpu::BlendState) -> Self {
+        self.blend = Some(blend);
+        self
+    }
+
+    /// The sample count of the destination texture.
+    ///
+    /// By default, this is `1`.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Build the full screen pass.
+    pub fn build(self, device: &wgpu::Device) -> FullScreenPass {
+        let Builder {
+            label,
+            fs_mod,
+            fs_entry_point,
+            textures,
+            uniforms_size,
+            sampler_desc,
+            color_format,
+            blend,
+            sample_count,
+        } = self;
+
+        let vs_desc = wgpu::include_wgsl!("shaders/vs.wgsl");
+        let vs_mod = device.create_shader_module(vs_desc);
+
+        let sampler_filtering = wgpu::sampler_filtering(&sampler_desc);
+        let sampler = device.create_sampler(&sampler_desc);
+
+        // Create the bind group layout.
+        let stages = wgpu::ShaderStages::FRAGMENT;
+        let mut layout_builder = wgpu::BindGroupLayoutBuilder::new();
+        for &sample_type in &textures {
+            let dimension = wgpu::TextureViewDimension::D2;
+            layout_builder = layout_builder.texture(stages, false, dimension, sample_type);
+        }
+        layout_builder = layout_builder.sampler(stages, sampler_filtering);
+        if uniforms_size.is_some() {
+            layout_builder = layout_builder.uniform_buffer(stages, false);
+        }
+        let bind_group_layout = layout_builder.build(device);
+
+        // Create the uniform buffer.
+        let uniform_buffer = uniforms_size.map(|size| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("nannou_full_screen_pass_uniforms"),
+                size: size.get(),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        });
+
+        // Create the render pipeline.
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let blend = blend.unwrap_or(wgpu::BlendState::REPLACE);
+        let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vs_mod)
+            .label(label)
+            .fragment_shader(fs_mod)
+            .fragment_entry_point(fs_entry_point)
+            .color_format(color_format)
+            .color_blend(blend.color)
+            .alpha_blend(blend.alpha)
+            .primitive_topology(wgpu::PrimitiveTopology::TriangleList)
+            .sample_count(sample_count)
+            .build(device);
+
+        FullScreenPass {
+            label: label.to_string(),
+            _vs_mod: vs_mod,
+            bind_group_layout,
+            render_pipeline,
+            sampler,
+            uniform_buffer,
+            texture_count: textures.len(),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @builtin(position) out_pos: vec4<f32>,
+};
+
+// Produces a single triangle covering the entire viewport from the vertex index alone, without
+// the need for a vertex buffer.
+@vertex
+fn main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    let tex_coords: vec2<f32> = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let out_pos: vec4<f32> = vec4<f32>(
+        tex_coords.x * 2.0 - 1.0,
+        1.0 - tex_coords.y * 2.0,
+        0.0,
+        1.0
+    );
+    return VertexOutput(tex_coords, out_pos);
+}
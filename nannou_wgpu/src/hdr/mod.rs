@@ -0,0 +1,385 @@
+//! A high dynamic range render target with tonemapping.
+//!
+//! Additive blending, bloom and emissive colours quickly produce values above `1.0`. When drawn
+//! directly to an 8-bit target these clip to white and all detail in the highlights is lost. The
+//! `Hdr` helper instead renders into an `Rgba16Float` target and applies exposure and a
+//! selectable `Tonemapper` curve when resolving to the destination, e.g. a window's surface.
+//!
+//! A `Tonemap` pass may also be used on its own to tonemap any existing float texture.
+
+use crate as wgpu;
+
+/// The curve used to map high dynamic range colours onto the displayable `0.0..=1.0` range.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Tonemapper {
+    /// No curve is applied and colours are clamped to the displayable range.
+    Clamp,
+    /// The Reinhard operator `c / (1 + c)`, applied per channel.
+    ///
+    /// Preserves the most detail in the highlights at the cost of some contrast.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    ///
+    /// Contrasty with slightly desaturated highlights.
+    Aces,
+    /// John Hable's filmic curve, as popularised by Uncharted 2.
+    ///
+    /// Softer than `Aces` with a gentle toe in the shadows.
+    Filmic,
+}
+
+/// A render pass that applies exposure and a tonemapping curve to a float texture while writing
+/// it to a destination texture.
+#[derive(Debug)]
+pub struct Tonemap {
+    _fs_mod: wgpu::ShaderModule,
+    pass: wgpu::FullScreenPass,
+    tonemapper: Tonemapper,
+    exposure: f32,
+}
+
+/// A high dynamic range render target that is tonemapped when resolved to a destination texture.
+///
+/// Draw to the `texture_view`, resolving to the `resolve_target` if one is returned, then call
+/// `encode` to tonemap the result to the destination.
+#[derive(Debug)]
+pub struct Hdr {
+    msaa_texture: Option<(wgpu::Texture, wgpu::TextureView)>,
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+    tonemap: Tonemap,
+    bind_group: wgpu::BindGroup,
+    label: &'static str,
+}
+
+/// A builder for an `Hdr` render target.
+#[derive(Debug)]
+pub struct Builder {
+    label: &'static str,
+    sample_count: u32,
+    dst_format: wgpu::TextureFormat,
+    tonemapper: Tonemapper,
+    exposure: f32,
+}
+
+// The uniforms of the tonemapping fragment shader.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Uniforms {
+    exposure_scale: f32,
+    tonemapper: u32,
+    _pad: [u32; 2],
+}
+
+impl Tonemapper {
+    // The index of the tonemapper within the fragment shader.
+    fn shader_index(&self) -> u32 {
+        match *self {
+            Tonemapper::Clamp => 0,
+            Tonemapper::Reinhard => 1,
+            Tonemapper::Aces => 2,
+            Tonemapper::Filmic => 3,
+        }
+    }
+}
+
+impl Default for Tonemapper {
+    fn default() -> Self {
+        Tonemapper::Aces
+    }
+}
+
+impl Tonemap {
+    /// Create a tonemapping pass that writes to textures of the given format.
+    pub fn new(
+        device: &wgpu::Device,
+        dst_format: wgpu::TextureFormat,
+        tonemapper: Tonemapper,
+        exposure: f32,
+    ) -> Self {
+        let fs_desc = wgpu::include_wgsl!("shaders/fs.wgsl");
+        let fs_mod = device.create_shader_module(fs_desc);
+        let pass = wgpu::FullScreenPass::builder(&fs_mod)
+            .label("nannou_tonemap")
+            .textures(1)
+            .uniforms::<Uniforms>()
+            .color_format(dst_format)
+            .build(device);
+        Tonemap {
+            _fs_mod: fs_mod,
+            pass,
+            tonemapper,
+            exposure,
+        }
+    }
+
+    /// The current tonemapping curve.
+    pub fn tonemapper(&self) -> Tonemapper {
+        self.tonemapper
+    }
+
+    /// Specify the tonemapping curve used by following passes.
+    pub fn set_tonemapper(&mut self, tonemapper: Tonemapper) {
+        self.tonemapper = tonemapper;
+    }
+
+    /// The current exposure in stops.
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Specify the exposure in stops used by following passes.
+    ///
+    /// Colours are scaled by `2^exposure` before the tonemapping curve is applied, so `1.0`
+    /// doubles the brightness and `-1.0` halves it.
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    /// Create a bind group for tonemapping the given source texture.
+    ///
+    /// The bind group only needs to be recreated when the source texture changes.
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        src_texture: &wgpu::TextureViewHandle,
+    ) -> wgpu::BindGroup {
+        self.pass.bind_group(device, &[src_texture])
+    }
+
+    /// Encode a render pass that tonemaps the source texture of the given bind group to the
+    /// `dst_texture`.
+    ///
+    /// The current tonemapper and exposure are written to the pass's uniform buffer via the given
+    /// queue. As queue writes take effect before the next submission, all tonemap passes encoded
+    /// by this `Tonemap` within a single submission use the most recent settings.
+    pub fn encode_render_pass(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        bind_group: &wgpu::BindGroup,
+        dst_texture: &wgpu::TextureViewHandle,
+    ) {
+        let uniforms = Uniforms {
+            exposure_scale: self.exposure.exp2(),
+            tonemapper: self.tonemapper.shader_index(),
+            _pad: [0; 2],
+        };
+        self.pass.write_uniforms(queue, &uniforms);
+        self.pass
+            .encode_render_pass(encoder, bind_group, dst_texture);
+    }
+}
+
+impl Hdr {
+    /// The format of the high dynamic range render target.
+    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    /// Begin building an `Hdr` render target.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// The high dynamic range texture from which the tonemap pass reads.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// The view to which graphics should be drawn.
+    ///
+    /// This is a view of the multisampled texture if the target was built with a sample count
+    /// greater than `1`.
+    pub fn texture_view(&self) -> &wgpu::TextureView {
+        match self.msaa_texture {
+            Some((_, ref view)) => view,
+            None => &self.texture_view,
+        }
+    }
+
+    /// The view to which the multisampled texture should be resolved, if multisampling is enabled.
+    ///
+    /// The `encode` method resolves the multisampled texture automatically, so this is only
+    /// required by render passes that resolve as they go.
+    pub fn resolve_target(&self) -> Option<&wgpu::TextureView> {
+        self.msaa_texture.as_ref().map(|_| &self.texture_view)
+    }
+
+    /// The size of the render target in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        self.texture.size()
+    }
+
+    /// The number of samples of the texture returned by `texture_view`.
+    pub fn sample_count(&self) -> u32 {
+        self.msaa_texture
+            .as_ref()
+            .map_or(1, |(texture, _)| texture.sample_count())
+    }
+
+    /// The tonemapping pass, e.g. for reading the current exposure.
+    pub fn tonemap(&self) -> &Tonemap {
+        &self.tonemap
+    }
+
+    /// Mutable access to the tonemapping pass, e.g. for adjusting the exposure.
+    pub fn tonemap_mut(&mut self) -> &mut Tonemap {
+        &mut self.tonemap
+    }
+
+    /// Recreate the render target's textures with the given size.
+    ///
+    /// The contents of the render target are lost. Has no effect if the size is unchanged.
+    pub fn resize(&mut self, device: &wgpu::Device, size: [u32; 2]) {
+        if self.size() == size {
+            return;
+        }
+        let sample_count = self.sample_count();
+        let (msaa_texture, texture, texture_view) =
+            create_textures(device, self.label, size, sample_count);
+        self.bind_group = self.tonemap.bind_group(device, &texture_view);
+        self.msaa_texture = msaa_texture;
+        self.texture = texture;
+        self.texture_view = texture_view;
+    }
+
+    /// Resolve the render target if it is multisampled, then tonemap it to the `dst_texture`.
+    ///
+    /// The destination must have the format specified via `Builder::dst_format`.
+    pub fn encode(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        dst_texture: &wgpu::TextureViewHandle,
+    ) {
+        if let Some((_, ref msaa_texture_view)) = self.msaa_texture {
+            wgpu::resolve_texture(msaa_texture_view, &self.texture_view, encoder);
+        }
+        self.tonemap
+            .encode_render_pass(queue, encoder, &self.bind_group, dst_texture);
+    }
+}
+
+impl Builder {
+    /// The default debug label of the render target's textures.
+    pub const DEFAULT_LABEL: &'static str = "nannou_hdr";
+    /// The default format of the destination texture.
+    pub const DEFAULT_DST_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+    /// The default exposure in stops.
+    pub const DEFAULT_EXPOSURE: f32 = 0.0;
+
+    /// Begin building an `Hdr` render target.
+    pub fn new() -> Self {
+        Builder {
+            label: Self::DEFAULT_LABEL,
+            sample_count: 1,
+            dst_format: Self::DEFAULT_DST_FORMAT,
+            tonemapper: Tonemapper::default(),
+            exposure: Self::DEFAULT_EXPOSURE,
+        }
+    }
+
+    /// Debug label of the render target's textures.
+    ///
+    /// By default, this is `"nannou_hdr"`.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// The number of samples of the texture to which graphics are drawn.
+    ///
+    /// By default, this is `1`.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// The format of the texture to which the render target is tonemapped.
+    ///
+    /// By default, this is `Builder::DEFAULT_DST_FORMAT`.
+    pub fn dst_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.dst_format = format;
+        self
+    }
+
+    /// The curve used to tonemap the render target.
+    ///
+    /// By default, this is `Tonemapper::Aces`.
+    pub fn tonemapper(mut self, tonemapper: Tonemapper) -> Self {
+        self.tonemapper = tonemapper;
+        self
+    }
+
+    /// The exposure in stops applied before tonemapping.
+    ///
+    /// By default, this is `Builder::DEFAULT_EXPOSURE`.
+    pub fn exposure(mut self, exposure: f32) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Build the render target with the given size in pixels.
+    pub fn build(self, device: &wgpu::Device, size: [u32; 2]) -> Hdr {
+        let Builder {
+            label,
+            sample_count,
+            dst_format,
+            tonemapper,
+            exposure,
+        } = self;
+        let (msaa_texture, texture, texture_view) =
+            create_textures(device, label, size, sample_count);
+        let tonemap = Tonemap::new(device, dst_format, tonemapper, exposure);
+        let bind_group = tonemap.bind_group(device, &texture_view);
+        Hdr {
+            msaa_texture,
+            texture,
+            texture_view,
+            tonemap,
+            bind_group,
+            label,
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Create the optional multisampled texture along with the resolved texture read by the tonemap
+// pass.
+fn create_textures(
+    device: &wgpu::Device,
+    label: &'static str,
+    size: [u32; 2],
+    sample_count: u32,
+) -> (
+    Option<(wgpu::Texture, wgpu::TextureView)>,
+    wgpu::Texture,
+    wgpu::TextureView,
+) {
+    let msaa_texture = match sample_count {
+        0 | 1 => None,
+        _ => {
+            let texture = wgpu::TextureBuilder::new()
+                .label(label)
+                .size(size)
+                .sample_count(sample_count)
+                .format(Hdr::TEXTURE_FORMAT)
+                .usage(wgpu::TextureUsages::RENDER_ATTACHMENT)
+                .build(device);
+            let view = texture.view().build();
+            Some((texture, view))
+        }
+    };
+    let texture = wgpu::TextureBuilder::new()
+        .label(label)
+        .size(size)
+        .format(Hdr::TEXTURE_FORMAT)
+        .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        .build(device);
+    let texture_view = texture.view().build();
+    (msaa_texture, texture, texture_view)
+}
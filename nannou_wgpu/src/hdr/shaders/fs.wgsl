@@ -0,0 +1,64 @@
+struct Uniforms {
+    // Linear scale applied before tonemapping, i.e. `2^exposure`.
+    exposure_scale: f32,
+    tonemapper: u32,
+    _pad: vec2<u32>,
+};
+
+@group(0) @binding(0)
+var tex: texture_2d<f32>;
+@group(0) @binding(1)
+var tex_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> uniforms: Uniforms;
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (x * (a * x + b)) / (x * (c * x + d) + e);
+}
+
+// John Hable's filmic curve, as used in Uncharted 2.
+fn hable(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+fn filmic(x: vec3<f32>) -> vec3<f32> {
+    let exposure_bias = 2.0;
+    let white_point = vec3<f32>(11.2);
+    return hable(x * exposure_bias) / hable(white_point);
+}
+
+@fragment
+fn main(
+    @location(0) tex_coords: vec2<f32>,
+) -> @location(0) vec4<f32> {
+    let color: vec4<f32> = textureSample(tex, tex_sampler, tex_coords);
+    let rgb = max(color.rgb * uniforms.exposure_scale, vec3<f32>(0.0));
+    var mapped: vec3<f32>;
+    switch uniforms.tonemapper {
+        case 1u: {
+            mapped = rgb / (rgb + vec3<f32>(1.0));
+        }
+        case 2u: {
+            mapped = aces(rgb);
+        }
+        case 3u: {
+            mapped = filmic(rgb);
+        }
+        default: {
+            mapped = rgb;
+        }
+    }
+    return vec4<f32>(clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0)), clamp(color.a, 0.0, 1.0));
+}
@@ -14,7 +14,11 @@
 //! process of downloading textures from the GPU and easily save them as image files. As an
 //! example, this is particularly useful for recording the contents of a window or sketch.
 //!
-//! Note that when using `nannou_wgpu` via `nannou::wgpu`, both features are enabled by default.
+//! The `winit` feature allows the `ArcBallCamera` and `FlyCamera` controllers to be driven
+//! directly by winit window events.
+//!
+//! Note that when using `nannou_wgpu` via `nannou::wgpu`, all of these features are enabled by
+//! default.
 //!
 //! Useful links:
 //!
@@ -26,11 +30,22 @@
 
 mod bind_group_builder;
 pub mod blend;
+mod buffer_arena;
+mod camera;
 mod device_map;
+mod frame_pacer;
+mod full_screen_pass;
+mod hdr;
+mod msaa;
+mod pipeline_cache;
+mod query;
+pub mod render_graph;
 mod render_pass;
 mod render_pipeline_builder;
 mod sampler_builder;
+mod sprite_batch;
 mod texture;
+mod yuv;
 
 // Re-export all of `wgpu` along with its documentation.
 //
@@ -39,15 +54,40 @@ mod texture;
 pub use self::bind_group_builder::{
     Builder as BindGroupBuilder, LayoutBuilder as BindGroupLayoutBuilder,
 };
+pub use self::buffer_arena::{
+    Allocation as BufferAllocation, BufferArena, Builder as BufferArenaBuilder,
+};
+pub use self::camera::{
+    ArcBallCamera, Camera, CameraUniformBuffer, CameraUniforms, FlyCamera, FlyDirection,
+    Mat4 as CameraMat4, Projection as CameraProjection, Vec3 as CameraVec3,
+};
 pub use self::device_map::{
     ActiveAdapter, AdapterMap, AdapterMapKey, DeviceMap, DeviceMapKey, DeviceQueuePair,
 };
+pub use self::frame_pacer::{
+    FrameMetric, FramePacer, FramePacerReport, FrameTimings, Percentiles as FramePercentiles,
+};
+pub use self::full_screen_pass::{Builder as FullScreenPassBuilder, FullScreenPass};
+pub use self::hdr::{Builder as HdrBuilder, Hdr, Tonemap, Tonemapper};
+pub use self::msaa::{
+    clamp_sample_count, supported_sample_counts, SampleCountFallback, SAMPLE_COUNTS,
+};
+pub use self::pipeline_cache::PipelineCacheManager;
+pub use self::query::{
+    OcclusionQueries, OcclusionScope, PipelineStatistics, PipelineStatisticsQueries, QueryIndex,
+};
 pub use self::render_pass::{
     Builder as RenderPassBuilder,
     ColorAttachmentDescriptorBuilder as RenderPassColorAttachmentDescriptorBuilder,
 };
 pub use self::render_pipeline_builder::RenderPipelineBuilder;
 pub use self::sampler_builder::SamplerBuilder;
+pub use self::sprite_batch::{
+    Builder as SpriteBatcherBuilder, PageId as SpritePageId, Sprite, SpriteBatcher, SpriteRegion,
+};
+pub use self::texture::array::{
+    AtlasRect, Builder as TextureArrayBuilder, TextureArray, TextureArrayMode,
+};
 #[cfg(feature = "capturer")]
 pub use self::texture::capturer::{
     AwaitWorkerTimeout as TextureCapturerAwaitWorkerTimeout, Capturer as TextureCapturer,
@@ -60,11 +100,15 @@ pub use self::texture::image::{
 };
 pub use self::texture::reshaper::Reshaper as TextureReshaper;
 pub use self::texture::row_padded_buffer::RowPaddedBuffer;
+pub use self::texture::transfer::{
+    copy_texture as copy_texture_between_devices, Transfer as TextureTransfer,
+};
 pub use self::texture::{
     descriptor_eq as texture_descriptor_eq, extent_3d_eq,
     format_size_bytes as texture_format_size_bytes, Builder as TextureBuilder, Texture, TextureId,
     TextureView, TextureViewId, ToTextureView,
 };
+pub use self::yuv::{Builder as YuvTextureBuilder, YuvColorSpace, YuvFormat, YuvRange, YuvTexture};
 #[doc(inline)]
 pub use wgpu_upstream::{
     include_wgsl,
@@ -136,6 +180,20 @@ pub fn clear_texture(
         .begin(encoder);
 }
 
+/// Encode the commands recorded by `f` within a debug group with the given label.
+///
+/// All passes and commands encoded within `f` will be grouped under the label in graphics
+/// debuggers such as RenderDoc or Xcode's GPU frame capture. Debug groups may be nested.
+pub fn debug_group<F, T>(encoder: &mut CommandEncoder, label: &str, f: F) -> T
+where
+    F: FnOnce(&mut CommandEncoder) -> T,
+{
+    encoder.push_debug_group(label);
+    let output = f(encoder);
+    encoder.pop_debug_group();
+    output
+}
+
 /// The default device descriptor used to instantiate a logical device when creating windows.
 pub fn default_device_descriptor() -> DeviceDescriptor<'static> {
     let features = Features::default();
@@ -0,0 +1,161 @@
+//! Helpers for clamping requested MSAA sample counts to those supported by the hardware.
+//!
+//! Creating a multisampled texture or render pipeline with a sample count that is unsupported for
+//! the given format results in a validation panic. The functions here query the supported sample
+//! counts so that a requested count can fall back to the nearest supported count instead.
+
+use crate as wgpu;
+
+/// Describes a requested sample count that was unsupported and the count used in its place.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SampleCountFallback {
+    /// The format for which the sample count was requested.
+    pub format: wgpu::TextureFormat,
+    /// The sample count that was requested.
+    pub requested: u32,
+    /// The greatest supported sample count that does not exceed the requested count.
+    pub supported: u32,
+}
+
+/// The sample counts that may be checked for support, in ascending order.
+pub const SAMPLE_COUNTS: [u32; 5] = [1, 2, 4, 8, 16];
+
+/// The sample counts supported by the given device for textures and render targets of the given
+/// format, in ascending order.
+///
+/// Unless the device was created with `Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`, only
+/// the sample counts guaranteed by the WebGPU specification are reported, regardless of what the
+/// adapter supports.
+///
+/// The result always contains at least `1`.
+pub fn supported_sample_counts(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> Vec<u32> {
+    let features = format_features(adapter, device, format);
+    sample_counts_from_flags(features.flags)
+}
+
+/// Clamp the requested sample count to the greatest count that is supported for the given format
+/// and that does not exceed `requested`.
+pub fn clamp_sample_count(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    requested: u32,
+) -> u32 {
+    let supported = supported_sample_counts(adapter, device, format);
+    clamp_to_supported(&supported, requested)
+}
+
+// Clamp the requested count for all given formats, calling `warn` if a fallback was necessary.
+pub(crate) fn clamp_sample_count_for_formats<I, F>(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    formats: I,
+    requested: u32,
+    warn: F,
+) -> u32
+where
+    I: IntoIterator<Item = wgpu::TextureFormat>,
+    F: FnOnce(SampleCountFallback),
+{
+    let mut count = requested;
+    let mut fallback = None;
+    for format in formats {
+        let supported = clamp_sample_count(adapter, device, format, count);
+        if supported < count {
+            count = supported;
+            fallback = Some(format);
+        }
+    }
+    if let Some(format) = fallback {
+        warn(SampleCountFallback {
+            format,
+            requested,
+            supported: count,
+        });
+    }
+    count
+}
+
+// The sample counts supported by the given format feature flags, in ascending order.
+fn sample_counts_from_flags(flags: wgpu::TextureFormatFeatureFlags) -> Vec<u32> {
+    SAMPLE_COUNTS
+        .iter()
+        .cloned()
+        .filter(|&count| count == 1 || flags.sample_count_supported(count))
+        .collect()
+}
+
+// The greatest of the ascending `supported` counts that does not exceed `requested`, or `1`.
+fn clamp_to_supported(supported: &[u32], requested: u32) -> u32 {
+    supported
+        .iter()
+        .cloned()
+        .filter(|&count| count <= requested)
+        .last()
+        .unwrap_or(1)
+}
+
+// The format features available to the given device.
+fn format_features(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> wgpu::TextureFormatFeatures {
+    let features = device.features();
+    if features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+        adapter.get_texture_format_features(format)
+    } else {
+        format.guaranteed_format_features(features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::TextureFormatFeatureFlags as Flags;
+
+    #[test]
+    fn no_multisampling_supports_only_one() {
+        assert_eq!(sample_counts_from_flags(Flags::empty()), [1]);
+    }
+
+    #[test]
+    fn counts_follow_flags_in_ascending_order() {
+        let flags = Flags::MULTISAMPLE_X4 | Flags::MULTISAMPLE_X2;
+        assert_eq!(sample_counts_from_flags(flags), [1, 2, 4]);
+        let flags = Flags::MULTISAMPLE_X4 | Flags::MULTISAMPLE_X8 | Flags::MULTISAMPLE_X16;
+        assert_eq!(sample_counts_from_flags(flags), [1, 4, 8, 16]);
+    }
+
+    #[test]
+    fn unrelated_flags_are_ignored() {
+        let flags = Flags::FILTERABLE | Flags::MULTISAMPLE_RESOLVE | Flags::MULTISAMPLE_X4;
+        assert_eq!(sample_counts_from_flags(flags), [1, 4]);
+    }
+
+    #[test]
+    fn clamp_keeps_supported_counts() {
+        let supported = [1, 2, 4, 8];
+        for &count in &supported {
+            assert_eq!(clamp_to_supported(&supported, count), count);
+        }
+    }
+
+    #[test]
+    fn clamp_falls_back_to_greatest_lower_count() {
+        let supported = [1, 4];
+        assert_eq!(clamp_to_supported(&supported, 2), 1);
+        assert_eq!(clamp_to_supported(&supported, 8), 4);
+        assert_eq!(clamp_to_supported(&supported, 16), 4);
+    }
+
+    #[test]
+    fn clamp_never_returns_zero() {
+        assert_eq!(clamp_to_supported(&[1, 4], 0), 1);
+        assert_eq!(clamp_to_supported(&[], 4), 1);
+    }
+}
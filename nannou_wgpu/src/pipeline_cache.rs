@@ -0,0 +1,290 @@
+//! Items aimed at compiling render and compute pipelines without stalling the main thread.
+//!
+//! Creating pipelines for shader-heavy applications can take a long time, causing long stalls on
+//! the first frame. The `PipelineCacheManager` allows for compiling pipelines on a bounded pool of
+//! background threads, polling for their readiness so that the application may present a loading
+//! state in the meantime.
+//!
+//! On targets without thread support (i.e. `wasm32`), or if the manager's `max_threads` is `0`,
+//! pipelines are compiled immediately on the calling thread.
+//!
+//! **Note:** wgpu does not yet expose the driver's pipeline cache, so compiled pipelines cannot
+//! currently be serialized to disk between runs.
+
+use crate as wgpu;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+/// Manages a set of named render and compute pipelines that are compiled in the background.
+///
+/// Pipelines are compiled on up to `max_threads` worker threads, which are spawned as pipelines
+/// are submitted and live for as long as the manager. Pipelines submitted while all workers are
+/// busy wait for the next free worker.
+pub struct PipelineCacheManager {
+    device: Arc<wgpu::DeviceQueuePair>,
+    render_pipelines: HashMap<String, Entry<wgpu::RenderPipeline>>,
+    compute_pipelines: HashMap<String, Entry<wgpu::ComputePipeline>>,
+    pool: WorkerPool,
+}
+
+// The state of a single pipeline.
+enum Entry<T> {
+    Pending(mpsc::Receiver<T>),
+    Ready(T),
+    // Compilation panicked on the worker thread.
+    Failed,
+}
+
+// A compilation job sent to the worker threads.
+type Job = Box<dyn FnOnce() + Send>;
+
+// A bounded pool of threads on which pipelines are compiled.
+struct WorkerPool {
+    max_threads: usize,
+    threads: usize,
+    jobs_tx: mpsc::Sender<Job>,
+    shared: Arc<PoolShared>,
+}
+
+// State shared between the pool and its worker threads.
+struct PoolShared {
+    // Workers take turns waiting on the receiver for the next job.
+    jobs_rx: Mutex<mpsc::Receiver<Job>>,
+    // Set when the manager is dropped so that queued jobs are discarded.
+    closed: AtomicBool,
+}
+
+impl PipelineCacheManager {
+    /// The maximum number of worker threads used by `PipelineCacheManager::new`.
+    pub const DEFAULT_MAX_THREADS: usize = 4;
+
+    /// Create a new, empty manager for pipelines created on the given device.
+    pub fn new(device: Arc<wgpu::DeviceQueuePair>) -> Self {
+        PipelineCacheManager {
+            device,
+            render_pipelines: Default::default(),
+            compute_pipelines: Default::default(),
+            pool: WorkerPool::new(Self::DEFAULT_MAX_THREADS),
+        }
+    }
+
+    /// The maximum number of threads on which pipelines are compiled concurrently.
+    ///
+    /// If `0`, pipelines are compiled immediately on the thread that submits them. This is always
+    /// the case on `wasm32`.
+    ///
+    /// By default, this value is `PipelineCacheManager::DEFAULT_MAX_THREADS`.
+    pub fn max_threads(mut self, max_threads: usize) -> Self {
+        self.pool.max_threads = max_threads;
+        self
+    }
+
+    /// Begin compiling a render pipeline with the given name.
+    ///
+    /// The given function is called on one of the manager's worker threads with the manager's
+    /// device. Any existing pipeline with the same name is replaced.
+    ///
+    /// As the function is sent to another thread, it must own all of its resources (e.g. shader
+    /// modules and layouts may be shared via `Arc`).
+    pub fn compile_render<F>(&mut self, name: impl Into<String>, compile: F)
+    where
+        F: 'static + FnOnce(&wgpu::Device) -> wgpu::RenderPipeline + Send,
+    {
+        let entry = self.pool.compile(self.device.clone(), compile);
+        self.render_pipelines.insert(name.into(), entry);
+    }
+
+    /// Begin compiling a compute pipeline with the given name.
+    ///
+    /// The given function is called on one of the manager's worker threads with the manager's
+    /// device. Any existing pipeline with the same name is replaced.
+    pub fn compile_compute<F>(&mut self, name: impl Into<String>, compile: F)
+    where
+        F: 'static + FnOnce(&wgpu::Device) -> wgpu::ComputePipeline + Send,
+    {
+        let entry = self.pool.compile(self.device.clone(), compile);
+        self.compute_pipelines.insert(name.into(), entry);
+    }
+
+    /// Check for completed pipelines.
+    ///
+    /// This is called automatically by the other polling methods, but may be called once per
+    /// frame to keep readiness state up to date.
+    pub fn poll(&mut self) {
+        self.render_pipelines.values_mut().for_each(Entry::poll);
+        self.compute_pipelines.values_mut().for_each(Entry::poll);
+    }
+
+    /// Whether or not all pipelines have finished compiling.
+    pub fn is_ready(&mut self) -> bool {
+        self.pending_count() == 0
+    }
+
+    /// The number of pipelines that are still compiling.
+    pub fn pending_count(&mut self) -> usize {
+        self.poll();
+        let render = self.render_pipelines.values().filter(|e| e.is_pending());
+        let compute = self.compute_pipelines.values().filter(|e| e.is_pending());
+        render.count() + compute.count()
+    }
+
+    /// The fraction of pipelines that have finished compiling within the range `0.0..=1.0`.
+    ///
+    /// Useful for displaying a loading progress bar.
+    pub fn progress(&mut self) -> f32 {
+        let total = self.render_pipelines.len() + self.compute_pipelines.len();
+        if total == 0 {
+            return 1.0;
+        }
+        let pending = self.pending_count();
+        (total - pending) as f32 / total as f32
+    }
+
+    /// The names of pipelines whose compilation failed.
+    pub fn failed(&mut self) -> Vec<&str> {
+        self.poll();
+        let render = self.render_pipelines.iter().filter(|(_, e)| e.is_failed());
+        let compute = self.compute_pipelines.iter().filter(|(_, e)| e.is_failed());
+        render
+            .map(|(k, _)| &k[..])
+            .chain(compute.map(|(k, _)| &k[..]))
+            .collect()
+    }
+
+    /// The render pipeline with the given name if it has finished compiling.
+    pub fn render_pipeline(&mut self, name: &str) -> Option<&wgpu::RenderPipeline> {
+        let entry = self.render_pipelines.get_mut(name)?;
+        entry.poll();
+        entry.ready()
+    }
+
+    /// The compute pipeline with the given name if it has finished compiling.
+    pub fn compute_pipeline(&mut self, name: &str) -> Option<&wgpu::ComputePipeline> {
+        let entry = self.compute_pipelines.get_mut(name)?;
+        entry.poll();
+        entry.ready()
+    }
+
+    /// Remove the render pipeline with the given name.
+    ///
+    /// If the pipeline is still compiling, the result will be discarded.
+    pub fn remove_render_pipeline(&mut self, name: &str) -> Option<wgpu::RenderPipeline> {
+        match self.render_pipelines.remove(name)? {
+            Entry::Ready(pipeline) => Some(pipeline),
+            _ => None,
+        }
+    }
+
+    /// Remove the compute pipeline with the given name.
+    ///
+    /// If the pipeline is still compiling, the result will be discarded.
+    pub fn remove_compute_pipeline(&mut self, name: &str) -> Option<wgpu::ComputePipeline> {
+        match self.compute_pipelines.remove(name)? {
+            Entry::Ready(pipeline) => Some(pipeline),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Entry<T> {
+    // Check whether the pending pipeline has been received.
+    fn poll(&mut self) {
+        let result = match *self {
+            Entry::Pending(ref rx) => rx.try_recv(),
+            _ => return,
+        };
+        match result {
+            Ok(pipeline) => *self = Entry::Ready(pipeline),
+            Err(mpsc::TryRecvError::Empty) => (),
+            Err(mpsc::TryRecvError::Disconnected) => *self = Entry::Failed,
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        matches!(*self, Entry::Pending(_))
+    }
+
+    fn is_failed(&self) -> bool {
+        matches!(*self, Entry::Failed)
+    }
+
+    fn ready(&self) -> Option<&T> {
+        match *self {
+            Entry::Ready(ref pipeline) => Some(pipeline),
+            _ => None,
+        }
+    }
+}
+
+impl WorkerPool {
+    fn new(max_threads: usize) -> Self {
+        let (jobs_tx, jobs_rx) = mpsc::channel();
+        let shared = Arc::new(PoolShared {
+            jobs_rx: Mutex::new(jobs_rx),
+            closed: AtomicBool::new(false),
+        });
+        WorkerPool {
+            max_threads,
+            threads: 0,
+            jobs_tx,
+            shared,
+        }
+    }
+
+    // Queue the pipeline for compilation on a worker, spawning a new worker if the pool is not yet
+    // full. Compiles immediately if the pool has no threads.
+    fn compile<T, F>(&mut self, device: Arc<wgpu::DeviceQueuePair>, compile: F) -> Entry<T>
+    where
+        T: 'static + Send,
+        F: 'static + FnOnce(&wgpu::Device) -> T + Send,
+    {
+        if self.max_threads == 0 || cfg!(target_arch = "wasm32") {
+            return Entry::Ready(compile(device.device()));
+        }
+        let (tx, rx) = mpsc::channel();
+        let job: Job = Box::new(move || {
+            tx.send(compile(device.device())).ok();
+        });
+        // The pool holds the receiver within `shared`, so sending cannot fail.
+        self.jobs_tx.send(job).ok();
+        if self.threads < self.max_threads {
+            let shared = self.shared.clone();
+            std::thread::Builder::new()
+                .name("nannou_pipeline_compile".into())
+                .spawn(move || run_worker(&shared))
+                .expect("failed to spawn pipeline compilation thread");
+            self.threads += 1;
+        }
+        Entry::Pending(rx)
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // Workers finish their current job then exit once the job sender is dropped.
+        self.shared.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+// Run compilation jobs until the pool is dropped.
+fn run_worker(shared: &PoolShared) {
+    loop {
+        // The lock is only held while waiting, so other workers may compile in the meantime.
+        let job = match shared.jobs_rx.lock() {
+            Ok(jobs_rx) => jobs_rx.recv(),
+            Err(_) => return,
+        };
+        let job = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        if shared.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        // A panicking job drops its result sender, marking the pipeline as failed, while the
+        // worker remains available for the next job.
+        panic::catch_unwind(AssertUnwindSafe(job)).ok();
+    }
+}
@@ -0,0 +1,364 @@
+//! Helpers for pipeline statistics and occlusion queries.
+//!
+//! Query results are resolved on the GPU and read back asynchronously, so they typically become
+//! available a frame or two after the commands that produced them. The general flow is:
+//!
+//! 1. `begin` a query within a render pass, draw, then `end` it.
+//! 2. Once the render pass has ended, `resolve` the queries into the encoder.
+//! 3. After the encoder has been submitted, `poll` once per frame until the results are ready.
+//!
+//! Queries require the `Features::PIPELINE_STATISTICS_QUERY` feature to be enabled on the device.
+//!
+//! **Note:** wgpu does not yet expose native occlusion queries. `OcclusionQueries` approximates
+//! them by counting fragment shader invocations. Whether or not fragments that fail the depth test
+//! are counted is up to the driver, so this is best used to detect geometry that is entirely
+//! off-screen or clipped.
+
+use crate as wgpu;
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+
+/// The index of a query within a set, in the order that queries were begun since the last
+/// `resolve`.
+pub type QueryIndex = usize;
+
+/// A set of pipeline statistics queries that may be reused every frame.
+#[derive(Debug)]
+pub struct PipelineStatisticsQueries {
+    query_set: wgpu::QuerySet,
+    types: wgpu::PipelineStatisticsTypes,
+    capacity: u32,
+    resolve_buffer: wgpu::Buffer,
+    read_buffer: wgpu::Buffer,
+    // The number of queries begun since the last resolve.
+    next: Cell<u32>,
+    readback: Readback,
+    results: Vec<PipelineStatistics>,
+}
+
+/// The statistics recorded for a single query.
+///
+/// Statistics that were not requested when creating the query set are `None`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub vertex_shader_invocations: Option<u64>,
+    pub clipper_invocations: Option<u64>,
+    pub clipper_primitives_out: Option<u64>,
+    pub fragment_shader_invocations: Option<u64>,
+    pub compute_shader_invocations: Option<u64>,
+}
+
+/// A set of queries for determining whether or not geometry produced any visible fragments.
+///
+/// See the module documentation for the limitations of this approximation.
+#[derive(Debug)]
+pub struct OcclusionQueries {
+    queries: PipelineStatisticsQueries,
+}
+
+/// An active occlusion query within a render pass.
+///
+/// Draw commands may be issued via the `Deref` implementation. The query ends when the scope is
+/// dropped or when `end` is called.
+pub struct OcclusionScope<'p, 'a> {
+    pass: &'p mut wgpu::RenderPass<'a>,
+    index: QueryIndex,
+}
+
+// The state of the asynchronous read back of resolved queries.
+#[derive(Debug)]
+enum Readback {
+    // The read buffer is free to be copied into.
+    Idle,
+    // A copy of the given number of queries has been encoded but not yet mapped.
+    Resolved(u32),
+    // The read buffer is being mapped.
+    Mapping(u32, Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>),
+}
+
+impl PipelineStatisticsQueries {
+    /// Create a set of `capacity` queries recording the given statistics.
+    ///
+    /// **Panic!**s if `types` is empty.
+    ///
+    /// The device must have the `Features::PIPELINE_STATISTICS_QUERY` feature enabled.
+    pub fn new(device: &wgpu::Device, types: wgpu::PipelineStatisticsTypes, capacity: u32) -> Self {
+        assert!(!types.is_empty(), "at least one statistic must be queried");
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("nannou_pipeline_statistics_queries"),
+            ty: wgpu::QueryType::PipelineStatistics(types),
+            count: capacity,
+        });
+        let size = capacity as wgpu::BufferAddress * result_size(types);
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("nannou_query_resolve_buffer"),
+            size,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let read_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("nannou_query_read_buffer"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        PipelineStatisticsQueries {
+            query_set,
+            types,
+            capacity,
+            resolve_buffer,
+            read_buffer,
+            next: Cell::new(0),
+            readback: Readback::Idle,
+            results: vec![],
+        }
+    }
+
+    /// The statistics recorded by each query.
+    pub fn types(&self) -> wgpu::PipelineStatisticsTypes {
+        self.types
+    }
+
+    /// The maximum number of queries that may be begun between each `resolve`.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Begin a query within the given render pass.
+    ///
+    /// Only one query may be active within a pass at a time. Returns `None` if the capacity of
+    /// the set has been reached since the last `resolve`.
+    pub fn begin<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) -> Option<QueryIndex> {
+        let index = self.next.get();
+        if index >= self.capacity {
+            return None;
+        }
+        self.next.set(index + 1);
+        pass.begin_pipeline_statistics_query(&self.query_set, index);
+        Some(index as QueryIndex)
+    }
+
+    /// End the active query within the given render pass.
+    pub fn end(&self, pass: &mut wgpu::RenderPass) {
+        pass.end_pipeline_statistics_query();
+    }
+
+    /// Encode commands for resolving all queries begun since the last call.
+    ///
+    /// If the results of a previous resolve are still being read back, the queries of this frame
+    /// are discarded.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let count = self.next.replace(0);
+        if count == 0 {
+            return;
+        }
+        if let Readback::Idle = self.readback {
+            let size = count as wgpu::BufferAddress * result_size(self.types);
+            encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve_buffer, 0);
+            encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.read_buffer, 0, size);
+            self.readback = Readback::Resolved(count);
+        }
+    }
+
+    /// Progress the read back of resolved queries.
+    ///
+    /// This must only be called after the encoder passed to `resolve` has been submitted. Returns
+    /// `true` if new results became available.
+    pub fn poll(&mut self, device: &wgpu::Device) -> bool {
+        if let Readback::Resolved(count) = self.readback {
+            let status = Arc::new(Mutex::new(None));
+            let status2 = status.clone();
+            self.read_buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |res| {
+                    *status2.lock().expect("failed to lock map status") = Some(res);
+                });
+            self.readback = Readback::Mapping(count, status);
+        }
+        device.poll(wgpu::Maintain::Poll);
+        let (count, res) = match self.readback {
+            Readback::Mapping(count, ref status) => {
+                match status.lock().expect("failed to lock map status").take() {
+                    None => return false,
+                    Some(res) => (count, res),
+                }
+            }
+            _ => return false,
+        };
+        self.readback = Readback::Idle;
+        if res.is_err() {
+            return false;
+        }
+        {
+            let size = count as wgpu::BufferAddress * result_size(self.types);
+            let view = self.read_buffer.slice(..size).get_mapped_range();
+            let values: Vec<u64> = view
+                .chunks_exact(8)
+                .map(|b| {
+                    let mut bytes = [0u8; 8];
+                    bytes.copy_from_slice(b);
+                    u64::from_ne_bytes(bytes)
+                })
+                .collect();
+            let n = stat_count(self.types);
+            let types = self.types;
+            self.results.clear();
+            self.results.extend(
+                values
+                    .chunks_exact(n)
+                    .map(|v| PipelineStatistics::from_values(types, v)),
+            );
+        }
+        self.read_buffer.unmap();
+        true
+    }
+
+    /// The most recently read back results, indexed by `QueryIndex`.
+    pub fn results(&self) -> &[PipelineStatistics] {
+        &self.results
+    }
+}
+
+impl PipelineStatistics {
+    // Assign the values of a single query in the order defined by the bits of `types`.
+    fn from_values(types: wgpu::PipelineStatisticsTypes, values: &[u64]) -> Self {
+        let mut stats = PipelineStatistics::default();
+        let mut values = values.iter().cloned();
+        let fields = [
+            (
+                wgpu::PipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS,
+                &mut stats.vertex_shader_invocations,
+            ),
+            (
+                wgpu::PipelineStatisticsTypes::CLIPPER_INVOCATIONS,
+                &mut stats.clipper_invocations,
+            ),
+            (
+                wgpu::PipelineStatisticsTypes::CLIPPER_PRIMITIVES_OUT,
+                &mut stats.clipper_primitives_out,
+            ),
+            (
+                wgpu::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS,
+                &mut stats.fragment_shader_invocations,
+            ),
+            (
+                wgpu::PipelineStatisticsTypes::COMPUTE_SHADER_INVOCATIONS,
+                &mut stats.compute_shader_invocations,
+            ),
+        ];
+        for (ty, field) in fields {
+            if types.contains(ty) {
+                *field = values.next();
+            }
+        }
+        stats
+    }
+}
+
+impl OcclusionQueries {
+    /// Create a set of `capacity` occlusion queries.
+    ///
+    /// The device must have the `Features::PIPELINE_STATISTICS_QUERY` feature enabled.
+    pub fn new(device: &wgpu::Device, capacity: u32) -> Self {
+        let types = wgpu::PipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS;
+        let queries = PipelineStatisticsQueries::new(device, types, capacity);
+        OcclusionQueries { queries }
+    }
+
+    /// The maximum number of queries that may be begun between each `resolve`.
+    pub fn capacity(&self) -> u32 {
+        self.queries.capacity()
+    }
+
+    /// Begin an occlusion query within the given render pass.
+    ///
+    /// Returns `None` if the capacity of the set has been reached since the last `resolve`.
+    pub fn begin<'p, 'a>(
+        &'a self,
+        pass: &'p mut wgpu::RenderPass<'a>,
+    ) -> Option<OcclusionScope<'p, 'a>> {
+        OcclusionScope::begin(self, pass)
+    }
+
+    /// Encode commands for resolving all queries begun since the last call.
+    ///
+    /// See `PipelineStatisticsQueries::resolve`.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        self.queries.resolve(encoder)
+    }
+
+    /// Progress the read back of resolved queries.
+    ///
+    /// See `PipelineStatisticsQueries::poll`.
+    pub fn poll(&mut self, device: &wgpu::Device) -> bool {
+        self.queries.poll(device)
+    }
+
+    /// The number of fragments produced by the given query in the latest results.
+    pub fn fragment_count(&self, index: QueryIndex) -> Option<u64> {
+        self.queries
+            .results()
+            .get(index)
+            .and_then(|stats| stats.fragment_shader_invocations)
+    }
+
+    /// Whether or not the given query produced any fragments in the latest results.
+    ///
+    /// Returns `None` if no results are available for the query yet.
+    pub fn is_visible(&self, index: QueryIndex) -> Option<bool> {
+        self.fragment_count(index).map(|count| count > 0)
+    }
+}
+
+impl<'p, 'a> OcclusionScope<'p, 'a> {
+    /// Begin an occlusion query within the given render pass.
+    ///
+    /// Returns `None` if the capacity of the set has been reached since the last `resolve`.
+    pub fn begin(
+        queries: &'a OcclusionQueries,
+        pass: &'p mut wgpu::RenderPass<'a>,
+    ) -> Option<Self> {
+        let index = queries.queries.begin(pass)?;
+        Some(OcclusionScope { pass, index })
+    }
+
+    /// The index at which the results of this query can be found.
+    pub fn index(&self) -> QueryIndex {
+        self.index
+    }
+
+    /// End the query, returning its index.
+    pub fn end(self) -> QueryIndex {
+        self.index
+    }
+}
+
+impl<'p, 'a> Deref for OcclusionScope<'p, 'a> {
+    type Target = wgpu::RenderPass<'a>;
+    fn deref(&self) -> &Self::Target {
+        self.pass
+    }
+}
+
+impl<'p, 'a> DerefMut for OcclusionScope<'p, 'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.pass
+    }
+}
+
+impl<'p, 'a> Drop for OcclusionScope<'p, 'a> {
+    fn drop(&mut self) {
+        self.pass.end_pipeline_statistics_query();
+    }
+}
+
+// The number of statistics recorded by each query.
+fn stat_count(types: wgpu::PipelineStatisticsTypes) -> usize {
+    types.bits().count_ones() as usize
+}
+
+// The size in bytes of the resolved results for a single query.
+fn result_size(types: wgpu::PipelineStatisticsTypes) -> wgpu::BufferAddress {
+    (stat_count(types) * std::mem::size_of::<u64>()) as wgpu::BufferAddress
+}
@@ -0,0 +1,797 @@
+//! A lightweight render graph for chaining passes without hand-managing intermediate textures.
+//!
+//! Multi-pass sketches, e.g. scene → bloom → composite → UI, typically require allocating an
+//! intermediate texture for each step, recreating them on resize and encoding each pass in the
+//! right order. A [**Graph**](./struct.Graph.html) takes care of this. Each pass declares the
+//! named textures that it reads and writes, and the graph:
+//!
+//! - orders the passes so that every texture is written before it is read,
+//! - allocates the transient textures along with the usages implied by how they are used,
+//! - reuses the same underlying texture for transient textures whose lifetimes do not overlap and
+//! - wraps each pass in a debug group with the pass's name.
+//!
+//! wgpu tracks the state of each texture and inserts the necessary barriers between uses itself,
+//! so it is sufficient that passes are encoded in dependency order with the correct usages.
+//!
+//! Textures that live outside of the graph, such as the frame's texture, are declared via
+//! `Graph::add_external` and supplied to each call to `Graph::execute`.
+//!
+//! ```ignore
+//! let mut graph = wgpu::render_graph::Graph::new();
+//! let size = frame.texture_size();
+//! graph.add_texture("scene", Texture::new(size, Frame::TEXTURE_FORMAT));
+//! graph.add_texture("bloom", Texture::new(size, Frame::TEXTURE_FORMAT));
+//! graph.add_external("frame");
+//! graph.add_pass("scene", &[], &["scene"], |ctx| { /* draw the scene */ });
+//! graph.add_pass("bloom", &["scene"], &["bloom"], |ctx| { /* blur the bright areas */ });
+//! graph.add_pass("composite", &["scene", "bloom"], &["frame"], |ctx| { /* combine */ });
+//! graph.execute(device, &mut encoder, &[("frame", frame.texture_view())])?;
+//! ```
+
+use crate as wgpu;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A set of render passes along with the textures that they read from and write to.
+///
+/// The order of execution and the allocation of transient textures are determined when the graph
+/// is compiled. The graph is automatically recompiled on the next call to `execute` after any
+/// pass or texture is added, while the transient textures themselves persist between executions.
+#[derive(Default)]
+pub struct Graph {
+    resources: Vec<Resource>,
+    resource_indices: HashMap<String, usize>,
+    passes: Vec<Pass>,
+    schedule: Option<Schedule>,
+    pool: Vec<PooledTexture>,
+}
+
+/// Describes a transient texture that is allocated and owned by the graph.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Texture {
+    /// The width and height of the texture.
+    pub size: [u32; 2],
+    /// The format of the texture.
+    pub format: wgpu::TextureFormat,
+    /// The number of samples per pixel.
+    pub sample_count: u32,
+    /// Usages in addition to those implied by the passes that use the texture.
+    pub usage: wgpu::TextureUsages,
+}
+
+/// The context provided to each pass during execution.
+pub struct PassContext<'a> {
+    name: &'a str,
+    device: &'a wgpu::Device,
+    encoder: &'a mut wgpu::CommandEncoder,
+    inputs: &'a [(&'a str, &'a wgpu::TextureView)],
+    outputs: &'a [(&'a str, &'a wgpu::TextureView)],
+}
+
+/// Errors that might occur while compiling or executing a graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A pass refers to a texture that was never added to the graph.
+    UnknownTexture { pass: String, texture: String },
+    /// More than one pass writes to the same texture.
+    MultipleWriters {
+        texture: String,
+        first: String,
+        second: String,
+    },
+    /// A pass both reads from and writes to the same texture.
+    ReadWriteConflict { pass: String, texture: String },
+    /// A transient texture is read by a pass but written by none.
+    Unwritten { texture: String },
+    /// The passes form a cycle and cannot be ordered.
+    Cycle { passes: Vec<String> },
+    /// An external texture used by a pass was not supplied to `execute`.
+    MissingExternal { texture: String },
+}
+
+/// The function called to encode a pass.
+pub type PassFn = dyn FnMut(&mut PassContext);
+
+// A texture known to the graph.
+struct Resource {
+    name: String,
+    kind: ResourceKind,
+}
+
+enum ResourceKind {
+    Transient(Texture),
+    External,
+}
+
+struct Pass {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    encode: Box<PassFn>,
+}
+
+// The result of compiling the graph.
+struct Schedule {
+    // Pass indices in the order in which they are executed.
+    order: Vec<usize>,
+    // The resolved input and output resource indices of each pass.
+    inputs: Vec<Vec<usize>>,
+    outputs: Vec<Vec<usize>>,
+    // The pool slot assigned to each resource, `None` for external resources.
+    slots: Vec<Option<usize>>,
+    // The descriptor of each pool slot, including the implied usages.
+    slot_descs: Vec<Texture>,
+}
+
+struct PooledTexture {
+    desc: Texture,
+    _texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+impl Graph {
+    /// The label given to all transient textures allocated by the graph.
+    pub const TEXTURE_LABEL: &'static str = "nannou_render_graph_texture";
+
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a transient texture that is allocated and owned by the graph.
+    ///
+    /// If a texture with the given name already exists its descriptor is replaced. This is the
+    /// recommended way of handling a resize of the window.
+    pub fn add_texture(&mut self, name: &str, texture: Texture) {
+        self.add_resource(name, ResourceKind::Transient(texture));
+    }
+
+    /// Add an external texture, the view for which is supplied on each call to `execute`.
+    pub fn add_external(&mut self, name: &str) {
+        self.add_resource(name, ResourceKind::External);
+    }
+
+    /// Add a pass that reads from the named `inputs` and writes to the named `outputs`.
+    ///
+    /// The pass's `encode` function is called once per execution of the graph, after all passes
+    /// that write to its inputs. Views of the textures may be retrieved via the `PassContext`.
+    pub fn add_pass<F>(&mut self, name: &str, inputs: &[&str], outputs: &[&str], encode: F)
+    where
+        F: 'static + FnMut(&mut PassContext),
+    {
+        let pass = Pass {
+            name: name.to_string(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+            encode: Box::new(encode),
+        };
+        self.passes.push(pass);
+        self.schedule = None;
+    }
+
+    /// Remove all passes with the given name.
+    ///
+    /// Returns whether or not any passes were removed.
+    pub fn remove_pass(&mut self, name: &str) -> bool {
+        let len = self.passes.len();
+        self.passes.retain(|pass| pass.name != name);
+        let removed = self.passes.len() != len;
+        if removed {
+            self.schedule = None;
+        }
+        removed
+    }
+
+    /// The descriptor of the named transient texture.
+    pub fn texture(&self, name: &str) -> Option<&Texture> {
+        let ix = *self.resource_indices.get(name)?;
+        match self.resources[ix].kind {
+            ResourceKind::Transient(ref texture) => Some(texture),
+            ResourceKind::External => None,
+        }
+    }
+
+    /// The names of the passes in the order in which they will be executed.
+    ///
+    /// Returns `None` if the graph has changed since it was last compiled.
+    pub fn pass_order(&self) -> Option<Vec<&str>> {
+        let schedule = self.schedule.as_ref()?;
+        let names = schedule
+            .order
+            .iter()
+            .map(|&ix| &self.passes[ix].name[..])
+            .collect();
+        Some(names)
+    }
+
+    /// The number of textures currently allocated by the graph.
+    ///
+    /// This may be fewer than the number of transient textures, as textures with matching
+    /// descriptors and non-overlapping lifetimes share an allocation.
+    pub fn allocated_texture_count(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// Validate the graph, order its passes and assign an allocation to each transient texture.
+    ///
+    /// This is called automatically by `execute` when necessary, though it may be useful to call
+    /// it ahead of time in order to catch errors early.
+    pub fn compile(&mut self) -> Result<(), Error> {
+        if self.schedule.is_none() {
+            self.schedule = Some(self.build_schedule()?);
+        }
+        Ok(())
+    }
+
+    /// Encode all passes of the graph in dependency order.
+    ///
+    /// All external textures used by the passes must be supplied via `externals`. Transient
+    /// textures are (re)allocated as necessary.
+    pub fn execute(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        externals: &[(&str, &wgpu::TextureView)],
+    ) -> Result<(), Error> {
+        self.compile()?;
+        let Graph {
+            ref resources,
+            ref resource_indices,
+            ref mut passes,
+            ref schedule,
+            ref mut pool,
+            ..
+        } = *self;
+        let schedule = schedule.as_ref().expect("graph was compiled above");
+
+        // Ensure the pool matches the compiled slots.
+        pool.truncate(schedule.slot_descs.len());
+        for (ix, desc) in schedule.slot_descs.iter().enumerate() {
+            if matches!(pool.get(ix), Some(pooled) if pooled.desc == *desc) {
+                continue;
+            }
+            let pooled = PooledTexture::new(device, *desc);
+            if ix < pool.len() {
+                pool[ix] = pooled;
+            } else {
+                pool.push(pooled);
+            }
+        }
+
+        // Collect the view for each resource.
+        let mut views: Vec<Option<&wgpu::TextureView>> = schedule
+            .slots
+            .iter()
+            .map(|slot| slot.map(|ix| &pool[ix].view))
+            .collect();
+        for &(name, view) in externals {
+            let ix = match resource_indices.get(name) {
+                Some(&ix) => ix,
+                None => continue,
+            };
+            if let ResourceKind::External = resources[ix].kind {
+                views[ix] = Some(view);
+            }
+        }
+
+        for &pass_ix in &schedule.order {
+            let resolve = |ixs: &[usize]| -> Result<Vec<(&str, &wgpu::TextureView)>, Error> {
+                ixs.iter()
+                    .map(|&ix| {
+                        let name = &resources[ix].name[..];
+                        views[ix]
+                            .map(|view| (name, view))
+                            .ok_or_else(|| Error::MissingExternal {
+                                texture: name.to_string(),
+                            })
+                    })
+                    .collect()
+            };
+            let inputs = resolve(&schedule.inputs[pass_ix])?;
+            let outputs = resolve(&schedule.outputs[pass_ix])?;
+            let pass = &mut passes[pass_ix];
+            let name = &pass.name;
+            let encode = &mut pass.encode;
+            wgpu::debug_group(encoder, name, |encoder| {
+                let mut ctx = PassContext {
+                    name,
+                    device,
+                    encoder,
+                    inputs: &inputs,
+                    outputs: &outputs,
+                };
+                encode(&mut ctx);
+            });
+        }
+
+        Ok(())
+    }
+
+    // Insert or replace the named resource.
+    fn add_resource(&mut self, name: &str, kind: ResourceKind) {
+        let resource = Resource {
+            name: name.to_string(),
+            kind,
+        };
+        match self.resource_indices.get(name) {
+            Some(&ix) => self.resources[ix] = resource,
+            None => {
+                self.resource_indices
+                    .insert(name.to_string(), self.resources.len());
+                self.resources.push(resource);
+            }
+        }
+        self.schedule = None;
+    }
+
+    // Produce the schedule for the current passes and resources.
+    fn build_schedule(&self) -> Result<Schedule, Error> {
+        let n_resources = self.resources.len();
+
+        // Resolve the resource names of each pass.
+        let resolve = |pass: &Pass, names: &[String]| -> Result<Vec<usize>, Error> {
+            names
+                .iter()
+                .map(|name| {
+                    self.resource_indices
+                        .get(name)
+                        .cloned()
+                        .ok_or_else(|| Error::UnknownTexture {
+                            pass: pass.name.clone(),
+                            texture: name.clone(),
+                        })
+                })
+                .collect()
+        };
+        let mut inputs = Vec::with_capacity(self.passes.len());
+        let mut outputs = Vec::with_capacity(self.passes.len());
+        for pass in &self.passes {
+            let pass_inputs = resolve(pass, &pass.inputs)?;
+            let pass_outputs = resolve(pass, &pass.outputs)?;
+            if let Some(&ix) = pass_inputs.iter().find(|ix| pass_outputs.contains(ix)) {
+                return Err(Error::ReadWriteConflict {
+                    pass: pass.name.clone(),
+                    texture: self.resources[ix].name.clone(),
+                });
+            }
+            inputs.push(pass_inputs);
+            outputs.push(pass_outputs);
+        }
+
+        // Find the writer of each resource.
+        let mut writers: Vec<Option<usize>> = vec![None; n_resources];
+        for (pass_ix, pass_outputs) in outputs.iter().enumerate() {
+            for &ix in pass_outputs {
+                if let Some(first) = writers[ix] {
+                    return Err(Error::MultipleWriters {
+                        texture: self.resources[ix].name.clone(),
+                        first: self.passes[first].name.clone(),
+                        second: self.passes[pass_ix].name.clone(),
+                    });
+                }
+                writers[ix] = Some(pass_ix);
+            }
+        }
+        for pass_inputs in &inputs {
+            for &ix in pass_inputs {
+                let resource = &self.resources[ix];
+                if let (None, ResourceKind::Transient(_)) = (writers[ix], &resource.kind) {
+                    return Err(Error::Unwritten {
+                        texture: resource.name.clone(),
+                    });
+                }
+            }
+        }
+
+        // Order the passes, preferring the order in which they were added.
+        let dependencies: Vec<Vec<usize>> = inputs
+            .iter()
+            .map(|pass_inputs| pass_inputs.iter().filter_map(|&ix| writers[ix]).collect())
+            .collect();
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut scheduled = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len())
+                .find(|&ix| !scheduled[ix] && dependencies[ix].iter().all(|&dep| scheduled[dep]));
+            match next {
+                Some(ix) => {
+                    scheduled[ix] = true;
+                    order.push(ix);
+                }
+                None => {
+                    let passes = (0..self.passes.len())
+                        .filter(|&ix| !scheduled[ix])
+                        .map(|ix| self.passes[ix].name.clone())
+                        .collect();
+                    return Err(Error::Cycle { passes });
+                }
+            }
+        }
+
+        // Determine the implied usages and the final use of each transient resource.
+        let mut usages = vec![wgpu::TextureUsages::empty(); n_resources];
+        let mut last_use = vec![0; n_resources];
+        for (position, &pass_ix) in order.iter().enumerate() {
+            for &ix in &inputs[pass_ix] {
+                usages[ix] |= wgpu::TextureUsages::TEXTURE_BINDING;
+                last_use[ix] = position;
+            }
+            for &ix in &outputs[pass_ix] {
+                usages[ix] |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+                last_use[ix] = last_use[ix].max(position);
+            }
+        }
+
+        // Assign pool slots, reusing slots whose previous resource is no longer in use.
+        let mut slots: Vec<Option<usize>> = vec![None; n_resources];
+        let mut slot_descs: Vec<Texture> = vec![];
+        let mut free: Vec<usize> = vec![];
+        for (position, &pass_ix) in order.iter().enumerate() {
+            for &ix in &outputs[pass_ix] {
+                let desc = match self.resources[ix].kind {
+                    ResourceKind::Transient(texture) => Texture {
+                        usage: texture.usage | usages[ix],
+                        ..texture
+                    },
+                    ResourceKind::External => continue,
+                };
+                let slot = match free.iter().position(|&s| slot_descs[s] == desc) {
+                    Some(free_ix) => free.remove(free_ix),
+                    None => {
+                        slot_descs.push(desc);
+                        slot_descs.len() - 1
+                    }
+                };
+                slots[ix] = Some(slot);
+            }
+            for ix in 0..n_resources {
+                match slots[ix] {
+                    Some(slot) if last_use[ix] == position => free.push(slot),
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(Schedule {
+            order,
+            inputs,
+            outputs,
+            slots,
+            slot_descs,
+        })
+    }
+}
+
+impl Texture {
+    /// Describe a single-sampled transient texture with the given size and format.
+    pub fn new(size: [u32; 2], format: wgpu::TextureFormat) -> Self {
+        Texture {
+            size,
+            format,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::empty(),
+        }
+    }
+
+    /// Specify the number of samples per pixel.
+    ///
+    /// By default, this value is `1`.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Specify usages in addition to those implied by the passes that use the texture, e.g.
+    /// `COPY_SRC` for capturing the texture.
+    ///
+    /// By default, this value is empty.
+    pub fn usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+}
+
+impl<'a> PassContext<'a> {
+    /// The name of the pass.
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    /// The device with which the graph is being executed.
+    pub fn device(&self) -> &wgpu::Device {
+        self.device
+    }
+
+    /// The encoder to which the pass's commands should be encoded.
+    pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
+        self.encoder
+    }
+
+    /// A view of the named input texture.
+    ///
+    /// **Panic!**s if the texture was not declared as an input of the pass.
+    pub fn input(&self, name: &str) -> &wgpu::TextureView {
+        find_view(self.inputs, name)
+            .unwrap_or_else(|| panic!("`{}` is not an input of pass `{}`", name, self.name))
+    }
+
+    /// A view of the named output texture.
+    ///
+    /// **Panic!**s if the texture was not declared as an output of the pass.
+    pub fn output(&self, name: &str) -> &wgpu::TextureView {
+        find_view(self.outputs, name)
+            .unwrap_or_else(|| panic!("`{}` is not an output of pass `{}`", name, self.name))
+    }
+
+    /// Views of all input textures in the order in which they were declared.
+    pub fn inputs(&self) -> impl Iterator<Item = &wgpu::TextureView> {
+        self.inputs.iter().map(|&(_, view)| view)
+    }
+
+    /// Views of all output textures in the order in which they were declared.
+    pub fn outputs(&self) -> impl Iterator<Item = &wgpu::TextureView> {
+        self.outputs.iter().map(|&(_, view)| view)
+    }
+}
+
+impl PooledTexture {
+    fn new(device: &wgpu::Device, desc: Texture) -> Self {
+        let texture = wgpu::TextureBuilder::new()
+            .label(Graph::TEXTURE_LABEL)
+            .size(desc.size)
+            .format(desc.format)
+            .sample_count(desc.sample_count)
+            .usage(desc.usage)
+            .build(device);
+        let view = texture.view().build();
+        PooledTexture {
+            desc,
+            _texture: texture,
+            view,
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::UnknownTexture {
+                ref pass,
+                ref texture,
+            } => write!(f, "pass `{}` refers to unknown texture `{}`", pass, texture),
+            Error::MultipleWriters {
+                ref texture,
+                ref first,
+                ref second,
+            } => write!(
+                f,
+                "texture `{}` is written by both pass `{}` and pass `{}`",
+                texture, first, second
+            ),
+            Error::ReadWriteConflict {
+                ref pass,
+                ref texture,
+            } => write!(
+                f,
+                "pass `{}` both reads from and writes to texture `{}`",
+                pass, texture
+            ),
+            Error::Unwritten { ref texture } => {
+                write!(f, "texture `{}` is read but never written", texture)
+            }
+            Error::Cycle { ref passes } => {
+                write!(f, "the passes {:?} form a dependency cycle", passes)
+            }
+            Error::MissingExternal { ref texture } => {
+                write!(f, "no view was supplied for external texture `{}`", texture)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Graph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let passes: Vec<_> = self.passes.iter().map(|pass| &pass.name).collect();
+        let textures: Vec<_> = self.resources.iter().map(|r| &r.name).collect();
+        f.debug_struct("Graph")
+            .field("passes", &passes)
+            .field("textures", &textures)
+            .field("allocated_textures", &self.pool.len())
+            .finish()
+    }
+}
+
+// Find the view with the given name.
+fn find_view<'a>(
+    views: &[(&str, &'a wgpu::TextureView)],
+    name: &str,
+) -> Option<&'a wgpu::TextureView> {
+    views
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|&(_, view)| view)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+    fn texture() -> Texture {
+        Texture::new([64, 64], FORMAT)
+    }
+
+    fn noop(_: &mut PassContext) {}
+
+    // The pool slot assigned to the named texture by the last compilation.
+    fn slot(graph: &Graph, name: &str) -> Option<usize> {
+        let schedule = graph.schedule.as_ref().expect("graph was not compiled");
+        schedule.slots[graph.resource_indices[name]]
+    }
+
+    #[test]
+    fn orders_passes_by_dependency() {
+        let mut graph = Graph::new();
+        graph.add_texture("scene", texture());
+        graph.add_texture("bloom", texture());
+        graph.add_external("frame");
+        graph.add_pass("composite", &["scene", "bloom"], &["frame"], noop);
+        graph.add_pass("bloom", &["scene"], &["bloom"], noop);
+        graph.add_pass("scene", &[], &["scene"], noop);
+        assert_eq!(graph.pass_order(), None);
+        graph.compile().unwrap();
+        assert_eq!(
+            graph.pass_order(),
+            Some(vec!["scene", "bloom", "composite"])
+        );
+    }
+
+    #[test]
+    fn independent_passes_keep_insertion_order() {
+        let mut graph = Graph::new();
+        graph.add_external("frame");
+        graph.add_texture("a", texture());
+        graph.add_texture("b", texture());
+        graph.add_pass("b", &[], &["b"], noop);
+        graph.add_pass("a", &[], &["a"], noop);
+        graph.add_pass("ui", &["frame"], &[], noop);
+        graph.compile().unwrap();
+        assert_eq!(graph.pass_order(), Some(vec!["b", "a", "ui"]));
+    }
+
+    #[test]
+    fn changes_invalidate_schedule() {
+        let mut graph = Graph::new();
+        graph.add_texture("a", texture());
+        graph.add_pass("a", &[], &["a"], noop);
+        graph.compile().unwrap();
+        graph.add_texture("a", texture().sample_count(4));
+        assert_eq!(graph.pass_order(), None);
+        graph.compile().unwrap();
+        assert!(graph.remove_pass("a"));
+        assert!(!graph.remove_pass("a"));
+        assert_eq!(graph.pass_order(), None);
+        graph.compile().unwrap();
+        assert_eq!(graph.pass_order(), Some(vec![]));
+    }
+
+    #[test]
+    fn unknown_texture() {
+        let mut graph = Graph::new();
+        graph.add_pass("scene", &[], &["scene"], noop);
+        let err = Error::UnknownTexture {
+            pass: "scene".into(),
+            texture: "scene".into(),
+        };
+        assert_eq!(graph.compile(), Err(err));
+    }
+
+    #[test]
+    fn multiple_writers() {
+        let mut graph = Graph::new();
+        graph.add_texture("scene", texture());
+        graph.add_pass("first", &[], &["scene"], noop);
+        graph.add_pass("second", &[], &["scene"], noop);
+        let err = Error::MultipleWriters {
+            texture: "scene".into(),
+            first: "first".into(),
+            second: "second".into(),
+        };
+        assert_eq!(graph.compile(), Err(err));
+    }
+
+    #[test]
+    fn read_write_conflict() {
+        let mut graph = Graph::new();
+        graph.add_texture("scene", texture());
+        graph.add_pass("feedback", &["scene"], &["scene"], noop);
+        let err = Error::ReadWriteConflict {
+            pass: "feedback".into(),
+            texture: "scene".into(),
+        };
+        assert_eq!(graph.compile(), Err(err));
+    }
+
+    #[test]
+    fn unwritten_transient() {
+        let mut graph = Graph::new();
+        graph.add_texture("scene", texture());
+        graph.add_external("frame");
+        graph.add_pass("composite", &["scene"], &["frame"], noop);
+        let err = Error::Unwritten {
+            texture: "scene".into(),
+        };
+        assert_eq!(graph.compile(), Err(err));
+    }
+
+    #[test]
+    fn cycle() {
+        let mut graph = Graph::new();
+        graph.add_texture("x", texture());
+        graph.add_texture("y", texture());
+        graph.add_texture("z", texture());
+        graph.add_pass("z", &[], &["z"], noop);
+        graph.add_pass("a", &["x", "z"], &["y"], noop);
+        graph.add_pass("b", &["y"], &["x"], noop);
+        let err = Error::Cycle {
+            passes: vec!["a".into(), "b".into()],
+        };
+        assert_eq!(graph.compile(), Err(err));
+    }
+
+    #[test]
+    fn non_overlapping_textures_share_allocation() {
+        let mut graph = Graph::new();
+        graph.add_texture("a", texture());
+        graph.add_texture("b", texture());
+        graph.add_texture("c", texture());
+        graph.add_external("frame");
+        graph.add_pass("a", &[], &["a"], noop);
+        graph.add_pass("b", &["a"], &["b"], noop);
+        graph.add_pass("c", &["b"], &["c"], noop);
+        graph.add_pass("frame", &["c"], &["frame"], noop);
+        graph.compile().unwrap();
+        assert_eq!(slot(&graph, "a"), Some(0));
+        assert_eq!(slot(&graph, "b"), Some(1));
+        assert_eq!(slot(&graph, "c"), Some(0));
+        assert_eq!(slot(&graph, "frame"), None);
+        assert_eq!(graph.schedule.as_ref().unwrap().slot_descs.len(), 2);
+    }
+
+    #[test]
+    fn mismatched_textures_do_not_share_allocation() {
+        let mut graph = Graph::new();
+        graph.add_texture("a", texture());
+        graph.add_texture("b", texture());
+        graph.add_texture("c", Texture::new([32, 32], FORMAT));
+        graph.add_pass("a", &[], &["a"], noop);
+        graph.add_pass("b", &["a"], &["b"], noop);
+        graph.add_pass("c", &["b"], &["c"], noop);
+        graph.compile().unwrap();
+        assert_eq!(slot(&graph, "c"), Some(2));
+        assert_eq!(graph.schedule.as_ref().unwrap().slot_descs.len(), 3);
+    }
+
+    #[test]
+    fn usages_are_implied_by_passes() {
+        let mut graph = Graph::new();
+        graph.add_texture("scene", texture().usage(wgpu::TextureUsages::COPY_SRC));
+        graph.add_texture("unread", texture());
+        graph.add_external("frame");
+        graph.add_pass("scene", &[], &["scene", "unread"], noop);
+        graph.add_pass("composite", &["scene"], &["frame"], noop);
+        graph.compile().unwrap();
+        let schedule = graph.schedule.as_ref().unwrap();
+        let desc = |name| schedule.slot_descs[slot(&graph, name).unwrap()];
+        assert_eq!(
+            desc("scene").usage,
+            wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+        );
+        assert_eq!(desc("unread").usage, wgpu::TextureUsages::RENDER_ATTACHMENT);
+    }
+}
@@ -3,6 +3,8 @@ use crate::{self as wgpu, Color, LoadOp};
 /// A builder type to simplify the process of creating a render pass descriptor.
 #[derive(Debug, Default)]
 pub struct Builder<'a> {
+    label: Option<&'a str>,
+    debug_markers: Vec<&'a str>,
     color_attachments: Vec<Option<wgpu::RenderPassColorAttachment<'a>>>,
     depth_stencil_attachment: Option<wgpu::RenderPassDepthStencilAttachment<'a>>,
 }
@@ -150,11 +152,32 @@ impl<'a> Builder<'a> {
     pub const DEFAULT_CLEAR_STENCIL: u32 =
         DepthStencilAttachmentDescriptorBuilder::DEFAULT_CLEAR_STENCIL;
 
+    pub const DEFAULT_LABEL: &'static str = "nannou_render_pass";
+
     /// Begin building a new render pass descriptor.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Debug label of the render pass.
+    ///
+    /// This will show up in graphics debuggers for easy identification.
+    ///
+    /// By default, this is `"nannou_render_pass"`.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Insert a debug marker with the given label at the beginning of the render pass.
+    ///
+    /// Call this multiple times in succession to insert multiple markers. To group the commands
+    /// of multiple passes, see `wgpu::debug_group`.
+    pub fn debug_marker(mut self, label: &'a str) -> Self {
+        self.debug_markers.push(label);
+        self
+    }
+
     /// Add a single color attachment descriptor to the render pass descriptor.
     ///
     /// Call this multiple times in succession to add multiple color attachments.
@@ -202,18 +225,25 @@ impl<'a> Builder<'a> {
         let Builder {
             color_attachments,
             depth_stencil_attachment,
+            ..
         } = self;
         (color_attachments, depth_stencil_attachment)
     }
 
     /// Begin a render pass with the specified parameters on the given encoder.
-    pub fn begin(self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+    pub fn begin(mut self, encoder: &'a mut wgpu::CommandEncoder) -> wgpu::RenderPass<'a> {
+        let label = self.label.unwrap_or(Self::DEFAULT_LABEL);
+        let debug_markers = std::mem::take(&mut self.debug_markers);
         let (color_attachments, depth_stencil_attachment) = self.into_inner();
         let descriptor = wgpu::RenderPassDescriptor {
-            label: Some("nannou_render_pass"),
+            label: Some(label),
             color_attachments: &color_attachments,
             depth_stencil_attachment,
         };
-        encoder.begin_render_pass(&descriptor)
+        let mut render_pass = encoder.begin_render_pass(&descriptor);
+        for marker in debug_markers {
+            render_pass.insert_debug_marker(marker);
+        }
+        render_pass
     }
 }
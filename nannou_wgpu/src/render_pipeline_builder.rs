@@ -5,6 +5,7 @@
 //! simplify the process and fall back to a set of reasonable defaults.
 
 use crate as wgpu;
+use crate::msaa;
 
 #[derive(Debug)]
 enum Layout<'a> {
@@ -23,6 +24,7 @@ pub trait IntoPipelineLayoutDescriptor<'a> {
 /// We've attempted to provide a suite of reasonable defaults in the case that none are provided.
 #[derive(Debug)]
 pub struct RenderPipelineBuilder<'a> {
+    label: &'a str,
     layout: Layout<'a>,
     vs_mod: &'a wgpu::ShaderModule,
     fs_mod: Option<&'a wgpu::ShaderModule>,
@@ -39,6 +41,8 @@ pub struct RenderPipelineBuilder<'a> {
 impl<'a> RenderPipelineBuilder<'a> {
     // The default entry point used for shaders when unspecified.
     pub const DEFAULT_SHADER_ENTRY_POINT: &'static str = "main";
+    // The default debug label of the render pipeline.
+    pub const DEFAULT_LABEL: &'static str = "nannou render pipeline";
 
     // Primitive state.
     pub const DEFAULT_FRONT_FACE: wgpu::FrontFace = wgpu::FrontFace::Ccw;
@@ -143,6 +147,7 @@ impl<'a> RenderPipelineBuilder<'a> {
     // Shared between constructors.
     fn new_inner(layout: Layout<'a>, vs_mod: &'a wgpu::ShaderModule) -> Self {
         RenderPipelineBuilder {
+            label: Self::DEFAULT_LABEL,
             layout,
             vs_mod,
             fs_mod: None,
@@ -159,6 +164,16 @@ impl<'a> RenderPipelineBuilder<'a> {
 
     // Builders
 
+    /// Debug label of the render pipeline.
+    ///
+    /// This will show up in graphics debuggers for easy identification.
+    ///
+    /// By default, this is `"nannou render pipeline"`.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = label;
+        self
+    }
+
     /// The name of the entry point in the compiled shader.
     ///
     /// There must be a function that returns void with this name in the shader.
@@ -446,6 +461,32 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    /// Clamp the specified sample count to the greatest count supported by the device for all
+    /// color and depth target formats of the pipeline.
+    ///
+    /// This should be called after specifying the sample count and all target formats.
+    pub fn clamp_sample_count(self, adapter: &wgpu::Adapter, device: &wgpu::Device) -> Self {
+        self.clamp_sample_count_with(adapter, device, |_| ())
+    }
+
+    /// The same as `clamp_sample_count`, but calls `warn` in the case that the specified sample
+    /// count is unsupported and a lower count is used instead.
+    pub fn clamp_sample_count_with<F>(
+        mut self,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        warn: F,
+    ) -> Self
+    where
+        F: FnOnce(wgpu::SampleCountFallback),
+    {
+        let formats = self.target_formats();
+        let requested = self.multisample.count;
+        self.multisample.count =
+            msaa::clamp_sample_count_for_formats(adapter, device, formats, requested, warn);
+        self
+    }
+
     /// Bitmask that restricts the samples of a pixel modified by this pipeline. All samples can be
     /// enabled using the value !0 (the default).
     pub fn sample_mask(mut self, sample_mask: u64) -> Self {
@@ -466,6 +507,22 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    // The formats of all color and depth targets that the pipeline will be built with.
+    fn target_formats(&self) -> Vec<wgpu::TextureFormat> {
+        let mut formats = vec![];
+        if self.color_states.is_empty() {
+            if self.fs_mod.is_some() {
+                let color_state = self.color_state.as_ref();
+                let format = color_state.map(|cs| cs.format);
+                formats.push(format.unwrap_or(Self::DEFAULT_COLOR_FORMAT));
+            }
+        } else {
+            formats.extend(self.color_states.iter().flatten().map(|cs| cs.format));
+        }
+        formats.extend(self.depth_stencil.as_ref().map(|ds| ds.format));
+        formats
+    }
+
     // Finalising methods.
 
     /// Build the render pipeline layout, its descriptor and ultimately the pipeline itself with
@@ -508,6 +565,7 @@ fn build(
     device: &wgpu::Device,
 ) -> wgpu::RenderPipeline {
     let RenderPipelineBuilder {
+        label,
         layout: _layout,
         vs_mod,
         fs_mod,
@@ -552,7 +610,7 @@ fn build(
     };
 
     let pipeline_desc = wgpu::RenderPipelineDescriptor {
-        label: Some("nannou render pipeline"),
+        label: Some(label),
         layout: Some(layout),
         vertex,
         primitive,
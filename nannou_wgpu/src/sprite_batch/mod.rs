@@ -0,0 +1,493 @@
+//! Batched rendering of textured quads, i.e. sprites.
+//!
+//! Drawing thousands of small images with one draw call each quickly becomes the bottleneck of a
+//! 2D sketch. A `SpriteBatcher` instead accumulates sprites throughout the frame and renders all
+//! sprites sharing a texture with a single instanced draw call.
+//!
+//! Each texture that sprites may be drawn from is registered as a *page*. An atlas produced by a
+//! `TextureArray` may be registered via `add_texture_array`, yielding a `SpriteRegion` for each of
+//! its textures. Sprites drawn from any region of the same atlas are then batched together.
+//!
+//! The flow for each frame is:
+//!
+//! 1. `push` the sprites for the frame.
+//! 2. `encode_render_pass` to upload and draw them, or `prepare` followed by `draw` to draw them
+//!    within an existing render pass.
+
+use crate::{self as wgpu, AtlasRect, CameraMat4};
+
+/// A textured quad drawn by a `SpriteBatcher`.
+///
+/// This is uploaded to the GPU as is, one instance per sprite.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprite {
+    /// The position of the centre of the sprite.
+    pub position: [f32; 2],
+    /// The width and height of the sprite.
+    pub size: [f32; 2],
+    /// The region of the page's texture that is drawn.
+    pub uv: AtlasRect,
+    /// The colour by which the texture is multiplied.
+    pub color: [f32; 4],
+    /// The rotation of the sprite around its centre in radians, counter-clockwise.
+    pub rotation: f32,
+}
+
+/// Identifies a texture registered with a `SpriteBatcher`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PageId(usize);
+
+/// A region of a page, e.g. a single texture packed into an atlas.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteRegion {
+    /// The page containing the region.
+    pub page: PageId,
+    /// The region within the page's texture.
+    pub rect: AtlasRect,
+}
+
+/// Accumulates sprites and draws them with a single instanced draw call per page.
+///
+/// Sprites of the same page are drawn in the order in which they were pushed. Pages are drawn in
+/// the order in which they were added, so sprites that must overlap in a specific order should
+/// share a page.
+#[derive(Debug)]
+pub struct SpriteBatcher {
+    label: &'static str,
+    _shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    render_pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    view_proj: CameraMat4,
+    pages: Vec<Page>,
+    // The instances of all pages, uploaded by `prepare`. Recreated when the capacity is exceeded.
+    instance_buffer: Option<wgpu::Buffer>,
+    instance_capacity: usize,
+    // The range of instances within the buffer for each page, as of the last `prepare`.
+    ranges: Vec<(PageId, std::ops::Range<u32>)>,
+}
+
+/// A builder for a `SpriteBatcher`.
+#[derive(Debug)]
+pub struct Builder {
+    label: &'static str,
+    color_format: wgpu::TextureFormat,
+    sample_count: u32,
+    blend: wgpu::BlendState,
+    sampler_desc: wgpu::SamplerDescriptor<'static>,
+}
+
+// A texture that sprites may be drawn from, along with the sprites pushed this frame.
+#[derive(Debug)]
+struct Page {
+    bind_group: wgpu::BindGroup,
+    sprites: Vec<Sprite>,
+}
+
+// The uniforms of the sprite shader.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Uniforms {
+    view_proj: CameraMat4,
+}
+
+impl Sprite {
+    /// A white sprite of the given size centred on the given position, covering the whole page.
+    pub fn new(position: [f32; 2], size: [f32; 2]) -> Self {
+        Sprite {
+            position,
+            size,
+            uv: AtlasRect::IDENTITY,
+            color: [1.0; 4],
+            rotation: 0.0,
+        }
+    }
+
+    /// The region of the page's texture that is drawn.
+    ///
+    /// By default, this is `AtlasRect::IDENTITY`.
+    pub fn uv(mut self, uv: AtlasRect) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    /// The colour by which the texture is multiplied.
+    ///
+    /// By default, this is opaque white.
+    pub fn color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// The rotation of the sprite around its centre in radians, counter-clockwise.
+    ///
+    /// By default, this value is `0.0`.
+    pub fn rotation(mut self, radians: f32) -> Self {
+        self.rotation = radians;
+        self
+    }
+}
+
+impl SpriteBatcher {
+    /// The vertex attributes of the sprite instances.
+    pub const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32x4,
+        5 => Float32
+    ];
+
+    /// Begin building a sprite batcher.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// Register a texture that sprites may be drawn from.
+    ///
+    /// The texture must be a filterable float texture.
+    pub fn add_page(&mut self, device: &wgpu::Device, view: &wgpu::TextureViewHandle) -> PageId {
+        let bind_group = wgpu::BindGroupBuilder::new()
+            .label(self.label)
+            .texture_view(view)
+            .sampler(&self.sampler)
+            .buffer_bytes(&self.uniform_buffer, 0, None)
+            .build(device, &self.bind_group_layout);
+        let id = PageId(self.pages.len());
+        self.pages.push(Page {
+            bind_group,
+            sprites: vec![],
+        });
+        id
+    }
+
+    /// Register the textures of the given array, returning the region of each texture in order.
+    ///
+    /// In `TextureArrayMode::Atlas` the atlas is registered as a single page, so that sprites
+    /// drawn from any of its textures are batched together. In `TextureArrayMode::Array` each
+    /// texture is registered as its own page.
+    pub fn add_texture_array(
+        &mut self,
+        device: &wgpu::Device,
+        array: &wgpu::TextureArray,
+    ) -> Vec<SpriteRegion> {
+        match array.mode() {
+            wgpu::TextureArrayMode::Atlas => {
+                let page = self.add_page(device, &array.views()[0]);
+                array
+                    .rects()
+                    .iter()
+                    .map(|&rect| SpriteRegion { page, rect })
+                    .collect()
+            }
+            wgpu::TextureArrayMode::Array => array
+                .views()
+                .iter()
+                .map(|view| SpriteRegion {
+                    page: self.add_page(device, view),
+                    rect: AtlasRect::IDENTITY,
+                })
+                .collect(),
+        }
+    }
+
+    /// The number of registered pages.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The total number of sprites pushed since the last `prepare`.
+    pub fn len(&self) -> usize {
+        self.pages.iter().map(|page| page.sprites.len()).sum()
+    }
+
+    /// Whether or not any sprites have been pushed since the last `prepare`.
+    pub fn is_empty(&self) -> bool {
+        self.pages.iter().all(|page| page.sprites.is_empty())
+    }
+
+    /// The matrix transforming sprite positions into clip space.
+    pub fn view_proj(&self) -> CameraMat4 {
+        self.view_proj
+    }
+
+    /// Specify the matrix transforming sprite positions into clip space, e.g. that of a camera.
+    ///
+    /// By default, this is an orthographic projection of a 1x1 viewport. See `set_viewport_size`.
+    pub fn set_view_proj(&mut self, view_proj: CameraMat4) {
+        self.view_proj = view_proj;
+    }
+
+    /// Use an orthographic projection in which positions are in pixels with the origin at the
+    /// centre of a viewport of the given size and the y axis pointing up.
+    ///
+    /// This matches the coordinate system of nannou's `Draw` API.
+    pub fn set_viewport_size(&mut self, width: f32, height: f32) {
+        self.view_proj = ortho(width, height);
+    }
+
+    /// Push a sprite to be drawn from the given page.
+    ///
+    /// **Panic!**s if the page was not registered with this batcher.
+    pub fn push(&mut self, page: PageId, sprite: Sprite) {
+        self.pages[page.0].sprites.push(sprite);
+    }
+
+    /// Push a sprite to be drawn from the given region.
+    ///
+    /// The sprite's `uv` is interpreted relative to the region, so the default covers the whole
+    /// region.
+    ///
+    /// **Panic!**s if the region's page was not registered with this batcher.
+    pub fn push_region(&mut self, region: SpriteRegion, mut sprite: Sprite) {
+        let SpriteRegion { page, rect } = region;
+        sprite.uv = AtlasRect {
+            offset: [
+                rect.offset[0] + sprite.uv.offset[0] * rect.scale[0],
+                rect.offset[1] + sprite.uv.offset[1] * rect.scale[1],
+            ],
+            scale: [
+                sprite.uv.scale[0] * rect.scale[0],
+                sprite.uv.scale[1] * rect.scale[1],
+            ],
+        };
+        self.push(page, sprite);
+    }
+
+    /// Discard all sprites pushed since the last `prepare`.
+    pub fn clear(&mut self) {
+        for page in &mut self.pages {
+            page.sprites.clear();
+        }
+    }
+
+    /// Upload the pushed sprites and the view projection in preparation for `draw`.
+    ///
+    /// The pushed sprites are taken, so that sprites may be pushed for the next frame.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let uniforms = Uniforms {
+            view_proj: self.view_proj,
+        };
+        let uniforms_bytes = unsafe { wgpu::bytes::from(&uniforms) };
+        queue.write_buffer(&self.uniform_buffer, 0, uniforms_bytes);
+
+        self.ranges.clear();
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+
+        // Grow the instance buffer if necessary.
+        if self.instance_buffer.is_none() || self.instance_capacity < len {
+            let capacity = len.next_power_of_two();
+            let size = (capacity * std::mem::size_of::<Sprite>()) as wgpu::BufferAddress;
+            self.instance_buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }));
+            self.instance_capacity = capacity;
+        }
+        let buffer = self.instance_buffer.as_ref().expect("created above");
+
+        // Write each page's sprites contiguously, recording the range of each.
+        let mut start = 0;
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            if page.sprites.is_empty() {
+                continue;
+            }
+            let end = start + page.sprites.len() as u32;
+            let offset = start as wgpu::BufferAddress * std::mem::size_of::<Sprite>() as u64;
+            let bytes = unsafe { wgpu::bytes::from_slice(&page.sprites) };
+            queue.write_buffer(buffer, offset, bytes);
+            self.ranges.push((PageId(i), start..end));
+            page.sprites.clear();
+            start = end;
+        }
+    }
+
+    /// Draw the sprites uploaded by the last `prepare` within the given render pass.
+    ///
+    /// The render pass must target a texture with the format and sample count with which the
+    /// batcher was built.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let buffer = match self.instance_buffer {
+            Some(ref buffer) if !self.ranges.is_empty() => buffer,
+            _ => return,
+        };
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, buffer.slice(..));
+        for (page, range) in &self.ranges {
+            render_pass.set_bind_group(0, &self.pages[page.0].bind_group, &[]);
+            render_pass.draw(0..4, range.clone());
+        }
+    }
+
+    /// Upload the pushed sprites and encode a render pass drawing them over the existing contents
+    /// of the destination texture.
+    pub fn encode_render_pass(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        dst_texture: &wgpu::TextureViewHandle,
+    ) {
+        self.prepare(device, queue);
+        let mut render_pass = wgpu::RenderPassBuilder::new()
+            .label(self.label)
+            .color_attachment(dst_texture, |color| color.load_op(wgpu::LoadOp::Load))
+            .begin(encoder);
+        self.draw(&mut render_pass);
+    }
+}
+
+impl Builder {
+    /// The default debug label of the batcher's resources.
+    pub const DEFAULT_LABEL: &'static str = "nannou_sprite_batcher";
+    /// The default format of the destination texture.
+    pub const DEFAULT_COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+    /// The default blend state, i.e. alpha blending.
+    pub const DEFAULT_BLEND: wgpu::BlendState = wgpu::BlendState {
+        color: wgpu::blend::NORMAL,
+        alpha: wgpu::blend::NORMAL,
+    };
+
+    /// Begin building a sprite batcher.
+    pub fn new() -> Self {
+        Builder {
+            label: Self::DEFAULT_LABEL,
+            color_format: Self::DEFAULT_COLOR_FORMAT,
+            sample_count: 1,
+            blend: Self::DEFAULT_BLEND,
+            sampler_desc: wgpu::SamplerBuilder::new().into_descriptor(),
+        }
+    }
+
+    /// Debug label of the batcher's resources.
+    ///
+    /// By default, this is `"nannou_sprite_batcher"`.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// The format of the destination texture.
+    ///
+    /// By default, this is `Builder::DEFAULT_COLOR_FORMAT`.
+    pub fn color_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.color_format = format;
+        self
+    }
+
+    /// The sample count of the destination texture.
+    ///
+    /// By default, this is `1`.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// The way in which sprites are blended with the destination.
+    ///
+    /// By default, this is `Builder::DEFAULT_BLEND`.
+    pub fn blend(mut self, blend: wgpu::BlendState) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// The sampler used to sample the pages.
+    ///
+    /// By default, this is the `SamplerBuilder` default.
+    pub fn sampler(mut self, desc: wgpu::SamplerDescriptor<'static>) -> Self {
+        self.sampler_desc = desc;
+        self
+    }
+
+    /// Build the sprite batcher.
+    pub fn build(self, device: &wgpu::Device) -> SpriteBatcher {
+        let Builder {
+            label,
+            color_format,
+            sample_count,
+            blend,
+            sampler_desc,
+        } = self;
+
+        let shader_desc = wgpu::include_wgsl!("shaders/sprite.wgsl");
+        let shader = device.create_shader_module(shader_desc);
+
+        let sampler_filtering = wgpu::sampler_filtering(&sampler_desc);
+        let sampler = device.create_sampler(&sampler_desc);
+
+        let bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .label(label)
+            .texture(
+                wgpu::ShaderStages::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                wgpu::TextureSampleType::Float { filterable: true },
+            )
+            .sampler(wgpu::ShaderStages::FRAGMENT, sampler_filtering)
+            .uniform_buffer(wgpu::ShaderStages::VERTEX, false)
+            .build(device);
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: std::mem::size_of::<Uniforms>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &shader)
+            .label(label)
+            .vertex_entry_point("vs_main")
+            .fragment_shader(&shader)
+            .fragment_entry_point("fs_main")
+            .add_instance_buffer::<Sprite>(&SpriteBatcher::INSTANCE_ATTRIBUTES)
+            .color_format(color_format)
+            .color_blend(blend.color)
+            .alpha_blend(blend.alpha)
+            .primitive_topology(wgpu::PrimitiveTopology::TriangleStrip)
+            .sample_count(sample_count)
+            .build(device);
+
+        SpriteBatcher {
+            label,
+            _shader: shader,
+            bind_group_layout,
+            render_pipeline,
+            sampler,
+            uniform_buffer,
+            view_proj: ortho(1.0, 1.0),
+            pages: vec![],
+            instance_buffer: None,
+            instance_capacity: 0,
+            ranges: vec![],
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// An orthographic projection of a viewport of the given size centred on the origin.
+fn ortho(width: f32, height: f32) -> CameraMat4 {
+    [
+        [2.0 / width, 0.0, 0.0, 0.0],
+        [0.0, 2.0 / height, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
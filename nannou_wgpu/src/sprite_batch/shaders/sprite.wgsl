@@ -0,0 +1,51 @@
+struct Uniforms {
+    view_proj: mat4x4<f32>,
+};
+
+struct Instance {
+    @location(0) position: vec2<f32>,
+    @location(1) size: vec2<f32>,
+    @location(2) uv_offset: vec2<f32>,
+    @location(3) uv_scale: vec2<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) rotation: f32,
+};
+
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @builtin(position) out_pos: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var tex: texture_2d<f32>;
+@group(0) @binding(1)
+var tex_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> uniforms: Uniforms;
+
+// Produces the four corners of each sprite's quad as a triangle strip from the vertex index.
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    instance: Instance,
+) -> VertexOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32((index >> 1u) & 1u));
+    let local = (corner - vec2<f32>(0.5, 0.5)) * instance.size;
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotated = vec2<f32>(local.x * c - local.y * s, local.x * s + local.y * c);
+    let world = instance.position + rotated;
+    let out_pos = uniforms.view_proj * vec4<f32>(world, 0.0, 1.0);
+    // Positions are y-up while texture coordinates have `(0, 0)` at the top-left.
+    let tex_coords = instance.uv_offset + vec2<f32>(corner.x, 1.0 - corner.y) * instance.uv_scale;
+    return VertexOutput(tex_coords, instance.color, out_pos);
+}
+
+@fragment
+fn fs_main(
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+) -> @location(0) vec4<f32> {
+    return textureSample(tex, tex_sampler, tex_coords) * color;
+}
@@ -0,0 +1,463 @@
+//! A set of textures that may be bound and sampled via a single binding.
+//!
+//! Where the device supports binding arrays of textures, each texture is bound as an element of
+//! the array and indexed within the shader. Otherwise, the textures are packed into a single atlas
+//! texture and each is addressed by its `AtlasRect` within the atlas.
+
+use crate::{self as wgpu, Texture, TextureView};
+
+/// A set of textures that may be bound via a single binding, regardless of device support for
+/// binding arrays.
+///
+/// Shaders must support both modes unless the mode is known ahead of time:
+///
+/// - `TextureArrayMode::Array`: the textures are bound as a `binding_array<texture_2d<f32>, N>`
+///   and each texture is sampled via its index.
+/// - `TextureArrayMode::Atlas`: a single `texture_2d<f32>` is bound and each texture is sampled by
+///   mapping texture coordinates into its `AtlasRect`, i.e. `rect.offset + uv * rect.scale`.
+#[derive(Debug)]
+pub struct TextureArray {
+    mode: TextureArrayMode,
+    views: Vec<TextureView>,
+    atlas: Option<Texture>,
+    rects: Vec<AtlasRect>,
+    format: wgpu::TextureFormat,
+    sample_type: wgpu::TextureSampleType,
+}
+
+/// The way in which the textures of a `TextureArray` are bound.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TextureArrayMode {
+    /// Each texture is an element of a binding array.
+    Array,
+    /// The textures are packed into a single atlas texture.
+    Atlas,
+}
+
+/// The region occupied by a texture within a texture atlas, in normalised texture coordinates.
+///
+/// Texture coordinates `uv` of a texture map to `offset + uv * scale` within the atlas. In
+/// `TextureArrayMode::Array`, every rect is the identity mapping.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRect {
+    pub offset: [f32; 2],
+    pub scale: [f32; 2],
+}
+
+/// A type aimed at simplifying the construction of a `TextureArray`.
+#[derive(Debug)]
+pub struct Builder<'a> {
+    textures: Vec<&'a Texture>,
+    label: &'static str,
+    mode: Option<TextureArrayMode>,
+    padding: u32,
+}
+
+impl TextureArray {
+    /// The way in which the textures are bound.
+    pub fn mode(&self) -> TextureArrayMode {
+        self.mode
+    }
+
+    /// The number of textures within the array.
+    pub fn len(&self) -> usize {
+        self.rects.len()
+    }
+
+    /// Whether or not the array contains no textures.
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// The region of the texture at the given index within the atlas.
+    ///
+    /// **Panic!**s if there is no texture at the given index.
+    pub fn rect(&self, index: usize) -> AtlasRect {
+        self.rects[index]
+    }
+
+    /// The regions of each texture within the atlas, in order.
+    ///
+    /// In `Atlas` mode, it is common to upload these to a uniform or storage buffer so that the
+    /// shader may look them up by texture index.
+    pub fn rects(&self) -> &[AtlasRect] {
+        &self.rects
+    }
+
+    /// The texture format shared by all textures within the array.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// The atlas texture into which the textures were packed, if in `Atlas` mode.
+    pub fn atlas(&self) -> Option<&Texture> {
+        self.atlas.as_ref()
+    }
+
+    /// The views that are bound.
+    ///
+    /// In `Array` mode there is one view per texture. In `Atlas` mode there is a single view of
+    /// the atlas.
+    pub fn views(&self) -> &[TextureView] {
+        &self.views
+    }
+
+    /// Add the binding for the textures to the given layout builder.
+    pub fn add_to_layout<'a>(
+        &self,
+        builder: wgpu::BindGroupLayoutBuilder<'a>,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayoutBuilder<'a> {
+        let dim = wgpu::TextureViewDimension::D2;
+        match self.mode {
+            TextureArrayMode::Array => {
+                builder.texture_array(visibility, dim, self.sample_type, self.len() as u32)
+            }
+            TextureArrayMode::Atlas => builder.texture(visibility, false, dim, self.sample_type),
+        }
+    }
+
+    /// Create a bind group layout with the textures at binding `0` and a sampler at binding `1`.
+    pub fn bind_group_layout(
+        &self,
+        device: &wgpu::Device,
+        visibility: wgpu::ShaderStages,
+    ) -> wgpu::BindGroupLayout {
+        let filtering = matches!(
+            self.sample_type,
+            wgpu::TextureSampleType::Float { filterable: true }
+        );
+        let builder = wgpu::BindGroupLayoutBuilder::new().label("nannou texture array layout");
+        self.add_to_layout(builder, visibility)
+            .sampler(visibility, filtering)
+            .build(device)
+    }
+
+    /// Create a bind group matching the layout produced by `bind_group_layout`.
+    pub fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        let handles: Vec<&wgpu::TextureViewHandle> = self.views.iter().map(|v| &**v).collect();
+        let builder = wgpu::BindGroupBuilder::new().label("nannou texture array");
+        let builder = match self.mode {
+            TextureArrayMode::Array => builder.texture_view_array(&handles),
+            TextureArrayMode::Atlas => builder.texture_view(handles[0]),
+        };
+        builder.sampler(sampler).build(device, layout)
+    }
+}
+
+impl AtlasRect {
+    /// The rect covering the entire texture.
+    pub const IDENTITY: Self = AtlasRect {
+        offset: [0.0; 2],
+        scale: [1.0; 2],
+    };
+}
+
+impl<'a> Builder<'a> {
+    pub const DEFAULT_LABEL: &'static str = "nannou texture atlas";
+    pub const DEFAULT_PADDING: u32 = 1;
+
+    /// Begin building a texture array.
+    pub fn new() -> Self {
+        Builder {
+            textures: vec![],
+            label: Self::DEFAULT_LABEL,
+            mode: None,
+            padding: Self::DEFAULT_PADDING,
+        }
+    }
+
+    /// Add a texture to the array.
+    ///
+    /// Its index within the array is the number of textures added before it.
+    pub fn texture(mut self, texture: &'a Texture) -> Self {
+        self.textures.push(texture);
+        self
+    }
+
+    /// Add all textures produced by the given iterator to the array.
+    pub fn textures<I>(mut self, textures: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Texture>,
+    {
+        self.textures.extend(textures);
+        self
+    }
+
+    /// Debug label of the atlas texture, if one is created.
+    ///
+    /// By default, this is `"nannou texture atlas"`.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Force a specific binding mode rather than selecting one based on device support.
+    ///
+    /// Forcing `Atlas` mode can be useful for testing the fallback path on capable hardware.
+    ///
+    /// By default, `Array` mode is used where the device supports it.
+    pub fn mode(mut self, mode: TextureArrayMode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// The gap in texels left between textures within the atlas to avoid bleeding when filtering.
+    ///
+    /// By default, this value is `1`.
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Whether or not the given device supports `Array` mode for the given number of textures.
+    ///
+    /// This requires both binding arrays of textures and non-uniform indexing into them, as
+    /// indices usually vary per instance or per vertex.
+    pub fn supports_array_mode(device: &wgpu::Device, len: usize) -> bool {
+        let required = wgpu::Features::TEXTURE_BINDING_ARRAY
+            | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING;
+        let max_len = device.limits().max_sampled_textures_per_shader_stage as usize;
+        device.features().contains(required) && len <= max_len
+    }
+
+    /// Build the texture array.
+    ///
+    /// In `Atlas` mode, commands copying each texture into the atlas are added to the given
+    /// encoder. The atlas must not be sampled until these have been submitted.
+    ///
+    /// **Panic!**s if no textures were added, if the textures do not share the same format, or
+    /// if any texture is not 2D. In `Atlas` mode, also panics if any texture lacks the `COPY_SRC`
+    /// usage or the textures do not fit within the device's maximum texture size.
+    pub fn build(self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) -> TextureArray {
+        let Builder {
+            textures,
+            label,
+            mode,
+            padding,
+        } = self;
+        assert!(!textures.is_empty(), "texture array must contain a texture");
+        let format = textures[0].format();
+        let sample_type = textures[0].sample_type();
+        for texture in &textures {
+            assert_eq!(texture.format(), format, "textures must share a format");
+            assert_eq!(
+                texture.dimension(),
+                wgpu::TextureDimension::D2,
+                "textures must be 2D",
+            );
+        }
+
+        let mode = mode.unwrap_or_else(|| {
+            if Self::supports_array_mode(device, textures.len()) {
+                TextureArrayMode::Array
+            } else {
+                TextureArrayMode::Atlas
+            }
+        });
+
+        match mode {
+            TextureArrayMode::Array => {
+                let views = textures.iter().map(|t| t.view().build()).collect();
+                let rects = vec![AtlasRect::IDENTITY; textures.len()];
+                TextureArray {
+                    mode,
+                    views,
+                    atlas: None,
+                    rects,
+                    format,
+                    sample_type,
+                }
+            }
+            TextureArrayMode::Atlas => {
+                let max_side = device.limits().max_texture_dimension_2d;
+                let sizes: Vec<[u32; 2]> = textures.iter().map(|t| t.size()).collect();
+                let (atlas_size, origins) = pack(&sizes, padding, max_side);
+                let atlas = wgpu::TextureBuilder::new()
+                    .label(label)
+                    .size(atlas_size)
+                    .format(format)
+                    .usage(
+                        wgpu::TextureUsages::TEXTURE_BINDING
+                            | wgpu::TextureUsages::COPY_DST
+                            | wgpu::TextureUsages::COPY_SRC,
+                    )
+                    .build(device);
+
+                let [atlas_w, atlas_h] = atlas_size;
+                let mut rects = Vec::with_capacity(textures.len());
+                for ((texture, [w, h]), [x, y]) in textures.iter().zip(sizes).zip(origins) {
+                    assert!(
+                        texture.usage().contains(wgpu::TextureUsages::COPY_SRC),
+                        "textures must have `COPY_SRC` usage to be copied into an atlas",
+                    );
+                    let src = wgpu::ImageCopyTexture {
+                        texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d::ZERO,
+                        aspect: wgpu::TextureAspect::All,
+                    };
+                    let dst = wgpu::ImageCopyTexture {
+                        texture: &atlas,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d { x, y, z: 0 },
+                        aspect: wgpu::TextureAspect::All,
+                    };
+                    let extent = wgpu::Extent3d {
+                        width: w,
+                        height: h,
+                        depth_or_array_layers: 1,
+                    };
+                    encoder.copy_texture_to_texture(src, dst, extent);
+                    rects.push(AtlasRect {
+                        offset: [x as f32 / atlas_w as f32, y as f32 / atlas_h as f32],
+                        scale: [w as f32 / atlas_w as f32, h as f32 / atlas_h as f32],
+                    });
+                }
+
+                let views = vec![atlas.view().build()];
+                TextureArray {
+                    mode,
+                    views,
+                    atlas: Some(atlas),
+                    rects,
+                    format,
+                    sample_type,
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Default for Builder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Pack rectangles of the given sizes into rows, tallest first.
+//
+// Returns the size of the atlas along with the origin of each rectangle in the order given.
+fn pack(sizes: &[[u32; 2]], padding: u32, max_side: u32) -> ([u32; 2], Vec<[u32; 2]>) {
+    // Aim for a roughly square atlas that is at least as wide as the widest texture.
+    let area: u64 = sizes
+        .iter()
+        .map(|&[w, h]| (w + padding) as u64 * (h + padding) as u64)
+        .sum();
+    let widest = sizes.iter().map(|&[w, _]| w).max().unwrap_or(0);
+    let target_w = ((area as f64).sqrt().ceil() as u32)
+        .max(widest)
+        .min(max_side);
+    assert!(
+        widest <= max_side,
+        "texture exceeds the maximum texture size"
+    );
+
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i][1]));
+
+    let mut origins = vec![[0; 2]; sizes.len()];
+    let (mut x, mut y, mut row_h, mut atlas_w) = (0, 0, 0, 0);
+    for i in order {
+        let [w, h] = sizes[i];
+        if x > 0 && x + w > target_w {
+            x = 0;
+            y += row_h + padding;
+            row_h = 0;
+        }
+        origins[i] = [x, y];
+        atlas_w = atlas_w.max(x + w);
+        row_h = row_h.max(h);
+        x += w + padding;
+    }
+    let atlas_h = y + row_h;
+    assert!(
+        atlas_h <= max_side,
+        "textures do not fit within a single atlas of the maximum texture size",
+    );
+    ([atlas_w.max(1), atlas_h.max(1)], origins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Whether or not the two rectangles overlap once each is extended by `padding` to the right
+    // and below.
+    fn overlaps(a: ([u32; 2], [u32; 2]), b: ([u32; 2], [u32; 2]), padding: u32) -> bool {
+        let ([ax, ay], [aw, ah]) = a;
+        let ([bx, by], [bw, bh]) = b;
+        ax < bx + bw + padding
+            && bx < ax + aw + padding
+            && ay < by + bh + padding
+            && by < ay + ah + padding
+    }
+
+    // A deterministic assortment of rectangle sizes.
+    fn sizes(n: u32) -> Vec<[u32; 2]> {
+        (0..n).map(|i| [i * 7 % 13 + 1, i * 5 % 11 + 1]).collect()
+    }
+
+    #[test]
+    fn packed_rects_do_not_overlap() {
+        for &padding in &[0, 1, 3] {
+            let sizes = sizes(40);
+            let (size, origins) = pack(&sizes, padding, 4096);
+            let rects: Vec<_> = origins.iter().cloned().zip(sizes.iter().cloned()).collect();
+            for (i, &a) in rects.iter().enumerate() {
+                let ([x, y], [w, h]) = a;
+                assert!(x + w <= size[0] && y + h <= size[1]);
+                for &b in &rects[i + 1..] {
+                    assert!(!overlaps(a, b, padding), "{:?} overlaps {:?}", a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn padding_separates_rows_and_columns() {
+        let (size, origins) = pack(&[[10, 10], [10, 10]], 2, 64);
+        assert_eq!(origins, vec![[0, 0], [0, 12]]);
+        assert_eq!(size, [10, 22]);
+        let (size, origins) = pack(&[[10, 10], [10, 10], [10, 10], [10, 10]], 2, 64);
+        assert_eq!(origins, vec![[0, 0], [12, 0], [0, 12], [12, 12]]);
+        assert_eq!(size, [22, 22]);
+    }
+
+    #[test]
+    fn tallest_rects_are_packed_first() {
+        let (_, origins) = pack(&[[2, 2], [2, 8]], 0, 64);
+        assert_eq!(origins, vec![[2, 0], [0, 0]]);
+    }
+
+    #[test]
+    fn empty_atlas_has_unit_size() {
+        let (size, origins) = pack(&[], 4, 64);
+        assert_eq!(size, [1, 1]);
+        assert!(origins.is_empty());
+    }
+
+    #[test]
+    fn atlas_fits_exactly_at_the_max_side() {
+        let (size, _) = pack(&[[50, 50]; 4], 0, 100);
+        assert_eq!(size, [100, 100]);
+    }
+
+    #[test]
+    #[should_panic(expected = "texture exceeds the maximum texture size")]
+    fn texture_wider_than_max_side_panics() {
+        pack(&[[200, 10]], 0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "do not fit within a single atlas")]
+    fn atlas_taller_than_max_side_panics() {
+        pack(&[[60, 40]; 3], 0, 100);
+    }
+}
@@ -35,7 +35,7 @@ impl<'b, P: Pixel> Deref for ImageHolder<'b, P> {
     }
 }
 
-impl wgpu::TextureBuilder {
+impl<'a> wgpu::TextureBuilder<'a> {
     /// The minimum required texture usage when loading from an image.
     pub const REQUIRED_IMAGE_TEXTURE_USAGE: wgpu::TextureUsages = wgpu::TextureUsages::COPY_DST;
 
@@ -490,7 +490,7 @@ pub fn format_from_image_color_type(color_type: image::ColorType) -> Option<wgpu
 /// should allow for copying to and from the texture, sampling the texture and rendering to the
 /// texture. Specifying only the texture usage required may result in better performance. It
 /// may be necessary to manually specify the the usage if `STORAGE` is required.
-pub fn builder_from_image_view<T>(image: &T) -> wgpu::TextureBuilder
+pub fn builder_from_image_view<T>(image: &T) -> wgpu::TextureBuilder<'static>
 where
     T: image::GenericImageView,
     T::Pixel: Pixel,
@@ -1,13 +1,15 @@
-use crate::{self as wgpu, RowPaddedBuffer, TextureHandle, TextureViewHandle};
+use crate::{self as wgpu, msaa, RowPaddedBuffer, TextureHandle, TextureViewHandle};
 use std::ops::Deref;
 use std::sync::Arc;
 
+pub mod array;
 #[cfg(feature = "capturer")]
 pub mod capturer;
 #[cfg(feature = "image")]
 pub mod image;
 pub mod reshaper;
 pub mod row_padded_buffer;
+pub mod transfer;
 
 /// Types that can produce a texture view.
 ///
@@ -102,7 +104,8 @@ pub struct TextureViewId(u64);
 /// non-linear sRGBA-8 texture. A suite of builder methods may be used to specify the exact
 /// properties desired.
 #[derive(Debug)]
-pub struct Builder {
+pub struct Builder<'a> {
+    label: Option<&'a str>,
     descriptor: wgpu::TextureDescriptor<'static>,
 }
 
@@ -412,7 +415,7 @@ impl TextureView {
     }
 }
 
-impl Builder {
+impl<'a> Builder<'a> {
     pub const DEFAULT_SIDE: u32 = 128;
     pub const DEFAULT_DEPTH: u32 = 1;
     pub const DEFAULT_SIZE: wgpu::Extent3d = wgpu::Extent3d {
@@ -426,8 +429,9 @@ impl Builder {
     pub const DEFAULT_DIMENSION: wgpu::TextureDimension = wgpu::TextureDimension::D2;
     pub const DEFAULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
     pub const DEFAULT_USAGE: wgpu::TextureUsages = wgpu::TextureUsages::all(); // TODO: is this the right choice?
+    pub const DEFAULT_LABEL: &'static str = "nannou Texture";
     pub const DEFAULT_DESCRIPTOR: wgpu::TextureDescriptor<'static> = wgpu::TextureDescriptor {
-        label: Some("nannou Texture"),
+        label: Some(Self::DEFAULT_LABEL),
         size: Self::DEFAULT_SIZE,
         mip_level_count: Self::DEFAULT_MIP_LEVEL_COUNT,
         sample_count: Self::DEFAULT_SAMPLE_COUNT,
@@ -442,6 +446,18 @@ impl Builder {
         Self::default()
     }
 
+    /// Debug label of the texture.
+    ///
+    /// This will show up in graphics debuggers for easy identification.
+    ///
+    /// By default, this is `"nannou Texture"`.
+    ///
+    /// As the label is borrowed, it is not retained by the built texture's `descriptor`.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
     /// Specify the width and height of the texture.
     ///
     /// Note: On calls to `size`, `depth` and `extent` the `Builder` will attempt to infer the
@@ -504,6 +520,32 @@ impl Builder {
         self
     }
 
+    /// Clamp the specified sample count to the greatest count supported by the device for the
+    /// specified format.
+    ///
+    /// This should be called after specifying both the `sample_count` and the `format`.
+    pub fn clamp_sample_count(self, adapter: &wgpu::Adapter, device: &wgpu::Device) -> Self {
+        self.clamp_sample_count_with(adapter, device, |_| ())
+    }
+
+    /// The same as `clamp_sample_count`, but calls `warn` in the case that the specified sample
+    /// count is unsupported and a lower count is used instead.
+    pub fn clamp_sample_count_with<F>(
+        mut self,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        warn: F,
+    ) -> Self
+    where
+        F: FnOnce(wgpu::SampleCountFallback),
+    {
+        let format = self.descriptor.format;
+        let requested = self.descriptor.sample_count;
+        self.descriptor.sample_count =
+            msaa::clamp_sample_count_for_formats(adapter, device, Some(format), requested, warn);
+        self
+    }
+
     /// Describes to the implementation how the texture is to be used.
     ///
     /// It is important that the set of usage bits reflects the
@@ -526,13 +568,17 @@ impl Builder {
 
     /// Build the texture resulting from the specified parameters with the given device.
     pub fn build(self, device: &wgpu::Device) -> Texture {
-        let handle = Arc::new(device.create_texture(&self.descriptor));
-        let descriptor = self.into();
+        let mut descriptor = self.descriptor.clone();
+        // The borrowed label cannot outlive the builder, so only a `'static` label is retained.
+        if self.label.is_some() {
+            descriptor.label = None;
+        }
+        let handle = Arc::new(device.create_texture(&self.into_descriptor()));
         Texture { handle, descriptor }
     }
 
     /// Consumes the builder and returns the resulting `wgpu::TextureDescriptor`.
-    pub fn into_descriptor(self) -> wgpu::TextureDescriptor<'static> {
+    pub fn into_descriptor(self) -> wgpu::TextureDescriptor<'a> {
         self.into()
     }
 }
@@ -704,9 +750,10 @@ impl Deref for TextureView {
     }
 }
 
-impl Default for Builder {
+impl<'a> Default for Builder<'a> {
     fn default() -> Self {
         Self {
+            label: None,
             descriptor: Self::DEFAULT_DESCRIPTOR,
         }
     }
@@ -724,15 +771,21 @@ impl From<TextureViewInfo> for wgpu::TextureViewDescriptor<'static> {
     }
 }
 
-impl From<wgpu::TextureDescriptor<'static>> for Builder {
+impl<'a> From<wgpu::TextureDescriptor<'static>> for Builder<'a> {
     fn from(descriptor: wgpu::TextureDescriptor<'static>) -> Self {
-        Self { descriptor }
+        Self {
+            label: None,
+            descriptor,
+        }
     }
 }
 
-impl Into<wgpu::TextureDescriptor<'static>> for Builder {
-    fn into(self) -> wgpu::TextureDescriptor<'static> {
-        self.descriptor
+impl<'a> Into<wgpu::TextureDescriptor<'a>> for Builder<'a> {
+    fn into(self) -> wgpu::TextureDescriptor<'a> {
+        wgpu::TextureDescriptor {
+            label: self.label.or(self.descriptor.label),
+            ..self.descriptor
+        }
     }
 }
 
@@ -0,0 +1,226 @@
+//! Copying textures between two devices, e.g. those of two adapters within the `AdapterMap`.
+//!
+//! Resources cannot be shared between wgpu devices directly. Instead, a texture is copied into a
+//! buffer on the source device, mapped, and then written to a texture on the destination device.
+//! This allows heavy processing to run on a dedicated device while its results are displayed by
+//! the device of a window, or for frames of a window to be handed to a device used for capture.
+//!
+//! The general flow, similar to that of queries, is:
+//!
+//! 1. `encode_copy` the source texture within an encoder of the source device.
+//! 2. After the encoder has been submitted, `poll` once per frame until the destination texture
+//!    has been written.
+//!
+//! Neither device is blocked while a transfer is in flight. `copy_texture` provides a blocking
+//! alternative for one-off copies.
+
+use crate::{self as wgpu, RowPaddedBuffer};
+use std::sync::{Arc, Mutex};
+
+/// Transfers the contents of a texture on one device to a texture on another.
+///
+/// Only one transfer may be in flight at a time. The buffer used to read back the source texture
+/// is reused between transfers. Only 2D textures with a single layer and mip level are supported.
+#[derive(Debug)]
+pub struct Transfer {
+    buffer: RowPaddedBuffer,
+    extent: wgpu::Extent3d,
+    format: wgpu::TextureFormat,
+    state: State,
+}
+
+// The state of the asynchronous read back of the source texture.
+#[derive(Debug)]
+enum State {
+    // The buffer is free to be copied into.
+    Idle,
+    // A copy has been encoded but not yet mapped.
+    Copied,
+    // The buffer is being mapped.
+    Mapping(Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>),
+}
+
+impl Transfer {
+    /// Create a transfer for textures with the size and format of the given source texture.
+    ///
+    /// The `device` must be the device of the source texture.
+    pub fn new(device: &wgpu::Device, src_texture: &wgpu::Texture) -> Self {
+        let usage = wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST;
+        let buffer = RowPaddedBuffer::for_texture(device, src_texture, usage);
+        Transfer {
+            buffer,
+            extent: src_texture.extent(),
+            format: src_texture.format(),
+            state: State::Idle,
+        }
+    }
+
+    /// The size of the textures transferred.
+    pub fn extent(&self) -> wgpu::Extent3d {
+        self.extent
+    }
+
+    /// The format of the textures transferred.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// Whether or not a transfer is currently in flight.
+    pub fn is_pending(&self) -> bool {
+        !matches!(self.state, State::Idle)
+    }
+
+    /// Encode a copy of the source texture into the transfer's buffer.
+    ///
+    /// The `encoder` must belong to the device with which the transfer was created and the texture
+    /// must have the `COPY_SRC` usage.
+    ///
+    /// Returns `false` without encoding anything if a previous transfer is still in flight.
+    ///
+    /// **Panic!**s if the texture's size or format differs from that of the transfer.
+    pub fn encode_copy(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        src_texture: &wgpu::Texture,
+    ) -> bool {
+        self.assert_compatible(src_texture);
+        if self.is_pending() {
+            return false;
+        }
+        self.buffer.encode_copy_from(encoder, src_texture);
+        self.state = State::Copied;
+        true
+    }
+
+    /// Progress an in-flight transfer without blocking.
+    ///
+    /// This must only be called after the encoder passed to `encode_copy` has been submitted. Once
+    /// the source texture has been read back, it is written to the `dst_texture` via the
+    /// destination queue and `true` is returned.
+    ///
+    /// The `dst_texture` must have the `COPY_DST` usage.
+    ///
+    /// **Panic!**s if the destination texture's size or format differs from that of the transfer.
+    pub fn poll(
+        &mut self,
+        src_device: &wgpu::Device,
+        dst_queue: &wgpu::Queue,
+        dst_texture: &wgpu::Texture,
+    ) -> bool {
+        self.progress(src_device, wgpu::Maintain::Poll, dst_queue, dst_texture)
+    }
+
+    /// Block until an in-flight transfer completes, writing the result to the `dst_texture`.
+    ///
+    /// Returns `false` if there was no transfer in flight or if mapping the buffer failed.
+    ///
+    /// **Panic!**s if the destination texture's size or format differs from that of the transfer.
+    pub fn wait(
+        &mut self,
+        src_device: &wgpu::Device,
+        dst_queue: &wgpu::Queue,
+        dst_texture: &wgpu::Texture,
+    ) -> bool {
+        self.progress(src_device, wgpu::Maintain::Wait, dst_queue, dst_texture)
+    }
+
+    // Begin mapping the buffer if necessary, poll the source device and write to the destination
+    // once the buffer is mapped.
+    fn progress(
+        &mut self,
+        src_device: &wgpu::Device,
+        maintain: wgpu::Maintain,
+        dst_queue: &wgpu::Queue,
+        dst_texture: &wgpu::Texture,
+    ) -> bool {
+        self.assert_compatible(dst_texture);
+        if let State::Copied = self.state {
+            let status = Arc::new(Mutex::new(None));
+            let status2 = status.clone();
+            self.buffer
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |res| {
+                    *status2.lock().expect("failed to lock map status") = Some(res);
+                });
+            self.state = State::Mapping(status);
+        }
+        src_device.poll(maintain);
+        let res = match self.state {
+            State::Mapping(ref status) => {
+                match status.lock().expect("failed to lock map status").take() {
+                    None => return false,
+                    Some(res) => res,
+                }
+            }
+            _ => return false,
+        };
+        self.state = State::Idle;
+        if res.is_err() {
+            return false;
+        }
+        {
+            let view = self.buffer.buffer.slice(..).get_mapped_range();
+            let layout = wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.buffer.padded_width()),
+                rows_per_image: Some(self.buffer.height()),
+            };
+            dst_queue.write_texture(dst_texture.as_image_copy(), &view, layout, self.extent);
+        }
+        self.buffer.buffer.unmap();
+        true
+    }
+
+    // Assert that the given texture matches the size and format of the transfer.
+    fn assert_compatible(&self, texture: &wgpu::Texture) {
+        assert_eq!(
+            texture.extent(),
+            self.extent,
+            "texture size does not match the transfer"
+        );
+        assert_eq!(
+            texture.format(),
+            self.format,
+            "texture format does not match the transfer"
+        );
+    }
+}
+
+/// Copy the contents of a texture on the `src` device to a texture on the `dst` device, blocking
+/// until the copy completes.
+///
+/// If both handles refer to the same device, the texture is copied directly on the GPU.
+/// Otherwise, a temporary `Transfer` is used. Prefer `Transfer` for copies that occur every frame
+/// so that the read back buffer is reused and neither device is blocked.
+///
+/// The source texture must have the `COPY_SRC` usage and the destination texture the `COPY_DST`
+/// usage. Returns `false` if mapping the read back buffer failed.
+///
+/// **Panic!**s if the textures differ in size or format.
+pub fn copy_texture(
+    src: &Arc<wgpu::DeviceQueuePair>,
+    src_texture: &wgpu::Texture,
+    dst: &Arc<wgpu::DeviceQueuePair>,
+    dst_texture: &wgpu::Texture,
+) -> bool {
+    let desc = wgpu::CommandEncoderDescriptor {
+        label: Some("nannou_texture_transfer"),
+    };
+    let mut encoder = src.device().create_command_encoder(&desc);
+    if Arc::ptr_eq(src, dst) {
+        assert_eq!(src_texture.extent(), dst_texture.extent());
+        assert_eq!(src_texture.format(), dst_texture.format());
+        encoder.copy_texture_to_texture(
+            src_texture.as_image_copy(),
+            dst_texture.as_image_copy(),
+            src_texture.extent(),
+        );
+        src.queue().submit(Some(encoder.finish()));
+        return true;
+    }
+    let mut transfer = Transfer::new(src.device(), src_texture);
+    transfer.encode_copy(&mut encoder, src_texture);
+    src.queue().submit(Some(encoder.finish()));
+    transfer.wait(src.device(), dst.queue(), dst_texture)
+}
@@ -0,0 +1,446 @@
+//! Upload of planar YUV video frames with conversion to RGB on the GPU.
+//!
+//! Video decoders and webcams typically produce frames in a planar YUV format, where the luma
+//! (`Y`) is stored at full resolution and the chroma (`U` and `V`) at a quarter resolution.
+//! Converting these frames to RGB on the CPU before upload is often the most expensive part of
+//! video playback. A `YuvTexture` instead uploads the planes directly and converts them to an RGB
+//! texture with a single full screen pass.
+
+use crate as wgpu;
+
+/// The layout of the planes of a YUV frame.
+///
+/// Both formats subsample the chroma by two in each dimension, i.e. 4:2:0.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum YuvFormat {
+    /// A full resolution `Y` plane followed by a half resolution plane of interleaved `U` and `V`.
+    Nv12,
+    /// A full resolution `Y` plane followed by half resolution `U` and `V` planes.
+    I420,
+}
+
+/// The standard describing the conversion from YUV to RGB.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum YuvColorSpace {
+    /// ITU-R BT.601, typical of standard definition video and many webcams.
+    Bt601,
+    /// ITU-R BT.709, typical of high definition video.
+    Bt709,
+}
+
+/// The range of values used by each component of a YUV frame.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum YuvRange {
+    /// `Y` is within `16..=235` and `U` and `V` are within `16..=240`, as is typical of video.
+    Limited,
+    /// All components use the full `0..=255` range, as is typical of JPEG and some webcams.
+    Full,
+}
+
+/// The planes of a YUV frame along with the RGB texture to which they are converted.
+///
+/// Upload each frame via `upload` or `upload_plane`, then call `encode` to convert the planes to
+/// the RGB `texture`.
+#[derive(Debug)]
+pub struct YuvTexture {
+    label: &'static str,
+    format: YuvFormat,
+    color_space: YuvColorSpace,
+    range: YuvRange,
+    planes: Vec<wgpu::Texture>,
+    texture: wgpu::Texture,
+    texture_view: wgpu::TextureView,
+    _fs_mod: wgpu::ShaderModule,
+    pass: wgpu::FullScreenPass,
+    bind_group: wgpu::BindGroup,
+}
+
+/// A builder for a `YuvTexture`.
+#[derive(Debug)]
+pub struct Builder {
+    label: &'static str,
+    format: YuvFormat,
+    color_space: YuvColorSpace,
+    range: YuvRange,
+    dst_format: wgpu::TextureFormat,
+}
+
+// The uniforms of the conversion fragment shader.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Uniforms {
+    r: [f32; 4],
+    g: [f32; 4],
+    b: [f32; 4],
+    interleaved: u32,
+    _pad: [u32; 3],
+}
+
+impl YuvFormat {
+    /// The number of planes within a frame of this format.
+    pub fn plane_count(&self) -> usize {
+        match *self {
+            YuvFormat::Nv12 => 2,
+            YuvFormat::I420 => 3,
+        }
+    }
+
+    /// The size in pixels of the given plane for a frame of the given size.
+    ///
+    /// The chroma planes are rounded up for frames with an odd width or height.
+    ///
+    /// **Panic!**s if the plane index is out of range.
+    pub fn plane_size(&self, [w, h]: [u32; 2], plane: usize) -> [u32; 2] {
+        assert!(plane < self.plane_count(), "plane index out of range");
+        match plane {
+            0 => [w, h],
+            _ => [(w + 1) / 2, (h + 1) / 2],
+        }
+    }
+
+    /// The texture format of the given plane.
+    ///
+    /// **Panic!**s if the plane index is out of range.
+    pub fn plane_format(&self, plane: usize) -> wgpu::TextureFormat {
+        assert!(plane < self.plane_count(), "plane index out of range");
+        match (*self, plane) {
+            (YuvFormat::Nv12, 1) => wgpu::TextureFormat::Rg8Unorm,
+            _ => wgpu::TextureFormat::R8Unorm,
+        }
+    }
+
+    /// The number of bytes within a tightly packed row of the given plane.
+    pub fn plane_bytes_per_row(&self, size: [u32; 2], plane: usize) -> u32 {
+        let [w, _] = self.plane_size(size, plane);
+        w * wgpu::texture_format_size_bytes(self.plane_format(plane))
+    }
+
+    /// The number of bytes within a tightly packed frame of the given size.
+    pub fn frame_size_bytes(&self, size: [u32; 2]) -> usize {
+        (0..self.plane_count())
+            .map(|plane| {
+                let [_, h] = self.plane_size(size, plane);
+                self.plane_bytes_per_row(size, plane) as usize * h as usize
+            })
+            .sum()
+    }
+}
+
+impl YuvColorSpace {
+    // The luma coefficients of the red and blue channels.
+    fn kr_kb(&self) -> (f32, f32) {
+        match *self {
+            YuvColorSpace::Bt601 => (0.299, 0.114),
+            YuvColorSpace::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+impl YuvTexture {
+    /// The format of the RGB texture by default.
+    ///
+    /// Converted colours are linear, so the destination should be either an sRGB or float format.
+    pub const DEFAULT_DST_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    /// Begin building a `YuvTexture`.
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// The layout of the planes.
+    pub fn format(&self) -> YuvFormat {
+        self.format
+    }
+
+    /// The size of the frame in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        self.texture.size()
+    }
+
+    /// The texture of each plane in order.
+    pub fn planes(&self) -> &[wgpu::Texture] {
+        &self.planes
+    }
+
+    /// The RGB texture to which the planes are converted.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// A view of the RGB texture to which the planes are converted.
+    pub fn texture_view(&self) -> &wgpu::TextureView {
+        &self.texture_view
+    }
+
+    /// The standard used to convert the planes to RGB.
+    pub fn color_space(&self) -> YuvColorSpace {
+        self.color_space
+    }
+
+    /// Specify the standard used by following conversions.
+    pub fn set_color_space(&mut self, color_space: YuvColorSpace) {
+        self.color_space = color_space;
+    }
+
+    /// The range of the values within the planes.
+    pub fn range(&self) -> YuvRange {
+        self.range
+    }
+
+    /// Specify the range of values expected by following conversions.
+    pub fn set_range(&mut self, range: YuvRange) {
+        self.range = range;
+    }
+
+    /// Write a tightly packed frame to the planes, e.g. the output of a decoder.
+    ///
+    /// **Panic!**s if the length of the data does not match `YuvFormat::frame_size_bytes`.
+    pub fn upload(&self, queue: &wgpu::Queue, data: &[u8]) {
+        let size = self.size();
+        assert_eq!(
+            data.len(),
+            self.format.frame_size_bytes(size),
+            "unexpected length of {:?} frame data",
+            self.format,
+        );
+        let mut start = 0;
+        for plane in 0..self.planes.len() {
+            let bytes_per_row = self.format.plane_bytes_per_row(size, plane);
+            let [_, h] = self.format.plane_size(size, plane);
+            let end = start + bytes_per_row as usize * h as usize;
+            self.upload_plane(queue, plane, &data[start..end], bytes_per_row);
+            start = end;
+        }
+    }
+
+    /// Write the given data to a single plane.
+    ///
+    /// The `bytes_per_row` is the stride of the data, which may be greater than the width of the
+    /// plane as is common with the output of hardware decoders.
+    ///
+    /// **Panic!**s if the plane index is out of range or if the data is too short.
+    pub fn upload_plane(&self, queue: &wgpu::Queue, plane: usize, data: &[u8], bytes_per_row: u32) {
+        let texture = &self.planes[plane];
+        let extent = texture.extent();
+        let row_bytes = self.format.plane_bytes_per_row(self.size(), plane);
+        assert!(
+            bytes_per_row >= row_bytes,
+            "`bytes_per_row` is less than the plane width"
+        );
+        let rows = (extent.height as usize).saturating_sub(1);
+        let len = bytes_per_row as usize * rows + row_bytes as usize;
+        assert!(data.len() >= len, "too little data for plane {}", plane);
+        let layout = wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: None,
+        };
+        queue.write_texture(texture.as_image_copy(), data, layout, extent);
+    }
+
+    /// Recreate the plane and RGB textures with the given size.
+    ///
+    /// The contents of the textures are lost. Has no effect if the size is unchanged.
+    pub fn resize(&mut self, device: &wgpu::Device, size: [u32; 2]) {
+        if self.size() == size {
+            return;
+        }
+        let dst_format = self.texture.format();
+        let planes = create_planes(device, self.label, self.format, size);
+        let (texture, texture_view) = create_texture(device, self.label, size, dst_format);
+        self.bind_group = bind_group(device, &self.pass, self.format, &planes);
+        self.planes = planes;
+        self.texture = texture;
+        self.texture_view = texture_view;
+    }
+
+    /// Encode a render pass that converts the planes to the RGB `texture`.
+    ///
+    /// The current color space and range are written to the pass's uniform buffer via the given
+    /// queue.
+    pub fn encode(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {
+        let uniforms = uniforms(self.format, self.color_space, self.range);
+        self.pass.write_uniforms(queue, &uniforms);
+        self.pass
+            .encode_render_pass(encoder, &self.bind_group, &self.texture_view);
+    }
+}
+
+impl Builder {
+    /// The default debug label of the textures.
+    pub const DEFAULT_LABEL: &'static str = "nannou_yuv";
+
+    /// Begin building a `YuvTexture`.
+    pub fn new() -> Self {
+        Builder {
+            label: Self::DEFAULT_LABEL,
+            format: YuvFormat::Nv12,
+            color_space: YuvColorSpace::Bt709,
+            range: YuvRange::Limited,
+            dst_format: YuvTexture::DEFAULT_DST_FORMAT,
+        }
+    }
+
+    /// Debug label of the textures.
+    ///
+    /// By default, this is `"nannou_yuv"`.
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// The layout of the planes.
+    ///
+    /// By default, this is `YuvFormat::Nv12`.
+    pub fn format(mut self, format: YuvFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// The standard used to convert the planes to RGB.
+    ///
+    /// By default, this is `YuvColorSpace::Bt709`.
+    pub fn color_space(mut self, color_space: YuvColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// The range of the values within the planes.
+    ///
+    /// By default, this is `YuvRange::Limited`.
+    pub fn range(mut self, range: YuvRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// The format of the RGB texture.
+    ///
+    /// By default, this is `YuvTexture::DEFAULT_DST_FORMAT`.
+    pub fn dst_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.dst_format = format;
+        self
+    }
+
+    /// Build the textures for frames of the given size in pixels.
+    pub fn build(self, device: &wgpu::Device, size: [u32; 2]) -> YuvTexture {
+        let Builder {
+            label,
+            format,
+            color_space,
+            range,
+            dst_format,
+        } = self;
+        let fs_desc = wgpu::include_wgsl!("shaders/fs.wgsl");
+        let fs_mod = device.create_shader_module(fs_desc);
+        let pass = wgpu::FullScreenPass::builder(&fs_mod)
+            .label(label)
+            .textures(3)
+            .uniforms::<Uniforms>()
+            .color_format(dst_format)
+            .build(device);
+        let planes = create_planes(device, label, format, size);
+        let (texture, texture_view) = create_texture(device, label, size, dst_format);
+        let bind_group = bind_group(device, &pass, format, &planes);
+        YuvTexture {
+            label,
+            format,
+            color_space,
+            range,
+            planes,
+            texture,
+            texture_view,
+            _fs_mod: fs_mod,
+            pass,
+            bind_group,
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Create a texture for each plane of the given format.
+fn create_planes(
+    device: &wgpu::Device,
+    label: &'static str,
+    format: YuvFormat,
+    size: [u32; 2],
+) -> Vec<wgpu::Texture> {
+    (0..format.plane_count())
+        .map(|plane| {
+            wgpu::TextureBuilder::new()
+                .label(label)
+                .size(format.plane_size(size, plane))
+                .format(format.plane_format(plane))
+                .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+                .build(device)
+        })
+        .collect()
+}
+
+// Create the RGB texture to which the planes are converted.
+fn create_texture(
+    device: &wgpu::Device,
+    label: &'static str,
+    size: [u32; 2],
+    format: wgpu::TextureFormat,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = wgpu::TextureBuilder::new()
+        .label(label)
+        .size(size)
+        .format(format)
+        .usage(
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+        )
+        .build(device);
+    let texture_view = texture.view().build();
+    (texture, texture_view)
+}
+
+// Bind the planes to the pass. The interleaved UV plane of NV12 is bound to both chroma slots.
+fn bind_group(
+    device: &wgpu::Device,
+    pass: &wgpu::FullScreenPass,
+    format: YuvFormat,
+    planes: &[wgpu::Texture],
+) -> wgpu::BindGroup {
+    let views: Vec<wgpu::TextureView> = planes.iter().map(|plane| plane.view().build()).collect();
+    let (y, u, v) = match format {
+        YuvFormat::Nv12 => (&views[0], &views[1], &views[1]),
+        YuvFormat::I420 => (&views[0], &views[1], &views[2]),
+    };
+    pass.bind_group(device, &[y, u, v])
+}
+
+// Produce the matrix converting `(y, u, v, 1)` to non-linear RGB, including the range offsets.
+fn uniforms(format: YuvFormat, color_space: YuvColorSpace, range: YuvRange) -> Uniforms {
+    let (kr, kb) = color_space.kr_kb();
+    let kg = 1.0 - kr - kb;
+    let (y_scale, y_offset, c_scale) = match range {
+        YuvRange::Limited => (255.0 / 219.0, 16.0 / 255.0, 255.0 / 224.0),
+        YuvRange::Full => (1.0, 0.0, 1.0),
+    };
+    let c_offset = 128.0 / 255.0;
+
+    // Coefficients of `u` and `v` once centred and scaled.
+    let r_v = 2.0 * (1.0 - kr) * c_scale;
+    let g_u = -2.0 * kb * (1.0 - kb) / kg * c_scale;
+    let g_v = -2.0 * kr * (1.0 - kr) / kg * c_scale;
+    let b_u = 2.0 * (1.0 - kb) * c_scale;
+
+    // Fold the offsets into the constant column.
+    let y0 = -y_offset * y_scale;
+    let row = |u: f32, v: f32| [y_scale, u, v, y0 - (u + v) * c_offset];
+    Uniforms {
+        r: row(0.0, r_v),
+        g: row(g_u, g_v),
+        b: row(b_u, 0.0),
+        interleaved: (format == YuvFormat::Nv12) as u32,
+        _pad: [0; 3],
+    }
+}
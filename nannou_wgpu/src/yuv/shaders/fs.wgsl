@@ -0,0 +1,43 @@
+struct Uniforms {
+    // The rows of the matrix converting `(y, u, v, 1)` to non-linear RGB.
+    r: vec4<f32>,
+    g: vec4<f32>,
+    b: vec4<f32>,
+    // Whether U and V are interleaved within a single plane, i.e. NV12.
+    interleaved: u32,
+    _pad0: u32,
+    _pad: vec2<u32>,
+};
+
+@group(0) @binding(0)
+var y_tex: texture_2d<f32>;
+// For NV12, the interleaved UV plane is bound to both `u_tex` and `v_tex`.
+@group(0) @binding(1)
+var u_tex: texture_2d<f32>;
+@group(0) @binding(2)
+var v_tex: texture_2d<f32>;
+@group(0) @binding(3)
+var tex_sampler: sampler;
+@group(0) @binding(4)
+var<uniform> uniforms: Uniforms;
+
+// Decode a non-linear sRGB channel to linear.
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    let lo = c / 12.92;
+    let hi = pow((c + vec3<f32>(0.055)) / 1.055, vec3<f32>(2.4));
+    return select(hi, lo, c <= vec3<f32>(0.04045));
+}
+
+@fragment
+fn main(
+    @location(0) tex_coords: vec2<f32>,
+) -> @location(0) vec4<f32> {
+    let y = textureSample(y_tex, tex_sampler, tex_coords).r;
+    let u_sample = textureSample(u_tex, tex_sampler, tex_coords);
+    let v_sample = textureSample(v_tex, tex_sampler, tex_coords);
+    let v = select(v_sample.r, v_sample.g, uniforms.interleaved != 0u);
+    let yuv = vec4<f32>(y, u_sample.r, v, 1.0);
+    let rgb = vec3<f32>(dot(uniforms.r, yuv), dot(uniforms.g, yuv), dot(uniforms.b, yuv));
+    let clamped = clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0));
+    return vec4<f32>(srgb_to_linear(clamped), 1.0);
+}